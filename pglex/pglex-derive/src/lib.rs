@@ -154,9 +154,15 @@ pub fn derive_lexable(input: TokenStream) -> TokenStream {
         }
 
         if !token_start_val.is_empty() && !token_end_val.is_empty() {
+            // Matches everything up to and including the first end sequence
             let match_statement = quote! {
                 if input.starts_with(#token_start_val) {
-                    if !input[0..input.len() - 1].ends_with(#token_end_val) {
+                    let body_start = #token_start_val.len();
+                    let is_open = match input[body_start..].find(#token_end_val) {
+                        Some(end_pos) => body_start + end_pos + #token_end_val.len() == input.len(),
+                        None => true
+                    };
+                    if is_open {
                         matches.push(#name::#variant_ident);
                     }
                 }
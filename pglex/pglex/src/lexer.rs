@@ -111,13 +111,7 @@ impl<'source, T, S> Lexer<T, S>
                 for token in last_matches.iter() {
                     if !token_matches.contains(token) {
                         if let Some(range) = token_match_map.get_mut(token) {
-                            if token.is_inclusive() {
-                                //println!("It is inclusive.");
-                                *range = range.start..self.current_pos - 1;
-                                //println!("Slice: {}", self.source.get_slice(range.start, range.end));
-                            } else {
-                                *range = range.start..self.current_pos;
-                            }
+                            *range = range.start..self.current_pos;
                         }
                     }
                 }
@@ -0,0 +1,240 @@
+extern crate proc_macro;
+extern crate proc_macro2;
+extern crate syn;
+extern crate quote;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use syn::{
+    ItemStruct,
+    Fields,
+    Ident
+};
+use quote::quote;
+
+/// Exposes the named fields of a struct to scripts, see `pgs::api::container::PgsContainer`.
+/// Fields marked with `#[skip]` are not exposed.
+#[proc_macro_derive(PgsContainer, attributes(skip))]
+pub fn derive_pgs_container(input: TokenStream) -> TokenStream {
+    let item: ItemStruct = syn::parse(input).expect("Only structs can be used as a PgsContainer.");
+
+    let name = &item.ident;
+    let name_string = name.to_string();
+
+    let fields = match &item.fields {
+        Fields::Named(fields) => &fields.named,
+        _ => panic!("`{}` needs named fields to be used as a PgsContainer.", name)
+    };
+
+    let skip_attr_ident = syn::parse_str::<Ident>("skip").unwrap();
+
+    let mut member_statements: Vec<TokenStream2> = Vec::new();
+
+    for field in fields.iter() {
+        let skip = field.attrs.iter()
+            .any(|attr| attr.path.is_ident(&skip_attr_ident));
+        if skip {
+            continue;
+        }
+
+        let field_ident = field.ident.as_ref().unwrap();
+        let field_type = &field.ty;
+        let field_string = field_ident.to_string();
+        let getter_string = format!("get_{}", field_string);
+        let setter_string = format!("set_{}", field_string);
+
+        let member_statement = quote! {
+            .with_variable((
+                String::from(#field_string),
+                ::pgs::api::function::Function::new(#getter_string)
+                    .with_arg(::pgs::parser::ast::Type::Int)
+                    .with_ret_type(<#field_type as ::pgs::api::container::ContainerField>::field_type())
+                    .with_closure(Box::new(|adapter: &mut ::pgs::api::adapter::Adapter| {
+                        let handle: u64 = adapter.get_arg(0);
+                        let item = match adapter.core.get_foreign_object::<#name>(handle) {
+                            Ok(item) => item,
+                            Err(error) => {
+                                adapter.core.set_foreign_error(error);
+                                return;
+                            }
+                        };
+                        let value = match item.lock() {
+                            Ok(item) => item.#field_ident.clone(),
+                            Err(_) => {
+                                adapter.raise(concat!(#name_string, " is poisoned"));
+                                return;
+                            }
+                        };
+                        ::pgs::api::container::ContainerField::return_field(value, adapter);
+                    }))
+            ))
+            .with_function(
+                ::pgs::api::function::Function::new(#setter_string)
+                    .with_arg(::pgs::parser::ast::Type::Int)
+                    .with_arg(<#field_type as ::pgs::api::container::ContainerField>::field_type())
+                    .with_ret_type(::pgs::parser::ast::Type::Void)
+                    .with_closure(Box::new(|adapter: &mut ::pgs::api::adapter::Adapter| {
+                        let handle: u64 = adapter.get_arg(0);
+                        let value: #field_type = adapter.get_arg(1);
+                        let item = match adapter.core.get_foreign_object::<#name>(handle) {
+                            Ok(item) => item,
+                            Err(error) => {
+                                adapter.core.set_foreign_error(error);
+                                return;
+                            }
+                        };
+                        match item.lock() {
+                            Ok(mut item) => item.#field_ident = value,
+                            Err(_) => adapter.raise(concat!(#name_string, " is poisoned"))
+                        };
+                    }))
+            )
+        };
+        member_statements.push(member_statement);
+    }
+
+    let token_stream = quote! {
+        impl ::pgs::api::container::PgsContainer for #name {
+            fn container() -> ::pgs::api::container::Container {
+                ::pgs::api::container::Container::new(String::from(#name_string))
+                    #(
+                        #member_statements
+                    )*
+            }
+        }
+    };
+    token_stream.into()
+}
+
+/// Passes a struct to scripts by value, as a container with a field for every named field,
+/// see `pgs::api::container::PgsValue`. It can be passed to and returned from `Engine::call_function()`.
+/// Fields marked with `#[skip]` are not exposed, they are set to their default
+/// when an instance is returned by a script and kept when it is changed through a reference.
+#[proc_macro_derive(PgsValue, attributes(skip))]
+pub fn derive_pgs_value(input: TokenStream) -> TokenStream {
+    let item: ItemStruct = syn::parse(input).expect("Only structs can be used as a PgsValue.");
+
+    let name = &item.ident;
+    let name_string = name.to_string();
+
+    let fields = match &item.fields {
+        Fields::Named(fields) => &fields.named,
+        _ => panic!("`{}` needs named fields to be used as a PgsValue.", name)
+    };
+
+    let skip_attr_ident = syn::parse_str::<Ident>("skip").unwrap();
+
+    let mut field_statements: Vec<TokenStream2> = Vec::new();
+    let mut size_terms: Vec<TokenStream2> = Vec::new();
+    let mut write_statements: Vec<TokenStream2> = Vec::new();
+    let mut read_statements: Vec<TokenStream2> = Vec::new();
+    let mut read_into_statements: Vec<TokenStream2> = Vec::new();
+    let mut field_inits: Vec<TokenStream2> = Vec::new();
+
+    for field in fields.iter() {
+        let field_ident = field.ident.as_ref().unwrap();
+        let field_type = &field.ty;
+        let field_string = field_ident.to_string();
+
+        let skip = field.attrs.iter()
+            .any(|attr| attr.path.is_ident(&skip_attr_ident));
+        if skip {
+            field_inits.push(quote! {
+                #field_ident: Default::default()
+            });
+            continue;
+        }
+
+        field_statements.push(quote! {
+            .with_field(
+                String::from(#field_string),
+                <#field_type as ::pgs::api::container::ValueField>::field_type()
+            )
+        });
+        size_terms.push(quote! {
+            <#field_type as ::pgs::api::container::ValueField>::get_size()
+        });
+        write_statements.push(quote! {
+            ::pgs::api::container::ValueField::write_bytes(&self.#field_ident, bytes);
+        });
+        read_statements.push(quote! {
+            let #field_ident = {
+                let size = <#field_type as ::pgs::api::container::ValueField>::get_size();
+                let value = <#field_type as ::pgs::api::container::ValueField>::read_bytes(&bytes[offset..offset + size]);
+                offset += size;
+                value
+            };
+        });
+        read_into_statements.push(quote! {
+            let size = <#field_type as ::pgs::api::container::ValueField>::get_size();
+            ::pgs::api::container::ValueField::read_bytes_into(&mut self.#field_ident, &bytes[offset..offset + size]);
+            offset += size;
+        });
+        field_inits.push(quote! {
+            #field_ident: #field_ident
+        });
+    }
+
+    let token_stream = quote! {
+        impl ::pgs::api::container::PgsValue for #name {
+            fn value_container() -> ::pgs::api::container::Container {
+                ::pgs::api::container::Container::new(String::from(#name_string))
+                    #(
+                        #field_statements
+                    )*
+            }
+
+            fn get_size() -> usize {
+                0 #(+ #size_terms)*
+            }
+
+            fn write_bytes(&self, bytes: &mut Vec<u8>) {
+                #(
+                    #write_statements
+                )*
+            }
+
+            #[allow(unused_assignments, unused_mut, unused_variables)]
+            fn read_bytes(bytes: &[u8]) -> Self {
+                let mut offset = 0;
+                #(
+                    #read_statements
+                )*
+                #name {
+                    #(
+                        #field_inits
+                    ),*
+                }
+            }
+
+            #[allow(unused_assignments, unused_mut, unused_variables)]
+            fn read_bytes_into(&mut self, bytes: &[u8]) {
+                let mut offset = 0;
+                #(
+                    #read_into_statements
+                )*
+            }
+        }
+
+        impl ::pgs::api::call::ScriptArg for #name {
+            fn is_script_type(script_type: &::pgs::parser::ast::Type) -> bool {
+                ::pgs::api::call::is_value_type::<#name>(script_type)
+            }
+
+            fn push_arg(&self, core: &mut ::pgs::vm::core::Core, _referenced_addr: u64) -> ::pgs::vm::core::CoreResult<()> {
+                ::pgs::api::call::push_value(self, core)
+            }
+        }
+
+        impl ::pgs::api::call::ScriptReturn for #name {
+            fn is_script_type(script_type: &::pgs::parser::ast::Type) -> bool {
+                ::pgs::api::call::is_value_type::<#name>(script_type)
+            }
+
+            fn get_return(core: &::pgs::vm::core::Core) -> ::pgs::vm::core::CoreResult<#name> {
+                ::pgs::api::call::get_value_return(core)
+            }
+        }
+    };
+    token_stream.into()
+}
@@ -7,7 +7,8 @@ use pgs::{
     },
     api::{
         function::{
-            Function
+            Function,
+            FunctionError
         },
         adapter::Adapter,
         module::{
@@ -20,11 +21,32 @@ use pgs::{
         }
     },
     vm::{
-        core::Core
+        core::{
+            Core,
+            CoreError,
+            CoreResult
+        }
     }
 };
 
-fn register_std_print(engine: &mut Engine) -> EngineResult<()> {
+use std::{
+    process::Command,
+    net::{
+        TcpStream,
+        TcpListener
+    },
+    io::{
+        self,
+        Read,
+        Write
+    },
+    sync::{
+        Arc,
+        Mutex
+    }
+};
+
+fn with_std_print(module: Module) -> Module {
     let printi_function = Function::new("printi")
         .with_arg(Type::Int)
         .with_ret_type(Type::Void)
@@ -56,18 +78,447 @@ fn register_std_print(engine: &mut Engine) -> EngineResult<()> {
             let arg: String = adapter.get_arg(0);
             println!("{}", arg);
         }));
-    
-    let module = Module::new("std")
-        .with_function(printi_function)
+
+    module.with_function(printi_function)
         .with_function(print_function)
         .with_function(println_function)
-        .with_function(printf_function);
-    
-    engine.register_module(module)
+        .with_function(printf_function)
+}
+
+fn with_std_convert(module: Module) -> Module {
+    // Invalid input raises an error, the try_parse functions return null instead
+    let parse_int_function = Function::new("parse_int")
+        .with_arg(Type::String)
+        .with_ret_type(Type::Int)
+        .with_closure(Box::new(|adapter: &mut Adapter| {
+            let arg: String = adapter.get_arg(0);
+            match arg.trim().parse::<i64>() {
+                Ok(value) => adapter.return_value(value),
+                Err(error) => adapter.raise(FunctionError::from_error(error))
+            };
+        }));
+    let parse_float_function = Function::new("parse_float")
+        .with_arg(Type::String)
+        .with_ret_type(Type::Float)
+        .with_closure(Box::new(|adapter: &mut Adapter| {
+            let arg: String = adapter.get_arg(0);
+            match arg.trim().parse::<f32>() {
+                Ok(value) => adapter.return_value(value),
+                Err(error) => adapter.raise(FunctionError::from_error(error))
+            };
+        }));
+    let try_parse_int_function = Function::new("try_parse_int")
+        .with_arg(Type::String)
+        .with_ret_type(Type::Optional(Box::new(Type::Int)))
+        .with_closure(Box::new(|adapter: &mut Adapter| {
+            let arg: String = adapter.get_arg(0);
+            let value = arg.trim().parse::<i64>().ok();
+            adapter.return_optional(value);
+        }));
+    let try_parse_float_function = Function::new("try_parse_float")
+        .with_arg(Type::String)
+        .with_ret_type(Type::Optional(Box::new(Type::Float)))
+        .with_closure(Box::new(|adapter: &mut Adapter| {
+            let arg: String = adapter.get_arg(0);
+            let value = arg.trim().parse::<f32>().ok();
+            adapter.return_optional(value);
+        }));
+    let int_to_string_function = Function::new("int_to_string")
+        .with_arg(Type::Int)
+        .with_ret_type(Type::String)
+        .with_closure(Box::new(|adapter: &mut Adapter| {
+            let arg: i64 = adapter.get_arg(0);
+            adapter.return_string(arg.to_string());
+        }));
+    let float_to_string_function = Function::new("float_to_string")
+        .with_arg(Type::Float)
+        .with_arg(Type::Int)
+        .with_ret_type(Type::String)
+        .with_closure(Box::new(|adapter: &mut Adapter| {
+            let arg: f32 = adapter.get_arg(0);
+            let precision: i64 = adapter.get_arg(1);
+            adapter.return_string(format!("{:.*}", precision.max(0) as usize, arg));
+        }));
+
+    module.with_function(parse_int_function)
+        .with_function(parse_float_function)
+        .with_function(try_parse_int_function)
+        .with_function(try_parse_float_function)
+        .with_function(int_to_string_function)
+        .with_function(float_to_string_function)
+}
+
+/// Size of a list header on the heap: length, capacity and address of the elements
+const LIST_HEADER_SIZE: usize = 24;
+/// Number of elements a list has room for before it first grows
+const LIST_INITIAL_CAPACITY: u64 = 4;
+
+/// Reads the length, capacity and element address of a list
+fn list_header(core: &Core, list: u64) -> CoreResult<(u64, u64, u64)> {
+    if !core.is_heap_alloc(list, LIST_HEADER_SIZE) {
+        return Err(CoreError::InvalidHandle(list));
+    }
+    let len: u64 = core.mem_get((list, 0))?;
+    let cap: u64 = core.mem_get((list, 8))?;
+    let data: u64 = core.mem_get((list, 16))?;
+    Ok((len, cap, data))
+}
+
+/// Checks an index into a list and returns the address of the element
+fn list_elem_addr(core: &Core, list: u64, index: i64) -> CoreResult<u64> {
+    let (len, _, data) = list_header(core, list)?;
+    if index < 0 || index as u64 >= len {
+        return Err(CoreError::OutOfBounds(index, index + 1, len as i64));
+    }
+    Ok(data + index as u64 * 8)
+}
+
+fn list_new(core: &mut Core) -> CoreResult<u64> {
+    let data = core.heap_alloc(&vec![0; LIST_INITIAL_CAPACITY as usize * 8])?;
+    let list = core.heap_alloc(&[0; LIST_HEADER_SIZE])?;
+    core.mem_set((list, 8), LIST_INITIAL_CAPACITY)?;
+    core.mem_set((list, 16), data)?;
+    Ok(list)
+}
+
+fn list_push(core: &mut Core, list: u64, value: i64) -> CoreResult<()> {
+    let (len, mut cap, mut data) = list_header(core, list)?;
+    if len == cap {
+        cap *= 2;
+        data = core.heap_realloc(data, cap as usize * 8)?;
+        core.mem_set((list, 8), cap)?;
+        core.mem_set((list, 16), data)?;
+    }
+    core.mem_set((data + len * 8, 0), value)?;
+    core.mem_set((list, 0), len + 1)
+}
+
+fn list_pop(core: &mut Core, list: u64) -> CoreResult<i64> {
+    let (len, _, _) = list_header(core, list)?;
+    let elem_addr = list_elem_addr(core, list, len as i64 - 1)?;
+    let value = core.mem_get((elem_addr, 0))?;
+    core.mem_set((list, 0), len - 1)?;
+    Ok(value)
+}
+
+/// Returns the result of a list operation, errors end the run after the call
+fn list_return<T: Default>(adapter: &mut Adapter, result: CoreResult<T>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(error) => {
+            adapter.core.set_foreign_error(error);
+            T::default()
+        }
+    }
+}
+
+fn std_list_module() -> Module {
+    // Lists hold ints and are passed around as handles
+    let new_function = Function::new("new")
+        .with_ret_type(Type::Int)
+        .with_closure(Box::new(|adapter: &mut Adapter| {
+            let list_res = list_new(adapter.core);
+            let list = list_return(adapter, list_res);
+            adapter.return_value(list);
+        }));
+    let push_function = Function::new("push")
+        .with_arg(Type::Int)
+        .with_arg(Type::Int)
+        .with_ret_type(Type::Void)
+        .with_closure(Box::new(|adapter: &mut Adapter| {
+            let list: u64 = adapter.get_arg(0);
+            let value: i64 = adapter.get_arg(1);
+            let push_res = list_push(adapter.core, list, value);
+            list_return(adapter, push_res);
+        }));
+    let pop_function = Function::new("pop")
+        .with_arg(Type::Int)
+        .with_ret_type(Type::Int)
+        .with_closure(Box::new(|adapter: &mut Adapter| {
+            let list: u64 = adapter.get_arg(0);
+            let pop_res = list_pop(adapter.core, list);
+            let value = list_return(adapter, pop_res);
+            adapter.return_value(value);
+        }));
+    let get_function = Function::new("get")
+        .with_arg(Type::Int)
+        .with_arg(Type::Int)
+        .with_ret_type(Type::Int)
+        .with_closure(Box::new(|adapter: &mut Adapter| {
+            let list: u64 = adapter.get_arg(0);
+            let index: i64 = adapter.get_arg(1);
+            let get_res = list_elem_addr(adapter.core, list, index)
+                .and_then(|elem_addr| adapter.core.mem_get::<i64>((elem_addr, 0)));
+            let value = list_return(adapter, get_res);
+            adapter.return_value(value);
+        }));
+    let set_function = Function::new("set")
+        .with_arg(Type::Int)
+        .with_arg(Type::Int)
+        .with_arg(Type::Int)
+        .with_ret_type(Type::Void)
+        .with_closure(Box::new(|adapter: &mut Adapter| {
+            let list: u64 = adapter.get_arg(0);
+            let index: i64 = adapter.get_arg(1);
+            let value: i64 = adapter.get_arg(2);
+            let set_res = list_elem_addr(adapter.core, list, index)
+                .and_then(|elem_addr| adapter.core.mem_set((elem_addr, 0), value));
+            list_return(adapter, set_res);
+        }));
+    // Not called "len", which is the intrinsic for strings and arrays
+    let length_function = Function::new("length")
+        .with_arg(Type::Int)
+        .with_ret_type(Type::Int)
+        .with_closure(Box::new(|adapter: &mut Adapter| {
+            let list: u64 = adapter.get_arg(0);
+            let header_res = list_header(adapter.core, list)
+                .map(|(len, _, _)| len as i64);
+            let len = list_return(adapter, header_res);
+            adapter.return_value(len);
+        }));
+
+    Module::new("list")
+        .with_function(new_function)
+        .with_function(push_function)
+        .with_function(pop_function)
+        .with_function(get_function)
+        .with_function(set_function)
+        .with_function(length_function)
+}
+
+fn std_process_module() -> Module {
+    // Arguments are passed as a slice of strings, failing to spawn the process raises an error
+    let args_type = Type::Reference(Box::new(Type::AutoArray(Box::new(Type::String))));
+    let run_function = Function::new("run")
+        .with_arg(Type::String)
+        .with_arg(args_type.clone())
+        .with_ret_type(Type::Int)
+        .with_closure(Box::new(|adapter: &mut Adapter| {
+            let cmd: String = adapter.get_arg(0);
+            let args: Vec<String> = adapter.get_arg(1);
+            match Command::new(cmd).args(args).status() {
+                // Processes killed by a signal have no exit code
+                Ok(status) => adapter.return_value(status.code().unwrap_or(-1) as i64),
+                Err(error) => adapter.raise(FunctionError::from_error(error))
+            };
+        }));
+    let run_capture_function = Function::new("run_capture")
+        .with_arg(Type::String)
+        .with_arg(args_type)
+        .with_ret_type(Type::String)
+        .with_closure(Box::new(|adapter: &mut Adapter| {
+            let cmd: String = adapter.get_arg(0);
+            let args: Vec<String> = adapter.get_arg(1);
+            match Command::new(cmd).args(args).output() {
+                Ok(output) => adapter.return_string(String::from_utf8_lossy(&output.stdout).into_owned()),
+                Err(error) => adapter.raise(FunctionError::from_error(error))
+            };
+        }));
+
+    Module::new("process")
+        .with_function(run_function)
+        .with_function(run_capture_function)
+}
+
+/// A socket handle owned by a script
+enum NetHandle {
+    Stream(TcpStream),
+    Listener(TcpListener)
+}
+
+fn net_insert_handle(adapter: &mut Adapter, handle: NetHandle) -> u64 {
+    adapter.insert_foreign_ptr(Arc::new(Mutex::new(handle)))
+}
+
+impl NetHandle {
+    /// Clones the socket, so blocking calls don't hold the lock of the handle
+    fn try_clone(&self) -> io::Result<NetHandle> {
+        match self {
+            NetHandle::Stream(stream) => stream.try_clone().map(NetHandle::Stream),
+            NetHandle::Listener(listener) => listener.try_clone().map(NetHandle::Listener)
+        }
+    }
+}
+
+/// Gets a clone of the socket of a handle. Handles of other types or closed sockets fail the run.
+fn net_get_handle(adapter: &mut Adapter, handle: u64) -> Option<NetHandle> {
+    let net_handle = match adapter.core.get_foreign_object::<NetHandle>(handle) {
+        Ok(net_handle) => net_handle,
+        Err(error) => {
+            adapter.core.set_foreign_error(error);
+            return None;
+        }
+    };
+    let clone_res = match net_handle.lock() {
+        Ok(net_handle) => net_handle.try_clone(),
+        Err(_) => {
+            adapter.raise("socket is poisoned");
+            return None;
+        }
+    };
+    match clone_res {
+        Ok(net_handle) => Some(net_handle),
+        Err(error) => {
+            adapter.raise(FunctionError::from_error(error));
+            None
+        }
+    }
+}
+
+fn std_net_module() -> Module {
+    // All calls block without holding the lock of the handle. Socket errors are raised,
+    // passing an invalid handle fails the run with CoreError::InvalidHandle.
+    let connect_function = Function::new("connect")
+        .with_arg(Type::String)
+        .with_ret_type(Type::Int)
+        .with_closure(Box::new(|adapter: &mut Adapter| {
+            let addr: String = adapter.get_arg(0);
+            match TcpStream::connect(addr) {
+                Ok(stream) => {
+                    let handle = net_insert_handle(adapter, NetHandle::Stream(stream));
+                    adapter.return_value(handle);
+                },
+                Err(error) => adapter.raise(FunctionError::from_error(error))
+            };
+        }));
+    let listen_function = Function::new("listen")
+        .with_arg(Type::String)
+        .with_ret_type(Type::Int)
+        .with_closure(Box::new(|adapter: &mut Adapter| {
+            let addr: String = adapter.get_arg(0);
+            match TcpListener::bind(addr) {
+                Ok(listener) => {
+                    let handle = net_insert_handle(adapter, NetHandle::Listener(listener));
+                    adapter.return_value(handle);
+                },
+                Err(error) => adapter.raise(FunctionError::from_error(error))
+            };
+        }));
+    let accept_function = Function::new("accept")
+        .with_arg(Type::Int)
+        .with_ret_type(Type::Int)
+        .with_closure(Box::new(|adapter: &mut Adapter| {
+            let listener: u64 = adapter.get_arg(0);
+            let listener = match net_get_handle(adapter, listener) {
+                Some(NetHandle::Listener(listener)) => listener,
+                Some(NetHandle::Stream(_)) => return adapter.raise("handle is not a listener"),
+                None => return
+            };
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    let handle = net_insert_handle(adapter, NetHandle::Stream(stream));
+                    adapter.return_value(handle);
+                },
+                Err(error) => adapter.raise(FunctionError::from_error(error))
+            };
+        }));
+    let read_function = Function::new("read")
+        .with_arg(Type::Int)
+        .with_arg(Type::Int)
+        .with_ret_type(Type::String)
+        .with_closure(Box::new(|adapter: &mut Adapter| {
+            let stream: u64 = adapter.get_arg(0);
+            let max_len: i64 = adapter.get_arg(1);
+            let mut stream = match net_get_handle(adapter, stream) {
+                Some(NetHandle::Stream(stream)) => stream,
+                Some(NetHandle::Listener(_)) => return adapter.raise("handle is not a stream"),
+                None => return
+            };
+            let mut buffer = vec![0; max_len.max(0) as usize];
+            match stream.read(&mut buffer) {
+                Ok(read_len) => {
+                    buffer.truncate(read_len);
+                    adapter.return_string(String::from_utf8_lossy(&buffer).into_owned());
+                },
+                Err(error) => adapter.raise(FunctionError::from_error(error))
+            };
+        }));
+    let write_function = Function::new("write")
+        .with_arg(Type::Int)
+        .with_arg(Type::String)
+        .with_ret_type(Type::Int)
+        .with_closure(Box::new(|adapter: &mut Adapter| {
+            let stream: u64 = adapter.get_arg(0);
+            let data: String = adapter.get_arg(1);
+            let mut stream = match net_get_handle(adapter, stream) {
+                Some(NetHandle::Stream(stream)) => stream,
+                Some(NetHandle::Listener(_)) => return adapter.raise("handle is not a stream"),
+                None => return
+            };
+            match stream.write_all(data.as_bytes()) {
+                Ok(_) => adapter.return_value(data.len() as i64),
+                Err(error) => adapter.raise(FunctionError::from_error(error))
+            };
+        }));
+    let close_function = Function::new("close")
+        .with_arg(Type::Int)
+        .with_ret_type(Type::Void)
+        .with_closure(Box::new(|adapter: &mut Adapter| {
+            let handle: u64 = adapter.get_arg(0);
+            if let Err(error) = adapter.core.remove_foreign_object::<NetHandle>(handle) {
+                adapter.core.set_foreign_error(error);
+            }
+        }));
+
+    Module::new("net")
+        .with_function(connect_function)
+        .with_function(listen_function)
+        .with_function(accept_function)
+        .with_function(read_function)
+        .with_function(write_function)
+        .with_function(close_function)
+}
+
+/// Capabilities granted to scripts.
+/// Modules that reach outside of the sandbox are only registered if granted.
+#[derive(Clone, Debug, Default)]
+pub struct Capabilities {
+    /// Allows running external processes (std::process)
+    pub process: bool,
+    /// Allows opening sockets (std::net)
+    pub net: bool
+}
+
+impl Capabilities {
+    /// Creates a new set of capabilities, nothing is granted
+    pub fn new() -> Capabilities {
+        Capabilities::default()
+    }
+
+    /// Grants or revokes running external processes
+    pub fn with_process(mut self, process: bool) -> Capabilities {
+        self.process = process;
+        self
+    }
+
+    /// Grants or revokes opening sockets
+    pub fn with_net(mut self, net: bool) -> Capabilities {
+        self.net = net;
+        self
+    }
+}
+
+/// Builds the std module with the given capabilities, e.g. for EngineBuilder::with_module()
+pub fn std_module(capabilities: &Capabilities) -> Module {
+    let mut module = Module::new("std");
+    module = with_std_print(module);
+    module = with_std_convert(module);
+    module = module.with_module(std_list_module());
+    if capabilities.process {
+        module = module.with_module(std_process_module());
+    }
+    if capabilities.net {
+        module = module.with_module(std_net_module());
+    }
+    module
+}
+
+/// Registers the std module with the given capabilities
+pub fn register_extension_with(engine: &mut Engine, capabilities: &Capabilities) -> EngineResult<()> {
+    engine.register_module(std_module(capabilities))
 }
 
 #[no_mangle]
 pub extern fn register_extension(engine: &mut Engine) -> EngineResult<()> {
-    register_std_print(engine)?;
-    Ok(())
+    register_extension_with(engine, &Capabilities::new())
 }
\ No newline at end of file
@@ -0,0 +1,514 @@
+extern crate pgs;
+extern crate pgs_std;
+
+use pgs::{
+    codegen::{
+        register::Register
+    },
+    engine::{
+        Engine,
+        EngineError
+    },
+    api::{
+        function::Function,
+        module::Module
+    }
+};
+
+use std::{
+    env::{
+        self,
+        consts::{
+            DLL_PREFIX,
+            DLL_SUFFIX
+        }
+    },
+    path::PathBuf
+};
+
+#[test]
+fn test_std_convert_round_trip() {
+    let code = String::from("
+        import std::{
+            parse_int,
+            parse_float,
+            int_to_string,
+            float_to_string
+        };
+
+        fn: main() ~ int {
+            var text = int_to_string(41);
+            var value = parse_int(text);
+            var float_text = float_to_string(parse_float(\"2.5\"), 2);
+            var float_value = parse_float(float_text);
+            if float_value == 2.5 {
+                value = value + 1;
+            }
+            return value;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let reg_res = pgs_std::register_extension(&mut engine);
+    assert!(reg_res.is_ok());
+
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+
+    let result_res = engine.get_register_value::<i64>(Register::R0);
+    assert!(result_res.is_ok());
+
+    assert_eq!(42, result_res.unwrap());
+}
+
+#[test]
+fn test_std_convert_parse_error() {
+    let code = String::from("
+        import std::{
+            parse_int,
+            parse_float
+        };
+
+        fn: main() ~ int {
+            return parse_int(\"forty two\");
+        }
+
+        fn: main_float() ~ int {
+            return parse_float(\"2.5.1\") as int;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let reg_res = pgs_std::register_extension(&mut engine);
+    assert!(reg_res.is_ok());
+
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    for (fn_name, function) in [("root::main", "parse_int"), ("root::main_float", "parse_float")] {
+        match engine.run_fn(fn_name) {
+            Err(error) => {
+                match *error {
+                    EngineError::CoreError(core_error) => {
+                        let function_error = core_error.get_function_error().unwrap();
+                        assert_eq!(function, function_error.function);
+                        assert!(function_error.payload.is_some());
+                    },
+                    other => panic!("Unexpected error {:?}", other)
+                };
+            },
+            Ok(_) => panic!("Invalid input was parsed")
+        };
+    }
+}
+
+#[test]
+fn test_std_convert_try_parse() {
+    let code = String::from("
+        import std::{
+            try_parse_int,
+            try_parse_float
+        };
+
+        fn: main() ~ int {
+            var value = try_parse_int(\" 42 \");
+            var invalid = try_parse_int(\"forty two\");
+            var float_value = try_parse_float(\"2.5\");
+            var res = 0;
+            if invalid == null {
+                res = value as int;
+            }
+            if float_value != null {
+                res += ((float_value as float) * 2.0) as int * 100;
+            }
+            return res;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let reg_res = pgs_std::register_extension(&mut engine);
+    assert!(reg_res.is_ok());
+
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+
+    let result_res = engine.get_register_value::<i64>(Register::R0);
+    assert!(result_res.is_ok());
+
+    assert_eq!(542, result_res.unwrap());
+}
+
+#[test]
+fn test_std_process_capability() {
+    let code = String::from("
+        import std::process::{
+            run,
+            run_capture
+        };
+
+        fn: main() ~ int {
+            var echo_args = [\"hello\"];
+            var output = run_capture(\"echo\", echo_args[..]);
+            var sh_args = [\"-c\", \"exit 3\"];
+            var exit_code = run(\"sh\", sh_args[..]);
+            return exit_code;
+        }
+
+        fn: main_missing() ~ int {
+            var args = [\"--version\"];
+            return run(\"pgs-no-such-command\", args[..]);
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let reg_res = pgs_std::register_extension(&mut engine);
+    assert!(reg_res.is_ok());
+
+    let load_res = engine.load_code(&code);
+    assert!(load_res.is_err());
+
+    let mut engine = Engine::new(1024);
+    let capabilities = pgs_std::Capabilities::new()
+        .with_process(true);
+    let reg_res = pgs_std::register_extension_with(&mut engine, &capabilities);
+    assert!(reg_res.is_ok());
+
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+
+    let result_res = engine.get_register_value::<i64>(Register::R0);
+    assert!(result_res.is_ok());
+
+    // The script passes "exit 3" as a single argument
+    assert_eq!(3, result_res.unwrap());
+
+    match engine.run_fn("root::main_missing") {
+        Err(error) => {
+            match *error {
+                EngineError::CoreError(core_error) => {
+                    let function_error = core_error.get_function_error().unwrap();
+                    assert_eq!("run", function_error.function);
+                    assert!(function_error.payload.is_some());
+                },
+                other => panic!("Unexpected error {:?}", other)
+            };
+        },
+        Ok(_) => panic!("Missing command was spawned")
+    };
+}
+
+#[test]
+fn test_std_net_echo() {
+    use std::{
+        net::TcpListener,
+        io::{
+            Read,
+            Write
+        },
+        thread
+    };
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        stream.write_all(b"pong").unwrap();
+        let mut buffer = [0; 4];
+        stream.read_exact(&mut buffer).unwrap();
+        buffer
+    });
+
+    let code = format!("
+        import std::net::{{
+            connect,
+            read,
+            write,
+            close
+        }};
+
+        fn: main() ~ int {{
+            var handle = connect(\"{}\");
+            var reply = read(handle, 4);
+            var written = write(handle, reply);
+            close(handle);
+            return written;
+        }}
+    ", addr);
+
+    let mut engine = Engine::new(1024);
+    let capabilities = pgs_std::Capabilities::new()
+        .with_net(true);
+    let reg_res = pgs_std::register_extension_with(&mut engine, &capabilities);
+    assert!(reg_res.is_ok());
+
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+
+    let result_res = engine.get_register_value::<i64>(Register::R0);
+    assert!(result_res.is_ok());
+
+    assert_eq!(4, result_res.unwrap());
+    assert_eq!(b"pong", &server.join().unwrap());
+}
+
+#[test]
+fn test_std_net_errors() {
+    use std::net::TcpListener;
+
+    // Nothing listens on the address once the listener is dropped
+    let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+
+    let code = format!("
+        import std::net::{{
+            connect,
+            listen,
+            read
+        }};
+
+        fn: main_connect() ~ int {{
+            return connect(\"{}\");
+        }}
+
+        fn: main_read() ~ int {{
+            var listener = listen(\"127.0.0.1:0\");
+            var data = read(listener, 4);
+            return 0;
+        }}
+    ", addr);
+
+    let mut engine = Engine::new(1024);
+    let capabilities = pgs_std::Capabilities::new()
+        .with_net(true);
+    let reg_res = pgs_std::register_extension_with(&mut engine, &capabilities);
+    assert!(reg_res.is_ok());
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    for (fn_name, function, has_payload) in [("root::main_connect", "connect", true), ("root::main_read", "read", false)] {
+        match engine.run_fn(fn_name) {
+            Err(error) => {
+                match *error {
+                    EngineError::CoreError(core_error) => {
+                        let function_error = core_error.get_function_error().unwrap();
+                        assert_eq!(function, function_error.function);
+                        assert_eq!(has_payload, function_error.payload.is_some());
+                    },
+                    other => panic!("Unexpected error {:?}", other)
+                };
+            },
+            Ok(_) => panic!("Socket error was not raised")
+        };
+    }
+}
+
+#[test]
+fn test_std_list() {
+    let code = String::from("
+        import std::list::{
+            new,
+            push,
+            pop,
+            get,
+            set,
+            length
+        };
+
+        fn: main() ~ int {
+            var list = new();
+            var i = 0;
+            while i < 10 {
+                push(list, i * i);
+                i += 1;
+            }
+            set(list, 0, 100);
+            var last = pop(list);
+            var first = get(list, 0);
+            var fifth = get(list, 5);
+            var len = length(list);
+            return first + fifth + last * 1000 + len * 100000;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let reg_res = pgs_std::register_extension(&mut engine);
+    assert!(reg_res.is_ok());
+
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+
+    let result_res = engine.get_register_value::<i64>(Register::R0);
+    assert!(result_res.is_ok());
+
+    assert_eq!(100 + 25 + 81000 + 900000, result_res.unwrap());
+}
+
+#[test]
+fn test_std_list_out_of_bounds() {
+    let code = String::from("
+        import std::list::{
+            new,
+            push,
+            pop
+        };
+
+        fn: main() ~ int {
+            var list = new();
+            push(list, 1);
+            pop(list);
+            return pop(list);
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let reg_res = pgs_std::register_extension(&mut engine);
+    assert!(reg_res.is_ok());
+
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_err());
+}
+
+/// Path of the cdylib of this crate, built next to the test binary
+fn get_extension_path() -> PathBuf {
+    let deps_dir = env::current_exe().unwrap()
+        .parent().unwrap()
+        .to_path_buf();
+    deps_dir.join(format!("{}pgs_std{}", DLL_PREFIX, DLL_SUFFIX))
+}
+
+#[test]
+fn test_std_load_extension() {
+    let code = String::from("
+        import std::int_to_string;
+        import std::parse_int;
+
+        fn: main() ~ int {
+            return parse_int(int_to_string(21)) * 2;
+        }
+    ");
+
+    let path = get_extension_path();
+    let mut engine = Engine::new(1024);
+    let ext_res = engine.load_extension(&path);
+    println!("{:?}", ext_res);
+    assert!(ext_res.is_ok());
+    // Loading it again does not register the module twice
+    assert!(engine.load_extension(&path).is_ok());
+    assert_eq!(1, engine.get_extensions().len());
+
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let mut isolate = engine.create_isolate().unwrap();
+    // The isolate keeps the library open
+    drop(engine);
+    let run_res = isolate.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(42, isolate.get_register_value::<i64>(Register::R0).unwrap());
+
+    let mut engine = Engine::new(1024);
+    match engine.load_extension(path.with_file_name("missing_extension.so")) {
+        Err(error) => {
+            assert!(matches!(*error, EngineError::ExtensionNotLoadable(..)));
+        },
+        Ok(_) => panic!("Missing library was loaded")
+    };
+
+    // A library failing to register is not kept, the modules registered before stay usable
+    let mut engine = Engine::new(1024);
+    let module = Module::new("std")
+        .with_function(Function::wrap("parse_int", |value: i64| value));
+    assert!(engine.register_module(module).is_ok());
+    let ext_res = engine.load_extension(&path);
+    println!("{:?}", ext_res);
+    assert!(ext_res.is_err());
+    assert!(engine.get_extensions().is_empty());
+
+    let load_res = engine.load_code("
+        import std::parse_int;
+
+        fn: main() ~ int {
+            return parse_int(42);
+        }
+    ");
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+    assert!(engine.run_fn("root::main").is_ok());
+    assert_eq!(42, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_std_net_invalid_handle() {
+    let code = String::from("
+        import std::net::{
+            write,
+            close
+        };
+
+        fn: send(handle: int) ~ int {
+            return write(handle, \"data\");
+        }
+
+        fn: shut(handle: int) {
+            close(handle);
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let capabilities = pgs_std::Capabilities::new()
+        .with_net(true);
+    let reg_res = pgs_std::register_extension_with(&mut engine, &capabilities);
+    assert!(reg_res.is_ok());
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    // Handles of other host objects are not taken for sockets
+    let other = std::sync::Arc::new(std::sync::Mutex::new(String::from("not a socket")));
+    let handle = engine.insert_foreign_ptr(other.clone()).unwrap();
+    let call_res = engine.call_function::<(i64,), i64>("root::send", (handle as i64,));
+    println!("{:?}", call_res);
+    assert!(call_res.unwrap_err().to_string().contains("InvalidHandle"));
+    let call_res = engine.call_function::<(i64,), ()>("root::shut", (handle as i64,));
+    println!("{:?}", call_res);
+    assert!(call_res.unwrap_err().to_string().contains("InvalidHandle"));
+    assert_eq!("not a socket", *other.lock().unwrap());
+    assert!(engine.remove_foreign_ptr::<String>(handle).is_ok());
+
+    let call_res = engine.call_function::<(i64,), i64>("root::send", (12345,));
+    assert!(call_res.unwrap_err().to_string().contains("InvalidHandle"));
+}
@@ -0,0 +1,49 @@
+extern crate pgs;
+use pgs::{
+    engine::Engine
+};
+
+use std::{
+    time::Instant
+};
+
+/// Number of timed runs, the fastest one is reported
+const RUNS: usize = 10;
+
+/// Measures the instruction dispatch of the core with a loop of integer arithmetic and calls.
+/// Run with "cargo bench --bench dispatch".
+fn main() {
+    let code = String::from("
+        fn: step(x: int, i: int) ~ int {
+            return (x * 31 + i) % 1000003;
+        }
+
+        fn: main() ~ int {
+            var x = 1;
+            var i = 0;
+            while i < 200000 {
+                x = step(x, i);
+                i += 1;
+            }
+            return x;
+        }
+    ");
+
+    let mut engine = Engine::new(1024 * 1024);
+    engine.load_code(&code)
+        .expect("Benchmark script failed to compile");
+
+    let best = (0..RUNS).map(|_| {
+        engine.reset_stats();
+        let start = Instant::now();
+        engine.run_fn("root::main")
+            .expect("Benchmark script failed to run");
+        start.elapsed()
+    }).min().unwrap();
+
+    let instructions = engine.get_stats().instructions;
+    println!("dispatch: {} instructions in {:?}, {:.2} ns per instruction",
+        instructions,
+        best,
+        best.as_nanos() as f64 / instructions as f64);
+}
@@ -1,17 +1,25 @@
 use crate::{
     api::{
         function::{
-            Function
-        }
+            Function,
+            FunctionError
+        },
+        container::Container
     },
     codegen::{
         register::{
             Register
         }
     },
+    parser::{
+        ast::{
+            Type
+        }
+    },
     vm::{
         core::{
-            Core
+            Core,
+            CoreError
         },
         register::{
             Register as RegisterUnion,
@@ -23,20 +31,32 @@ use crate::{
 use std::{
     sync::{
         Arc,
-        Mutex
-    }
+        Mutex,
+        PoisonError
+    },
+    ops::Deref,
+    mem::size_of,
+    any::Any
 };
 
 use serde::{
+    Serialize,
     de::DeserializeOwned
 };
 
+/// The view a foreign function gets of the calling script, passed to its closure.
+///
+/// Arguments are read with get_arg() by their index, values are handed back with
+/// return_value(), return_string() or return_optional(), matching the return type of the function.
+/// Errors are raised with raise(), they end the run once the function returns.
 pub struct Adapter<'c> {
+    /// The function being called
     pub function: Function,
     pub core: &'c mut Core
 }
 
 impl<'c> Adapter<'c> {
+    /// Creates the adapter for a call of the given function, done by the core
     pub fn new(func: &Function, core: &'c mut Core) -> Adapter<'c> {
         Adapter {
             function: func.clone(),
@@ -44,17 +64,76 @@ impl<'c> Adapter<'c> {
         }
     }
 
+    /// Gets the argument with the given index, the first one has index 0
     pub fn get_arg<T>(&mut self, arg_index: usize) -> T
     where T: FromArg {
         T::get(self, arg_index)
     }
 
+    /// Returns a value of a register type, i.e. anything but strings, optionals and containers
     pub fn return_value<T>(&mut self, value: T)
     where RegisterUnion: RegisterAccess<T> {
         self.core.reg(Register::R0.into()).unwrap().set::<T>(value);
     }
 
-    // Retrieves a foreign pointer and returns the correct
+    /// Gets the context of the given type set for the core, e.g. with Engine::set_context()
+    pub fn get_context_mut<T: Any + Send>(&mut self) -> Option<&mut T> {
+        self.core.get_context_mut::<T>()
+    }
+
+    /// Returns a string, its contents are copied onto the heap
+    pub fn return_string(&mut self, value: String) {
+        match self.core.heap_alloc(value.as_bytes()) {
+            Ok(addr) => {
+                self.core.push_stack(value.len() as u64).unwrap();
+                self.core.push_stack(addr).unwrap();
+            },
+            Err(error) => {
+                // Keep the stack layout intact, the error ends the run after the call
+                self.core.push_stack(0u64).unwrap();
+                self.core.push_stack(0u64).unwrap();
+                self.core.set_foreign_error(error);
+            }
+        };
+    }
+
+    /// Returns an optional of a register type, None returns null.
+    /// Chars have to be returned as their u32 code point.
+    pub fn return_optional<T>(&mut self, value: Option<T>)
+    where T: Serialize + Default {
+        let value_size = match &self.function.return_type {
+            Type::Optional(inner_type) => {
+                match inner_type.deref() {
+                    Type::Int | Type::Double | Type::Enum(_) | Type::Reference(_) => 8,
+                    _ => 4
+                }
+            },
+            _ => panic!("Not an optional return type!")
+        };
+        let has_value = value.is_some();
+        self.core.push_stack(value.unwrap_or_default()).unwrap();
+        // Pad the value to its size in scripts, e.g. bools take up 4 bytes
+        for _ in size_of::<T>()..value_size {
+            self.core.push_stack(0u8).unwrap();
+        }
+        self.core.push_stack(has_value as u64).unwrap();
+    }
+
+    /// Raises an error, the run ends with CoreError::FunctionError once the function returns.
+    /// Accepts messages as well, e.g. adapter.raise("file not found").
+    pub fn raise<E: Into<FunctionError>>(&mut self, error: E) {
+        let mut error = error.into();
+        error.function = self.function.name.clone();
+        self.core.set_foreign_error(CoreError::FunctionError(Box::new(error)));
+    }
+
+    /// Signals that the function is waiting for the host.
+    /// Execution stops after the function returns, and the function is called again on resume.
+    pub fn block(&mut self) {
+        self.core.block_foreign_call();
+    }
+
+    /// Retrieves a foreign pointer and returns the correct
     /// Arc<Mutex<T>> if found.
     pub fn get_foreign_ptr<T>(&self, ptr: u64) -> Arc<Mutex<T>> {
         self.core.get_foreign_ptr(ptr).unwrap()
@@ -71,6 +150,7 @@ impl<'c> Adapter<'c> {
     }
 }
 
+/// Rust types arguments can be read as, see Adapter::get_arg()
 pub trait FromArg: DeserializeOwned {
     fn get(adapter: &mut Adapter, arg_index: usize) -> Self;
 }
@@ -79,7 +159,7 @@ impl FromArg for String {
     fn get(adapter: &mut Adapter, arg_index: usize) -> String {
         let arg_offset = adapter.function.get_arg_offset(arg_index).abs() as u64;
         //println!("Arg offset of Arg #{}: -{}B", arg_index, arg_offset);
-        let mut stack_addr = adapter.core.reg(16).unwrap().get::<u64>();
+        let mut stack_addr = adapter.core.get_registers().sp.get::<u64>();
         stack_addr -= arg_offset;
         let string_res = adapter.core.mem_get_string(stack_addr);
         //println!("{:?}", string_res);
@@ -87,10 +167,24 @@ impl FromArg for String {
     }
 }
 
+/// A slice of strings, read from its (length, address) pair
+impl FromArg for Vec<String> {
+    fn get(adapter: &mut Adapter, arg_index: usize) -> Vec<String> {
+        let arg_offset = adapter.function.get_arg_offset(arg_index).unsigned_abs();
+        let mut stack_addr = adapter.core.get_registers().sp.get::<u64>();
+        stack_addr -= arg_offset;
+        let length: u64 = adapter.core.mem_get((stack_addr, 0)).unwrap();
+        let data_addr: u64 = adapter.core.mem_get((stack_addr, 8)).unwrap();
+        (0..length)
+            .map(|index| adapter.core.mem_get_string(data_addr + index * 16).unwrap())
+            .collect()
+    }
+}
+
 impl FromArg for i64 {
     fn get(adapter: &mut Adapter, arg_index: usize) -> i64 {
         let arg_offset = adapter.function.get_arg_offset(arg_index) as i16;
-        let addr = adapter.core.reg(16).unwrap().get::<u64>();
+        let addr = adapter.core.get_registers().sp.get::<u64>();
         adapter.core.mem_get((addr, arg_offset)).unwrap()
     }
 }
@@ -98,7 +192,15 @@ impl FromArg for i64 {
 impl FromArg for f32 {
     fn get(adapter: &mut Adapter, arg_index: usize) -> f32 {
         let arg_offset = adapter.function.get_arg_offset(arg_index) as i16;
-        let addr = adapter.core.reg(16).unwrap().get::<u64>();
+        let addr = adapter.core.get_registers().sp.get::<u64>();
+        adapter.core.mem_get((addr, arg_offset)).unwrap()
+    }
+}
+
+impl FromArg for f64 {
+    fn get(adapter: &mut Adapter, arg_index: usize) -> f64 {
+        let arg_offset = adapter.function.get_arg_offset(arg_index) as i16;
+        let addr = adapter.core.get_registers().sp.get::<u64>();
         adapter.core.mem_get((addr, arg_offset)).unwrap()
     }
 }
@@ -106,7 +208,267 @@ impl FromArg for f32 {
 impl FromArg for u64 {
     fn get(adapter: &mut Adapter, arg_index: usize) -> u64 {
         let arg_offset = adapter.function.get_arg_offset(arg_index) as i16;
-        let addr = adapter.core.reg(16).unwrap().get::<u64>();
+        let addr = adapter.core.get_registers().sp.get::<u64>();
+        adapter.core.mem_get((addr, arg_offset)).unwrap()
+    }
+}
+impl FromArg for bool {
+    fn get(adapter: &mut Adapter, arg_index: usize) -> bool {
+        let arg_offset = adapter.function.get_arg_offset(arg_index) as i16;
+        let addr = adapter.core.get_registers().sp.get::<u64>();
         adapter.core.mem_get((addr, arg_offset)).unwrap()
     }
-}
\ No newline at end of file
+}
+
+/// An argument of a function created with Function::wrap()
+pub trait WrapArg: FromArg {
+    /// Gets the script type of the argument
+    fn get_type() -> Type;
+}
+
+impl WrapArg for String {
+    fn get_type() -> Type {
+        Type::String
+    }
+}
+
+impl WrapArg for Vec<String> {
+    fn get_type() -> Type {
+        Type::Reference(Box::new(Type::AutoArray(Box::new(Type::String))))
+    }
+}
+
+impl WrapArg for i64 {
+    fn get_type() -> Type {
+        Type::Int
+    }
+}
+
+impl WrapArg for f32 {
+    fn get_type() -> Type {
+        Type::Float
+    }
+}
+
+impl WrapArg for f64 {
+    fn get_type() -> Type {
+        Type::Double
+    }
+}
+
+impl WrapArg for bool {
+    fn get_type() -> Type {
+        Type::Bool
+    }
+}
+
+/// The return value of a function created with Function::wrap()
+pub trait WrapReturn {
+    /// Gets the script type of the return value
+    fn get_type() -> Type;
+
+    /// Hands the value to the calling script
+    fn return_to(self, adapter: &mut Adapter);
+}
+
+impl WrapReturn for () {
+    fn get_type() -> Type {
+        Type::Void
+    }
+
+    fn return_to(self, _adapter: &mut Adapter) {}
+}
+
+impl WrapReturn for String {
+    fn get_type() -> Type {
+        Type::String
+    }
+
+    fn return_to(self, adapter: &mut Adapter) {
+        adapter.return_string(self);
+    }
+}
+
+impl WrapReturn for i64 {
+    fn get_type() -> Type {
+        Type::Int
+    }
+
+    fn return_to(self, adapter: &mut Adapter) {
+        adapter.return_value(self);
+    }
+}
+
+impl WrapReturn for f32 {
+    fn get_type() -> Type {
+        Type::Float
+    }
+
+    fn return_to(self, adapter: &mut Adapter) {
+        adapter.return_value(self);
+    }
+}
+
+impl WrapReturn for f64 {
+    fn get_type() -> Type {
+        Type::Double
+    }
+
+    fn return_to(self, adapter: &mut Adapter) {
+        adapter.return_value(self);
+    }
+}
+
+impl WrapReturn for bool {
+    fn get_type() -> Type {
+        Type::Bool
+    }
+
+    fn return_to(self, adapter: &mut Adapter) {
+        adapter.return_value(self);
+    }
+}
+
+/// Raises the error of a failed call, the value of a successful one is returned
+impl<T: WrapReturn, E: Into<FunctionError>> WrapReturn for Result<T, E> {
+    fn get_type() -> Type {
+        T::get_type()
+    }
+
+    fn return_to(self, adapter: &mut Adapter) {
+        match self {
+            Ok(value) => value.return_to(adapter),
+            Err(error) => adapter.raise(error)
+        };
+    }
+}
+
+/// Hands a host object to the calling script, which gets its handle
+impl<T: ObjectAdapter> WrapReturn for Arc<Mutex<T>> {
+    fn get_type() -> Type {
+        Type::Int
+    }
+
+    fn return_to(self, adapter: &mut Adapter) {
+        match adapter.core.insert_foreign_ptr(self) {
+            Ok(handle) => adapter.return_value(handle as i64),
+            Err(error) => adapter.core.set_foreign_error(error)
+        };
+    }
+}
+
+/// A host type bridged to scripts as an opaque handle with methods, see Engine::register_adapter().
+///
+/// Scripts only see an int handle, the foreign pointer of an instance shared with
+/// Engine::insert_foreign_ptr() or returned by a method as Arc<Mutex<T>>. A method "read"
+/// of the type "File" is called as "File::read(file, ...)" and runs on the locked instance.
+/// Handles of other types fail the run with CoreError::InvalidHandle.
+pub trait ObjectAdapter: Sized + Send + 'static {
+    /// Name of the container the methods are members of
+    fn type_name() -> String;
+
+    /// Gets the methods scripts can call
+    fn methods() -> Vec<Method<Self>>;
+}
+
+/// Closure of a method, gets the instance and the adapter of the call
+pub type MethodClosureType<T> = dyn FnMut(&mut T, &mut Adapter) + Send;
+
+/// A method of an ObjectAdapter
+pub struct Method<T> {
+    pub name: String,
+    /// Argument types, without the handle
+    pub arg_types: Vec<Type>,
+    pub return_type: Type,
+    closure: Box<MethodClosureType<T>>
+}
+
+impl<T: ObjectAdapter> Method<T> {
+    /// Creates a method calling a Rust closure, which gets the instance and the arguments, e.g.
+    /// Method::wrap("seek", |file: &mut File, pos: i64| file.pos = pos).
+    /// Argument and return types work like with Function::wrap().
+    pub fn wrap<N, F, A, R>(name: N, method: F) -> Method<T>
+    where String: From<N>, F: WrapMethod<T, A, R> {
+        let mut method = method;
+        Method {
+            name: String::from(name),
+            arg_types: F::get_arg_types(),
+            return_type: F::get_ret_type(),
+            closure: Box::new(move |object: &mut T, adapter: &mut Adapter| method.call(object, adapter, 1))
+        }
+    }
+
+    /// Builds the foreign member function of the container, taking the handle as its first argument
+    pub fn into_function(self) -> Function {
+        let mut closure = self.closure;
+        let mut function = Function::new(self.name)
+            .with_arg(Type::Int)
+            .with_ret_type(self.return_type)
+            .with_closure(Box::new(move |adapter: &mut Adapter| {
+                let handle: u64 = adapter.get_arg(0);
+                match adapter.core.get_foreign_object::<T>(handle) {
+                    Ok(object) => {
+                        let mut object = object.lock()
+                            .unwrap_or_else(PoisonError::into_inner);
+                        closure(&mut object, adapter);
+                    },
+                    Err(error) => adapter.core.set_foreign_error(error)
+                };
+            }));
+        for arg_type in self.arg_types {
+            function = function.with_arg(arg_type);
+        }
+        function
+    }
+}
+
+/// Builds the container of an ObjectAdapter, with a member function for every method
+pub fn adapter_container<T: ObjectAdapter>() -> Container {
+    T::methods().into_iter()
+        .fold(Container::new(T::type_name()), |container, method| {
+            container.with_function(method.into_function())
+        })
+}
+
+/// A Rust closure usable as a method, see Method::wrap()
+pub trait WrapMethod<T, A, R>: Send + 'static {
+    fn get_arg_types() -> Vec<Type>;
+
+    fn get_ret_type() -> Type;
+
+    /// Reads the arguments from the given index on, calls the closure and returns its value
+    fn call(&mut self, object: &mut T, adapter: &mut Adapter, first_arg: usize);
+}
+
+/// Implements WrapMethod for closures with the given argument types
+macro_rules! impl_wrap_method {
+    ($($arg:ident),*) => {
+        impl<T, F, R, $($arg),*> WrapMethod<T, ($($arg,)*), R> for F
+        where F: FnMut(&mut T, $($arg),*) -> R + Send + 'static, R: WrapReturn, $($arg: WrapArg),* {
+            fn get_arg_types() -> Vec<Type> {
+                vec![$($arg::get_type()),*]
+            }
+
+            fn get_ret_type() -> Type {
+                R::get_type()
+            }
+
+            #[allow(non_snake_case, unused_mut, unused_variables, unused_assignments)]
+            fn call(&mut self, object: &mut T, adapter: &mut Adapter, first_arg: usize) {
+                let mut arg_index = first_arg;
+                $(
+                    let $arg: $arg = adapter.get_arg(arg_index);
+                    arg_index += 1;
+                )*
+                self(object, $($arg),*).return_to(adapter);
+            }
+        }
+    };
+}
+
+impl_wrap_method!();
+impl_wrap_method!(A1);
+impl_wrap_method!(A1, A2);
+impl_wrap_method!(A1, A2, A3);
+impl_wrap_method!(A1, A2, A3, A4);
+impl_wrap_method!(A1, A2, A3, A4, A5);
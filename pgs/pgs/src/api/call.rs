@@ -0,0 +1,400 @@
+use crate::{
+    api::{
+        container::PgsValue,
+        adapter::ObjectAdapter
+    },
+    codegen::{
+        register::Register
+    },
+    parser::{
+        ast::Type
+    },
+    vm::{
+        core::{
+            Core,
+            CoreError,
+            CoreResult
+        }
+    }
+};
+
+use std::{
+    sync::{
+        Arc,
+        Mutex
+    }
+};
+
+/// A value passed to a script function by Engine::call_function()
+pub trait ScriptArg {
+    /// Checks if the value can be passed as an argument of the given script type
+    fn is_script_type(script_type: &Type) -> bool;
+
+    /// Pushes the value on the stack, taking up the size of its script type.
+    /// References get the address of the value pushed by push_referenced().
+    fn push_arg(&self, core: &mut Core, referenced_addr: u64) -> CoreResult<()>;
+
+    /// Pushes the value a reference refers to, before the arguments are pushed
+    fn push_referenced(&self, _core: &mut Core) -> CoreResult<()> {
+        Ok(())
+    }
+
+    /// Reads back the value a reference refers to once the function returned
+    fn read_referenced(&mut self, _core: &Core, _referenced_addr: u64) -> CoreResult<()> {
+        Ok(())
+    }
+}
+
+/// A value returned by a script function to Engine::call_function()
+pub trait ScriptReturn: Sized {
+    /// Checks if a return value of the given script type can be read as this type
+    fn is_script_type(script_type: &Type) -> bool;
+
+    /// Reads the return value after the function returned
+    fn get_return(core: &Core) -> CoreResult<Self>;
+}
+
+/// The arguments of a script function, a tuple of ScriptArg values in declaration order
+pub trait ScriptArgs {
+    /// Checks if the arguments can be passed to a function with the given argument types
+    fn is_script_types(arg_types: &[Type]) -> bool;
+
+    /// Pushes the values references refer to and then the arguments, the first one first.
+    /// Returns the addresses of the referenced values for read_back().
+    fn push_args(&self, core: &mut Core) -> CoreResult<Vec<u64>>;
+
+    /// Reads back the values references refer to, which the function may have changed
+    fn read_back(&mut self, core: &Core, referenced_addrs: &[u64]) -> CoreResult<()>;
+}
+
+impl ScriptArg for i64 {
+    fn is_script_type(script_type: &Type) -> bool {
+        matches!(script_type, Type::Int | Type::Enum(_))
+    }
+
+    fn push_arg(&self, core: &mut Core, _referenced_addr: u64) -> CoreResult<()> {
+        core.push_stack(*self)
+    }
+}
+
+impl ScriptArg for f32 {
+    fn is_script_type(script_type: &Type) -> bool {
+        *script_type == Type::Float
+    }
+
+    fn push_arg(&self, core: &mut Core, _referenced_addr: u64) -> CoreResult<()> {
+        core.push_stack(*self)
+    }
+}
+
+impl ScriptArg for f64 {
+    fn is_script_type(script_type: &Type) -> bool {
+        *script_type == Type::Double
+    }
+
+    fn push_arg(&self, core: &mut Core, _referenced_addr: u64) -> CoreResult<()> {
+        core.push_stack(*self)
+    }
+}
+
+impl ScriptArg for bool {
+    fn is_script_type(script_type: &Type) -> bool {
+        *script_type == Type::Bool
+    }
+
+    /// Bools take up 4 bytes in scripts
+    fn push_arg(&self, core: &mut Core, _referenced_addr: u64) -> CoreResult<()> {
+        core.push_stack(*self as u32)
+    }
+}
+
+impl ScriptArg for char {
+    fn is_script_type(script_type: &Type) -> bool {
+        *script_type == Type::Char
+    }
+
+    /// Chars are passed as their u32 code point
+    fn push_arg(&self, core: &mut Core, _referenced_addr: u64) -> CoreResult<()> {
+        core.push_stack(*self as u32)
+    }
+}
+
+/// A value scripts change through a reference, e.g. "&Vec2". It is read back once the function returned.
+impl<T: PgsValue> ScriptArg for &mut T {
+    fn is_script_type(script_type: &Type) -> bool {
+        match script_type {
+            Type::Reference(inner_type) => is_value_type::<T>(inner_type),
+            _ => false
+        }
+    }
+
+    fn push_arg(&self, core: &mut Core, referenced_addr: u64) -> CoreResult<()> {
+        core.push_stack(referenced_addr)
+    }
+
+    fn push_referenced(&self, core: &mut Core) -> CoreResult<()> {
+        push_value(&**self, core)
+    }
+
+    fn read_referenced(&mut self, core: &Core, referenced_addr: u64) -> CoreResult<()> {
+        let bytes = core.mem_get_bytes(referenced_addr, T::get_size())?;
+        self.read_bytes_into(bytes);
+        Ok(())
+    }
+}
+
+/// Checks if the given script type is the container of a PgsValue
+pub fn is_value_type<T: PgsValue>(script_type: &Type) -> bool {
+    let name = T::value_container().name;
+    match script_type {
+        Type::Other(type_name) => *type_name == name || type_name.ends_with(&format!("::{}", name)),
+        _ => false
+    }
+}
+
+/// Pushes a PgsValue on the stack, laid out like in scripts
+pub fn push_value<T: PgsValue>(value: &T, core: &mut Core) -> CoreResult<()> {
+    let mut bytes = Vec::with_capacity(T::get_size());
+    value.write_bytes(&mut bytes);
+    core.push_bytes(&bytes)
+}
+
+/// Reads a PgsValue returned by a script function, containers are returned on top of the stack
+pub fn get_value_return<T: PgsValue>(core: &Core) -> CoreResult<T> {
+    let size = T::get_size();
+    let sp: u64 = core.get_registers().sp.get();
+    let addr = sp.checked_sub(size as u64)
+        .ok_or(CoreError::InvalidStackPointer)?;
+    Ok(T::read_bytes(core.mem_get_bytes(addr, size)?))
+}
+
+impl ScriptReturn for () {
+    fn is_script_type(script_type: &Type) -> bool {
+        *script_type == Type::Void
+    }
+
+    fn get_return(_core: &Core) -> CoreResult<()> {
+        Ok(())
+    }
+}
+
+impl ScriptReturn for i64 {
+    fn is_script_type(script_type: &Type) -> bool {
+        matches!(script_type, Type::Int | Type::Enum(_))
+    }
+
+    fn get_return(core: &Core) -> CoreResult<i64> {
+        Ok(core.get_register(Register::R0.into())?.get())
+    }
+}
+
+impl ScriptReturn for f32 {
+    fn is_script_type(script_type: &Type) -> bool {
+        *script_type == Type::Float
+    }
+
+    fn get_return(core: &Core) -> CoreResult<f32> {
+        Ok(core.get_register(Register::R0.into())?.get())
+    }
+}
+
+impl ScriptReturn for f64 {
+    fn is_script_type(script_type: &Type) -> bool {
+        *script_type == Type::Double
+    }
+
+    fn get_return(core: &Core) -> CoreResult<f64> {
+        Ok(core.get_register(Register::R0.into())?.get())
+    }
+}
+
+impl ScriptReturn for bool {
+    fn is_script_type(script_type: &Type) -> bool {
+        *script_type == Type::Bool
+    }
+
+    fn get_return(core: &Core) -> CoreResult<bool> {
+        Ok(core.get_register(Register::R0.into())?.get())
+    }
+}
+
+impl ScriptReturn for char {
+    fn is_script_type(script_type: &Type) -> bool {
+        *script_type == Type::Char
+    }
+
+    fn get_return(core: &Core) -> CoreResult<char> {
+        let code_point = core.get_register(Register::R0.into())?.get::<u64>() as u32;
+        std::char::from_u32(code_point)
+            .ok_or(CoreError::OperatorDeserialize)
+    }
+}
+
+/// A host object returned by its handle, see ObjectAdapter
+impl<T: ObjectAdapter> ScriptReturn for Arc<Mutex<T>> {
+    fn is_script_type(script_type: &Type) -> bool {
+        *script_type == Type::Int
+    }
+
+    fn get_return(core: &Core) -> CoreResult<Arc<Mutex<T>>> {
+        let handle = core.get_register(Register::R0.into())?.get::<u64>();
+        core.get_foreign_object(handle)
+    }
+}
+
+/// A value of a module level variable, see Engine::get_global() and Engine::set_global()
+pub trait ScriptGlobal: Sized {
+    /// Checks if a variable of the given script type holds this type
+    fn is_script_type(script_type: &Type) -> bool;
+
+    /// Reads the variable at the given address
+    fn read_global(core: &Core, addr: u64) -> CoreResult<Self>;
+
+    /// Writes the variable at the given address
+    fn write_global(&self, core: &mut Core, addr: u64) -> CoreResult<()>;
+}
+
+impl ScriptGlobal for i64 {
+    fn is_script_type(script_type: &Type) -> bool {
+        *script_type == Type::Int
+    }
+
+    fn read_global(core: &Core, addr: u64) -> CoreResult<i64> {
+        core.mem_get((addr, 0))
+    }
+
+    fn write_global(&self, core: &mut Core, addr: u64) -> CoreResult<()> {
+        core.mem_set((addr, 0), *self)
+    }
+}
+
+impl ScriptGlobal for f32 {
+    fn is_script_type(script_type: &Type) -> bool {
+        *script_type == Type::Float
+    }
+
+    fn read_global(core: &Core, addr: u64) -> CoreResult<f32> {
+        core.mem_get((addr, 0))
+    }
+
+    fn write_global(&self, core: &mut Core, addr: u64) -> CoreResult<()> {
+        core.mem_set((addr, 0), *self)
+    }
+}
+
+impl ScriptGlobal for f64 {
+    fn is_script_type(script_type: &Type) -> bool {
+        *script_type == Type::Double
+    }
+
+    fn read_global(core: &Core, addr: u64) -> CoreResult<f64> {
+        core.mem_get((addr, 0))
+    }
+
+    fn write_global(&self, core: &mut Core, addr: u64) -> CoreResult<()> {
+        core.mem_set((addr, 0), *self)
+    }
+}
+
+/// Bools take up 4 bytes in scripts
+impl ScriptGlobal for bool {
+    fn is_script_type(script_type: &Type) -> bool {
+        *script_type == Type::Bool
+    }
+
+    fn read_global(core: &Core, addr: u64) -> CoreResult<bool> {
+        core.mem_get((addr, 0))
+    }
+
+    fn write_global(&self, core: &mut Core, addr: u64) -> CoreResult<()> {
+        core.mem_set((addr, 0), *self as u32)
+    }
+}
+
+/// Chars are stored as their u32 code point
+impl ScriptGlobal for char {
+    fn is_script_type(script_type: &Type) -> bool {
+        *script_type == Type::Char
+    }
+
+    fn read_global(core: &Core, addr: u64) -> CoreResult<char> {
+        let code_point: u32 = core.mem_get((addr, 0))?;
+        std::char::from_u32(code_point)
+            .ok_or(CoreError::OperatorDeserialize)
+    }
+
+    fn write_global(&self, core: &mut Core, addr: u64) -> CoreResult<()> {
+        core.mem_set((addr, 0), *self as u32)
+    }
+}
+
+/// Strings written by the host are copied onto the heap
+impl ScriptGlobal for String {
+    fn is_script_type(script_type: &Type) -> bool {
+        *script_type == Type::String
+    }
+
+    fn read_global(core: &Core, addr: u64) -> CoreResult<String> {
+        core.mem_get_string(addr)
+    }
+
+    fn write_global(&self, core: &mut Core, addr: u64) -> CoreResult<()> {
+        let string_addr = core.heap_alloc(self.as_bytes())?;
+        core.mem_set((addr, 0), self.len() as u64)?;
+        core.mem_set((addr, 8), string_addr)
+    }
+}
+
+/// Implements ScriptArgs for a tuple of the given element types
+macro_rules! impl_script_args {
+    ($($arg:ident),*) => {
+        impl<$($arg: ScriptArg),*> ScriptArgs for ($($arg,)*) {
+            #[allow(unused_mut, unused_variables)]
+            fn is_script_types(arg_types: &[Type]) -> bool {
+                let mut arg_types = arg_types.iter();
+                $(
+                    match arg_types.next() {
+                        Some(arg_type) if $arg::is_script_type(arg_type) => {},
+                        _ => return false
+                    };
+                )*
+                arg_types.next().is_none()
+            }
+
+            #[allow(non_snake_case, unused_mut, unused_variables)]
+            fn push_args(&self, core: &mut Core) -> CoreResult<Vec<u64>> {
+                let ($($arg,)*) = self;
+                let referenced_addrs: Vec<u64> = vec![$({
+                    let referenced_addr = core.get_registers().sp.get::<u64>();
+                    $arg.push_referenced(core)?;
+                    referenced_addr
+                }),*];
+                let mut addrs = referenced_addrs.iter();
+                $(
+                    $arg.push_arg(core, addrs.next().cloned().unwrap_or_default())?;
+                )*
+                Ok(referenced_addrs)
+            }
+
+            #[allow(non_snake_case, unused_mut, unused_variables)]
+            fn read_back(&mut self, core: &Core, referenced_addrs: &[u64]) -> CoreResult<()> {
+                let ($($arg,)*) = self;
+                let mut referenced_addrs = referenced_addrs.iter();
+                $(
+                    $arg.read_referenced(core, referenced_addrs.next().cloned().unwrap_or_default())?;
+                )*
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_script_args!();
+impl_script_args!(A);
+impl_script_args!(A, B);
+impl_script_args!(A, B, C);
+impl_script_args!(A, B, C, D);
+impl_script_args!(A, B, C, D, E);
+impl_script_args!(A, B, C, D, E, F);
+impl_script_args!(A, B, C, D, E, F, G);
+impl_script_args!(A, B, C, D, E, F, G, H);
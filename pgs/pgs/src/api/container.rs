@@ -1,17 +1,34 @@
 use crate::{
     api::{
-        function::Function
+        function::Function,
+        adapter::{
+            Adapter,
+            FromArg
+        }
+    },
+    parser::{
+        ast::Type
     }
 };
 
+#[cfg(feature = "derive")]
+pub use pgs_derive::{
+    PgsContainer,
+    PgsValue
+};
+
 use std::{
-    collections::HashMap
+    collections::HashMap,
+    convert::TryInto
 };
 
 /// A Container definition
+#[derive(Clone)]
 pub struct Container {
     pub name: String,
-    pub members: HashMap<String, ContainerMember>
+    pub members: HashMap<String, ContainerMember>,
+    /// Member variables stored in the instances, in declaration order
+    pub fields: Vec<(String, Type)>
 }
 
 impl Container {
@@ -19,7 +36,8 @@ impl Container {
     pub fn new(name: String) -> Container {
         Container {
             name: name,
-            members: HashMap::new()
+            members: HashMap::new(),
+            fields: Vec::new()
         }
     }
 
@@ -37,8 +55,15 @@ impl Container {
         });
         self
     }
+
+    /// ...with a member variable stored in the instances, laid out after the previous ones
+    pub fn with_field(mut self, name: String, field_type: Type) -> Container {
+        self.fields.push((name, field_type));
+        self
+    }
 }
 
+#[derive(Clone)]
 pub enum ContainerMember {
     Function(Function),
     Variable {
@@ -47,3 +72,227 @@ pub enum ContainerMember {
     }
 }
 
+
+/// A Rust type exposed to scripts as a container.
+///
+/// Usually implemented with `#[derive(PgsContainer)]`, which exposes every field `x`
+/// as a member variable with the accessor `get_x(handle: int)` and the setter
+/// `set_x(handle: int, value)`. The handle is the foreign pointer of an instance.
+pub trait PgsContainer: Sized + 'static {
+    /// Builds the container definition
+    fn container() -> Container;
+}
+
+/// Rust types usable as fields of a PgsContainer
+pub trait ContainerField: FromArg + Clone {
+    /// Gets the script type of the field
+    fn field_type() -> Type;
+
+    /// Returns the value from a foreign function
+    fn return_field(self, adapter: &mut Adapter);
+}
+
+impl ContainerField for i64 {
+    fn field_type() -> Type {
+        Type::Int
+    }
+
+    fn return_field(self, adapter: &mut Adapter) {
+        adapter.return_value(self);
+    }
+}
+
+impl ContainerField for f32 {
+    fn field_type() -> Type {
+        Type::Float
+    }
+
+    fn return_field(self, adapter: &mut Adapter) {
+        adapter.return_value(self);
+    }
+}
+
+impl ContainerField for f64 {
+    fn field_type() -> Type {
+        Type::Double
+    }
+
+    fn return_field(self, adapter: &mut Adapter) {
+        adapter.return_value(self);
+    }
+}
+
+impl ContainerField for bool {
+    fn field_type() -> Type {
+        Type::Bool
+    }
+
+    fn return_field(self, adapter: &mut Adapter) {
+        adapter.return_value(self);
+    }
+}
+
+impl ContainerField for String {
+    fn field_type() -> Type {
+        Type::String
+    }
+
+    fn return_field(self, adapter: &mut Adapter) {
+        adapter.return_string(self);
+    }
+}
+
+/// A Rust type passed to scripts by value, as a container with the same fields.
+///
+/// Usually implemented with `#[derive(PgsValue)]`. Register it with Engine::register_value(),
+/// then scripts can create instances, read and assign their fields, and the host passes them
+/// to Engine::call_function() by value or as a reference scripts mutate.
+pub trait PgsValue: Sized + 'static {
+    /// Builds the container definition, with a field for every member
+    fn value_container() -> Container;
+
+    /// Gets the size of an instance in scripts
+    fn get_size() -> usize;
+
+    /// Appends the bytes of the instance, laid out like in scripts
+    fn write_bytes(&self, bytes: &mut Vec<u8>);
+
+    /// Reads an instance from bytes laid out like in scripts
+    fn read_bytes(bytes: &[u8]) -> Self;
+
+    /// Updates the exposed fields from bytes laid out like in scripts, skipped fields are kept
+    fn read_bytes_into(&mut self, bytes: &[u8]);
+}
+
+/// Rust types usable as fields of a PgsValue
+pub trait ValueField: Sized {
+    /// Gets the script type of the field
+    fn field_type() -> Type;
+
+    fn get_size() -> usize;
+
+    fn write_bytes(&self, bytes: &mut Vec<u8>);
+
+    fn read_bytes(bytes: &[u8]) -> Self;
+
+    fn read_bytes_into(&mut self, bytes: &[u8]) {
+        *self = Self::read_bytes(bytes);
+    }
+}
+
+impl ValueField for i64 {
+    fn field_type() -> Type {
+        Type::Int
+    }
+
+    fn get_size() -> usize {
+        8
+    }
+
+    fn write_bytes(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.to_le_bytes());
+    }
+
+    fn read_bytes(bytes: &[u8]) -> i64 {
+        i64::from_le_bytes(bytes.try_into().unwrap())
+    }
+}
+
+impl ValueField for f32 {
+    fn field_type() -> Type {
+        Type::Float
+    }
+
+    fn get_size() -> usize {
+        4
+    }
+
+    fn write_bytes(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.to_le_bytes());
+    }
+
+    fn read_bytes(bytes: &[u8]) -> f32 {
+        f32::from_le_bytes(bytes.try_into().unwrap())
+    }
+}
+
+impl ValueField for f64 {
+    fn field_type() -> Type {
+        Type::Double
+    }
+
+    fn get_size() -> usize {
+        8
+    }
+
+    fn write_bytes(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.to_le_bytes());
+    }
+
+    fn read_bytes(bytes: &[u8]) -> f64 {
+        f64::from_le_bytes(bytes.try_into().unwrap())
+    }
+}
+
+/// Bools take up 4 bytes in scripts
+impl ValueField for bool {
+    fn field_type() -> Type {
+        Type::Bool
+    }
+
+    fn get_size() -> usize {
+        4
+    }
+
+    fn write_bytes(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&(*self as u32).to_le_bytes());
+    }
+
+    fn read_bytes(bytes: &[u8]) -> bool {
+        bytes[0] != 0
+    }
+}
+
+/// Chars are stored as their u32 code point, invalid ones are read as the replacement character
+impl ValueField for char {
+    fn field_type() -> Type {
+        Type::Char
+    }
+
+    fn get_size() -> usize {
+        4
+    }
+
+    fn write_bytes(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&(*self as u32).to_le_bytes());
+    }
+
+    fn read_bytes(bytes: &[u8]) -> char {
+        let code_point = u32::from_le_bytes(bytes.try_into().unwrap());
+        std::char::from_u32(code_point)
+            .unwrap_or(std::char::REPLACEMENT_CHARACTER)
+    }
+}
+
+/// Values nest, their containers have to be registered as well
+impl<T: PgsValue> ValueField for T {
+    fn field_type() -> Type {
+        Type::Other(T::value_container().name)
+    }
+
+    fn get_size() -> usize {
+        <T as PgsValue>::get_size()
+    }
+
+    fn write_bytes(&self, bytes: &mut Vec<u8>) {
+        <T as PgsValue>::write_bytes(self, bytes);
+    }
+
+    fn read_bytes(bytes: &[u8]) -> T {
+        <T as PgsValue>::read_bytes(bytes)
+    }
+
+    fn read_bytes_into(&mut self, bytes: &[u8]) {
+        <T as PgsValue>::read_bytes_into(self, bytes);
+    }
+}
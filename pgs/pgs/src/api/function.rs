@@ -4,13 +4,19 @@ use crate::{
             Module
         },
         adapter::{
-            Adapter
+            Adapter,
+            WrapArg,
+            WrapReturn,
+            WrapMethod
         }
     },
     parser::{
         ast::{
             Type
         }
+    },
+    vm::{
+        core::CoreError
     }
 };
 
@@ -28,14 +34,20 @@ use std::{
     fmt::{
         Formatter,
         Result as FmtResult,
-        Debug
+        Debug,
+        Display
     },
+    error::Error,
     clone::{
         Clone
     },
     sync::{
         Arc,
         Mutex
+    },
+    any::{
+        Any,
+        type_name
     }
 };
 
@@ -78,7 +90,71 @@ impl Clone for Function {
     }
 }*/
 
-pub type FunctionClosureType = dyn FnMut(&mut Adapter) -> ();
+/// Closures are shared by the isolates of an engine, which may run on other threads
+pub type FunctionClosureType = dyn FnMut(&mut Adapter) -> () + Send;
+
+/// An error raised by a foreign function with Adapter::raise(), it ends the run with CoreError::FunctionError.
+/// The error of the run carries the source position of the call.
+#[derive(Debug)]
+pub struct FunctionError {
+    /// Name of the function that raised the error, set when it is raised
+    pub function: String,
+    pub message: String,
+    /// The error the message was created from, if any
+    pub payload: Option<Box<dyn Error + Send + Sync>>
+}
+
+impl FunctionError {
+    /// Creates an error with the given message
+    pub fn new<T>(message: T) -> FunctionError
+    where String: From<T> {
+        FunctionError {
+            function: String::new(),
+            message: String::from(message),
+            payload: None
+        }
+    }
+
+    /// Creates an error carrying the given error as its payload, described by its message
+    pub fn from_error<E: Error + Send + Sync + 'static>(error: E) -> FunctionError {
+        FunctionError {
+            function: String::new(),
+            message: error.to_string(),
+            payload: Some(Box::new(error))
+        }
+    }
+
+    /// Gets the payload as the given error type
+    pub fn get_payload<E: Error + 'static>(&self) -> Option<&E> {
+        self.payload.as_ref()
+            .and_then(|payload| payload.downcast_ref::<E>())
+    }
+}
+
+impl Display for FunctionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}: {}", self.function, self.message)
+    }
+}
+
+impl Error for FunctionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.payload.as_ref()
+            .map(|payload| payload.as_ref() as &(dyn Error + 'static))
+    }
+}
+
+impl From<String> for FunctionError {
+    fn from(message: String) -> FunctionError {
+        FunctionError::new(message)
+    }
+}
+
+impl From<&str> for FunctionError {
+    fn from(message: &str) -> FunctionError {
+        FunctionError::new(message)
+    }
+}
 
 impl Function {
     /// Creates a new function
@@ -126,6 +202,12 @@ impl Function {
         *self.arg_offsets.get(&arg_index).unwrap()
     }
 
+    /// Checks if the function has a closure, functions of loaded bytecode files
+    /// and imports of other programs have none until they are bound
+    pub fn has_closure(&self) -> bool {
+        self.closure.is_some()
+    }
+
     /// Runs the internal closure
     pub fn run(&self, adapter: &mut Adapter) {
         let closure_arc = self.closure.as_ref().unwrap();
@@ -134,10 +216,89 @@ impl Function {
         closure(adapter);
     }
     
+    /// Creates a function calling a Rust closure, its argument and return types are taken from the closure.
+    /// The arguments are read from the stack and the return value is handed back to the script, e.g.
+    /// Function::wrap("add", |a: i64, b: i64| a + b). Strings are passed as String.
+    pub fn wrap<T, F, A, R>(name: T, function: F) -> Function
+    where String: From<T>, F: WrapFn<A, R> {
+        let mut function = function;
+        let mut wrapped = Function::new(name)
+            .with_ret_type(F::get_ret_type())
+            .with_closure(Box::new(move |adapter: &mut Adapter| function.call(adapter)));
+        wrapped.arg_types = F::get_arg_types();
+        wrapped
+    }
+
+    /// Creates a function like wrap(), whose closure borrows the context of its type first, e.g.
+    /// Function::wrap_with_context("damage", |game: &mut Game, amount: i64| game.health -= amount).
+    /// The run fails with CoreError::MissingContext if no context of the type was set.
+    pub fn wrap_with_context<C, T, F, A, R>(name: T, function: F) -> Function
+    where C: Any + Send, String: From<T>, F: WrapMethod<C, A, R> {
+        let mut function = function;
+        let mut wrapped = Function::new(name)
+            .with_ret_type(F::get_ret_type())
+            .with_closure(Box::new(move |adapter: &mut Adapter| {
+                // Taken out while the closure runs, so it can use the adapter as well
+                match adapter.core.take_context::<C>() {
+                    Some(mut context) => {
+                        function.call(&mut context, adapter, 0);
+                        adapter.core.set_context(context);
+                    },
+                    None => adapter.core.set_foreign_error(CoreError::MissingContext(type_name::<C>()))
+                };
+            }));
+        wrapped.arg_types = F::get_arg_types();
+        wrapped
+    }
+
     /// Sets the closure to be executes
     pub fn with_closure(mut self, closure: Box<FunctionClosureType>) -> Function {
         let closure_arc = Arc::new(Mutex::new(closure));
         self.closure = Some(closure_arc);
         self
     }
-}
\ No newline at end of file
+}
+
+/// A Rust closure usable as a foreign function, see Function::wrap()
+pub trait WrapFn<A, R>: Send + 'static {
+    fn get_arg_types() -> Vec<Type>;
+
+    fn get_ret_type() -> Type;
+
+    /// Reads the arguments, calls the closure and returns its value
+    fn call(&mut self, adapter: &mut Adapter);
+}
+
+/// Implements WrapFn for closures with the given argument types
+macro_rules! impl_wrap_fn {
+    ($($arg:ident),*) => {
+        impl<F, R, $($arg),*> WrapFn<($($arg,)*), R> for F
+        where F: FnMut($($arg),*) -> R + Send + 'static, R: WrapReturn, $($arg: WrapArg),* {
+            fn get_arg_types() -> Vec<Type> {
+                vec![$($arg::get_type()),*]
+            }
+
+            fn get_ret_type() -> Type {
+                R::get_type()
+            }
+
+            #[allow(non_snake_case, unused_mut, unused_variables, unused_assignments)]
+            fn call(&mut self, adapter: &mut Adapter) {
+                let mut arg_index = 0;
+                $(
+                    let $arg: $arg = adapter.get_arg(arg_index);
+                    arg_index += 1;
+                )*
+                self($($arg),*).return_to(adapter);
+            }
+        }
+    };
+}
+
+impl_wrap_fn!();
+impl_wrap_fn!(A1);
+impl_wrap_fn!(A1, A2);
+impl_wrap_fn!(A1, A2, A3);
+impl_wrap_fn!(A1, A2, A3, A4);
+impl_wrap_fn!(A1, A2, A3, A4, A5);
+impl_wrap_fn!(A1, A2, A3, A4, A5, A6);
@@ -8,4 +8,7 @@ pub mod module;
 pub mod adapter;
 
 /// Contains the container API
-pub mod container;
\ No newline at end of file
+pub mod container;
+
+/// Contains the typed script function call API
+pub mod call;
\ No newline at end of file
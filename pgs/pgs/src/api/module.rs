@@ -8,14 +8,80 @@ use crate::{
     api::{
         function::{
             Function
+        },
+        container::{
+            Container,
+            PgsContainer,
+            PgsValue
+        },
+        adapter::{
+            ObjectAdapter,
+            adapter_container
         }
     }
 };
 
+/// Value of a module level constant, e.g. "std::math::PI"
+#[derive(PartialEq, Debug, Clone)]
+pub enum Constant {
+    Int(i64),
+    Float(f32),
+    Double(f64),
+    Bool(bool),
+    Char(char),
+    String(String)
+}
+
+impl From<i64> for Constant {
+    fn from(value: i64) -> Constant {
+        Constant::Int(value)
+    }
+}
+
+impl From<f32> for Constant {
+    fn from(value: f32) -> Constant {
+        Constant::Float(value)
+    }
+}
+
+impl From<f64> for Constant {
+    fn from(value: f64) -> Constant {
+        Constant::Double(value)
+    }
+}
+
+impl From<bool> for Constant {
+    fn from(value: bool) -> Constant {
+        Constant::Bool(value)
+    }
+}
+
+impl From<char> for Constant {
+    fn from(value: char) -> Constant {
+        Constant::Char(value)
+    }
+}
+
+impl From<String> for Constant {
+    fn from(value: String) -> Constant {
+        Constant::String(value)
+    }
+}
+
+impl From<&str> for Constant {
+    fn from(value: &str) -> Constant {
+        Constant::String(String::from(value))
+    }
+}
+
+#[derive(Clone)]
 pub struct Module {
     pub name: String,
     pub functions: HashMap<String, Function>,
-    pub modules: HashMap<String, Module>
+    pub modules: HashMap<String, Module>,
+    pub containers: HashMap<String, Container>,
+    /// Constants scripts read by path, they can not be assigned to
+    pub constants: HashMap<String, Constant>
 }
 
 impl Module {
@@ -25,7 +91,9 @@ impl Module {
         Module {
             name: name,
             functions: HashMap::new(),
-            modules: HashMap::new()
+            modules: HashMap::new(),
+            containers: HashMap::new(),
+            constants: HashMap::new()
         }
     }
 
@@ -38,4 +106,31 @@ impl Module {
         self.modules.insert(module.name.clone(), module);
         self
     }
+
+    pub fn with_container(mut self, container: Container) -> Module {
+        self.containers.insert(container.name.clone(), container);
+        self
+    }
+
+    /// ...with a Rust type exposed as a container, see Engine::register_container()
+    pub fn with_container_type<T: PgsContainer>(self) -> Module {
+        self.with_container(T::container())
+    }
+
+    /// ...with a Rust type passed by value as a container, see Engine::register_value()
+    pub fn with_value_type<T: PgsValue>(self) -> Module {
+        self.with_container(T::value_container())
+    }
+
+    /// ...with a host type scripts call methods on through handles, see Engine::register_adapter()
+    pub fn with_adapter_type<T: ObjectAdapter>(self) -> Module {
+        self.with_container(adapter_container::<T>())
+    }
+
+    /// ...with a constant, e.g. Module::new("math").with_constant("PI", std::f64::consts::PI)
+    pub fn with_constant<T, C>(mut self, name: T, value: C) -> Module
+    where String: From<T>, C: Into<Constant> {
+        self.constants.insert(String::from(name), value.into());
+        self
+    }
 }
\ No newline at end of file
@@ -87,12 +87,20 @@ impl Builder {
         self.data.append(&mut data);
     }
 
+    /// Encodes the instructions. Jump targets are offsets into the instructions while building,
+    /// they are encoded relative to their jump, so the code runs wherever it is placed.
     pub fn build(mut self) -> Vec<u8> {
         let mut code = Vec::new();
 
         code.append(&mut self.data);
 
-        for instruction in self.instructions {
+        let mut offset = 0;
+        for mut instruction in self.instructions {
+            if let Some(pos) = instruction.opcode.get_jump_target_pos() {
+                let target: u64 = instruction.get_operand(pos, 8);
+                instruction.set_operand::<i64>(pos, target as i64 - offset as i64);
+            }
+            offset += instruction.get_size();
             let mut instr_code = instruction.get_code();
             code.append(&mut instr_code);
         }
@@ -100,7 +108,7 @@ impl Builder {
         code
     }
 
-    pub fn get_label_offset(&mut self, label: &String) -> Option<usize> {
+    pub fn get_label_offset(&self, label: &String) -> Option<usize> {
         let mut code_before_size = 0;
         let label_instr_offset = self.labels.get(label)
             .or(None)?;
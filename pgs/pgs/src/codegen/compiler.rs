@@ -1,7 +1,14 @@
 use crate::{
     api::{
-        module::Module,
-        function::Function
+        module::{
+            Module,
+            Constant
+        },
+        function::Function,
+        container::{
+            Container,
+            ContainerMember
+        }
     },
     codegen::{
         context::{
@@ -13,13 +20,18 @@ use crate::{
         uid_generator::UIDGenerator,
         def::{
             ContainerDef,
-            FunctionDef
+            EnumDef,
+            FunctionDef,
+            GenericFunctionDef,
+            InterfaceDef,
+            StaticDef
         },
         builder::{
             Builder
         },
         register::{
-            Register
+            Register,
+            RegisterAllocator
         },
         instruction::{
             Instruction
@@ -28,7 +40,21 @@ use crate::{
             Data
         },
         program::{
-            Program
+            Program,
+            GlobalDef,
+            SourcePosition
+        },
+        optimizer::{
+            ConstantPropagator,
+            fold_expr
+        },
+        debug::{
+            DebugInfo,
+            StatementInfo,
+            VariableInfo
+        },
+        fusion::{
+            fuse_instructions
         }
     },
     parser::{
@@ -37,7 +63,10 @@ use crate::{
             Statement,
             Type,
             Expression,
-            IfStatementArgs
+            IfStatementArgs,
+            MatchStatementArgs,
+            VariableDeclArgs,
+            FunctionDeclArgs
         }
     },
     vm::{
@@ -47,7 +76,11 @@ use crate::{
     }
 };
 
+use bincode::serialize;
+
 use std::{
+    cell::RefCell,
+    convert::TryFrom,
     fmt::{
         Display,
         Result as FmtResult,
@@ -64,7 +97,12 @@ use std::{
         DerefMut
     },
     collections::{
-        BTreeMap
+        BTreeMap,
+        BTreeSet
+    },
+    mem::{
+        take,
+        replace
     }
 };
 
@@ -94,7 +132,39 @@ pub enum CompilerError {
     TypeMismatch(Type, Type),
     CannotDerefNonPointer,
     CannotDerefSlice,
-    RegisterMapping
+    RegisterMapping,
+    NotConstant(Expression),
+    StaticAssertion(String),
+    NotIterable(Type),
+    NotIndexable(Type),
+    NotMatchable(Type),
+    DuplicateMatchArm(Expression),
+    InvalidCast(Type, Type),
+    NotATuple(Type),
+    TupleIndexOutOfBounds(Type, usize),
+    TupleArityMismatch(Type, usize),
+    DuplicateEnum(String),
+    UnknownEnum(String),
+    UnknownEnumVariant(String, String),
+    InvalidStaticType(Type),
+    NotAResult(Type),
+    CannotInferType(String),
+    TypeArgumentMismatch(String, usize),
+    InfiniteSize(String),
+    DuplicateInterface(String),
+    UnknownInterface(String),
+    InterfaceMismatch(String, String),
+    UnsizedType(Type),
+    PrivateItem(String),
+    AssignToConstant(String),
+    UnresolvedFileImport(String),
+    UnresolvedModuleFile(String),
+    /// Only script functions without reference arguments can be spawned
+    InvalidSpawn(String),
+    /// Coroutine functions can not be foreign, member functions or take references
+    InvalidCoroutine(String),
+    /// Values can only be yielded by coroutine functions
+    InvalidYield
 }
 
 impl Display for CompilerError {
@@ -108,6 +178,217 @@ impl Error for CompilerError {}
 /// Convenience type for Results returned by a compilation process
 pub type CompilerResult<T> = Result<T, CompilerError>;
 
+/// Gets the type two different numeric types are promoted to in a binary operation, the wider one.
+/// Ints are promoted to floats and doubles, floats to doubles.
+fn promote_numeric(lhs_type: &Type, rhs_type: &Type) -> Option<Type> {
+    let rank = |num_type: &Type| {
+        match num_type {
+            Type::Int => Some(0),
+            Type::Float => Some(1),
+            Type::Double => Some(2),
+            _ => None
+        }
+    };
+    if rank(lhs_type)? >= rank(rhs_type)? {
+        Some(lhs_type.clone())
+    } else {
+        Some(rhs_type.clone())
+    }
+}
+
+/// Checks if an expression results in a value that already holds its own string references.
+/// Script functions count a reference for each returned string, foreign ones return fresh copies.
+/// Concatenations result in a new string as well.
+fn is_call_result(expr: &Expression) -> bool {
+    match expr {
+        Expression::Call(_, _) |
+        Expression::Addition(_, _) => true,
+        Expression::MemberAccess(_, rhs_expr) => is_call_result(rhs_expr),
+        _ => false
+    }
+}
+
+/// Returns the opcode loading a register type value from memory, None for other types
+fn get_load_opcode(value_type: &Type) -> Option<Opcode> {
+    match value_type {
+        Type::Int | Type::Enum(_) | Type::Coroutine(_) => Some(Opcode::MOVI_AR),
+        Type::Float => Some(Opcode::MOVF_AR),
+        Type::Double => Some(Opcode::MOVD_AR),
+        Type::Bool => Some(Opcode::MOVB_AR),
+        Type::Char => Some(Opcode::MOVC_AR),
+        Type::Reference(inner_type) => {
+            match inner_type.deref() {
+                Type::AutoArray(_) => None,
+                Type::Interface(_) => None,
+                _ => Some(Opcode::MOVA_AR)
+            }
+        },
+        _ => None
+    }
+}
+
+/// Returns the bytes of a literal of the given type as it is laid out in memory, None for other expressions
+fn get_literal_bytes(expr: &Expression, value_type: &Type) -> Option<Vec<u8>> {
+    let bytes = match (expr, value_type) {
+        (Expression::IntLiteral(int), Type::Int) => serialize(int),
+        (Expression::FloatLiteral(float), Type::Float) => serialize(float),
+        (Expression::DoubleLiteral(double), Type::Double) => serialize(double),
+        (Expression::BoolLiteral(boolean), Type::Bool) => serialize(boolean),
+        (Expression::CharLiteral(character), Type::Char) => serialize(&(*character as u32)),
+        _ => return None
+    };
+    bytes.ok()
+}
+
+/// Binds the type parameters used in a parameter type to the matching parts of an argument type.
+/// Parts that don't match are left to the argument type check of the call.
+/// Instances of generic containers are looked up in instances, by canonical name.
+fn bind_type_params(generics: &[String], param_type: &Type, arg_type: &Type, type_params: &mut HashMap<String, Type>, instances: &HashMap<String, ContainerDef>) -> CompilerResult<()> {
+    match (param_type, arg_type) {
+        (_, Type::Null) => {},
+        (Type::Other(type_name), _) if generics.contains(type_name) => {
+            match type_params.get(type_name) {
+                Some(bound_type) if bound_type != arg_type => {
+                    return Err(CompilerError::TypeMismatch(bound_type.clone(), arg_type.clone()));
+                },
+                Some(_) => {},
+                None => {
+                    type_params.insert(type_name.clone(), arg_type.clone());
+                }
+            };
+        },
+        (Type::Reference(param_inner), Type::Reference(arg_inner)) |
+        (Type::Optional(param_inner), Type::Optional(arg_inner)) |
+        (Type::Result(param_inner), Type::Result(arg_inner)) |
+        (Type::Coroutine(param_inner), Type::Coroutine(arg_inner)) |
+        (Type::AutoArray(param_inner), Type::AutoArray(arg_inner)) |
+        (Type::Array(param_inner, _), Type::Array(arg_inner, _)) => {
+            bind_type_params(generics, param_inner, arg_inner, type_params, instances)?;
+        },
+        (Type::Optional(param_inner), _) |
+        (Type::Result(param_inner), _) => {
+            bind_type_params(generics, param_inner, arg_type, type_params, instances)?;
+        },
+        (Type::Tuple(param_types), Type::Tuple(arg_types)) => {
+            for (param_type, arg_type) in param_types.iter().zip(arg_types.iter()) {
+                bind_type_params(generics, param_type, arg_type, type_params, instances)?;
+            }
+        },
+        (Type::Generic(_, param_types), Type::Other(instance_name)) => {
+            if let Some(cont_def) = instances.get(instance_name) {
+                for (param_type, arg_type) in param_types.iter().zip(cont_def.type_args.iter()) {
+                    bind_type_params(generics, param_type, arg_type, type_params, instances)?;
+                }
+            }
+        },
+        (Type::Function(param_args, param_ret), Type::Function(arg_args, arg_ret)) => {
+            for (param_type, arg_type) in param_args.iter().zip(arg_args.iter()) {
+                bind_type_params(generics, param_type, arg_type, type_params, instances)?;
+            }
+            bind_type_params(generics, param_ret, arg_ret, type_params, instances)?;
+        },
+        _ => {}
+    };
+    Ok(())
+}
+
+/// Replaces the bound type parameters in a type with their concrete types
+fn substitute_type_params(var_type: &Type, type_params: &HashMap<String, Type>) -> Type {
+    let substitute = |inner_type: &Type| Box::new(substitute_type_params(inner_type, type_params));
+    match var_type {
+        Type::Other(type_name) if type_params.contains_key(type_name) => type_params[type_name].clone(),
+        Type::Reference(inner_type) => Type::Reference(substitute(inner_type)),
+        Type::Optional(inner_type) => Type::Optional(substitute(inner_type)),
+        Type::Result(inner_type) => Type::Result(substitute(inner_type)),
+        Type::Coroutine(inner_type) => Type::Coroutine(substitute(inner_type)),
+        Type::AutoArray(inner_type) => Type::AutoArray(substitute(inner_type)),
+        Type::Array(inner_type, size) => Type::Array(substitute(inner_type), *size),
+        Type::Tuple(elem_types) => Type::Tuple(
+            elem_types.iter()
+                .map(|elem_type| substitute_type_params(elem_type, type_params))
+                .collect()
+        ),
+        Type::Function(arg_types, ret_type) => Type::Function(
+            arg_types.iter()
+                .map(|arg_type| substitute_type_params(arg_type, type_params))
+                .collect(),
+            substitute(ret_type)
+        ),
+        Type::Generic(cont_name, type_args) => Type::Generic(
+            cont_name.clone(),
+            type_args.iter()
+                .map(|type_arg| substitute_type_params(type_arg, type_params))
+                .collect()
+        ),
+        _ => var_type.clone()
+    }
+}
+
+/// Prefixes the names of named types in a type with a module path, unless they are absolute
+fn qualify_type_names(var_type: &mut Type, mod_path: &str) {
+    match var_type {
+        Type::Other(type_name) if !type_name.starts_with("root::") => {
+            *type_name = format!("{}{}", mod_path, type_name);
+        },
+        Type::Reference(inner_type) |
+        Type::Optional(inner_type) |
+        Type::Result(inner_type) |
+        Type::Coroutine(inner_type) |
+        Type::AutoArray(inner_type) |
+        Type::Array(inner_type, _) => qualify_type_names(inner_type, mod_path),
+        Type::Tuple(elem_types) => {
+            for elem_type in elem_types.iter_mut() {
+                qualify_type_names(elem_type, mod_path);
+            }
+        },
+        _ => {}
+    };
+}
+
+/// Replaces the short name of a generic container in a type with its canonical name
+fn qualify_generic_name(var_type: &mut Type, cont_name: &str, canonical_name: &str) {
+    match var_type {
+        Type::Generic(type_name, type_args) => {
+            if type_name == cont_name {
+                *type_name = canonical_name.to_string();
+            }
+            for type_arg in type_args.iter_mut() {
+                qualify_generic_name(type_arg, cont_name, canonical_name);
+            }
+        },
+        Type::Reference(inner_type) |
+        Type::Optional(inner_type) |
+        Type::Result(inner_type) |
+        Type::Coroutine(inner_type) |
+        Type::AutoArray(inner_type) |
+        Type::Array(inner_type, _) => qualify_generic_name(inner_type, cont_name, canonical_name),
+        Type::Tuple(elem_types) => {
+            for elem_type in elem_types.iter_mut() {
+                qualify_generic_name(elem_type, cont_name, canonical_name);
+            }
+        },
+        _ => {}
+    };
+}
+
+/// Gets the start and the exclusive end of an expression of the range type
+fn get_range_bounds(range_expr: &Expression) -> (Expression, Expression) {
+    match range_expr {
+        Expression::Range(start_expr, end_expr, inclusive) => {
+            let end_expr = if *inclusive {
+                Expression::Addition(end_expr.clone(), Box::new(Expression::IntLiteral(1)))
+            } else {
+                end_expr.deref().clone()
+            };
+            (start_expr.deref().clone(), end_expr)
+        },
+        _ => {
+            let range_expr = Box::new(range_expr.clone());
+            (Expression::TupleIndex(range_expr.clone(), 0), Expression::TupleIndex(range_expr, 1))
+        }
+    }
+}
+
 /// The compiler
 pub struct Compiler {
     fn_context_stack: VecDeque<FunctionContext>,
@@ -119,7 +400,27 @@ pub struct Compiler {
     uid_generator: UIDGenerator,
     builder: Builder,
     current_cont: Option<String>,
-    data: Data
+    data: Data,
+    debug_info: DebugInfo,
+    lambda_count: usize,
+    /// Concrete types of the type parameters of the generic function instance being compiled
+    type_params: HashMap<String, Type>,
+    /// Definitions of functions compiled in place (lambdas and generic instances) by full name
+    inline_fn_defs: HashMap<String, FunctionDef>,
+    /// Instances of generic containers by canonical name, created while resolving types
+    container_instances: RefCell<HashMap<String, ContainerDef>>,
+    /// Canonical names of the containers whose size is being computed
+    layout_containers: RefCell<HashSet<String>>,
+    /// Types of the locals of the block expressions being type checked, innermost last
+    block_var_types: RefCell<Vec<HashMap<String, Type>>>,
+    /// Paths of the modules items are being resolved as seen from, innermost last
+    visibility_scopes: RefCell<Vec<String>>,
+    /// If set, operands of binary operations need the same numeric type instead of being promoted
+    strict_numeric: bool,
+    /// If set, locals reference count the heap strings they hold and release them when going out of scope
+    ref_counting: bool,
+    /// If set, get_program() fuses common instruction pairs into superinstructions
+    instruction_fusion: bool
 }
 
 impl Compiler {
@@ -138,8 +439,63 @@ impl Compiler {
             uid_generator: UIDGenerator::new(),
             builder: Builder::new(),
             current_cont: None,
-            data: Data::new()
+            data: Data::new(),
+            debug_info: DebugInfo::new(),
+            lambda_count: 0,
+            type_params: HashMap::new(),
+            inline_fn_defs: HashMap::new(),
+            container_instances: RefCell::new(HashMap::new()),
+            layout_containers: RefCell::new(HashSet::new()),
+            block_var_types: RefCell::new(Vec::new()),
+            visibility_scopes: RefCell::new(Vec::new()),
+            strict_numeric: false,
+            ref_counting: false,
+            instruction_fusion: true
+        }
+    }
+
+    /// Disables the promotion of mixed numeric operands, e.g. "1 + 2.5" becomes a type mismatch
+    pub fn set_strict_numeric(&mut self, strict_numeric: bool) {
+        self.strict_numeric = strict_numeric;
+    }
+
+    pub fn is_strict_numeric(&self) -> bool {
+        self.strict_numeric
+    }
+
+    /// Emits INCREF and DECREF instructions, so heap strings are freed once no local holds them anymore.
+    /// Strings put into optionals, results, closures, statics or through references are never freed.
+    pub fn set_ref_counting(&mut self, ref_counting: bool) {
+        self.ref_counting = ref_counting;
+    }
+
+    pub fn is_ref_counting(&self) -> bool {
+        self.ref_counting
+    }
+
+    /// Fuses common instruction pairs into superinstructions, see codegen::fusion. Enabled by default.
+    pub fn set_instruction_fusion(&mut self, instruction_fusion: bool) {
+        self.instruction_fusion = instruction_fusion;
+    }
+
+    pub fn is_instruction_fusion(&self) -> bool {
+        self.instruction_fusion
+    }
+
+    /// Retrieves the debug info of the compiled program, with offsets matching get_program()
+    pub fn get_debug_info(&self) -> CompilerResult<DebugInfo> {
+        let mut debug_info = self.debug_info.clone();
+        for (fn_name, fn_uid) in self.fn_uid_map.iter() {
+            if self.is_function_foreign(*fn_uid)? {
+                continue;
+            }
+            let fn_offset = self.builder.get_label_offset(fn_name)
+                .ok_or(CompilerError::Unknown)?;
+            debug_info.functions.insert(fn_offset, fn_name.clone());
         }
+        Ok(
+            debug_info.with_offset(self.data.bytes.len())
+        )
     }
 
     /// Retrieves a reference to the underlying builder
@@ -149,24 +505,15 @@ impl Compiler {
 
     /// Retrieves the program instance compiled by this compiler instance.
     pub fn get_program(&mut self) -> CompilerResult<Program> {
-        let mut builder = self.builder.clone();
+        // Done in place, so the builder and the debug info match the program
+        if self.instruction_fusion {
+            fuse_instructions(&mut self.builder, &mut self.debug_info);
+        }
+
+        let builder = self.builder.clone();
         let data = self.data.clone();
         let data_len = data.bytes.len();
 
-        // Modify target jump addresses of JMP instructions accordingly 
-        for offset in builder.jmp_instructions.clone().iter() {
-            let instr = builder.get_instr(offset)
-                .ok_or(CompilerError::Unknown)?;
-            let addr: u64 = match instr.opcode {
-                Opcode::JMP => instr.get_operand(0, 8),
-                Opcode::JMPF => instr.get_operand(1, 8),
-                Opcode::JMPT => instr.get_operand(1, 8),
-                _ => return Err(CompilerError::Unknown)
-            };
-            instr.remove_operand_bytes(8);
-            instr.append_operand(addr + data_len as u64);
-        }
-
         let mut functions: HashMap<u64, usize> = HashMap::new();
 
         // correctly set function offsets
@@ -182,6 +529,27 @@ impl Compiler {
         let foreign_functions = self.foreign_functions.take()
             .ok_or(CompilerError::Unknown)?;
 
+        let lines = self.debug_info.statements.iter()
+            .map(|(stmt_offset, stmt_info)| {
+                let position = SourcePosition {
+                    line: stmt_info.line,
+                    column: stmt_info.column
+                };
+                (stmt_offset + data_len, position)
+            })
+            .collect();
+
+        let globals = self.get_static_defs()?
+            .into_iter()
+            .map(|(name, static_def)| {
+                let global_def = GlobalDef {
+                    address: static_def.address,
+                    var_type: static_def.var_type,
+                    constant: static_def.constant
+                };
+                (name, global_def)
+            })
+            .collect();
 
         let mut code = data.bytes;
         let mut builder_code = builder.build();
@@ -190,8 +558,13 @@ impl Compiler {
 
         let program = Program::new()
             .with_code(code)
+            .with_data_size(data_len)
             .with_functions(functions)
-            .with_foreign_functions(foreign_functions);
+            .with_function_uids(self.fn_uid_map.clone())
+            .with_foreign_functions(foreign_functions)
+            .with_lines(lines)
+            .with_globals(globals)
+            .with_data_pointers(data.pointers);
         
         Ok(program)
     }
@@ -208,6 +581,48 @@ impl Compiler {
         ret
     }
 
+    /// Checks if an item of the module at the given path, with trailing "::", is visible in the current module.
+    /// Private items are only visible in their module and its submodules.
+    /// Inside resolve_in_scope(), visibility is checked from the module of the scope instead.
+    pub fn check_visibility(&self, mod_path: &str, name: &str, public: bool) -> CompilerResult<()> {
+        if public {
+            return Ok(());
+        }
+        let visible = match self.visibility_scopes.borrow().last() {
+            Some(scope_path) => scope_path.starts_with(mod_path),
+            None => self.get_module_path().starts_with(mod_path)
+        };
+        if visible {
+            return Ok(());
+        }
+        Err(CompilerError::PrivateItem(format!("{}{}", mod_path, name)))
+    }
+
+    /// Resolves an item as seen from the module at the given path, e.g. one re-exported by that module.
+    /// A module can re-export items of its private submodules.
+    fn resolve_in_scope<T, F>(&self, scope_path: &str, resolve: F) -> CompilerResult<T>
+        where F: FnOnce() -> CompilerResult<T> {
+        self.visibility_scopes.borrow_mut().push(String::from(scope_path));
+        let resolve_res = resolve();
+        self.visibility_scopes.borrow_mut().pop();
+        resolve_res
+    }
+
+    /// Gets the full name of a function or constant of the current module or impl block.
+    /// Items of impls of containers of other modules are named after the container.
+    pub fn get_full_name(&self, name: &str) -> String {
+        let mut full_name = match self.current_cont.as_ref() {
+            Some(cont_name) if cont_name.starts_with("root::") => String::new(),
+            _ => self.get_module_path()
+        };
+        if let Some(cont_name) = self.current_cont.as_ref() {
+            full_name += cont_name;
+            full_name += "::";
+        }
+        full_name += name;
+        full_name
+    }
+
     /// Gets the current module context (the one at the top of the stack)
     pub fn get_current_module(&self) -> CompilerResult<&ModuleContext> {
         self.mod_context_stack.get(0)
@@ -319,26 +734,145 @@ impl Compiler {
             .ok_or(CompilerError::UnknownFunction(name.clone()))
     }
 
+    /// Gets the uids of all functions by full name
+    pub fn get_function_uids(&self) -> &HashMap<String, u64> {
+        &self.fn_uid_map
+    }
+
+    /// Reuses the given function uids for functions of the same name compiled later on
+    pub fn reserve_function_uids(&mut self, fn_uids: &HashMap<String, u64>) {
+        for (fn_name, fn_uid) in fn_uids.iter() {
+            self.uid_generator.reserve_function_uid(fn_name, *fn_uid);
+        }
+    }
+
+    /// Gets the definitions of all compiled, non-foreign functions by full name
+    pub fn get_function_defs(&self) -> CompilerResult<BTreeMap<String, FunctionDef>> {
+        let mut fn_defs = BTreeMap::new();
+        for (fn_name, fn_uid) in self.fn_uid_map.iter() {
+            if self.is_function_foreign(*fn_uid)? {
+                continue;
+            }
+            fn_defs.insert(fn_name.clone(), self.get_function_def(fn_name)?);
+        }
+        Ok(fn_defs)
+    }
+
+    /// Gets the definitions of all statics of modules by canonical name, including constants of foreign modules
+    pub fn get_static_defs(&self) -> CompilerResult<BTreeMap<String, StaticDef>> {
+        let mut static_defs = BTreeMap::new();
+        let mut mod_ctxs = vec![self.get_root_module()?];
+        while let Some(mod_ctx) = mod_ctxs.pop() {
+            for static_def in mod_ctx.statics.values() {
+                static_defs.insert(static_def.canonical_name.clone(), static_def.clone());
+            }
+            mod_ctxs.extend(mod_ctx.modules.values());
+        }
+        Ok(static_defs)
+    }
+
+    /// Gets the definition of a compiled function by full name, e.g. "root::update"
+    pub fn get_function_def(&self, name: &str) -> CompilerResult<FunctionDef> {
+        if let Some(fn_def) = self.inline_fn_defs.get(name) {
+            return Ok(fn_def.clone());
+        }
+        self.find_function_by_full_name(name)
+    }
+
+    /// Finds a declared function by its full name, e.g. "root::geo::Point::new".
+    /// Unlike resolve_function, this does not depend on the current module or visibility.
+    fn find_function_by_full_name(&self, full_fn_name: &str) -> CompilerResult<FunctionDef> {
+        let unknown_fn = || CompilerError::UnknownFunction(full_fn_name.to_string());
+        let path_fragments: Vec<&str> = full_fn_name.split("::").skip(1).collect();
+        let (fn_name, mod_names) = path_fragments.split_last()
+            .ok_or_else(unknown_fn)?;
+        let mut mod_ctx = self.get_root_module()?;
+        for (i, mod_name) in mod_names.iter().enumerate() {
+            if let Some(child_mod_ctx) = mod_ctx.modules.get(*mod_name) {
+                mod_ctx = child_mod_ctx;
+            } else if i == mod_names.len() - 1 {
+                let cont_def = mod_ctx.get_container(&mod_name.to_string())?;
+                return cont_def.get_member_function(&fn_name.to_string())
+                    .cloned();
+            } else {
+                return Err(CompilerError::UnknownModule(mod_name.to_string()));
+            }
+        }
+        mod_ctx.get_function(&fn_name.to_string())
+            .cloned()
+    }
+
+    /// Expands a path that does not start at a module or container of the current module.
+    /// The first fragment is looked up in the current modules imports, then in the root module
+    /// and finally in the modules imported by wildcard.
+    pub fn expand_module_path(&self, name: &String) -> CompilerResult<Option<String>> {
+        let path_fragments: Vec<&str> = name.splitn(2, "::").collect();
+        let first = String::from(path_fragments[0]);
+        if first == "root" || first == "super" || path_fragments.len() < 2 {
+            return Ok(None);
+        }
+
+        let mod_ctx = self.get_current_module()?;
+        if mod_ctx.modules.contains_key(&first) || mod_ctx.containers.contains_key(&first) {
+            return Ok(None);
+        }
+
+        if let Some(import_path) = mod_ctx.imports.get(&first) {
+            // Guard against imports that alias themselves, e.g. "import extlib;"
+            if import_path.split("::").next() != Some(path_fragments[0]) {
+                return Ok(Some(format!("{}::{}", import_path, path_fragments[1])));
+            }
+        }
+
+        if self.mod_context_stack.len() > 1 && self.get_root_module()?.modules.contains_key(&first) {
+            return Ok(Some(format!("root::{}", name)));
+        }
+
+        // Containers brought into scope by a wildcard import.
+        // Only wildcards starting at a known module are considered, so this can not recurse.
+        let root_mod_ctx = self.get_root_module()?;
+        for wildcard_path in mod_ctx.wildcard_imports.iter() {
+            let wildcard_first = wildcard_path.split("::").next().unwrap_or("");
+            if wildcard_first != "root" &&
+                !mod_ctx.modules.contains_key(wildcard_first) &&
+                !root_mod_ctx.modules.contains_key(wildcard_first) {
+                continue;
+            }
+            if self.resolve_container(&format!("{}{}", wildcard_path, first)).is_ok() {
+                return Ok(Some(format!("{}{}", wildcard_path, name)));
+            }
+        }
+
+        Ok(None)
+    }
+
     /// Resolves a function by name to a FunctionDef
     pub fn resolve_function(&self, name: &String) -> CompilerResult<FunctionDef> {
         //println!("Resolving function: {}", name);
+        if let Some(expanded_name) = self.expand_module_path(name)? {
+            return self.resolve_function(&expanded_name);
+        }
         if name.contains("::") {
             let path_fragments: Vec<String> = name.split("::").map(|s| String::from(s)).collect();
             let mut mod_ctx_opt = None;
             let mut cont_def_opt = None;
             let mut start_i = 0;
+            let mut mod_path;
             if path_fragments[0] == "root" {
                 start_i = 1;
                 mod_ctx_opt = Some(self.get_root_module()?);
+                mod_path = String::from("root::");
             } else if path_fragments[0] == "super" {
                 start_i = 1;
                 return Err(CompilerError::Unimplemented(format!("Blub")));
             } else {
                 mod_ctx_opt = Some(self.get_current_module()?);
+                mod_path = self.get_module_path();
             }
 
             for i in start_i..path_fragments.len() - 1 {
-                let mod_ctx = mod_ctx_opt.unwrap();
+                let mod_ctx = mod_ctx_opt
+                    .ok_or_else(|| CompilerError::UnknownModule(path_fragments[i - 1].clone()))?;
                 if mod_ctx.containers.contains_key(&path_fragments[i]) {
                     //println!("Function is in container {}", &path_fragments[i]);
                     if i != path_fragments.len() - 2 {
@@ -346,11 +880,18 @@ impl Compiler {
                         //println!("{:?}", path_fragments);
                         return Err(CompilerError::InvalidModulePath(name.clone()));
                     }
-                    cont_def_opt = Some(mod_ctx.get_container(&path_fragments[i])?);
+                    let cont_def = mod_ctx.get_container(&path_fragments[i])?;
+                    self.check_visibility(&mod_path, &cont_def.name, cont_def.public)?;
+                    cont_def_opt = Some(cont_def);
                     break;
                 }
                 //println!("Blub");
                 mod_ctx_opt = mod_ctx.modules.get(&path_fragments[i]);
+                if let Some(child_mod_ctx) = mod_ctx_opt {
+                    self.check_visibility(&mod_path, &path_fragments[i], child_mod_ctx.public)?;
+                }
+                mod_path += &path_fragments[i];
+                mod_path += "::";
             }
 
             let last_path = path_fragments.last().unwrap();
@@ -358,17 +899,25 @@ impl Compiler {
             //println!("Resolving function {} for mod_ctx {}", last_path, mod_ctx_opt.as_ref().unwrap().name);
             if cont_def_opt.is_some() {
                 let cont_def = cont_def_opt.unwrap();
-                return Ok(
-                    cont_def.get_member_function(last_path)?
-                        .clone()
-                )
+                let fn_def = cont_def.get_member_function(last_path)?;
+                let member_name = format!("{}::{}", cont_def.name, fn_def.name);
+                self.check_visibility(cont_def.get_impl_path(last_path), &member_name, fn_def.public)?;
+                return Ok(fn_def.clone());
             } else {
                 //println!("Resolved {}. Was in module!", name);
-                let mod_ctx = mod_ctx_opt.unwrap();
+                let mod_ctx = mod_ctx_opt
+                    .ok_or_else(|| CompilerError::UnknownModule(path_fragments[path_fragments.len() - 2].clone()))?;
                 //println!("Blub");
-                return mod_ctx.functions.get(last_path)
-                    .cloned()
-                    .ok_or(CompilerError::UnknownFunction(name.clone()));
+                if let Some(fn_def) = mod_ctx.functions.get(last_path) {
+                    self.check_visibility(&mod_path, last_path, fn_def.public)?;
+                    return Ok(fn_def.clone());
+                }
+                for export_path in mod_ctx.get_export_paths(&mod_path, last_path).iter() {
+                    if let Ok(fn_def) = self.resolve_in_scope(&mod_path, || self.resolve_function(export_path)) {
+                        return Ok(fn_def);
+                    }
+                }
+                return Err(CompilerError::UnknownFunction(name.clone()));
             }
         } else {
             let mod_ctx = self.get_current_module()?;
@@ -382,6 +931,11 @@ impl Compiler {
                     .ok_or(CompilerError::Unknown)?;
                 return self.resolve_function(import_path);
             }
+            for wildcard_path in mod_ctx.wildcard_imports.iter() {
+                if let Ok(fn_def) = self.resolve_function(&format!("{}{}", wildcard_path, name)) {
+                    return Ok(fn_def);
+                }
+            }
 
             return Err(CompilerError::UnknownFunction(name.clone()));
         }
@@ -389,35 +943,135 @@ impl Compiler {
 
     /// Resolves a container by name to a ContainerDef
     pub fn resolve_container(&self, name: &String) -> CompilerResult<ContainerDef> {
+        // Canonical names come from types, which were checked for visibility when they were canonized
+        let mut cont_def = if name.starts_with("root::") {
+            self.resolve_in_scope(name, || self.find_container(name))?
+        } else {
+            self.find_container(name)?
+        };
+        // Members of generic containers only have types in instances
+        if !cont_def.generics.is_empty() {
+            return Ok(cont_def);
+        }
+        let mod_path = cont_def.get_module_path().to_string();
+        // Member types are seen from the module of the container
+        self.resolve_in_scope(&mod_path, || {
+            for member_type in cont_def.member_variables.values_mut() {
+                // Members of instances of generic containers are instantiated on use
+                if member_type.has_type_args() {
+                    self.canonize_type(member_type)?;
+                } else {
+                    self.canonize_member_type(member_type, &mod_path)?;
+                }
+            }
+            Ok(())
+        })?;
+        Ok(cont_def)
+    }
+
+    /// Resolves the container holding the member functions of a type, or a reference to it.
+    /// Impls of builtin types are looked up in the current module, then in the root module.
+    pub fn resolve_impl_container(&self, impl_type: &Type) -> CompilerResult<ContainerDef> {
+        match impl_type {
+            Type::Other(cont_name) => self.resolve_container(cont_name),
+            Type::Int |
+            Type::Float |
+            Type::Double |
+            Type::Bool |
+            Type::Char |
+            Type::String => {
+                let type_name = impl_type.to_string();
+                self.resolve_container(&type_name)
+                    .or_else(|_| self.resolve_container(&format!("root::{}", type_name)))
+                    .map_err(|_| CompilerError::MemberAccessOnNonContainer)
+            },
+            Type::Reference(inner_type) if !matches!(inner_type.deref(), Type::Reference(_)) => {
+                self.resolve_impl_container(inner_type)
+            },
+            _ => Err(CompilerError::MemberAccessOnNonContainer)
+        }
+    }
+
+    /// Canonizes the type of a container member.
+    /// Names are resolved in the module of the container first, then in the current module.
+    fn canonize_member_type(&self, member_type: &mut Type, mod_path: &str) -> CompilerResult<()> {
+        let mut qualified_type = member_type.clone();
+        qualify_type_names(&mut qualified_type, mod_path);
+        if self.canonize_type(&mut qualified_type).is_ok() {
+            *member_type = qualified_type;
+            return Ok(());
+        }
+        self.canonize_type(member_type)
+    }
+
+    /// Marks a container as being laid out.
+    /// Fails if it already is, as the container then contains itself and has no finite size.
+    pub fn begin_layout(&self, canonical_name: &str) -> CompilerResult<()> {
+        if !self.layout_containers.borrow_mut().insert(canonical_name.to_string()) {
+            return Err(CompilerError::InfiniteSize(canonical_name.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Marks a container as laid out
+    pub fn end_layout(&self, canonical_name: &str) {
+        self.layout_containers.borrow_mut().remove(canonical_name);
+    }
+
+    /// Finds a container definition by name, as it was declared or instantiated
+    fn find_container(&self, name: &String) -> CompilerResult<ContainerDef> {
+        if let Some(cont_def) = self.container_instances.borrow().get(name) {
+            return Ok(cont_def.clone());
+        }
         //println!("Resolving container by name {}", name);
+        if let Some(expanded_name) = self.expand_module_path(name)? {
+            return self.resolve_container(&expanded_name);
+        }
         if name.contains("::") {
             let path_fragments: Vec<String> = name.split("::").map(|s| String::from(s)).collect();
             let mut mod_ctx_opt = None;
             let mut start_i = 0;
+            let mut mod_path;
             if path_fragments[0] == "root" {
                 start_i = 1;
                 mod_ctx_opt = Some(self.get_root_module()?);
+                mod_path = String::from("root::");
             } else if path_fragments[0] == "super" {
                 start_i = 1;
                 return Err(CompilerError::Unimplemented(format!("Blub")));
             } else {
                 mod_ctx_opt = Some(self.get_current_module()?);
+                mod_path = self.get_module_path();
             }
 
             for i in start_i..path_fragments.len() - 1 {
-                let mod_ctx = mod_ctx_opt.unwrap();
+                let mod_ctx = mod_ctx_opt
+                    .ok_or_else(|| CompilerError::UnknownModule(path_fragments[i - 1].clone()))?;
                 //println!("Blub");
                 mod_ctx_opt = mod_ctx.modules.get(&path_fragments[i]);
+                if let Some(child_mod_ctx) = mod_ctx_opt {
+                    self.check_visibility(&mod_path, &path_fragments[i], child_mod_ctx.public)?;
+                }
+                mod_path += &path_fragments[i];
+                mod_path += "::";
             }
 
             let last_path = path_fragments.last().unwrap();
 
             //println!("Resolving function {} for mod_ctx {}", last_path, mod_ctx_opt.as_ref().unwrap().name);
 
-            let mod_ctx = mod_ctx_opt.unwrap();
-            return mod_ctx.containers.get(last_path)
-                .cloned()
-                .ok_or(CompilerError::UnknownContainer(name.clone()));
+            let mod_ctx = mod_ctx_opt
+                .ok_or_else(|| CompilerError::UnknownModule(path_fragments[path_fragments.len() - 2].clone()))?;
+            if let Some(cont_def) = mod_ctx.containers.get(last_path) {
+                self.check_visibility(&mod_path, last_path, cont_def.public)?;
+                return Ok(cont_def.clone());
+            }
+            for export_path in mod_ctx.get_export_paths(&mod_path, last_path).iter() {
+                if let Ok(cont_def) = self.resolve_in_scope(&mod_path, || self.resolve_container(export_path)) {
+                    return Ok(cont_def);
+                }
+            }
+            return Err(CompilerError::UnknownContainer(name.clone()));
         } else {
             let mod_ctx = self.get_current_module()?;
             if mod_ctx.containers.contains_key(name) {
@@ -430,78 +1084,425 @@ impl Compiler {
                     .ok_or(CompilerError::Unknown)?;
                 return self.resolve_container(import_path);
             }
+            for wildcard_path in mod_ctx.wildcard_imports.iter() {
+                if let Ok(cont_def) = self.resolve_container(&format!("{}{}", wildcard_path, name)) {
+                    return Ok(cont_def);
+                }
+            }
 
             return Err(CompilerError::UnknownContainer(name.clone()));
         }
     }
 
-    /// Returns the byte size of a given Type
-    pub fn get_size_of_type(&self, var_type: &Type) -> CompilerResult<usize> {
-        //println!("Getting size of type");
-        let size = match var_type {
-            Type::String => 16,
-            Type::Void => 0,
-            Type::Int => 8,
-            Type::Reference(inner) => {
-                match inner.deref() {
-                    Type::AutoArray(_) => 16,
-                    _ => 8
+    /// Resolves an enum by name to an EnumDef
+    pub fn resolve_enum(&self, name: &String) -> CompilerResult<EnumDef> {
+        if let Some(expanded_name) = self.expand_module_path(name)? {
+            return self.resolve_enum(&expanded_name);
+        }
+        if name.contains("::") {
+            let path_fragments: Vec<String> = name.split("::").map(String::from).collect();
+            let (mut mod_ctx_opt, start_i, mut mod_path) = match path_fragments[0].as_str() {
+                "root" => (Some(self.get_root_module()?), 1, String::from("root::")),
+                "super" => return Err(CompilerError::Unimplemented(name.clone())),
+                _ => (Some(self.get_current_module()?), 0, self.get_module_path())
+            };
+
+            for i in start_i..path_fragments.len() - 1 {
+                let mod_ctx = mod_ctx_opt
+                    .ok_or_else(|| CompilerError::UnknownModule(path_fragments[i - 1].clone()))?;
+                mod_ctx_opt = mod_ctx.modules.get(&path_fragments[i]);
+                if let Some(child_mod_ctx) = mod_ctx_opt {
+                    self.check_visibility(&mod_path, &path_fragments[i], child_mod_ctx.public)?;
                 }
-            },
-            Type::Float => 4,
-            Type::Bool => 4,
-            Type::Other(cont_name) => {
-                let cont_def = self.resolve_container(&cont_name)?;
-                cont_def.get_size(self)?
-            },
-            Type::Array(inner_type, size) => {
-                let inner_type_size = self.get_size_of_type(&inner_type)?;
-                inner_type_size * size
-            },
-            _ => {
-                //println!("Error in get_size_of_type()!");
-                return Err(CompilerError::UnknownType(var_type.clone()));
+                mod_path += &path_fragments[i];
+                mod_path += "::";
             }
-        };
-        Ok(size)
-    }
 
-    /// Returns the type of a given variable
-    pub fn get_type_of_var(&self, var_name: &String) -> CompilerResult<Type> {
-        let mut type_opt = None;
+            let last_path = path_fragments.last().unwrap();
 
-        for i in 0..self.fn_context_stack.len() {
-            let fn_ctx = self.get_function(i)?;
-            let var_type_res = fn_ctx.get_var_type(var_name);
-            if var_type_res.is_ok() {
-                type_opt = Some(var_type_res.unwrap());
-                break;
+            let mod_ctx = mod_ctx_opt
+                .ok_or_else(|| CompilerError::UnknownModule(path_fragments[path_fragments.len() - 2].clone()))?;
+            if let Some(enum_def) = mod_ctx.enums.get(last_path) {
+                return Ok(enum_def.clone());
+            }
+            for export_path in mod_ctx.get_export_paths(&mod_path, last_path).iter() {
+                if let Ok(enum_def) = self.resolve_in_scope(&mod_path, || self.resolve_enum(export_path)) {
+                    return Ok(enum_def);
+                }
+            }
+            Err(CompilerError::UnknownEnum(name.clone()))
+        } else {
+            let mod_ctx = self.get_current_module()?;
+            if let Some(enum_def) = mod_ctx.enums.get(name) {
+                return Ok(enum_def.clone());
             }
+            if let Some(import_path) = mod_ctx.imports.get(name) {
+                return self.resolve_enum(import_path);
+            }
+            for wildcard_path in mod_ctx.wildcard_imports.iter() {
+                if let Ok(enum_def) = self.resolve_enum(&format!("{}{}", wildcard_path, name)) {
+                    return Ok(enum_def);
+                }
+            }
+            Err(CompilerError::UnknownEnum(name.clone()))
         }
-
-        type_opt.ok_or(CompilerError::UnknownVariable(var_name.clone()))
     }
 
-    /// Returns the offset to SP for a given variable
-    pub fn get_sp_offset_of_var(&self, var_name: &String) -> CompilerResult<i64> {
-        let fn_ctx = self.get_current_function()?;
-        let stack_pos = fn_ctx.get_var_pos(var_name)?;
-        let stack_size = fn_ctx.stack_size as i64;
-        let mut offset = stack_size - stack_pos;
-        if offset > 0 {
-            offset = -offset;
+    /// Resolves an interface by name to an InterfaceDef
+    pub fn resolve_interface(&self, name: &String) -> CompilerResult<InterfaceDef> {
+        if let Some(expanded_name) = self.expand_module_path(name)? {
+            return self.resolve_interface(&expanded_name);
         }
-        Ok(
-            offset
-        )
-    }
+        if name.contains("::") {
+            let path_fragments: Vec<String> = name.split("::").map(String::from).collect();
+            let (mut mod_ctx_opt, start_i, mut mod_path) = match path_fragments[0].as_str() {
+                "root" => (Some(self.get_root_module()?), 1, String::from("root::")),
+                "super" => return Err(CompilerError::Unimplemented(name.clone())),
+                _ => (Some(self.get_current_module()?), 0, self.get_module_path())
+            };
 
-    /// Increments the stack of the current function context
-    pub fn inc_stack(&mut self, size: usize) -> CompilerResult<usize> {
-        let fn_ctx = self.get_current_function_mut()?;
-        fn_ctx.stack_size += size;
-        //println!("COMP: Incrementing stack by {}", size);
-        //println!("Incrementing stack of {:?} by {}", fn_ctx, size);
+            for i in start_i..path_fragments.len() - 1 {
+                let mod_ctx = mod_ctx_opt
+                    .ok_or_else(|| CompilerError::UnknownModule(path_fragments[i - 1].clone()))?;
+                mod_ctx_opt = mod_ctx.modules.get(&path_fragments[i]);
+                if let Some(child_mod_ctx) = mod_ctx_opt {
+                    self.check_visibility(&mod_path, &path_fragments[i], child_mod_ctx.public)?;
+                }
+                mod_path += &path_fragments[i];
+                mod_path += "::";
+            }
+
+            let last_path = path_fragments.last().unwrap();
+
+            let mod_ctx = mod_ctx_opt
+                .ok_or_else(|| CompilerError::UnknownModule(path_fragments[path_fragments.len() - 2].clone()))?;
+            if let Some(iface_def) = mod_ctx.interfaces.get(last_path) {
+                return Ok(iface_def.clone());
+            }
+            for export_path in mod_ctx.get_export_paths(&mod_path, last_path).iter() {
+                if let Ok(iface_def) = self.resolve_in_scope(&mod_path, || self.resolve_interface(export_path)) {
+                    return Ok(iface_def);
+                }
+            }
+            Err(CompilerError::UnknownInterface(name.clone()))
+        } else {
+            let mod_ctx = self.get_current_module()?;
+            if let Some(iface_def) = mod_ctx.interfaces.get(name) {
+                return Ok(iface_def.clone());
+            }
+            if let Some(import_path) = mod_ctx.imports.get(name) {
+                return self.resolve_interface(import_path);
+            }
+            for wildcard_path in mod_ctx.wildcard_imports.iter() {
+                if let Ok(iface_def) = self.resolve_interface(&format!("{}{}", wildcard_path, name)) {
+                    return Ok(iface_def);
+                }
+            }
+            Err(CompilerError::UnknownInterface(name.clone()))
+        }
+    }
+
+    /// Resolves a path that parsed as an enum value but names an associated constant
+    /// of a container or a static of a module instead, e.g. "Point::ORIGIN" or "math::PI".
+    /// Returns None for actual enums.
+    pub fn resolve_assoc_constant(&self, enum_name: &String, variant: &str) -> CompilerResult<Option<StaticDef>> {
+        match self.resolve_enum(enum_name) {
+            Ok(_) => Ok(None),
+            Err(err) => {
+                let static_res = self.resolve_static(&format!("{}::{}", enum_name, variant));
+                if self.resolve_container(enum_name).is_err() {
+                    return static_res.map(Some)
+                        .map_err(|_| err);
+                }
+                Ok(Some(static_res?))
+            }
+        }
+    }
+
+    /// Resolves a module level (static) variable by its name or path
+    pub fn resolve_static(&self, name: &String) -> CompilerResult<StaticDef> {
+        if let Some(expanded_name) = self.expand_module_path(name)? {
+            return self.resolve_static(&expanded_name);
+        }
+        if name.contains("::") {
+            let path_fragments: Vec<String> = name.split("::").map(String::from).collect();
+            let (mut mod_ctx_opt, start_i, mut mod_path) = match path_fragments[0].as_str() {
+                "root" => (Some(self.get_root_module()?), 1, String::from("root::")),
+                "super" => return Err(CompilerError::Unimplemented(name.clone())),
+                _ => (Some(self.get_current_module()?), 0, self.get_module_path())
+            };
+
+            let last_path = path_fragments.last().unwrap();
+
+            for i in start_i..path_fragments.len() - 1 {
+                let mod_ctx = mod_ctx_opt
+                    .ok_or_else(|| CompilerError::UnknownModule(path_fragments[i - 1].clone()))?;
+                // Associated constant of a container
+                if i == path_fragments.len() - 2 && mod_ctx.containers.contains_key(&path_fragments[i]) {
+                    let cont_def = mod_ctx.get_container(&path_fragments[i])?;
+                    self.check_visibility(&mod_path, &cont_def.name, cont_def.public)?;
+                    return cont_def.constants.get(last_path)
+                        .cloned()
+                        .ok_or_else(|| CompilerError::UnknownVariable(name.clone()));
+                }
+                mod_ctx_opt = mod_ctx.modules.get(&path_fragments[i]);
+                if let Some(child_mod_ctx) = mod_ctx_opt {
+                    self.check_visibility(&mod_path, &path_fragments[i], child_mod_ctx.public)?;
+                }
+                mod_path += &path_fragments[i];
+                mod_path += "::";
+            }
+
+            let mod_ctx = mod_ctx_opt
+                .ok_or_else(|| CompilerError::UnknownModule(path_fragments[path_fragments.len() - 2].clone()))?;
+            if let Some(static_def) = mod_ctx.statics.get(last_path) {
+                return Ok(static_def.clone());
+            }
+            for export_path in mod_ctx.get_export_paths(&mod_path, last_path).iter() {
+                if let Ok(static_def) = self.resolve_in_scope(&mod_path, || self.resolve_static(export_path)) {
+                    return Ok(static_def);
+                }
+            }
+            Err(CompilerError::UnknownVariable(name.clone()))
+        } else {
+            // Constants of the container are in scope in its impl blocks
+            if let Some(cont_def) = self.current_cont.as_ref().and_then(|cont_name| self.resolve_container(cont_name).ok()) {
+                if let Some(static_def) = cont_def.constants.get(name) {
+                    return Ok(static_def.clone());
+                }
+            }
+            let mod_ctx = self.get_current_module()?;
+            if let Some(static_def) = mod_ctx.statics.get(name) {
+                return Ok(static_def.clone());
+            }
+            if let Some(import_path) = mod_ctx.imports.get(name) {
+                return self.resolve_static(import_path);
+            }
+            for wildcard_path in mod_ctx.wildcard_imports.iter() {
+                if let Ok(static_def) = self.resolve_static(&format!("{}{}", wildcard_path, name)) {
+                    return Ok(static_def);
+                }
+            }
+            Err(CompilerError::UnknownVariable(name.clone()))
+        }
+    }
+
+    /// Resolves a generic function by name to a GenericFunctionDef
+    pub fn resolve_generic_function(&self, name: &String) -> CompilerResult<GenericFunctionDef> {
+        if let Some(expanded_name) = self.expand_module_path(name)? {
+            return self.resolve_generic_function(&expanded_name);
+        }
+        if name.contains("::") {
+            let path_fragments: Vec<String> = name.split("::").map(String::from).collect();
+            let (mut mod_ctx_opt, start_i, mut mod_path) = match path_fragments[0].as_str() {
+                "root" => (Some(self.get_root_module()?), 1, String::from("root::")),
+                "super" => return Err(CompilerError::Unimplemented(name.clone())),
+                _ => (Some(self.get_current_module()?), 0, self.get_module_path())
+            };
+
+            for i in start_i..path_fragments.len() - 1 {
+                let mod_ctx = mod_ctx_opt
+                    .ok_or_else(|| CompilerError::UnknownModule(path_fragments[i - 1].clone()))?;
+                mod_ctx_opt = mod_ctx.modules.get(&path_fragments[i]);
+                if let Some(child_mod_ctx) = mod_ctx_opt {
+                    self.check_visibility(&mod_path, &path_fragments[i], child_mod_ctx.public)?;
+                }
+                mod_path += &path_fragments[i];
+                mod_path += "::";
+            }
+
+            let last_path = path_fragments.last().unwrap();
+
+            let mod_ctx = mod_ctx_opt
+                .ok_or_else(|| CompilerError::UnknownModule(path_fragments[path_fragments.len() - 2].clone()))?;
+            if let Some(generic_def) = mod_ctx.generic_functions.get(last_path) {
+                self.check_visibility(&mod_path, last_path, generic_def.decl.public)?;
+                return Ok(generic_def.clone());
+            }
+            for export_path in mod_ctx.get_export_paths(&mod_path, last_path).iter() {
+                if let Ok(generic_def) = self.resolve_in_scope(&mod_path, || self.resolve_generic_function(export_path)) {
+                    return Ok(generic_def);
+                }
+            }
+            Err(CompilerError::UnknownFunction(name.clone()))
+        } else {
+            let mod_ctx = self.get_current_module()?;
+            if let Some(generic_def) = mod_ctx.generic_functions.get(name) {
+                return Ok(generic_def.clone());
+            }
+            if let Some(import_path) = mod_ctx.imports.get(name) {
+                return self.resolve_generic_function(import_path);
+            }
+            for wildcard_path in mod_ctx.wildcard_imports.iter() {
+                if let Ok(generic_def) = self.resolve_generic_function(&format!("{}{}", wildcard_path, name)) {
+                    return Ok(generic_def);
+                }
+            }
+            Err(CompilerError::UnknownFunction(name.clone()))
+        }
+    }
+
+    /// Returns the byte size of a given Type
+    pub fn get_size_of_type(&self, var_type: &Type) -> CompilerResult<usize> {
+        //println!("Getting size of type");
+        let size = match var_type {
+            Type::String => 16,
+            Type::Void => 0,
+            Type::Int => 8,
+            Type::Enum(_) => 8,
+            Type::Coroutine(_) => 8,
+            Type::Reference(inner) => {
+                match inner.deref() {
+                    Type::AutoArray(_) => 16,
+                    // The object address and the vtable address
+                    Type::Interface(_) => 16,
+                    _ => 8
+                }
+            },
+            Type::Float => 4,
+            Type::Double => 8,
+            Type::Bool => 4,
+            Type::Char => 4,
+            Type::Other(type_name) if self.type_params.contains_key(type_name) => {
+                self.get_size_of_type(&self.type_params[type_name])?
+            },
+            Type::Other(cont_name) => {
+                let cont_def = self.resolve_container(&cont_name)?;
+                cont_def.get_size(self)?
+            },
+            Type::Generic(_, _) => {
+                let mut instance_type = var_type.clone();
+                self.canonize_type(&mut instance_type)?;
+                self.get_size_of_type(&instance_type)?
+            },
+            Type::Array(inner_type, size) => {
+                let inner_type_size = self.get_size_of_type(&inner_type)?;
+                inner_type_size * size
+            },
+            Type::Optional(inner_type) => {
+                // The value is followed by the "has value" flag
+                self.get_size_of_type(inner_type)? + 8
+            },
+            Type::Result(inner_type) => {
+                // The value is followed by the error message and the "is ok" flag
+                self.get_size_of_type(inner_type)? + 24
+            },
+            // The function uid and the address of the captured environment
+            Type::Function(_, _) => 16,
+            // The start and the exclusive end
+            Type::Range => 16,
+            // Interfaces are only usable behind a reference
+            Type::Interface(_) => {
+                return Err(CompilerError::UnsizedType(var_type.clone()));
+            },
+            Type::Tuple(elem_types) => {
+                let mut tuple_size = 0;
+                for elem_type in elem_types.iter() {
+                    tuple_size += self.get_size_of_type(elem_type)?;
+                }
+                tuple_size
+            },
+            _ => {
+                //println!("Error in get_size_of_type()!");
+                return Err(CompilerError::UnknownType(var_type.clone()));
+            }
+        };
+        Ok(size)
+    }
+
+    /// Returns the type of a given variable
+    pub fn get_type_of_var(&self, var_name: &String) -> CompilerResult<Type> {
+        // Locals of block expressions are only known while type checking them
+        let block_var_type = self.block_var_types.borrow().iter()
+            .rev()
+            .find_map(|var_types| var_types.get(var_name).cloned());
+        if let Some(var_type) = block_var_type {
+            return Ok(var_type);
+        }
+
+        let mut type_opt = None;
+
+        for i in 0..self.fn_context_stack.len() {
+            let fn_ctx = self.get_function(i)?;
+            let var_type_res = fn_ctx.get_var_type(var_name);
+            if var_type_res.is_ok() {
+                type_opt = Some(var_type_res.unwrap());
+                break;
+            }
+            // Locals of functions enclosing a lambda are only visible as captures
+            if !fn_ctx.weak {
+                break;
+            }
+        }
+
+        match type_opt {
+            Some(var_type) => Ok(var_type),
+            None => Ok(self.resolve_static(var_name)?.var_type)
+        }
+    }
+
+    /// Gets the type of a local variable holding a callable value, if there is one with this name
+    pub fn get_closure_type(&self, var_name: &String) -> Option<Type> {
+        let fn_ctx = self.get_current_function().ok()?;
+        match fn_ctx.get_var_type(var_name).ok()? {
+            Type::Function(arg_types, ret_type) => Some(Type::Function(arg_types, ret_type)),
+            _ => None
+        }
+    }
+
+    /// Checks if a variable is a local of the current function, and not a static variable
+    pub fn is_local_var(&self, var_name: &String) -> CompilerResult<bool> {
+        let fn_ctx = self.get_current_function()?;
+        Ok(fn_ctx.get_var_pos(var_name).is_ok())
+    }
+
+    /// Returns the offset to SP for a given variable
+    pub fn get_sp_offset_of_var(&self, var_name: &String) -> CompilerResult<i64> {
+        let fn_ctx = self.get_current_function()?;
+        let stack_pos = fn_ctx.get_var_pos(var_name)?;
+        let stack_size = fn_ctx.stack_size as i64;
+        let mut offset = stack_size - stack_pos;
+        if offset > 0 {
+            offset = -offset;
+        }
+        Ok(
+            offset
+        )
+    }
+
+    /// Returns the offset to FP for a given variable.
+    /// Arguments lie below the frame pointer, locals above it.
+    pub fn get_fp_offset_of_var(&self, var_name: &String) -> CompilerResult<i64> {
+        let sp_offset = self.get_sp_offset_of_var(var_name)?;
+        let frame_stack_size = self.get_frame_stack_size()? as i64;
+        Ok(
+            sp_offset + frame_stack_size
+        )
+    }
+
+    /// Computes the address of a local variable into the target register
+    pub fn compile_var_addr(&mut self, var_name: &String, target_reg: Register) -> CompilerResult<()> {
+        let fp_offset = self.get_fp_offset_of_var(var_name)?;
+        let opcode = if fp_offset < 0 {
+            Opcode::SUBU_I
+        }
+        else {
+            Opcode::ADDU_I
+        };
+        let addr_instr = Instruction::new(opcode)
+            .with_operand::<u8>(Register::FP.into())
+            .with_operand::<u64>(fp_offset.abs() as u64)
+            .with_operand::<u8>(target_reg.into());
+        self.builder.push_instr(addr_instr);
+        Ok(())
+    }
+
+    /// Increments the stack of the current function context
+    pub fn inc_stack(&mut self, size: usize) -> CompilerResult<usize> {
+        let fn_ctx = self.get_current_function_mut()?;
+        fn_ctx.stack_size += size;
+        //println!("COMP: Incrementing stack by {}", size);
+        //println!("Incrementing stack of {:?} by {}", fn_ctx, size);
         Ok(fn_ctx.stack_size)
     }
 
@@ -544,6 +1545,14 @@ impl Compiler {
             self.register_foreign_module(module, &path)?;
         }
 
+        for (_, container) in module.containers {
+            self.register_foreign_container(container, &path)?;
+        }
+
+        for (name, constant) in module.constants {
+            self.register_foreign_constant(name, constant, &path)?;
+        }
+
         mod_ctx = self.pop_module_context()?;
 
         let front_mod_ctx = self.get_current_module_mut()?;
@@ -552,7 +1561,81 @@ impl Compiler {
         Ok(())
     }
 
-    fn register_foreign_function(&mut self, mut function: Function, path: &String) -> CompilerResult<()> {
+    /// Registers a constant of a foreign module as a static of the current module, which can not be assigned to
+    fn register_foreign_constant(&mut self, name: String, constant: Constant, path: &String) -> CompilerResult<()> {
+        let (var_type, bytes) = match constant {
+            Constant::Int(int) => (Type::Int, serialize(&int)),
+            Constant::Float(float) => (Type::Float, serialize(&float)),
+            Constant::Double(double) => (Type::Double, serialize(&double)),
+            Constant::Bool(boolean) => (Type::Bool, serialize(&boolean)),
+            Constant::Char(character) => (Type::Char, serialize(&(character as u32))),
+            Constant::String(string) => {
+                let (string_size, string_addr) = self.data.get_string_slice(&string);
+                (Type::String, serialize(&(string_size, string_addr)))
+            }
+        };
+        let mut bytes = bytes.map_err(|_| CompilerError::Unknown)?;
+        bytes.resize(self.get_size_of_type(&var_type)?, 0);
+
+        let address = match var_type {
+            Type::String => self.data.add_static_string(&bytes),
+            _ => self.data.add_static(&bytes)
+        };
+        let static_def = StaticDef {
+            name: name.clone(),
+            canonical_name: path.clone() + &name,
+            var_type: var_type,
+            address: address,
+            constant: true
+        };
+        self.get_current_module_mut()?.add_static(static_def)
+    }
+
+    /// Registers a foreign container in the root module
+    pub fn register_foreign_root_container(&mut self, container: Container) -> CompilerResult<()> {
+        self.register_foreign_container(container, &String::from("root::"))
+    }
+
+    /// Registers a foreign container. Fields and member variables become fields of the container,
+    /// the accessors of member variables and all member functions become foreign member functions.
+    fn register_foreign_container(&mut self, container: Container, path: &String) -> CompilerResult<()> {
+        let canonical_name = path.clone() + &container.name;
+        let cont_path = format!("{}::", canonical_name);
+        let mut cont_def = ContainerDef::new(container.name.clone(), canonical_name);
+
+        for field in container.fields {
+            cont_def.add_member_variable(field)?;
+        }
+
+        for (_, member) in container.members {
+            let function = match member {
+                ContainerMember::Function(function) => function,
+                ContainerMember::Variable { name, accessor_fn } => {
+                    cont_def.add_member_variable((name, accessor_fn.return_type.clone()))?;
+                    accessor_fn
+                }
+            };
+            let fn_def = self.register_foreign_fn_def(function, &cont_path)?;
+            cont_def.add_member_function(fn_def)?;
+        }
+
+        let front_mod_ctx = self.get_current_module_mut()?;
+        front_mod_ctx.add_container(cont_def)?;
+
+        Ok(())
+    }
+
+    fn register_foreign_function(&mut self, function: Function, path: &String) -> CompilerResult<()> {
+        let fn_def = self.register_foreign_fn_def(function, path)?;
+
+        let front_mod_ctx = self.get_current_module_mut()?;
+        front_mod_ctx.add_function(fn_def)?;
+
+        Ok(())
+    }
+
+    /// Registers the implementation of a foreign function and returns its definition
+    fn register_foreign_fn_def(&mut self, mut function: Function, path: &String) -> CompilerResult<FunctionDef> {
         if self.foreign_functions.is_none() {
             self.foreign_functions = Some(HashMap::new());
         }
@@ -566,16 +1649,12 @@ impl Compiler {
         let mut arg_offsets = Vec::new();
         arg_sizes.resize(function.arg_types.len(), 0);
         arg_offsets.resize(function.arg_types.len(), 0);
-        let mut i = arg_sizes.len() - 1;
-        for arg_type in function_clone.arg_types.iter().rev() {
+        for (i, arg_type) in function_clone.arg_types.iter().enumerate().rev() {
             let arg_size = self.get_size_of_type(&arg_type)?;
             arg_sizes[i] = arg_size;
             arg_offset_sum -= arg_size as i64;
             arg_offsets[i] = arg_offset_sum;
             //println!("Registering arg i={}", i);
-            if i > 0 {
-                i -= 1;
-            }
         }
 
         function.set_arg_offsets(arg_offsets);
@@ -593,10 +1672,7 @@ impl Compiler {
             .with_ret_type(function_clone.return_type)
             .with_uid(fn_uid);
 
-        let front_mod_ctx = self.get_current_module_mut()?;
-        front_mod_ctx.add_function(fn_def)?;
-
-        Ok(())
+        Ok(fn_def)
     }
 
     /// Canonizes (adds module path when necessary) a given Type
@@ -609,11 +1685,48 @@ impl Compiler {
                     Type::Reference(Box::new(inner_type.clone()))
                 )
             },
-            Type::Other(cont_name) => {
-                let cont_def = self.resolve_container(cont_name)?;
-                Some(
-                    Type::Other(cont_def.canonical_name.clone())
-                )
+            Type::Other(type_name) if self.type_params.contains_key(type_name) => {
+                Some(self.type_params[type_name].clone())
+            },
+            Type::Other(type_name) => {
+                // Named types are either containers, enums or interfaces
+                match self.find_container(type_name) {
+                    Ok(cont_def) if !cont_def.generics.is_empty() => {
+                        return Err(CompilerError::TypeArgumentMismatch(type_name.clone(), 0));
+                    },
+                    Ok(cont_def) => Some(Type::Other(cont_def.canonical_name.clone())),
+                    Err(error) => {
+                        if let Ok(enum_def) = self.resolve_enum(type_name) {
+                            Some(Type::Enum(enum_def.canonical_name))
+                        } else {
+                            let iface_def = self.resolve_interface(type_name)
+                                .map_err(|_| error)?;
+                            Some(Type::Interface(iface_def.canonical_name))
+                        }
+                    }
+                }
+            },
+            Type::Tuple(elem_types) => {
+                for elem_type in elem_types.iter_mut() {
+                    self.canonize_type(elem_type)?;
+                }
+                None
+            },
+            Type::Optional(inner_type) |
+            Type::Result(inner_type) |
+            Type::Coroutine(inner_type) => {
+                self.canonize_type(inner_type.deref_mut())?;
+                None
+            },
+            Type::Function(arg_types, ret_type) => {
+                for arg_type in arg_types.iter_mut() {
+                    self.canonize_type(arg_type)?;
+                }
+                self.canonize_type(ret_type.deref_mut())?;
+                None
+            },
+            Type::Generic(cont_name, type_args) => {
+                Some(self.instantiate_generic_container(cont_name, type_args)?)
             },
             _ => None
         };
@@ -623,13 +1736,60 @@ impl Compiler {
         Ok(())
     }
 
+    /// Gets the type of an instance of a generic container, named after the container and
+    /// its type arguments, e.g. "root::Box<int>". Its definition is created on first use.
+    pub fn instantiate_generic_container(&self, cont_name: &String, type_args: &[Type]) -> CompilerResult<Type> {
+        let generic_def = self.find_container(cont_name)?;
+        if generic_def.generics.len() != type_args.len() {
+            return Err(CompilerError::TypeArgumentMismatch(cont_name.clone(), type_args.len()));
+        }
+        let mut canon_type_args = type_args.to_vec();
+        for type_arg in canon_type_args.iter_mut() {
+            self.canonize_type(type_arg)?;
+        }
+        let type_names: Vec<String> = canon_type_args.iter()
+            .map(|type_arg| type_arg.to_string())
+            .collect();
+        let instance_name = format!("{}<{}>", generic_def.canonical_name, type_names.join(", "));
+        if self.container_instances.borrow().contains_key(&instance_name) {
+            return Ok(Type::Other(instance_name));
+        }
+
+        let type_params: HashMap<String, Type> = generic_def.generics.iter()
+            .cloned()
+            .zip(canon_type_args.iter().cloned())
+            .collect();
+        let mut cont_def = generic_def.clone();
+        cont_def.name = format!("{}<{}>", generic_def.name, type_names.join(", "));
+        cont_def.canonical_name = instance_name.clone();
+        cont_def.generics = Vec::new();
+        cont_def.type_args = canon_type_args;
+        for member_type in cont_def.member_variables.values_mut() {
+            *member_type = substitute_type_params(member_type, &type_params);
+            // The container may refer to itself by its short name
+            qualify_generic_name(member_type, &generic_def.name, &generic_def.canonical_name);
+        }
+        // Registered before its members are resolved, so they can refer to the instance itself
+        self.container_instances.borrow_mut().insert(instance_name.clone(), cont_def.clone());
+        for member_type in cont_def.member_variables.values_mut() {
+            if member_type.has_type_args() {
+                self.canonize_type(member_type)?;
+            }
+        }
+        self.container_instances.borrow_mut().insert(instance_name.clone(), cont_def);
+        Ok(Type::Other(instance_name))
+    }
+
     // #endregion
 
     // #region declare functions
 
     /// (Pre-)declares a given declaration list
     pub fn declare_decl_list(&mut self, decl_list: &[Declaration]) -> CompilerResult<()> {
-        for decl in decl_list.iter() {
+        // Interfaces come first, so their impls and references don't depend on the declaration order
+        let (iface_decls, other_decls): (Vec<&Declaration>, Vec<&Declaration>) = decl_list.iter()
+            .partition(|decl| matches!(decl, Declaration::Interface(_)));
+        for decl in iface_decls.into_iter().chain(other_decls) {
             self.declare_decl(decl)?;
         }
         Ok(())
@@ -638,19 +1798,76 @@ impl Compiler {
     /// (Pre-)declares a given declaration
     pub fn declare_decl(&mut self, decl: &Declaration) -> CompilerResult<()> {
         match decl {
-            Declaration::Module(_, _) => self.declare_mod_decl(decl)?,
+            Declaration::Module(_, _) |
+            Declaration::PubModule(_, _) |
+            Declaration::FileModule(_, _) |
+            Declaration::PubFileModule(_, _) => self.declare_mod_decl(decl)?,
+            // File imports and module files are replaced by the source resolver before compiling
+            Declaration::FileImport(file_path, _) => return Err(CompilerError::UnresolvedFileImport(file_path.clone())),
+            Declaration::ModuleFile(mod_name) |
+            Declaration::PubModuleFile(mod_name) => return Err(CompilerError::UnresolvedModuleFile(mod_name.clone())),
             Declaration::Function(_) => self.declare_fn_decl(decl)?,
             Declaration::Container(_) => self.declare_cont_decl(decl)?,
-            Declaration::Import(_, _) => self.declare_import_decl(decl)?,
+            Declaration::Enum(_) => self.declare_enum_decl(decl)?,
+            Declaration::Import(_, _) |
+            Declaration::PubImport(_, _) => self.declare_import_decl(decl)?,
             Declaration::Impl(_, _, _) => self.declare_impl_decl(decl)?,
-            Declaration::StaticVar(_) => self.declare_static_var(decl)?
+            Declaration::StaticVar(_) => self.declare_static_var(decl)?,
+            Declaration::Interface(_) => self.declare_iface_decl(decl)?
         };
         Ok(())
     }
 
-    /// (Pre-)declares a given static var declaration
+    /// (Pre-)declares a given static var declaration.
+    /// Reserves the variable in the data segment, initialized with its constant assignment.
     pub fn declare_static_var(&mut self, decl: &Declaration) -> CompilerResult<()> {
-        Ok(())
+        let var_decl_args = match decl {
+            Declaration::StaticVar(var_decl_args) => var_decl_args,
+            _ => return Err(CompilerError::Unknown)
+        };
+
+        let value = self.eval_const_expr(&var_decl_args.assignment)?;
+        let var_type = self.check_expr_type(&var_decl_args.assignment)?;
+        if var_decl_args.var_type != Type::Auto {
+            let mut decl_type = var_decl_args.var_type.clone();
+            self.canonize_type(&mut decl_type)?;
+            if decl_type != var_type {
+                return Err(CompilerError::TypeMismatch(decl_type, var_type));
+            }
+        }
+
+        let mut bytes = match value {
+            Expression::IntLiteral(int) => serialize(&int),
+            Expression::FloatLiteral(float) => serialize(&float),
+            Expression::DoubleLiteral(double) => serialize(&double),
+            Expression::BoolLiteral(boolean) => serialize(&boolean),
+            Expression::CharLiteral(character) => serialize(&(character as u32)),
+            Expression::StringLiteral(string) => {
+                let string = String::from(&string[1..string.len() - 1]);
+                let (string_size, string_addr) = self.data.get_string_slice(&string);
+                serialize(&(string_size, string_addr))
+            },
+            _ => return Err(CompilerError::InvalidStaticType(var_type))
+        }.map_err(|_| CompilerError::Unknown)?;
+        bytes.resize(self.get_size_of_type(&var_type)?, 0);
+
+        // Variables of impl blocks are associated constants of the container, e.g. "Point::ORIGIN"
+        let canon_name = self.get_full_name(&var_decl_args.name);
+        let address = match var_type {
+            Type::String => self.data.add_static_string(&bytes),
+            _ => self.data.add_static(&bytes)
+        };
+        let static_def = StaticDef {
+            name: var_decl_args.name.clone(),
+            canonical_name: canon_name,
+            var_type: var_type,
+            address: address,
+            constant: self.current_cont.is_some()
+        };
+        match self.current_cont.clone() {
+            Some(cont_name) => self.get_impl_container_mut(&cont_name)?.add_constant(static_def),
+            None => self.get_current_module_mut()?.add_static(static_def)
+        }
     }
 
     /// (Pre-)declares a given function declaration
@@ -660,12 +1877,17 @@ impl Compiler {
             _ => return Err(CompilerError::Unknown)
         };
 
-        let mut full_fn_name = self.get_module_path();
-        if let Some(cont_name) = self.current_cont.as_ref().cloned() {
-            full_fn_name += &cont_name;
-            full_fn_name += "::";
+        let full_fn_name = self.get_full_name(&fn_decl_args.name);
+
+        // Generic functions get a uid per instance, once they are called
+        if !fn_decl_args.generics.is_empty() {
+            if self.current_cont.is_some() {
+                return Err(CompilerError::Unimplemented(format!("Generic member function {}", full_fn_name)));
+            }
+            let generic_def = GenericFunctionDef::from_decl(fn_decl_args, full_fn_name);
+            let mod_ctx = self.get_current_module_mut()?;
+            return mod_ctx.add_generic_function(generic_def);
         }
-        full_fn_name += &fn_decl_args.name;
 
         let uid = self.uid_generator.get_function_uid(&full_fn_name);
         self.fn_uid_map.insert(full_fn_name.clone(), uid.clone());
@@ -676,10 +1898,14 @@ impl Compiler {
         for (arg_name, arg_type) in fn_def.arguments.iter_mut() {
             self.canonize_type(arg_type)?;
         }
+        self.canonize_type(&mut fn_def.ret_type)?;
 
         if let Some(cont_name) = self.current_cont.as_ref().cloned() {
-            let mod_ctx = self.get_current_module_mut()?;
-            let cont_def = mod_ctx.get_container_mut(&cont_name)?;
+            let mod_path = self.get_module_path();
+            let cont_def = self.get_impl_container_mut(&cont_name)?;
+            if cont_def.get_module_path() != mod_path {
+                cont_def.impl_paths.insert(fn_def.name.clone(), mod_path);
+            }
             cont_def.add_member_function(fn_def)?;
         } else {
             let mod_ctx = self.get_current_module_mut()?;
@@ -692,11 +1918,15 @@ impl Compiler {
     /// (Pre-)declares a given module declaration
     pub fn declare_mod_decl(&mut self, decl: &Declaration) -> CompilerResult<()> {
         let (mod_name, decl_list) = match decl {
-            Declaration::Module(mod_name, decl_list) => (mod_name, decl_list),
+            Declaration::Module(mod_name, decl_list) |
+            Declaration::PubModule(mod_name, decl_list) |
+            Declaration::FileModule(mod_name, decl_list) |
+            Declaration::PubFileModule(mod_name, decl_list) => (mod_name, decl_list),
             _ => return Err(CompilerError::Unknown)
         };
 
         let mut mod_ctx = ModuleContext::new(mod_name.clone());
+        mod_ctx.public = matches!(decl, Declaration::PubModule(_, _) | Declaration::PubFileModule(_, _));
 
         self.push_module_context(mod_ctx);
 
@@ -729,6 +1959,9 @@ impl Compiler {
         if mod_ctx.containers.contains_key(&cont_decl_args.name) {
             let cont_def = mod_ctx.containers.get_mut(&cont_decl_args.name)
                 .ok_or(CompilerError::UnknownContainer(cont_decl_args.name.clone()))?;
+            if !cont_decl_args.generics.is_empty() {
+                return Err(CompilerError::Unimplemented(format!("impl of generic container {}", canon_name)));
+            }
             cont_def.merge_cont_decl(cont_decl_args);
         } else {
             let cont_def = ContainerDef::from_decl(cont_decl_args, canon_name);
@@ -743,15 +1976,79 @@ impl Compiler {
         Ok(())
     }
 
-    /// (Pre-)declares a given import declaration
-    pub fn declare_import_decl(&mut self, decl: &Declaration) -> CompilerResult<()> {
-        let (import_path, import_as) = match decl {
-            Declaration::Import(import_path, import_as) => (import_path, import_as),
+    /// (Pre-)declares a given enum declaration
+    pub fn declare_enum_decl(&mut self, decl: &Declaration) -> CompilerResult<()> {
+        let enum_decl_args = match decl {
+            Declaration::Enum(args) => args,
             _ => return Err(CompilerError::Unknown)
         };
 
+        let mut canon_name = self.get_module_path();
+        canon_name += &enum_decl_args.name;
         let mod_ctx = self.get_current_module_mut()?;
-        mod_ctx.add_import(import_as.clone(), import_path.clone())?;
+        if mod_ctx.modules.contains_key(&enum_decl_args.name) {
+            return Err(CompilerError::AlreadyContainsModule(enum_decl_args.name.clone()));
+        }
+        if mod_ctx.containers.contains_key(&enum_decl_args.name) {
+            return Err(CompilerError::AlreadyContainsContainer(enum_decl_args.name.clone()));
+        }
+        let enum_def = EnumDef::from_decl(enum_decl_args, canon_name);
+        mod_ctx.add_enum(enum_def)?;
+
+        Ok(())
+    }
+
+    /// (Pre-)declares a given interface declaration
+    pub fn declare_iface_decl(&mut self, decl: &Declaration) -> CompilerResult<()> {
+        let iface_decl_args = match decl {
+            Declaration::Interface(args) => args,
+            _ => return Err(CompilerError::Unknown)
+        };
+
+        let mut canon_name = self.get_module_path();
+        canon_name += &iface_decl_args.name;
+        let mut iface_def = InterfaceDef::from_decl(iface_decl_args, canon_name.clone());
+        for (i, fn_def) in iface_def.functions.iter().enumerate() {
+            if iface_def.functions[..i].iter().any(|other_def| other_def.name == fn_def.name) {
+                return Err(CompilerError::DuplicateFunction(fn_def.name.clone()));
+            }
+        }
+        for fn_def in iface_def.functions.iter_mut() {
+            if !fn_def.is_member_function() {
+                return Err(CompilerError::NotAMemberFunction(fn_def.name.clone()));
+            }
+            // "this" is the interface itself, which is not declared yet
+            fn_def.arguments[0].1 = Type::Reference(Box::new(Type::Interface(canon_name.clone())));
+            for (_, arg_type) in fn_def.arguments.iter_mut().skip(1) {
+                self.canonize_type(arg_type)?;
+            }
+            self.canonize_type(&mut fn_def.ret_type)?;
+        }
+
+        let mod_ctx = self.get_current_module_mut()?;
+        if mod_ctx.modules.contains_key(&iface_decl_args.name) {
+            return Err(CompilerError::AlreadyContainsModule(iface_decl_args.name.clone()));
+        }
+        if mod_ctx.containers.contains_key(&iface_decl_args.name) {
+            return Err(CompilerError::AlreadyContainsContainer(iface_decl_args.name.clone()));
+        }
+        mod_ctx.add_interface(iface_def)?;
+
+        Ok(())
+    }
+
+    /// (Pre-)declares a given import declaration
+    pub fn declare_import_decl(&mut self, decl: &Declaration) -> CompilerResult<()> {
+        let mod_ctx = self.get_current_module_mut()?;
+        match decl {
+            Declaration::Import(import_path, import_as) => {
+                mod_ctx.add_import(import_as.clone(), import_path.clone())?;
+            },
+            Declaration::PubImport(import_path, import_as) => {
+                mod_ctx.add_pub_import(import_as.clone(), import_path.clone())?;
+            },
+            _ => return Err(CompilerError::Unknown)
+        };
 
         Ok(())
     }
@@ -766,23 +2063,126 @@ impl Compiler {
         let mut canonical_name = self.get_module_path();
         canonical_name += &impl_type;
 
-        if impl_type == impl_for {
+        // Impls of containers declared in another module add to the declared container
+        let is_local = self.get_current_module()?.containers.contains_key(impl_for);
+        let is_foreign = !is_local && (impl_for.contains("::") || self.resolve_container(impl_for).is_ok());
+        if impl_type != impl_for || is_foreign {
+            let iface_def = if impl_type != impl_for {
+                Some(self.resolve_interface(impl_type)?)
+            } else {
+                None
+            };
+            let cont_def = self.resolve_container(impl_for)?;
+            if !cont_def.generics.is_empty() {
+                return Err(CompilerError::Unimplemented(format!("impl of generic container {}", cont_def.canonical_name)));
+            }
+            let cont_name = if is_local {
+                impl_for.clone()
+            } else {
+                cont_def.canonical_name
+            };
+            self.current_cont = Some(cont_name.clone());
+            self.declare_decl_list(decl_list)?;
+            self.current_cont = None;
+            if let Some(iface_def) = iface_def {
+                self.declare_vtable(&iface_def, &cont_name)?;
+            }
+        } else {
+            // Also creates the containers holding the member functions of builtin types
             let mod_ctx = self.get_current_module_mut()?;
             let cont_res = mod_ctx.get_container(impl_type);
-            if cont_res.is_err() {
-                let cont_def = ContainerDef::new(impl_type.clone(), canonical_name);
-                mod_ctx.add_container(cont_def)?;
-            }
+            match cont_res {
+                Ok(cont_def) if !cont_def.generics.is_empty() => {
+                    return Err(CompilerError::Unimplemented(format!("impl of generic container {}", canonical_name)));
+                },
+                Ok(_) => {},
+                Err(_) => {
+                    let cont_def = ContainerDef::new(impl_type.clone(), canonical_name);
+                    mod_ctx.add_container(cont_def)?;
+                }
+            };
             self.current_cont = Some(impl_type.clone());
             self.declare_decl_list(decl_list)?;
             self.current_cont = None;
-        } else {
-            return Err(CompilerError::Unimplemented(format!("Cannot currently compile non-cont impls!")));
         }
 
         Ok(())
     }
 
+    /// Builds the vtable of a container for an interface in the data segment.
+    /// It holds the uids of the implementing member functions, in the order of the interface functions.
+    pub fn declare_vtable(&mut self, iface_def: &InterfaceDef, cont_name: &String) -> CompilerResult<()> {
+        let cont_def = self.resolve_container(cont_name)?;
+        let mut vtable = Vec::new();
+        for iface_fn_def in iface_def.functions.iter() {
+            let fn_def = cont_def.get_member_function(&iface_fn_def.name)
+                .map_err(|_| CompilerError::InterfaceMismatch(iface_def.canonical_name.clone(), iface_fn_def.name.clone()))?;
+            let arg_types = fn_def.arguments.iter().skip(1).map(|(_, arg_type)| arg_type);
+            let iface_arg_types = iface_fn_def.arguments.iter().skip(1).map(|(_, arg_type)| arg_type);
+            let is_matching = fn_def.is_member_function() &&
+                fn_def.arguments.len() == iface_fn_def.arguments.len() &&
+                arg_types.eq(iface_arg_types) &&
+                fn_def.ret_type == iface_fn_def.ret_type;
+            if !is_matching {
+                return Err(CompilerError::InterfaceMismatch(iface_def.canonical_name.clone(), iface_fn_def.name.clone()));
+            }
+            let mut uid_bytes = serialize(&fn_def.uid)
+                .map_err(|_| CompilerError::Unknown)?;
+            vtable.append(&mut uid_bytes);
+        }
+        let vtable_address = self.data.add_static(&vtable);
+        let cont_def = self.get_impl_container_mut(cont_name)?;
+        cont_def.interfaces.insert(iface_def.canonical_name.clone(), vtable_address);
+        // Implementations of interface functions are as visible as the interface
+        for iface_fn_def in iface_def.functions.iter() {
+            if let Some(fn_def) = cont_def.member_functions.get_mut(&iface_fn_def.name) {
+                fn_def.public = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets the container an impl block adds to, which may be declared in another module
+    fn get_impl_container_mut(&mut self, cont_name: &String) -> CompilerResult<&mut ContainerDef> {
+        if self.get_current_module()?.containers.contains_key(cont_name) {
+            return self.get_current_module_mut()?.get_container_mut(cont_name);
+        }
+        let cont_def = self.resolve_container(cont_name)?;
+        self.get_module_mut_by_path(cont_def.get_module_path())?
+            .get_container_mut(&cont_def.name)
+    }
+
+    /// Gets the module at the given path, with trailing "::", while declaring.
+    /// Modules being declared are still on the stack, all others are already part of their parent.
+    fn get_module_mut_by_path(&mut self, mod_path: &str) -> CompilerResult<&mut ModuleContext> {
+        let mod_names: Vec<&str> = mod_path.split("::")
+            .skip(1)
+            .filter(|mod_name| !mod_name.is_empty())
+            .collect();
+        let mut stack_index = self.mod_context_stack.len() - 1;
+        let mut name_index = 0;
+        while name_index < mod_names.len() && stack_index > 0 &&
+            self.mod_context_stack[stack_index - 1].name == mod_names[name_index] {
+            stack_index -= 1;
+            name_index += 1;
+        }
+        let mut mod_ctx = self.mod_context_stack.get_mut(stack_index)
+            .ok_or(CompilerError::Unknown)?;
+        for mod_name in mod_names[name_index..].iter() {
+            mod_ctx = mod_ctx.modules.get_mut(*mod_name)
+                .ok_or_else(|| CompilerError::UnknownModule(mod_name.to_string()))?;
+        }
+        Ok(mod_ctx)
+    }
+
+    /// Returns the address of the vtable of a container for an interface
+    pub fn get_vtable_address(&self, cont_name: &String, iface_name: &String) -> CompilerResult<u64> {
+        let cont_def = self.resolve_container(cont_name)?;
+        cont_def.interfaces.get(iface_name)
+            .cloned()
+            .ok_or_else(|| CompilerError::InterfaceMismatch(iface_name.clone(), cont_def.canonical_name.clone()))
+    }
+
     // #endregion
     
     // #region compile functions
@@ -807,12 +2207,33 @@ impl Compiler {
         match decl {
             Declaration::Function(_) => self.compile_fn_decl(decl)?,
             Declaration::Impl(_, _, _) => self.compile_impl_decl(decl)?,
-            Declaration::Module(_, _) => self.compile_mod_decl(decl)?,
+            Declaration::Module(_, _) |
+            Declaration::PubModule(_, _) => self.compile_mod_decl(decl)?,
+            Declaration::FileModule(_, _) |
+            Declaration::PubFileModule(_, _) => self.compile_mod_decl(decl)?,
+            Declaration::Container(_) => self.compile_cont_decl(decl)?,
             _ => {}
         };
         Ok(())
     }
 
+    /// Compiles a container declaration, checking that its layout can be computed
+    pub fn compile_cont_decl(&mut self, decl: &Declaration) -> CompilerResult<()> {
+        let cont_decl_args = match decl {
+            Declaration::Container(args) => args,
+            _ => return Err(CompilerError::Unknown)
+        };
+
+        // Generic containers are laid out per instance
+        if !cont_decl_args.generics.is_empty() {
+            return Ok(());
+        }
+        let cont_def = self.resolve_container(&cont_decl_args.name)?;
+        cont_def.get_size(self)?;
+
+        Ok(())
+    }
+
     /// Compiles a function declaration
     pub fn compile_fn_decl(&mut self, decl: &Declaration) -> CompilerResult<()> {
         let fn_decl_args = match decl {
@@ -822,6 +2243,11 @@ impl Compiler {
 
         //println!("Compiling fn_decl");
 
+        // Generic functions are compiled per instance at their call sites
+        if !fn_decl_args.generics.is_empty() {
+            return Ok(());
+        }
+
         let fn_def = {
             if self.current_cont.is_none() {
                 self.get_current_module()?
@@ -841,12 +2267,7 @@ impl Compiler {
 
         let mut fn_ctx = FunctionContext::new(self, fn_def)?;
 
-        let mut full_fn_name = self.get_module_path();
-        if self.current_cont.is_some() {
-            full_fn_name += self.current_cont.as_ref().unwrap();
-            full_fn_name += "::";
-        }
-        full_fn_name += &fn_decl_args.name;
+        let full_fn_name = self.get_full_name(&fn_decl_args.name);
 
         //println!("Compiling fn decl with label {}", full_fn_name);
 
@@ -855,12 +2276,12 @@ impl Compiler {
 
         self.push_function_context(fn_ctx);
 
-        if let Some(stmt_list) = &fn_decl_args.code_block {
-            self.compile_stmt_list(stmt_list)?;
+        if let Some(stmt_list) = ConstantPropagator::new().propagate_fn(fn_decl_args) {
+            self.compile_stmt_list(&stmt_list)?;
         }
 
-        // If the type is void, automatically add a return Statement
-        if fn_ret_type == Type::Void {
+        // If the type is void, automatically add a return Statement. Coroutines return nothing either.
+        if fn_ret_type == Type::Void || matches!(fn_ret_type, Type::Coroutine(_)) {
             let ret_stmt = Statement::Return(None);
             self.compile_return_stmt(&ret_stmt)?;
         }
@@ -875,6 +2296,8 @@ impl Compiler {
 
     /// Compiles the proper SUBU_I instruction for a break statement
     pub fn compile_stack_loop(&mut self) -> CompilerResult<()> {
+        self.compile_rc_release_locals(|ctx| ctx.is_loop)?;
+
         let mut pop_size = 0;
 
         // Pop all values until the first loop context is hit
@@ -900,6 +2323,12 @@ impl Compiler {
     pub fn compile_stack_cleanup_block(&mut self, fn_ctx: &FunctionContext) -> CompilerResult<()> {
         let pop_size = fn_ctx.stack_size;
 
+        // The block began where the enclosing context ends
+        let block_base = self.get_frame_stack_size()? as i64;
+        for (var_pos, var_type) in fn_ctx.rc_vars.iter() {
+            self.compile_rc_release(var_type, Register::FP, (block_base + var_pos) as i16)?;
+        }
+
         //println!("Compiling stack cleanup with stack size {}", pop_size);
 
         // Instruction for popping values off the stack
@@ -913,6 +2342,8 @@ impl Compiler {
 
     /// Compiles a full stack unwind until the parent function is hit 
     pub fn compile_stack_cleanup_return(&mut self) -> CompilerResult<()> {
+        self.compile_rc_release_locals(|ctx| !ctx.weak)?;
+
         let mut parent_fn_ctx_opt = None;
         let mut stack_size = 0;
 
@@ -947,9 +2378,13 @@ impl Compiler {
         if pop_size > 0 {
             //println!("Popping {} off the stack at return.", pop_size);
             let pop_stack_instr = Instruction::new_dec_stack(pop_size);
-            self.dec_stack(pop_size)?;
             self.builder.push_instr(pop_stack_instr);
         }
+        // Code after the return keeps the stack layout of its block,
+        // only the pushed return value is gone
+        if !ret_type.is_primitive() {
+            self.dec_stack(ret_size)?;
+        }
 
         Ok(())
     }
@@ -957,21 +2392,20 @@ impl Compiler {
     /// Compiles a module declaration
     pub fn compile_mod_decl(&mut self, decl: &Declaration) -> CompilerResult<()> {
         let (mod_name, decl_list) = match decl {
-            Declaration::Module(mod_name, decl_list) => (mod_name, decl_list),
+            Declaration::Module(mod_name, decl_list) |
+            Declaration::PubModule(mod_name, decl_list) |
+            Declaration::FileModule(mod_name, decl_list) |
+            Declaration::PubFileModule(mod_name, decl_list) => (mod_name, decl_list),
             _ => return Err(CompilerError::Unknown)
         };
 
-        let mod_ctx = ModuleContext::new(mod_name.clone());
-
-        let module_declared = {
+        let mod_ctx = {
             let front_mod_ctx = self.get_current_module()?;
-            front_mod_ctx.modules.contains_key(mod_name)
+            front_mod_ctx.modules.get(mod_name)
+                .cloned()
+                .ok_or(CompilerError::UnknownModule(mod_name.clone()))?
         };
 
-        if !module_declared {
-            return Err(CompilerError::UnknownModule(mod_name.clone()));
-        }
-
         self.push_module_context(mod_ctx);
 
         self.compile_decl_list(decl_list)?;
@@ -983,20 +2417,22 @@ impl Compiler {
 
     /// Compiles an impl declaration
     pub fn compile_impl_decl(&mut self, decl: &Declaration) -> CompilerResult<()> {
-        let (impl_type, impl_for, decl_list) = match decl {
-            Declaration::Impl(impl_type, impl_for, decl_list) => (impl_type, impl_for, decl_list), 
+        // Member functions of interface impls belong to the implementing container
+        let (impl_for, decl_list) = match decl {
+            Declaration::Impl(_, impl_for, decl_list) => (impl_for, decl_list), 
             _ => return Err(CompilerError::Unknown)
         };
 
         //println!("Compiling impl: {:?}", decl);
 
-        if impl_type == impl_for {
-            self.current_cont = Some(impl_type.clone());
-            self.compile_decl_list(decl_list)?;
-            self.current_cont = None;
+        let is_local = self.get_current_module()?.containers.contains_key(impl_for);
+        if is_local {
+            self.current_cont = Some(impl_for.clone());
         } else {
-            return Err(CompilerError::Unimplemented(format!("impl of interfaces not supported yet!")));
+            self.current_cont = Some(self.resolve_container(impl_for)?.canonical_name);
         }
+        self.compile_decl_list(decl_list)?;
+        self.current_cont = None;
 
         Ok(())
     }
@@ -1015,17 +2451,50 @@ impl Compiler {
     pub fn compile_stmt(&mut self, stmt: &Statement) -> CompilerResult<()> {
         match stmt {
             Statement::VariableDecl(_) => self.compile_var_decl_stmt(stmt)?,
+            Statement::DestructureDecl(_) => self.compile_destructure_decl_stmt(stmt)?,
             Statement::Expression(_) => self.compile_expr_stmt(stmt)?,
             Statement::Return(_) => self.compile_return_stmt(stmt)?,
             Statement::If(_) => self.compile_if_stmt(stmt)?,
-            Statement::While(_, _) => self.compile_while_stmt(stmt)?, 
+            Statement::Match(_) => self.compile_match_stmt(stmt)?,
+            Statement::While(_, _) |
+            Statement::DoWhile(_, _) => self.compile_while_stmt(stmt)?,
+            Statement::ForIn(_, _, _) => self.compile_for_in_stmt(stmt)?,
             Statement::Continue => self.compile_continue_stmt(stmt)?,
             Statement::Break => self.compile_break_stmt(stmt)?,
+            Statement::StaticAssert(_, _) => self.compile_static_assert_stmt(stmt)?,
+            Statement::Throw(_) => self.compile_throw_stmt(stmt)?,
+            Statement::Spawn(_) => self.compile_spawn_stmt(stmt)?,
+            Statement::Yield(_) => self.compile_yield_stmt(stmt)?,
+            Statement::Increment(_, _) => self.compile_increment_stmt(stmt)?,
+            Statement::Positioned(inner_stmt, line, column) => {
+                self.add_statement_info(*line, *column)?;
+                self.compile_stmt(inner_stmt)?;
+            },
             _ => return Err(CompilerError::Unimplemented(format!("Compilation of {:?} not implemented!", stmt)))
         };
         Ok(())
     }
 
+    /// Records the debug info of the statement starting at the current offset
+    fn add_statement_info(&mut self, line: usize, column: usize) -> CompilerResult<()> {
+        let mut variables = Vec::new();
+        for var_name in self.get_current_function()?.get_var_names() {
+            variables.push(VariableInfo {
+                var_type: self.get_type_of_var(&var_name)?,
+                sp_offset: self.get_sp_offset_of_var(&var_name)?,
+                name: var_name
+            });
+        }
+        let stmt_info = StatementInfo {
+            line: line,
+            column: column,
+            variables: variables
+        };
+        let offset = self.builder.get_current_offset();
+        self.debug_info.statements.insert(offset, stmt_info);
+        Ok(())
+    }
+
     /// Compiles a variable declaration statement
     pub fn compile_var_decl_stmt(&mut self, stmt: &Statement) -> CompilerResult<()> {
         let var_decl_args = match stmt {
@@ -1044,7 +2513,16 @@ impl Compiler {
         //println!("var decl assign expr type: {:?}", assignment_expr_type);
         // Special handling for auto typed vars
         if var_type == Type::Auto {
+            // The type of an optional can not be inferred from null
+            if assignment_expr_type == Type::Null {
+                return Err(CompilerError::UnknownType(Type::Null));
+            }
             var_type = assignment_expr_type;
+        } else {
+            self.canonize_type(&mut var_type)?;
+            if let Type::Optional(_) | Type::Result(_) | Type::Reference(_) = var_type {
+                self.check_assignable(&var_type, &assignment_expr_type)?;
+            }
         }
 
         //println!("Var type: {:?}", var_type);
@@ -1053,7 +2531,7 @@ impl Compiler {
         //println!("Size of type: {}", var_size);
         // Compile said expression
         //println!("Compiling assignment expr ({:?}). SP: {}", assignment_expr, self.get_stack_size()?);
-        self.compile_expr(assignment_expr)?;
+        self.compile_assigned_expr(assignment_expr, &var_type)?;
         //println!("Compiled assignment expr ({:?}). SP: {}", assignment_expr, self.get_stack_size()?);
 
         // If the type can be contained in a register
@@ -1068,7 +2546,7 @@ impl Compiler {
             self.builder.push_instr(stack_inc_instr);
             self.inc_stack(var_size)?;
             let mov_instr = match var_type {
-                Type::Int => {
+                Type::Int | Type::Enum(_) | Type::Coroutine(_) => {
                     Instruction::new(Opcode::MOVI_RA)
                         .with_operand::<u8>(last_reg.into())
                         .with_operand::<u8>(Register::SP.into())
@@ -1080,6 +2558,12 @@ impl Compiler {
                         .with_operand::<u8>(Register::SP.into())
                         .with_operand::<i16>(var_sp_offset)
                 },
+                Type::Double => {
+                    Instruction::new(Opcode::MOVD_RA)
+                        .with_operand::<u8>(last_reg.into())
+                        .with_operand::<u8>(Register::SP.into())
+                        .with_operand::<i16>(var_sp_offset)
+                },
                 Type::Reference(_) => {
                     Instruction::new(Opcode::MOVA_RA)
                         .with_operand::<u8>(last_reg.into())
@@ -1092,6 +2576,12 @@ impl Compiler {
                         .with_operand::<u8>(Register::SP.into())
                         .with_operand::<i16>(var_sp_offset)
                 },
+                Type::Char => {
+                    Instruction::new(Opcode::MOVC_RA)
+                        .with_operand::<u8>(last_reg.into())
+                        .with_operand::<u8>(Register::SP.into())
+                        .with_operand::<i16>(var_sp_offset)
+                },
                 _ => {
                     //println!("Error in compile_var_decl_stmt()!");
                     return Err(CompilerError::UnknownType(var_type));
@@ -1102,11 +2592,66 @@ impl Compiler {
         // Otherwise, the value is already on the top of the stack.
         // Set the variable in the context.
         let fn_ctx = self.get_current_function_mut()?;
-        fn_ctx.set_stack_var((var_name.clone(), var_type.clone()), (fn_ctx.stack_size - var_size) as i64)?;
+        let var_pos = (fn_ctx.stack_size - var_size) as i64;
+        fn_ctx.set_stack_var((var_name.clone(), var_type.clone()), var_pos)?;
+
+        // The variable owns references to its strings until going out of scope
+        if self.ref_counting && !self.get_string_offsets(&var_type)?.is_empty() {
+            if !is_call_result(assignment_expr) {
+                self.compile_rc_retain(&var_type, Register::SP, -(var_size as i16))?;
+            }
+            self.get_current_function_mut()?
+                .rc_vars
+                .push((var_pos, var_type.clone()));
+        }
         //println!("Setting var {}: {:?} to position {}", var_name, var_type, fn_ctx.stack_size - var_size);
         Ok(())
     }
 
+    /// Compiles a tuple destructuring declaration.
+    /// The tuple stays on the stack and the names are bound to its elements, "_" skips one.
+    pub fn compile_destructure_decl_stmt(&mut self, stmt: &Statement) -> CompilerResult<()> {
+        let destructure_decl_args = match stmt {
+            Statement::DestructureDecl(destructure_decl_args) => destructure_decl_args,
+            _ => return Err(CompilerError::Unknown)
+        };
+
+        let assignment_expr = &destructure_decl_args.assignment;
+        let assignment_expr_type = self.check_expr_type(assignment_expr)?;
+        let mut tuple_type = destructure_decl_args.var_type.clone();
+        if tuple_type == Type::Auto {
+            tuple_type = assignment_expr_type;
+        } else {
+            self.canonize_type(&mut tuple_type)?;
+            if tuple_type != assignment_expr_type {
+                return Err(CompilerError::TypeMismatch(tuple_type, assignment_expr_type));
+            }
+        }
+
+        let elem_types = match &tuple_type {
+            Type::Tuple(elem_types) => elem_types.clone(),
+            _ => return Err(CompilerError::NotATuple(tuple_type))
+        };
+        let names = &destructure_decl_args.names;
+        if names.len() != elem_types.len() {
+            return Err(CompilerError::TupleArityMismatch(tuple_type, names.len()));
+        }
+
+        let tuple_size = self.get_size_of_type(&tuple_type)?;
+        self.compile_expr(assignment_expr)?;
+
+        let mut elem_pos = self.get_stack_size()? - tuple_size;
+        for (name, elem_type) in names.iter().zip(elem_types.iter()) {
+            let elem_size = self.get_size_of_type(elem_type)?;
+            if name != "_" {
+                let fn_ctx = self.get_current_function_mut()?;
+                fn_ctx.set_stack_var((name.clone(), elem_type.clone()), elem_pos as i64)?;
+            }
+            elem_pos += elem_size;
+        }
+        Ok(())
+    }
+
     /// Compiles a statement expression
     pub fn compile_expr_stmt(&mut self, stmt: &Statement) -> CompilerResult<()> {
         let stmt_expr = match stmt {
@@ -1116,6 +2661,7 @@ impl Compiler {
 
         match stmt_expr {
             Expression::Call(_, _) => self.compile_expr(stmt_expr)?,
+            Expression::MemberAccess(_, _) if stmt_expr.is_member_call() => self.compile_expr(stmt_expr)?,
             Expression::Assign(_, _) => self.compile_var_assign_stmt_expr(stmt_expr)?,
             Expression::AddAssign(_, _) => self.compile_var_assign_stmt_expr(stmt_expr)?,
             Expression::SubAssign(_, _) => self.compile_var_assign_stmt_expr(stmt_expr)?,
@@ -1206,7 +2752,7 @@ impl Compiler {
                     
                     // Update the jump destination
                     jmp_next_instr.remove_operand_bytes(8);
-                    jmp_next_instr.append_operand(pos);
+                    jmp_next_instr.append_operand::<u64>(pos as u64);
                 }
                 // Only boolean expressions are allowed
                 let expr_type = self.check_expr_type(else_if_expr)?;
@@ -1274,7 +2820,7 @@ impl Compiler {
                     
                 // Update the jump destination
                 jmp_next_instr.remove_operand_bytes(8);
-                jmp_next_instr.append_operand(pos);
+                jmp_next_instr.append_operand::<u64>(pos as u64);
             }
 
             // Create a new weak function context
@@ -1309,7 +2855,7 @@ impl Compiler {
                     
                 // Update the jump destination
                 jmp_next_instr.remove_operand_bytes(8);
-                jmp_next_instr.append_operand(pos);
+                jmp_next_instr.append_operand::<u64>(pos as u64);
             }
         }
 
@@ -1324,21 +2870,171 @@ impl Compiler {
             let jmp_instr = self.builder.get_instr(jmp_end_pos)
                 .ok_or(CompilerError::Unknown)?;
             jmp_instr.remove_operand_bytes(8);
-            jmp_instr.append_operand(pos_end);
+            jmp_instr.append_operand::<u64>(pos_end as u64);
+        }
+
+        Ok(())
+    }
+
+    /// Compiles a match statement
+    pub fn compile_match_stmt(&mut self, stmt: &Statement) -> CompilerResult<()> {
+        let match_stmt_args: &MatchStatementArgs = match stmt {
+            Statement::Match(match_stmt_args) => match_stmt_args,
+            _ => return Err(CompilerError::Unknown)
+        };
+
+        let match_type = self.check_expr_type(&match_stmt_args.match_expr)?;
+        let eq_opcode = match match_type {
+            Type::Int | Type::Char | Type::Enum(_) => Opcode::EQI,
+            Type::Float => Opcode::EQF,
+            Type::Double => Opcode::EQD,
+            _ => return Err(CompilerError::NotMatchable(match_type))
+        };
+
+        // Every arm has to be a distinct constant of the matched type
+        let mut arm_values = Vec::new();
+        for (arm_expr, _) in match_stmt_args.arms.iter() {
+            let arm_type = self.check_expr_type(arm_expr)?;
+            if arm_type != match_type {
+                return Err(CompilerError::TypeMismatch(arm_type, match_type));
+            }
+            let arm_value = self.eval_const_expr(arm_expr)?;
+            if arm_values.contains(&arm_value) {
+                return Err(CompilerError::DuplicateMatchArm(arm_value));
+            }
+            arm_values.push(arm_value);
+        }
+
+        // Evaluate the matched expression only once
+        self.compile_expr(&match_stmt_args.match_expr)?;
+        let match_reg = self.get_last_register()?;
+        // Keep it from being overwritten by the comparisons below
+        {
+            let fn_ctx = self.get_current_function_mut()?;
+            fn_ctx.register_allocator.block_register(match_reg.clone())?;
+        }
+
+        // Compare against all arm values up front, jumping straight into the matching arm
+        let mut arm_tags = Vec::new();
+        for arm_value in arm_values.iter() {
+            self.compile_expr(arm_value)?;
+            let value_reg = self.get_last_register()?;
+            let res_reg = self.get_next_register()?;
+            let eq_instr = Instruction::new(eq_opcode.clone())
+                .with_operand::<u8>(match_reg.clone().into())
+                .with_operand::<u8>(value_reg.into())
+                .with_operand::<u8>(res_reg.clone().into());
+            self.builder.push_instr(eq_instr);
+
+            let tag_arm = self.uid_generator.generate();
+            let jmpt_instr = Instruction::new(Opcode::JMPT)
+                .with_operand::<u8>(res_reg.into())
+                .with_operand(tag_arm);
+            self.builder.tag(tag_arm);
+            self.builder.push_instr(jmpt_instr);
+            arm_tags.push(tag_arm);
+        }
+
+        {
+            let fn_ctx = self.get_current_function_mut()?;
+            fn_ctx.register_allocator.unblock_register(match_reg)?;
+        }
+
+        // No arm matched, continue with the default arm
+        let tag_default = self.uid_generator.generate();
+        let jmp_default_instr = Instruction::new(Opcode::JMP)
+            .with_operand(tag_default);
+        self.builder.tag(tag_default);
+        self.builder.push_instr(jmp_default_instr);
+
+        // Generate an instruction tag to fill in the end of this match
+        let tag_end = self.uid_generator.generate();
+
+        for ((_, arm_stmt_list), tag_arm) in match_stmt_args.arms.iter().zip(arm_tags.iter()) {
+            let pos = self.builder.get_current_offset();
+            self.patch_jump_tag(tag_arm, pos)?;
+
+            self.compile_block_stmt_list(arm_stmt_list)?;
+
+            // Instruction for jumping to the end
+            let jmp_end_instr = Instruction::new(Opcode::JMP)
+                .with_operand(tag_end);
+            self.builder.tag(tag_end);
+            self.builder.push_instr(jmp_end_instr);
+        }
+
+        let pos_default = self.builder.get_current_offset();
+        self.patch_jump_tag(&tag_default, pos_default)?;
+
+        if let Some(default_stmt_list) = &match_stmt_args.default_block {
+            self.compile_block_stmt_list(default_stmt_list)?;
         }
 
+        // Current position is at the end of the entire match
+        let pos_end = self.builder.get_current_offset();
+        if !match_stmt_args.arms.is_empty() {
+            self.patch_jump_tag(&tag_end, pos_end)?;
+        }
+
+        Ok(())
+    }
+
+    /// Compiles a statement list in a block of its own
+    fn compile_block_stmt_list(&mut self, stmt_list: &[Statement]) -> CompilerResult<()> {
+        // Create a new weak function context
+        let mut block_fn_ctx = {
+            let fn_ctx = self.get_current_function()?;
+            FunctionContext::new_weak(fn_ctx)?
+        };
+        // And push it on the stack
+        self.push_function_context(block_fn_ctx);
+
+        self.compile_stmt_list(stmt_list)?;
+
+        // Pop it off the stack again
+        block_fn_ctx = self.pop_function_context()?;
+
+        self.compile_stack_cleanup_block(&block_fn_ctx)
+    }
+
+    /// Sets the destination of all jump instructions with the given tag
+    fn patch_jump_tag(&mut self, tag: &u64, pos: usize) -> CompilerResult<()> {
+        let jmp_pos_list = self.builder.get_tag(tag)
+            .ok_or(CompilerError::Unknown)?;
+        for jmp_pos in jmp_pos_list.iter() {
+            let jmp_instr = self.builder.get_instr(jmp_pos)
+                .ok_or(CompilerError::Unknown)?;
+            jmp_instr.remove_operand_bytes(8);
+            jmp_instr.append_operand::<u64>(pos as u64);
+        }
         Ok(())
     }
 
-    /// Compiles a while statement
+    /// Compiles a while statement.
+    /// A do-while loop jumps over the condition on entry, continue then still checks it.
     pub fn compile_while_stmt(&mut self, stmt: &Statement) -> CompilerResult<()> {
-        let (while_expr, while_stmt_list) = match stmt {
-            Statement::While(while_expr, while_stmt_list) => (while_expr, while_stmt_list),
+        let (while_expr, while_stmt_list, check_first) = match stmt {
+            Statement::While(while_expr, while_stmt_list) => (while_expr, while_stmt_list, true),
+            Statement::DoWhile(while_stmt_list, while_expr) => (while_expr, while_stmt_list, false),
             _ => return Err(CompilerError::Unknown)
         };
+        self.compile_loop(while_expr, &[], while_stmt_list, check_first)
+    }
 
+    /// Compiles a loop running while the expression is true, checking it before the first run if check_first is set.
+    /// The generated statements run before the statement list on each run.
+    fn compile_loop(&mut self, while_expr: &Expression, generated_stmt_list: &[Statement], while_stmt_list: &[Statement], check_first: bool) -> CompilerResult<()> {
         let while_fn_ctx = FunctionContext::new_loop(self.get_current_function()?)?;
         self.push_function_context(while_fn_ctx);
+
+        let tag_body = self.uid_generator.generate();
+        if !check_first {
+            let jmp_body_instr = Instruction::new(Opcode::JMP)
+                .with_operand::<u64>(tag_body);
+            self.builder.tag(tag_body);
+            self.builder.push_instr(jmp_body_instr);
+        }
+
         let while_start_pos = self.builder.get_current_offset();
         let tag_end = self.uid_generator.generate();
         let mut while_loop_ctx = LoopContext::new(while_start_pos, tag_end);
@@ -1366,7 +3062,13 @@ impl Compiler {
             .with_operand(tag_end);
         self.builder.push_instr(jmpf_instr);
 
+        if !check_first {
+            let body_pos = self.builder.get_current_offset();
+            self.patch_jump_tag(&tag_body, body_pos)?;
+        }
+
         // Compile the statement list
+        self.compile_stmt_list(generated_stmt_list)?;
         self.compile_stmt_list(while_stmt_list)?;
 
         // Compile a continue statement
@@ -1394,6 +3096,135 @@ impl Compiler {
         Ok(())
     }
 
+    /// Compiles a for-in loop by lowering it onto the iterator protocol of the iterated container.
+    /// An iterator implements `has_next(&this) ~ bool` and `next(&this) ~ T`, the loop then runs as
+    /// `while iter.has_next() { var item = iter.next(); ... }` on a copy of the iterated container.
+    /// Arrays and slices are iterated by index instead, binding each element in turn, ranges bind their ints.
+    pub fn compile_for_in_stmt(&mut self, stmt: &Statement) -> CompilerResult<()> {
+        let (item_name, iter_expr, for_stmt_list) = match stmt {
+            Statement::ForIn(item_name, iter_expr, for_stmt_list) => (item_name, iter_expr, for_stmt_list),
+            _ => return Err(CompilerError::Unknown)
+        };
+
+        let iter_type = self.check_expr_type(iter_expr)?;
+        let is_array = self.get_array_elem_type(&iter_type).is_some();
+        let is_range = iter_type == Type::Range;
+        if !is_array && !is_range {
+            self.check_iterator_type(&iter_type)?;
+        }
+
+        // The iterator lives in a block of its own, so it is dropped after the loop
+        let iter_fn_ctx = {
+            let fn_ctx = self.get_current_function()?;
+            FunctionContext::new_weak(fn_ctx)?
+        };
+        self.push_function_context(iter_fn_ctx);
+
+        // Not a valid identifier, so it can not clash with variables of the script
+        let iter_name = format!("for${}", self.fn_context_stack.len());
+        let iter_decl_stmt = Statement::VariableDecl(VariableDeclArgs {
+            var_type: iter_type,
+            name: iter_name.clone(),
+            assignment: iter_expr.clone()
+        });
+        self.compile_var_decl_stmt(&iter_decl_stmt)?;
+
+        let (while_expr, generated_stmt_list) = if is_array || is_range {
+            // Ranges count from their start up to their end, arrays from 0 up to their length
+            let iter_var = Expression::Variable(iter_name.clone());
+            let (start_expr, end_expr) = if is_range {
+                get_range_bounds(&iter_var)
+            } else {
+                (Expression::IntLiteral(0), Expression::Len(Box::new(iter_var.clone())))
+            };
+            let index_name = format!("{}$i", iter_name);
+            let index_decl_stmt = Statement::VariableDecl(VariableDeclArgs {
+                var_type: Type::Int,
+                name: index_name.clone(),
+                assignment: Box::new(start_expr)
+            });
+            self.compile_var_decl_stmt(&index_decl_stmt)?;
+
+            let index_var = || Box::new(Expression::Variable(index_name.clone()));
+            let item_expr = if is_range {
+                Expression::Variable(index_name.clone())
+            } else {
+                Expression::Index(Box::new(iter_var.clone()), index_var())
+            };
+            // The index is advanced before the body runs, so continue can not skip it
+            let generated_stmt_list = vec![
+                Statement::VariableDecl(VariableDeclArgs {
+                    var_type: Type::Auto,
+                    name: item_name.clone(),
+                    assignment: Box::new(item_expr)
+                }),
+                Statement::Increment(Expression::Variable(index_name.clone()), 1)
+            ];
+            let while_expr = Expression::LessThan(index_var(), Box::new(end_expr));
+            (while_expr, generated_stmt_list)
+        } else {
+            let iter_call = |fn_name: &str| {
+                Expression::MemberAccess(
+                    Box::new(Expression::Variable(iter_name.clone())),
+                    Box::new(Expression::Call(String::from(fn_name), Vec::new()))
+                )
+            };
+            let generated_stmt_list = vec![
+                Statement::VariableDecl(VariableDeclArgs {
+                    var_type: Type::Auto,
+                    name: item_name.clone(),
+                    assignment: Box::new(iter_call("next"))
+                })
+            ];
+            (iter_call("has_next"), generated_stmt_list)
+        };
+        self.compile_loop(&while_expr, &generated_stmt_list, for_stmt_list, true)?;
+
+        let iter_fn_ctx = self.pop_function_context()?;
+        self.compile_stack_cleanup_block(&iter_fn_ctx)?;
+
+        Ok(())
+    }
+
+    /// Returns the element type of an array or slice that is iterated by index
+    pub fn get_array_elem_type(&self, iter_type: &Type) -> Option<Type> {
+        match iter_type {
+            Type::Array(inner_type, _) => Some(inner_type.deref().clone()),
+            Type::Reference(ref_type) => {
+                match ref_type.deref() {
+                    Type::Array(inner_type, _) |
+                    Type::AutoArray(inner_type) => Some(inner_type.deref().clone()),
+                    _ => None
+                }
+            },
+            _ => None
+        }
+    }
+
+    /// Checks if a type implements the iterator protocol, returns the type of the items
+    pub fn check_iterator_type(&self, iter_type: &Type) -> CompilerResult<Type> {
+        let cont_def = match iter_type {
+            Type::Other(cont_name) => self.resolve_container(cont_name)?,
+            _ => return Err(CompilerError::NotIterable(iter_type.clone()))
+        };
+
+        let has_next_def = cont_def.get_member_function(&String::from("has_next"))
+            .map_err(|_| CompilerError::NotIterable(iter_type.clone()))?;
+        let next_def = cont_def.get_member_function(&String::from("next"))
+            .map_err(|_| CompilerError::NotIterable(iter_type.clone()))?;
+
+        for fn_def in [has_next_def, next_def].iter() {
+            if !fn_def.is_member_function() || fn_def.arguments.len() != 1 {
+                return Err(CompilerError::NotIterable(iter_type.clone()));
+            }
+        }
+        if has_next_def.ret_type != Type::Bool {
+            return Err(CompilerError::TypeMismatch(Type::Bool, has_next_def.ret_type.clone()));
+        }
+
+        Ok(next_def.ret_type.clone())
+    }
+
     /// Compiles a break statement
     pub fn compile_break_stmt(&mut self, stmt: &Statement) -> CompilerResult<()> {
         if *stmt != Statement::Break {
@@ -1418,36 +3249,210 @@ impl Compiler {
         Ok(())
     }
 
-    /// Compiles a continue statement
-    pub fn compile_continue_stmt(&mut self, stmt: &Statement) -> CompilerResult<()> {
-        if *stmt != Statement::Continue {
-            return Err(CompilerError::Unknown);
+    /// Checks a static assertion, no code is emitted for it
+    pub fn compile_static_assert_stmt(&mut self, stmt: &Statement) -> CompilerResult<()> {
+        let (assert_expr, message_expr) = match stmt {
+            Statement::StaticAssert(assert_expr, message_expr) => (assert_expr, message_expr),
+            _ => return Err(CompilerError::Unknown)
+        };
+
+        let holds = match self.eval_const_expr(assert_expr)? {
+            Expression::BoolLiteral(holds) => holds,
+            _ => return Err(CompilerError::TypeMismatch(Type::Bool, self.check_expr_type(assert_expr)?))
+        };
+        if holds {
+            return Ok(());
         }
 
-        // Compile the stack cleanup
-        self.compile_stack_loop()?;
+        let message = match message_expr {
+            Some(message_expr) => {
+                match self.eval_const_expr(message_expr)? {
+                    Expression::StringLiteral(string) => String::from(&string[1..string.len() - 1]),
+                    _ => return Err(CompilerError::TypeMismatch(Type::String, self.check_expr_type(message_expr)?))
+                }
+            },
+            None => format!("static assertion failed: {:?}", assert_expr)
+        };
+        Err(CompilerError::StaticAssertion(message))
+    }
 
-        let loop_start_pos = {
-            self.get_current_loop()?
-                .pos_start
+    /// Compiles an increment or decrement statement.
+    /// The int is changed in place through its address, with a single immediate add.
+    pub fn compile_increment_stmt(&mut self, stmt: &Statement) -> CompilerResult<()> {
+        let (target_expr, step) = match stmt {
+            Statement::Increment(target_expr, step) => (target_expr, *step),
+            _ => return Err(CompilerError::Unknown)
         };
 
-        // JMP to begin instr
-        let jmp_begin_instr = Instruction::new(Opcode::JMP)
-            .with_operand::<u64>(loop_start_pos as u64);
-        self.builder.push_instr(jmp_begin_instr);
-        
+        let target_type = self.check_expr_type(target_expr)?;
+        if target_type != Type::Int {
+            return Err(CompilerError::TypeMismatch(Type::Int, target_type));
+        }
+
+        self.compile_lhs_assign_expr(target_expr)?;
+        let addr_reg = self.get_last_register()?;
+        let value_reg = self.get_next_register()?;
+
+        let load_instr = Instruction::new(Opcode::MOVI_AR)
+            .with_operand::<u8>(addr_reg.clone().into())
+            .with_operand::<i16>(0)
+            .with_operand::<u8>(value_reg.clone().into());
+        let addi_instr = Instruction::new(Opcode::ADDI_I)
+            .with_operand::<u8>(value_reg.clone().into())
+            .with_operand::<i64>(step)
+            .with_operand::<u8>(value_reg.clone().into());
+        let store_instr = Instruction::new(Opcode::MOVI_RA)
+            .with_operand::<u8>(value_reg.into())
+            .with_operand::<u8>(addr_reg.into())
+            .with_operand::<i16>(0);
+        self.builder.push_instr(load_instr);
+        self.builder.push_instr(addi_instr);
+        self.builder.push_instr(store_instr);
+
         Ok(())
     }
 
-    /// Compiles a return statement
-    pub fn compile_return_stmt(&mut self, stmt: &Statement) -> CompilerResult<()> {
-        let return_expr_opt = match stmt {
-            Statement::Return(ret_expr) => ret_expr,
+    /// Compiles a throw statement, returning an error result from the current function
+    pub fn compile_throw_stmt(&mut self, stmt: &Statement) -> CompilerResult<()> {
+        let message_expr = match stmt {
+            Statement::Throw(message_expr) => message_expr,
             _ => return Err(CompilerError::Unknown)
         };
 
-        let mut return_expr_type = Type::Void;
+        let fn_ret_type = {
+            let fn_ctx = self.get_parent_function()?;
+            fn_ctx.get_ret_type()?
+        };
+        let value_type = match &fn_ret_type {
+            Type::Result(value_type) => value_type.deref().clone(),
+            _ => return Err(CompilerError::NotAResult(fn_ret_type))
+        };
+        let message_type = self.check_expr_type(message_expr)?;
+        if message_type != Type::String {
+            return Err(CompilerError::TypeMismatch(Type::String, message_type));
+        }
+
+        // The value of an error is left unset
+        let value_size = self.get_size_of_type(&value_type)?;
+        let stack_inc_instr = Instruction::new_inc_stack(value_size);
+        self.inc_stack(value_size)?;
+        self.builder.push_instr(stack_inc_instr);
+        self.compile_expr(message_expr)?;
+        if !is_call_result(message_expr) {
+            self.compile_rc_retain(&Type::String, Register::SP, -16)?;
+        }
+        self.compile_push_flag(false)?;
+
+        self.compile_stack_cleanup_return()?;
+        let ret_instr = Instruction::new(Opcode::RET);
+        self.builder.push_instr(ret_instr);
+
+        Ok(())
+    }
+
+    /// Compiles a spawn statement, the arguments are pushed like for a call and moved to the stack of the new task.
+    /// References could point into the stack of the spawning task, so they can not be passed.
+    pub fn compile_spawn_stmt(&mut self, stmt: &Statement) -> CompilerResult<()> {
+        let (fn_name, fn_arg_exprs) = match stmt {
+            Statement::Spawn(Expression::Call(fn_name, fn_args)) => (fn_name, fn_args),
+            _ => return Err(CompilerError::Unknown)
+        };
+
+        let fn_def = match self.resolve_function(fn_name) {
+            Ok(fn_def) => fn_def,
+            Err(error) => {
+                let generic_def = self.resolve_generic_function(fn_name)
+                    .map_err(|_| error)?;
+                self.instantiate_generic_function(&generic_def, fn_arg_exprs)?
+            }
+        };
+
+        if fn_arg_exprs.len() != fn_def.arguments.len() {
+            return Err(CompilerError::UnknownFunction(fn_name.clone()));
+        }
+        let has_ref_arg = fn_def.arguments.iter()
+            .any(|(_, arg_type)| matches!(arg_type, Type::Reference(_)));
+        if self.is_function_foreign(fn_def.uid)? || has_ref_arg {
+            return Err(CompilerError::InvalidSpawn(fn_name.clone()));
+        }
+
+        let before_call_stack_size = self.get_stack_size()?;
+
+        self.compile_call_args(&fn_def, fn_arg_exprs)?;
+
+        let args_size = self.get_stack_size()? - before_call_stack_size;
+        let spawn_instr = Instruction::new(Opcode::SPAWN)
+            .with_operand::<u64>(fn_def.uid)
+            .with_operand::<u32>(args_size as u32);
+        self.builder.push_instr(spawn_instr);
+
+        // The task owns the arguments now, only the stack space is given back
+        self.compile_call_cleanup(&Type::Void, before_call_stack_size)
+    }
+
+    /// Compiles a yield statement, the value is pushed and moved to the stack of the one resuming the coroutine
+    pub fn compile_yield_stmt(&mut self, stmt: &Statement) -> CompilerResult<()> {
+        let value_expr = match stmt {
+            Statement::Yield(value_expr) => value_expr,
+            _ => return Err(CompilerError::Unknown)
+        };
+
+        let value_type = match self.get_parent_function()?.get_ret_type()? {
+            Type::Coroutine(value_type) => value_type.deref().clone(),
+            _ => return Err(CompilerError::InvalidYield)
+        };
+        let expr_type = self.check_expr_type(value_expr)?;
+        self.check_assignable(&value_type, &expr_type)?;
+
+        if value_type.is_primitive() {
+            self.compile_push_expr(value_expr)?;
+        } else {
+            self.compile_assigned_expr(value_expr, &value_type)?;
+        }
+        let value_size = self.get_size_of_type(&value_type)?;
+        // The one resuming gets its own references to the yielded strings
+        if !is_call_result(value_expr) {
+            self.compile_rc_retain(&value_type, Register::SP, -(value_size as i16))?;
+        }
+
+        let yield_instr = Instruction::new(Opcode::YIELD)
+            .with_operand::<u32>(value_size as u32);
+        self.builder.push_instr(yield_instr);
+        self.dec_stack(value_size)?;
+
+        Ok(())
+    }
+
+    /// Compiles a continue statement
+    pub fn compile_continue_stmt(&mut self, stmt: &Statement) -> CompilerResult<()> {
+        if *stmt != Statement::Continue {
+            return Err(CompilerError::Unknown);
+        }
+
+        // Compile the stack cleanup
+        self.compile_stack_loop()?;
+
+        let loop_start_pos = {
+            self.get_current_loop()?
+                .pos_start
+        };
+
+        // JMP to begin instr
+        let jmp_begin_instr = Instruction::new(Opcode::JMP)
+            .with_operand::<u64>(loop_start_pos as u64);
+        self.builder.push_instr(jmp_begin_instr);
+        
+        Ok(())
+    }
+
+    /// Compiles a return statement
+    pub fn compile_return_stmt(&mut self, stmt: &Statement) -> CompilerResult<()> {
+        let return_expr_opt = match stmt {
+            Statement::Return(ret_expr) => ret_expr,
+            _ => return Err(CompilerError::Unknown)
+        };
+
+        let mut return_expr_type = Type::Void;
 
         if return_expr_opt.is_some() {
             let return_expr_ref = return_expr_opt.as_ref().unwrap();
@@ -1458,9 +3463,18 @@ impl Compiler {
             let fn_ctx = self.get_parent_function()?;
             fn_ctx.get_ret_type()?
         };
+        // Coroutines hand out their values by yielding, returning finishes them
+        let fn_ret_type = match fn_ret_type {
+            Type::Coroutine(_) => Type::Void,
+            fn_ret_type => fn_ret_type
+        };
 
-        if fn_ret_type != return_expr_type {
-            return Err(CompilerError::TypeMismatch(fn_ret_type, return_expr_type));
+        self.check_assignable(&fn_ret_type, &return_expr_type)?;
+
+        if let Some(return_expr) = return_expr_opt {
+            if fn_ret_type == return_expr_type && self.is_tail_call(return_expr)? {
+                return self.compile_tail_call_expr(return_expr);
+            }
         }
 
         if return_expr_opt.is_some() {
@@ -1468,12 +3482,18 @@ impl Compiler {
             let ret_expr_type = self.check_expr_type(return_expr)?;
             //println!("Ret expr type: {:?}", ret_expr_type);
             //println!("Ret expr: {:?}", return_expr);
-            self.compile_expr(return_expr)?;
+            self.compile_assigned_expr(return_expr, &fn_ret_type)?;
+
+            // The caller gets its own references to the returned strings
+            if !ret_expr_type.is_primitive() && !is_call_result(return_expr) {
+                let ret_size = self.get_size_of_type(&fn_ret_type)?;
+                self.compile_rc_retain(&fn_ret_type, Register::SP, -(ret_size as i16))?;
+            }
 
             // Move to R0 register if type is primitive
             if ret_expr_type.is_primitive() {
                 match fn_ret_type {
-                    Type::Int => {
+                    Type::Int | Type::Enum(_) => {
                         let last_reg = {
                             let fn_ctx = self.get_current_function()?;
                             fn_ctx.register_allocator.get_last_temp_register()?
@@ -1495,6 +3515,17 @@ impl Compiler {
                             .with_operand::<u8>(Register::R0.into());
                         self.builder.push_instr(mov_ret_instr);
                     },
+                    Type::Double => {
+                        let last_reg = {
+                            let fn_ctx = self.get_current_function()?;
+                            fn_ctx.register_allocator.get_last_temp_register()?
+                        };
+                        // Instruction for doing so
+                        let mov_ret_instr = Instruction::new(Opcode::MOVD)
+                            .with_operand::<u8>(last_reg.into())
+                            .with_operand::<u8>(Register::R0.into());
+                        self.builder.push_instr(mov_ret_instr);
+                    },
                     Type::Bool => {
                         let last_reg = {
                             let fn_ctx = self.get_current_function()?;
@@ -1506,6 +3537,17 @@ impl Compiler {
                             .with_operand::<u8>(Register::R0.into());
                         self.builder.push_instr(mov_ret_instr);
                     },
+                    Type::Char => {
+                        let last_reg = {
+                            let fn_ctx = self.get_current_function()?;
+                            fn_ctx.register_allocator.get_last_temp_register()?
+                        };
+                        // Instruction for doing so
+                        let mov_ret_instr = Instruction::new(Opcode::MOVI)
+                            .with_operand::<u8>(last_reg.into())
+                            .with_operand::<u8>(Register::R0.into());
+                        self.builder.push_instr(mov_ret_instr);
+                    },
                     Type::Reference(_) => {
                         let last_reg = {
                             let fn_ctx = self.get_current_function()?;
@@ -1532,6 +3574,202 @@ impl Compiler {
         Ok(())
     }
 
+    /// Checks if a returned expression can be compiled as a tail call.
+    /// The callee has to be a script function whose arguments fit into the ones of the current function.
+    /// References could point into the replaced frame, so calls taking them are excluded.
+    pub fn is_tail_call(&self, expr: &Expression) -> CompilerResult<bool> {
+        let (fn_name, fn_arg_exprs) = match expr {
+            Expression::Call(fn_name, fn_args) => (fn_name, fn_args),
+            _ => return Ok(false)
+        };
+        if self.get_closure_type(fn_name).is_some() || self.resolve_generic_function(fn_name).is_ok() {
+            return Ok(false);
+        }
+
+        let fn_def = self.resolve_function(fn_name)?;
+        if self.is_function_foreign(fn_def.uid)? || fn_arg_exprs.len() != fn_def.arguments.len() {
+            return Ok(false);
+        }
+
+        let mut args_size = 0;
+        for (_, arg_type) in fn_def.arguments.iter() {
+            if let Type::Reference(_) = arg_type {
+                return Ok(false);
+            }
+            args_size += self.get_size_of_type(arg_type)?;
+        }
+
+        let parent_fn_def = self.get_parent_function()?
+            .def.as_ref()
+            .ok_or(CompilerError::Unknown)?;
+        let mut parent_args_size = 0;
+        for (_, arg_type) in parent_fn_def.arguments.iter() {
+            parent_args_size += self.get_size_of_type(arg_type)?;
+        }
+
+        Ok(args_size <= parent_args_size)
+    }
+
+    /// Compiles a call in tail position. The arguments replace the current frame
+    /// and TCALL jumps to the callee, which then returns to our caller directly.
+    pub fn compile_tail_call_expr(&mut self, expr: &Expression) -> CompilerResult<()> {
+        let (fn_name, fn_arg_exprs) = match expr {
+            Expression::Call(fn_name, fn_args) => (fn_name, fn_args),
+            _ => return Err(CompilerError::Unknown)
+        };
+
+        let fn_def = self.resolve_function(fn_name)?;
+        let ret_type = fn_def.ret_type.clone();
+
+        let frame_size = self.get_frame_stack_size()?;
+        self.compile_call_args(&fn_def, fn_arg_exprs)?;
+        let args_size = self.get_frame_stack_size()? - frame_size;
+        // The replaced frame is gone after the call, the arguments hold their own references
+        self.compile_rc_release_locals(|ctx| !ctx.weak)?;
+
+        let tcall_instr = Instruction::new(Opcode::TCALL)
+            .with_operand::<u64>(fn_def.uid)
+            .with_operand::<u32>(frame_size as u32)
+            .with_operand::<u32>(args_size as u32);
+        self.builder.push_instr(tcall_instr);
+
+        // Same bookkeeping as a regular return, which leaves non-primitive return values on the stack
+        let stack_size = frame_size + args_size;
+        let ret_size = if ret_type.is_primitive() {
+            0
+        } else {
+            self.get_size_of_type(&ret_type)?
+        };
+        if stack_size > ret_size {
+            self.dec_stack(stack_size - ret_size)?;
+        } else {
+            self.inc_stack(ret_size - stack_size)?;
+        }
+
+        Ok(())
+    }
+
+    /// Gets the stack size of the current function, including the stack of its blocks
+    pub fn get_frame_stack_size(&self) -> CompilerResult<usize> {
+        let mut stack_size = 0;
+        for ctx in self.fn_context_stack.iter() {
+            stack_size += ctx.stack_size;
+            if !ctx.weak {
+                break;
+            }
+        }
+        Ok(stack_size)
+    }
+
+    /// Returns the offsets of the reference counted strings inside a value of the given type.
+    /// Optionals, results and callables are not counted, as parts of them may be unset.
+    pub fn get_string_offsets(&self, value_type: &Type) -> CompilerResult<Vec<usize>> {
+        self.collect_string_offsets(value_type, false)
+    }
+
+    /// Returns the offsets of all strings inside a value of the given type,
+    /// including the values and error messages of optionals and results, which may be unset
+    pub fn get_all_string_offsets(&self, value_type: &Type) -> CompilerResult<Vec<usize>> {
+        self.collect_string_offsets(value_type, true)
+    }
+
+    fn collect_string_offsets(&self, value_type: &Type, with_unset: bool) -> CompilerResult<Vec<usize>> {
+        let mut offsets = Vec::new();
+        match value_type {
+            Type::String => offsets.push(0),
+            Type::Array(inner_type, size) => {
+                let inner_offsets = self.collect_string_offsets(inner_type, with_unset)?;
+                let inner_size = self.get_size_of_type(inner_type)?;
+                for i in 0..*size {
+                    offsets.extend(inner_offsets.iter().map(|offset| i * inner_size + offset));
+                }
+            },
+            Type::Tuple(elem_types) => {
+                let mut elem_offset = 0;
+                for elem_type in elem_types.iter() {
+                    let elem_offsets = self.collect_string_offsets(elem_type, with_unset)?;
+                    offsets.extend(elem_offsets.iter().map(|offset| elem_offset + offset));
+                    elem_offset += self.get_size_of_type(elem_type)?;
+                }
+            },
+            Type::Other(type_name) if self.type_params.contains_key(type_name) => {
+                return self.collect_string_offsets(&self.type_params[type_name], with_unset);
+            },
+            Type::Other(cont_name) => {
+                let cont_def = self.resolve_container(cont_name)?;
+                for (member_name, member_type) in cont_def.member_variables.iter() {
+                    let member_offset = cont_def.get_member_offset(self, member_name)?;
+                    let member_offsets = self.collect_string_offsets(member_type, with_unset)?;
+                    offsets.extend(member_offsets.iter().map(|offset| member_offset + offset));
+                }
+            },
+            Type::Generic(_, _) => {
+                let mut instance_type = value_type.clone();
+                self.canonize_type(&mut instance_type)?;
+                return self.collect_string_offsets(&instance_type, with_unset);
+            },
+            Type::Optional(inner_type) if with_unset => {
+                return self.collect_string_offsets(inner_type, with_unset);
+            },
+            Type::Result(inner_type) if with_unset => {
+                // The error message follows the value
+                offsets = self.collect_string_offsets(inner_type, with_unset)?;
+                offsets.push(self.get_size_of_type(inner_type)?);
+            },
+            _ => {}
+        };
+        Ok(offsets)
+    }
+
+    /// Counts a reference to each string inside the value of the given type at [base_reg+offset]
+    pub fn compile_rc_retain(&mut self, value_type: &Type, base_reg: Register, offset: i16) -> CompilerResult<()> {
+        self.compile_rc_instrs(Opcode::INCREF, value_type, base_reg, offset)
+    }
+
+    /// Drops a reference to each string inside the value of the given type at [base_reg+offset]
+    pub fn compile_rc_release(&mut self, value_type: &Type, base_reg: Register, offset: i16) -> CompilerResult<()> {
+        self.compile_rc_instrs(Opcode::DECREF, value_type, base_reg, offset)
+    }
+
+    fn compile_rc_instrs(&mut self, opcode: Opcode, value_type: &Type, base_reg: Register, offset: i16) -> CompilerResult<()> {
+        if !self.ref_counting {
+            return Ok(());
+        }
+        for string_offset in self.get_string_offsets(value_type)? {
+            let rc_instr = Instruction::new(opcode.clone())
+                .with_operand::<u8>(base_reg.clone().into())
+                .with_operand::<i16>(offset + string_offset as i16);
+            self.builder.push_instr(rc_instr);
+        }
+        Ok(())
+    }
+
+    /// Returns the frame pointer offsets and types of the locals owning string references,
+    /// from the innermost context up to the first one for which until returns true, or the function itself
+    fn get_rc_slots<F: Fn(&FunctionContext) -> bool>(&self, until: F) -> CompilerResult<Vec<(i64, Type)>> {
+        let mut slots = Vec::new();
+        let mut ctx_end = self.get_frame_stack_size()? as i64;
+        for ctx in self.fn_context_stack.iter() {
+            let ctx_base = ctx_end - ctx.stack_size as i64;
+            for (var_pos, var_type) in ctx.rc_vars.iter() {
+                slots.push((ctx_base + var_pos, var_type.clone()));
+            }
+            if until(ctx) || !ctx.weak {
+                break;
+            }
+            ctx_end = ctx_base;
+        }
+        Ok(slots)
+    }
+
+    /// Releases the strings of the locals going out of scope, see get_rc_slots()
+    fn compile_rc_release_locals<F: Fn(&FunctionContext) -> bool>(&mut self, until: F) -> CompilerResult<()> {
+        for (fp_offset, var_type) in self.get_rc_slots(until)? {
+            self.compile_rc_release(&var_type, Register::FP, fp_offset as i16)?;
+        }
+        Ok(())
+    }
+
     /// Compiles a variable assign statement expression
     pub fn compile_var_assign_stmt_expr(&mut self, assign_expr: &Expression) -> CompilerResult<()> {
         let (lhs_expr, rhs_expr) = match assign_expr {
@@ -1586,16 +3824,14 @@ impl Compiler {
         let rhs_expr_type = self.check_expr_type(&rhs_expr)?;
 
         // Check for type mismatch
-        if lhs_expr_type != rhs_expr_type {
-            return Err(CompilerError::TypeMismatch(lhs_expr_type, rhs_expr_type));
-        }
+        self.check_assignable(&lhs_expr_type, &rhs_expr_type)?;
 
         let mut stack_size = self.get_stack_size()?;
 
         //println!("Stack size before assign expr: {}", stack_size);
 
         // Compile the right hand of this expression
-        self.compile_expr(&rhs_expr)?;
+        self.compile_assigned_expr(&rhs_expr, &lhs_expr_type)?;
         stack_size = self.get_stack_size()?;
         //println!("Stack size after assign expr: {}", stack_size);
 
@@ -1616,9 +3852,28 @@ impl Compiler {
             .with_operand::<u8>(lhs_reg.clone().into());
         self.builder.push_instr(mov_stack_instr);
 
+        // The new strings are referenced by the destination, the ones of an owning local are dropped
+        if !self.get_string_offsets(&lhs_expr_type)?.is_empty() {
+            let size = self.get_size_of_type(&lhs_expr_type)?;
+            if !is_call_result(&rhs_expr) {
+                self.compile_rc_retain(&lhs_expr_type, Register::SP, -(size as i16))?;
+            }
+            if let Expression::Variable(var_name) = &lhs_expr {
+                if self.is_local_var(var_name)? {
+                    let fp_offset = self.get_fp_offset_of_var(var_name)?;
+                    let is_owning = self.get_rc_slots(|_| false)?
+                        .iter()
+                        .any(|(slot_offset, _)| *slot_offset == fp_offset);
+                    if is_owning {
+                        self.compile_rc_release(&lhs_expr_type, lhs_reg.clone(), 0)?;
+                    }
+                }
+            }
+        }
+
         // Move the value to the assignment destination
-        let assign_instr = match rhs_expr_type {
-            Type::Int => {
+        let assign_instr = match lhs_expr_type {
+            Type::Int | Type::Enum(_) | Type::Coroutine(_) => {
                 //println!("Moving value from {:?} to the address in {:?}", rhs_reg, lhs_reg);
                 Instruction::new(Opcode::MOVI_RA)
                     .with_operand::<u8>(rhs_reg.into())
@@ -1631,15 +3886,27 @@ impl Compiler {
                     .with_operand::<u8>(lhs_reg.into())
                     .with_operand::<i16>(0)
             },
+            Type::Double => {
+                Instruction::new(Opcode::MOVD_RA)
+                    .with_operand::<u8>(rhs_reg.into())
+                    .with_operand::<u8>(lhs_reg.into())
+                    .with_operand::<i16>(0)
+            },
             Type::Bool => {
                 Instruction::new(Opcode::MOVB_RA)
                     .with_operand::<u8>(rhs_reg.into())
                     .with_operand::<u8>(lhs_reg.into())
                     .with_operand::<i16>(0)
             },
+            Type::Char => {
+                Instruction::new(Opcode::MOVC_RA)
+                    .with_operand::<u8>(rhs_reg.into())
+                    .with_operand::<u8>(lhs_reg.into())
+                    .with_operand::<i16>(0)
+            },
             Type::Reference(inner) => {
                 match inner.deref() {
-                    Type::AutoArray(_) => {
+                    Type::AutoArray(_) | Type::Interface(_) => {
                         Instruction::new(Opcode::MOVN_A)
                             .with_operand::<u8>(Register::SP.into())
                             .with_operand::<i16>(-16)
@@ -1656,7 +3923,7 @@ impl Compiler {
                 }
             },
             _ => {
-                let size = self.get_size_of_type(&rhs_expr_type)?;
+                let size = self.get_size_of_type(&lhs_expr_type)?;
                 Instruction::new(Opcode::MOVN_A)
                     .with_operand::<u8>(Register::SP.into())
                     .with_operand::<i16>(-(size as i16))
@@ -1671,21 +3938,37 @@ impl Compiler {
         //Err(CompilerError::Unimplemented(format!("Var assign compilation not implemented!")))
     }
 
+    /// Loads the address of a static variable as the target of an assignment
+    fn compile_lhs_static_expr(&mut self, static_def: StaticDef) -> CompilerResult<Type> {
+        if static_def.constant {
+            return Err(CompilerError::AssignToConstant(static_def.canonical_name));
+        }
+        let target_reg = self.get_next_register()?;
+        let lda_instr = Instruction::new(Opcode::LDA)
+            .with_operand::<u64>(static_def.address)
+            .with_operand::<u8>(target_reg.into());
+        self.builder.push_instr(lda_instr);
+        Ok(static_def.var_type)
+    }
+
     /// Compiles the left hand side of an assignment expression
     pub fn compile_lhs_assign_expr(&mut self, expr: &Expression) -> CompilerResult<Type> {
         let expr_type = match expr {
+            Expression::Variable(var_name) if !self.is_local_var(var_name)? => {
+                let static_def = self.resolve_static(var_name)?;
+                self.compile_lhs_static_expr(static_def)?
+            },
+            Expression::EnumValue(enum_name, variant) => {
+                let static_def = self.resolve_static(&format!("{}::{}", enum_name, variant))?;
+                self.compile_lhs_static_expr(static_def)?
+            },
             Expression::Variable(var_name) => {
-                let stack_offset = self.get_sp_offset_of_var(var_name)?.abs() as u64;
                 let target_reg = {
                     let fn_ctx = self.get_current_function_mut()?;
                     fn_ctx.register_allocator.get_temp_register()?
                 };
-                // Instruction for assign
-                let stack_offset_instr = Instruction::new(Opcode::SUBU_I)
-                    .with_operand::<u8>(Register::SP.into())
-                    .with_operand::<u64>(stack_offset)
-                    .with_operand::<u8>(target_reg.into());
-                self.builder.push_instr(stack_offset_instr);
+                // Address of the variable to assign to
+                self.compile_var_addr(var_name, target_reg)?;
                 self.get_type_of_var(var_name)?
             },
             Expression::MemberAccess(lhs_expr, rhs_expr) => {
@@ -1693,24 +3976,20 @@ impl Compiler {
                     Expression::Variable(var_name) => var_name,
                     _ => return Err(CompilerError::UnsupportedExpression(lhs_expr.deref().clone()))
                 };
-                let var_offset = self.get_sp_offset_of_var(var_name)?;
+                let var_offset = self.get_fp_offset_of_var(var_name)?;
                 let var_type = self.get_type_of_var(var_name)?;
                 let lhs_ptr_reg = self.get_next_register()?;
 
                 let cont_def = match var_type {
                     Type::Other(cont_name) => {
-                        let subui_instr = Instruction::new(Opcode::SUBU_I)
-                            .with_operand::<u8>(Register::SP.into())
-                            .with_operand::<u64>(var_offset.abs() as u64)
-                            .with_operand::<u8>(lhs_ptr_reg.into());
-                        self.builder.push_instr(subui_instr);
+                        self.compile_var_addr(var_name, lhs_ptr_reg)?;
                         self.resolve_container(&cont_name)?
                     },
                     Type::Reference(inner_type) => {
                         match inner_type.deref() {
                             Type::Other(cont_name) => {
                                 let mova_instr = Instruction::new(Opcode::MOVA_AR)
-                                    .with_operand::<u8>(Register::SP.into())
+                                    .with_operand::<u8>(Register::FP.into())
                                     .with_operand::<i16>(var_offset as i16)
                                     .with_operand::<u8>(lhs_ptr_reg.into());
                                 self.builder.push_instr(mova_instr);
@@ -1724,6 +4003,27 @@ impl Compiler {
 
                 self.compile_lhs_assign_member_expr(rhs_expr, &cont_def)?
             },
+            Expression::Index(base_expr, _) => {
+                // Strings are immutable
+                if self.check_expr_type(base_expr)? == Type::String {
+                    return Err(CompilerError::UnsupportedExpression(expr.clone()));
+                }
+                self.compile_elem_addr_expr(expr)?
+            },
+            Expression::Deref(op_expr) => {
+                // The reference already is the address to assign to
+                let ref_type = match self.check_expr_type(op_expr)? {
+                    Type::Reference(ref_type) => *ref_type,
+                    op_type => return Err(CompilerError::TypeMismatch(Type::Reference(Box::new(Type::Auto)), op_type))
+                };
+                match ref_type {
+                    Type::AutoArray(_) => return Err(CompilerError::CannotDerefSlice),
+                    Type::Interface(_) => return Err(CompilerError::UnsupportedExpression(expr.clone())),
+                    _ => {}
+                };
+                self.compile_expr(op_expr)?;
+                ref_type
+            },
             _ => return Err(CompilerError::UnsupportedExpression(expr.clone()))
         };
         Ok(expr_type)
@@ -1743,7 +4043,9 @@ impl Compiler {
                     .with_operand::<u8>(next_reg.into());
                 
                 self.builder.push_instr(addui_instr);
-                cont_def.get_member_type(var_name)
+                let mut member_type = cont_def.get_member_type(var_name)?;
+                self.canonize_type(&mut member_type)?;
+                Ok(member_type)
             },
             Expression::MemberAccess(lhs_expr, rhs_expr) => {
                 let var_name;
@@ -1792,6 +4094,9 @@ impl Compiler {
 
     /// Compiles an expression
     pub fn compile_expr(&mut self, expr: &Expression) -> CompilerResult<()> {
+        if let Some(promoted_expr) = self.promote_operands(expr)? {
+            return self.compile_expr(&promoted_expr);
+        }
         let expr_type = self.check_expr_type(expr)?;
         let expr_size = self.get_size_of_type(&expr_type)?;
         //println!("Expr size: {}", expr_size);
@@ -1803,9 +4108,18 @@ impl Compiler {
                     fn_ctx.register_allocator.get_temp_register()?
                 };
 
-                let ldi_instr = Instruction::new(Opcode::LDI)
-                    .with_operand::<i64>(*int)
-                    .with_operand::<u8>(reg.into());
+                let ldi_instr = match self.get_constant_addr(expr) {
+                    Some(const_addr) => {
+                        Instruction::new(Opcode::LDC)
+                            .with_operand::<u32>(const_addr)
+                            .with_operand::<u8>(reg.into())
+                    },
+                    None => {
+                        Instruction::new(Opcode::LDI)
+                            .with_operand::<i64>(*int)
+                            .with_operand::<u8>(reg.into())
+                    }
+                };
 
                 self.builder.push_instr(ldi_instr);
             },
@@ -1821,6 +4135,27 @@ impl Compiler {
                     
                 self.builder.push_instr(ldf_instr);
             },
+            Expression::DoubleLiteral(double) => {
+                let reg = {
+                    let fn_ctx = self.get_current_function_mut()?;
+                    fn_ctx.register_allocator.get_temp_register()?
+                };
+
+                let ldd_instr = match self.get_constant_addr(expr) {
+                    Some(const_addr) => {
+                        Instruction::new(Opcode::LDC)
+                            .with_operand::<u32>(const_addr)
+                            .with_operand::<u8>(reg.into())
+                    },
+                    None => {
+                        Instruction::new(Opcode::LDD)
+                            .with_operand::<f64>(*double)
+                            .with_operand::<u8>(reg.into())
+                    }
+                };
+                    
+                self.builder.push_instr(ldd_instr);
+            },
             Expression::BoolLiteral(boolean) => {
                 let reg = {
                     let fn_ctx = self.get_current_function_mut()?;
@@ -1833,6 +4168,35 @@ impl Compiler {
                     
                 self.builder.push_instr(ldb_instr);
             },
+            Expression::CharLiteral(character) => {
+                let reg = {
+                    let fn_ctx = self.get_current_function_mut()?;
+                    fn_ctx.register_allocator.get_temp_register()?
+                };
+
+                let ldi_instr = Instruction::new(Opcode::LDI)
+                    .with_operand::<i64>(*character as i64)
+                    .with_operand::<u8>(reg.into());
+
+                self.builder.push_instr(ldi_instr);
+            },
+            Expression::EnumValue(enum_name, variant) => {
+                if let Some(static_def) = self.resolve_assoc_constant(enum_name, variant)? {
+                    return self.compile_static_var_expr(&static_def);
+                }
+                let enum_def = self.resolve_enum(enum_name)?;
+                let value = enum_def.get_variant_value(variant)?;
+                let reg = {
+                    let fn_ctx = self.get_current_function_mut()?;
+                    fn_ctx.register_allocator.get_temp_register()?
+                };
+
+                let ldi_instr = Instruction::new(Opcode::LDI)
+                    .with_operand::<i64>(value)
+                    .with_operand::<u8>(reg.into());
+
+                self.builder.push_instr(ldi_instr);
+            },
             Expression::StringLiteral(string) => {
                 let string = String::from(&string[1..string.len() - 1]);
                 let (string_size, string_addr) = self.data.get_string_slice(&string);
@@ -1842,8 +4206,9 @@ impl Compiler {
                 let size_reg = self.get_next_register()?;
                 let addr_reg = self.get_next_register()?;
                 
-                let size_lda_instr = Instruction::new(Opcode::LDA)
-                    .with_operand(string_size)
+                // Only addresses are loaded with LDA, so they can be moved when linking
+                let size_ldi_instr = Instruction::new(Opcode::LDI)
+                    .with_operand::<i64>(string_size as i64)
                     .with_operand::<u8>(size_reg.clone().into());
                 let addr_lda_instr = Instruction::new(Opcode::LDA)
                     .with_operand(string_addr)
@@ -1858,18 +4223,47 @@ impl Compiler {
                     .with_operand::<i16>(-8);
 
                 self.builder.push_instr(stack_inc_instr);
-                self.builder.push_instr(size_lda_instr);
+                self.builder.push_instr(size_ldi_instr);
                 self.builder.push_instr(addr_lda_instr);
                 self.builder.push_instr(mov_size_instr);
                 self.builder.push_instr(mov_addr_instr);
             },
-            Expression::ContainerInstance(_, _) => {
-                self.compile_cont_instance_expr(expr)?;
+            Expression::SizeOf(_) | Expression::TypeOf(_) => {
+                let literal = self.eval_const_expr(expr)?;
+                self.compile_expr(&literal)?;
             },
-            Expression::Variable(_) => {
-                self.compile_var_expr(expr)?;
+            Expression::Slice(_, _, _) => {
+                self.compile_slice_expr(expr)?;
             },
-            Expression::Ref(op_expr) => {
+            Expression::Index(base_expr, index_expr) if self.check_expr_type(index_expr)? == Type::Range => {
+                let (start_expr, end_expr) = get_range_bounds(index_expr);
+                let slice_expr = Expression::Slice(base_expr.clone(), Some(Box::new(start_expr)), Some(Box::new(end_expr)));
+                self.compile_slice_expr(&slice_expr)?;
+            },
+            Expression::Index(_, _) => {
+                self.compile_index_expr(expr)?;
+            },
+            Expression::Range(_, _, _) => {
+                // Stored like an (int, int) tuple
+                let (start_expr, end_expr) = get_range_bounds(expr);
+                self.compile_expr(&Expression::Tuple(vec![start_expr, end_expr]))?;
+            },
+            Expression::Len(_) => {
+                self.compile_len_expr(expr)?;
+            },
+            Expression::CharAt(_, _) => {
+                self.compile_char_at_expr(expr)?;
+            },
+            Expression::Resume(_) => {
+                self.compile_resume_expr(expr)?;
+            },
+            Expression::ContainerInstance(_, _) => {
+                self.compile_cont_instance_expr(expr)?;
+            },
+            Expression::Variable(_) => {
+                self.compile_var_expr(expr)?;
+            },
+            Expression::Ref(op_expr) => {
                 self.compile_lhs_assign_expr(op_expr)?;
             },
             Expression::Deref(op_expr) => {
@@ -1880,7 +4274,7 @@ impl Compiler {
                     let last_reg = self.get_last_register()?;
                     let next_reg = self.get_next_register()?;
                     match ref_type {
-                        Type::Int => {
+                        Type::Int | Type::Enum(_) | Type::Coroutine(_) => {
                             let movi_instr = Instruction::new(Opcode::MOVI_AR)
                                 .with_operand::<u8>(last_reg.into())
                                 .with_operand::<i16>(0)
@@ -1894,6 +4288,13 @@ impl Compiler {
                                 .with_operand::<u8>(next_reg.into());
                             self.builder.push_instr(movf_instr);
                         },
+                        Type::Double => {
+                            let movd_instr = Instruction::new(Opcode::MOVD_AR)
+                                .with_operand::<u8>(last_reg.into())
+                                .with_operand::<i16>(0)
+                                .with_operand::<u8>(next_reg.into());
+                            self.builder.push_instr(movd_instr);
+                        },
                         Type::Bool => {
                             let movb_instr = Instruction::new(Opcode::MOVB_AR)
                                 .with_operand::<u8>(last_reg.into())
@@ -1901,6 +4302,13 @@ impl Compiler {
                                 .with_operand::<u8>(next_reg.into());
                             self.builder.push_instr(movb_instr);
                         },
+                        Type::Char => {
+                            let movc_instr = Instruction::new(Opcode::MOVC_AR)
+                                .with_operand::<u8>(last_reg.into())
+                                .with_operand::<i16>(0)
+                                .with_operand::<u8>(next_reg.into());
+                            self.builder.push_instr(movc_instr);
+                        },
                         Type::Reference(inner_type) => {
                             match inner_type.deref() {
                                 Type::AutoArray(_) => {
@@ -1924,7 +4332,7 @@ impl Compiler {
                 if expr_type.is_primitive() && !expr.is_member_call() {
                     let next_reg = self.get_next_register()?;
                     match expr_type {
-                        Type::Int => {
+                        Type::Int | Type::Enum(_) | Type::Coroutine(_) => {
                             //println!("Saving member access return value int into {:?}", next_reg);
                             let movi_instr = Instruction::new(Opcode::MOVI_AR)
                                 .with_operand::<u8>(last_reg.into())
@@ -1940,24 +4348,58 @@ impl Compiler {
                                 .with_operand::<u8>(next_reg.into());
                             self.builder.push_instr(movf_instr);
                         },
+                        Type::Double => {
+                            //println!("Saving member access return value int into {:?}", next_reg);
+                            let movd_instr = Instruction::new(Opcode::MOVD_AR)
+                                .with_operand::<u8>(last_reg.into())
+                                .with_operand::<i16>(0)
+                                .with_operand::<u8>(next_reg.into());
+                            self.builder.push_instr(movd_instr);
+                        },
+                        Type::Char => {
+                            //println!("Saving member access return value char into {:?}", next_reg);
+                            let movc_instr = Instruction::new(Opcode::MOVC_AR)
+                                .with_operand::<u8>(last_reg.into())
+                                .with_operand::<i16>(0)
+                                .with_operand::<u8>(next_reg.into());
+                            self.builder.push_instr(movc_instr);
+                        },
                         Type::Bool => {
-                        
+                            let movb_instr = Instruction::new(Opcode::MOVB_AR)
+                                .with_operand::<u8>(last_reg.into())
+                                .with_operand::<i16>(0)
+                                .with_operand::<u8>(next_reg.into());
+                            self.builder.push_instr(movb_instr);
                         },
                         Type::Reference(_) => {
-
+                            let mova_instr = Instruction::new(Opcode::MOVA_AR)
+                                .with_operand::<u8>(last_reg.into())
+                                .with_operand::<i16>(0)
+                                .with_operand::<u8>(next_reg.into());
+                            self.builder.push_instr(mova_instr);
                         },
                         _ => {}
                     };
+                } else if !expr.is_member_call() {
+                    // Non primitive members are copied on top of the stack
+                    let member_size = self.get_size_of_type(&expr_type)?;
+                    let stack_inc_instr = Instruction::new_inc_stack(member_size);
+                    self.inc_stack(member_size)?;
+                    let movn_instr = Instruction::new(Opcode::MOVN_A)
+                        .with_operand::<u8>(last_reg.into())
+                        .with_operand::<i16>(0)
+                        .with_operand::<u8>(Register::SP.into())
+                        .with_operand::<i16>(-(member_size as i16))
+                        .with_operand::<u32>(member_size as u32);
+                    self.builder.push_instr(stack_inc_instr);
+                    self.builder.push_instr(movn_instr);
                 }
                 //println!("Stack size after member access: {}", self.get_stack_size()?);
             },
-            Expression::Call(fn_name, _) => {
+            Expression::Call(_, _) => {
                 //println!("Stack size before call expr: {}", self.get_stack_size()?);
                 self.compile_call_expr(expr)?;
-                let fn_ret_type = {
-                    let fn_def = self.resolve_function(fn_name)?;
-                    fn_def.ret_type.clone()
-                };
+                let fn_ret_type = self.check_expr_type(expr)?;
                 if fn_ret_type.is_primitive() {
                     self.get_current_function_mut()?
                         .register_allocator
@@ -1965,6 +4407,9 @@ impl Compiler {
                 }
                 //println!("Stack size after call expr: {}", self.get_stack_size()?);
             },
+            Expression::Addition(lhs, _) if self.check_expr_type(lhs)? == Type::String => {
+                self.compile_concat_expr(expr)?;
+            },
             Expression::Addition(lhs, rhs) => {
                 let expr_type = self.check_expr_type(lhs)?;
                 self.compile_expr(lhs)?;
@@ -2002,6 +4447,17 @@ impl Compiler {
                             .with_operand::<u8>(res_reg.into());
                         self.builder.push_instr(addf_instr);
                     },
+                    Type::Double => {
+                        let res_reg = {
+                            let fn_ctx = self.get_current_function_mut()?;
+                            fn_ctx.register_allocator.get_temp_register()?
+                        };
+                        let addd_instr = Instruction::new(Opcode::ADDD)
+                            .with_operand::<u8>(lhs_reg.into())
+                            .with_operand::<u8>(rhs_reg.into())
+                            .with_operand::<u8>(res_reg.into());
+                        self.builder.push_instr(addd_instr);
+                    },
                     _ => return Err(CompilerError::UnsupportedExpression(lhs.deref().clone()))
                 };
             },
@@ -2040,6 +4496,17 @@ impl Compiler {
                             .with_operand::<u8>(res_reg.into());
                         self.builder.push_instr(subf_instr);
                     },
+                    Type::Double => {
+                        let res_reg = {
+                            let fn_ctx = self.get_current_function_mut()?;
+                            fn_ctx.register_allocator.get_temp_register()?
+                        };
+                        let subd_instr = Instruction::new(Opcode::SUBD)
+                            .with_operand::<u8>(lhs_reg.into())
+                            .with_operand::<u8>(rhs_reg.into())
+                            .with_operand::<u8>(res_reg.into());
+                        self.builder.push_instr(subd_instr);
+                    },
                     _ => return Err(CompilerError::UnsupportedExpression(lhs.deref().clone()))
                 };
             },
@@ -2078,6 +4545,17 @@ impl Compiler {
                             .with_operand::<u8>(res_reg.into());
                         self.builder.push_instr(mulf_instr);
                     },
+                    Type::Double => {
+                        let res_reg = {
+                            let fn_ctx = self.get_current_function_mut()?;
+                            fn_ctx.register_allocator.get_temp_register()?
+                        };
+                        let muld_instr = Instruction::new(Opcode::MULD)
+                            .with_operand::<u8>(lhs_reg.into())
+                            .with_operand::<u8>(rhs_reg.into())
+                            .with_operand::<u8>(res_reg.into());
+                        self.builder.push_instr(muld_instr);
+                    },
                     _ => return Err(CompilerError::UnsupportedExpression(lhs.deref().clone()))
                 };
             },
@@ -2116,10 +4594,21 @@ impl Compiler {
                             .with_operand::<u8>(res_reg.into());
                         self.builder.push_instr(divf_instr);
                     },
+                    Type::Double => {
+                        let res_reg = {
+                            let fn_ctx = self.get_current_function_mut()?;
+                            fn_ctx.register_allocator.get_temp_register()?
+                        };
+                        let divd_instr = Instruction::new(Opcode::DIVD)
+                            .with_operand::<u8>(lhs_reg.into())
+                            .with_operand::<u8>(rhs_reg.into())
+                            .with_operand::<u8>(res_reg.into());
+                        self.builder.push_instr(divd_instr);
+                    },
                     _ => return Err(CompilerError::UnsupportedExpression(lhs.deref().clone()))
                 };
             },
-            Expression::LessThan(lhs, rhs) => {
+            Expression::Modulo(lhs, rhs) => {
                 let expr_type = self.check_expr_type(lhs)?;
                 self.compile_expr(lhs)?;
                 let lhs_reg = {
@@ -2133,6 +4622,55 @@ impl Compiler {
                 };
                 match expr_type {
                     Type::Int => {
+                        let res_reg = {
+                            let fn_ctx = self.get_current_function_mut()?;
+                            fn_ctx.register_allocator.get_temp_register()?
+                        };
+                        let modi_instr = Instruction::new(Opcode::MODI)
+                            .with_operand::<u8>(lhs_reg.into())
+                            .with_operand::<u8>(rhs_reg.into())
+                            .with_operand::<u8>(res_reg.into());
+                        self.builder.push_instr(modi_instr);
+                    },
+                    Type::Float => {
+                        let res_reg = {
+                            let fn_ctx = self.get_current_function_mut()?;
+                            fn_ctx.register_allocator.get_temp_register()?
+                        };
+                        let modf_instr = Instruction::new(Opcode::MODF)
+                            .with_operand::<u8>(lhs_reg.into())
+                            .with_operand::<u8>(rhs_reg.into())
+                            .with_operand::<u8>(res_reg.into());
+                        self.builder.push_instr(modf_instr);
+                    },
+                    Type::Double => {
+                        let res_reg = {
+                            let fn_ctx = self.get_current_function_mut()?;
+                            fn_ctx.register_allocator.get_temp_register()?
+                        };
+                        let modd_instr = Instruction::new(Opcode::MODD)
+                            .with_operand::<u8>(lhs_reg.into())
+                            .with_operand::<u8>(rhs_reg.into())
+                            .with_operand::<u8>(res_reg.into());
+                        self.builder.push_instr(modd_instr);
+                    },
+                    _ => return Err(CompilerError::UnsupportedExpression(lhs.deref().clone()))
+                };
+            },
+            Expression::LessThan(lhs, rhs) => {
+                let expr_type = self.check_expr_type(lhs)?;
+                self.compile_expr(lhs)?;
+                let lhs_reg = {
+                    let fn_ctx = self.get_current_function()?;
+                    fn_ctx.register_allocator.get_last_temp_register()?
+                };
+                self.compile_expr(rhs)?;
+                let rhs_reg = {
+                    let fn_ctx = self.get_current_function()?;
+                    fn_ctx.register_allocator.get_last_temp_register()?
+                };
+                match expr_type {
+                    Type::Int | Type::Char => {
                         let res_reg = {
                             let fn_ctx = self.get_current_function_mut()?;
                             fn_ctx.register_allocator.get_temp_register()?
@@ -2154,6 +4692,17 @@ impl Compiler {
                             .with_operand::<u8>(res_reg.into());
                         self.builder.push_instr(ltf_instr);
                     },
+                    Type::Double => {
+                        let res_reg = {
+                            let fn_ctx = self.get_current_function_mut()?;
+                            fn_ctx.register_allocator.get_temp_register()?
+                        };
+                        let ltd_instr = Instruction::new(Opcode::LTD)
+                            .with_operand::<u8>(lhs_reg.into())
+                            .with_operand::<u8>(rhs_reg.into())
+                            .with_operand::<u8>(res_reg.into());
+                        self.builder.push_instr(ltd_instr);
+                    },
                     _ => return Err(CompilerError::UnsupportedExpression(lhs.deref().clone()))
                 };
             },
@@ -2171,7 +4720,7 @@ impl Compiler {
                     fn_ctx.register_allocator.get_last_temp_register()?
                 };
                 match expr_type {
-                    Type::Int => {
+                    Type::Int | Type::Char => {
                         let res_reg = {
                             let fn_ctx = self.get_current_function_mut()?;
                             fn_ctx.register_allocator.get_temp_register()?
@@ -2193,6 +4742,17 @@ impl Compiler {
                             .with_operand::<u8>(res_reg.into());
                         self.builder.push_instr(gtf_instr);
                     },
+                    Type::Double => {
+                        let res_reg = {
+                            let fn_ctx = self.get_current_function_mut()?;
+                            fn_ctx.register_allocator.get_temp_register()?
+                        };
+                        let gtd_instr = Instruction::new(Opcode::GTD)
+                            .with_operand::<u8>(lhs_reg.into())
+                            .with_operand::<u8>(rhs_reg.into())
+                            .with_operand::<u8>(res_reg.into());
+                        self.builder.push_instr(gtd_instr);
+                    },
                     _ => return Err(CompilerError::UnsupportedExpression(lhs.deref().clone()))
                 };
             },
@@ -2210,7 +4770,7 @@ impl Compiler {
                     fn_ctx.register_allocator.get_last_temp_register()?
                 };
                 match expr_type {
-                    Type::Int => {
+                    Type::Int | Type::Char => {
                         let res_reg = {
                             let fn_ctx = self.get_current_function_mut()?;
                             fn_ctx.register_allocator.get_temp_register()?
@@ -2232,6 +4792,17 @@ impl Compiler {
                             .with_operand::<u8>(res_reg.into());
                         self.builder.push_instr(lteqf_instr);
                     },
+                    Type::Double => {
+                        let res_reg = {
+                            let fn_ctx = self.get_current_function_mut()?;
+                            fn_ctx.register_allocator.get_temp_register()?
+                        };
+                        let lteqd_instr = Instruction::new(Opcode::LTEQD)
+                            .with_operand::<u8>(lhs_reg.into())
+                            .with_operand::<u8>(rhs_reg.into())
+                            .with_operand::<u8>(res_reg.into());
+                        self.builder.push_instr(lteqd_instr);
+                    },
                     _ => return Err(CompilerError::UnsupportedExpression(lhs.deref().clone()))
                 };
             },
@@ -2249,7 +4820,7 @@ impl Compiler {
                     fn_ctx.register_allocator.get_last_temp_register()?
                 };
                 match expr_type {
-                    Type::Int => {
+                    Type::Int | Type::Char => {
                         let res_reg = {
                             let fn_ctx = self.get_current_function_mut()?;
                             fn_ctx.register_allocator.get_temp_register()?
@@ -2271,10 +4842,27 @@ impl Compiler {
                             .with_operand::<u8>(res_reg.into());
                         self.builder.push_instr(gteqf_instr);
                     },
+                    Type::Double => {
+                        let res_reg = {
+                            let fn_ctx = self.get_current_function_mut()?;
+                            fn_ctx.register_allocator.get_temp_register()?
+                        };
+                        let gteqd_instr = Instruction::new(Opcode::GTEQD)
+                            .with_operand::<u8>(lhs_reg.into())
+                            .with_operand::<u8>(rhs_reg.into())
+                            .with_operand::<u8>(res_reg.into());
+                        self.builder.push_instr(gteqd_instr);
+                    },
                     _ => return Err(CompilerError::UnsupportedExpression(lhs.deref().clone()))
                 };
             },
 
+            Expression::Equals(lhs, rhs) if Self::is_null_check(lhs, rhs) => {
+                self.compile_null_check_expr(lhs, rhs, Opcode::EQI)?;
+            },
+            Expression::NotEquals(lhs, rhs) if Self::is_null_check(lhs, rhs) => {
+                self.compile_null_check_expr(lhs, rhs, Opcode::NEQI)?;
+            },
             Expression::Equals(lhs, rhs) => {
                 let expr_type = self.check_expr_type(lhs)?;
                 self.compile_expr(lhs)?;
@@ -2288,7 +4876,7 @@ impl Compiler {
                     fn_ctx.register_allocator.get_last_temp_register()?
                 };
                 match expr_type {
-                    Type::Int => {
+                    Type::Int | Type::Char | Type::Enum(_) => {
                         let res_reg = {
                             let fn_ctx = self.get_current_function_mut()?;
                             fn_ctx.register_allocator.get_temp_register()?
@@ -2310,6 +4898,17 @@ impl Compiler {
                             .with_operand::<u8>(res_reg.into());
                         self.builder.push_instr(eqf_instr);
                     },
+                    Type::Double => {
+                        let res_reg = {
+                            let fn_ctx = self.get_current_function_mut()?;
+                            fn_ctx.register_allocator.get_temp_register()?
+                        };
+                        let eqd_instr = Instruction::new(Opcode::EQD)
+                            .with_operand::<u8>(lhs_reg.into())
+                            .with_operand::<u8>(rhs_reg.into())
+                            .with_operand::<u8>(res_reg.into());
+                        self.builder.push_instr(eqd_instr);
+                    },
                     _ => return Err(CompilerError::UnsupportedExpression(lhs.deref().clone()))
                 };
             },
@@ -2326,7 +4925,7 @@ impl Compiler {
                     fn_ctx.register_allocator.get_last_temp_register()?
                 };
                 match expr_type {
-                    Type::Int => {
+                    Type::Int | Type::Char | Type::Enum(_) => {
                         let res_reg = {
                             let fn_ctx = self.get_current_function_mut()?;
                             fn_ctx.register_allocator.get_temp_register()?
@@ -2348,6 +4947,17 @@ impl Compiler {
                             .with_operand::<u8>(res_reg.into());
                         self.builder.push_instr(neqf_instr);
                     },
+                    Type::Double => {
+                        let res_reg = {
+                            let fn_ctx = self.get_current_function_mut()?;
+                            fn_ctx.register_allocator.get_temp_register()?
+                        };
+                        let neqd_instr = Instruction::new(Opcode::NEQD)
+                            .with_operand::<u8>(lhs_reg.into())
+                            .with_operand::<u8>(rhs_reg.into())
+                            .with_operand::<u8>(res_reg.into());
+                        self.builder.push_instr(neqd_instr);
+                    },
                     _ => return Err(CompilerError::UnsupportedExpression(lhs.deref().clone()))
                 };
             },
@@ -2364,29 +4974,88 @@ impl Compiler {
                     .with_operand::<u8>(target_reg.into());
                 self.builder.push_instr(not_instr);
             },
-            Expression::And(lhs, rhs) => {
-                self.compile_expr(lhs)?;
-                let lhs_reg = self.get_last_register()?;
-                self.compile_expr(rhs)?;
-                let rhs_reg = self.get_last_register()?;
+            Expression::Negation(op) => {
+                let op_type = self.check_expr_type(op)?;
+                let neg_opcode = match op_type {
+                    Type::Int => Opcode::NEGI,
+                    Type::Float => Opcode::NEGF,
+                    Type::Double => Opcode::NEGD,
+                    _ => return Err(CompilerError::UnsupportedExpression(op.deref().clone()))
+                };
+                self.compile_expr(op)?;
+                let op_reg = self.get_last_register()?;
                 let target_reg = self.get_next_register()?;
-                let and_instr = Instruction::new(Opcode::AND)
-                    .with_operand::<u8>(lhs_reg.into())
-                    .with_operand::<u8>(rhs_reg.into())
+                let neg_instr = Instruction::new(neg_opcode)
+                    .with_operand::<u8>(op_reg.into())
                     .with_operand::<u8>(target_reg.into());
-                self.builder.push_instr(and_instr);
+                self.builder.push_instr(neg_instr);
+            },
+            Expression::Cast(op, cast_type) if self.is_optional_of(op, cast_type)? => {
+                self.compile_unwrap_expr(op, cast_type)?;
+            },
+            Expression::Cast(op, cast_type) if matches!(self.check_expr_type(op)?, Type::Result(_)) => {
+                self.compile_result_cast_expr(op, cast_type)?;
+            },
+            Expression::Try(op) => {
+                self.compile_try_expr(op)?;
+            },
+            Expression::Lambda(_) => {
+                self.compile_lambda_expr(expr)?;
+            },
+            Expression::Cast(op, cast_type) => {
+                let op_type = self.check_expr_type(op)?;
+                let cast_opcode = match (&op_type, cast_type) {
+                    (Type::Int, Type::Float) => Some(Opcode::ITOF),
+                    (Type::Float, Type::Int) => Some(Opcode::FTOI),
+                    (Type::Int, Type::Double) => Some(Opcode::ITOD),
+                    (Type::Double, Type::Int) => Some(Opcode::DTOI),
+                    (Type::Float, Type::Double) => Some(Opcode::FTOD),
+                    (Type::Double, Type::Float) => Some(Opcode::DTOF),
+                    (Type::Bool, Type::Int) => Some(Opcode::BTOI),
+                    // Chars already live in registers as their code point, enums as their index
+                    (Type::Char, Type::Int) |
+                    (Type::Enum(_), Type::Int) => None,
+                    (op_type, cast_type) if op_type == cast_type && op_type.is_primitive() => None,
+                    _ => return Err(CompilerError::InvalidCast(op_type.clone(), cast_type.clone()))
+                };
+                self.compile_expr(op)?;
+                if let Some(cast_opcode) = cast_opcode {
+                    let op_reg = self.get_last_register()?;
+                    let target_reg = self.get_next_register()?;
+                    let cast_instr = Instruction::new(cast_opcode)
+                        .with_operand::<u8>(op_reg.into())
+                        .with_operand::<u8>(target_reg.into());
+                    self.builder.push_instr(cast_instr);
+                }
+            },
+            Expression::Tuple(elem_exprs) => {
+                for elem_expr in elem_exprs.iter() {
+                    self.compile_push_expr(elem_expr)?;
+                }
+            },
+            Expression::TupleIndex(base_expr, index) => {
+                self.compile_tuple_index_expr(base_expr, *index)?;
+            },
+            Expression::Array(elem_exprs) => {
+                for elem_expr in elem_exprs.iter() {
+                    self.compile_push_expr(elem_expr)?;
+                }
+            },
+            Expression::Conditional(cond_expr, then_expr, else_expr) => {
+                self.check_expr_type(expr)?;
+                self.compile_conditional_expr(cond_expr, then_expr, else_expr)?;
+            },
+            Expression::Block(stmt_list, value_expr) => {
+                self.check_expr_type(expr)?;
+                self.compile_block_expr(stmt_list, value_expr)?;
+            },
+            Expression::And(lhs, rhs) => {
+                // The rhs is skipped if the lhs is already false
+                self.compile_short_circuit_expr(lhs, rhs, Opcode::JMPF)?;
             },
             Expression::Or(lhs, rhs) => {
-                self.compile_expr(lhs)?;
-                let lhs_reg = self.get_last_register()?;
-                self.compile_expr(rhs)?;
-                let rhs_reg = self.get_last_register()?;
-                let target_reg = self.get_next_register()?;
-                let or_instr = Instruction::new(Opcode::OR)
-                    .with_operand::<u8>(lhs_reg.into())
-                    .with_operand::<u8>(rhs_reg.into())
-                    .with_operand::<u8>(target_reg.into());
-                self.builder.push_instr(or_instr);
+                // The rhs is skipped if the lhs is already true
+                self.compile_short_circuit_expr(lhs, rhs, Opcode::JMPT)?;
             },
             _ => return Err(CompilerError::UnsupportedExpression(expr.clone()))
         };
@@ -2405,7 +5074,7 @@ impl Compiler {
                     .with_operand::<i16>(-(expr_size as i16))
                     .with_operand::<u8>(Register::SP.into())
                     .with_operand::<i16>(-(stack_diff as i16))
-                    .with_operand::<u32>(16);
+                    .with_operand::<u32>(expr_size as u32);
                 self.builder.push_instr(mov_stack_instr);
             }
         }
@@ -2434,27 +5103,38 @@ impl Compiler {
             Expression::Variable(var_name) => {
                 // If variable is on stack
                 if cont_def.is_none() {
-                    let var_offset = self.get_sp_offset_of_var(var_name)?;
+                    let var_offset = self.get_fp_offset_of_var(var_name)?;
                     //println!("Member access of stack variable {}. Saving [SP]-{} into register {:?}.", var_name, var_offset.abs(), lhs_reg);
                     let var_type = self.get_type_of_var(var_name)?;
                     //println!("Compiling member access for var {}:{:?} at offset {}", var_name, var_type, var_offset);
                     match &var_type {
-                        Type::Other(cont_name) => {
+                        Type::Other(_) |
+                        Type::Int |
+                        Type::Float |
+                        Type::Double |
+                        Type::Bool |
+                        Type::Char |
+                        Type::String => {
                             //println!("Doing this by subtracting {} from SP.", var_offset.abs());
                             //println!("Converting [SP]-8 to pointer in register {:?}", lhs_reg);
-                            let subui_instr = Instruction::new(Opcode::SUBU_I)
-                                .with_operand::<u8>(Register::SP.into())
-                                .with_operand::<u64>(var_offset.abs() as u64)
-                                .with_operand::<u8>(lhs_reg.clone().into());
-                            self.builder.push_instr(subui_instr);
+                            self.compile_var_addr(var_name, lhs_reg.clone())?;
                         },
                         Type::Reference(inner_type) => {
                             match inner_type.deref() {
-                                Type::Other(cont_name) => {
+                                Type::Interface(iface_name) => {
+                                    return self.compile_iface_call_expr(var_name, iface_name, rhs_expr);
+                                },
+                                Type::Other(_) |
+                                Type::Int |
+                                Type::Float |
+                                Type::Double |
+                                Type::Bool |
+                                Type::Char |
+                                Type::String => {
                                     //println!("Doing this by moving pointer at [SP]-{}.", var_offset.abs());
                                     //println!("Saving pointer at [SP]-8 to register {:?}", lhs_reg);
                                     let mova_instr = Instruction::new(Opcode::MOVA_AR)
-                                        .with_operand::<u8>(Register::SP.into())
+                                        .with_operand::<u8>(Register::FP.into())
                                         .with_operand::<i16>(var_offset as i16)
                                         .with_operand::<u8>(lhs_reg.clone().into());
                                     self.builder.push_instr(mova_instr);
@@ -2501,17 +5181,7 @@ impl Compiler {
             _ => return Err(CompilerError::UnsupportedExpression(lhs_expr.deref().clone()))
         };
 
-        let cont_name = match &var_type {
-            Type::Other(cont_name) => cont_name,
-            Type::Reference(inner_type) => {
-                match inner_type.deref() {
-                    Type::Other(cont_name) => cont_name,
-                    _ => return Err(CompilerError::MemberAccessOnNonContainer)
-                }
-            },
-            _ => return Err(CompilerError::MemberAccessOnNonContainer)
-        };
-        let cont_def = self.resolve_container(cont_name)?;
+        let cont_def = self.resolve_impl_container(&var_type)?;
 
         match rhs_expr {
             Expression::Variable(member_name) => {
@@ -2543,29 +5213,14 @@ impl Compiler {
                 }
                 //println!("Stack size after member call expr: {}", self.get_stack_size()?);
             },
-            Expression::MemberAccess(member_expr, _) => {
+            Expression::MemberAccess(_, _) => {
+                // The lhs of the nested access is a member of this container
                 let rhs_reg = self.get_next_register()?;
-                let member_name = match member_expr.deref() {
-                    Expression::Variable(var_name) => var_name,
-                    _ => return Err(CompilerError::UnsupportedExpression(member_expr.deref().clone()))
-                };
-                let member_type = cont_def.get_member_type(member_name)?;
-                let cont_name = match &member_type {
-                    Type::Other(cont_name) => cont_name,
-                    Type::Reference(inner_type) => {
-                        match inner_type.deref() {
-                            Type::Other(cont_name) => cont_name,
-                            _ => return Err(CompilerError::MemberAccessOnNonContainer)
-                        }
-                    },
-                    _ => return Err(CompilerError::MemberAccessOnNonContainer)
-                };
-                let inner_cont_def = self.resolve_container(cont_name)?;
                 let mova_instr = Instruction::new(Opcode::MOVA)
                     .with_operand::<u8>(lhs_reg.into())
                     .with_operand::<u8>(rhs_reg.into());
                 self.builder.push_instr(mova_instr);
-                self.compile_member_access_expr(rhs_expr, Some(&inner_cont_def))?;
+                self.compile_member_access_expr(rhs_expr, Some(&cont_def))?;
             },
             _ => return Err(CompilerError::UnsupportedExpression(rhs_expr.clone()))
         };
@@ -2587,6 +5242,17 @@ impl Compiler {
 
         let fn_def = cont_def.get_member_function(fn_name)?;
 
+        if !fn_def.is_member_function() {
+            return Err(CompilerError::NotAMemberFunction(fn_name.clone()));
+        }
+        // The container is passed by reference, which a coroutine can not hold on to
+        if let Type::Coroutine(_) = fn_def.ret_type {
+            return Err(CompilerError::InvalidCoroutine(fn_name.clone()));
+        }
+
+        let member_name = format!("{}::{}", cont_def.name, fn_def.name);
+        self.check_visibility(cont_def.get_impl_path(fn_name), &member_name, fn_def.public)?;
+
         let fn_ret_size = self.get_size_of_type(&fn_def.ret_type)?;
 
         if fn_arg_exprs.len() + 1 != fn_def.arguments.len() {
@@ -2598,7 +5264,7 @@ impl Compiler {
                 .ok_or(CompilerError::Unknown)?;
             fn_arg.1.clone()
         };
-        let fn_args_first_arg_type = Type::Reference(Box::new(Type::Other(cont_def.canonical_name.clone())));
+        let fn_args_first_arg_type = cont_def.get_this_type();
         if fn_def_first_arg_type != fn_args_first_arg_type {
             return Err(CompilerError::TypeMismatch(fn_def_first_arg_type, fn_args_first_arg_type));
         }
@@ -2617,18 +5283,17 @@ impl Compiler {
         self.builder.push_instr(mova_instr);
 
         let mut stack_size = before_stack_size;
+        let callee_foreign = self.is_function_foreign(fn_def.uid)?;
 
         for i in 0..fn_arg_exprs.len() {
             let mut expr_type = self.check_expr_type(&fn_arg_exprs[i])?;
             self.canonize_type(&mut expr_type)?;
             let fn_arg_type = &fn_def.arguments[i + 1].1;
-
-            if *fn_arg_type != expr_type {
-                return Err(CompilerError::TypeMismatch(fn_arg_type.clone(), expr_type.clone()));
-            }
+            self.check_assignable(fn_arg_type, &expr_type)?;
+            expr_type = fn_arg_type.clone();
 
             // Compile this expr
-            self.compile_expr(&fn_arg_exprs[i])?;
+            self.compile_assigned_expr(&fn_arg_exprs[i], &expr_type)?;
 
             let curr_stack_size = self.get_stack_size()?;
 
@@ -2671,8 +5336,13 @@ impl Compiler {
                 self.inc_stack(size)?;
             }
 
+            // Script functions release their arguments when returning
+            if !callee_foreign && !is_call_result(&fn_arg_exprs[i]) {
+                self.compile_rc_retain(&expr_type, Register::SP, -(size as i16))?;
+            }
+
             let mov_instr_opt = match expr_type {
-                Type::Int => {
+                Type::Int | Type::Enum(_) | Type::Coroutine(_) => {
                     Some(Instruction::new(Opcode::MOVI_RA)
                         .with_operand::<u8>(last_reg.into())
                         .with_operand::<u8>(Register::SP.into())
@@ -2684,16 +5354,34 @@ impl Compiler {
                         .with_operand::<u8>(Register::SP.into())
                         .with_operand::<i16>(-(size as i16)))
                 },
+                Type::Double => {
+                    Some(Instruction::new(Opcode::MOVD_RA)
+                        .with_operand::<u8>(last_reg.into())
+                        .with_operand::<u8>(Register::SP.into())
+                        .with_operand::<i16>(-(size as i16)))
+                },
                 Type::Bool => {
                     Some(Instruction::new(Opcode::MOVB_RA)
                         .with_operand::<u8>(last_reg.into())
                         .with_operand::<u8>(Register::SP.into())
                         .with_operand::<i16>(-(size as i16)))
                 },
+                Type::Char => {
+                    Some(Instruction::new(Opcode::MOVC_RA)
+                        .with_operand::<u8>(last_reg.into())
+                        .with_operand::<u8>(Register::SP.into())
+                        .with_operand::<i16>(-(size as i16)))
+                },
                 Type::String => None,
+                Type::Tuple(_) => None,
+                Type::Range => None,
+                Type::Array(_, _) => None,
+                Type::Optional(_) => None,
+                Type::Result(_) => None,
+                Type::Function(_, _) => None,
                 Type::Reference(inner_type) => {
                     match inner_type.deref() {
-                        Type::AutoArray(_) => None,
+                        Type::AutoArray(_) | Type::Interface(_) => None,
                         _ => {
                             Some(
                                 Instruction::new(Opcode::MOVA_RA)
@@ -2749,338 +5437,2101 @@ impl Compiler {
         Ok(())
     }
 
-    /// Compiles a cont instance expression
-    pub fn compile_cont_instance_expr(&mut self, expr: &Expression) -> CompilerResult<()> {
-        //println!("Line 2638");
-        let (cont_name, cont_memper_map) = match expr {
-            Expression::ContainerInstance(a1, a2) => (a1, a2),
-            _ => return Err(CompilerError::Unknown)
+    /// Compiles a member call through an interface reference.
+    /// The object address is passed as "this", the function uid is looked up in the vtable at runtime.
+    pub fn compile_iface_call_expr(&mut self, var_name: &String, iface_name: &String, expr: &Expression) -> CompilerResult<()> {
+        let (fn_name, fn_arg_exprs) = match expr {
+            Expression::Call(fn_name, fn_args) => (fn_name, fn_args),
+            _ => return Err(CompilerError::MemberAccessOnNonContainer)
         };
-
-        // Sorts the member instance expressions in the correct way
-        let mut member_map_ordered = BTreeMap::new();
-
-        // Resolve the container definition
-        let cont_def = self.resolve_container(cont_name)?;
-
-        // Insert the expressions at the correct position
-        for (name, expr) in cont_memper_map.iter() {
-            // Retrieve position from container def
-            let index = cont_def.get_member_index(name)?;
-            member_map_ordered.insert(index, expr);
+        let iface_def = self.resolve_interface(iface_name)?;
+        let (fn_index, iface_fn_def) = iface_def.get_function(fn_name)?;
+        if fn_arg_exprs.len() + 1 != iface_fn_def.arguments.len() {
+            return Err(CompilerError::UnknownFunction(fn_name.clone()));
         }
+        let ret_type = iface_fn_def.ret_type.clone();
+        let fn_def = FunctionDef::new(fn_name.clone())
+            .with_ret_type(ret_type.clone())
+            .with_arguments(&iface_fn_def.arguments[1..]);
 
-        // Finally, compile the expressions in the correct order
-        for (_, expr) in member_map_ordered.iter() {
-            let expr_type = self.check_expr_type(expr)?;
-            self.compile_expr(expr)?;
-            // Special handling for copying register type values on the stack
-            let last_reg = self.get_last_register()?;
-            match expr_type {
-                Type::Int => {
-                    let stack_inc_instr = Instruction::new_inc_stack(8);
-                    self.inc_stack(8)?;
-                    let movi_instr = Instruction::new(Opcode::MOVI_RA)
-                        .with_operand::<u8>(last_reg.clone().into())
-                        .with_operand::<u8>(Register::SP.into())
-                        .with_operand::<i16>(-8);
-                    self.builder.push_instr(stack_inc_instr);
-                    self.builder.push_instr(movi_instr);
-                },
-                Type::Bool => {
-                    let stack_inc_instr = Instruction::new_inc_stack(1);
-                    self.inc_stack(1)?;
-                    let movb_instr = Instruction::new(Opcode::MOVB_RA)
-                        .with_operand::<u8>(last_reg.clone().into())
-                        .with_operand::<u8>(Register::SP.into())
-                        .with_operand::<i16>(-1);
-                    self.builder.push_instr(stack_inc_instr);
-                    self.builder.push_instr(movb_instr);
-                },
-                Type::Float => {
-                    let stack_inc_instr = Instruction::new_inc_stack(4);
-                    self.inc_stack(4)?;
-                    let movf_instr = Instruction::new(Opcode::MOVF_RA)
-                        .with_operand::<u8>(last_reg.clone().into())
-                        .with_operand::<u8>(Register::SP.into())
-                        .with_operand::<i16>(-4);
-                    self.builder.push_instr(stack_inc_instr);
-                    self.builder.push_instr(movf_instr);
-                },
-                Type::Reference(inner_type) => {
-                    match inner_type.deref() {
-                        Type::AutoArray(_) => {},
-                        _ => {
-                            let stack_inc_instr = Instruction::new_inc_stack(8);
-                            self.inc_stack(8)?;
-                            let mova_instr = Instruction::new(Opcode::MOVA_RA)
-                                .with_operand::<u8>(last_reg.clone().into())
-                                .with_operand::<u8>(Register::SP.into())
-                                .with_operand::<i16>(-8);
-                            self.builder.push_instr(stack_inc_instr);
-                            self.builder.push_instr(mova_instr);
-                        }
-                    };
-                },
-                _ => {}
-            };
+        let before_call_stack_size = self.get_stack_size()?;
+
+        // The object address is the first half of the interface reference
+        let var_offset = self.get_fp_offset_of_var(var_name)?;
+        let this_reg = self.get_next_register()?;
+        let mova_this_instr = Instruction::new(Opcode::MOVA_AR)
+            .with_operand::<u8>(Register::FP.into())
+            .with_operand::<i16>(var_offset as i16)
+            .with_operand::<u8>(this_reg.clone().into());
+        let stack_inc_instr = Instruction::new_inc_stack(8);
+        self.inc_stack(8)?;
+        let push_this_instr = Instruction::new(Opcode::MOVA_RA)
+            .with_operand::<u8>(this_reg.into())
+            .with_operand::<u8>(Register::SP.into())
+            .with_operand::<i16>(-8);
+        self.builder.push_instr(mova_this_instr);
+        self.builder.push_instr(stack_inc_instr);
+        self.builder.push_instr(push_this_instr);
+
+        self.compile_call_args(&fn_def, fn_arg_exprs)?;
+
+        // The vtable address is the second half
+        let var_offset = self.get_fp_offset_of_var(var_name)?;
+        let vtable_reg = self.get_next_register()?;
+        let uid_reg = self.get_next_register()?;
+        let mova_vtable_instr = Instruction::new(Opcode::MOVA_AR)
+            .with_operand::<u8>(Register::FP.into())
+            .with_operand::<i16>((var_offset + 8) as i16)
+            .with_operand::<u8>(vtable_reg.clone().into());
+        let movi_uid_instr = Instruction::new(Opcode::MOVI_AR)
+            .with_operand::<u8>(vtable_reg.into())
+            .with_operand::<i16>((fn_index * 8) as i16)
+            .with_operand::<u8>(uid_reg.clone().into());
+        let dcall_instr = Instruction::new(Opcode::DCALL)
+            .with_operand::<u8>(uid_reg.into());
+        self.builder.push_instr(mova_vtable_instr);
+        self.builder.push_instr(movi_uid_instr);
+        self.builder.push_instr(dcall_instr);
+
+        self.compile_call_cleanup(&ret_type, before_call_stack_size)?;
+        if ret_type.is_primitive() {
+            self.get_current_function_mut()?
+                .register_allocator
+                .force_temp_register(Register::R0);
         }
 
         Ok(())
     }
 
-    /// Compiles a call expresion
-    pub fn compile_call_expr(&mut self, expr: &Expression) -> CompilerResult<()> {
-        //println!("Line 2718");
-        let (fn_name, fn_arg_exprs) = match expr {
-            Expression::Call(fn_name, fn_args) => (fn_name, fn_args),
+    /// Compiles a slice expression. Like strings, the slice is a (length, address) pair on the stack,
+    /// pointing into the sliced value without copying it. The bounds are checked at runtime.
+    pub fn compile_slice_expr(&mut self, expr: &Expression) -> CompilerResult<()> {
+        let (base_expr, start_expr, end_expr) = match expr {
+            Expression::Slice(base_expr, start_expr, end_expr) => (base_expr, start_expr, end_expr),
             _ => return Err(CompilerError::Unknown)
         };
 
-        //println!("Compiling call expr");
-
-        let fn_def = self.resolve_function(fn_name)?;
+        let base_type = self.check_expr_type(base_expr)?;
+        let elem_size = self.get_slice_elem_size(&base_type)?;
 
-        let fn_ret_size = self.get_size_of_type(&fn_def.ret_type)?;
+        // Push the (length, address) pair of the sliced value
+        self.compile_push_slice_pair(base_expr, &base_type)?;
 
-        if fn_arg_exprs.len() != fn_def.arguments.len() {
-            return Err(CompilerError::UnknownFunction(fn_name.clone()));
+        // Compile the bounds
+        let start_reg = match start_expr {
+            Some(start_expr) => {
+                self.compile_expr(start_expr)?;
+                self.get_last_register()?
+            },
+            None => {
+                let start_reg = self.get_next_register()?;
+                let ldi_instr = Instruction::new(Opcode::LDI)
+                    .with_operand::<i64>(0)
+                    .with_operand::<u8>(start_reg.clone().into());
+                self.builder.push_instr(ldi_instr);
+                start_reg
+            }
+        };
+        let end_reg_opt = match end_expr {
+            Some(end_expr) => {
+                self.compile_expr(end_expr)?;
+                Some(self.get_last_register()?)
+            },
+            None => None
+        };
+        let len_reg = self.get_next_register()?;
+        let movi_len_instr = Instruction::new(Opcode::MOVI_AR)
+            .with_operand::<u8>(Register::SP.into())
+            .with_operand::<i16>(-16)
+            .with_operand::<u8>(len_reg.clone().into());
+        self.builder.push_instr(movi_len_instr);
+        let end_reg = end_reg_opt.unwrap_or_else(|| len_reg.clone());
+
+        // Substrings are checked to start and end on char boundaries as well
+        if base_type == Type::String {
+            let ssub_instr = Instruction::new(Opcode::SSUB)
+                .with_operand::<u8>(Register::SP.into())
+                .with_operand::<i16>(-16)
+                .with_operand::<u8>(start_reg.into())
+                .with_operand::<u8>(end_reg.into());
+            self.builder.push_instr(ssub_instr);
+            return Ok(());
         }
-        
-        let before_call_stack_size = self.get_stack_size()?;
-        let mut stack_size = before_call_stack_size;
 
-        for i in 0..fn_def.arguments.len() {
-            let mut expr_type = self.check_expr_type(&fn_arg_exprs[i])?;
-            self.canonize_type(&mut expr_type)?;
-            let fn_arg_type = &fn_def.arguments[i].1;
-            if *fn_arg_type != expr_type {
-                return Err(CompilerError::TypeMismatch(fn_arg_type.clone(), expr_type.clone()));
-            }
+        let chkb_instr = Instruction::new(Opcode::CHKB)
+            .with_operand::<u8>(start_reg.clone().into())
+            .with_operand::<u8>(end_reg.clone().into())
+            .with_operand::<u8>(len_reg.into());
+        self.builder.push_instr(chkb_instr);
+
+        // Length of the slice
+        let slice_len_reg = self.get_next_register()?;
+        let subi_instr = Instruction::new(Opcode::SUBI)
+            .with_operand::<u8>(end_reg.into())
+            .with_operand::<u8>(start_reg.clone().into())
+            .with_operand::<u8>(slice_len_reg.clone().into());
+        let movi_slice_len_instr = Instruction::new(Opcode::MOVI_RA)
+            .with_operand::<u8>(slice_len_reg.into())
+            .with_operand::<u8>(Register::SP.into())
+            .with_operand::<i16>(-16);
+        self.builder.push_instr(subi_instr);
+        self.builder.push_instr(movi_slice_len_instr);
+
+        // Address of the first element of the slice
+        let offset_reg = self.get_next_register()?;
+        let muli_instr = Instruction::new(Opcode::MULI_I)
+            .with_operand::<u8>(start_reg.into())
+            .with_operand::<i64>(elem_size as i64)
+            .with_operand::<u8>(offset_reg.clone().into());
+        let addr_reg = self.get_next_register()?;
+        let mova_addr_instr = Instruction::new(Opcode::MOVA_AR)
+            .with_operand::<u8>(Register::SP.into())
+            .with_operand::<i16>(-8)
+            .with_operand::<u8>(addr_reg.clone().into());
+        let addu_instr = Instruction::new(Opcode::ADDU)
+            .with_operand::<u8>(addr_reg.clone().into())
+            .with_operand::<u8>(offset_reg.into())
+            .with_operand::<u8>(addr_reg.clone().into());
+        let mova_slice_addr_instr = Instruction::new(Opcode::MOVA_RA)
+            .with_operand::<u8>(addr_reg.into())
+            .with_operand::<u8>(Register::SP.into())
+            .with_operand::<i16>(-8);
+        self.builder.push_instr(muli_instr);
+        self.builder.push_instr(mova_addr_instr);
+        self.builder.push_instr(addu_instr);
+        self.builder.push_instr(mova_slice_addr_instr);
 
-            //println!("Compiling call expr arg. Stack size: {}", self.get_stack_size()?);
-            //println!("Type of call expr: {:?}, size: {}", expr_type, self.get_size_of_type(&expr_type)?);
+        Ok(())
+    }
 
-            // Compile this expr
-            self.compile_expr(&fn_arg_exprs[i])?;
+    /// Pushes the (length, address) pair of a sliceable value.
+    /// Fixed size arrays without an address, like literals, are put on the stack first.
+    fn compile_push_slice_pair(&mut self, base_expr: &Expression, base_type: &Type) -> CompilerResult<()> {
+        let array_size = match base_type {
+            Type::Array(_, size) => {
+                if self.is_addressable(base_expr) {
+                    self.compile_lhs_assign_expr(base_expr)?;
+                } else {
+                    let array_byte_size = self.get_size_of_type(base_type)?;
+                    self.compile_expr(base_expr)?;
+                    let addr_reg = self.get_next_register()?;
+                    let subui_instr = Instruction::new(Opcode::SUBU_I)
+                        .with_operand::<u8>(Register::SP.into())
+                        .with_operand::<u64>(array_byte_size as u64)
+                        .with_operand::<u8>(addr_reg.into());
+                    self.builder.push_instr(subui_instr);
+                }
+                Some(*size)
+            },
+            Type::Reference(inner_type) => {
+                match inner_type.deref() {
+                    Type::Array(_, size) => {
+                        self.compile_expr(base_expr)?;
+                        Some(*size)
+                    },
+                    _ => {
+                        self.compile_expr(base_expr)?;
+                        None
+                    }
+                }
+            },
+            _ => {
+                self.compile_expr(base_expr)?;
+                None
+            }
+        };
+        if let Some(size) = array_size {
+            // The address register is reused for the length once it is saved
+            let addr_reg = self.get_last_register()?;
+            let stack_inc_instr = Instruction::new_inc_stack(16);
+            self.inc_stack(16)?;
+            let mov_addr_instr = Instruction::new(Opcode::MOVA_RA)
+                .with_operand::<u8>(addr_reg.clone().into())
+                .with_operand::<u8>(Register::SP.into())
+                .with_operand::<i16>(-8);
+            let ldi_instr = Instruction::new(Opcode::LDI)
+                .with_operand::<i64>(size as i64)
+                .with_operand::<u8>(addr_reg.clone().into());
+            let mov_len_instr = Instruction::new(Opcode::MOVI_RA)
+                .with_operand::<u8>(addr_reg.into())
+                .with_operand::<u8>(Register::SP.into())
+                .with_operand::<i16>(-16);
+            self.builder.push_instr(stack_inc_instr);
+            self.builder.push_instr(mov_addr_instr);
+            self.builder.push_instr(ldi_instr);
+            self.builder.push_instr(mov_len_instr);
+        }
+        Ok(())
+    }
 
+    /// Checks if compile_lhs_assign_expr() can compute the address of an expression
+    fn is_addressable(&self, expr: &Expression) -> bool {
+        match expr {
+            Expression::Variable(_) => true,
+            Expression::MemberAccess(lhs_expr, _) => {
+                !expr.is_member_call() && matches!(lhs_expr.deref(), Expression::Variable(_))
+            },
+            Expression::Index(_, _) => true,
+            _ => false
+        }
+    }
 
-            //println!("Compiled call expr arg. Stack size: {}", self.get_stack_size()?);
+    /// Computes the address of an indexed element into a register and returns the element type.
+    /// The index is checked at runtime.
+    fn compile_elem_addr_expr(&mut self, expr: &Expression) -> CompilerResult<Type> {
+        let (base_expr, index_expr) = match expr {
+            Expression::Index(base_expr, index_expr) => (base_expr, index_expr),
+            _ => return Err(CompilerError::Unknown)
+        };
 
-            let curr_stack_size = self.get_stack_size()?;
+        let elem_type = self.check_expr_type(expr)?;
+        let base_type = self.check_expr_type(base_expr)?;
+        let elem_size = self.get_slice_elem_size(&base_type)?;
 
-            let stack_diff = curr_stack_size - stack_size;
-            let mut pop_size = stack_diff;
+        // Push the (length, address) pair of the indexed value
+        self.compile_push_slice_pair(base_expr, &base_type)?;
 
-            let size = self.get_size_of_type(&expr_type)?;
+        self.compile_expr(index_expr)?;
+        let index_reg = self.get_last_register()?;
 
-            if !expr_type.is_primitive() {
-                pop_size -= size;
-                if pop_size > 0 {
-                    let mov_stack_instr = Instruction::new(Opcode::MOVN_A)
-                        .with_operand::<u8>(Register::SP.into())
-                        .with_operand::<i16>(-(size as i16))
-                        .with_operand::<u8>(Register::SP.into())
-                        .with_operand::<i16>(-(stack_diff as i16))
-                        .with_operand::<u32>(size as u32);
-                    self.builder.push_instr(mov_stack_instr);
-                }
-            }
-            if pop_size > 0 {
-                let stack_dec_instr = Instruction::new_dec_stack(pop_size);
-                self.dec_stack(pop_size)?;
-                self.builder.push_instr(stack_dec_instr);
-            }
+        // The registers are reused for the element address afterwards
+        let len_reg = self.get_next_register()?;
+        let end_reg = self.get_next_register()?;
+        let addi_instr = Instruction::new(Opcode::ADDI_I)
+            .with_operand::<u8>(index_reg.clone().into())
+            .with_operand::<i64>(1)
+            .with_operand::<u8>(end_reg.clone().into());
+        let movi_len_instr = Instruction::new(Opcode::MOVI_AR)
+            .with_operand::<u8>(Register::SP.into())
+            .with_operand::<i16>(-16)
+            .with_operand::<u8>(len_reg.clone().into());
+        let chkb_instr = Instruction::new(Opcode::CHKB)
+            .with_operand::<u8>(index_reg.clone().into())
+            .with_operand::<u8>(end_reg.clone().into())
+            .with_operand::<u8>(len_reg.clone().into());
+        self.builder.push_instr(addi_instr);
+        self.builder.push_instr(movi_len_instr);
+        self.builder.push_instr(chkb_instr);
+
+        let offset_reg = len_reg;
+        let addr_reg = end_reg;
+        let muli_instr = Instruction::new(Opcode::MULI_I)
+            .with_operand::<u8>(index_reg.into())
+            .with_operand::<i64>(elem_size as i64)
+            .with_operand::<u8>(offset_reg.clone().into());
+        let mova_addr_instr = Instruction::new(Opcode::MOVA_AR)
+            .with_operand::<u8>(Register::SP.into())
+            .with_operand::<i16>(-8)
+            .with_operand::<u8>(addr_reg.clone().into());
+        let addu_instr = Instruction::new(Opcode::ADDU)
+            .with_operand::<u8>(addr_reg.clone().into())
+            .with_operand::<u8>(offset_reg.into())
+            .with_operand::<u8>(addr_reg.into());
+        self.builder.push_instr(muli_instr);
+        self.builder.push_instr(mova_addr_instr);
+        self.builder.push_instr(addu_instr);
+
+        Ok(elem_type)
+    }
 
-            let last_reg = {
-                self.get_current_function()?
-                    .register_allocator
-                    .get_last_temp_register()?
-            };
+    /// Compiles an index expression, resulting in the element at that index.
+    /// Strings are indexed by bytes.
+    pub fn compile_index_expr(&mut self, expr: &Expression) -> CompilerResult<()> {
+        let base_expr = match expr {
+            Expression::Index(base_expr, _) => base_expr,
+            _ => return Err(CompilerError::Unknown)
+        };
 
-            //println!("CHECKING IF EXPR TYPE IS PRIMITIVE");
+        let is_string = self.check_expr_type(base_expr)? == Type::String;
+        let elem_type = self.compile_elem_addr_expr(expr)?;
+        let addr_reg = self.get_last_register()?;
 
-            if expr_type.is_primitive() {
-                //println!("incrementing stack for primitive type arg");
-                let stack_instr = Instruction::new_inc_stack(size);
-                self.builder.push_instr(stack_instr);
-                self.inc_stack(size)?;
+        let load_opcode = if is_string {
+            Some(Opcode::MOVU8_AR)
+        } else {
+            get_load_opcode(&elem_type)
+        };
+
+        match load_opcode {
+            Some(load_opcode) => {
+                // The element replaces its address in the same register
+                let load_instr = Instruction::new(load_opcode)
+                    .with_operand::<u8>(addr_reg.clone().into())
+                    .with_operand::<i16>(0)
+                    .with_operand::<u8>(addr_reg.into());
+                self.builder.push_instr(load_instr);
+            },
+            None => {
+                // Copy the element on top of the stack
+                let elem_size = self.get_size_of_type(&elem_type)?;
+                let stack_inc_instr = Instruction::new_inc_stack(elem_size);
+                self.inc_stack(elem_size)?;
+                let movn_instr = Instruction::new(Opcode::MOVN_A)
+                    .with_operand::<u8>(addr_reg.into())
+                    .with_operand::<i16>(0)
+                    .with_operand::<u8>(Register::SP.into())
+                    .with_operand::<i16>(-(elem_size as i16))
+                    .with_operand::<u32>(elem_size as u32);
+                self.builder.push_instr(stack_inc_instr);
+                self.builder.push_instr(movn_instr);
             }
+        };
 
-            let mov_instr_opt = match expr_type {
-                Type::Int => {
-                    Some(Instruction::new(Opcode::MOVI_RA)
-                        .with_operand::<u8>(last_reg.into())
-                        .with_operand::<u8>(Register::SP.into())
-                        .with_operand::<i16>(-(size as i16)))
-                },
-                Type::Float => {
-                    Some(Instruction::new(Opcode::MOVF_RA)
-                        .with_operand::<u8>(last_reg.into())
-                        .with_operand::<u8>(Register::SP.into())
-                        .with_operand::<i16>(-(size as i16)))
-                },
-                Type::Bool => {
-                    Some(Instruction::new(Opcode::MOVB_RA)
-                        .with_operand::<u8>(last_reg.into())
-                        .with_operand::<u8>(Register::SP.into())
-                        .with_operand::<i16>(-(size as i16)))
-                },
-                Type::String => None,
-                Type::Reference(inner_type) => {
-                    match inner_type.deref() {
-                        Type::AutoArray(_) => None,
-                        _ => {
-                            Some(
-                                Instruction::new(Opcode::MOVA_RA)
-                                    .with_operand::<u8>(last_reg.into())
-                                    .with_operand::<u8>(Register::SP.into())
-                                    .with_operand::<i16>(-(size as i16))
-                            )
-                        }
-                    }
-                },
-                _ => {
-                    //println!("Error in compile_call_expr()!");
-                    return Err(CompilerError::UnknownType(expr_type));
-                }
-            };
+        Ok(())
+    }
 
-            if mov_instr_opt.is_some() {
-                self.builder.push_instr(mov_instr_opt.unwrap());
-            }
+    /// Compiles a len() intrinsic. Lengths of fixed size arrays are known at compile time,
+    /// strings and slices store theirs on the stack.
+    pub fn compile_len_expr(&mut self, expr: &Expression) -> CompilerResult<()> {
+        let base_expr = match expr {
+            Expression::Len(base_expr) => base_expr,
+            _ => return Err(CompilerError::Unknown)
+        };
 
-            stack_size = self.get_stack_size()?;
-        }
+        let base_type = self.check_expr_type(base_expr)?;
+        let array_size = match &base_type {
+            Type::Array(_, size) => Some(*size),
+            Type::Reference(inner_type) => {
+                match inner_type.deref() {
+                    Type::Array(_, size) => Some(*size),
+                    _ => None
+                }
+            },
+            _ => None
+        };
 
-        let call_instr = Instruction::new(Opcode::CALL)
-            .with_operand::<u64>(fn_def.uid);
-        self.builder.push_instr(call_instr);
-        if !fn_def.ret_type.is_primitive() {
-            self.inc_stack(fn_ret_size)?;
+        if let Some(size) = array_size {
+            let len_reg = self.get_next_register()?;
+            let ldi_instr = Instruction::new(Opcode::LDI)
+                .with_operand::<i64>(size as i64)
+                .with_operand::<u8>(len_reg.into());
+            self.builder.push_instr(ldi_instr);
+            return Ok(());
         }
 
-        let stack_diff = self.get_stack_size()? - before_call_stack_size;
-        //println!("Stack diff after args + call: {}", stack_diff);
-        let mut pop_size = stack_diff;
+        // Push the (length, address) pair
+        self.compile_expr(base_expr)?;
 
-        if !fn_def.ret_type.is_primitive() {
-            let mov_stack_instr = Instruction::new(Opcode::MOVN_A)
-                .with_operand::<u8>(Register::SP.into())
-                .with_operand::<i16>(-(fn_ret_size as i16))
-                .with_operand::<u8>(Register::SP.into())
-                .with_operand::<i16>(-(stack_diff as i16))
-                .with_operand::<u32>(fn_ret_size as u32);
-            pop_size -= fn_ret_size;
-            self.builder.push_instr(mov_stack_instr);
-        }
-        
-        let stack_dec_instr = Instruction::new_dec_stack(pop_size);
-        self.dec_stack(pop_size)?;
-        self.builder.push_instr(stack_dec_instr);
+        let len_opcode = if base_type == Type::String {
+            Opcode::SLEN
+        } else {
+            Opcode::MOVI_AR
+        };
+        let len_reg = self.get_next_register()?;
+        let len_instr = Instruction::new(len_opcode)
+            .with_operand::<u8>(Register::SP.into())
+            .with_operand::<i16>(-16)
+            .with_operand::<u8>(len_reg.into());
+        self.builder.push_instr(len_instr);
 
         Ok(())
     }
 
-    /// Compiles a variable expression
-    pub fn compile_var_expr(&mut self, expr: &Expression) -> CompilerResult<()> {
-        let var_name = match expr {
-            Expression::Variable(var_name) => var_name,
+    /// Compiles a char_at() intrinsic, decoding the char starting at a byte index of a string
+    pub fn compile_char_at_expr(&mut self, expr: &Expression) -> CompilerResult<()> {
+        let (base_expr, index_expr) = match expr {
+            Expression::CharAt(base_expr, index_expr) => (base_expr, index_expr),
             _ => return Err(CompilerError::Unknown)
         };
 
-        //println!("Compiling var expr");
+        // Push the (length, address) pair
+        self.compile_expr(base_expr)?;
+        self.compile_expr(index_expr)?;
+        let index_reg = self.get_last_register()?;
 
-        let var_type = self.get_type_of_var(var_name)?;
-        let mut var_offset = self.get_sp_offset_of_var(var_name)?;
-        match var_type {
-            Type::Int => {
-                let reg = {
-                    let fn_ctx = self.get_current_function_mut()?;
-                    fn_ctx.register_allocator.get_temp_register()?
-                };
-                let movi_instr = Instruction::new(Opcode::MOVI_AR)
-                    .with_operand::<u8>(Register::SP.into())
-                    .with_operand::<i16>(var_offset as i16)
-                    .with_operand::<u8>(reg.into());
-                self.builder.push_instr(movi_instr);
+        let char_reg = self.get_next_register()?;
+        let schr_instr = Instruction::new(Opcode::SCHR)
+            .with_operand::<u8>(Register::SP.into())
+            .with_operand::<i16>(-16)
+            .with_operand::<u8>(index_reg.into())
+            .with_operand::<u8>(char_reg.into());
+        self.builder.push_instr(schr_instr);
+
+        Ok(())
+    }
+
+    /// Compiles the concatenation of two strings into a new one on the heap
+    pub fn compile_concat_expr(&mut self, expr: &Expression) -> CompilerResult<()> {
+        let (lhs, rhs) = match expr {
+            Expression::Addition(lhs, rhs) => (lhs, rhs),
+            _ => return Err(CompilerError::Unknown)
+        };
+        let rhs_type = self.check_expr_type(rhs)?;
+        if rhs_type != Type::String {
+            return Err(CompilerError::TypeMismatch(Type::String, rhs_type));
+        }
+
+        // Both strings are followed by room for the result
+        self.compile_expr(lhs)?;
+        self.compile_expr(rhs)?;
+        let stack_inc_instr = Instruction::new_inc_stack(16);
+        self.inc_stack(16)?;
+        let scat_instr = Instruction::new(Opcode::SCAT)
+            .with_operand::<u8>(Register::SP.into())
+            .with_operand::<i16>(-48);
+        self.builder.push_instr(stack_inc_instr);
+        self.builder.push_instr(scat_instr);
+
+        // Operands owning their strings are done with them
+        if is_call_result(lhs) {
+            self.compile_rc_release(&Type::String, Register::SP, -48)?;
+        }
+        if is_call_result(rhs) {
+            self.compile_rc_release(&Type::String, Register::SP, -32)?;
+        }
+
+        let movn_instr = Instruction::new(Opcode::MOVN_A)
+            .with_operand::<u8>(Register::SP.into())
+            .with_operand::<i16>(-16)
+            .with_operand::<u8>(Register::SP.into())
+            .with_operand::<i16>(-48)
+            .with_operand::<u32>(16);
+        let stack_dec_instr = Instruction::new_dec_stack(32);
+        self.dec_stack(32)?;
+        self.builder.push_instr(movn_instr);
+        self.builder.push_instr(stack_dec_instr);
+
+        Ok(())
+    }
+
+    /// Returns the size of the elements of a sliceable type, strings are sliced by bytes
+    pub fn get_slice_elem_size(&self, base_type: &Type) -> CompilerResult<usize> {
+        match base_type {
+            Type::String => Ok(1),
+            Type::Array(inner_type, _) => self.get_size_of_type(inner_type),
+            Type::Reference(ref_type) => {
+                match ref_type.deref() {
+                    Type::Array(inner_type, _) |
+                    Type::AutoArray(inner_type) => self.get_size_of_type(inner_type),
+                    _ => Err(CompilerError::NotIndexable(base_type.clone()))
+                }
+            },
+            _ => Err(CompilerError::NotIndexable(base_type.clone()))
+        }
+    }
+
+    /// Resolves the container of a cont instance expression.
+    /// The type arguments of a generic container are inferred from the member values.
+    pub fn resolve_cont_instance_def(&self, cont_name: &String, member_map: &HashMap<String, Expression>) -> CompilerResult<ContainerDef> {
+        let cont_def = self.resolve_container(cont_name)?;
+        if cont_def.generics.is_empty() {
+            return Ok(cont_def);
+        }
+        let mut type_params = HashMap::new();
+        for (member_name, member_expr) in member_map.iter() {
+            let member_type = cont_def.get_member_type(member_name)?;
+            let expr_type = self.check_expr_type(member_expr)?;
+            bind_type_params(&cont_def.generics, &member_type, &expr_type, &mut type_params, &self.container_instances.borrow())?;
+        }
+        let mut type_args = Vec::new();
+        for type_param in cont_def.generics.iter() {
+            let type_arg = type_params.remove(type_param)
+                .ok_or_else(|| CompilerError::CannotInferType(type_param.clone()))?;
+            type_args.push(type_arg);
+        }
+        let instance_type = self.instantiate_generic_container(&cont_def.canonical_name, &type_args)?;
+        match instance_type {
+            Type::Other(instance_name) => self.resolve_container(&instance_name),
+            _ => Err(CompilerError::Unknown)
+        }
+    }
+
+    /// Compiles a cont instance expression
+    pub fn compile_cont_instance_expr(&mut self, expr: &Expression) -> CompilerResult<()> {
+        //println!("Line 2638");
+        let (cont_name, cont_memper_map) = match expr {
+            Expression::ContainerInstance(a1, a2) => (a1, a2),
+            _ => return Err(CompilerError::Unknown)
+        };
+
+        // Sorts the member instance expressions in the correct way
+        let mut member_map_ordered = BTreeMap::new();
+
+        // Resolve the container definition
+        let cont_def = self.resolve_cont_instance_def(cont_name, cont_memper_map)?;
+
+        // Insert the expressions at the correct position
+        for (name, expr) in cont_memper_map.iter() {
+            // Retrieve position from container def
+            let index = cont_def.get_member_index(name)?;
+            member_map_ordered.insert(index, expr);
+        }
+
+        // Instances of literals only are copied from the constant pool
+        if let Some(bytes) = self.get_constant_instance_bytes(&cont_def, cont_memper_map)? {
+            return self.compile_constant_copy(&bytes);
+        }
+
+        // Finally, compile the expressions in the correct order
+        for (_, expr) in member_map_ordered.iter() {
+            self.compile_push_expr(expr)?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds an int or double literal taking all 8 bytes to the constant pool and returns its address.
+    /// Smaller values are common and cheaper to embed in the instruction loading them, they return None.
+    fn get_constant_addr(&mut self, expr: &Expression) -> Option<u32> {
+        let bytes = match expr {
+            Expression::IntLiteral(int) if i32::try_from(*int).is_err() => int.to_le_bytes(),
+            Expression::DoubleLiteral(double) if (*double as f32) as f64 != *double => double.to_le_bytes(),
+            _ => return None
+        };
+        u32::try_from(self.data.add_constant(&bytes)).ok()
+    }
+
+    /// Gets the bytes of a container instance whose members are all literals of their types, None otherwise
+    fn get_constant_instance_bytes(&self, cont_def: &ContainerDef, member_map: &HashMap<String, Expression>) -> CompilerResult<Option<Vec<u8>>> {
+        // A single member is cheaper to push than to copy
+        if member_map.len() < 2 || member_map.len() != cont_def.member_variables.len() {
+            return Ok(None);
+        }
+        let mut bytes = vec![0; cont_def.get_size(self)?];
+        for (name, expr) in member_map.iter() {
+            let member_type = cont_def.get_member_type(name)?;
+            let member_bytes = match get_literal_bytes(expr, &member_type) {
+                Some(member_bytes) => member_bytes,
+                None => return Ok(None)
+            };
+            let offset = cont_def.get_member_offset(self, name)?;
+            bytes[offset..offset + member_bytes.len()].copy_from_slice(&member_bytes);
+        }
+        Ok(Some(bytes))
+    }
+
+    /// Pushes a copy of the given bytes, which are added to the constant pool
+    fn compile_constant_copy(&mut self, bytes: &[u8]) -> CompilerResult<()> {
+        let const_addr = self.data.add_constant(bytes);
+        let size = bytes.len();
+        let addr_reg = self.get_next_register()?;
+        let lda_instr = Instruction::new(Opcode::LDA)
+            .with_operand::<u64>(const_addr)
+            .with_operand::<u8>(addr_reg.clone().into());
+        let stack_inc_instr = Instruction::new_inc_stack(size);
+        self.inc_stack(size)?;
+        let movn_instr = Instruction::new(Opcode::MOVN_A)
+            .with_operand::<u8>(addr_reg.into())
+            .with_operand::<i16>(0)
+            .with_operand::<u8>(Register::SP.into())
+            .with_operand::<i16>(-(size as i16))
+            .with_operand::<u32>(size as u32);
+        self.builder.push_instr(lda_instr);
+        self.builder.push_instr(stack_inc_instr);
+        self.builder.push_instr(movn_instr);
+        Ok(())
+    }
+
+    /// Compiles a tuple element access.
+    /// The whole tuple is put on the stack, compile_expr() pops all but the element afterwards.
+    pub fn compile_tuple_index_expr(&mut self, base_expr: &Expression, index: usize) -> CompilerResult<()> {
+        let base_type = self.check_expr_type(base_expr)?;
+        let elem_types = match &base_type {
+            Type::Tuple(elem_types) => elem_types.clone(),
+            Type::Range => vec![Type::Int, Type::Int],
+            _ => return Err(CompilerError::NotATuple(base_type))
+        };
+        let elem_type = elem_types.get(index)
+            .cloned()
+            .ok_or_else(|| CompilerError::TupleIndexOutOfBounds(base_type.clone(), index))?;
+
+        let mut elem_offset = 0;
+        for prev_type in elem_types[..index].iter() {
+            elem_offset += self.get_size_of_type(prev_type)?;
+        }
+        let tuple_size = self.get_size_of_type(&base_type)?;
+        let elem_size = self.get_size_of_type(&elem_type)?;
+
+        self.compile_expr(base_expr)?;
+
+        // Offset of the element relative to the stack pointer
+        let elem_sp_offset = elem_offset as i16 - tuple_size as i16;
+        match get_load_opcode(&elem_type) {
+            Some(load_opcode) => {
+                let reg = self.get_next_register()?;
+                let load_instr = Instruction::new(load_opcode)
+                    .with_operand::<u8>(Register::SP.into())
+                    .with_operand::<i16>(elem_sp_offset)
+                    .with_operand::<u8>(reg.into());
+                self.builder.push_instr(load_instr);
+            },
+            None => {
+                // Copy the element on top of the tuple
+                let stack_inc_instr = Instruction::new_inc_stack(elem_size);
+                self.inc_stack(elem_size)?;
+                let movn_instr = Instruction::new(Opcode::MOVN_A)
+                    .with_operand::<u8>(Register::SP.into())
+                    .with_operand::<i16>(elem_sp_offset - elem_size as i16)
+                    .with_operand::<u8>(Register::SP.into())
+                    .with_operand::<i16>(-(elem_size as i16))
+                    .with_operand::<u32>(elem_size as u32);
+                self.builder.push_instr(stack_inc_instr);
+                self.builder.push_instr(movn_instr);
+            }
+        };
+
+        Ok(())
+    }
+
+    /// Compiles an expression and pushes its value onto the stack.
+    /// Non-primitive values already end up there, register values are copied.
+    fn compile_push_expr(&mut self, expr: &Expression) -> CompilerResult<()> {
+        let expr_type = self.check_expr_type(expr)?;
+        self.compile_expr(expr)?;
+        // Special handling for copying register type values on the stack
+        let last_reg = self.get_last_register()?;
+        match expr_type {
+            Type::Int | Type::Enum(_) | Type::Coroutine(_) => {
+                let stack_inc_instr = Instruction::new_inc_stack(8);
+                self.inc_stack(8)?;
+                let movi_instr = Instruction::new(Opcode::MOVI_RA)
+                    .with_operand::<u8>(last_reg.clone().into())
+                    .with_operand::<u8>(Register::SP.into())
+                    .with_operand::<i16>(-8);
+                self.builder.push_instr(stack_inc_instr);
+                self.builder.push_instr(movi_instr);
+            },
+            Type::Bool => {
+                let stack_inc_instr = Instruction::new_inc_stack(4);
+                self.inc_stack(4)?;
+                let movb_instr = Instruction::new(Opcode::MOVB_RA)
+                    .with_operand::<u8>(last_reg.clone().into())
+                    .with_operand::<u8>(Register::SP.into())
+                    .with_operand::<i16>(-4);
+                self.builder.push_instr(stack_inc_instr);
+                self.builder.push_instr(movb_instr);
+            },
+            Type::Char => {
+                let stack_inc_instr = Instruction::new_inc_stack(4);
+                self.inc_stack(4)?;
+                let movc_instr = Instruction::new(Opcode::MOVC_RA)
+                    .with_operand::<u8>(last_reg.clone().into())
+                    .with_operand::<u8>(Register::SP.into())
+                    .with_operand::<i16>(-4);
+                self.builder.push_instr(stack_inc_instr);
+                self.builder.push_instr(movc_instr);
+            },
+            Type::Float => {
+                let stack_inc_instr = Instruction::new_inc_stack(4);
+                self.inc_stack(4)?;
+                let movf_instr = Instruction::new(Opcode::MOVF_RA)
+                    .with_operand::<u8>(last_reg.clone().into())
+                    .with_operand::<u8>(Register::SP.into())
+                    .with_operand::<i16>(-4);
+                self.builder.push_instr(stack_inc_instr);
+                self.builder.push_instr(movf_instr);
+            },
+            Type::Double => {
+                let stack_inc_instr = Instruction::new_inc_stack(8);
+                self.inc_stack(8)?;
+                let movd_instr = Instruction::new(Opcode::MOVD_RA)
+                    .with_operand::<u8>(last_reg.clone().into())
+                    .with_operand::<u8>(Register::SP.into())
+                    .with_operand::<i16>(-8);
+                self.builder.push_instr(stack_inc_instr);
+                self.builder.push_instr(movd_instr);
+            },
+            Type::Reference(inner_type) => {
+                match inner_type.deref() {
+                    Type::AutoArray(_) | Type::Interface(_) => {},
+                    _ => {
+                        let stack_inc_instr = Instruction::new_inc_stack(8);
+                        self.inc_stack(8)?;
+                        let mova_instr = Instruction::new(Opcode::MOVA_RA)
+                            .with_operand::<u8>(last_reg.clone().into())
+                            .with_operand::<u8>(Register::SP.into())
+                            .with_operand::<i16>(-8);
+                        self.builder.push_instr(stack_inc_instr);
+                        self.builder.push_instr(mova_instr);
+                    }
+                };
+            },
+            _ => {}
+        };
+
+        Ok(())
+    }
+
+    /// Compiles a call expresion
+    pub fn compile_call_expr(&mut self, expr: &Expression) -> CompilerResult<()> {
+        //println!("Line 2718");
+        let (fn_name, fn_arg_exprs) = match expr {
+            Expression::Call(fn_name, fn_args) => (fn_name, fn_args),
+            _ => return Err(CompilerError::Unknown)
+        };
+
+        //println!("Compiling call expr");
+
+        if let Some(fn_type) = self.get_closure_type(fn_name) {
+            return self.compile_closure_call_expr(fn_name, &fn_type, fn_arg_exprs);
+        }
+
+        let fn_def = match self.resolve_function(fn_name) {
+            Ok(fn_def) => fn_def,
+            Err(error) => {
+                let generic_def = self.resolve_generic_function(fn_name)
+                    .map_err(|_| error)?;
+                self.instantiate_generic_function(&generic_def, fn_arg_exprs)?
+            }
+        };
+
+        if fn_arg_exprs.len() != fn_def.arguments.len() {
+            return Err(CompilerError::UnknownFunction(fn_name.clone()));
+        }
+        if let Type::Coroutine(_) = fn_def.ret_type {
+            return self.compile_coroutine_call_expr(&fn_def, fn_arg_exprs);
+        }
+        
+        let before_call_stack_size = self.get_stack_size()?;
+
+        self.compile_call_args(&fn_def, fn_arg_exprs)?;
+
+        let call_instr = Instruction::new(Opcode::CALL)
+            .with_operand::<u64>(fn_def.uid);
+        self.builder.push_instr(call_instr);
+
+        self.compile_call_cleanup(&fn_def.ret_type, before_call_stack_size)
+    }
+
+    /// Compiles a call to a coroutine function, which creates the coroutine without running it.
+    /// Its handle ends up in R0, the arguments are moved to the stack of the coroutine like for a spawn.
+    pub fn compile_coroutine_call_expr(&mut self, fn_def: &FunctionDef, fn_arg_exprs: &[Expression]) -> CompilerResult<()> {
+        let has_ref_arg = fn_def.arguments.iter()
+            .any(|(_, arg_type)| matches!(arg_type, Type::Reference(_)));
+        if self.is_function_foreign(fn_def.uid)? || has_ref_arg {
+            return Err(CompilerError::InvalidCoroutine(fn_def.name.clone()));
+        }
+
+        let before_call_stack_size = self.get_stack_size()?;
+
+        self.compile_call_args(fn_def, fn_arg_exprs)?;
+
+        let args_size = self.get_stack_size()? - before_call_stack_size;
+        let coro_instr = Instruction::new(Opcode::CORO)
+            .with_operand::<u64>(fn_def.uid)
+            .with_operand::<u32>(args_size as u32);
+        self.builder.push_instr(coro_instr);
+
+        self.compile_call_cleanup(&fn_def.ret_type, before_call_stack_size)
+    }
+
+    /// Compiles a resume() intrinsic, the value yielded by the coroutine is pushed as an optional
+    pub fn compile_resume_expr(&mut self, expr: &Expression) -> CompilerResult<()> {
+        let co_expr = match expr {
+            Expression::Resume(co_expr) => co_expr,
+            _ => return Err(CompilerError::Unknown)
+        };
+
+        let value_type = match self.check_expr_type(co_expr)? {
+            Type::Coroutine(value_type) => value_type.deref().clone(),
+            co_type => return Err(CompilerError::TypeMismatch(Type::Coroutine(Box::new(Type::Auto)), co_type))
+        };
+        let value_size = self.get_size_of_type(&value_type)?;
+
+        self.compile_expr(co_expr)?;
+        let handle_reg = self.get_last_register()?;
+        let resume_instr = Instruction::new(Opcode::RESUME)
+            .with_operand::<u8>(handle_reg.into())
+            .with_operand::<u32>(value_size as u32);
+        self.builder.push_instr(resume_instr);
+        // The value and the "has value" flag
+        self.inc_stack(value_size + 8)?;
+
+        Ok(())
+    }
+
+    /// Compiles a call through a local variable holding a callable value.
+    /// The callable itself is passed after the arguments, so the callee finds its environment.
+    pub fn compile_closure_call_expr(&mut self, var_name: &str, fn_type: &Type, fn_arg_exprs: &[Expression]) -> CompilerResult<()> {
+        let (arg_types, ret_type) = match fn_type {
+            Type::Function(arg_types, ret_type) => (arg_types, ret_type.deref()),
+            _ => return Err(CompilerError::Unknown)
+        };
+        if fn_arg_exprs.len() != arg_types.len() {
+            return Err(CompilerError::UnknownFunction(var_name.to_string()));
+        }
+        let arguments: Vec<(String, Type)> = arg_types.iter()
+            .map(|arg_type| (String::new(), arg_type.clone()))
+            .collect();
+        let fn_def = FunctionDef::new(var_name.to_string())
+            .with_ret_type(ret_type.clone())
+            .with_arguments(&arguments);
+
+        let before_call_stack_size = self.get_stack_size()?;
+
+        self.compile_call_args(&fn_def, fn_arg_exprs)?;
+        self.compile_push_expr(&Expression::Variable(var_name.to_string()))?;
+
+        let uid_reg = self.get_next_register()?;
+        let movi_instr = Instruction::new(Opcode::MOVI_AR)
+            .with_operand::<u8>(Register::SP.into())
+            .with_operand::<i16>(-16)
+            .with_operand::<u8>(uid_reg.clone().into());
+        let dcall_instr = Instruction::new(Opcode::DCALL)
+            .with_operand::<u8>(uid_reg.into());
+        self.builder.push_instr(movi_instr);
+        self.builder.push_instr(dcall_instr);
+
+        self.compile_call_cleanup(ret_type, before_call_stack_size)
+    }
+
+    /// Compiles a lambda expression, pushing a callable value on the stack.
+    /// The body is compiled in place as its own function, jumped over by the enclosing one.
+    /// Captured locals are copied into a heap allocated environment, so they are captured by value.
+    pub fn compile_lambda_expr(&mut self, expr: &Expression) -> CompilerResult<()> {
+        let fn_decl_args = match expr {
+            Expression::Lambda(fn_decl_args) => fn_decl_args,
+            _ => return Err(CompilerError::Unknown)
+        };
+        let fn_type = self.check_expr_type(expr)?;
+
+        // Locals of the enclosing function used by the body
+        let mut used = BTreeSet::new();
+        let mut declared = BTreeSet::new();
+        for stmt in fn_decl_args.code_block.iter().flatten() {
+            stmt.collect_variables(&mut used, &mut declared);
+        }
+        let mut captures = Vec::new();
+        for var_name in used.iter() {
+            let is_arg = fn_decl_args.arguments.iter()
+                .any(|(arg_name, _)| arg_name == var_name);
+            if is_arg || declared.contains(var_name) || !self.is_local_var(var_name)? {
+                continue;
+            }
+            captures.push((var_name.clone(), self.get_type_of_var(var_name)?));
+        }
+
+        let lambda_name = format!("{}lambda#{}", self.get_module_path(), self.lambda_count);
+        self.lambda_count += 1;
+        let uid = self.uid_generator.get_function_uid(&lambda_name);
+        self.fn_uid_map.insert(lambda_name.clone(), uid);
+
+        let tag_end = self.uid_generator.generate();
+        let jmp_end_instr = Instruction::new(Opcode::JMP)
+            .with_operand(tag_end);
+        self.builder.tag(tag_end);
+        self.builder.push_instr(jmp_end_instr);
+
+        self.compile_lambda_body(fn_decl_args, &lambda_name, uid, &fn_type, &captures)?;
+
+        let pos_end = self.builder.get_current_offset();
+        self.patch_jump_tag(&tag_end, pos_end)?;
+
+        // Copy the captured values into the environment
+        let env_reg = if captures.is_empty() {
+            let env_reg = self.get_next_register()?;
+            let ldi_instr = Instruction::new(Opcode::LDI)
+                .with_operand::<i64>(0)
+                .with_operand::<u8>(env_reg.clone().into());
+            self.builder.push_instr(ldi_instr);
+            env_reg
+        } else {
+            let mut env_size = 0;
+            for (var_name, var_type) in captures.iter() {
+                self.compile_push_expr(&Expression::Variable(var_name.clone()))?;
+                // The environment is never freed, neither are the strings in it
+                let var_size = self.get_size_of_type(var_type)?;
+                self.compile_rc_retain(var_type, Register::SP, -(var_size as i16))?;
+                env_size += var_size;
+            }
+            let env_reg = self.get_next_register()?;
+            let alloc_instr = Instruction::new(Opcode::ALLOC)
+                .with_operand::<u8>(Register::SP.into())
+                .with_operand::<i16>(-(env_size as i16))
+                .with_operand::<u32>(env_size as u32)
+                .with_operand::<u8>(env_reg.clone().into());
+            let stack_dec_instr = Instruction::new_dec_stack(env_size);
+            self.dec_stack(env_size)?;
+            self.builder.push_instr(alloc_instr);
+            self.builder.push_instr(stack_dec_instr);
+            env_reg
+        };
+
+        let stack_inc_instr = Instruction::new_inc_stack(16);
+        self.inc_stack(16)?;
+        let uid_reg = self.get_next_register()?;
+        let ldi_instr = Instruction::new(Opcode::LDI)
+            .with_operand::<i64>(uid as i64)
+            .with_operand::<u8>(uid_reg.clone().into());
+        let movi_instr = Instruction::new(Opcode::MOVI_RA)
+            .with_operand::<u8>(uid_reg.into())
+            .with_operand::<u8>(Register::SP.into())
+            .with_operand::<i16>(-16);
+        let mova_instr = Instruction::new(Opcode::MOVA_RA)
+            .with_operand::<u8>(env_reg.into())
+            .with_operand::<u8>(Register::SP.into())
+            .with_operand::<i16>(-8);
+        self.builder.push_instr(stack_inc_instr);
+        self.builder.push_instr(ldi_instr);
+        self.builder.push_instr(movi_instr);
+        self.builder.push_instr(mova_instr);
+
+        Ok(())
+    }
+
+    /// Compiles the body of a lambda as a function taking the callable itself as its last argument.
+    /// The captured values are copied from its environment into locals first.
+    fn compile_lambda_body(&mut self, fn_decl_args: &FunctionDeclArgs, lambda_name: &str, uid: u64, fn_type: &Type, captures: &[(String, Type)]) -> CompilerResult<()> {
+        let ret_type = match fn_type {
+            Type::Function(_, ret_type) => ret_type.deref().clone(),
+            _ => return Err(CompilerError::Unknown)
+        };
+        let mut arguments = Vec::new();
+        for (arg_name, arg_type) in fn_decl_args.arguments.iter() {
+            let mut arg_type = arg_type.clone();
+            self.canonize_type(&mut arg_type)?;
+            arguments.push((arg_name.clone(), arg_type));
+        }
+        let closure_var = String::from("#closure");
+        arguments.push((closure_var.clone(), fn_type.clone()));
+        let fn_def = FunctionDef::new(lambda_name.to_string())
+            .with_ret_type(ret_type.clone())
+            .with_arguments(&arguments)
+            .with_uid(uid);
+        self.inline_fn_defs.insert(lambda_name.to_string(), fn_def.clone());
+
+        let fn_ctx = FunctionContext::new(self, fn_def)?;
+        self.builder.push_label(lambda_name.to_string());
+        self.push_function_context(fn_ctx);
+        // Loops of the enclosing function can not be left from inside the lambda
+        let loop_ctx_stack = take(&mut self.loop_ctx_stack);
+
+        let mut env_offset = 0;
+        for (var_name, var_type) in captures.iter() {
+            let var_size = self.get_size_of_type(var_type)?;
+            let env_reg = self.get_next_register()?;
+            let closure_offset = self.get_fp_offset_of_var(&closure_var)?;
+            let mova_instr = Instruction::new(Opcode::MOVA_AR)
+                .with_operand::<u8>(Register::FP.into())
+                .with_operand::<i16>((closure_offset + 8) as i16)
+                .with_operand::<u8>(env_reg.clone().into());
+            let stack_inc_instr = Instruction::new_inc_stack(var_size);
+            self.inc_stack(var_size)?;
+            let movn_instr = Instruction::new(Opcode::MOVN_A)
+                .with_operand::<u8>(env_reg.into())
+                .with_operand::<i16>(env_offset as i16)
+                .with_operand::<u8>(Register::SP.into())
+                .with_operand::<i16>(-(var_size as i16))
+                .with_operand::<u32>(var_size as u32);
+            self.builder.push_instr(mova_instr);
+            self.builder.push_instr(stack_inc_instr);
+            self.builder.push_instr(movn_instr);
+            env_offset += var_size;
+
+            let fn_ctx = self.get_current_function_mut()?;
+            fn_ctx.set_stack_var((var_name.clone(), var_type.clone()), (fn_ctx.stack_size - var_size) as i64)?;
+        }
+
+        if let Some(stmt_list) = ConstantPropagator::new().propagate_fn(fn_decl_args) {
+            self.compile_stmt_list(&stmt_list)?;
+        }
+        if ret_type == Type::Void {
+            let ret_stmt = Statement::Return(None);
+            self.compile_return_stmt(&ret_stmt)?;
+        }
+        let halt_instr = Instruction::new(Opcode::HALT)
+            .with_operand::<u8>(1);
+        self.builder.push_instr(halt_instr);
+
+        self.loop_ctx_stack = loop_ctx_stack;
+        self.pop_function_context()?;
+        Ok(())
+    }
+
+    /// Infers the concrete types of the type parameters of a generic function from the call arguments
+    pub fn infer_type_params(&self, generic_def: &GenericFunctionDef, fn_arg_exprs: &[Expression]) -> CompilerResult<HashMap<String, Type>> {
+        let fn_decl_args = &generic_def.decl;
+        if fn_arg_exprs.len() != fn_decl_args.arguments.len() {
+            return Err(CompilerError::UnknownFunction(generic_def.name.clone()));
+        }
+        let mut type_params = HashMap::new();
+        for ((_, param_type), arg_expr) in fn_decl_args.arguments.iter().zip(fn_arg_exprs.iter()) {
+            let arg_type = self.check_expr_type(arg_expr)?;
+            bind_type_params(&fn_decl_args.generics, param_type, &arg_type, &mut type_params, &self.container_instances.borrow())?;
+        }
+        for type_param in fn_decl_args.generics.iter() {
+            if !type_params.contains_key(type_param) {
+                return Err(CompilerError::CannotInferType(type_param.clone()));
+            }
+        }
+        Ok(type_params)
+    }
+
+    /// Gets the full name of the instance of a generic function, e.g. "root::max<int>"
+    pub fn get_generic_instance_name(&self, generic_def: &GenericFunctionDef, type_params: &HashMap<String, Type>) -> String {
+        let type_names: Vec<String> = generic_def.decl.generics.iter()
+            .map(|type_param| type_params[type_param].to_string())
+            .collect();
+        format!("{}<{}>", generic_def.canonical_name, type_names.join(", "))
+    }
+
+    /// Gets the return type of a call to a generic function, without instantiating it
+    pub fn get_generic_ret_type(&self, generic_def: &GenericFunctionDef, fn_arg_exprs: &[Expression]) -> CompilerResult<Type> {
+        let type_params = self.infer_type_params(generic_def, fn_arg_exprs)?;
+        let instance_name = self.get_generic_instance_name(generic_def, &type_params);
+        if let Some(fn_def) = self.inline_fn_defs.get(&instance_name) {
+            return Ok(fn_def.ret_type.clone());
+        }
+        let mut ret_type = substitute_type_params(&generic_def.decl.returns, &type_params);
+        self.canonize_type(&mut ret_type)?;
+        Ok(ret_type)
+    }
+
+    /// Gets the definition of the instance of a generic function called with the given arguments.
+    /// Each instance is compiled in place the first time it is called, jumped over by the calling function.
+    pub fn instantiate_generic_function(&mut self, generic_def: &GenericFunctionDef, fn_arg_exprs: &[Expression]) -> CompilerResult<FunctionDef> {
+        let type_params = self.infer_type_params(generic_def, fn_arg_exprs)?;
+        let instance_name = self.get_generic_instance_name(generic_def, &type_params);
+        if let Some(fn_def) = self.inline_fn_defs.get(&instance_name) {
+            return Ok(fn_def.clone());
+        }
+        let uid = self.uid_generator.get_function_uid(&instance_name);
+        self.fn_uid_map.insert(instance_name.clone(), uid);
+
+        let tag_end = self.uid_generator.generate();
+        let jmp_end_instr = Instruction::new(Opcode::JMP)
+            .with_operand(tag_end);
+        self.builder.tag(tag_end);
+        self.builder.push_instr(jmp_end_instr);
+
+        // The body sees the module it was declared in, with the type parameters bound
+        let mod_path = generic_def.get_module_path();
+        let mod_context_stack = if mod_path != self.get_module_path() {
+            let mod_context_stack = self.get_module_stack(mod_path)?;
+            Some(replace(&mut self.mod_context_stack, mod_context_stack))
+        } else {
+            None
+        };
+        let type_params = replace(&mut self.type_params, type_params);
+        let current_cont = self.current_cont.take();
+
+        let fn_def_res = self.compile_generic_instance(generic_def, &instance_name, uid);
+
+        if let Some(mod_context_stack) = mod_context_stack {
+            self.mod_context_stack = mod_context_stack;
+        }
+        self.type_params = type_params;
+        self.current_cont = current_cont;
+
+        let pos_end = self.builder.get_current_offset();
+        self.patch_jump_tag(&tag_end, pos_end)?;
+
+        fn_def_res
+    }
+
+    /// Compiles the body of a generic function for the currently bound type parameters
+    fn compile_generic_instance(&mut self, generic_def: &GenericFunctionDef, instance_name: &str, uid: u64) -> CompilerResult<FunctionDef> {
+        let mut fn_def = FunctionDef::from(&generic_def.decl)
+            .with_uid(uid);
+        fn_def.name = instance_name.to_string();
+        for (_, arg_type) in fn_def.arguments.iter_mut() {
+            self.canonize_type(arg_type)?;
+        }
+        self.canonize_type(&mut fn_def.ret_type)?;
+        // Registered before compiling the body, so the instance can call itself
+        self.inline_fn_defs.insert(instance_name.to_string(), fn_def.clone());
+
+        let fn_ret_type = fn_def.ret_type.clone();
+        let fn_ctx = FunctionContext::new(self, fn_def.clone())?;
+        self.builder.push_label(instance_name.to_string());
+        self.push_function_context(fn_ctx);
+        let loop_ctx_stack = take(&mut self.loop_ctx_stack);
+
+        if let Some(stmt_list) = ConstantPropagator::new().propagate_fn(&generic_def.decl) {
+            self.compile_stmt_list(&stmt_list)?;
+        }
+        if fn_ret_type == Type::Void || matches!(fn_ret_type, Type::Coroutine(_)) {
+            let ret_stmt = Statement::Return(None);
+            self.compile_return_stmt(&ret_stmt)?;
+        }
+        let halt_instr = Instruction::new(Opcode::HALT)
+            .with_operand::<u8>(1);
+        self.builder.push_instr(halt_instr);
+
+        self.loop_ctx_stack = loop_ctx_stack;
+        self.pop_function_context()?;
+        Ok(fn_def)
+    }
+
+    /// Builds a module context stack for the module at the given path, with trailing "::"
+    fn get_module_stack(&self, mod_path: &str) -> CompilerResult<VecDeque<ModuleContext>> {
+        let mut mod_context_stack = VecDeque::new();
+        let mut mod_ctx = self.get_root_module()?.clone();
+        for mod_name in mod_path.split("::").skip(1).filter(|mod_name| !mod_name.is_empty()) {
+            let child_mod_ctx = mod_ctx.modules.get(mod_name)
+                .cloned()
+                .ok_or_else(|| CompilerError::UnknownModule(mod_name.to_string()))?;
+            mod_context_stack.push_front(mod_ctx);
+            mod_ctx = child_mod_ctx;
+        }
+        mod_context_stack.push_front(mod_ctx);
+        Ok(mod_context_stack)
+    }
+
+    /// Pops the arguments of a finished call, keeping a non primitive return value on top of the stack
+    fn compile_call_cleanup(&mut self, ret_type: &Type, before_call_stack_size: usize) -> CompilerResult<()> {
+        let fn_ret_size = self.get_size_of_type(ret_type)?;
+        if !ret_type.is_primitive() {
+            self.inc_stack(fn_ret_size)?;
+        }
+
+        let stack_diff = self.get_stack_size()? - before_call_stack_size;
+        //println!("Stack diff after args + call: {}", stack_diff);
+        let mut pop_size = stack_diff;
+
+        if !ret_type.is_primitive() {
+            let mov_stack_instr = Instruction::new(Opcode::MOVN_A)
+                .with_operand::<u8>(Register::SP.into())
+                .with_operand::<i16>(-(fn_ret_size as i16))
+                .with_operand::<u8>(Register::SP.into())
+                .with_operand::<i16>(-(stack_diff as i16))
+                .with_operand::<u32>(fn_ret_size as u32);
+            pop_size -= fn_ret_size;
+            self.builder.push_instr(mov_stack_instr);
+        }
+        
+        let stack_dec_instr = Instruction::new_dec_stack(pop_size);
+        self.dec_stack(pop_size)?;
+        self.builder.push_instr(stack_dec_instr);
+
+        Ok(())
+    }
+
+    /// Compiles the arguments of a call, pushing them on the stack in order
+    pub fn compile_call_args(&mut self, fn_def: &FunctionDef, fn_arg_exprs: &[Expression]) -> CompilerResult<()> {
+        let mut stack_size = self.get_stack_size()?;
+        let callee_foreign = self.is_function_foreign(fn_def.uid)?;
+
+        for i in 0..fn_def.arguments.len() {
+            let mut expr_type = self.check_expr_type(&fn_arg_exprs[i])?;
+            self.canonize_type(&mut expr_type)?;
+            let fn_arg_type = &fn_def.arguments[i].1;
+            self.check_assignable(fn_arg_type, &expr_type)?;
+            expr_type = fn_arg_type.clone();
+
+            //println!("Compiling call expr arg. Stack size: {}", self.get_stack_size()?);
+            //println!("Type of call expr: {:?}, size: {}", expr_type, self.get_size_of_type(&expr_type)?);
+
+            // Compile this expr
+            self.compile_assigned_expr(&fn_arg_exprs[i], &expr_type)?;
+
+
+            //println!("Compiled call expr arg. Stack size: {}", self.get_stack_size()?);
+
+            let curr_stack_size = self.get_stack_size()?;
+
+            let stack_diff = curr_stack_size - stack_size;
+            let mut pop_size = stack_diff;
+
+            let size = self.get_size_of_type(&expr_type)?;
+
+            if !expr_type.is_primitive() {
+                pop_size -= size;
+                if pop_size > 0 {
+                    let mov_stack_instr = Instruction::new(Opcode::MOVN_A)
+                        .with_operand::<u8>(Register::SP.into())
+                        .with_operand::<i16>(-(size as i16))
+                        .with_operand::<u8>(Register::SP.into())
+                        .with_operand::<i16>(-(stack_diff as i16))
+                        .with_operand::<u32>(size as u32);
+                    self.builder.push_instr(mov_stack_instr);
+                }
+            }
+            if pop_size > 0 {
+                let stack_dec_instr = Instruction::new_dec_stack(pop_size);
+                self.dec_stack(pop_size)?;
+                self.builder.push_instr(stack_dec_instr);
+            }
+
+            let last_reg = {
+                self.get_current_function()?
+                    .register_allocator
+                    .get_last_temp_register()?
+            };
+
+            //println!("CHECKING IF EXPR TYPE IS PRIMITIVE");
+
+            if expr_type.is_primitive() {
+                //println!("incrementing stack for primitive type arg");
+                let stack_instr = Instruction::new_inc_stack(size);
+                self.builder.push_instr(stack_instr);
+                self.inc_stack(size)?;
+            }
+
+            // Script functions release their arguments when returning
+            if !callee_foreign && !is_call_result(&fn_arg_exprs[i]) {
+                self.compile_rc_retain(&expr_type, Register::SP, -(size as i16))?;
+            }
+
+            let mov_instr_opt = match expr_type {
+                Type::Int | Type::Enum(_) | Type::Coroutine(_) => {
+                    Some(Instruction::new(Opcode::MOVI_RA)
+                        .with_operand::<u8>(last_reg.into())
+                        .with_operand::<u8>(Register::SP.into())
+                        .with_operand::<i16>(-(size as i16)))
+                },
+                Type::Float => {
+                    Some(Instruction::new(Opcode::MOVF_RA)
+                        .with_operand::<u8>(last_reg.into())
+                        .with_operand::<u8>(Register::SP.into())
+                        .with_operand::<i16>(-(size as i16)))
+                },
+                Type::Double => {
+                    Some(Instruction::new(Opcode::MOVD_RA)
+                        .with_operand::<u8>(last_reg.into())
+                        .with_operand::<u8>(Register::SP.into())
+                        .with_operand::<i16>(-(size as i16)))
+                },
+                Type::Bool => {
+                    Some(Instruction::new(Opcode::MOVB_RA)
+                        .with_operand::<u8>(last_reg.into())
+                        .with_operand::<u8>(Register::SP.into())
+                        .with_operand::<i16>(-(size as i16)))
+                },
+                Type::Char => {
+                    Some(Instruction::new(Opcode::MOVC_RA)
+                        .with_operand::<u8>(last_reg.into())
+                        .with_operand::<u8>(Register::SP.into())
+                        .with_operand::<i16>(-(size as i16)))
+                },
+                Type::String => None,
+                Type::Tuple(_) => None,
+                Type::Range => None,
+                Type::Array(_, _) => None,
+                Type::Optional(_) => None,
+                Type::Result(_) => None,
+                Type::Function(_, _) => None,
+                Type::Reference(inner_type) => {
+                    match inner_type.deref() {
+                        Type::AutoArray(_) | Type::Interface(_) => None,
+                        _ => {
+                            Some(
+                                Instruction::new(Opcode::MOVA_RA)
+                                    .with_operand::<u8>(last_reg.into())
+                                    .with_operand::<u8>(Register::SP.into())
+                                    .with_operand::<i16>(-(size as i16))
+                            )
+                        }
+                    }
+                },
+                _ => {
+                    //println!("Error in compile_call_expr()!");
+                    return Err(CompilerError::UnknownType(expr_type));
+                }
+            };
+
+            if mov_instr_opt.is_some() {
+                self.builder.push_instr(mov_instr_opt.unwrap());
+            }
+
+            stack_size = self.get_stack_size()?;
+        }
+
+        Ok(())
+    }
+
+    /// Compiles a variable expression
+    pub fn compile_var_expr(&mut self, expr: &Expression) -> CompilerResult<()> {
+        let var_name = match expr {
+            Expression::Variable(var_name) => var_name,
+            _ => return Err(CompilerError::Unknown)
+        };
+
+        //println!("Compiling var expr");
+
+        if !self.is_local_var(var_name)? {
+            let static_def = self.resolve_static(var_name)?;
+            return self.compile_static_var_expr(&static_def);
+        }
+
+        let var_type = self.get_type_of_var(var_name)?;
+        let var_offset = self.get_fp_offset_of_var(var_name)?;
+        match var_type {
+            Type::Int | Type::Enum(_) | Type::Coroutine(_) => {
+                let reg = {
+                    let fn_ctx = self.get_current_function_mut()?;
+                    fn_ctx.register_allocator.get_temp_register()?
+                };
+                let movi_instr = Instruction::new(Opcode::MOVI_AR)
+                    .with_operand::<u8>(Register::FP.into())
+                    .with_operand::<i16>(var_offset as i16)
+                    .with_operand::<u8>(reg.into());
+                self.builder.push_instr(movi_instr);
+            },
+            Type::Float => {
+                let reg = {
+                    let fn_ctx = self.get_current_function_mut()?;
+                    fn_ctx.register_allocator.get_temp_register()?
+                };
+                let movf_instr = Instruction::new(Opcode::MOVF_AR)
+                    .with_operand::<u8>(Register::FP.into())
+                    .with_operand::<i16>(var_offset as i16)
+                    .with_operand::<u8>(reg.into());
+                self.builder.push_instr(movf_instr);
+            },
+            Type::Double => {
+                let reg = {
+                    let fn_ctx = self.get_current_function_mut()?;
+                    fn_ctx.register_allocator.get_temp_register()?
+                };
+                let movd_instr = Instruction::new(Opcode::MOVD_AR)
+                    .with_operand::<u8>(Register::FP.into())
+                    .with_operand::<i16>(var_offset as i16)
+                    .with_operand::<u8>(reg.into());
+                self.builder.push_instr(movd_instr);
+            },
+            Type::Bool => {
+                let reg = {
+                    let fn_ctx = self.get_current_function_mut()?;
+                    fn_ctx.register_allocator.get_temp_register()?
+                };
+                let movb_instr = Instruction::new(Opcode::MOVB_AR)
+                    .with_operand::<u8>(Register::FP.into())
+                    .with_operand::<i16>(var_offset as i16)
+                    .with_operand::<u8>(reg.into());
+                self.builder.push_instr(movb_instr);
+            },
+            Type::Char => {
+                let reg = {
+                    let fn_ctx = self.get_current_function_mut()?;
+                    fn_ctx.register_allocator.get_temp_register()?
+                };
+                let movc_instr = Instruction::new(Opcode::MOVC_AR)
+                    .with_operand::<u8>(Register::FP.into())
+                    .with_operand::<i16>(var_offset as i16)
+                    .with_operand::<u8>(reg.into());
+                self.builder.push_instr(movc_instr);
+            },
+            Type::String => {
+                let stack_inc_instr = Instruction::new_inc_stack(16);
+                self.inc_stack(16)?;
+                let movn_instr = Instruction::new(Opcode::MOVN_A)
+                    .with_operand::<u8>(Register::FP.into())
+                    .with_operand::<i16>(var_offset as i16)
+                    .with_operand::<u8>(Register::SP.into())
+                    .with_operand::<i16>(-16)
+                    .with_operand::<u32>(16);
+                self.builder.push_instr(stack_inc_instr);
+                self.builder.push_instr(movn_instr);
+            },
+            Type::Reference(inner_type) => {
+                match inner_type.deref() {
+                    Type::AutoArray(_) | Type::Interface(_) => {
+                        let stack_inc_instr = Instruction::new_inc_stack(16);
+                        self.inc_stack(16)?;
+                        let movn_instr = Instruction::new(Opcode::MOVN_A)
+                            .with_operand::<u8>(Register::FP.into())
+                            .with_operand::<i16>(var_offset as i16)
+                            .with_operand::<u8>(Register::SP.into())
+                            .with_operand::<i16>(-16)
+                            .with_operand::<u32>(16);
+                        self.builder.push_instr(stack_inc_instr);
+                        self.builder.push_instr(movn_instr);
+                    },
+                    _ => {
+                        let reg = {
+                            let fn_ctx = self.get_current_function_mut()?;
+                            fn_ctx.register_allocator.get_temp_register()?
+                        };
+                        let mova_instr = Instruction::new(Opcode::MOVA_AR)
+                            .with_operand::<u8>(Register::FP.into())
+                            .with_operand::<i16>(var_offset as i16)
+                            .with_operand::<u8>(reg.into());
+                        self.builder.push_instr(mova_instr);
+                    }
+                };
+            },
+            Type::Other(_) |
+            Type::Tuple(_) |
+            Type::Range |
+            Type::Array(_, _) |
+            Type::Optional(_) |
+            Type::Result(_) |
+            Type::Function(_, _) => {
+                let size = self.get_size_of_type(&var_type)?;
+
+                let stack_inc_instr = Instruction::new_inc_stack(size);
+                self.inc_stack(size)?;
+
+                let movn_instr = Instruction::new(Opcode::MOVN_A)
+                    .with_operand::<u8>(Register::FP.into())
+                    .with_operand::<i16>(var_offset as i16)
+                    .with_operand::<u8>(Register::SP.into())
+                    .with_operand::<i16>(-(size as i16))
+                    .with_operand::<u32>(size as u32);
+                
+                self.builder.push_instr(stack_inc_instr);
+                self.builder.push_instr(movn_instr);
+            },
+            _ => {
+                //println!("Errors in compile_var_expr()");
+                return Err(CompilerError::UnknownType(var_type));
+            },
+        };
+
+        Ok(())
+    }
+
+    /// Compiles a static variable expression, loading the variable from its fixed address
+    pub fn compile_static_var_expr(&mut self, static_def: &StaticDef) -> CompilerResult<()> {
+        let addr_reg = self.get_next_register()?;
+        let lda_instr = Instruction::new(Opcode::LDA)
+            .with_operand::<u64>(static_def.address)
+            .with_operand::<u8>(addr_reg.clone().into());
+        self.builder.push_instr(lda_instr);
+
+        match get_load_opcode(&static_def.var_type) {
+            Some(load_opcode) => {
+                // Load into the address register, keeping it the last register used
+                let load_instr = Instruction::new(load_opcode)
+                    .with_operand::<u8>(addr_reg.clone().into())
+                    .with_operand::<i16>(0)
+                    .with_operand::<u8>(addr_reg.into());
+                self.builder.push_instr(load_instr);
+            },
+            None => {
+                let size = self.get_size_of_type(&static_def.var_type)?;
+                let stack_inc_instr = Instruction::new_inc_stack(size);
+                self.inc_stack(size)?;
+                let movn_instr = Instruction::new(Opcode::MOVN_A)
+                    .with_operand::<u8>(addr_reg.into())
+                    .with_operand::<i16>(0)
+                    .with_operand::<u8>(Register::SP.into())
+                    .with_operand::<i16>(-(size as i16))
+                    .with_operand::<u32>(size as u32);
+                self.builder.push_instr(stack_inc_instr);
+                self.builder.push_instr(movn_instr);
+            }
+        };
+
+        Ok(())
+    }
+
+    /// Checks if a value of expr_type can be assigned to target_type.
+    /// Values of the inner type are assignable to optionals and results, null only to optionals.
+    pub fn check_assignable(&self, target_type: &Type, expr_type: &Type) -> CompilerResult<()> {
+        let is_assignable = match target_type {
+            Type::Optional(inner_type) => {
+                expr_type == target_type ||
+                expr_type == inner_type.deref() ||
+                *expr_type == Type::Null
+            },
+            Type::Result(inner_type) => {
+                expr_type == target_type ||
+                expr_type == inner_type.deref()
+            },
+            Type::Reference(inner_type) => {
+                // References to containers implementing an interface are assignable to it
+                expr_type == target_type ||
+                match (inner_type.deref(), expr_type) {
+                    (Type::Interface(iface_name), Type::Reference(expr_inner_type)) => {
+                        match expr_inner_type.deref() {
+                            Type::Other(cont_name) => self.get_vtable_address(cont_name, iface_name).is_ok(),
+                            _ => false
+                        }
+                    },
+                    _ => false
+                }
+            },
+            _ => expr_type == target_type
+        };
+        if !is_assignable {
+            return Err(CompilerError::TypeMismatch(target_type.clone(), expr_type.clone()));
+        }
+        Ok(())
+    }
+
+    /// Compiles an expression assigned to a value of target_type.
+    /// Values of the inner type and null are wrapped into an optional or result on the stack.
+    /// Container references are paired with their vtable when assigned to an interface reference.
+    pub fn compile_assigned_expr(&mut self, expr: &Expression, target_type: &Type) -> CompilerResult<()> {
+        let (inner_type, message_size) = match target_type {
+            Type::Optional(inner_type) => (inner_type.deref(), 0),
+            Type::Result(inner_type) => (inner_type.deref(), 16),
+            Type::Reference(inner_type) => {
+                if let Type::Interface(iface_name) = inner_type.deref() {
+                    return self.compile_iface_ref_expr(expr, iface_name);
+                }
+                return self.compile_expr(expr);
+            },
+            _ => return self.compile_expr(expr)
+        };
+        let expr_type = self.check_expr_type(expr)?;
+        if expr_type == *target_type {
+            return self.compile_expr(expr);
+        }
+
+        let has_value = expr_type != Type::Null;
+        // Null leaves the value unset, an ok result the error message
+        let mut unset_size = message_size;
+        if has_value {
+            self.compile_push_expr(expr)?;
+            // Optionals and results are not counted, their strings are kept forever
+            if !is_call_result(expr) {
+                let inner_size = self.get_size_of_type(inner_type)?;
+                self.compile_rc_retain(inner_type, Register::SP, -(inner_size as i16))?;
+            }
+        } else {
+            unset_size += self.get_size_of_type(inner_type)?;
+        }
+        if unset_size > 0 {
+            let stack_inc_instr = Instruction::new_inc_stack(unset_size);
+            self.inc_stack(unset_size)?;
+            self.builder.push_instr(stack_inc_instr);
+        }
+
+        self.compile_push_flag(has_value)
+    }
+
+    /// Compiles an expression assigned to an interface reference.
+    /// References to containers are pushed on the stack, followed by the address of their vtable.
+    fn compile_iface_ref_expr(&mut self, expr: &Expression, iface_name: &String) -> CompilerResult<()> {
+        let expr_type = self.check_expr_type(expr)?;
+        let cont_name = match &expr_type {
+            Type::Reference(inner_type) => {
+                match inner_type.deref() {
+                    Type::Other(cont_name) => cont_name,
+                    _ => return self.compile_expr(expr)
+                }
+            },
+            _ => return self.compile_expr(expr)
+        };
+        let vtable_address = self.get_vtable_address(cont_name, iface_name)?;
+
+        self.compile_push_expr(expr)?;
+
+        let vtable_reg = self.get_next_register()?;
+        let stack_inc_instr = Instruction::new_inc_stack(8);
+        self.inc_stack(8)?;
+        let lda_instr = Instruction::new(Opcode::LDA)
+            .with_operand::<u64>(vtable_address)
+            .with_operand::<u8>(vtable_reg.clone().into());
+        let mova_instr = Instruction::new(Opcode::MOVA_RA)
+            .with_operand::<u8>(vtable_reg.into())
+            .with_operand::<u8>(Register::SP.into())
+            .with_operand::<i16>(-8);
+        self.builder.push_instr(stack_inc_instr);
+        self.builder.push_instr(lda_instr);
+        self.builder.push_instr(mova_instr);
+
+        Ok(())
+    }
+
+    /// Pushes the 8 byte flag of an optional or result on the stack
+    fn compile_push_flag(&mut self, is_set: bool) -> CompilerResult<()> {
+        let flag_reg = self.get_next_register()?;
+        let stack_inc_instr = Instruction::new_inc_stack(8);
+        self.inc_stack(8)?;
+        let ldi_instr = Instruction::new(Opcode::LDI)
+            .with_operand::<i64>(is_set as i64)
+            .with_operand::<u8>(flag_reg.clone().into());
+        let movi_instr = Instruction::new(Opcode::MOVI_RA)
+            .with_operand::<u8>(flag_reg.into())
+            .with_operand::<u8>(Register::SP.into())
+            .with_operand::<i16>(-8);
+        self.builder.push_instr(stack_inc_instr);
+        self.builder.push_instr(ldi_instr);
+        self.builder.push_instr(movi_instr);
+
+        Ok(())
+    }
+
+    /// Checks if a comparison compares a value against null
+    fn is_null_check(lhs: &Expression, rhs: &Expression) -> bool {
+        *lhs == Expression::NullLiteral || *rhs == Expression::NullLiteral
+    }
+
+    /// Splits the operands of a null check into the null literal and the optional
+    fn split_null_check<'e>(lhs: &'e Expression, rhs: &'e Expression) -> (&'e Expression, &'e Expression) {
+        if *lhs == Expression::NullLiteral {
+            (lhs, rhs)
+        } else {
+            (rhs, lhs)
+        }
+    }
+
+    /// Compiles the comparison of an optional against null, by comparing its "has value" flag to 0
+    fn compile_null_check_expr(&mut self, lhs: &Expression, rhs: &Expression, cmp_opcode: Opcode) -> CompilerResult<()> {
+        let (_, opt_expr) = Self::split_null_check(lhs, rhs);
+        let opt_type = self.check_expr_type(opt_expr)?;
+        let opt_size = self.get_size_of_type(&opt_type)?;
+        self.compile_expr(opt_expr)?;
+
+        let flag_reg = self.get_next_register()?;
+        let zero_reg = self.get_next_register()?;
+        let res_reg = self.get_next_register()?;
+        let movi_instr = Instruction::new(Opcode::MOVI_AR)
+            .with_operand::<u8>(Register::SP.into())
+            .with_operand::<i16>(-8)
+            .with_operand::<u8>(flag_reg.clone().into());
+        let stack_dec_instr = Instruction::new_dec_stack(opt_size);
+        self.dec_stack(opt_size)?;
+        let ldi_instr = Instruction::new(Opcode::LDI)
+            .with_operand::<i64>(0)
+            .with_operand::<u8>(zero_reg.clone().into());
+        let cmp_instr = Instruction::new(cmp_opcode)
+            .with_operand::<u8>(flag_reg.into())
+            .with_operand::<u8>(zero_reg.into())
+            .with_operand::<u8>(res_reg.into());
+        self.builder.push_instr(movi_instr);
+        self.builder.push_instr(stack_dec_instr);
+        self.builder.push_instr(ldi_instr);
+        self.builder.push_instr(cmp_instr);
+
+        Ok(())
+    }
+
+    /// Checks if an expression is an optional of the given type
+    fn is_optional_of(&self, expr: &Expression, inner_type: &Type) -> CompilerResult<bool> {
+        let expr_type = self.check_expr_type(expr)?;
+        Ok(expr_type == Type::Optional(Box::new(inner_type.clone())))
+    }
+
+    /// Compiles unwrapping an optional, fails at runtime if it is null.
+    /// Register type values end up in the last register, others on the stack.
+    fn compile_unwrap_expr(&mut self, opt_expr: &Expression, inner_type: &Type) -> CompilerResult<()> {
+        let opt_size = self.get_size_of_type(&Type::Optional(Box::new(inner_type.clone())))?;
+        self.compile_expr(opt_expr)?;
+
+        let flag_reg = self.get_next_register()?;
+        let movi_instr = Instruction::new(Opcode::MOVI_AR)
+            .with_operand::<u8>(Register::SP.into())
+            .with_operand::<i16>(-8)
+            .with_operand::<u8>(flag_reg.clone().into());
+        let chkn_instr = Instruction::new(Opcode::CHKN)
+            .with_operand::<u8>(flag_reg.into());
+        self.builder.push_instr(movi_instr);
+        self.builder.push_instr(chkn_instr);
+
+        self.compile_take_value(inner_type, opt_size)
+    }
+
+    /// Replaces the optional or result of the given size on top of the stack by its value.
+    /// Register type values end up in the last register, others stay on the stack.
+    fn compile_take_value(&mut self, value_type: &Type, wrapper_size: usize) -> CompilerResult<()> {
+        let value_size = self.get_size_of_type(value_type)?;
+        let pop_size = match get_load_opcode(value_type) {
+            Some(load_opcode) => {
+                let value_reg = self.get_next_register()?;
+                let load_instr = Instruction::new(load_opcode)
+                    .with_operand::<u8>(Register::SP.into())
+                    .with_operand::<i16>(-(wrapper_size as i16))
+                    .with_operand::<u8>(value_reg.into());
+                self.builder.push_instr(load_instr);
+                wrapper_size
+            },
+            None => wrapper_size - value_size
+        };
+        let stack_dec_instr = Instruction::new_dec_stack(pop_size);
+        self.dec_stack(pop_size)?;
+        self.builder.push_instr(stack_dec_instr);
+
+        Ok(())
+    }
+
+    /// Compiles a cast of a result, to its value or to an optional of it.
+    /// Unwrapping an error fails at runtime with its message, converting it results in null.
+    fn compile_result_cast_expr(&mut self, result_expr: &Expression, cast_type: &Type) -> CompilerResult<()> {
+        let result_type = self.check_expr_type(result_expr)?;
+        let result_size = self.get_size_of_type(&result_type)?;
+        self.compile_expr(result_expr)?;
+
+        if let Type::Optional(_) = cast_type {
+            // Drop the message, moving the flag in its place
+            let flag_reg = self.get_next_register()?;
+            let movi_ar_instr = Instruction::new(Opcode::MOVI_AR)
+                .with_operand::<u8>(Register::SP.into())
+                .with_operand::<i16>(-8)
+                .with_operand::<u8>(flag_reg.clone().into());
+            let stack_dec_instr = Instruction::new_dec_stack(16);
+            self.dec_stack(16)?;
+            let movi_ra_instr = Instruction::new(Opcode::MOVI_RA)
+                .with_operand::<u8>(flag_reg.into())
+                .with_operand::<u8>(Register::SP.into())
+                .with_operand::<i16>(-8);
+            self.builder.push_instr(movi_ar_instr);
+            self.builder.push_instr(stack_dec_instr);
+            self.builder.push_instr(movi_ra_instr);
+            return Ok(());
+        }
+
+        let message_reg = self.get_next_register()?;
+        let subui_instr = Instruction::new(Opcode::SUBU_I)
+            .with_operand::<u8>(Register::SP.into())
+            .with_operand::<u64>(24)
+            .with_operand::<u8>(message_reg.clone().into());
+        let chkr_instr = Instruction::new(Opcode::CHKR)
+            .with_operand::<u8>(message_reg.into());
+        self.builder.push_instr(subui_instr);
+        self.builder.push_instr(chkr_instr);
+
+        self.compile_take_value(cast_type, result_size)
+    }
+
+    /// Compiles the "?" operator: unwraps a result, or returns its error from the current function
+    fn compile_try_expr(&mut self, result_expr: &Expression) -> CompilerResult<()> {
+        let result_type = self.check_expr_type(result_expr)?;
+        let value_type = match &result_type {
+            Type::Result(value_type) => value_type.deref().clone(),
+            _ => return Err(CompilerError::NotAResult(result_type))
+        };
+        let fn_ret_type = {
+            let fn_ctx = self.get_parent_function()?;
+            fn_ctx.get_ret_type()?
+        };
+        if let Type::Result(_) = fn_ret_type {} else {
+            return Err(CompilerError::NotAResult(fn_ret_type));
+        }
+        let result_size = self.get_size_of_type(&result_type)?;
+        let ret_size = self.get_size_of_type(&fn_ret_type)?;
+
+        self.compile_expr(result_expr)?;
+
+        let flag_reg = self.get_next_register()?;
+        let movi_instr = Instruction::new(Opcode::MOVI_AR)
+            .with_operand::<u8>(Register::SP.into())
+            .with_operand::<i16>(-8)
+            .with_operand::<u8>(flag_reg.clone().into());
+        self.builder.push_instr(movi_instr);
+        let tag_ok = self.uid_generator.generate();
+        let jmp_ok_instr = Instruction::new(Opcode::JMPT)
+            .with_operand::<u8>(flag_reg.into())
+            .with_operand(tag_ok);
+        self.builder.tag(tag_ok);
+        self.builder.push_instr(jmp_ok_instr);
+
+        // Copy the error message into a result of the return type, and return it
+        let stack_inc_instr = Instruction::new_inc_stack(ret_size);
+        self.inc_stack(ret_size)?;
+        let movn_instr = Instruction::new(Opcode::MOVN_A)
+            .with_operand::<u8>(Register::SP.into())
+            .with_operand::<i16>(-((ret_size + 24) as i16))
+            .with_operand::<u8>(Register::SP.into())
+            .with_operand::<i16>(-24)
+            .with_operand::<u32>(16);
+        self.builder.push_instr(stack_inc_instr);
+        self.builder.push_instr(movn_instr);
+        let error_flag_reg = self.get_next_register()?;
+        let ldi_instr = Instruction::new(Opcode::LDI)
+            .with_operand::<i64>(0)
+            .with_operand::<u8>(error_flag_reg.clone().into());
+        let movi_ra_instr = Instruction::new(Opcode::MOVI_RA)
+            .with_operand::<u8>(error_flag_reg.into())
+            .with_operand::<u8>(Register::SP.into())
+            .with_operand::<i16>(-8);
+        self.builder.push_instr(ldi_instr);
+        self.builder.push_instr(movi_ra_instr);
+        self.compile_stack_cleanup_return()?;
+        let ret_instr = Instruction::new(Opcode::RET);
+        self.builder.push_instr(ret_instr);
+
+        let pos_ok = self.builder.get_current_offset();
+        self.patch_jump_tag(&tag_ok, pos_ok)?;
+
+        self.compile_take_value(&value_type, result_size)
+    }
+
+    /// Compiles a short circuiting boolean expression.
+    /// The rhs is only evaluated if the jump on the lhs result is not taken.
+    fn compile_short_circuit_expr(&mut self, lhs: &Expression, rhs: &Expression, jmp_opcode: Opcode) -> CompilerResult<()> {
+        self.compile_expr(lhs)?;
+        let lhs_reg = self.get_last_register()?;
+        let target_reg = self.get_next_register()?;
+        let mov_lhs_instr = Instruction::new(Opcode::MOVB)
+            .with_operand::<u8>(lhs_reg.into())
+            .with_operand::<u8>(target_reg.clone().into());
+        self.builder.push_instr(mov_lhs_instr);
+        // Keep the result from being overwritten by the rhs
+        {
+            let fn_ctx = self.get_current_function_mut()?;
+            fn_ctx.register_allocator.block_register(target_reg.clone())?;
+        }
+
+        let tag_end = self.uid_generator.generate();
+        let jmp_end_instr = Instruction::new(jmp_opcode)
+            .with_operand::<u8>(target_reg.clone().into())
+            .with_operand(tag_end);
+        self.builder.tag(tag_end);
+        self.builder.push_instr(jmp_end_instr);
+
+        self.compile_expr(rhs)?;
+        let rhs_reg = self.get_last_register()?;
+        let mov_rhs_instr = Instruction::new(Opcode::MOVB)
+            .with_operand::<u8>(rhs_reg.into())
+            .with_operand::<u8>(target_reg.clone().into());
+        self.builder.push_instr(mov_rhs_instr);
+
+        let pos_end = self.builder.get_current_offset();
+        self.patch_jump_tag(&tag_end, pos_end)?;
+
+        // Unblocking puts the register at the end of the queue, making it the last temporary register again.
+        // A call on the rhs forces R0, which holds an old value if the rhs was skipped.
+        let fn_ctx = self.get_current_function_mut()?;
+        fn_ctx.register_allocator.clear_forced_temp_register();
+        fn_ctx.register_allocator.unblock_register(target_reg)
+    }
+
+    /// Compiles a conditional expression, only the picked branch is evaluated.
+    /// Both branches move their value into the same register.
+    fn compile_conditional_expr(&mut self, cond_expr: &Expression, then_expr: &Expression, else_expr: &Expression) -> CompilerResult<()> {
+        self.compile_expr(cond_expr)?;
+        let cond_reg = self.get_last_register()?;
+
+        let tag_else = self.uid_generator.generate();
+        let jmpf_else_instr = Instruction::new(Opcode::JMPF)
+            .with_operand::<u8>(cond_reg.into())
+            .with_operand(tag_else);
+        self.builder.tag(tag_else);
+        self.builder.push_instr(jmpf_else_instr);
+
+        self.compile_expr(then_expr)?;
+        let then_reg = self.get_last_register()?;
+        let target_reg = self.get_next_register()?;
+        let mov_then_instr = Instruction::new(Opcode::MOVA)
+            .with_operand::<u8>(then_reg.into())
+            .with_operand::<u8>(target_reg.clone().into());
+        self.builder.push_instr(mov_then_instr);
+        // Keep the result from being overwritten by the else branch
+        {
+            let fn_ctx = self.get_current_function_mut()?;
+            fn_ctx.register_allocator.block_register(target_reg.clone())?;
+        }
+
+        let tag_end = self.uid_generator.generate();
+        let jmp_end_instr = Instruction::new(Opcode::JMP)
+            .with_operand(tag_end);
+        self.builder.tag(tag_end);
+        self.builder.push_instr(jmp_end_instr);
+
+        let pos_else = self.builder.get_current_offset();
+        self.patch_jump_tag(&tag_else, pos_else)?;
+
+        self.compile_expr(else_expr)?;
+        let else_reg = self.get_last_register()?;
+        let mov_else_instr = Instruction::new(Opcode::MOVA)
+            .with_operand::<u8>(else_reg.into())
+            .with_operand::<u8>(target_reg.clone().into());
+        self.builder.push_instr(mov_else_instr);
+
+        let pos_end = self.builder.get_current_offset();
+        self.patch_jump_tag(&tag_end, pos_end)?;
+
+        // Unblocking makes it the last temporary register again
+        let fn_ctx = self.get_current_function_mut()?;
+        fn_ctx.register_allocator.unblock_register(target_reg)
+    }
+
+    /// Compiles a block expression, its value is left in the last register.
+    /// The locals of the block are popped off the stack before continuing.
+    fn compile_block_expr(&mut self, stmt_list: &[Statement], value_expr: &Expression) -> CompilerResult<()> {
+        // The block continues with the registers of the enclosing expression,
+        // so registers blocked by it stay untouched
+        let mut block_fn_ctx = {
+            let fn_ctx = self.get_current_function_mut()?;
+            let mut block_fn_ctx = FunctionContext::new_weak(fn_ctx)?;
+            block_fn_ctx.register_allocator = replace(&mut fn_ctx.register_allocator, RegisterAllocator::new());
+            block_fn_ctx
+        };
+        self.push_function_context(block_fn_ctx);
+
+        self.compile_stmt_list(stmt_list)?;
+        self.compile_expr(value_expr)?;
+
+        block_fn_ctx = self.pop_function_context()?;
+        self.compile_stack_cleanup_block(&block_fn_ctx)?;
+
+        // Hand the registers back, the value is in the last one
+        let fn_ctx = self.get_current_function_mut()?;
+        fn_ctx.register_allocator = block_fn_ctx.register_allocator;
+        Ok(())
+    }
+
+    /// Evaluates an expression at compile time, returns the literal it results in
+    pub fn eval_const_expr(&self, expr: &Expression) -> CompilerResult<Expression> {
+        let binary = |lhs: &Expression, rhs: &Expression| -> CompilerResult<(Box<Expression>, Box<Expression>)> {
+            Ok((Box::new(self.eval_const_expr(lhs)?), Box::new(self.eval_const_expr(rhs)?)))
+        };
+        let evaluated = match expr {
+            Expression::IntLiteral(_) |
+            Expression::FloatLiteral(_) |
+            Expression::DoubleLiteral(_) |
+            Expression::BoolLiteral(_) |
+            Expression::CharLiteral(_) |
+            Expression::StringLiteral(_) => return Ok(expr.clone()),
+            Expression::EnumValue(enum_name, variant) => {
+                let enum_def = self.resolve_enum(enum_name)?;
+                Expression::IntLiteral(enum_def.get_variant_value(variant)?)
             },
-            Type::Float => {
-                let reg = {
-                    let fn_ctx = self.get_current_function_mut()?;
-                    fn_ctx.register_allocator.get_temp_register()?
-                };
-                let movf_instr = Instruction::new(Opcode::MOVF_AR)
-                    .with_operand::<u8>(Register::SP.into())
-                    .with_operand::<i16>(var_offset as i16)
-                    .with_operand::<u8>(reg.into());
-                self.builder.push_instr(movf_instr);
+            Expression::SizeOf(size_type) => {
+                Expression::IntLiteral(self.get_size_of_type(size_type)? as i64)
             },
-            Type::Bool => {
-                let reg = {
-                    let fn_ctx = self.get_current_function_mut()?;
-                    fn_ctx.register_allocator.get_temp_register()?
-                };
-                let movb_instr = Instruction::new(Opcode::MOVB_AR)
-                    .with_operand::<u8>(Register::SP.into())
-                    .with_operand::<i16>(var_offset as i16)
-                    .with_operand::<u8>(reg.into());
-                self.builder.push_instr(movb_instr);
+            Expression::TypeOf(inner) => {
+                Expression::StringLiteral(format!("\"{}\"", self.check_expr_type(inner)?))
             },
-            Type::Reference(inner_type) => {
-                match inner_type.deref() {
-                    Type::AutoArray(_) => {
-                        let stack_inc_instr = Instruction::new_inc_stack(16);
-                        self.inc_stack(16)?;
-                        var_offset -= 16;
-                        let movn_instr = Instruction::new(Opcode::MOVN_A)
-                            .with_operand::<u8>(Register::SP.into())
-                            .with_operand::<i16>(var_offset as i16)
-                            .with_operand::<u8>(Register::SP.into())
-                            .with_operand::<i16>(-16)
-                            .with_operand::<u32>(16);
-                        self.builder.push_instr(stack_inc_instr);
-                        self.builder.push_instr(movn_instr);
-                    },
-                    _ => {
-                        let reg = {
-                            let fn_ctx = self.get_current_function_mut()?;
-                            fn_ctx.register_allocator.get_temp_register()?
-                        };
-                        let mova_instr = Instruction::new(Opcode::MOVA_AR)
-                            .with_operand::<u8>(Register::SP.into())
-                            .with_operand::<i16>(var_offset as i16)
-                            .with_operand::<u8>(reg.into());
-                        self.builder.push_instr(mova_instr);
-                    }
-                };
+            Expression::Not(inner) => Expression::Not(Box::new(self.eval_const_expr(inner)?)),
+            Expression::Negation(inner) => Expression::Negation(Box::new(self.eval_const_expr(inner)?)),
+            Expression::Conditional(cond_expr, then_expr, else_expr) => {
+                self.check_expr_type(expr)?;
+                match self.eval_const_expr(cond_expr)? {
+                    Expression::BoolLiteral(true) => self.eval_const_expr(then_expr)?,
+                    _ => self.eval_const_expr(else_expr)?
+                }
             },
-            Type::Other(cont_name) => {
-                let cont_def = self.resolve_container(&cont_name)?;
-                let size = cont_def.get_size(self)?;
+            Expression::Cast(inner, cast_type) => {
+                self.check_expr_type(expr)?;
+                Expression::Cast(Box::new(self.eval_const_expr(inner)?), cast_type.clone())
+            },
+            Expression::Addition(lhs, rhs) => {
+                let (lhs, rhs) = binary(lhs, rhs)?;
+                Expression::Addition(lhs, rhs)
+            },
+            Expression::Subtraction(lhs, rhs) => {
+                let (lhs, rhs) = binary(lhs, rhs)?;
+                Expression::Subtraction(lhs, rhs)
+            },
+            Expression::Multiplication(lhs, rhs) => {
+                let (lhs, rhs) = binary(lhs, rhs)?;
+                Expression::Multiplication(lhs, rhs)
+            },
+            Expression::Division(lhs, rhs) => {
+                let (lhs, rhs) = binary(lhs, rhs)?;
+                Expression::Division(lhs, rhs)
+            },
+            Expression::Modulo(lhs, rhs) => {
+                let (lhs, rhs) = binary(lhs, rhs)?;
+                Expression::Modulo(lhs, rhs)
+            },
+            Expression::And(lhs, rhs) => {
+                let (lhs, rhs) = binary(lhs, rhs)?;
+                Expression::And(lhs, rhs)
+            },
+            Expression::Or(lhs, rhs) => {
+                let (lhs, rhs) = binary(lhs, rhs)?;
+                Expression::Or(lhs, rhs)
+            },
+            Expression::Equals(lhs, rhs) => {
+                let (lhs, rhs) = binary(lhs, rhs)?;
+                Expression::Equals(lhs, rhs)
+            },
+            Expression::NotEquals(lhs, rhs) => {
+                let (lhs, rhs) = binary(lhs, rhs)?;
+                Expression::NotEquals(lhs, rhs)
+            },
+            Expression::GreaterThan(lhs, rhs) => {
+                let (lhs, rhs) = binary(lhs, rhs)?;
+                Expression::GreaterThan(lhs, rhs)
+            },
+            Expression::LessThan(lhs, rhs) => {
+                let (lhs, rhs) = binary(lhs, rhs)?;
+                Expression::LessThan(lhs, rhs)
+            },
+            Expression::GreaterThanEquals(lhs, rhs) => {
+                let (lhs, rhs) = binary(lhs, rhs)?;
+                Expression::GreaterThanEquals(lhs, rhs)
+            },
+            Expression::LessThanEquals(lhs, rhs) => {
+                let (lhs, rhs) = binary(lhs, rhs)?;
+                Expression::LessThanEquals(lhs, rhs)
+            },
+            _ => return Err(CompilerError::NotConstant(expr.clone()))
+        };
 
-                let stack_inc_instr = Instruction::new_inc_stack(size);
-                self.inc_stack(size)?;
+        // Mixed numeric operands are converted like at runtime
+        if let Some(promoted_expr) = self.promote_operands(&evaluated)? {
+            return self.eval_const_expr(&promoted_expr);
+        }
 
-                var_offset -= size as i64;
+        match fold_expr(evaluated) {
+            folded @ Expression::IntLiteral(_) |
+            folded @ Expression::FloatLiteral(_) |
+            folded @ Expression::DoubleLiteral(_) |
+            folded @ Expression::BoolLiteral(_) |
+            folded @ Expression::CharLiteral(_) |
+            folded @ Expression::StringLiteral(_) => Ok(folded),
+            _ => Err(CompilerError::NotConstant(expr.clone()))
+        }
+    }
 
-                let movn_instr = Instruction::new(Opcode::MOVN_A)
-                    .with_operand::<u8>(Register::SP.into())
-                    .with_operand::<i16>(var_offset as i16)
-                    .with_operand::<u8>(Register::SP.into())
-                    .with_operand::<i16>(-(size as i16))
-                    .with_operand::<u32>(size as u32);
-                
-                self.builder.push_instr(stack_inc_instr);
-                self.builder.push_instr(movn_instr);
-            },
-            _ => {
-                //println!("Errors in compile_var_expr()");
-                return Err(CompilerError::UnknownType(var_type));
-            },
+    /// Checks the operand types of a binary operation, returns the type both are converted to
+    fn check_operand_types(&self, lhs: &Expression, rhs: &Expression) -> CompilerResult<Type> {
+        let lhs_type = self.check_expr_type(lhs)?;
+        let rhs_type = self.check_expr_type(rhs)?;
+        if lhs_type == rhs_type {
+            return Ok(lhs_type);
+        }
+        match promote_numeric(&lhs_type, &rhs_type) {
+            Some(op_type) if !self.strict_numeric => Ok(op_type),
+            _ => Err(CompilerError::TypeMismatch(lhs_type, rhs_type))
+        }
+    }
+
+    /// Rewrites a binary operation on different numeric types to cast the narrower operand,
+    /// returns None if no operand needs to be converted
+    fn promote_operands(&self, expr: &Expression) -> CompilerResult<Option<Expression>> {
+        let (lhs, rhs) = match expr {
+            Expression::Addition(lhs, rhs) |
+            Expression::Subtraction(lhs, rhs) |
+            Expression::Multiplication(lhs, rhs) |
+            Expression::Division(lhs, rhs) |
+            Expression::Modulo(lhs, rhs) |
+            Expression::LessThan(lhs, rhs) |
+            Expression::GreaterThan(lhs, rhs) |
+            Expression::LessThanEquals(lhs, rhs) |
+            Expression::GreaterThanEquals(lhs, rhs) |
+            Expression::Equals(lhs, rhs) |
+            Expression::NotEquals(lhs, rhs) => (lhs, rhs),
+            _ => return Ok(None)
+        };
+        let lhs_type = self.check_expr_type(lhs)?;
+        let rhs_type = self.check_expr_type(rhs)?;
+        if lhs_type == rhs_type || self.strict_numeric {
+            return Ok(None);
+        }
+        let op_type = match promote_numeric(&lhs_type, &rhs_type) {
+            Some(op_type) => op_type,
+            None => return Ok(None)
+        };
+        let convert = |op: &Expression, from_type: Type| {
+            if from_type == op_type {
+                Box::new(op.clone())
+            } else {
+                Box::new(Expression::Cast(Box::new(op.clone()), op_type.clone()))
+            }
+        };
+        let lhs = convert(lhs, lhs_type);
+        let rhs = convert(rhs, rhs_type);
+        let promoted_expr = match expr {
+            Expression::Addition(_, _) => Expression::Addition(lhs, rhs),
+            Expression::Subtraction(_, _) => Expression::Subtraction(lhs, rhs),
+            Expression::Multiplication(_, _) => Expression::Multiplication(lhs, rhs),
+            Expression::Division(_, _) => Expression::Division(lhs, rhs),
+            Expression::Modulo(_, _) => Expression::Modulo(lhs, rhs),
+            Expression::LessThan(_, _) => Expression::LessThan(lhs, rhs),
+            Expression::GreaterThan(_, _) => Expression::GreaterThan(lhs, rhs),
+            Expression::LessThanEquals(_, _) => Expression::LessThanEquals(lhs, rhs),
+            Expression::GreaterThanEquals(_, _) => Expression::GreaterThanEquals(lhs, rhs),
+            Expression::Equals(_, _) => Expression::Equals(lhs, rhs),
+            _ => Expression::NotEquals(lhs, rhs)
         };
+        Ok(Some(promoted_expr))
+    }
 
-        Ok(())
+    /// Checks the type of the value of a block expression, with the locals of the block declared
+    fn check_block_expr_type(&self, stmt_list: &[Statement], value_expr: &Expression) -> CompilerResult<Type> {
+        for stmt in stmt_list.iter() {
+            if let Statement::VariableDecl(var_decl_args) = stmt.without_position() {
+                let mut var_type = var_decl_args.var_type.clone();
+                if var_type == Type::Auto {
+                    var_type = self.check_expr_type(&var_decl_args.assignment)?;
+                } else {
+                    self.canonize_type(&mut var_type)?;
+                }
+                if let Some(var_types) = self.block_var_types.borrow_mut().last_mut() {
+                    var_types.insert(var_decl_args.name.clone(), var_type);
+                }
+            }
+        }
+        self.check_expr_type(value_expr)
     }
 
     /// Returns the type of an expression and checks for type mismatches
@@ -3089,8 +7540,136 @@ impl Compiler {
         let expr_type = match expr {
             Expression::IntLiteral(_) => Type::Int,
             Expression::FloatLiteral(_) => Type::Float,
+            Expression::DoubleLiteral(_) => Type::Double,
             Expression::BoolLiteral(_) => Type::Bool,
+            Expression::CharLiteral(_) => Type::Char,
             Expression::StringLiteral(_) => Type::String,
+            Expression::NullLiteral => Type::Null,
+            Expression::Try(op) => {
+                match self.check_expr_type(op)? {
+                    Type::Result(value_type) => value_type.deref().clone(),
+                    op_type => return Err(CompilerError::NotAResult(op_type))
+                }
+            },
+            Expression::EnumValue(enum_name, variant) => {
+                if let Some(static_def) = self.resolve_assoc_constant(enum_name, variant)? {
+                    return Ok(static_def.var_type);
+                }
+                let enum_def = self.resolve_enum(enum_name)?;
+                enum_def.get_variant_value(variant)?;
+                Type::Enum(enum_def.canonical_name)
+            },
+            Expression::SizeOf(size_type) => {
+                self.get_size_of_type(size_type)?;
+                Type::Int
+            },
+            Expression::TypeOf(inner) => {
+                self.check_expr_type(inner)?;
+                Type::String
+            },
+            Expression::Slice(base_expr, start_expr, end_expr) => {
+                for bound_expr in [start_expr, end_expr].iter().filter_map(|bound_expr| bound_expr.as_ref()) {
+                    let bound_type = self.check_expr_type(bound_expr)?;
+                    if bound_type != Type::Int {
+                        return Err(CompilerError::TypeMismatch(Type::Int, bound_type));
+                    }
+                }
+                let base_type = self.check_expr_type(base_expr)?;
+                self.get_slice_elem_size(&base_type)?;
+                match base_type {
+                    Type::String => Type::String,
+                    Type::Array(inner_type, _) => Type::Reference(Box::new(Type::AutoArray(inner_type))),
+                    Type::Reference(ref_type) => {
+                        match *ref_type {
+                            Type::Array(inner_type, _) |
+                            Type::AutoArray(inner_type) => Type::Reference(Box::new(Type::AutoArray(inner_type))),
+                            ref_type => return Err(CompilerError::NotIndexable(Type::Reference(Box::new(ref_type))))
+                        }
+                    },
+                    base_type => return Err(CompilerError::NotIndexable(base_type))
+                }
+            },
+            Expression::Index(base_expr, index_expr) => {
+                let index_type = self.check_expr_type(index_expr)?;
+                if index_type == Type::Range {
+                    let (start_expr, end_expr) = get_range_bounds(index_expr);
+                    let slice_expr = Expression::Slice(base_expr.clone(), Some(Box::new(start_expr)), Some(Box::new(end_expr)));
+                    return self.check_expr_type(&slice_expr);
+                }
+                if index_type != Type::Int {
+                    return Err(CompilerError::TypeMismatch(Type::Int, index_type));
+                }
+                match self.check_expr_type(base_expr)? {
+                    Type::String => Type::Int,
+                    Type::Array(inner_type, _) => *inner_type,
+                    Type::Reference(ref_type) => {
+                        match *ref_type {
+                            Type::Array(inner_type, _) |
+                            Type::AutoArray(inner_type) => *inner_type,
+                            ref_type => return Err(CompilerError::NotIndexable(Type::Reference(Box::new(ref_type))))
+                        }
+                    },
+                    base_type => return Err(CompilerError::NotIndexable(base_type))
+                }
+            },
+            Expression::Conditional(cond_expr, then_expr, else_expr) => {
+                let cond_type = self.check_expr_type(cond_expr)?;
+                if cond_type != Type::Bool {
+                    return Err(CompilerError::TypeMismatch(Type::Bool, cond_type));
+                }
+                let then_type = self.check_expr_type(then_expr)?;
+                let else_type = self.check_expr_type(else_expr)?;
+                if then_type != else_type {
+                    return Err(CompilerError::TypeMismatch(then_type, else_type));
+                }
+                // The value is picked in a register
+                if !then_type.is_primitive() {
+                    return Err(CompilerError::UnsupportedExpression(expr.clone()));
+                }
+                then_type
+            },
+            Expression::Block(stmt_list, value_expr) => {
+                self.block_var_types.borrow_mut().push(HashMap::new());
+                let value_type_res = self.check_block_expr_type(stmt_list, value_expr);
+                self.block_var_types.borrow_mut().pop();
+                let value_type = value_type_res?;
+                // The value is kept in a register past the end of the block
+                if !value_type.is_primitive() {
+                    return Err(CompilerError::UnsupportedExpression(expr.clone()));
+                }
+                value_type
+            },
+            Expression::Range(start_expr, end_expr, _) => {
+                for bound_expr in [start_expr, end_expr].iter() {
+                    let bound_type = self.check_expr_type(bound_expr)?;
+                    if bound_type != Type::Int {
+                        return Err(CompilerError::TypeMismatch(Type::Int, bound_type));
+                    }
+                }
+                Type::Range
+            },
+            Expression::Len(base_expr) => {
+                let base_type = self.check_expr_type(base_expr)?;
+                self.get_slice_elem_size(&base_type)?;
+                Type::Int
+            },
+            Expression::CharAt(base_expr, index_expr) => {
+                let base_type = self.check_expr_type(base_expr)?;
+                if base_type != Type::String {
+                    return Err(CompilerError::TypeMismatch(Type::String, base_type));
+                }
+                let index_type = self.check_expr_type(index_expr)?;
+                if index_type != Type::Int {
+                    return Err(CompilerError::TypeMismatch(Type::Int, index_type));
+                }
+                Type::Char
+            },
+            Expression::Resume(co_expr) => {
+                match self.check_expr_type(co_expr)? {
+                    Type::Coroutine(value_type) => Type::Optional(value_type),
+                    co_type => return Err(CompilerError::TypeMismatch(Type::Coroutine(Box::new(Type::Auto)), co_type))
+                }
+            },
             Expression::Ref(expr) => {
                 let expr_type = self.check_expr_type(expr)?;
                 Type::Reference(Box::new(expr_type))
@@ -3107,9 +7686,28 @@ impl Compiler {
                     _ => return Err(CompilerError::CannotDerefNonPointer)
                 };
             },
-            Expression::Call(fn_name, _) => {
-                let fn_def = self.resolve_function(fn_name)?;
-                fn_def.ret_type
+            Expression::Call(fn_name, fn_args) => {
+                match self.get_closure_type(fn_name) {
+                    Some(Type::Function(_, ret_type)) => ret_type.deref().clone(),
+                    _ => match self.resolve_function(fn_name) {
+                        Ok(fn_def) => fn_def.ret_type,
+                        Err(error) => {
+                            let generic_def = self.resolve_generic_function(fn_name)
+                                .map_err(|_| error)?;
+                            self.get_generic_ret_type(&generic_def, fn_args)?
+                        }
+                    }
+                }
+            },
+            Expression::Lambda(fn_decl_args) => {
+                let mut fn_type = Type::Function(
+                    fn_decl_args.arguments.iter()
+                        .map(|(_, arg_type)| arg_type.clone())
+                        .collect(),
+                    Box::new(fn_decl_args.returns.clone())
+                );
+                self.canonize_type(&mut fn_type)?;
+                fn_type
             },
             Expression::Variable(var_name) => {
                 self.get_type_of_var(var_name)?
@@ -3117,8 +7715,9 @@ impl Compiler {
             Expression::MemberAccess(_, _) => {
                 self.check_member_access_expr_type(expr, None)?
             },
-            Expression::ContainerInstance(cont_name, _) => {
-                Type::Other(cont_name.clone())
+            Expression::ContainerInstance(cont_name, member_map) => {
+                let cont_def = self.resolve_cont_instance_def(cont_name, member_map)?;
+                Type::Other(cont_def.canonical_name.clone())
             },
             Expression::Assign(lhs, rhs) => {
                 let lhs_type = self.check_expr_type(lhs)?;
@@ -3129,83 +7728,50 @@ impl Compiler {
                 lhs_type
             },
             Expression::Addition(lhs, rhs) => {
-                let lhs_type = self.check_expr_type(lhs)?;
-                let rhs_type = self.check_expr_type(rhs)?;
-                if lhs_type != rhs_type {
-                    return Err(CompilerError::TypeMismatch(lhs_type, rhs_type));
-                }
-                lhs_type
+                self.check_operand_types(lhs, rhs)?
             },
             Expression::Subtraction(lhs, rhs) => {
-                let lhs_type = self.check_expr_type(lhs)?;
-                let rhs_type = self.check_expr_type(rhs)?;
-                if lhs_type != rhs_type {
-                    return Err(CompilerError::TypeMismatch(lhs_type, rhs_type));
-                }
-                lhs_type
+                self.check_operand_types(lhs, rhs)?
             },
             Expression::Multiplication(lhs, rhs) => {
-                let lhs_type = self.check_expr_type(lhs)?;
-                let rhs_type = self.check_expr_type(rhs)?;
-                if lhs_type != rhs_type {
-                    return Err(CompilerError::TypeMismatch(lhs_type, rhs_type));
-                }
-                lhs_type
+                self.check_operand_types(lhs, rhs)?
             },
             Expression::Division(lhs, rhs) => {
-                let lhs_type = self.check_expr_type(lhs)?;
-                let rhs_type = self.check_expr_type(rhs)?;
-                if lhs_type != rhs_type {
-                    return Err(CompilerError::TypeMismatch(lhs_type, rhs_type));
-                }
-                lhs_type
+                self.check_operand_types(lhs, rhs)?
+            },
+            Expression::Modulo(lhs, rhs) => {
+                self.check_operand_types(lhs, rhs)?
             },
             Expression::LessThan(lhs, rhs) => {
-                let lhs_type = self.check_expr_type(lhs)?;
-                let rhs_type = self.check_expr_type(rhs)?;
-                if lhs_type != rhs_type {
-                    return Err(CompilerError::TypeMismatch(lhs_type, rhs_type));
-                }
+                self.check_operand_types(lhs, rhs)?;
                 Type::Bool
             },
             Expression::GreaterThan(lhs, rhs) => {
-                let lhs_type = self.check_expr_type(lhs)?;
-                let rhs_type = self.check_expr_type(rhs)?;
-                if lhs_type != rhs_type {
-                    return Err(CompilerError::TypeMismatch(lhs_type, rhs_type));
-                }
+                self.check_operand_types(lhs, rhs)?;
                 Type::Bool
             },
             Expression::LessThanEquals(lhs, rhs) => {
-                let lhs_type = self.check_expr_type(lhs)?;
-                let rhs_type = self.check_expr_type(rhs)?;
-                if lhs_type != rhs_type {
-                    return Err(CompilerError::TypeMismatch(lhs_type, rhs_type));
-                }
+                self.check_operand_types(lhs, rhs)?;
                 Type::Bool
             },
             Expression::GreaterThanEquals(lhs, rhs) => {
-                let lhs_type = self.check_expr_type(lhs)?;
-                let rhs_type = self.check_expr_type(rhs)?;
-                if lhs_type != rhs_type {
-                    return Err(CompilerError::TypeMismatch(lhs_type, rhs_type));
-                }
+                self.check_operand_types(lhs, rhs)?;
                 Type::Bool
             },
-            Expression::Equals(lhs, rhs) => {
-                let lhs_type = self.check_expr_type(lhs)?;
-                let rhs_type = self.check_expr_type(rhs)?;
-                if lhs_type != rhs_type {
-                    return Err(CompilerError::TypeMismatch(lhs_type, rhs_type));
+            Expression::Equals(lhs, rhs) |
+            Expression::NotEquals(lhs, rhs) if Self::is_null_check(lhs, rhs) => {
+                let (_, opt_expr) = Self::split_null_check(lhs, rhs);
+                match self.check_expr_type(opt_expr)? {
+                    Type::Optional(_) => Type::Bool,
+                    opt_type => return Err(CompilerError::TypeMismatch(Type::Optional(Box::new(opt_type.clone())), opt_type))
                 }
+            },
+            Expression::Equals(lhs, rhs) => {
+                self.check_operand_types(lhs, rhs)?;
                 Type::Bool
             },
             Expression::NotEquals(lhs, rhs) => {
-                let lhs_type = self.check_expr_type(lhs)?;
-                let rhs_type = self.check_expr_type(rhs)?;
-                if lhs_type != rhs_type {
-                    return Err(CompilerError::TypeMismatch(lhs_type, rhs_type));
-                }
+                self.check_operand_types(lhs, rhs)?;
                 Type::Bool
             },
             Expression::Not(op) => {
@@ -3215,19 +7781,81 @@ impl Compiler {
                 }
                 Type::Bool
             },
-            Expression::And(lhs, rhs) => {
-                let lhs_type = self.check_expr_type(lhs)?;
-                let rhs_type = self.check_expr_type(rhs)?;
-                if lhs_type != rhs_type {
-                    return Err(CompilerError::TypeMismatch(lhs_type, rhs_type));
+            Expression::Negation(op) => {
+                let op_type = self.check_expr_type(op)?;
+                if op_type != Type::Int && op_type != Type::Float && op_type != Type::Double {
+                    return Err(CompilerError::TypeMismatch(Type::Int, op_type));
                 }
-                Type::Bool
+                op_type
+            },
+            Expression::Tuple(elem_exprs) => {
+                let mut elem_types = Vec::new();
+                for elem_expr in elem_exprs.iter() {
+                    elem_types.push(self.check_expr_type(elem_expr)?);
+                }
+                Type::Tuple(elem_types)
+            },
+            Expression::Array(elem_exprs) => {
+                let mut elem_type_opt: Option<Type> = None;
+                for elem_expr in elem_exprs.iter() {
+                    let elem_type = self.check_expr_type(elem_expr)?;
+                    match &elem_type_opt {
+                        Some(first_type) if *first_type != elem_type => {
+                            return Err(CompilerError::TypeMismatch(first_type.clone(), elem_type));
+                        },
+                        _ => elem_type_opt = Some(elem_type)
+                    };
+                }
+                let elem_type = elem_type_opt.ok_or_else(|| CompilerError::UnsupportedExpression(expr.clone()))?;
+                Type::Array(Box::new(elem_type), elem_exprs.len())
+            },
+            Expression::TupleIndex(base_expr, index) => {
+                let base_type = self.check_expr_type(base_expr)?;
+                match &base_type {
+                    Type::Tuple(elem_types) => {
+                        elem_types.get(*index)
+                            .cloned()
+                            .ok_or_else(|| CompilerError::TupleIndexOutOfBounds(base_type.clone(), *index))?
+                    },
+                    // The start and the exclusive end
+                    Type::Range if *index < 2 => Type::Int,
+                    Type::Range => return Err(CompilerError::TupleIndexOutOfBounds(base_type.clone(), *index)),
+                    _ => return Err(CompilerError::NotATuple(base_type))
+                }
+            },
+            Expression::Cast(op, cast_type) => {
+                let op_type = self.check_expr_type(op)?;
+                let is_valid = match (&op_type, cast_type) {
+                    // Unwraps an optional, null fails at runtime
+                    (Type::Optional(inner_type), cast_type) if inner_type.deref() == cast_type => true,
+                    // Unwraps a result, errors fail at runtime, or converts it to an optional
+                    (Type::Result(inner_type), cast_type) => {
+                        inner_type.deref() == cast_type ||
+                        Type::Optional(inner_type.clone()) == *cast_type
+                    },
+                    (Type::Int, Type::Float) |
+                    (Type::Float, Type::Int) |
+                    (Type::Int, Type::Double) |
+                    (Type::Double, Type::Int) |
+                    (Type::Float, Type::Double) |
+                    (Type::Double, Type::Float) |
+                    (Type::Bool, Type::Int) |
+                    (Type::Char, Type::Int) |
+                    (Type::Enum(_), Type::Int) => true,
+                    (op_type, cast_type) => op_type == cast_type && op_type.is_primitive()
+                };
+                if !is_valid {
+                    return Err(CompilerError::InvalidCast(op_type, cast_type.clone()));
+                }
+                cast_type.clone()
             },
+            Expression::And(lhs, rhs) |
             Expression::Or(lhs, rhs) => {
-                let lhs_type = self.check_expr_type(lhs)?;
-                let rhs_type = self.check_expr_type(rhs)?;
-                if lhs_type != rhs_type {
-                    return Err(CompilerError::TypeMismatch(lhs_type, rhs_type));
+                for op in [lhs, rhs].iter() {
+                    let op_type = self.check_expr_type(op)?;
+                    if Type::Bool != op_type {
+                        return Err(CompilerError::TypeMismatch(Type::Bool, op_type));
+                    }
                 }
                 Type::Bool
             },
@@ -3258,45 +7886,34 @@ impl Compiler {
             _ => return Err(CompilerError::UnsupportedExpression(lhs_expr.clone()))
         };
 
-        let cont_name = match &lhs_type {
-            Type::Other(cont_name) => cont_name,
-            Type::Reference(inner_type) => {
-                match inner_type.deref() {
-                    Type::Other(cont_name) => cont_name,
+        if let Type::Reference(inner_type) = &lhs_type {
+            if let Type::Interface(iface_name) = inner_type.deref() {
+                // Only member functions can be used through interface references
+                let fn_name = match rhs_expr {
+                    Expression::Call(fn_name, _) => fn_name,
                     _ => return Err(CompilerError::MemberAccessOnNonContainer)
-                }
-            },
-            _ => return Err(CompilerError::MemberAccessOnNonContainer)
-        };
+                };
+                let iface_def = self.resolve_interface(iface_name)?;
+                let (_, fn_def) = iface_def.get_function(fn_name)?;
+                return Ok(fn_def.ret_type.clone());
+            }
+        }
 
-        let cont_def = self.resolve_container(cont_name)?;
+        let cont_def = self.resolve_impl_container(&lhs_type)?;
 
         match &rhs_expr {
             Expression::Variable(var_name) => {
-                cont_def.get_member_type(var_name)
+                let mut member_type = cont_def.get_member_type(var_name)?;
+                self.canonize_type(&mut member_type)?;
+                Ok(member_type)
             },
             Expression::Call(fn_name, _) => {
                 let fn_def = cont_def.get_member_function(fn_name)?;
                 Ok(fn_def.ret_type.clone())
             },
-            Expression::MemberAccess(member_expr, _) => {
-                let member_name = match member_expr.deref() {
-                    Expression::Variable(var_name) => var_name,
-                    _ => return Err(CompilerError::UnsupportedExpression(member_expr.deref().clone()))
-                };
-                let member_type = cont_def.get_member_type(member_name)?;
-                let child_cont_name = match &member_type {
-                    Type::Other(cont_name) => cont_name,
-                    Type::Reference(inner_type) => {
-                        match inner_type.deref() {
-                            Type::Other(cont_name) => cont_name,
-                            _ => return Err(CompilerError::MemberAccessOnNonContainer)
-                        }
-                    },
-                    _ => return Err(CompilerError::MemberAccessOnNonContainer)
-                };
-                let child_cont_def = self.resolve_container(child_cont_name)?;
-                self.check_member_access_expr_type(rhs_expr, Some(&child_cont_def))
+            Expression::MemberAccess(_, _) => {
+                // The lhs of the nested access is a member of this container
+                self.check_member_access_expr_type(rhs_expr, Some(&cont_def))
             },
             _ => return Err(CompilerError::MemberAccessOnNonContainer)
         }
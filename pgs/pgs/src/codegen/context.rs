@@ -2,7 +2,11 @@ use crate::{
     codegen::{
         def::{
             ContainerDef,
-            FunctionDef
+            EnumDef,
+            FunctionDef,
+            GenericFunctionDef,
+            InterfaceDef,
+            StaticDef
         },
         register::{
             Register,
@@ -23,17 +27,26 @@ use crate::{
 
 use std::{
     collections::{
-        HashMap
+        HashMap,
+        HashSet
     }
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ModuleContext {
     pub name: String,
     pub modules: HashMap<String, ModuleContext>,
     pub functions: HashMap<String, FunctionDef>,
+    pub generic_functions: HashMap<String, GenericFunctionDef>,
     pub containers: HashMap<String, ContainerDef>,
-    pub imports: HashMap<String, String>
+    pub enums: HashMap<String, EnumDef>,
+    pub interfaces: HashMap<String, InterfaceDef>,
+    pub statics: HashMap<String, StaticDef>,
+    pub imports: HashMap<String, String>,
+    pub wildcard_imports: Vec<String>,
+    pub exports: HashSet<String>,
+    /// Whether the module is visible outside of its parent module
+    pub public: bool
 }
 
 impl ModuleContext {
@@ -43,8 +56,15 @@ impl ModuleContext {
             name: name,
             modules: HashMap::new(),
             functions: HashMap::new(),
+            generic_functions: HashMap::new(),
             containers: HashMap::new(),
-            imports: HashMap::new()
+            enums: HashMap::new(),
+            interfaces: HashMap::new(),
+            statics: HashMap::new(),
+            imports: HashMap::new(),
+            wildcard_imports: Vec::new(),
+            exports: HashSet::new(),
+            public: true
         }
     }
 
@@ -52,13 +72,24 @@ impl ModuleContext {
     /// Throws a DuplicateFunctionError if a function with the 
     /// same name already exists.
     pub fn add_function(&mut self, def: FunctionDef) -> CompilerResult<()> {
-        if self.functions.contains_key(&def.name) {
+        if self.functions.contains_key(&def.name) || self.generic_functions.contains_key(&def.name) {
             return Err(CompilerError::DuplicateFunction(def.name));
         }
         self.functions.insert(def.name.clone(), def);
         Ok(())
     }
 
+    /// Adds a generic function definition to a module context.
+    /// Throws a DuplicateFunctionError if a function with the
+    /// same name already exists.
+    pub fn add_generic_function(&mut self, def: GenericFunctionDef) -> CompilerResult<()> {
+        if self.functions.contains_key(&def.name) || self.generic_functions.contains_key(&def.name) {
+            return Err(CompilerError::DuplicateFunction(def.name));
+        }
+        self.generic_functions.insert(def.name.clone(), def);
+        Ok(())
+    }
+
     /// Adds a module context to a module context.
     /// Throws a DuplicateModuleError if a module with the
     /// same name already exists.
@@ -81,10 +112,46 @@ impl ModuleContext {
         Ok(())
     }
 
+    /// Adds an enum definition to a module context.
+    /// Throws a DuplicateEnumError if an enum with the
+    /// same name already exists.
+    pub fn add_enum(&mut self, enum_def: EnumDef) -> CompilerResult<()> {
+        if self.enums.contains_key(&enum_def.name) {
+            return Err(CompilerError::DuplicateEnum(enum_def.name));
+        }
+        self.enums.insert(enum_def.name.clone(), enum_def);
+        Ok(())
+    }
+
+    /// Adds an interface definition to a module context.
+    /// Throws a DuplicateInterfaceError if an interface with the
+    /// same name already exists.
+    pub fn add_interface(&mut self, iface_def: InterfaceDef) -> CompilerResult<()> {
+        if self.interfaces.contains_key(&iface_def.name) {
+            return Err(CompilerError::DuplicateInterface(iface_def.name));
+        }
+        self.interfaces.insert(iface_def.name.clone(), iface_def);
+        Ok(())
+    }
+
+    /// Adds a static variable definition to a module context.
+    /// Throws a DuplicateVariableError if a static variable with the
+    /// same name already exists.
+    pub fn add_static(&mut self, static_def: StaticDef) -> CompilerResult<()> {
+        if self.statics.contains_key(&static_def.name) {
+            return Err(CompilerError::DuplicateVariable(static_def.name));
+        }
+        self.statics.insert(static_def.name.clone(), static_def);
+        Ok(())
+    }
+
     /// Adds an import declaration to a module context
     /// Throws a DuplicateImportError if an import with the same
     /// "import_as" name already exists.
     pub fn add_import(&mut self, import_as: String, import_path: String) -> CompilerResult<()> {
+        if import_as == "*" {
+            return self.add_wildcard_import(import_path);
+        }
         if self.imports.contains_key(&import_as) {
             return Err(CompilerError::DuplicateImport(import_as));
         }
@@ -92,6 +159,56 @@ impl ModuleContext {
         Ok(())
     }
 
+    /// Adds a wildcard import of all symbols of a module, the path ends with "::".
+    /// Throws a DuplicateImportError if the module is already imported this way.
+    pub fn add_wildcard_import(&mut self, import_path: String) -> CompilerResult<()> {
+        if self.wildcard_imports.contains(&import_path) {
+            return Err(CompilerError::DuplicateImport(import_path + "*"));
+        }
+        self.wildcard_imports.push(import_path);
+        Ok(())
+    }
+
+    /// Adds an import declaration that is re-exported by this module.
+    /// Re-exported symbols can be accessed through this module from the outside.
+    pub fn add_pub_import(&mut self, import_as: String, import_path: String) -> CompilerResult<()> {
+        if import_as == "*" {
+            self.exports.insert(import_path.clone() + "*");
+        } else {
+            self.exports.insert(import_as.clone());
+        }
+        self.add_import(import_as, import_path)
+    }
+
+    /// Gets the absolute paths a symbol re-exported by this module may refer to.
+    /// mod_path is the path of this module, with trailing "::"
+    pub fn get_export_paths(&self, mod_path: &str, name: &String) -> Vec<String> {
+        let mut export_paths = Vec::new();
+        if self.exports.contains(name) {
+            if let Some(import_path) = self.imports.get(name) {
+                export_paths.push(self.get_absolute_import_path(mod_path, import_path));
+            }
+        }
+        for wildcard_path in self.wildcard_imports.iter() {
+            if self.exports.contains(&(wildcard_path.clone() + "*")) {
+                export_paths.push(self.get_absolute_import_path(mod_path, wildcard_path) + name);
+            }
+        }
+        export_paths
+    }
+
+    /// Gets the absolute path of an import declared in this module
+    pub fn get_absolute_import_path(&self, mod_path: &str, import_path: &String) -> String {
+        let first = import_path.split("::").next().unwrap_or("");
+        if first == "root" {
+            import_path.clone()
+        } else if self.modules.contains_key(first) || self.containers.contains_key(first) {
+            format!("{}{}", mod_path, import_path)
+        } else {
+            format!("root::{}", import_path)
+        }
+    }
+
     /// Gets a mutable reference to a container definition, given the name
     pub fn get_container_mut(&mut self, name: &String) -> CompilerResult<&mut ContainerDef> {
         self.containers.get_mut(name)
@@ -125,6 +242,8 @@ pub struct FunctionContext {
     pub stack_size: usize,
     variable_types: HashMap<String, Type>,
     variable_positions: HashMap<String, i64>,
+    /// Positions and types of the locals of this context owning references to strings
+    pub rc_vars: Vec<(i64, Type)>,
     pub register_allocator: RegisterAllocator
 }
 
@@ -132,6 +251,7 @@ impl FunctionContext {
     pub fn new(compiler: &Compiler, def: FunctionDef) -> CompilerResult<FunctionContext> {
         let mut variable_types = HashMap::new();
         let mut variable_positions = HashMap::new();
+        let mut rc_vars = Vec::new();
         let mut pos: i64 = 0;
 
         for (_, arg_type) in def.arguments.iter().rev() {
@@ -143,6 +263,10 @@ impl FunctionContext {
             let size_of_type = compiler.get_size_of_type(arg_type)?;
             variable_types.insert(arg_name.clone(), arg_type.clone());
             variable_positions.insert(arg_name.clone(), pos);
+            // Arguments are handed over by the caller along with their references
+            if compiler.is_ref_counting() && !compiler.get_string_offsets(arg_type)?.is_empty() {
+                rc_vars.push((pos, arg_type.clone()));
+            }
             pos += size_of_type as i64;
         }
 
@@ -154,6 +278,7 @@ impl FunctionContext {
                 stack_size: 0,
                 variable_types: variable_types,
                 variable_positions: variable_positions,
+                rc_vars: rc_vars,
                 register_allocator: RegisterAllocator::new()
             }
         )
@@ -180,6 +305,7 @@ impl FunctionContext {
                 stack_size: 0,
                 variable_types: fn_ctx.variable_types.clone(),
                 variable_positions: variable_positions,
+                rc_vars: Vec::new(),
                 register_allocator: RegisterAllocator::new()
             }
         )
@@ -206,6 +332,7 @@ impl FunctionContext {
                 stack_size: 0,
                 variable_types: fn_ctx.variable_types.clone(),
                 variable_positions: variable_positions,
+                rc_vars: Vec::new(),
                 register_allocator: RegisterAllocator::new()
             }
         )
@@ -240,6 +367,12 @@ impl FunctionContext {
         )
     }
 
+    pub fn get_var_names(&self) -> Vec<String> {
+        self.variable_positions.keys()
+            .cloned()
+            .collect()
+    }
+
     pub fn get_var_pos(&self, var_name: &String) -> CompilerResult<i64> {
         self.variable_positions.get(var_name)
             .cloned()
@@ -8,7 +8,14 @@ use std::{
 #[derive(Clone)]
 pub struct Data {
     pub bytes: Vec<u8>,
-    strings: HashMap<String, usize>
+    /// Address of every interned string literal
+    strings: HashMap<String, usize>,
+    /// Literals whose bytes were appended, with their address, in the order they were added
+    string_slices: Vec<(String, usize)>,
+    /// Address of every pooled constant, by its bytes
+    constants: HashMap<Vec<u8>, usize>,
+    /// Offsets of statics holding the address of other data, i.e. of a string slice
+    pub pointers: Vec<usize>
 }
 
 impl Data {
@@ -16,21 +23,63 @@ impl Data {
     pub fn new() -> Data {
         Self {
             bytes: Vec::new(),
-            strings: HashMap::new()
+            strings: HashMap::new(),
+            string_slices: Vec::new(),
+            constants: HashMap::new(),
+            pointers: Vec::new()
         }
     }
 
+    /// Interns a string literal, returns the length and address of its bytes.
+    /// Equal literals share one slice, a literal contained in an earlier one shares its bytes.
+    /// Equal length and address thus always mean equal contents.
     pub fn get_string_slice(&mut self, string: &String) -> (u64, u64) {
-        if self.strings.contains_key(string) {
-            let byte_len = string.as_bytes().len() as u64;
-            let addr = *self.strings.get(string).unwrap() as u64;
-            return (byte_len, addr);
+        let byte_len = string.len() as u64;
+        if let Some(addr) = self.strings.get(string) {
+            return (byte_len, *addr as u64);
         }
-        let bytes = string.as_bytes();
-        let byte_len = bytes.len() as u64;
-        let addr = self.bytes.len();
-        self.bytes.extend_from_slice(bytes);
+        let shared_addr = self.string_slices.iter()
+            .find_map(|(slice, slice_addr)| {
+                slice.find(string.as_str())
+                    .map(|index| slice_addr + index)
+            });
+        let addr = match shared_addr {
+            Some(addr) => addr,
+            None => {
+                let addr = self.bytes.len();
+                self.bytes.extend_from_slice(string.as_bytes());
+                self.string_slices.push((string.clone(), addr));
+                addr
+            }
+        };
         self.strings.insert(string.clone(), addr);
         (byte_len, addr as u64)
     }
-}
\ No newline at end of file
+
+    /// Adds a constant to the pool, returns its address. Equal constants share one copy.
+    /// Constants are never written to, unlike statics.
+    pub fn add_constant(&mut self, bytes: &[u8]) -> u64 {
+        if let Some(addr) = self.constants.get(bytes) {
+            return *addr as u64;
+        }
+        let addr = self.bytes.len();
+        self.bytes.extend_from_slice(bytes);
+        self.constants.insert(bytes.to_vec(), addr);
+        addr as u64
+    }
+
+    /// Appends the initial bytes of a static variable, returns its address
+    pub fn add_static(&mut self, bytes: &[u8]) -> u64 {
+        let addr = self.bytes.len();
+        self.bytes.extend_from_slice(bytes);
+        addr as u64
+    }
+
+    /// Appends the initial bytes of a string static, its length followed by the address of its slice.
+    /// Returns its address.
+    pub fn add_static_string(&mut self, bytes: &[u8]) -> u64 {
+        let addr = self.add_static(bytes);
+        self.pointers.push(addr as usize + 8);
+        addr
+    }
+}
@@ -0,0 +1,90 @@
+use crate::{
+    parser::{
+        ast::Type
+    }
+};
+
+use std::{
+    collections::{
+        BTreeMap
+    }
+};
+
+/// A variable visible at a statement
+#[derive(PartialEq, Debug, Clone)]
+pub struct VariableInfo {
+    pub name: String,
+    pub var_type: Type,
+    /// Offset to SP when the statement starts executing
+    pub sp_offset: i64
+}
+
+/// Debug information of a single statement
+#[derive(PartialEq, Debug, Clone)]
+pub struct StatementInfo {
+    pub line: usize,
+    pub column: usize,
+    pub variables: Vec<VariableInfo>
+}
+
+/// Debug information gathered by the compiler, keyed by code offset
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct DebugInfo {
+    pub functions: BTreeMap<usize, String>,
+    pub statements: BTreeMap<usize, StatementInfo>
+}
+
+impl DebugInfo {
+    /// Creates empty debug info
+    pub fn new() -> DebugInfo {
+        DebugInfo::default()
+    }
+
+    /// Returns a copy with all offsets moved by the given amount
+    pub fn with_offset(&self, offset: usize) -> DebugInfo {
+        DebugInfo {
+            functions: self.functions.iter()
+                .map(|(fn_offset, name)| (fn_offset + offset, name.clone()))
+                .collect(),
+            statements: self.statements.iter()
+                .map(|(stmt_offset, stmt_info)| (stmt_offset + offset, stmt_info.clone()))
+                .collect()
+        }
+    }
+
+    /// Gets the name of the function containing the given offset
+    pub fn get_function_at(&self, offset: usize) -> Option<&String> {
+        self.functions.range(..=offset)
+            .next_back()
+            .map(|(_, name)| name)
+    }
+
+    /// Gets the offset of a function by its full name
+    pub fn get_function_offset(&self, name: &str) -> Option<usize> {
+        self.functions.iter()
+            .find(|(_, fn_name)| fn_name.as_str() == name)
+            .map(|(offset, _)| *offset)
+    }
+
+    /// Gets the statement starting exactly at the given offset
+    pub fn get_statement(&self, offset: usize) -> Option<&StatementInfo> {
+        self.statements.get(&offset)
+    }
+
+    /// Gets the source line of the statement containing the given offset
+    pub fn get_line_at(&self, offset: usize) -> Option<usize> {
+        let fn_offset = self.functions.range(..=offset)
+            .next_back()
+            .map(|(fn_offset, _)| *fn_offset)?;
+        self.statements.range(fn_offset..=offset)
+            .next_back()
+            .map(|(_, stmt_info)| stmt_info.line)
+    }
+
+    /// Gets the offset of the first statement on a source line
+    pub fn get_line_offset(&self, line: usize) -> Option<usize> {
+        self.statements.iter()
+            .find(|(_, stmt_info)| stmt_info.line == line)
+            .map(|(offset, _)| *offset)
+    }
+}
@@ -3,7 +3,9 @@ use crate::{
         ast::{
             Type,
             FunctionDeclArgs,
-            ContainerDeclArgs
+            ContainerDeclArgs,
+            EnumDeclArgs,
+            InterfaceDeclArgs
         }
     },
     codegen::{
@@ -31,7 +33,9 @@ pub struct FunctionDef {
     pub name: String,
     pub uid: u64,
     pub ret_type: Type,
-    pub arguments: Vec<(String, Type)>
+    pub arguments: Vec<(String, Type)>,
+    /// Whether the function is visible outside of its module
+    pub public: bool
 }
 
 impl FunctionDef {
@@ -41,7 +45,8 @@ impl FunctionDef {
             name: name,
             uid: 0,
             ret_type: Type::Void,
-            arguments: Vec::new()
+            arguments: Vec::new(),
+            public: true
         }
     }
 
@@ -59,11 +64,25 @@ impl FunctionDef {
         self
     }
 
+    /// With a visibility
+    pub fn with_public(mut self, public: bool) -> FunctionDef {
+        self.public = public;
+        self
+    }
+
     /// With a uid
     pub fn with_uid(mut self, uid: u64) -> FunctionDef {
         self.uid = uid;
         self
     }
+
+    /// Checks if this is a member function taking "this" as its first argument.
+    /// Functions of a container without it are associated functions, called as `Cont::function(...)`.
+    pub fn is_member_function(&self) -> bool {
+        self.arguments.first()
+            .map(|(arg_name, _)| arg_name == "this")
+            .unwrap_or(false)
+    }
 }
 
 impl From<&FunctionDeclArgs> for FunctionDef {
@@ -71,6 +90,34 @@ impl From<&FunctionDeclArgs> for FunctionDef {
         FunctionDef::new(item.name.clone())
             .with_ret_type(item.returns.clone())
             .with_arguments(&item.arguments)
+            .with_public(item.public)
+    }
+}
+
+/// A generic function definition, instantiated per set of concrete types at its call sites
+#[derive(Clone, Debug)]
+pub struct GenericFunctionDef {
+    /// Name of the function
+    pub name: String,
+    /// Name of the function, including full module path
+    pub canonical_name: String,
+    /// The declaration, compiled once per instance
+    pub decl: FunctionDeclArgs
+}
+
+impl GenericFunctionDef {
+    /// Creates a new GenericFunctionDef from a declaration
+    pub fn from_decl(item: &FunctionDeclArgs, canon_name: String) -> GenericFunctionDef {
+        GenericFunctionDef {
+            name: item.name.clone(),
+            canonical_name: canon_name,
+            decl: item.clone()
+        }
+    }
+
+    /// Gets the path of the declaring module, with trailing "::"
+    pub fn get_module_path(&self) -> &str {
+        &self.canonical_name[..self.canonical_name.len() - self.name.len()]
     }
 }
 
@@ -86,7 +133,19 @@ pub struct ContainerDef {
     /// Map of member variable indices
     pub member_indices: BTreeMap<String, usize>,
     /// Map of member functions
-    pub member_functions: HashMap<String, FunctionDef>
+    pub member_functions: HashMap<String, FunctionDef>,
+    /// Names of the type parameters, if this is a generic container
+    pub generics: Vec<String>,
+    /// Concrete types of the type parameters, if this is an instance of a generic container
+    pub type_args: Vec<Type>,
+    /// Addresses of the vtables of implemented interfaces, by canonical interface name
+    pub interfaces: HashMap<String, u64>,
+    /// Whether the container is visible outside of its module
+    pub public: bool,
+    /// Module paths of impls in other modules, by the name of the member functions they declared
+    pub impl_paths: HashMap<String, String>,
+    /// Associated constants, declared as variables in impl blocks
+    pub constants: HashMap<String, StaticDef>
 }
 
 impl ContainerDef {
@@ -97,7 +156,13 @@ impl ContainerDef {
             canonical_name: canon_name,
             member_indices: BTreeMap::new(),
             member_functions: HashMap::new(),
-            member_variables: HashMap::new()
+            member_variables: HashMap::new(),
+            generics: Vec::new(),
+            type_args: Vec::new(),
+            interfaces: HashMap::new(),
+            public: true,
+            impl_paths: HashMap::new(),
+            constants: HashMap::new()
         }
     }
 
@@ -121,17 +186,25 @@ impl ContainerDef {
         Ok(())
     }
 
+    /// Adds an associated constant
+    pub fn add_constant(&mut self, static_def: StaticDef) -> CompilerResult<()> {
+        if self.constants.contains_key(&static_def.name) || self.member_variables.contains_key(&static_def.name) {
+            return Err(CompilerError::DuplicateVariable(static_def.name));
+        }
+        self.constants.insert(static_def.name.clone(), static_def);
+        Ok(())
+    }
+
     /// Gets the byte offset of a member
     pub fn get_member_offset(&self, compiler: &Compiler, var_name: &String) -> CompilerResult<usize> {
         let target_index = self.get_member_index(var_name)?;
         let mut offset = 0;
+        // Members are laid out in declaration order
         for (member_name, member_index) in self.member_indices.iter() {
-            let member_type = self.get_member_type(member_name)?;
-            let member_size = compiler.get_size_of_type(&member_type)?;
-            if *member_index == target_index {
-                break;
+            if *member_index < target_index {
+                let member_type = self.get_member_type(member_name)?;
+                offset += compiler.get_size_of_type(&member_type)?;
             }
-            offset += member_size;
         }
         Ok(offset)
     }
@@ -143,13 +216,18 @@ impl ContainerDef {
             .ok_or(CompilerError::UnknownMember(var_name.clone()))
     }
 
-    /// Returns the byte size of this container
+    /// Returns the byte size of this container.
+    /// Generic containers have no layout of their own, only their instances do.
     pub fn get_size(&self, compiler: &Compiler) -> CompilerResult<usize> {
-        let mut size = 0;
-        for (_, var_type) in self.member_variables.iter() {
-            size += compiler.get_size_of_type(var_type)?;
+        if !self.generics.is_empty() {
+            return Err(CompilerError::TypeArgumentMismatch(self.canonical_name.clone(), 0));
         }
-        Ok(size)
+        compiler.begin_layout(&self.canonical_name)?;
+        let size_res = self.member_variables.values()
+            .map(|var_type| compiler.get_size_of_type(var_type))
+            .sum();
+        compiler.end_layout(&self.canonical_name);
+        size_res
     }
 
     /// Returns the index of a member
@@ -167,15 +245,115 @@ impl ContainerDef {
 
     /// Merges a container declaration into an existing containerdef
     pub fn merge_cont_decl(&mut self, item: &ContainerDeclArgs) {
+        self.public = item.public;
         for member in item.members.iter() {
             self.add_member_variable(member.clone()).unwrap();
         }
     }
 
+    /// Returns the module path of this container, e.g. "root::geo::"
+    pub fn get_module_path(&self) -> &str {
+        &self.canonical_name[..self.canonical_name.len() - self.name.len()]
+    }
+
+    /// Gets the module path of the impl declaring a member function.
+    /// Private member functions are visible in this module.
+    pub fn get_impl_path(&self, fn_name: &str) -> &str {
+        self.impl_paths.get(fn_name)
+            .map(|impl_path| impl_path.as_str())
+            .unwrap_or_else(|| self.get_module_path())
+    }
+
+    /// Gets the type of "this" in member functions.
+    /// Impls of builtin types, e.g. "impl: int", refer to the builtin type itself.
+    pub fn get_this_type(&self) -> Type {
+        let this_type = Type::from_builtin_name(&self.name)
+            .unwrap_or_else(|| Type::Other(self.canonical_name.clone()));
+        Type::Reference(Box::new(this_type))
+    }
+
     /// Creates a new ContainerDef from a declaration
     pub fn from_decl(item: &ContainerDeclArgs, canon_name: String) -> ContainerDef {
         let mut def = ContainerDef::new(item.name.clone(), canon_name);
+        def.generics = item.generics.clone();
         def.merge_cont_decl(item);
         def
     }
-}
\ No newline at end of file
+}
+
+/// An enum definition
+#[derive(Clone, Debug)]
+pub struct EnumDef {
+    /// Name of the enum
+    pub name: String,
+    /// Name of the enum, including full module path
+    pub canonical_name: String,
+    /// Names of the variants, their index is their value
+    pub variants: Vec<String>
+}
+
+impl EnumDef {
+    /// Creates a new EnumDef from a declaration
+    pub fn from_decl(item: &EnumDeclArgs, canon_name: String) -> EnumDef {
+        EnumDef {
+            name: item.name.clone(),
+            canonical_name: canon_name,
+            variants: item.variants.clone()
+        }
+    }
+
+    /// Returns the value of a variant
+    pub fn get_variant_value(&self, variant: &String) -> CompilerResult<i64> {
+        self.variants.iter()
+            .position(|name| name == variant)
+            .map(|index| index as i64)
+            .ok_or_else(|| CompilerError::UnknownEnumVariant(self.canonical_name.clone(), variant.clone()))
+    }
+}
+
+/// An interface definition
+#[derive(Clone, Debug)]
+pub struct InterfaceDef {
+    /// Name of the interface
+    pub name: String,
+    /// Name of the interface, including full module path
+    pub canonical_name: String,
+    /// Member function signatures, their index is their vtable slot
+    pub functions: Vec<FunctionDef>
+}
+
+impl InterfaceDef {
+    /// Creates a new InterfaceDef from a declaration
+    pub fn from_decl(item: &InterfaceDeclArgs, canon_name: String) -> InterfaceDef {
+        InterfaceDef {
+            name: item.name.clone(),
+            canonical_name: canon_name,
+            functions: item.functions.iter()
+                .map(FunctionDef::from)
+                .collect()
+        }
+    }
+
+    /// Returns the vtable slot and signature of a member function
+    pub fn get_function(&self, fn_name: &String) -> CompilerResult<(usize, &FunctionDef)> {
+        self.functions.iter()
+            .enumerate()
+            .find(|(_, fn_def)| fn_def.name == *fn_name)
+            .ok_or_else(|| CompilerError::UnknownMember(fn_name.clone()))
+    }
+}
+
+/// A module level (static) variable definition
+#[derive(Clone, Debug)]
+pub struct StaticDef {
+    /// Name of the variable
+    pub name: String,
+    /// Name of the variable, including full module path
+    pub canonical_name: String,
+    /// Type of the variable
+    pub var_type: Type,
+    /// Fixed address of the variable in the data segment of the program
+    pub address: u64,
+    /// Whether this is an associated constant of a container, which can not be assigned to
+    pub constant: bool
+}
@@ -0,0 +1,174 @@
+use super::{
+    builder::Builder,
+    debug::DebugInfo,
+    instruction::Instruction,
+    register::Register
+};
+use crate::{
+    vm::{
+        is::Opcode
+    }
+};
+
+use std::{
+    collections::{
+        HashMap,
+        HashSet
+    },
+    mem::take
+};
+
+/// Fuses common pairs of instructions into superinstructions, so tight loops dispatch fewer of them:
+/// - LDI followed by MOVI_RA of the loaded register becomes LDI_RA
+/// - an integer comparison followed by JMPF on its result becomes JMPF_CMPI
+/// - ADDU_I growing the stack followed by MOVI_RA or MOVA_RA relative to SP becomes INCSP_RA
+///
+/// Instructions that are jumped to or start a function or a statement are never fused into the one before them.
+/// Jumps, labels and the offsets of the statements are moved along with the code.
+pub fn fuse_instructions(builder: &mut Builder, debug_info: &mut DebugInfo) {
+    let instructions = take(&mut builder.instructions);
+
+    // Index of the instruction at each code offset, the end of the code is one past the last instruction
+    let mut indices = HashMap::new();
+    let mut offset = 0;
+    for (index, instr) in instructions.iter().enumerate() {
+        indices.insert(offset, index);
+        offset += instr.get_size();
+    }
+    indices.insert(offset, instructions.len());
+
+    let mut barriers: HashSet<usize> = builder.labels.values()
+        .cloned()
+        .collect();
+    for index in builder.jmp_instructions.iter() {
+        let target = get_jump_target(&instructions[*index]);
+        if let Some(target_index) = target.and_then(|target| indices.get(&(target as usize))) {
+            barriers.insert(*target_index);
+        }
+    }
+    for stmt_offset in debug_info.statements.keys() {
+        if let Some(stmt_index) = indices.get(stmt_offset) {
+            barriers.insert(*stmt_index);
+        }
+    }
+
+    // Index of each instruction in the fused code, both instructions of a pair get the index of the fused one
+    let mut new_indices = Vec::with_capacity(instructions.len() + 1);
+    let mut fused: Vec<Instruction> = Vec::with_capacity(instructions.len());
+    let mut iter = instructions.into_iter().enumerate().peekable();
+    while let Some((_, instr)) = iter.next() {
+        new_indices.push(fused.len());
+        let fused_instr = match iter.peek() {
+            Some((next_index, next)) if !barriers.contains(next_index) => fuse_pair(&instr, next),
+            _ => None
+        };
+        match fused_instr {
+            Some(fused_instr) => {
+                iter.next();
+                new_indices.push(fused.len());
+                fused.push(fused_instr);
+            },
+            None => fused.push(instr)
+        };
+    }
+    new_indices.push(fused.len());
+
+    let mut new_offsets = Vec::with_capacity(fused.len() + 1);
+    let mut offset = 0;
+    for instr in fused.iter() {
+        new_offsets.push(offset);
+        offset += instr.get_size();
+    }
+    new_offsets.push(offset);
+    let map_offset = |old_offset: usize| -> Option<usize> {
+        indices.get(&old_offset)
+            .map(|index| new_offsets[new_indices[*index]])
+    };
+
+    builder.jmp_instructions = builder.jmp_instructions.iter()
+        .map(|index| new_indices[*index])
+        .collect();
+    for index in builder.jmp_instructions.iter() {
+        let instr = &mut fused[*index];
+        let target = get_jump_target(instr)
+            .and_then(|target| map_offset(target as usize));
+        if let (Some(target), Some(pos)) = (target, instr.opcode.get_jump_target_pos()) {
+            instr.set_operand::<u64>(pos, target as u64);
+        }
+    }
+    for label_index in builder.labels.values_mut() {
+        *label_index = new_indices[*label_index];
+    }
+    for tag_list in builder.tags.values_mut() {
+        for tag_index in tag_list.iter_mut() {
+            *tag_index = new_indices[*tag_index];
+        }
+    }
+    debug_info.statements = take(&mut debug_info.statements).into_iter()
+        .filter_map(|(stmt_offset, stmt_info)| {
+            map_offset(stmt_offset).map(|stmt_offset| (stmt_offset, stmt_info))
+        })
+        .collect();
+
+    builder.instructions = fused;
+}
+
+/// Gets the superinstruction replacing the given pair, if there is one
+fn fuse_pair(first: &Instruction, second: &Instruction) -> Option<Instruction> {
+    let sp: u8 = Register::SP.into();
+    match (&first.opcode, &second.opcode) {
+        (Opcode::LDI, Opcode::MOVI_RA) => {
+            let value: i64 = first.get_operand(0, 8);
+            let reg: u8 = first.get_operand(8, 1);
+            if second.get_operand::<u8>(0, 1) != reg {
+                return None;
+            }
+            Some(
+                Instruction::new(Opcode::LDI_RA)
+                    .with_operand(value)
+                    .with_operand(reg)
+                    .with_operand::<u8>(second.get_operand(1, 1))
+                    .with_operand::<i16>(second.get_operand(2, 2))
+            )
+        },
+        (Opcode::EQI, Opcode::JMPF) |
+        (Opcode::NEQI, Opcode::JMPF) |
+        (Opcode::LTI, Opcode::JMPF) |
+        (Opcode::GTI, Opcode::JMPF) |
+        (Opcode::LTEQI, Opcode::JMPF) |
+        (Opcode::GTEQI, Opcode::JMPF) => {
+            let target_reg: u8 = first.get_operand(2, 1);
+            if second.get_operand::<u8>(0, 1) != target_reg {
+                return None;
+            }
+            Some(
+                Instruction::new(Opcode::JMPF_CMPI)
+                    .with_operand::<u8>(first.opcode.clone().into())
+                    .with_operand::<u8>(first.get_operand(0, 1))
+                    .with_operand::<u8>(first.get_operand(1, 1))
+                    .with_operand(target_reg)
+                    .with_operand::<u64>(second.get_operand(1, 8))
+            )
+        },
+        (Opcode::ADDU_I, Opcode::MOVI_RA) |
+        (Opcode::ADDU_I, Opcode::MOVA_RA) => {
+            let grows_stack = first.get_operand::<u8>(0, 1) == sp && first.get_operand::<u8>(9, 1) == sp;
+            if !grows_stack || second.get_operand::<u8>(1, 1) != sp {
+                return None;
+            }
+            Some(
+                Instruction::new(Opcode::INCSP_RA)
+                    .with_operand::<u64>(first.get_operand(1, 8))
+                    .with_operand::<u8>(second.get_operand(0, 1))
+                    .with_operand::<i16>(second.get_operand(2, 2))
+            )
+        },
+        _ => None
+    }
+}
+
+/// Gets the target of a jump, an offset into the instructions of the builder until they are built
+fn get_jump_target(instr: &Instruction) -> Option<u64> {
+    instr.opcode.get_jump_target_pos()
+        .map(|pos| instr.get_operand(pos, 8))
+}
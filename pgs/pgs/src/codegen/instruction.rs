@@ -1,6 +1,9 @@
 use crate::{
     vm::{
-        is::Opcode
+        is::{
+            Opcode,
+            Operand
+        }
     },
     codegen::{
         register::Register
@@ -8,16 +11,6 @@ use crate::{
 };
 
 
-
-use serde::{
-    Serialize,
-    de::DeserializeOwned
-};
-use bincode::{
-    deserialize,
-    serialize
-};
-
 #[derive(Clone, Debug)]
 pub struct Instruction {
     pub opcode: Opcode,
@@ -46,15 +39,20 @@ impl Instruction {
             .with_operand::<u8>(Register::SP.into())
     }
 
-    pub fn with_operand<T: Serialize>(mut self, operand: T) -> Instruction {
-        let mut data = serialize(&operand).expect("ERROR Serializing operand!");
-        self.operands.append(&mut data);
+    pub fn with_operand<T: Operand>(mut self, operand: T) -> Instruction {
+        operand.write_le(&mut self.operands);
         self
     }
 
-    pub fn append_operand<T: Serialize>(&mut self, operand: T) {
-        let mut data = serialize(&operand).expect("ERROR Serializing operand!");
-        self.operands.append(&mut data);
+    pub fn append_operand<T: Operand>(&mut self, operand: T) {
+        operand.write_le(&mut self.operands);
+    }
+
+    /// Overwrites the operand at the given byte offset
+    pub fn set_operand<T: Operand>(&mut self, offset: usize, operand: T) {
+        let mut bytes = Vec::new();
+        operand.write_le(&mut bytes);
+        self.operands[offset..offset + bytes.len()].copy_from_slice(&bytes);
     }
 
     pub fn remove_operand_bytes(&mut self, n: usize) {
@@ -82,8 +80,7 @@ impl Instruction {
         self.operands.len() + 1
     }
 
-    pub fn get_operand<T: DeserializeOwned>(&self, offset: usize, size: usize) -> T {
-        let t = deserialize(&self.operands[offset..offset + size]).expect("ERROR Deserializing operand!");
-        t
+    pub fn get_operand<T: Operand>(&self, offset: usize, size: usize) -> T {
+        T::read_le(&self.operands[offset..offset + size])
     }
 }
\ No newline at end of file
@@ -14,4 +14,10 @@ pub mod uid_generator;
 
 pub mod def;
 
-pub mod register;
\ No newline at end of file
+pub mod register;
+
+pub mod debug;
+
+pub mod optimizer;
+
+pub mod fusion;
\ No newline at end of file
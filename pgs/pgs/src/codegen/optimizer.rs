@@ -0,0 +1,640 @@
+use crate::{
+    parser::{
+        ast::{
+            Expression,
+            Statement,
+            Type,
+            FunctionDeclArgs,
+            VariableDeclArgs,
+            IfStatementArgs,
+            MatchStatementArgs,
+            DestructureDeclArgs
+        }
+    }
+};
+
+use std::{
+    collections::{
+        HashMap,
+        HashSet
+    }
+};
+
+/// Substitutes locals holding a constant at their use sites, and folds constant expressions.
+///
+/// A local is treated as a constant if its initializer folds to an int, float, double or bool literal,
+/// it is declared only once in the function, and it is never assigned to or referenced.
+/// Declarations are kept, so the statement structure of the function does not change.
+pub struct ConstantPropagator {
+    declarations: HashMap<String, usize>,
+    mutated: HashSet<String>,
+    scopes: Vec<HashMap<String, Expression>>
+}
+
+impl ConstantPropagator {
+    /// Creates a new constant propagator
+    pub fn new() -> ConstantPropagator {
+        ConstantPropagator {
+            declarations: HashMap::new(),
+            mutated: HashSet::new(),
+            scopes: Vec::new()
+        }
+    }
+
+    /// Runs the pass over the body of a function, returns the rewritten body
+    pub fn propagate_fn(mut self, fn_decl_args: &FunctionDeclArgs) -> Option<Vec<Statement>> {
+        let stmt_list = fn_decl_args.code_block.as_ref()?;
+
+        for (arg_name, _) in fn_decl_args.arguments.iter() {
+            *self.declarations.entry(arg_name.clone()).or_insert(0) += 1;
+        }
+        self.collect_stmt_list(stmt_list);
+
+        Some(self.propagate_block(stmt_list))
+    }
+
+    /// Records variable declarations and mutations of a statement list
+    fn collect_stmt_list(&mut self, stmt_list: &[Statement]) {
+        for stmt in stmt_list.iter() {
+            match stmt.without_position() {
+                Statement::VariableDecl(var_decl_args) => {
+                    *self.declarations.entry(var_decl_args.name.clone()).or_insert(0) += 1;
+                    self.collect_expr(&var_decl_args.assignment);
+                },
+                Statement::DestructureDecl(destructure_decl_args) => {
+                    for name in destructure_decl_args.names.iter() {
+                        *self.declarations.entry(name.clone()).or_insert(0) += 1;
+                    }
+                    self.collect_expr(&destructure_decl_args.assignment);
+                },
+                Statement::Assignment(var_name, expr) => {
+                    self.mutated.insert(var_name.clone());
+                    self.collect_expr(expr);
+                },
+                Statement::Increment(expr, _) => {
+                    if let Some(var_name) = Self::get_base_variable(expr) {
+                        self.mutated.insert(var_name);
+                    }
+                    self.collect_expr(expr);
+                },
+                Statement::Call(_, arg_exprs) => {
+                    for arg_expr in arg_exprs.iter() {
+                        self.collect_expr(arg_expr);
+                    }
+                },
+                Statement::Return(Some(expr)) |
+                Statement::Expression(expr) |
+                Statement::Throw(expr) |
+                Statement::Spawn(expr) |
+                Statement::Yield(expr) => self.collect_expr(expr),
+                Statement::CodeBlock(inner_list) | Statement::Loop(inner_list) => self.collect_stmt_list(inner_list),
+                Statement::While(while_expr, inner_list) |
+                Statement::DoWhile(inner_list, while_expr) => {
+                    self.collect_expr(while_expr);
+                    self.collect_stmt_list(inner_list);
+                },
+                Statement::ForIn(item_name, iter_expr, inner_list) => {
+                    *self.declarations.entry(item_name.clone()).or_insert(0) += 1;
+                    self.collect_expr(iter_expr);
+                    self.collect_stmt_list(inner_list);
+                },
+                Statement::If(if_stmt_args) => {
+                    self.collect_expr(&if_stmt_args.if_expr);
+                    self.collect_stmt_list(&if_stmt_args.if_block);
+                    if let Some(else_if_list) = &if_stmt_args.else_if_list {
+                        for (else_if_expr, else_if_block) in else_if_list.iter() {
+                            self.collect_expr(else_if_expr);
+                            self.collect_stmt_list(else_if_block);
+                        }
+                    }
+                    if let Some(else_block) = &if_stmt_args.else_block {
+                        self.collect_stmt_list(else_block);
+                    }
+                },
+                Statement::Match(match_stmt_args) => {
+                    self.collect_expr(&match_stmt_args.match_expr);
+                    for (arm_expr, arm_block) in match_stmt_args.arms.iter() {
+                        self.collect_expr(arm_expr);
+                        self.collect_stmt_list(arm_block);
+                    }
+                    if let Some(default_block) = &match_stmt_args.default_block {
+                        self.collect_stmt_list(default_block);
+                    }
+                },
+                _ => {}
+            };
+        }
+    }
+
+    /// Records variable mutations of an expression
+    fn collect_expr(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Assign(lhs, rhs) |
+            Expression::AddAssign(lhs, rhs) |
+            Expression::SubAssign(lhs, rhs) |
+            Expression::MulAssign(lhs, rhs) |
+            Expression::DivAssign(lhs, rhs) => {
+                if let Some(var_name) = Self::get_base_variable(lhs) {
+                    self.mutated.insert(var_name);
+                }
+                self.collect_expr(lhs);
+                self.collect_expr(rhs);
+            },
+            Expression::Ref(inner) => {
+                if let Some(var_name) = Self::get_base_variable(inner) {
+                    self.mutated.insert(var_name);
+                }
+                self.collect_expr(inner);
+            },
+            // Member functions get a reference to their receiver as "this"
+            Expression::MemberAccess(lhs, rhs) if expr.is_member_call() => {
+                if let Some(var_name) = Self::get_base_variable(lhs) {
+                    self.mutated.insert(var_name);
+                }
+                self.collect_expr(lhs);
+                self.collect_expr(rhs);
+            },
+            Expression::Addition(lhs, rhs) |
+            Expression::Subtraction(lhs, rhs) |
+            Expression::Multiplication(lhs, rhs) |
+            Expression::Division(lhs, rhs) |
+            Expression::Modulo(lhs, rhs) |
+            Expression::And(lhs, rhs) |
+            Expression::Or(lhs, rhs) |
+            Expression::Equals(lhs, rhs) |
+            Expression::NotEquals(lhs, rhs) |
+            Expression::GreaterThan(lhs, rhs) |
+            Expression::LessThan(lhs, rhs) |
+            Expression::GreaterThanEquals(lhs, rhs) |
+            Expression::LessThanEquals(lhs, rhs) |
+            Expression::MemberAccess(lhs, rhs) |
+            Expression::Index(lhs, rhs) |
+            Expression::CharAt(lhs, rhs) |
+            Expression::Range(lhs, rhs, _) => {
+                self.collect_expr(lhs);
+                self.collect_expr(rhs);
+            },
+            Expression::Not(inner) |
+            Expression::Negation(inner) |
+            Expression::Cast(inner, _) |
+            Expression::TupleIndex(inner, _) |
+            Expression::Deref(inner) |
+            Expression::Len(inner) |
+            Expression::Resume(inner) |
+            Expression::Try(inner) => self.collect_expr(inner),
+            Expression::Conditional(cond_expr, then_expr, else_expr) => {
+                self.collect_expr(cond_expr);
+                self.collect_expr(then_expr);
+                self.collect_expr(else_expr);
+            },
+            Expression::Block(stmt_list, value_expr) => {
+                self.collect_stmt_list(stmt_list);
+                self.collect_expr(value_expr);
+            },
+            Expression::Slice(base_expr, start_expr, end_expr) => {
+                self.collect_expr(base_expr);
+                for bound_expr in [start_expr, end_expr].iter().filter_map(|bound_expr| bound_expr.as_ref()) {
+                    self.collect_expr(bound_expr);
+                }
+            },
+            Expression::Call(_, arg_exprs) => {
+                for arg_expr in arg_exprs.iter() {
+                    self.collect_expr(arg_expr);
+                }
+            },
+            Expression::ContainerInstance(_, member_exprs) => {
+                for member_expr in member_exprs.values() {
+                    self.collect_expr(member_expr);
+                }
+            },
+            Expression::Tuple(elem_exprs) |
+            Expression::Array(elem_exprs) => {
+                for elem_expr in elem_exprs.iter() {
+                    self.collect_expr(elem_expr);
+                }
+            },
+            _ => {}
+        };
+    }
+
+    /// Gets the variable at the base of a (member access) expression
+    fn get_base_variable(expr: &Expression) -> Option<String> {
+        match expr {
+            Expression::Variable(var_name) => Some(var_name.clone()),
+            Expression::MemberAccess(lhs, _) => Self::get_base_variable(lhs),
+            Expression::Index(base_expr, _) => Self::get_base_variable(base_expr),
+            Expression::Deref(inner) => Self::get_base_variable(inner),
+            _ => None
+        }
+    }
+
+    fn propagate_block(&mut self, stmt_list: &[Statement]) -> Vec<Statement> {
+        self.scopes.push(HashMap::new());
+        let ret = stmt_list.iter()
+            .map(|stmt| self.propagate_stmt(stmt))
+            .collect();
+        self.scopes.pop();
+        ret
+    }
+
+    fn propagate_stmt(&mut self, stmt: &Statement) -> Statement {
+        match stmt {
+            Statement::VariableDecl(var_decl_args) => {
+                let assignment = self.propagate_expr(&var_decl_args.assignment);
+                if self.is_constant_candidate(&var_decl_args.name) && Self::literal_matches(&assignment, &var_decl_args.var_type) {
+                    if let Some(scope) = self.scopes.last_mut() {
+                        scope.insert(var_decl_args.name.clone(), assignment.clone());
+                    }
+                }
+                Statement::VariableDecl(VariableDeclArgs {
+                    var_type: var_decl_args.var_type.clone(),
+                    name: var_decl_args.name.clone(),
+                    assignment: Box::new(assignment)
+                })
+            },
+            Statement::DestructureDecl(destructure_decl_args) => {
+                Statement::DestructureDecl(DestructureDeclArgs {
+                    var_type: destructure_decl_args.var_type.clone(),
+                    names: destructure_decl_args.names.clone(),
+                    assignment: Box::new(self.propagate_expr(&destructure_decl_args.assignment))
+                })
+            },
+            Statement::Assignment(var_name, expr) => {
+                Statement::Assignment(var_name.clone(), Box::new(self.propagate_expr(expr)))
+            },
+            Statement::Call(fn_name, arg_exprs) => {
+                let arg_exprs = arg_exprs.iter()
+                    .map(|arg_expr| self.propagate_expr(arg_expr))
+                    .collect();
+                Statement::Call(fn_name.clone(), arg_exprs)
+            },
+            Statement::Return(expr_opt) => {
+                Statement::Return(expr_opt.as_ref().map(|expr| self.propagate_expr(expr)))
+            },
+            Statement::CodeBlock(inner_list) => Statement::CodeBlock(self.propagate_block(inner_list)),
+            Statement::Loop(inner_list) => Statement::Loop(self.propagate_block(inner_list)),
+            Statement::While(while_expr, inner_list) => {
+                let while_expr = self.propagate_expr(while_expr);
+                Statement::While(Box::new(while_expr), self.propagate_block(inner_list))
+            },
+            Statement::DoWhile(inner_list, while_expr) => {
+                let inner_list = self.propagate_block(inner_list);
+                Statement::DoWhile(inner_list, Box::new(self.propagate_expr(while_expr)))
+            },
+            Statement::ForIn(item_name, iter_expr, inner_list) => {
+                let iter_expr = self.propagate_expr(iter_expr);
+                Statement::ForIn(item_name.clone(), Box::new(iter_expr), self.propagate_block(inner_list))
+            },
+            Statement::Expression(expr) => Statement::Expression(self.propagate_expr(expr)),
+            Statement::If(if_stmt_args) => {
+                let if_expr = self.propagate_expr(&if_stmt_args.if_expr);
+                let if_block = self.propagate_block(&if_stmt_args.if_block);
+                let else_if_list = if_stmt_args.else_if_list.as_ref().map(|else_if_list| {
+                    else_if_list.iter()
+                        .map(|(else_if_expr, else_if_block)| {
+                            (self.propagate_expr(else_if_expr), self.propagate_block(else_if_block))
+                        })
+                        .collect()
+                });
+                let else_block = if_stmt_args.else_block.as_ref()
+                    .map(|else_block| self.propagate_block(else_block));
+                Statement::If(IfStatementArgs {
+                    if_expr: if_expr,
+                    if_block: if_block,
+                    else_block: else_block,
+                    else_if_list: else_if_list
+                })
+            },
+            Statement::Match(match_stmt_args) => {
+                let match_expr = self.propagate_expr(&match_stmt_args.match_expr);
+                let arms = match_stmt_args.arms.iter()
+                    .map(|(arm_expr, arm_block)| {
+                        (self.propagate_expr(arm_expr), self.propagate_block(arm_block))
+                    })
+                    .collect();
+                let default_block = match_stmt_args.default_block.as_ref()
+                    .map(|default_block| self.propagate_block(default_block));
+                Statement::Match(MatchStatementArgs {
+                    match_expr: match_expr,
+                    arms: arms,
+                    default_block: default_block
+                })
+            },
+            Statement::StaticAssert(assert_expr, message_expr) => {
+                let message_expr = message_expr.as_ref().map(|message_expr| self.propagate_expr(message_expr));
+                Statement::StaticAssert(self.propagate_expr(assert_expr), message_expr)
+            },
+            Statement::Throw(message_expr) => Statement::Throw(self.propagate_expr(message_expr)),
+            Statement::Spawn(call_expr) => Statement::Spawn(self.propagate_expr(call_expr)),
+            Statement::Yield(value_expr) => Statement::Yield(self.propagate_expr(value_expr)),
+            Statement::Increment(expr, step) => Statement::Increment(expr.clone(), *step),
+            Statement::Break => Statement::Break,
+            Statement::Continue => Statement::Continue,
+            Statement::Positioned(inner_stmt, line, column) => {
+                Statement::Positioned(Box::new(self.propagate_stmt(inner_stmt)), *line, *column)
+            }
+        }
+    }
+
+    fn propagate_expr(&self, expr: &Expression) -> Expression {
+        let binary = |lhs: &Expression, rhs: &Expression| {
+            (Box::new(self.propagate_expr(lhs)), Box::new(self.propagate_expr(rhs)))
+        };
+        let propagated = match expr {
+            Expression::Variable(var_name) => {
+                match self.get_constant(var_name) {
+                    Some(constant) => constant.clone(),
+                    None => expr.clone()
+                }
+            },
+            Expression::Addition(lhs, rhs) => {
+                let (lhs, rhs) = binary(lhs, rhs);
+                Expression::Addition(lhs, rhs)
+            },
+            Expression::Subtraction(lhs, rhs) => {
+                let (lhs, rhs) = binary(lhs, rhs);
+                Expression::Subtraction(lhs, rhs)
+            },
+            Expression::Multiplication(lhs, rhs) => {
+                let (lhs, rhs) = binary(lhs, rhs);
+                Expression::Multiplication(lhs, rhs)
+            },
+            Expression::Division(lhs, rhs) => {
+                let (lhs, rhs) = binary(lhs, rhs);
+                Expression::Division(lhs, rhs)
+            },
+            Expression::Modulo(lhs, rhs) => {
+                let (lhs, rhs) = binary(lhs, rhs);
+                Expression::Modulo(lhs, rhs)
+            },
+            Expression::And(lhs, rhs) => {
+                let (lhs, rhs) = binary(lhs, rhs);
+                Expression::And(lhs, rhs)
+            },
+            Expression::Or(lhs, rhs) => {
+                let (lhs, rhs) = binary(lhs, rhs);
+                Expression::Or(lhs, rhs)
+            },
+            Expression::Equals(lhs, rhs) => {
+                let (lhs, rhs) = binary(lhs, rhs);
+                Expression::Equals(lhs, rhs)
+            },
+            Expression::NotEquals(lhs, rhs) => {
+                let (lhs, rhs) = binary(lhs, rhs);
+                Expression::NotEquals(lhs, rhs)
+            },
+            Expression::GreaterThan(lhs, rhs) => {
+                let (lhs, rhs) = binary(lhs, rhs);
+                Expression::GreaterThan(lhs, rhs)
+            },
+            Expression::LessThan(lhs, rhs) => {
+                let (lhs, rhs) = binary(lhs, rhs);
+                Expression::LessThan(lhs, rhs)
+            },
+            Expression::GreaterThanEquals(lhs, rhs) => {
+                let (lhs, rhs) = binary(lhs, rhs);
+                Expression::GreaterThanEquals(lhs, rhs)
+            },
+            Expression::LessThanEquals(lhs, rhs) => {
+                let (lhs, rhs) = binary(lhs, rhs);
+                Expression::LessThanEquals(lhs, rhs)
+            },
+            Expression::Not(inner) => Expression::Not(Box::new(self.propagate_expr(inner))),
+            Expression::Negation(inner) => Expression::Negation(Box::new(self.propagate_expr(inner))),
+            Expression::Cast(inner, cast_type) => Expression::Cast(Box::new(self.propagate_expr(inner)), cast_type.clone()),
+            Expression::Try(inner) => Expression::Try(Box::new(self.propagate_expr(inner))),
+            Expression::Resume(inner) => Expression::Resume(Box::new(self.propagate_expr(inner))),
+            Expression::Slice(base_expr, start_expr, end_expr) => {
+                let propagate_bound = |bound_expr: &Option<Box<Expression>>| {
+                    bound_expr.as_ref()
+                        .map(|bound_expr| Box::new(self.propagate_expr(bound_expr)))
+                };
+                Expression::Slice(base_expr.clone(), propagate_bound(start_expr), propagate_bound(end_expr))
+            },
+            Expression::Index(base_expr, index_expr) => {
+                Expression::Index(base_expr.clone(), Box::new(self.propagate_expr(index_expr)))
+            },
+            Expression::CharAt(base_expr, index_expr) => {
+                let (base_expr, index_expr) = binary(base_expr, index_expr);
+                Expression::CharAt(base_expr, index_expr)
+            },
+            Expression::Range(start_expr, end_expr, inclusive) => {
+                let (start_expr, end_expr) = binary(start_expr, end_expr);
+                Expression::Range(start_expr, end_expr, *inclusive)
+            },
+            Expression::Conditional(cond_expr, then_expr, else_expr) => {
+                let (then_expr, else_expr) = binary(then_expr, else_expr);
+                Expression::Conditional(Box::new(self.propagate_expr(cond_expr)), then_expr, else_expr)
+            },
+            // Locals declared in the block need their own scope, it is left as is
+            Expression::Block(_, _) => expr.clone(),
+            Expression::Call(fn_name, arg_exprs) => {
+                let arg_exprs = arg_exprs.iter()
+                    .map(|arg_expr| self.propagate_expr(arg_expr))
+                    .collect();
+                Expression::Call(fn_name.clone(), arg_exprs)
+            },
+            Expression::ContainerInstance(cont_name, member_exprs) => {
+                let member_exprs = member_exprs.iter()
+                    .map(|(member_name, member_expr)| (member_name.clone(), self.propagate_expr(member_expr)))
+                    .collect();
+                Expression::ContainerInstance(cont_name.clone(), member_exprs)
+            },
+            Expression::Tuple(elem_exprs) => {
+                let elem_exprs = elem_exprs.iter()
+                    .map(|elem_expr| self.propagate_expr(elem_expr))
+                    .collect();
+                Expression::Tuple(elem_exprs)
+            },
+            Expression::Array(elem_exprs) => {
+                let elem_exprs = elem_exprs.iter()
+                    .map(|elem_expr| self.propagate_expr(elem_expr))
+                    .collect();
+                Expression::Array(elem_exprs)
+            },
+            Expression::TupleIndex(base_expr, index) => Expression::TupleIndex(Box::new(self.propagate_expr(base_expr)), *index),
+            Expression::MemberAccess(lhs, rhs) => {
+                Expression::MemberAccess(Box::new(self.propagate_expr(lhs)), Box::new(self.propagate_member(rhs)))
+            },
+            Expression::Assign(lhs, rhs) => Expression::Assign(lhs.clone(), Box::new(self.propagate_expr(rhs))),
+            Expression::AddAssign(lhs, rhs) => Expression::AddAssign(lhs.clone(), Box::new(self.propagate_expr(rhs))),
+            Expression::SubAssign(lhs, rhs) => Expression::SubAssign(lhs.clone(), Box::new(self.propagate_expr(rhs))),
+            Expression::MulAssign(lhs, rhs) => Expression::MulAssign(lhs.clone(), Box::new(self.propagate_expr(rhs))),
+            Expression::DivAssign(lhs, rhs) => Expression::DivAssign(lhs.clone(), Box::new(self.propagate_expr(rhs))),
+            _ => expr.clone()
+        };
+        fold_expr(propagated)
+    }
+
+    /// Propagates into the right hand side of a member access, where variables name members
+    fn propagate_member(&self, expr: &Expression) -> Expression {
+        match expr {
+            Expression::Call(fn_name, arg_exprs) => {
+                let arg_exprs = arg_exprs.iter()
+                    .map(|arg_expr| self.propagate_expr(arg_expr))
+                    .collect();
+                Expression::Call(fn_name.clone(), arg_exprs)
+            },
+            Expression::MemberAccess(lhs, rhs) => {
+                Expression::MemberAccess(Box::new(self.propagate_member(lhs)), Box::new(self.propagate_member(rhs)))
+            },
+            _ => expr.clone()
+        }
+    }
+
+    fn get_constant(&self, var_name: &str) -> Option<&Expression> {
+        self.scopes.iter()
+            .rev()
+            .find_map(|scope| scope.get(var_name))
+    }
+
+    fn is_constant_candidate(&self, var_name: &String) -> bool {
+        !self.mutated.contains(var_name) && self.declarations.get(var_name) == Some(&1)
+    }
+
+    /// Checks if an expression is a literal of the declared variable type
+    fn literal_matches(expr: &Expression, var_type: &Type) -> bool {
+        matches!(
+            (expr, var_type),
+            (Expression::IntLiteral(_), Type::Int) |
+            (Expression::IntLiteral(_), Type::Auto) |
+            (Expression::FloatLiteral(_), Type::Float) |
+            (Expression::FloatLiteral(_), Type::Auto) |
+            (Expression::DoubleLiteral(_), Type::Double) |
+            (Expression::DoubleLiteral(_), Type::Auto) |
+            (Expression::BoolLiteral(_), Type::Bool) |
+            (Expression::BoolLiteral(_), Type::Auto) |
+            (Expression::CharLiteral(_), Type::Char) |
+            (Expression::CharLiteral(_), Type::Auto)
+        )
+    }
+}
+
+impl Default for ConstantPropagator {
+    fn default() -> ConstantPropagator {
+        ConstantPropagator::new()
+    }
+}
+
+/// Folds an operation on literals into a single literal.
+/// Operations that would overflow or divide an int by zero are left for the runtime.
+pub fn fold_expr(expr: Expression) -> Expression {
+    let folded = match &expr {
+        Expression::Addition(lhs, rhs) => {
+            match (lhs.as_ref(), rhs.as_ref()) {
+                (Expression::IntLiteral(l), Expression::IntLiteral(r)) => l.checked_add(*r).map(Expression::IntLiteral),
+                (Expression::FloatLiteral(l), Expression::FloatLiteral(r)) => Some(Expression::FloatLiteral(l + r)),
+                (Expression::DoubleLiteral(l), Expression::DoubleLiteral(r)) => Some(Expression::DoubleLiteral(l + r)),
+                (Expression::StringLiteral(l), Expression::StringLiteral(r)) => {
+                    // String literals keep their quotes
+                    Some(Expression::StringLiteral(format!("{}{}", &l[..l.len() - 1], &r[1..])))
+                },
+                _ => None
+            }
+        },
+        Expression::Subtraction(lhs, rhs) => {
+            match (lhs.as_ref(), rhs.as_ref()) {
+                (Expression::IntLiteral(l), Expression::IntLiteral(r)) => l.checked_sub(*r).map(Expression::IntLiteral),
+                (Expression::FloatLiteral(l), Expression::FloatLiteral(r)) => Some(Expression::FloatLiteral(l - r)),
+                (Expression::DoubleLiteral(l), Expression::DoubleLiteral(r)) => Some(Expression::DoubleLiteral(l - r)),
+                _ => None
+            }
+        },
+        Expression::Multiplication(lhs, rhs) => {
+            match (lhs.as_ref(), rhs.as_ref()) {
+                (Expression::IntLiteral(l), Expression::IntLiteral(r)) => l.checked_mul(*r).map(Expression::IntLiteral),
+                (Expression::FloatLiteral(l), Expression::FloatLiteral(r)) => Some(Expression::FloatLiteral(l * r)),
+                (Expression::DoubleLiteral(l), Expression::DoubleLiteral(r)) => Some(Expression::DoubleLiteral(l * r)),
+                _ => None
+            }
+        },
+        Expression::Division(lhs, rhs) => {
+            match (lhs.as_ref(), rhs.as_ref()) {
+                (Expression::IntLiteral(l), Expression::IntLiteral(r)) => l.checked_div(*r).map(Expression::IntLiteral),
+                (Expression::FloatLiteral(l), Expression::FloatLiteral(r)) => Some(Expression::FloatLiteral(l / r)),
+                (Expression::DoubleLiteral(l), Expression::DoubleLiteral(r)) => Some(Expression::DoubleLiteral(l / r)),
+                _ => None
+            }
+        },
+        Expression::Modulo(lhs, rhs) => {
+            match (lhs.as_ref(), rhs.as_ref()) {
+                (Expression::IntLiteral(l), Expression::IntLiteral(r)) => l.checked_rem(*r).map(Expression::IntLiteral),
+                (Expression::FloatLiteral(l), Expression::FloatLiteral(r)) => Some(Expression::FloatLiteral(l % r)),
+                (Expression::DoubleLiteral(l), Expression::DoubleLiteral(r)) => Some(Expression::DoubleLiteral(l % r)),
+                _ => None
+            }
+        },
+        Expression::Equals(lhs, rhs) => fold_comparison(lhs, rhs, |l, r| l == r, |l, r| l == r, Some(|l, r| l == r), Some(|l, r| l == r)),
+        Expression::NotEquals(lhs, rhs) => fold_comparison(lhs, rhs, |l, r| l != r, |l, r| l != r, Some(|l, r| l != r), Some(|l, r| l != r)),
+        Expression::GreaterThan(lhs, rhs) => fold_comparison(lhs, rhs, |l, r| l > r, |l, r| l > r, None, None),
+        Expression::LessThan(lhs, rhs) => fold_comparison(lhs, rhs, |l, r| l < r, |l, r| l < r, None, None),
+        Expression::GreaterThanEquals(lhs, rhs) => fold_comparison(lhs, rhs, |l, r| l >= r, |l, r| l >= r, None, None),
+        Expression::LessThanEquals(lhs, rhs) => fold_comparison(lhs, rhs, |l, r| l <= r, |l, r| l <= r, None, None),
+        Expression::And(lhs, rhs) => {
+            match (lhs.as_ref(), rhs.as_ref()) {
+                (Expression::BoolLiteral(l), Expression::BoolLiteral(r)) => Some(Expression::BoolLiteral(*l && *r)),
+                _ => None
+            }
+        },
+        Expression::Or(lhs, rhs) => {
+            match (lhs.as_ref(), rhs.as_ref()) {
+                (Expression::BoolLiteral(l), Expression::BoolLiteral(r)) => Some(Expression::BoolLiteral(*l || *r)),
+                _ => None
+            }
+        },
+        Expression::Not(inner) => {
+            match inner.as_ref() {
+                Expression::BoolLiteral(value) => Some(Expression::BoolLiteral(!*value)),
+                _ => None
+            }
+        },
+        Expression::Negation(inner) => {
+            match inner.as_ref() {
+                Expression::IntLiteral(value) => value.checked_neg().map(Expression::IntLiteral),
+                Expression::FloatLiteral(value) => Some(Expression::FloatLiteral(-*value)),
+                Expression::DoubleLiteral(value) => Some(Expression::DoubleLiteral(-*value)),
+                _ => None
+            }
+        },
+        Expression::Cast(inner, cast_type) => {
+            match (inner.as_ref(), cast_type) {
+                (Expression::IntLiteral(value), Type::Float) => Some(Expression::FloatLiteral(*value as f32)),
+                (Expression::FloatLiteral(value), Type::Int) => Some(Expression::IntLiteral(*value as i64)),
+                (Expression::IntLiteral(value), Type::Double) => Some(Expression::DoubleLiteral(*value as f64)),
+                (Expression::DoubleLiteral(value), Type::Int) => Some(Expression::IntLiteral(*value as i64)),
+                (Expression::FloatLiteral(value), Type::Double) => Some(Expression::DoubleLiteral(*value as f64)),
+                (Expression::DoubleLiteral(value), Type::Float) => Some(Expression::FloatLiteral(*value as f32)),
+                (Expression::BoolLiteral(value), Type::Int) => Some(Expression::IntLiteral(*value as i64)),
+                (Expression::CharLiteral(value), Type::Int) => Some(Expression::IntLiteral(*value as i64)),
+                (literal @ Expression::IntLiteral(_), Type::Int) |
+                (literal @ Expression::FloatLiteral(_), Type::Float) |
+                (literal @ Expression::DoubleLiteral(_), Type::Double) |
+                (literal @ Expression::BoolLiteral(_), Type::Bool) |
+                (literal @ Expression::CharLiteral(_), Type::Char) => Some(literal.clone()),
+                _ => None
+            }
+        },
+        _ => None
+    };
+    folded.unwrap_or(expr)
+}
+
+type Comparison<T> = fn(T, T) -> bool;
+
+fn fold_comparison(lhs: &Expression, rhs: &Expression, int_cmp: Comparison<i64>, float_cmp: Comparison<f64>, bool_cmp: Option<Comparison<bool>>, string_cmp: Option<fn(&str, &str) -> bool>) -> Option<Expression> {
+    match (lhs, rhs) {
+        (Expression::IntLiteral(l), Expression::IntLiteral(r)) => Some(Expression::BoolLiteral(int_cmp(*l, *r))),
+        // Floats widen to doubles losslessly
+        (Expression::FloatLiteral(l), Expression::FloatLiteral(r)) => Some(Expression::BoolLiteral(float_cmp(*l as f64, *r as f64))),
+        (Expression::DoubleLiteral(l), Expression::DoubleLiteral(r)) => Some(Expression::BoolLiteral(float_cmp(*l, *r))),
+        // Chars compare by their code point
+        (Expression::CharLiteral(l), Expression::CharLiteral(r)) => Some(Expression::BoolLiteral(int_cmp(*l as i64, *r as i64))),
+        (Expression::BoolLiteral(l), Expression::BoolLiteral(r)) => {
+            bool_cmp.map(|bool_cmp| Expression::BoolLiteral(bool_cmp(*l, *r)))
+        },
+        (Expression::StringLiteral(l), Expression::StringLiteral(r)) => {
+            string_cmp.map(|string_cmp| Expression::BoolLiteral(string_cmp(l, r)))
+        },
+        _ => None
+    }
+}
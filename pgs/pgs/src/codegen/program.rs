@@ -2,6 +2,15 @@ use crate::{
     api::{
         function::Function
     },
+    parser::{
+        ast::Type
+    },
+    vm::{
+        is::{
+            Opcode,
+            Operand
+        }
+    }
 };
 
 use std::{
@@ -9,24 +18,118 @@ use std::{
         BTreeMap,
         HashMap
     },
-    ops::Range
+    convert::TryFrom,
+    ops::Range,
+    fmt::{
+        Display,
+        Formatter,
+        Result as FmtResult
+    },
+    fs::{
+        read,
+        write
+    },
+    path::{
+        Path,
+        PathBuf
+    }
+};
+
+use bincode::{
+    serialize,
+    deserialize
 };
 
+/// Magic number at the start of bytecode files
+pub const PROGRAM_MAGIC: &[u8; 4] = b"PGSB";
+/// Version of the bytecode file format
+pub const PROGRAM_VERSION: u16 = 5;
+/// File extension of bytecode files
+pub const PROGRAM_EXTENSION: &str = "pgsb";
+
+/// Name, uid and code offset of a function in a bytecode file, foreign functions have no offset
+type FunctionEntry = (String, u64, Option<u64>);
+/// Code offset, line and column of a statement in a bytecode file
+type LineEntry = (u64, u64, u64);
+/// Full name, address, type tag and constness of a global in a bytecode file
+type GlobalEntry = (String, u64, u8, bool);
+/// Data segment, function table, code, line table, globals and data pointers of a bytecode file
+type ProgramBody = (Vec<u8>, Vec<FunctionEntry>, Vec<u8>, Vec<LineEntry>, Vec<GlobalEntry>, Vec<u64>);
+
+/// Types globals can have, indexed by their tag in bytecode files
+const GLOBAL_TYPES: [Type; 6] = [Type::Int, Type::Float, Type::Double, Type::Bool, Type::Char, Type::String];
+
+/// A module level variable of a program, read and written by the host with Engine::get_global() and set_global()
+#[derive(PartialEq, Debug, Clone)]
+pub struct GlobalDef {
+    /// Address in the data segment
+    pub address: u64,
+    pub var_type: Type,
+    /// Constants of foreign modules can not be assigned to
+    pub constant: bool
+}
+
+/// Line and column in the source code, both starting at 1
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct SourcePosition {
+    pub line: usize,
+    pub column: usize
+}
+
+impl Display for SourcePosition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[derive(Debug)]
+pub enum ProgramError {
+    NotReadable(PathBuf),
+    NotWritable(PathBuf),
+    /// The file does not start with the bytecode magic number
+    InvalidMagic,
+    /// The file was written in another version of the format
+    UnsupportedVersion(u16),
+    /// The contents of the file could not be decoded
+    Malformed,
+    /// A function of this name is defined by more than one of the linked programs
+    DuplicateFunction(String),
+    /// A static of this name is declared by more than one of the linked programs
+    DuplicateGlobal(String)
+}
+
+pub type ProgramResult<T> = Result<T, ProgramError>;
+
 #[derive(PartialEq, Debug)]
 pub struct Program {
     pub code: Vec<u8>,
+    /// Size of the data segment at the start of the code
+    pub data_size: usize,
     pub functions: HashMap<u64, usize>,
+    /// Uids of all functions, including foreign ones, by full name
+    pub function_uids: HashMap<String, u64>,
     pub foreign_functions: HashMap<u64, Function>,
-    pub static_pointers: BTreeMap<usize, Range<usize>> 
+    pub static_pointers: BTreeMap<usize, Range<usize>>,
+    /// Source position of the statement starting at each code offset
+    pub lines: BTreeMap<usize, SourcePosition>,
+    /// Module level variables by full name, e.g. "root::difficulty"
+    pub globals: HashMap<String, GlobalDef>,
+    /// Offsets in the data segment holding the address of other data, moved when linking
+    pub data_pointers: Vec<usize>
 }
 
 impl Program {
     pub fn new() -> Program {
         Program {
             code: Vec::new(),
+            data_size: 0,
             functions: HashMap::new(),
+            function_uids: HashMap::new(),
             foreign_functions: HashMap::new(),
-            static_pointers: BTreeMap::new() 
+            static_pointers: BTreeMap::new(),
+            lines: BTreeMap::new(),
+            globals: HashMap::new(),
+            data_pointers: Vec::new()
         }
     }
 
@@ -35,11 +138,21 @@ impl Program {
         self
     }
 
+    pub fn with_data_size(mut self, data_size: usize) -> Program {
+        self.data_size = data_size;
+        self
+    }
+
     pub fn with_functions(mut self, functions: HashMap<u64, usize>) -> Program {
         self.functions = functions;
         self
     }
 
+    pub fn with_function_uids(mut self, function_uids: HashMap<String, u64>) -> Program {
+        self.function_uids = function_uids;
+        self
+    }
+
     pub fn with_foreign_functions(mut self, functions: HashMap<u64, Function>) -> Program {
         self.foreign_functions = functions;
         self
@@ -50,7 +163,444 @@ impl Program {
         self
     }
 
+    pub fn with_lines(mut self, lines: BTreeMap<usize, SourcePosition>) -> Program {
+        self.lines = lines;
+        self
+    }
+
+    pub fn with_globals(mut self, globals: HashMap<String, GlobalDef>) -> Program {
+        self.globals = globals;
+        self
+    }
+
+    pub fn with_data_pointers(mut self, data_pointers: Vec<usize>) -> Program {
+        self.data_pointers = data_pointers;
+        self
+    }
+
     pub fn get_size(&self) -> usize {
         self.code.len()
     }
-}
\ No newline at end of file
+
+    /// Gets the uid of the function starting at the given code offset
+    pub fn get_function_uid_at(&self, offset: usize) -> Option<u64> {
+        self.functions.iter()
+            .find(|(_, fn_offset)| **fn_offset == offset)
+            .map(|(fn_uid, _)| *fn_uid)
+    }
+
+    /// Gets the full name of a function by uid
+    pub fn get_function_name(&self, fn_uid: u64) -> Option<&String> {
+        self.function_uids.iter()
+            .find(|(_, uid)| **uid == fn_uid)
+            .map(|(fn_name, _)| fn_name)
+    }
+
+    /// Gets a module level variable by full name, names without a module path are looked up in the root,
+    /// e.g. "difficulty" is "root::difficulty"
+    pub fn get_global(&self, name: &str) -> Option<&GlobalDef> {
+        self.globals.get(name)
+            .or_else(|| self.globals.get(&format!("root::{}", name)))
+    }
+
+    /// Gets the source position of the statement containing the given code offset
+    pub fn get_position_at(&self, offset: usize) -> Option<SourcePosition> {
+        let (stmt_offset, position) = self.lines.range(..=offset)
+            .next_back()?;
+        // Code between the start of a function and its first statement belongs to no statement
+        let in_other_fn = self.functions.values()
+            .any(|fn_offset| fn_offset > stmt_offset && *fn_offset <= offset);
+        if in_other_fn {
+            return None;
+        }
+        Some(*position)
+    }
+
+    /// Links separately compiled programs into one, e.g. a library and the script using it.
+    ///
+    /// The data segments of the programs come first, followed by their code, both in the given order.
+    /// A foreign function named like a function of another program, e.g. declared with a module
+    /// of functions without closures, is resolved to that function. Foreign functions of the same name
+    /// are merged. Lambdas of later programs get the index of their program appended to their name.
+    pub fn link(programs: &[&Program]) -> ProgramResult<Program> {
+        let mut data_offsets = Vec::new();
+        let mut data_size = 0;
+        for program in programs.iter() {
+            if program.data_size > program.code.len() {
+                return Err(ProgramError::Malformed);
+            }
+            data_offsets.push(data_size);
+            data_size += program.data_size;
+        }
+        let mut code_offsets = Vec::new();
+        let mut code_size = data_size;
+        for program in programs.iter() {
+            code_offsets.push(code_size);
+            code_size += program.code.len() - program.data_size;
+        }
+
+        // Script functions first, so foreign functions can be resolved to them
+        let mut functions = HashMap::new();
+        let mut function_uids = HashMap::new();
+        for (index, program) in programs.iter().enumerate() {
+            for (fn_name, fn_uid) in program.function_uids.iter() {
+                let fn_offset = match program.functions.get(fn_uid) {
+                    Some(fn_offset) => *fn_offset,
+                    None => continue
+                };
+                // Lambdas are named after their module and a counter, so they are found in other programs as well
+                let mut linked_name = fn_name.clone();
+                if linked_name.contains('#') && function_uids.contains_key(&linked_name) {
+                    linked_name = format!("{}#{}", fn_name, index);
+                }
+                if function_uids.contains_key(&linked_name) || functions.contains_key(fn_uid) {
+                    return Err(ProgramError::DuplicateFunction(fn_name.clone()));
+                }
+                function_uids.insert(linked_name, *fn_uid);
+                functions.insert(*fn_uid, fn_offset - program.data_size + code_offsets[index]);
+            }
+        }
+
+        // Uids of foreign functions to replace in the code of each program
+        let mut uid_maps = vec![HashMap::new(); programs.len()];
+        let mut foreign_functions = HashMap::new();
+        for (index, program) in programs.iter().enumerate() {
+            for (fn_name, fn_uid) in program.function_uids.iter() {
+                if program.functions.contains_key(fn_uid) {
+                    continue;
+                }
+                match function_uids.get(fn_name) {
+                    Some(linked_uid) => {
+                        if linked_uid != fn_uid {
+                            uid_maps[index].insert(*fn_uid, *linked_uid);
+                        }
+                    },
+                    None => {
+                        function_uids.insert(fn_name.clone(), *fn_uid);
+                        if let Some(function) = program.foreign_functions.get(fn_uid) {
+                            foreign_functions.insert(*fn_uid, function.clone());
+                        }
+                    }
+                };
+            }
+        }
+
+        let mut code = Vec::new();
+        let mut data_pointers = Vec::new();
+        let mut static_pointers = BTreeMap::new();
+        let mut globals: HashMap<String, GlobalDef> = HashMap::new();
+        for (index, program) in programs.iter().enumerate() {
+            let data_offset = data_offsets[index];
+            let mut data = program.code[..program.data_size].to_vec();
+            for pointer in program.data_pointers.iter() {
+                let addr = data.get(*pointer..*pointer + 8)
+                    .map(u64::read_le)
+                    .ok_or(ProgramError::Malformed)?;
+                data[*pointer..*pointer + 8].copy_from_slice(&(addr + data_offset as u64).to_le_bytes());
+                data_pointers.push(pointer + data_offset);
+            }
+            code.append(&mut data);
+
+            for (addr, range) in program.static_pointers.iter() {
+                static_pointers.insert(addr + data_offset, range.start + data_offset..range.end + data_offset);
+            }
+
+            for (name, global_def) in program.globals.iter() {
+                match globals.get(name) {
+                    // Constants of foreign modules are part of every program using the module
+                    Some(linked_def) if linked_def.constant && global_def.constant => {},
+                    Some(_) => return Err(ProgramError::DuplicateGlobal(name.clone())),
+                    None => {
+                        globals.insert(name.clone(), GlobalDef {
+                            address: global_def.address + data_offset as u64,
+                            var_type: global_def.var_type.clone(),
+                            constant: global_def.constant
+                        });
+                    }
+                };
+            }
+        }
+
+        let mut lines = BTreeMap::new();
+        for (index, program) in programs.iter().enumerate() {
+            let mut fn_code = program.code[program.data_size..].to_vec();
+            relocate_code(&mut fn_code, data_offsets[index] as u64, &uid_maps[index])?;
+            code.append(&mut fn_code);
+
+            for (offset, position) in program.lines.iter() {
+                lines.insert(offset - program.data_size + code_offsets[index], *position);
+            }
+        }
+
+        Ok(
+            Program::new()
+                .with_code(code)
+                .with_data_size(data_size)
+                .with_functions(functions)
+                .with_function_uids(function_uids)
+                .with_foreign_functions(foreign_functions)
+                .with_static_pointers(static_pointers)
+                .with_lines(lines)
+                .with_globals(globals)
+                .with_data_pointers(data_pointers)
+        )
+    }
+
+    /// Appends a recompiled program to this one while frames still run its code, e.g. a script reloaded between ticks.
+    ///
+    /// The code of this program stays in place behind the given data, the current data segment of this program,
+    /// so the return addresses and data addresses held by the running frames remain valid. The data segment and
+    /// the code of the recompiled program follow. Its functions replace the ones of the same uid, calls made by the
+    /// kept code reach them as well. The moved statics are given by their address range in this program and their
+    /// address in the recompiled one, the kept code addresses them there.
+    pub fn append_reloaded(&self, data: &[u8], program: &Program, moved_statics: &[(Range<u64>, u64)]) -> ProgramResult<Program> {
+        if data.len() != self.data_size || self.data_size > self.code.len() || program.data_size > program.code.len() {
+            return Err(ProgramError::Malformed);
+        }
+        let data_offset = self.code.len();
+
+        let mut code = data.to_vec();
+        let mut kept_code = self.code[self.data_size..].to_vec();
+        let moved_statics: Vec<(Range<u64>, u64)> = moved_statics.iter()
+            .map(|(range, addr)| (range.clone(), addr + data_offset as u64))
+            .collect();
+        move_static_addresses(&mut kept_code, &moved_statics)?;
+        code.append(&mut kept_code);
+
+        let mut new_data = program.code[..program.data_size].to_vec();
+        let mut data_pointers = self.data_pointers.clone();
+        for pointer in program.data_pointers.iter() {
+            let addr = new_data.get(*pointer..*pointer + 8)
+                .map(u64::read_le)
+                .ok_or(ProgramError::Malformed)?;
+            new_data[*pointer..*pointer + 8].copy_from_slice(&(addr + data_offset as u64).to_le_bytes());
+            data_pointers.push(pointer + data_offset);
+        }
+        code.append(&mut new_data);
+        let data_size = code.len();
+
+        let mut fn_code = program.code[program.data_size..].to_vec();
+        relocate_code(&mut fn_code, data_offset as u64, &HashMap::new())?;
+        code.append(&mut fn_code);
+
+        let mut functions = self.functions.clone();
+        for (fn_uid, fn_offset) in program.functions.iter() {
+            functions.insert(*fn_uid, fn_offset + data_offset);
+        }
+        let mut function_uids = self.function_uids.clone();
+        function_uids.extend(program.function_uids.clone());
+        let mut foreign_functions = self.foreign_functions.clone();
+        foreign_functions.extend(program.foreign_functions.clone());
+
+        let mut static_pointers = self.static_pointers.clone();
+        for (addr, range) in program.static_pointers.iter() {
+            static_pointers.insert(addr + data_offset, range.start + data_offset..range.end + data_offset);
+        }
+        let mut lines = self.lines.clone();
+        for (offset, position) in program.lines.iter() {
+            lines.insert(offset + data_offset, *position);
+        }
+        // Only the statics of the recompiled program are visible to the host
+        let globals = program.globals.iter()
+            .map(|(name, global_def)| {
+                let global_def = GlobalDef {
+                    address: global_def.address + data_offset as u64,
+                    var_type: global_def.var_type.clone(),
+                    constant: global_def.constant
+                };
+                (name.clone(), global_def)
+            })
+            .collect();
+
+        Ok(
+            Program::new()
+                .with_code(code)
+                .with_data_size(data_size)
+                .with_functions(functions)
+                .with_function_uids(function_uids)
+                .with_foreign_functions(foreign_functions)
+                .with_static_pointers(static_pointers)
+                .with_lines(lines)
+                .with_globals(globals)
+                .with_data_pointers(data_pointers)
+        )
+    }
+
+    /// Writes the program to a bytecode file.
+    /// Foreign functions are stored by name and have to be bound again when loading it.
+    pub fn save(&self, path: &Path) -> ProgramResult<()> {
+        let mut functions: Vec<FunctionEntry> = self.function_uids.iter()
+            .map(|(fn_name, fn_uid)| {
+                let fn_offset = self.functions.get(fn_uid)
+                    .map(|fn_offset| *fn_offset as u64);
+                (fn_name.clone(), *fn_uid, fn_offset)
+            })
+            .collect();
+        functions.sort();
+
+        let lines = self.lines.iter()
+            .map(|(offset, position)| (*offset as u64, position.line as u64, position.column as u64))
+            .collect();
+
+        let mut globals: Vec<GlobalEntry> = self.globals.iter()
+            .filter_map(|(name, global_def)| {
+                let type_tag = GLOBAL_TYPES.iter()
+                    .position(|global_type| *global_type == global_def.var_type)?;
+                Some((name.clone(), global_def.address, type_tag as u8, global_def.constant))
+            })
+            .collect();
+        globals.sort();
+
+        let data_pointers = self.data_pointers.iter()
+            .map(|pointer| *pointer as u64)
+            .collect();
+
+        let data_size = self.data_size.min(self.code.len());
+        let body: ProgramBody = (
+            self.code[..data_size].to_vec(),
+            functions,
+            self.code[data_size..].to_vec(),
+            lines,
+            globals,
+            data_pointers
+        );
+
+        let mut bytes = PROGRAM_MAGIC.to_vec();
+        bytes.extend_from_slice(&PROGRAM_VERSION.to_le_bytes());
+        let mut body_bytes = serialize(&body)
+            .map_err(|_| ProgramError::Malformed)?;
+        bytes.append(&mut body_bytes);
+
+        write(path, bytes)
+            .map_err(|_| ProgramError::NotWritable(path.to_path_buf()))
+    }
+
+    /// Reads a program from a bytecode file written by save(), without foreign functions
+    pub fn load(path: &Path) -> ProgramResult<Program> {
+        let bytes = read(path)
+            .map_err(|_| ProgramError::NotReadable(path.to_path_buf()))?;
+        if bytes.len() < PROGRAM_MAGIC.len() + 2 || &bytes[..PROGRAM_MAGIC.len()] != PROGRAM_MAGIC {
+            return Err(ProgramError::InvalidMagic);
+        }
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if version != PROGRAM_VERSION {
+            return Err(ProgramError::UnsupportedVersion(version));
+        }
+
+        let (mut code, function_entries, mut fn_code, line_entries, global_entries, data_pointers): ProgramBody = deserialize(&bytes[6..])
+            .map_err(|_| ProgramError::Malformed)?;
+
+        let mut functions = HashMap::new();
+        let mut function_uids = HashMap::new();
+        for (fn_name, fn_uid, fn_offset) in function_entries {
+            if let Some(fn_offset) = fn_offset {
+                functions.insert(fn_uid, fn_offset as usize);
+            }
+            function_uids.insert(fn_name, fn_uid);
+        }
+
+        let lines = line_entries.into_iter()
+            .map(|(offset, line, column)| {
+                let position = SourcePosition {
+                    line: line as usize,
+                    column: column as usize
+                };
+                (offset as usize, position)
+            })
+            .collect();
+
+        let mut globals = HashMap::new();
+        for (name, address, type_tag, constant) in global_entries {
+            let var_type = GLOBAL_TYPES.get(type_tag as usize)
+                .cloned()
+                .ok_or(ProgramError::Malformed)?;
+            globals.insert(name, GlobalDef {
+                address: address,
+                var_type: var_type,
+                constant: constant
+            });
+        }
+
+        let data_size = code.len();
+        code.append(&mut fn_code);
+
+        Ok(
+            Program::new()
+                .with_code(code)
+                .with_data_size(data_size)
+                .with_functions(functions)
+                .with_function_uids(function_uids)
+                .with_lines(lines)
+                .with_globals(globals)
+                .with_data_pointers(data_pointers.into_iter().map(|pointer| pointer as usize).collect())
+        )
+    }
+}
+
+/// Moves the data addresses loaded by the code of a linked program by the offset of its data segment,
+/// and replaces the uids of its foreign functions resolved to other ones
+fn relocate_code(code: &mut [u8], data_offset: u64, uid_map: &HashMap<u64, u64>) -> ProgramResult<()> {
+    let mut offset = 0;
+    while offset < code.len() {
+        let opcode = Opcode::try_from(code[offset])
+            .map_err(|_| ProgramError::Malformed)?;
+        let operands_start = offset + 1;
+        let operands_end = operands_start + opcode.get_operands_size();
+        if operands_end > code.len() {
+            return Err(ProgramError::Malformed);
+        }
+        let operands = &mut code[operands_start..operands_end];
+
+        match opcode {
+            Opcode::LDA => {
+                let addr = u64::read_le(&operands[0..8]) + data_offset;
+                operands[0..8].copy_from_slice(&addr.to_le_bytes());
+            },
+            Opcode::LDC => {
+                let addr = u32::try_from(u32::read_le(&operands[0..4]) as u64 + data_offset)
+                    .map_err(|_| ProgramError::Malformed)?;
+                operands[0..4].copy_from_slice(&addr.to_le_bytes());
+            },
+            Opcode::CALL |
+            Opcode::TCALL |
+            Opcode::SPAWN |
+            Opcode::CORO => {
+                let fn_uid = u64::read_le(&operands[0..8]);
+                if let Some(linked_uid) = uid_map.get(&fn_uid) {
+                    operands[0..8].copy_from_slice(&linked_uid.to_le_bytes());
+                }
+            },
+            _ => {}
+        };
+
+        offset = operands_end;
+    }
+    Ok(())
+}
+
+/// Moves the addresses of statics loaded by the code from their address range to their new address
+fn move_static_addresses(code: &mut [u8], moved_statics: &[(Range<u64>, u64)]) -> ProgramResult<()> {
+    let mut offset = 0;
+    while offset < code.len() {
+        let opcode = Opcode::try_from(code[offset])
+            .map_err(|_| ProgramError::Malformed)?;
+        let operands_start = offset + 1;
+        let operands_end = operands_start + opcode.get_operands_size();
+        if operands_end > code.len() {
+            return Err(ProgramError::Malformed);
+        }
+        let operands = &mut code[operands_start..operands_end];
+
+        if opcode == Opcode::LDA {
+            let addr = u64::read_le(&operands[0..8]);
+            let moved = moved_statics.iter()
+                .find(|(range, _)| range.contains(&addr));
+            if let Some((range, new_addr)) = moved {
+                operands[0..8].copy_from_slice(&(new_addr + (addr - range.start)).to_le_bytes());
+            }
+        }
+
+        offset = operands_end;
+    }
+    Ok(())
+}
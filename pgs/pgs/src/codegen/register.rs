@@ -41,7 +41,8 @@ pub enum Register {
     R14 = 14,
     R15 = 15,
     SP = 16,
-    IP = 17
+    IP = 17,
+    FP = 18
 }
 
 impl From<u8> for Register {
@@ -123,4 +124,9 @@ impl RegisterAllocator {
     pub fn force_temp_register(&mut self, reg: Register) {
         self.forced_temp = Some(reg);
     }
+
+    /// Stops forcing a register, get_last_temp_register() returns the last one of the queue again
+    pub fn clear_forced_temp_register(&mut self) {
+        self.forced_temp = None;
+    }
 }
\ No newline at end of file
@@ -34,6 +34,12 @@ impl UIDGenerator {
         uid
     }
 
+    /// Reserves a uid for a function name, so it is kept when the function is compiled again
+    pub fn reserve_function_uid(&mut self, name: &String, uid: u64) {
+        self.uid_set.insert(uid);
+        self.functions.insert(name.clone(), uid);
+    }
+
     pub fn get_function_uid(&mut self, name: &String) -> u64 {
         if self.functions.contains_key(name) {
             let uid = self.functions.get(name).unwrap();
@@ -2,12 +2,28 @@ use crate::{
     vm::{
         core::{
             Core,
-            CoreError
+            CoreError,
+            CoreStats,
+            TickState,
+            RunState,
+            TraceHook,
+            DEFAULT_TASK_SLICE
+        },
+        profile::{
+            ProfileReport
+        },
+        interrupt::{
+            CoreHandle
         },
         register::{
             RegisterAccess,
             Register as RegisterUnion
-        }
+        },
+        verifier::{
+            verify,
+            VerifyError
+        },
+        address::Address
     },
     parser::{
         parser::{
@@ -16,7 +32,8 @@ use crate::{
         },
         ast::{
             Declaration,
-            Statement
+            Statement,
+            Type
         }
     },
     codegen::{
@@ -24,32 +41,83 @@ use crate::{
             Compiler,
             CompilerError
         },
-        register::Register
+        register::Register,
+        debug::DebugInfo,
+        program::{
+            Program,
+            ProgramError,
+            GlobalDef
+        },
+        def::StaticDef
     },
     api::{
-        module::Module
+        module::Module,
+        call::{
+            ScriptArgs,
+            ScriptReturn,
+            ScriptGlobal
+        },
+        container::{
+            Container,
+            PgsContainer,
+            PgsValue
+        },
+        adapter::{
+            ObjectAdapter,
+            adapter_container
+        }
+    },
+    package::{
+        PackageResolver,
+        DEFAULT_PACKAGES_ROOT
+    },
+    source::{
+        SourceResolver,
+        DEFAULT_SOURCES_ROOT
+    },
+    isolate::{
+        Isolate,
+        IsolatePool
     }
 };
 
+#[cfg(feature = "extensions")]
+use crate::extension::Extension;
+
 use std::{
+    collections::{
+        HashMap,
+        BTreeMap
+    },
     io::{
-        Read
+        Read,
+        Write
     },
     fs::{
-        File
+        read_to_string
     },
     path::{
-        Path
+        Path,
+        PathBuf
     },
     error::Error,
+    sync::{
+        Arc,
+        Mutex
+    },
     fmt::{
         Display,
         Debug,
         Formatter,
         Result as FmtResult
-    }
+    },
+    mem::size_of,
+    ops::Range,
+    any::Any
 };
 
+use bincode::deserialize;
+
 use serde::{
     de::DeserializeOwned,
     Serialize
@@ -57,25 +125,281 @@ use serde::{
 
 pub struct Engine {
     core: Core,
-    pub compiler: Compiler
+    pub compiler: Compiler,
+    packages: PackageResolver,
+    sources: SourceResolver,
+    runtime_error_handler: Option<RuntimeErrorHandler>,
+    foreign_modules: Vec<Module>,
+    foreign_containers: Vec<Container>,
+    /// Loaded extension libraries. Declared last, so the closures they registered are dropped first.
+    #[cfg(feature = "extensions")]
+    extensions: Vec<Arc<Extension>>
+}
+
+/// Changes to the script functions made by a reload, by full function name
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct ReloadReport {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// Functions whose arguments or return type changed
+    pub changed: Vec<String>,
+    pub unchanged: Vec<String>,
+    /// Statics that kept their values, as their type did not change
+    pub preserved: Vec<String>
+}
+
+/// The value of a static carried over by a reload
+struct PreservedStatic {
+    /// Address in the data segment of the old program
+    old_address: u64,
+    /// Address in the data segment of the new program
+    address: u64,
+    bytes: Vec<u8>,
+    /// Offsets and contents of the strings in the value pointing into the data segment of the old program
+    strings: Vec<(usize, Vec<u8>)>
+}
+
+/// Callback invoked with information about runtime errors
+pub type RuntimeErrorHandler = Box<dyn Fn(&RuntimeErrorInfo)>;
+
+/// A single frame of a script stack trace
+#[derive(PartialEq, Debug, Clone)]
+pub struct StackFrame {
+    /// Code offset of the instruction executing in this frame
+    pub offset: usize,
+    pub function: Option<String>,
+    pub line: Option<usize>
+}
+
+/// Information about an error raised while running a script
+#[derive(Debug)]
+pub struct RuntimeErrorInfo {
+    pub error: CoreError,
+    /// Instruction pointer at the time of the error
+    pub ip: usize,
+    pub function: Option<String>,
+    /// Stack trace, innermost frame first
+    pub stack_trace: Vec<StackFrame>
 }
 
 pub type EngineResult<T> = Result<T, Box<EngineError>>;
 
+/// Stack size in bytes of engines built without setting one
+pub const DEFAULT_STACK_SIZE: usize = 1024;
+
+/// Builds engines with resource limits, foreign modules and tracing, e.g.
+/// EngineBuilder::new().with_max_heap(1 << 20).with_module(std_module).build()
+pub struct EngineBuilder {
+    stack_size: usize,
+    max_instructions: Option<u64>,
+    fuel: Option<u64>,
+    max_heap: Option<usize>,
+    strict_numeric: bool,
+    ref_counting: bool,
+    task_slice: u64,
+    /// Foreign modules registered in the root module, in the order they were added
+    modules: Vec<Module>,
+    trace_hook: Option<TraceHook>,
+    profiling: bool,
+    contexts: Vec<Box<dyn Any + Send>>
+}
+
+impl EngineBuilder {
+    /// Creates a new builder with a stack size of DEFAULT_STACK_SIZE bytes and no limits
+    pub fn new() -> EngineBuilder {
+        EngineBuilder {
+            stack_size: DEFAULT_STACK_SIZE,
+            max_instructions: None,
+            fuel: None,
+            max_heap: None,
+            strict_numeric: false,
+            ref_counting: false,
+            task_slice: DEFAULT_TASK_SLICE,
+            modules: Vec::new(),
+            trace_hook: None,
+            profiling: false,
+            contexts: Vec::new()
+        }
+    }
+
+    /// Sets the stack size in bytes, which also bounds the depth of recursion
+    pub fn with_stack_size(mut self, stack_size: usize) -> EngineBuilder {
+        self.stack_size = stack_size;
+        self
+    }
+
+    /// Sets the maximum number of instructions a single run may execute
+    pub fn with_max_instructions(mut self, max_instructions: u64) -> EngineBuilder {
+        self.max_instructions = Some(max_instructions);
+        self
+    }
+
+    /// Sets the number of instructions all runs together may execute, see Engine::resume()
+    pub fn with_fuel(mut self, fuel: u64) -> EngineBuilder {
+        self.fuel = Some(fuel);
+        self
+    }
+
+    /// Sets the maximum heap size in bytes
+    pub fn with_max_heap(mut self, max_heap: usize) -> EngineBuilder {
+        self.max_heap = Some(max_heap);
+        self
+    }
+
+    /// Disables the promotion of mixed numeric operands, see Engine::set_strict_numeric()
+    pub fn with_strict_numeric(mut self, strict_numeric: bool) -> EngineBuilder {
+        self.strict_numeric = strict_numeric;
+        self
+    }
+
+    /// Frees heap strings deterministically by reference counting, see Engine::set_ref_counting()
+    pub fn with_ref_counting(mut self, ref_counting: bool) -> EngineBuilder {
+        self.ref_counting = ref_counting;
+        self
+    }
+
+    /// Sets the number of instructions a spawned task runs before the next one gets its turn
+    pub fn with_task_slice(mut self, task_slice: u64) -> EngineBuilder {
+        self.task_slice = task_slice;
+        self
+    }
+
+    /// Registers a foreign module, e.g. the std module of pgs-std with the capabilities scripts may use
+    pub fn with_module(mut self, module: Module) -> EngineBuilder {
+        self.modules.push(module);
+        self
+    }
+
+    /// Sets the hook every executed instruction is passed to, see Engine::set_trace_hook()
+    pub fn with_trace_hook(mut self, trace_hook: TraceHook) -> EngineBuilder {
+        self.trace_hook = Some(trace_hook);
+        self
+    }
+
+    /// Collects counters per opcode and function, see Engine::set_profiling()
+    pub fn with_profiling(mut self, profiling: bool) -> EngineBuilder {
+        self.profiling = profiling;
+        self
+    }
+
+    /// Sets a context foreign functions can borrow, see Engine::set_context()
+    pub fn with_context<T: Any + Send>(mut self, context: T) -> EngineBuilder {
+        self.contexts.push(Box::new(context));
+        self
+    }
+
+    /// Builds the engine, fails if a module can not be registered
+    pub fn build(self) -> EngineResult<Engine> {
+        let mut engine = Engine::new(self.stack_size);
+        engine.core.set_max_instructions(self.max_instructions);
+        engine.core.set_fuel(self.fuel);
+        engine.core.set_max_heap(self.max_heap);
+        engine.core.set_task_slice(self.task_slice);
+        engine.set_strict_numeric(self.strict_numeric);
+        engine.set_ref_counting(self.ref_counting);
+        engine.set_trace_hook(self.trace_hook);
+        engine.set_profiling(self.profiling);
+        for context in self.contexts {
+            engine.core.set_context_box(context);
+        }
+        for module in self.modules {
+            engine.register_module(module)?;
+        }
+        Ok(engine)
+    }
+}
+
+impl Default for EngineBuilder {
+    fn default() -> EngineBuilder {
+        EngineBuilder::new()
+    }
+}
+
 #[derive(Debug)]
 pub enum EngineError {
     Unknown,
     CoreError(CoreError),
     ParseError(ParseError),
     CompileError(CompilerError),
+    PackageNotReadable(PathBuf),
+    PackageParseError(PathBuf, ParseError),
+    /// A package module has the same path as another package module or a module of the importing code
+    PackageModuleCollision(String),
+    SourceNotReadable(PathBuf),
+    SourceParseError(PathBuf, ParseError),
+    /// A file imports itself, directly or through other files
+    ImportCycle(PathBuf),
+    ModuleFileNotFound(PathBuf),
+    /// A shared program can only be loaded while no function is running, including between the ticks of a run
+    ReloadWhileRunning,
+    ProgramError(ProgramError),
+    /// A foreign function of a loaded program is not registered with the engine
+    UnboundForeignFunction(String),
+    VerifyError(VerifyError),
+    /// The arguments or return type passed to Engine::call_function() do not match the function
+    SignatureMismatch(String),
+    /// An error raised while running a script, with its stack trace innermost frame first
+    RuntimeError(CoreError, Vec<StackFrame>),
+    /// An extension library could not be opened, with the reason given by the loader
+    ExtensionNotLoadable(PathBuf, String),
+    /// An extension library does not export a register function
+    ExtensionEntryNotFound(PathBuf),
+    /// The loaded program has no module level variable of this name
+    UnknownGlobal(String),
+    /// A module level variable was accessed as another type than the one it was declared with
+    GlobalTypeMismatch(String, Type),
+    /// A constant of a foreign module was set by the host
+    AssignToConstantGlobal(String)
 }
 
 impl Display for EngineError {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        write!(f, "{:?}", self)
+        match self {
+            EngineError::RuntimeError(core_error, stack_trace) => {
+                write!(f, "{}\n    {}", core_error, format_stack_trace(stack_trace))
+            },
+            _ => write!(f, "{:?}", self)
+        }
     }
 }
 
+/// Renders a stack trace outermost frame first, e.g. "fn main -> fn foo -> fn bar"
+pub fn format_stack_trace(stack_trace: &[StackFrame]) -> String {
+    stack_trace.iter()
+        .rev()
+        .map(|frame| {
+            let fn_name = frame.function.as_ref()
+                .map(|fn_name| fn_name.trim_start_matches("root::"))
+                .unwrap_or("?");
+            format!("fn {}", fn_name)
+        })
+        .collect::<Vec<String>>()
+        .join(" -> ")
+}
+
+/// Gets the script stack trace of the current run of a core, innermost frame first
+pub(crate) fn get_core_stack_trace(core: &Core) -> Vec<StackFrame> {
+    let program = core.get_program();
+    // Both the ip and the return addresses point past the instruction of their frame
+    let mut offsets = vec![core.get_ip()];
+    offsets.append(&mut core.get_call_stack());
+    offsets.into_iter()
+        .zip(core.get_function_stack())
+        .map(|(offset, fn_uid)| {
+            let instr_offset = offset.saturating_sub(1);
+            StackFrame {
+                offset: offset,
+                function: program
+                    .and_then(|program| program.get_function_name(fn_uid?))
+                    .cloned(),
+                line: program
+                    .and_then(|program| program.get_position_at(instr_offset))
+                    .map(|position| position.line)
+            }
+        })
+        .collect()
+}
+
 impl Error for EngineError {
 }
 
@@ -84,48 +408,425 @@ impl Engine {
         let mut compiler = Compiler::new();
         Engine {
             core: Core::new(stack_size),
-            compiler: compiler
+            compiler: compiler,
+            packages: PackageResolver::new(DEFAULT_PACKAGES_ROOT),
+            sources: SourceResolver::new(DEFAULT_SOURCES_ROOT),
+            runtime_error_handler: None,
+            foreign_modules: Vec::new(),
+            foreign_containers: Vec::new(),
+            #[cfg(feature = "extensions")]
+            extensions: Vec::new()
         }
     }
 
+    /// Sets a callback invoked whenever running a script fails with a core error
+    pub fn on_runtime_error(&mut self, handler: RuntimeErrorHandler) {
+        self.runtime_error_handler = Some(handler);
+    }
+
+    /// Passes a core error raised while running to the runtime error callback,
+    /// and wraps it into an engine error
+    fn runtime_error(&self, error: CoreError) -> Box<EngineError> {
+        let handler = match &self.runtime_error_handler {
+            Some(handler) => handler,
+            None => return Box::new(EngineError::CoreError(error))
+        };
+
+        let stack_trace = self.get_stack_trace();
+        let info = RuntimeErrorInfo {
+            error: error,
+            ip: self.core.get_ip(),
+            function: stack_trace[0].function.clone(),
+            stack_trace: stack_trace
+        };
+        handler(&info);
+
+        Box::new(EngineError::CoreError(info.error))
+    }
+
+    /// Gets the script stack trace of the current run, innermost frame first
+    pub fn get_stack_trace(&self) -> Vec<StackFrame> {
+        get_core_stack_trace(&self.core)
+    }
+
+    /// Runs the main function of the loaded code, runtime errors carry the script stack trace
+    fn run_main(&mut self) -> EngineResult<()> {
+        self.run_fn("root::main")
+            .map_err(|error| {
+                match *error {
+                    EngineError::CoreError(core_error) => {
+                        Box::new(EngineError::RuntimeError(core_error, self.get_stack_trace()))
+                    },
+                    other => Box::new(other)
+                }
+            })
+    }
+
+    /// Disables the promotion of mixed numeric operands for code loaded afterwards.
+    /// By default, ints are promoted to floats and doubles, floats to doubles, e.g. in "1 + 2.5".
+    pub fn set_strict_numeric(&mut self, strict_numeric: bool) {
+        self.compiler.set_strict_numeric(strict_numeric);
+    }
+
+    /// Reference counts heap strings in code loaded afterwards, freeing them once the last local holding them
+    /// goes out of scope instead of keeping them for the lifetime of the engine.
+    /// String arguments pushed by the host are released by the called function.
+    pub fn set_ref_counting(&mut self, ref_counting: bool) {
+        self.compiler.set_ref_counting(ref_counting);
+    }
+
+    /// Fuses common instruction pairs of the code loaded next into superinstructions, enabled by default
+    pub fn set_instruction_fusion(&mut self, instruction_fusion: bool) {
+        self.compiler.set_instruction_fusion(instruction_fusion);
+    }
+
+    /// Sets the directory package imports are resolved in
+    pub fn set_packages_root<P: AsRef<Path>>(&mut self, path: P) {
+        self.packages.set_root(path);
+    }
+
+    /// Sets the directory file imports of code not loaded from a file are resolved in
+    pub fn set_sources_root<P: AsRef<Path>>(&mut self, path: P) {
+        self.sources.set_root(path);
+    }
+
     pub fn run_code(&mut self, code: &str) -> EngineResult<()> {
         self.load_code(code)?;
-        self.run_fn(&String::from("root::main"))
+        self.run_main()
+    }
+
+    /// Compiles source code from memory and runs its main function, like run_file() without the file
+    pub fn run_source(&mut self, source: &str) -> EngineResult<()> {
+        self.run_code(source)
+    }
+
+    /// Compiles source code from memory without loading it. The program can be loaded
+    /// into this engine, other engines with the same foreign modules, or run many times.
+    /// File imports are resolved in the sources root.
+    pub fn compile(&self, source: &str) -> EngineResult<Arc<Program>> {
+        let mut compiler = self.new_compiler(self.compiler.get_function_uids())?;
+        let program = Self::compile_code(&mut compiler, &self.packages, &self.sources, source, None)?;
+        Ok(Arc::new(program))
+    }
+
+    /// Loads a program returned by compile(), sharing it instead of copying it.
+    /// The statics start out with their initial values.
+    pub fn load_shared_program(&mut self, program: Arc<Program>) -> EngineResult<()> {
+        if self.core.is_running() {
+            return Err(Box::new(EngineError::ReloadWhileRunning));
+        }
+        // The compiler only knows the foreign functions then, the program carries the uids of its functions
+        self.compiler = self.new_compiler(&program.function_uids)?;
+        self.core.load_shared_program(program);
+        Ok(())
+    }
+
+    /// Loads a program returned by compile() and runs its main function
+    pub fn run_program(&mut self, program: Arc<Program>) -> EngineResult<()> {
+        self.load_shared_program(program)?;
+        self.run_main()
     }
 
     pub fn load_code(&mut self, code: &str) -> EngineResult<()> {
-        let parser = Parser::new(String::from(code));
-        let decl_list = parser.parse_root_decl_list()
-            .map_err(|p| {
-                let mut offset = 0;
-                let token_range = p.token_pos.clone();
-                let mut line_nr = 0;
-                for line in code.lines() {
-                    if offset <= token_range.start && offset + line.len() >= token_range.end {
-                        println!("Parse error in line #{} at offset {}", line_nr, token_range.start - offset);
+        let program = Self::compile_code(&mut self.compiler, &self.packages, &self.sources, code, None)?;
+        self.core.load_program(program);
+        Ok(())
+    }
+
+    /// Loads the code of a script file, its file imports are relative to the file
+    pub fn load_file(&mut self, path: &Path) -> EngineResult<()> {
+        let code = read_to_string(path)
+            .map_err(|_| Box::new(EngineError::SourceNotReadable(path.to_path_buf())))?;
+        let program = Self::compile_code(&mut self.compiler, &self.packages, &self.sources, &code, Some(path))?;
+        self.core.load_program(program);
+        Ok(())
+    }
+
+    /// Parses and compiles code, including the files and packages it imports.
+    /// The path is set if the code was read from a file.
+    fn compile_code(compiler: &mut Compiler, packages: &PackageResolver, sources: &SourceResolver, code: &str, code_path: Option<&Path>) -> EngineResult<Program> {
+        // Only the positions of the code itself are recorded, imported files have lines of their own
+        let parser = Parser::new(String::from(code))
+            .with_positions();
+        let mut decl_list = parser.parse_root_decl_list()
+            .map_err(|p| Box::new(EngineError::ParseError(p)))?;
+        match code_path {
+            Some(code_path) => sources.resolve_file(&mut decl_list, code_path)?,
+            None => sources.resolve(&mut decl_list)?
+        };
+        packages.resolve(&mut decl_list)?;
+        compiler.compile_root(&decl_list)
+            .map_err(|c| Box::new(EngineError::CompileError(c)))?;
+        compiler.get_program()
+            .map_err(|c| Box::new(EngineError::CompileError(c)))
+    }
+
+    /// Recompiles the script from a file and swaps it in, see reload_code()
+    pub fn reload_file(&mut self, path: &Path) -> EngineResult<ReloadReport> {
+        let code = read_to_string(path)
+            .map_err(|_| Box::new(EngineError::SourceNotReadable(path.to_path_buf())))?;
+        self.reload(&code, Some(path))
+    }
+
+    /// Recompiles the script and replaces the loaded program, e.g. between the ticks of a run.
+    /// Functions keep their uids, heap memory and foreign pointers are preserved.
+    /// Statics keep their values if their type did not change, others start out with their new initial value.
+    /// If the new code fails to compile, the old program stays loaded.
+    /// Frames running at the time of the reload, including suspended coroutines, finish in the old code,
+    /// the functions they call and the statics they keep are the new ones.
+    pub fn reload_code(&mut self, code: &str) -> EngineResult<ReloadReport> {
+        self.reload(code, None)
+    }
+
+    /// Gets the value of a module level variable of the loaded program, e.g. "difficulty" or "root::game::level"
+    pub fn get_global<T: ScriptGlobal>(&self, name: &str) -> EngineResult<T> {
+        let global_def = self.get_global_def::<T>(name)?;
+        T::read_global(&self.core, global_def.address)
+            .map_err(|c| Box::new(EngineError::CoreError(c)))
+    }
+
+    /// Sets a module level variable of the loaded program, scripts see the value in their next run.
+    /// The value stays until the program is loaded again, reloads keep it like every static.
+    pub fn set_global<T: ScriptGlobal>(&mut self, name: &str, value: T) -> EngineResult<()> {
+        let global_def = self.get_global_def::<T>(name)?;
+        if global_def.constant {
+            return Err(Box::new(EngineError::AssignToConstantGlobal(String::from(name))));
+        }
+        value.write_global(&mut self.core, global_def.address)
+            .map_err(|c| Box::new(EngineError::CoreError(c)))
+    }
+
+    /// Looks up a module level variable of the loaded program, checking it holds the given type
+    fn get_global_def<T: ScriptGlobal>(&self, name: &str) -> EngineResult<GlobalDef> {
+        let program = self.core.get_program()
+            .ok_or(EngineError::CoreError(CoreError::NoProgram))?;
+        let global_def = program.get_global(name)
+            .ok_or_else(|| Box::new(EngineError::UnknownGlobal(String::from(name))))?;
+        if !T::is_script_type(&global_def.var_type) {
+            return Err(Box::new(EngineError::GlobalTypeMismatch(String::from(name), global_def.var_type.clone())));
+        }
+        Ok(global_def.clone())
+    }
+
+    /// Recompiles and swaps in the script, the path is set if the code was read from a file
+    fn reload(&mut self, code: &str, code_path: Option<&Path>) -> EngineResult<ReloadReport> {
+        let compiler_fn_uids = self.compiler.get_function_uids().clone();
+        let mut compiler = self.new_compiler(&compiler_fn_uids)?;
+        let program = Self::compile_code(&mut compiler, &self.packages, &self.sources, code, code_path)?;
+
+        let old_fn_defs = self.compiler.get_function_defs()
+            .map_err(|ce| Box::new(EngineError::CompileError(ce)))?;
+        let new_fn_defs = compiler.get_function_defs()
+            .map_err(|ce| Box::new(EngineError::CompileError(ce)))?;
+
+        let mut report = ReloadReport::default();
+        for (fn_name, new_fn_def) in new_fn_defs.iter() {
+            match old_fn_defs.get(fn_name) {
+                Some(old_fn_def) => {
+                    let old_args = old_fn_def.arguments.iter().map(|(_, arg_type)| arg_type);
+                    let new_args = new_fn_def.arguments.iter().map(|(_, arg_type)| arg_type);
+                    if old_fn_def.ret_type == new_fn_def.ret_type && old_args.eq(new_args) {
+                        report.unchanged.push(fn_name.clone());
+                    } else {
+                        report.changed.push(fn_name.clone());
                     }
-                    offset += line.len();
-                    line_nr += 1;
+                },
+                None => report.added.push(fn_name.clone())
+            };
+        }
+        for fn_name in old_fn_defs.keys() {
+            if !new_fn_defs.contains_key(fn_name) {
+                report.removed.push(fn_name.clone());
+            }
+        }
+
+        let preserved_statics = self.get_preserved_statics(&compiler.get_static_defs()
+            .map_err(|ce| Box::new(EngineError::CompileError(ce)))?)?;
+
+        // Running frames return into the old code, which is kept in place in front of the new program
+        let keeps_frames = self.core.is_running() || self.core.get_coroutine_count() > 0;
+        let (program, data_offset) = match self.core.get_program() {
+            Some(old_program) if keeps_frames => {
+                let data = self.core.mem_get_bytes(0, old_program.data_size)
+                    .map_err(|c| Box::new(EngineError::CoreError(c)))?;
+                let moved_statics: Vec<(Range<u64>, u64)> = preserved_statics.iter()
+                    .map(|(_, preserved)| (preserved.old_address..preserved.old_address + preserved.bytes.len() as u64, preserved.address))
+                    .collect();
+                let data_offset = old_program.code.len() as u64;
+                let program = old_program.append_reloaded(data, &program, &moved_statics)
+                    .map_err(|pe| Box::new(EngineError::ProgramError(pe)))?;
+                (program, data_offset)
+            },
+            _ => (program, 0)
+        };
+
+        self.compiler = compiler;
+        self.core.load_program(program);
+
+        for (static_name, mut preserved) in preserved_statics {
+            // Literals of the old program are gone, its strings are moved onto the heap
+            for (string_offset, string_bytes) in preserved.strings.iter() {
+                let string_addr = self.core.heap_alloc(string_bytes)
+                    .map_err(|c| Box::new(EngineError::CoreError(c)))?;
+                preserved.bytes[string_offset + 8..string_offset + 16].copy_from_slice(&string_addr.to_le_bytes());
+            }
+            self.core.mem_set_bytes(preserved.address + data_offset, &preserved.bytes)
+                .map_err(|c| Box::new(EngineError::CoreError(c)))?;
+            report.preserved.push(static_name);
+        }
+
+        Ok(report)
+    }
+
+    /// Reads the values of the loaded statics that keep their type in the given new statics
+    fn get_preserved_statics(&self, new_static_defs: &BTreeMap<String, StaticDef>) -> EngineResult<Vec<(String, PreservedStatic)>> {
+        // The statics of the loaded program, whose addresses moved if it was reloaded while running
+        let old_static_defs = match self.core.get_program() {
+            Some(program) => &program.globals,
+            None => return Ok(Vec::new())
+        };
+        let mut preserved_statics = Vec::new();
+        for (static_name, new_static_def) in new_static_defs.iter() {
+            if new_static_def.constant {
+                continue;
+            }
+            let old_static_def = match old_static_defs.get(static_name) {
+                Some(old_static_def) if old_static_def.var_type == new_static_def.var_type => old_static_def,
+                _ => continue
+            };
+            let size = self.compiler.get_size_of_type(&old_static_def.var_type)
+                .map_err(|ce| Box::new(EngineError::CompileError(ce)))?;
+            let bytes = self.core.mem_get_bytes(old_static_def.address, size)
+                .map_err(|c| Box::new(EngineError::CoreError(c)))?
+                .to_vec();
+            // Strings nested in the value are moved as well, e.g. the string members of a container
+            let string_offsets = self.compiler.get_all_string_offsets(&old_static_def.var_type)
+                .map_err(|ce| Box::new(EngineError::CompileError(ce)))?;
+            let mut strings = Vec::new();
+            for string_offset in string_offsets {
+                let string_size: u64 = deserialize(&bytes[string_offset..string_offset + 8])
+                    .map_err(|_| Box::new(EngineError::CoreError(CoreError::OperatorDeserialize)))?;
+                let string_addr: u64 = deserialize(&bytes[string_offset + 8..string_offset + 16])
+                    .map_err(|_| Box::new(EngineError::CoreError(CoreError::OperatorDeserialize)))?;
+                // Empty strings read nothing, e.g. the message of a result without an error
+                if string_size == 0 || Address::is_heap(string_addr) {
+                    continue;
                 }
-                Box::new(EngineError::ParseError(p))
-            })?;
-        self.compiler.compile_root(&decl_list)
-            .map_err(|c| Box::new(EngineError::CompileError(c)))?;
-        let program = self.compiler.get_program()
-            .map_err(|c| Box::new(EngineError::CompileError(c)))?;
+                let string_bytes = self.core.mem_get_bytes(string_addr, string_size as usize)
+                    .map_err(|c| Box::new(EngineError::CoreError(c)))?;
+                strings.push((string_offset, string_bytes.to_vec()));
+            }
+            preserved_statics.push((static_name.clone(), PreservedStatic {
+                old_address: old_static_def.address,
+                address: new_static_def.address,
+                bytes: bytes,
+                strings: strings
+            }));
+        }
+        Ok(preserved_statics)
+    }
+
+    /// Creates a compiler with the settings and foreign modules of this engine,
+    /// reusing the given function uids
+    fn new_compiler(&self, fn_uids: &HashMap<String, u64>) -> EngineResult<Compiler> {
+        let mut compiler = Compiler::new();
+        compiler.set_strict_numeric(self.compiler.is_strict_numeric());
+        compiler.set_ref_counting(self.compiler.is_ref_counting());
+        compiler.set_instruction_fusion(self.compiler.is_instruction_fusion());
+        compiler.reserve_function_uids(fn_uids);
+        for module in self.foreign_modules.iter() {
+            compiler.register_foreign_root_module(module.clone())
+                .map_err(|ce| Box::new(EngineError::CompileError(ce)))?;
+        }
+        for container in self.foreign_containers.iter() {
+            compiler.register_foreign_root_container(container.clone())
+                .map_err(|ce| Box::new(EngineError::CompileError(ce)))?;
+        }
+        Ok(compiler)
+    }
+
+    /// Writes the loaded program to a bytecode file, which load_program() runs without compiling it again
+    pub fn save_program(&self, path: &Path) -> EngineResult<()> {
+        let program = self.core.get_program()
+            .ok_or(EngineError::CoreError(CoreError::NoProgram))?;
+        program.save(path)
+            .map_err(|pe| Box::new(EngineError::ProgramError(pe)))
+    }
+
+    /// Loads a bytecode file written by save_program(), after verifying it.
+    /// Its foreign functions are bound by name to the modules and containers registered with this engine.
+    pub fn load_program(&mut self, path: &Path) -> EngineResult<()> {
+        let mut program = Program::load(path)
+            .map_err(|pe| Box::new(EngineError::ProgramError(pe)))?;
+        let compiler = self.bind_foreign_functions(&mut program)?;
+        verify(&program)
+            .map_err(|ve| Box::new(EngineError::VerifyError(ve)))?;
+
+        self.compiler = compiler;
         self.core.load_program(program);
         Ok(())
     }
 
-    pub fn run_file(&mut self, path: &Path) -> EngineResult<()> {
-        let mut file = File::open(path)
-            .map_err(|_| Box::new(EngineError::Unknown))?;
+    /// Binds the foreign functions of a program without a closure by name to the modules and containers
+    /// registered with this engine. Returns the compiler knowing the foreign functions under the uids of the program.
+    fn bind_foreign_functions(&self, program: &mut Program) -> EngineResult<Compiler> {
+        // Registering the foreign modules with the uids of the program yields their functions under these uids
+        let mut compiler = self.new_compiler(&program.function_uids)?;
+        let foreign_program = compiler.get_program()
+            .map_err(|ce| Box::new(EngineError::CompileError(ce)))?;
+        for (fn_name, fn_uid) in program.function_uids.iter() {
+            let is_bound = program.functions.contains_key(fn_uid) || program.foreign_functions.get(fn_uid)
+                .map(|function| function.has_closure())
+                .unwrap_or(false);
+            if is_bound {
+                continue;
+            }
+            // Imports registered with the engine have no closure either
+            let function = foreign_program.foreign_functions.get(fn_uid)
+                .filter(|function| function.has_closure())
+                .ok_or_else(|| Box::new(EngineError::UnboundForeignFunction(fn_name.clone())))?;
+            program.foreign_functions.insert(*fn_uid, function.clone());
+        }
+        Ok(compiler)
+    }
 
-        let mut file_content = String::new();
-        file.read_to_string(&mut file_content)
-            .map_err(|_| Box::new(EngineError::Unknown))?;
+    /// Links programs returned by compile() into one, which is loaded with load_shared_program(), see Program::link().
+    /// Foreign functions no program defines and that have no closure are bound to the modules of this engine.
+    pub fn link(&self, programs: &[Arc<Program>]) -> EngineResult<Arc<Program>> {
+        let programs: Vec<&Program> = programs.iter()
+            .map(|program| program.as_ref())
+            .collect();
+        let mut program = Program::link(&programs)
+            .map_err(|pe| Box::new(EngineError::ProgramError(pe)))?;
+        self.bind_foreign_functions(&mut program)?;
+        verify(&program)
+            .map_err(|ve| Box::new(EngineError::VerifyError(ve)))?;
+        Ok(Arc::new(program))
+    }
 
-        self.run_code(&file_content)
+    /// Loads a bytecode file written by save_program() and runs its main function
+    pub fn run_bytecode_file(&mut self, path: &Path) -> EngineResult<()> {
+        self.load_program(path)?;
+        self.run_main()
+    }
+
+    /// Gets the uid of a function by full name, preferring the loaded program
+    /// as it may have been loaded from a bytecode file
+    fn get_function_uid(&self, name: &String) -> EngineResult<u64> {
+        let program_uid = self.core.get_program()
+            .and_then(|program| program.function_uids.get(name));
+        match program_uid {
+            Some(fn_uid) => Ok(*fn_uid),
+            None => {
+                self.compiler.get_function_uid(name)
+                    .map_err(|ce| Box::new(EngineError::CompileError(ce)))
+            }
+        }
+    }
+
+    pub fn run_file(&mut self, path: &Path) -> EngineResult<()> {
+        self.load_file(path)?;
+        self.run_main()
     }
 
     pub fn run_stream(&mut self, readable: Box<dyn Read>) -> EngineResult<()> {
@@ -142,9 +843,9 @@ impl Engine {
             .map_err(|c| Box::new(EngineError::CoreError(c)))
     }
 
-    pub fn get_register_value<T>(&mut self, reg: Register) -> EngineResult<T>
+    pub fn get_register_value<T>(&self, reg: Register) -> EngineResult<T>
         where RegisterUnion: RegisterAccess<T> {
-        let val = self.core.reg(reg.into())
+        let val = self.core.get_register(reg.into())
             .map_err(|ce| EngineError::CoreError(ce))?
             .get::<T>();
         Ok(val)
@@ -157,14 +858,339 @@ impl Engine {
     pub fn run_fn<T>(&mut self, name: T) -> EngineResult<()>
         where String: From<T> {
         let name = String::from(name);
-        let fn_uid = self.compiler.get_function_uid(&name)
-            .map_err(|ce| EngineError::CompileError(ce))?;
+        let fn_uid = self.get_function_uid(&name)?;
         self.core.run_fn(fn_uid)
+            .map_err(|c| self.runtime_error(c))
+    }
+
+    /// Calls a function by full name with the given arguments, returns its return value, e.g.
+    /// engine.call_function::<(i64, f32), i64>("root::update", (tick, dt)).
+    /// The arguments and the return value are register types like i64 or PgsValue types, references
+    /// to PgsValue types are read back after the call. They are checked against the signature of
+    /// functions compiled by this engine. The stack is left as it was before the call.
+    pub fn call_function<A, R>(&mut self, name: &str, mut args: A) -> EngineResult<R>
+        where A: ScriptArgs, R: ScriptReturn {
+        let name = String::from(name);
+        let fn_uid = self.get_function_uid(&name)?;
+        if let Ok(fn_def) = self.compiler.get_function_def(&name) {
+            let arg_types: Vec<Type> = fn_def.arguments.iter()
+                .map(|(_, arg_type)| arg_type.clone())
+                .collect();
+            if !A::is_script_types(&arg_types) || !R::is_script_type(&fn_def.ret_type) {
+                return Err(Box::new(EngineError::SignatureMismatch(name)));
+            }
+        }
+
+        let stack_size = self.core.get_stack_size();
+        let referenced_addrs = args.push_args(&mut self.core)
+            .map_err(|c| Box::new(EngineError::CoreError(c)))?;
+        self.core.run_fn(fn_uid)
+            .map_err(|c| self.runtime_error(c))?;
+        args.read_back(&self.core, &referenced_addrs)
+            .map_err(|c| Box::new(EngineError::CoreError(c)))?;
+        let ret = R::get_return(&self.core)
+            .map_err(|c| Box::new(EngineError::CoreError(c)))?;
+        self.core.truncate_stack(stack_size)
+            .map_err(|c| Box::new(EngineError::CoreError(c)))?;
+        Ok(ret)
+    }
+
+    /// Prepares running a function instruction by instruction with step()
+    pub fn start_fn<T>(&mut self, name: T) -> EngineResult<()>
+        where String: From<T> {
+        let name = String::from(name);
+        let fn_uid = self.get_function_uid(&name)?;
+        self.core.start_fn(fn_uid)
             .map_err(|c| Box::new(EngineError::CoreError(c)))
     }
 
-    pub fn register_module(&mut self, module: Module) -> EngineResult<()> {
-        self.compiler.register_foreign_root_module(module)
+    /// Executes a single instruction, returns false once the program has finished
+    pub fn step(&mut self) -> EngineResult<bool> {
+        self.core.step()
+            .map_err(|c| self.runtime_error(c))
+    }
+
+    /// Executes at most budget instructions of the function started with start_fn().
+    /// Meant to be called once per frame of a game loop.
+    pub fn tick(&mut self, budget: u64) -> EngineResult<TickState> {
+        self.core.tick(budget)
+            .map_err(|c| self.runtime_error(c))
+    }
+
+    /// Sets the number of instructions left to execute across runs, None removes the limit.
+    /// Running out of it fails with CoreError::OutOfFuel.
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.core.set_fuel(fuel);
+    }
+
+    /// Gets the fuel left
+    pub fn get_fuel(&self) -> Option<u64> {
+        self.core.get_fuel()
+    }
+
+    /// Enables or disables counting executions and time per opcode and function, see Engine::get_profile_report()
+    pub fn set_profiling(&mut self, profiling: bool) {
+        self.core.set_profiling(profiling);
+    }
+
+    /// Gets where the scripts spent their time since profiling was enabled, None if it is disabled
+    pub fn get_profile_report(&self) -> Option<ProfileReport> {
+        self.core.profile_report()
+    }
+
+    /// Gets a handle to pause, resume or cancel the running script from another thread.
+    /// A cancelled script fails with CoreError::Cancelled.
+    pub fn get_handle(&self) -> CoreHandle {
+        self.core.get_handle()
+    }
+
+    /// Sets the number of calls after which a script function is compiled to native code, None disables the jit.
+    /// Native code does not count instructions, it is not used while fuel or an instruction limit is set.
+    #[cfg(feature = "jit")]
+    pub fn set_jit_threshold(&mut self, jit_threshold: Option<u64>) {
+        self.core.set_jit_threshold(jit_threshold);
+    }
+
+    /// Gets the number of functions compiled to native code
+    #[cfg(feature = "jit")]
+    pub fn get_jit_compiled_count(&self) -> usize {
+        self.core.get_jit_compiled_count()
+    }
+
+    /// Sets the number of instructions a spawned task runs before the next one gets its turn
+    pub fn set_task_slice(&mut self, task_slice: u64) {
+        self.core.set_task_slice(task_slice);
+    }
+
+    /// Sets the stack size of spawned tasks, it defaults to the stack size of the engine
+    pub fn set_task_stack_size(&mut self, task_stack_size: usize) {
+        self.core.set_task_stack_size(task_stack_size);
+    }
+
+    /// Gets the number of spawned tasks that have not finished
+    pub fn get_task_count(&self) -> usize {
+        self.core.get_task_count()
+    }
+
+    /// Creates an isolate of the loaded program, which runs independently of this engine
+    pub fn create_isolate(&self) -> EngineResult<Isolate> {
+        Isolate::new(&self.core)
+    }
+
+    /// Creates a pool of isolates of the loaded program, keeping up to size of them around
+    pub fn create_pool(&self, size: usize) -> EngineResult<IsolatePool> {
+        IsolatePool::new(self.create_isolate()?, size)
+    }
+
+    /// Gets the number of coroutines that have not finished
+    pub fn get_coroutine_count(&self) -> usize {
+        self.core.get_coroutine_count()
+    }
+
+    /// Creates a coroutine running a script function returning "co<T>", without arguments.
+    /// Returns its handle for resume_coroutine().
+    pub fn create_coroutine<T>(&mut self, name: T) -> EngineResult<u64>
+        where String: From<T> {
+        let name = String::from(name);
+        let fn_uid = self.get_function_uid(&name)?;
+        self.core.new_coroutine(fn_uid, 0)
+            .map_err(|c| Box::new(EngineError::CoreError(c)))
+    }
+
+    /// Resumes a coroutine by its handle, created by the host or by a script.
+    /// Returns the yielded value, or None once the coroutine finished. Only register types like i64 are supported.
+    pub fn resume_coroutine<T: DeserializeOwned>(&mut self, handle: u64) -> EngineResult<Option<T>> {
+        let value = self.core.resume_coroutine(handle, size_of::<T>())
+            .map_err(|c| self.runtime_error(c))?;
+        match value {
+            Some(bytes) => {
+                let value = deserialize(&bytes)
+                    .map_err(|_| Box::new(EngineError::CoreError(CoreError::OperatorDeserialize)))?;
+                Ok(Some(value))
+            },
+            None => Ok(None)
+        }
+    }
+
+    /// Adds fuel and continues a run that ran out of it
+    pub fn resume(&mut self, fuel: u64) -> EngineResult<()> {
+        self.core.resume(fuel)
+            .map_err(|c| self.runtime_error(c))
+    }
+
+    /// Gets the execution state
+    pub fn get_run_state(&self) -> RunState {
+        self.core.get_run_state()
+    }
+
+    /// Pauses run_to_breakpoint() before the instruction at the given code offset,
+    /// see DebugInfo::get_line_offset() for the offset of a line
+    pub fn add_breakpoint(&mut self, offset: usize) {
+        self.core.add_breakpoint(offset);
+    }
+
+    /// Removes the breakpoint at the given code offset, returns false if there was none
+    pub fn remove_breakpoint(&mut self, offset: usize) -> bool {
+        self.core.remove_breakpoint(offset)
+    }
+
+    /// Removes all breakpoints
+    pub fn clear_breakpoints(&mut self) {
+        self.core.clear_breakpoints();
+    }
+
+    /// Continues the function started with start_fn() until it reaches a breakpoint or finishes
+    pub fn run_to_breakpoint(&mut self) -> EngineResult<RunState> {
+        self.core.run_to_breakpoint()
+            .map_err(|c| self.runtime_error(c))
+    }
+
+    /// Sets a callback invoked after every executed instruction, None disables tracing
+    pub fn set_trace_hook(&mut self, trace_hook: Option<TraceHook>) {
+        self.core.set_trace_hook(trace_hook);
+    }
+
+    /// Writes a line per executed instruction to the given sink
+    pub fn trace_to<W: Write + Send + 'static>(&mut self, writer: W) {
+        self.core.trace_to(writer);
+    }
+
+    /// Gets the current instruction pointer
+    pub fn get_ip(&self) -> usize {
+        self.core.get_ip()
+    }
+
+    /// Gets the return addresses on the call stack, innermost first
+    pub fn get_call_stack(&self) -> Vec<usize> {
+        self.core.get_call_stack()
+    }
+
+    /// Gets the debug info of the loaded code
+    pub fn get_debug_info(&self) -> EngineResult<DebugInfo> {
+        self.compiler.get_debug_info()
             .map_err(|ce| Box::new(EngineError::CompileError(ce)))
     }
+
+    /// Reads a value on the stack, relative to the stack pointer
+    pub fn get_stack_value<T: DeserializeOwned>(&self, sp_offset: i64) -> EngineResult<T> {
+        self.core.get_stack_value(sp_offset as i16)
+            .map_err(|c| Box::new(EngineError::CoreError(c)))
+    }
+
+    /// Reads a string on the stack, relative to the stack pointer
+    pub fn get_stack_string(&self, sp_offset: i64) -> EngineResult<String> {
+        self.core.get_stack_string(sp_offset as i16)
+            .map_err(|c| Box::new(EngineError::CoreError(c)))
+    }
+
+    /// Gets the execution counters of the core
+    pub fn get_stats(&self) -> &CoreStats {
+        self.core.get_stats()
+    }
+
+    /// Resets the execution counters of the core
+    pub fn reset_stats(&mut self) {
+        self.core.reset_stats();
+    }
+
+    pub fn register_module(&mut self, module: Module) -> EngineResult<()> {
+        self.compiler.register_foreign_root_module(module.clone())
+            .map_err(|ce| Box::new(EngineError::CompileError(ce)))?;
+        self.foreign_modules.push(module);
+        Ok(())
+    }
+
+    /// Registers a Rust type as a container in the root module
+    pub fn register_container<T: PgsContainer>(&mut self) -> EngineResult<()> {
+        let container = T::container();
+        self.compiler.register_foreign_root_container(container.clone())
+            .map_err(|ce| Box::new(EngineError::CompileError(ce)))?;
+        self.foreign_containers.push(container);
+        Ok(())
+    }
+
+    /// Registers a Rust type passed by value as a container in the root module
+    pub fn register_value<T: PgsValue>(&mut self) -> EngineResult<()> {
+        let container = T::value_container();
+        self.compiler.register_foreign_root_container(container.clone())
+            .map_err(|ce| Box::new(EngineError::CompileError(ce)))?;
+        self.foreign_containers.push(container);
+        Ok(())
+    }
+
+    /// Registers a host type scripts call methods on through handles, as a container in the root module
+    pub fn register_adapter<T: ObjectAdapter>(&mut self) -> EngineResult<()> {
+        let container = adapter_container::<T>();
+        self.compiler.register_foreign_root_container(container.clone())
+            .map_err(|ce| Box::new(EngineError::CompileError(ce)))?;
+        self.foreign_containers.push(container);
+        Ok(())
+    }
+
+    /// Loads an extension library and calls its register function, e.g. the cdylib of pgs-std.
+    /// The library stays open as long as the engine and the isolates created from it.
+    /// Loading an already loaded library again does nothing. If registering fails, the modules
+    /// it registered are removed again and the library is closed.
+    #[cfg(feature = "extensions")]
+    pub fn load_extension<P: AsRef<Path>>(&mut self, path: P) -> EngineResult<()> {
+        let path = path.as_ref();
+        let loaded = self.extensions.iter()
+            .any(|extension| extension.get_path() == path);
+        if loaded {
+            return Ok(());
+        }
+        let extension = Arc::new(Extension::open(path)?);
+        let module_count = self.foreign_modules.len();
+        let container_count = self.foreign_containers.len();
+        if let Err(error) = extension.register(self) {
+            // The functions registered before the failure live in the library, they are dropped before it
+            self.foreign_modules.truncate(module_count);
+            self.foreign_containers.truncate(container_count);
+            let fn_uids = self.compiler.get_function_uids().clone();
+            self.compiler = self.new_compiler(&fn_uids)?;
+            return Err(error);
+        }
+        self.extensions.push(extension.clone());
+        self.core.add_extension(extension);
+        Ok(())
+    }
+
+    /// Gets the loaded extension libraries, in load order
+    #[cfg(feature = "extensions")]
+    pub fn get_extensions(&self) -> &[Arc<Extension>] {
+        &self.extensions
+    }
+
+    /// Shares an item with scripts, returns the handle scripts access it by
+    pub fn insert_foreign_ptr<T>(&mut self, item: Arc<Mutex<T>>) -> EngineResult<u64> {
+        self.core.insert_foreign_ptr(item)
+            .map_err(|c| Box::new(EngineError::CoreError(c)))
+    }
+
+    /// Stops sharing an item with scripts
+    pub fn remove_foreign_ptr<T>(&mut self, handle: u64) -> EngineResult<Arc<Mutex<T>>> {
+        self.core.remove_foreign_ptr(handle)
+            .map_err(|c| Box::new(EngineError::CoreError(c)))
+    }
+
+    /// Sets the context of its type foreign functions can borrow, see Function::wrap_with_context().
+    /// Returns the context of the same type it replaces. Isolates do not share the contexts of the engine.
+    pub fn set_context<T: Any + Send>(&mut self, context: T) -> Option<T> {
+        self.core.set_context(context)
+    }
+
+    /// Gets the context of the given type
+    pub fn get_context<T: Any + Send>(&self) -> Option<&T> {
+        self.core.get_context::<T>()
+    }
+
+    /// Gets the context of the given type for modification, e.g. to read the game state after a run
+    pub fn get_context_mut<T: Any + Send>(&mut self) -> Option<&mut T> {
+        self.core.get_context_mut::<T>()
+    }
+
+    /// Removes the context of the given type
+    pub fn take_context<T: Any + Send>(&mut self) -> Option<T> {
+        self.core.take_context::<T>()
+    }
 }
\ No newline at end of file
@@ -0,0 +1,64 @@
+use crate::{
+    engine::{
+        Engine,
+        EngineError,
+        EngineResult
+    }
+};
+
+use std::{
+    path::{
+        Path,
+        PathBuf
+    }
+};
+
+use libloading::{
+    Library,
+    Symbol
+};
+
+/// Name of the function an extension library exports to register its modules, e.g. pgs-std
+pub const REGISTER_EXTENSION_SYMBOL: &str = "register_extension";
+
+/// Signature of the register function, exported as `#[no_mangle] pub extern fn register_extension`.
+/// Engine and error are passed as Rust types, so extensions have to be built with the same compiler and pgs version.
+pub type RegisterExtensionFn = unsafe extern "C" fn(&mut Engine) -> EngineResult<()>;
+
+/// A dynamic library registering foreign functions with an engine, see Engine::load_extension().
+///
+/// The closures of the functions live in the library, so it stays open while the engine,
+/// or any isolate created from it, may call or drop them.
+pub struct Extension {
+    path: PathBuf,
+    library: Library
+}
+
+impl Extension {
+    /// Opens the library at the given path
+    pub fn open<P: AsRef<Path>>(path: P) -> EngineResult<Extension> {
+        let path = path.as_ref().to_path_buf();
+        // Loading runs the initializers of the library, which is trusted like any other host code
+        let library = unsafe { Library::new(&path) }
+            .map_err(|error| Box::new(EngineError::ExtensionNotLoadable(path.clone(), error.to_string())))?;
+        Ok(
+            Extension {
+                path: path,
+                library: library
+            }
+        )
+    }
+
+    /// Calls the register function of the library
+    pub fn register(&self, engine: &mut Engine) -> EngineResult<()> {
+        let register_fn: Symbol<RegisterExtensionFn> = unsafe {
+            self.library.get(REGISTER_EXTENSION_SYMBOL.as_bytes())
+        }.map_err(|_| Box::new(EngineError::ExtensionEntryNotFound(self.path.clone())))?;
+        unsafe { register_fn(engine) }
+    }
+
+    /// Gets the path the library was opened from
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+}
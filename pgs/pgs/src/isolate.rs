@@ -0,0 +1,233 @@
+use crate::{
+    vm::{
+        core::{
+            Core,
+            CoreStats
+        },
+        interrupt::{
+            CoreHandle
+        },
+        register::{
+            RegisterAccess,
+            Register as RegisterUnion
+        }
+    },
+    codegen::{
+        compiler::CompilerError,
+        register::Register
+    },
+    api::{
+        call::{
+            ScriptArgs,
+            ScriptReturn
+        }
+    },
+    engine::{
+        get_core_stack_trace,
+        EngineError,
+        EngineResult,
+        StackFrame
+    }
+};
+
+use std::{
+    sync::Mutex,
+    any::Any
+};
+
+use serde::{
+    de::DeserializeOwned,
+    Serialize
+};
+
+/// An independent instance of the program loaded by an engine, created with Engine::create_isolate().
+///
+/// Isolates share the compiled code with the engine, but have a stack, heap and statics of their own.
+/// They can be moved to other threads, e.g. to run a script per request of a server.
+/// Foreign functions are shared as well, each one is only run by a single isolate at a time.
+pub struct Isolate {
+    core: Core
+}
+
+impl Isolate {
+    /// Creates an isolate sharing the program loaded into the given core
+    pub(crate) fn new(core: &Core) -> EngineResult<Isolate> {
+        let core = core.new_isolate()
+            .map_err(|c| Box::new(EngineError::CoreError(c)))?;
+        Ok(
+            Isolate {
+                core: core
+            }
+        )
+    }
+
+    /// Runs a function by full name, e.g. "root::main"
+    pub fn run_fn<T>(&mut self, name: T) -> EngineResult<()>
+        where String: From<T> {
+        let name = String::from(name);
+        let fn_uid = self.get_function_uid(&name)?;
+        self.core.run_fn(fn_uid)
+            .map_err(|c| Box::new(EngineError::CoreError(c)))
+    }
+
+    /// Calls a function by full name with the given arguments, returns its return value.
+    /// Unlike Engine::call_function(), the signature is not checked.
+    pub fn call_function<A, R>(&mut self, name: &str, mut args: A) -> EngineResult<R>
+        where A: ScriptArgs, R: ScriptReturn {
+        let fn_uid = self.get_function_uid(name)?;
+        let stack_size = self.core.get_stack_size();
+        let referenced_addrs = args.push_args(&mut self.core)
+            .map_err(|c| Box::new(EngineError::CoreError(c)))?;
+        self.core.run_fn(fn_uid)
+            .map_err(|c| Box::new(EngineError::CoreError(c)))?;
+        args.read_back(&self.core, &referenced_addrs)
+            .map_err(|c| Box::new(EngineError::CoreError(c)))?;
+        let ret = R::get_return(&self.core)
+            .map_err(|c| Box::new(EngineError::CoreError(c)))?;
+        self.core.truncate_stack(stack_size)
+            .map_err(|c| Box::new(EngineError::CoreError(c)))?;
+        Ok(ret)
+    }
+
+    fn get_function_uid(&self, name: &str) -> EngineResult<u64> {
+        self.core.get_program()
+            .and_then(|program| program.function_uids.get(name))
+            .cloned()
+            .ok_or_else(|| Box::new(EngineError::CompileError(CompilerError::UnknownFunction(name.to_string()))))
+    }
+
+    pub fn push_stack<T: Serialize>(&mut self, item: T) -> EngineResult<()> {
+        self.core.push_stack(item)
+            .map_err(|c| Box::new(EngineError::CoreError(c)))
+    }
+
+    pub fn pop_stack<T: DeserializeOwned>(&mut self) -> EngineResult<T> {
+        self.core.pop_stack()
+            .map_err(|c| Box::new(EngineError::CoreError(c)))
+    }
+
+    pub fn get_register_value<T>(&self, reg: Register) -> EngineResult<T>
+        where RegisterUnion: RegisterAccess<T> {
+        let val = self.core.get_register(reg.into())
+            .map_err(EngineError::CoreError)?
+            .get::<T>();
+        Ok(val)
+    }
+
+    /// Sets the number of instructions left to execute across runs, None removes the limit
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.core.set_fuel(fuel);
+    }
+
+    /// Gets the number of instructions left to execute, None if unlimited
+    pub fn get_fuel(&self) -> Option<u64> {
+        self.core.get_fuel()
+    }
+
+    /// Gets the script stack trace of the current run, innermost frame first
+    pub fn get_stack_trace(&self) -> Vec<StackFrame> {
+        get_core_stack_trace(&self.core)
+    }
+
+    /// Gets the execution counters of this isolate
+    pub fn get_stats(&self) -> &CoreStats {
+        self.core.get_stats()
+    }
+
+    /// Gets a handle to pause, resume or cancel the running script from another thread.
+    /// Handles obtained before a reset do not reach the reset isolate.
+    pub fn get_handle(&self) -> CoreHandle {
+        self.core.get_handle()
+    }
+
+    /// Sets the context of its type foreign functions can borrow, see Engine::set_context()
+    pub fn set_context<T: Any + Send>(&mut self, context: T) -> Option<T> {
+        self.core.set_context(context)
+    }
+
+    /// Gets the context of the given type for modification
+    pub fn get_context_mut<T: Any + Send>(&mut self) -> Option<&mut T> {
+        self.core.get_context_mut::<T>()
+    }
+
+    /// Removes the context of the given type
+    pub fn take_context<T: Any + Send>(&mut self) -> Option<T> {
+        self.core.take_context::<T>()
+    }
+
+    /// Drops the stack, heap and statics, the isolate is then like a newly created one.
+    /// Contexts are kept.
+    pub fn reset(&mut self) -> EngineResult<()> {
+        let mut core = self.core.new_isolate()
+            .map_err(|c| Box::new(EngineError::CoreError(c)))?;
+        for (_, context) in self.core.take_contexts() {
+            core.set_context_box(context);
+        }
+        self.core = core;
+        Ok(())
+    }
+}
+
+/// Hands out isolates of one program to the threads of a server, created with Engine::create_pool().
+/// Released isolates are reset and reused, up to the size of the pool.
+pub struct IsolatePool {
+    /// Isolate new ones are created from, it never runs
+    template: Mutex<Isolate>,
+    idle: Mutex<Vec<Isolate>>,
+    size: usize
+}
+
+impl IsolatePool {
+    /// Creates a pool keeping up to size idle isolates, created from the given one
+    pub(crate) fn new(template: Isolate, size: usize) -> EngineResult<IsolatePool> {
+        let mut idle = Vec::new();
+        for _ in 0..size {
+            idle.push(Isolate::new(&template.core)?);
+        }
+        Ok(
+            IsolatePool {
+                template: Mutex::new(template),
+                idle: Mutex::new(idle),
+                size: size
+            }
+        )
+    }
+
+    /// Takes an idle isolate, or creates a new one if all of them are in use
+    pub fn acquire(&self) -> EngineResult<Isolate> {
+        let isolate = self.idle.lock()
+            .map_err(|_| Box::new(EngineError::Unknown))?
+            .pop();
+        match isolate {
+            Some(isolate) => Ok(isolate),
+            None => {
+                let template = self.template.lock()
+                    .map_err(|_| Box::new(EngineError::Unknown))?;
+                Isolate::new(&template.core)
+            }
+        }
+    }
+
+    /// Resets an isolate and returns it to the pool, it is dropped if the pool is full
+    pub fn release(&self, mut isolate: Isolate) -> EngineResult<()> {
+        isolate.reset()?;
+        let mut idle = self.idle.lock()
+            .map_err(|_| Box::new(EngineError::Unknown))?;
+        if idle.len() < self.size {
+            idle.push(isolate);
+        }
+        Ok(())
+    }
+
+    /// Gets the number of idle isolates
+    pub fn get_idle_count(&self) -> usize {
+        self.idle.lock()
+            .map(|idle| idle.len())
+            .unwrap_or(0)
+    }
+
+    /// Gets the maximum number of idle isolates kept
+    pub fn get_size(&self) -> usize {
+        self.size
+    }
+}
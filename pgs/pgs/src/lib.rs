@@ -5,6 +5,10 @@ extern crate rand;
 #[macro_use] extern crate memoffset;
 extern crate enum_primitive_derive as epd;
 extern crate num_traits;
+#[cfg(feature = "derive")]
+extern crate pgs_derive;
+#[cfg(feature = "extensions")]
+extern crate libloading;
 
 pub mod parser;
 
@@ -14,4 +18,13 @@ pub mod codegen;
 
 pub mod engine;
 
-pub mod api;
\ No newline at end of file
+pub mod isolate;
+
+pub mod api;
+
+pub mod package;
+
+pub mod source;
+
+#[cfg(feature = "extensions")]
+pub mod extension;
\ No newline at end of file
@@ -0,0 +1,196 @@
+use crate::{
+    parser::{
+        parser::Parser,
+        ast::Declaration
+    },
+    engine::{
+        EngineError,
+        EngineResult
+    }
+};
+
+use std::{
+    path::{
+        Path,
+        PathBuf
+    },
+    fs::read_to_string,
+    collections::{
+        HashSet,
+        VecDeque
+    }
+};
+
+/// Default directory packages are looked up in
+pub const DEFAULT_PACKAGES_ROOT: &str = "pgs_modules";
+
+/// Resolves package-style imports to script files in a packages root.
+///
+/// `import extlib::json;` resolves to `<root>/extlib/json.pgs`, which is
+/// compiled as the module `root::extlib::json`. Every package gets its own
+/// module in the root, so its symbols never collide with the script's.
+/// Declaring a module at the path of a package fails with EngineError::PackageModuleCollision.
+pub struct PackageResolver {
+    root: PathBuf
+}
+
+impl PackageResolver {
+    /// Creates a new resolver for the given packages root
+    pub fn new<P: AsRef<Path>>(root: P) -> PackageResolver {
+        PackageResolver {
+            root: root.as_ref().to_path_buf()
+        }
+    }
+
+    /// Gets the packages root
+    pub fn get_root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Sets the packages root
+    pub fn set_root<P: AsRef<Path>>(&mut self, root: P) {
+        self.root = root.as_ref().to_path_buf();
+    }
+
+    /// Loads all packages imported by the given declaration list (and by the packages themselves),
+    /// and appends them as modules to the declaration list.
+    pub fn resolve(&self, decl_list: &mut Vec<Declaration>) -> EngineResult<()> {
+        let mut loaded = HashSet::new();
+        let mut import_queue = VecDeque::new();
+        Self::collect_imports(decl_list, &mut import_queue);
+
+        let mut package_decls = Vec::new();
+        let mut namespaces = HashSet::new();
+
+        while let Some(import_path) = import_queue.pop_front() {
+            let (file_path, mod_path) = match self.find_package_file(&import_path) {
+                Some(found) => found,
+                None => continue
+            };
+
+            if loaded.contains(&file_path) {
+                continue;
+            }
+            loaded.insert(file_path.clone());
+
+            let code = read_to_string(&file_path)
+                .map_err(|_| Box::new(EngineError::PackageNotReadable(file_path.clone())))?;
+            let parser = Parser::new(code);
+            let file_decls = parser.parse_root_decl_list()
+                .map_err(|p| Box::new(EngineError::PackageParseError(file_path.clone(), p)))?;
+
+            Self::collect_imports(&file_decls, &mut import_queue);
+            Self::insert_module(&mut package_decls, &mut namespaces, &mod_path, 0, file_decls)?;
+        }
+
+        // Packages never extend modules of the importing code
+        for package_decl in package_decls.iter() {
+            if let Some(name) = Self::get_module_name(package_decl) {
+                if decl_list.iter().any(|decl| Self::get_module_name(decl) == Some(name)) {
+                    return Err(Box::new(EngineError::PackageModuleCollision(format!("root::{}", name))));
+                }
+            }
+        }
+        decl_list.append(&mut package_decls);
+
+        Ok(())
+    }
+
+    /// Finds the script file for an import path, together with the module path it is mounted at
+    fn find_package_file(&self, import_path: &str) -> Option<(PathBuf, Vec<String>)> {
+        let path_fragments: Vec<String> = import_path.split("::")
+            .map(String::from)
+            .collect();
+
+        if path_fragments[0] == "root" || path_fragments[0] == "super" {
+            return None;
+        }
+
+        // Longest match first, a package file needs at least "package::module"
+        for len in (2..=path_fragments.len()).rev() {
+            let mut file_path = self.root.clone();
+            for fragment in path_fragments[..len - 1].iter() {
+                file_path.push(fragment);
+            }
+            file_path.push(format!("{}.pgs", path_fragments[len - 1]));
+
+            if file_path.is_file() {
+                return Some((file_path, path_fragments[..len].to_vec()));
+            }
+        }
+
+        None
+    }
+
+    /// Collects the import paths of a declaration list, including nested modules
+    fn collect_imports(decl_list: &[Declaration], import_queue: &mut VecDeque<String>) {
+        for decl in decl_list.iter() {
+            match decl {
+                Declaration::Import(import_path, _) |
+                Declaration::PubImport(import_path, _) => {
+                    import_queue.push_back(import_path.clone());
+                },
+                Declaration::Module(_, mod_decl_list) |
+                Declaration::PubModule(_, mod_decl_list) |
+                Declaration::FileModule(_, mod_decl_list) |
+                Declaration::PubFileModule(_, mod_decl_list) => {
+                    Self::collect_imports(mod_decl_list, import_queue);
+                },
+                _ => {}
+            };
+        }
+    }
+
+    /// Gets the name of a module declaration
+    fn get_module_name(decl: &Declaration) -> Option<&String> {
+        match decl {
+            Declaration::Module(name, _) |
+            Declaration::PubModule(name, _) |
+            Declaration::FileModule(name, _) |
+            Declaration::PubFileModule(name, _) => Some(name),
+            _ => None
+        }
+    }
+
+    /// Inserts a declaration list as a (nested) module.
+    /// Only the namespace modules created for the package paths are shared between packages,
+    /// a module declared twice fails with PackageModuleCollision.
+    fn insert_module(decl_list: &mut Vec<Declaration>, namespaces: &mut HashSet<Vec<String>>, mod_path: &[String], depth: usize, mut mod_decls: Vec<Declaration>) -> EngineResult<()> {
+        let collision = |name: &String| {
+            let mut full_path = vec![String::from("root")];
+            full_path.extend_from_slice(&mod_path[..depth]);
+            full_path.push(name.clone());
+            Box::new(EngineError::PackageModuleCollision(full_path.join("::")))
+        };
+
+        if depth == mod_path.len() {
+            for mod_decl in mod_decls.iter() {
+                if let Some(name) = Self::get_module_name(mod_decl) {
+                    if decl_list.iter().any(|decl| Self::get_module_name(decl) == Some(name)) {
+                        return Err(collision(name));
+                    }
+                }
+            }
+            decl_list.append(&mut mod_decls);
+            return Ok(());
+        }
+
+        let name = &mod_path[depth];
+        let existing = decl_list.iter_mut().find(|decl| Self::get_module_name(decl) == Some(name));
+
+        match existing {
+            Some(Declaration::PubModule(_, inner_decl_list)) if namespaces.contains(&mod_path[..=depth]) => {
+                Self::insert_module(inner_decl_list, namespaces, mod_path, depth + 1, mod_decls)
+            },
+            Some(_) => Err(collision(name)),
+            None => {
+                // Package modules are visible to the importing code
+                let mut inner_decl_list = Vec::new();
+                namespaces.insert(mod_path[..=depth].to_vec());
+                Self::insert_module(&mut inner_decl_list, namespaces, mod_path, depth + 1, mod_decls)?;
+                decl_list.push(Declaration::PubModule(name.clone(), inner_decl_list));
+                Ok(())
+            }
+        }
+    }
+}
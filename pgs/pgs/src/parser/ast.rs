@@ -1,19 +1,29 @@
 use std::{
     collections::{
         HashMap,
-        BTreeMap
+        BTreeMap,
+        BTreeSet
     },
-    ops::Deref
+    ops::Deref,
+    fmt::{
+        Display,
+        Formatter,
+        Result as FmtResult
+    }
 };
 
 #[derive(PartialEq, Debug, Clone)]
 pub enum Expression {
     IntLiteral(i64),
     FloatLiteral(f32),
+    DoubleLiteral(f64),
     StringLiteral(String),
     BoolLiteral(bool),
+    CharLiteral(char),
+    NullLiteral,
     Variable(String),
     ContainerInstance(String, HashMap<String, Expression>),
+    EnumValue(String, String),
     MemberAccess(Box<Expression>, Box<Expression>),
     Deref(Box<Expression>),
     Ref(Box<Expression>),
@@ -22,7 +32,13 @@ pub enum Expression {
     Subtraction(Box<Expression>, Box<Expression>),
     Multiplication(Box<Expression>, Box<Expression>),
     Division(Box<Expression>, Box<Expression>),
+    Modulo(Box<Expression>, Box<Expression>),
     Not(Box<Expression>),
+    Negation(Box<Expression>),
+    Cast(Box<Expression>, Type),
+    Tuple(Vec<Expression>),
+    Array(Vec<Expression>),
+    TupleIndex(Box<Expression>, usize),
     And(Box<Expression>, Box<Expression>),
     Or(Box<Expression>, Box<Expression>),
     Equals(Box<Expression>, Box<Expression>),
@@ -36,6 +52,25 @@ pub enum Expression {
     SubAssign(Box<Expression>, Box<Expression>),
     MulAssign(Box<Expression>, Box<Expression>),
     DivAssign(Box<Expression>, Box<Expression>),
+    SizeOf(Type),
+    TypeOf(Box<Expression>),
+    Slice(Box<Expression>, Option<Box<Expression>>, Option<Box<Expression>>),
+    Index(Box<Expression>, Box<Expression>),
+    Len(Box<Expression>),
+    /// Decodes the char starting at a byte index of a string, e.g. "char_at(name, 0)"
+    CharAt(Box<Expression>, Box<Expression>),
+    /// Runs a coroutine until it yields, e.g. "resume(gen)". Evaluates to the yielded value, or null once it finished.
+    Resume(Box<Expression>),
+    /// A range of ints, e.g. "0..10" or "0..=9". The flag is set if the end is inclusive.
+    Range(Box<Expression>, Box<Expression>, bool),
+    /// Picks one of two values by a condition, e.g. "if x > 0 { x } else { 0 }"
+    Conditional(Box<Expression>, Box<Expression>, Box<Expression>),
+    /// A block evaluating to its trailing expression, e.g. "{ var y = x * 2; y + 1 }"
+    Block(Vec<Statement>, Box<Expression>),
+    /// Unwraps a result, returning its error from the current function, e.g. "parse(text)?"
+    Try(Box<Expression>),
+    /// An anonymous function, e.g. "|x: int| ~ int { return x * 2; }". The name is left empty.
+    Lambda(Box<FunctionDeclArgs>)
 }
 
 impl Expression {
@@ -51,9 +86,15 @@ impl Expression {
             Expression::FloatLiteral(float) => {
                 println!("{} Float:{}", baseline, float);
             },
+            Expression::DoubleLiteral(double) => {
+                println!("{} Double:{}", baseline, double);
+            },
             Expression::StringLiteral(string) => {
                 println!("{} String:{}", baseline, string);
             },
+            Expression::CharLiteral(character) => {
+                println!("{} Char:{:?}", baseline, character);
+            },
             Expression::Variable(variable) => {
                 println!("{} Variable:{}", baseline, variable);
             },
@@ -77,6 +118,11 @@ impl Expression {
                 lhs.print(n + 1);
                 rhs.print(n + 1)
             },
+            Expression::Modulo(lhs, rhs) => {
+                println!("{} Modulo:", baseline);
+                lhs.print(n + 1);
+                rhs.print(n + 1)
+            },
             Expression::MemberAccess(lhs, rhs) => {
                 println!("{} Member access:", baseline);
                 lhs.print(n + 1);
@@ -120,6 +166,92 @@ impl Expression {
         }
     }
 
+    /// Collects the names of all variables and called functions used by an expression
+    pub fn collect_variables(&self, used: &mut BTreeSet<String>) {
+        match self {
+            Expression::Variable(var_name) => {
+                used.insert(var_name.clone());
+            },
+            Expression::Call(fn_name, args) => {
+                used.insert(fn_name.clone());
+                for arg in args.iter() {
+                    arg.collect_variables(used);
+                }
+            },
+            Expression::ContainerInstance(_, members) => {
+                for member in members.values() {
+                    member.collect_variables(used);
+                }
+            },
+            Expression::Tuple(exprs) |
+            Expression::Array(exprs) => {
+                for expr in exprs.iter() {
+                    expr.collect_variables(used);
+                }
+            },
+            Expression::MemberAccess(lhs, rhs) |
+            Expression::Addition(lhs, rhs) |
+            Expression::Subtraction(lhs, rhs) |
+            Expression::Multiplication(lhs, rhs) |
+            Expression::Division(lhs, rhs) |
+            Expression::Modulo(lhs, rhs) |
+            Expression::And(lhs, rhs) |
+            Expression::Or(lhs, rhs) |
+            Expression::Equals(lhs, rhs) |
+            Expression::NotEquals(lhs, rhs) |
+            Expression::GreaterThan(lhs, rhs) |
+            Expression::LessThan(lhs, rhs) |
+            Expression::GreaterThanEquals(lhs, rhs) |
+            Expression::LessThanEquals(lhs, rhs) |
+            Expression::Assign(lhs, rhs) |
+            Expression::AddAssign(lhs, rhs) |
+            Expression::SubAssign(lhs, rhs) |
+            Expression::MulAssign(lhs, rhs) |
+            Expression::DivAssign(lhs, rhs) |
+            Expression::Index(lhs, rhs) |
+            Expression::CharAt(lhs, rhs) |
+            Expression::Range(lhs, rhs, _) => {
+                lhs.collect_variables(used);
+                rhs.collect_variables(used);
+            },
+            Expression::Deref(inner) |
+            Expression::Ref(inner) |
+            Expression::Not(inner) |
+            Expression::Negation(inner) |
+            Expression::Cast(inner, _) |
+            Expression::TupleIndex(inner, _) |
+            Expression::TypeOf(inner) |
+            Expression::Len(inner) |
+            Expression::Resume(inner) |
+            Expression::Try(inner) => inner.collect_variables(used),
+            Expression::Conditional(cond, then_expr, else_expr) => {
+                cond.collect_variables(used);
+                then_expr.collect_variables(used);
+                else_expr.collect_variables(used);
+            },
+            Expression::Block(stmt_list, value_expr) => {
+                let mut declared = BTreeSet::new();
+                for stmt in stmt_list.iter() {
+                    stmt.collect_variables(used, &mut declared);
+                }
+                value_expr.collect_variables(used);
+            },
+            Expression::Slice(base, start, end) => {
+                base.collect_variables(used);
+                for bound in [start, end].iter().filter_map(|bound| bound.as_ref()) {
+                    bound.collect_variables(used);
+                }
+            },
+            Expression::Lambda(fn_decl_args) => {
+                let mut declared = BTreeSet::new();
+                for stmt in fn_decl_args.code_block.iter().flatten() {
+                    stmt.collect_variables(used, &mut declared);
+                }
+            },
+            _ => {}
+        }
+    }
+
     /// Checks if an expression is a member access expr
     pub fn is_member_access(&self) -> bool {
         match self {
@@ -131,7 +263,10 @@ impl Expression {
     pub fn is_member_call(&self) -> bool {
         match self {
             Expression::MemberAccess(_, rhs) => {
-                rhs.is_member_call()
+                match rhs.deref() {
+                    Expression::Call(_, _) => true,
+                    _ => rhs.is_member_call()
+                }
             },
             _ => false
         }
@@ -158,25 +293,58 @@ pub enum Operator {
 #[derive(PartialEq, Debug, Clone)]
 pub struct FunctionDeclArgs {
     pub name: String,
+    /// Names of the type parameters of a generic function, e.g. "T" in "fn: max<T>(a: T, b: T) ~ T"
+    pub generics: Vec<String>,
     pub arguments: Vec<(String, Type)>,
     pub returns: Type,
-    pub code_block: Option<Vec<Statement>>
+    pub code_block: Option<Vec<Statement>>,
+    /// Whether the function is visible outside of its module, declared with "pub"
+    pub public: bool
 }
 
 #[derive(PartialEq, Debug, Clone)]
 pub struct ContainerDeclArgs {
     pub name: String,
-    pub members: Vec<(String, Type)>
+    /// Names of the type parameters of a generic container, e.g. "T" in "cont: Box<T>"
+    pub generics: Vec<String>,
+    pub members: Vec<(String, Type)>,
+    /// Whether the container is visible outside of its module, declared with "pub"
+    pub public: bool
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct InterfaceDeclArgs {
+    pub name: String,
+    /// Member function signatures, without code blocks
+    pub functions: Vec<FunctionDeclArgs>
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct EnumDeclArgs {
+    pub name: String,
+    pub variants: Vec<String>
 }
 
 #[derive(PartialEq, Debug)]
 pub enum Declaration {
     Function(FunctionDeclArgs),
     Module(String, Vec<Declaration>),
+    PubModule(String, Vec<Declaration>),
     Container(ContainerDeclArgs),
+    Enum(EnumDeclArgs),
     Import(String, String),
+    PubImport(String, String),
+    /// Imports a script file as a module, e.g. "import \"utils.pgs\";". Holds the path and module name.
+    FileImport(String, String),
+    /// A module declared without a body, e.g. "mod: shapes;". Loaded from "shapes.pgs" or "shapes/mod.pgs".
+    ModuleFile(String),
+    PubModuleFile(String),
+    /// A module loaded from a script file, replaces its file import or module file once resolved
+    FileModule(String, Vec<Declaration>),
+    PubFileModule(String, Vec<Declaration>),
     Impl(String, String, Vec<Declaration>),
-    StaticVar(VariableDeclArgs)
+    StaticVar(VariableDeclArgs),
+    Interface(InterfaceDeclArgs)
 }
 
 #[derive(PartialEq, Debug, Clone)]
@@ -186,6 +354,13 @@ pub struct VariableDeclArgs {
     pub assignment: Box<Expression>
 }
 
+#[derive(PartialEq, Debug, Clone)]
+pub struct DestructureDeclArgs {
+    pub var_type: Type,
+    pub names: Vec<String>,
+    pub assignment: Box<Expression>
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct IfStatementArgs {
     pub if_expr: Expression,
@@ -194,19 +369,137 @@ pub struct IfStatementArgs {
     pub else_if_list: Option<Vec<(Expression, Vec<Statement>)>>
 }
 
+#[derive(PartialEq, Debug, Clone)]
+pub struct MatchStatementArgs {
+    pub match_expr: Expression,
+    pub arms: Vec<(Expression, Vec<Statement>)>,
+    pub default_block: Option<Vec<Statement>>
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub enum Statement {
     VariableDecl(VariableDeclArgs),
+    DestructureDecl(DestructureDeclArgs),
     Assignment(String, Box<Expression>),
     Call(String, Vec<Expression>),
     Return(Option<Expression>),
     CodeBlock(Vec<Statement>),
     Loop(Vec<Statement>),
     While(Box<Expression>, Vec<Statement>),
+    /// A loop checking its condition after each run of the body, e.g. "loop { ... } while x < 10;"
+    DoWhile(Vec<Statement>, Box<Expression>),
     Break,
     Continue,
     Expression(Expression),
-    If(IfStatementArgs)
+    If(IfStatementArgs),
+    Match(MatchStatementArgs),
+    ForIn(String, Box<Expression>, Vec<Statement>),
+    StaticAssert(Expression, Option<Expression>),
+    /// Returns an error with the given message from the current function
+    Throw(Expression),
+    /// Runs a call as a task of its own, concurrently with the current one
+    Spawn(Expression),
+    /// Suspends the current coroutine, handing a value to the one resuming it
+    Yield(Expression),
+    /// Adds a constant step to an int, e.g. "x++;" or "x--;"
+    Increment(Expression, i64),
+    /// A statement with the source line and column it starts at, see Parser::with_positions()
+    Positioned(Box<Statement>, usize, usize)
+}
+
+impl Statement {
+    /// Gets the statement without its source position
+    pub fn without_position(&self) -> &Statement {
+        match self {
+            Statement::Positioned(stmt, _, _) => stmt.without_position(),
+            stmt => stmt
+        }
+    }
+
+    /// Collects the names of all variables used by a statement, and the ones it declares
+    pub fn collect_variables(&self, used: &mut BTreeSet<String>, declared: &mut BTreeSet<String>) {
+        match self {
+            Statement::VariableDecl(var_decl_args) => {
+                declared.insert(var_decl_args.name.clone());
+                var_decl_args.assignment.collect_variables(used);
+            },
+            Statement::DestructureDecl(destructure_decl_args) => {
+                declared.extend(destructure_decl_args.names.iter().cloned());
+                destructure_decl_args.assignment.collect_variables(used);
+            },
+            Statement::Assignment(var_name, expr) => {
+                used.insert(var_name.clone());
+                expr.collect_variables(used);
+            },
+            Statement::Call(fn_name, args) => {
+                used.insert(fn_name.clone());
+                for arg in args.iter() {
+                    arg.collect_variables(used);
+                }
+            },
+            Statement::Return(Some(expr)) |
+            Statement::Expression(expr) |
+            Statement::Throw(expr) |
+            Statement::Spawn(expr) |
+            Statement::Yield(expr) |
+            Statement::Increment(expr, _) => expr.collect_variables(used),
+            Statement::StaticAssert(expr, message) => {
+                expr.collect_variables(used);
+                if let Some(message) = message {
+                    message.collect_variables(used);
+                }
+            },
+            Statement::CodeBlock(stmt_list) |
+            Statement::Loop(stmt_list) => {
+                for stmt in stmt_list.iter() {
+                    stmt.collect_variables(used, declared);
+                }
+            },
+            Statement::While(expr, stmt_list) |
+            Statement::DoWhile(stmt_list, expr) => {
+                expr.collect_variables(used);
+                for stmt in stmt_list.iter() {
+                    stmt.collect_variables(used, declared);
+                }
+            },
+            Statement::ForIn(item_name, expr, stmt_list) => {
+                declared.insert(item_name.clone());
+                expr.collect_variables(used);
+                for stmt in stmt_list.iter() {
+                    stmt.collect_variables(used, declared);
+                }
+            },
+            Statement::If(if_stmt_args) => {
+                if_stmt_args.if_expr.collect_variables(used);
+                for stmt in if_stmt_args.if_block.iter() {
+                    stmt.collect_variables(used, declared);
+                }
+                for (else_if_expr, else_if_block) in if_stmt_args.else_if_list.iter().flatten() {
+                    else_if_expr.collect_variables(used);
+                    for stmt in else_if_block.iter() {
+                        stmt.collect_variables(used, declared);
+                    }
+                }
+                for stmt in if_stmt_args.else_block.iter().flatten() {
+                    stmt.collect_variables(used, declared);
+                }
+            },
+            Statement::Match(match_stmt_args) => {
+                match_stmt_args.match_expr.collect_variables(used);
+                for (arm_expr, arm_block) in match_stmt_args.arms.iter() {
+                    arm_expr.collect_variables(used);
+                    for stmt in arm_block.iter() {
+                        stmt.collect_variables(used, declared);
+                    }
+                }
+                for stmt in match_stmt_args.default_block.iter().flatten() {
+                    stmt.collect_variables(used, declared);
+                }
+            },
+            Statement::Positioned(stmt, _, _) => stmt.collect_variables(used, declared),
+            _ => {}
+        }
+    }
 }
 
 #[derive(PartialEq, Debug, Clone)]
@@ -215,24 +508,50 @@ pub enum Type {
     Int,
     String,
     Float,
+    /// A 64 bit float
+    Double,
     Bool,
+    Char,
     Auto,
     Array(Box<Type>, usize),
     AutoArray(Box<Type>),
     Other(String),
+    /// An enum, by its canonical name. Named types are only resolved to enums by the compiler.
+    Enum(String),
     Tuple(Vec<Type>),
-    Reference(Box<Type>)
+    Reference(Box<Type>),
+    /// A value that may be null, stored as the value followed by an 8 byte "has value" flag
+    Optional(Box<Type>),
+    /// The type of the null literal, only assignable to optionals
+    Null,
+    /// Either a value or an error message, stored as the value, the message and an 8 byte "is ok" flag
+    Result(Box<Type>),
+    /// A callable value with argument and return types, stored as the function uid and the address of its environment
+    Function(Vec<Type>, Box<Type>),
+    /// A generic container with type arguments, e.g. "Box<int>". Resolved to a named instance by the compiler.
+    Generic(String, Vec<Type>),
+    /// An interface, by its canonical name. Only usable behind a reference, stored as the object address and the address of its vtable.
+    Interface(String),
+    /// A range of ints, stored as the start and the exclusive end
+    Range,
+    /// A handle to a coroutine yielding values of the inner type, e.g. "co<int>"
+    Coroutine(Box<Type>)
 }
 
 impl Type {
     pub fn is_primitive(&self) -> bool {
         match self {
             Type::Bool => true,
+            Type::Char => true,
             Type::Int => true,
+            Type::Enum(_) => true,
+            Type::Coroutine(_) => true,
             Type::Float => true,
+            Type::Double => true,
             Type::Reference(inner_type) => {
                 match inner_type.deref() {
                     Type::AutoArray(_) => false,
+                    Type::Interface(_) => false,
                     _ => true
                 }
             },
@@ -240,6 +559,36 @@ impl Type {
         }
     }
 
+    /// Checks if a type contains type arguments of a generic container
+    pub fn has_type_args(&self) -> bool {
+        match self {
+            Type::Generic(_, _) => true,
+            Type::Reference(inner_type) |
+            Type::Optional(inner_type) |
+            Type::Result(inner_type) |
+            Type::AutoArray(inner_type) |
+            Type::Array(inner_type, _) => inner_type.has_type_args(),
+            Type::Tuple(elem_types) => elem_types.iter().any(Type::has_type_args),
+            Type::Function(arg_types, ret_type) => {
+                arg_types.iter().any(Type::has_type_args) || ret_type.has_type_args()
+            },
+            _ => false
+        }
+    }
+
+    /// Gets the builtin type with the given name, e.g. "int"
+    pub fn from_builtin_name(name: &str) -> Option<Type> {
+        match name {
+            "int" => Some(Type::Int),
+            "float" => Some(Type::Float),
+            "double" => Some(Type::Double),
+            "bool" => Some(Type::Bool),
+            "char" => Some(Type::Char),
+            "string" => Some(Type::String),
+            _ => None
+        }
+    }
+
     pub fn get_ref_type(&self) -> Type {
         match self {
             Type::Reference(inner_type) => {
@@ -249,3 +598,48 @@ impl Type {
         }
     }
 }
+
+/// Formats a type the way it is written in scripts
+impl Display for Type {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Type::Void => write!(f, "void"),
+            Type::Int => write!(f, "int"),
+            Type::String => write!(f, "string"),
+            Type::Float => write!(f, "float"),
+            Type::Double => write!(f, "double"),
+            Type::Bool => write!(f, "bool"),
+            Type::Char => write!(f, "char"),
+            Type::Auto => write!(f, "var"),
+            Type::Array(inner_type, size) => write!(f, "[{}; {}]", inner_type, size),
+            Type::AutoArray(inner_type) => write!(f, "[{}]", inner_type),
+            Type::Other(typename) => write!(f, "{}", typename),
+            Type::Enum(enum_name) => write!(f, "{}", enum_name),
+            Type::Interface(iface_name) => write!(f, "{}", iface_name),
+            Type::Tuple(types) => {
+                let names: Vec<String> = types.iter()
+                    .map(|inner_type| inner_type.to_string())
+                    .collect();
+                write!(f, "({})", names.join(", "))
+            },
+            Type::Reference(inner_type) => write!(f, "&{}", inner_type),
+            Type::Optional(inner_type) => write!(f, "{}?", inner_type),
+            Type::Result(inner_type) => write!(f, "{}!", inner_type),
+            Type::Function(arg_types, ret_type) => {
+                let names: Vec<String> = arg_types.iter()
+                    .map(|arg_type| arg_type.to_string())
+                    .collect();
+                write!(f, "fn({}) ~ {}", names.join(", "), ret_type)
+            },
+            Type::Generic(cont_name, type_args) => {
+                let names: Vec<String> = type_args.iter()
+                    .map(|type_arg| type_arg.to_string())
+                    .collect();
+                write!(f, "{}<{}>", cont_name, names.join(", "))
+            },
+            Type::Null => write!(f, "null"),
+            Type::Range => write!(f, "range"),
+            Type::Coroutine(inner_type) => write!(f, "co<{}>", inner_type)
+        }
+    }
+}
@@ -24,6 +24,10 @@ pub enum Token {
     #[prio = 1]
     Var,
 
+    #[token = "enum"]
+    #[prio = 1]
+    Enum,
+
     #[token = "mod"]
     #[prio = 1]
     Mod,
@@ -36,6 +40,14 @@ pub enum Token {
     #[prio = 1]
     Impl,
 
+    #[token = "iface"]
+    #[prio = 1]
+    Iface,
+
+    #[token = "pub"]
+    #[prio = 1]
+    Pub,
+
     #[token = "int"]
     #[prio = 1]
     Int,
@@ -52,6 +64,10 @@ pub enum Token {
     #[prio = 1]
     For,
 
+    #[token = "in"]
+    #[prio = 1]
+    In,
+
     #[token = "loop"]
     #[prio = 1]
     Loop,
@@ -64,6 +80,10 @@ pub enum Token {
     #[prio = 1]
     Bool,
 
+    #[token = "char"]
+    #[prio = 1]
+    Char,
+
     #[token = "true"]
     #[prio = 1]
     True,
@@ -72,6 +92,10 @@ pub enum Token {
     #[prio = 1]
     False,
 
+    #[token = "null"]
+    #[prio = 1]
+    Null,
+
     #[token = "if"]
     #[prio = 1]
     If,
@@ -83,6 +107,10 @@ pub enum Token {
     #[prio = 1]
     Else,
 
+    #[token = "match"]
+    #[prio = 1]
+    Match,
+
     #[token = "break"]
     #[prio = 1]
     Break,
@@ -94,15 +122,21 @@ pub enum Token {
     #[regex = "([a-zA-Z_][a-zA-Z0-9_]*)"]
     Text,
 
-    #[regex = "[0-9]+"]
+    #[regex = "(0x[0-9a-fA-F]+|0b[01]+|0o[0-7]+|[0-9]+)"]
     IntLiteral,
 
-    #[regex = "([0-9]+\\.[0-9]+)"]
+    #[regex = "([0-9]+\\.[0-9]+([eE][+-]?[0-9]+)?f?|[0-9]+[eE][+-]?[0-9]+f?)"]
     FloatLiteral,
 
+    #[regex = "([0-9]+\\.[0-9]+([eE][+-]?[0-9]+)?d|[0-9]+[eE][+-]?[0-9]+d)"]
+    DoubleLiteral,
+
     #[regex = "\"([^\"]|\\.)*\""]
     StringLiteral,
 
+    #[regex = "'([^'\\\\]|\\\\.)'"]
+    CharLiteral,
+
     #[token = "("]
     OpenParan,
 
@@ -136,12 +170,18 @@ pub enum Token {
     #[token = "||"]
     Or,
 
+    #[token = "|"]
+    Pipe,
+
     #[token = "&&"]
     DoubleAnd,
 
     #[token = "="]
     Assign,
 
+    #[token = "=>"]
+    FatArrow,
+
     #[token = "+="]
     AddAssign,
 
@@ -154,6 +194,12 @@ pub enum Token {
     #[token = "/="]
     DivAssign,
 
+    #[token = "++"]
+    Increment,
+
+    #[token = "--"]
+    Decrement,
+
     #[token = "+"]
     Plus,
     
@@ -166,6 +212,9 @@ pub enum Token {
     #[token = "/"]
     Divide,
 
+    #[token = "%"]
+    Modulo,
+
     #[token = "=="]
     Equals,
 
@@ -196,10 +245,48 @@ pub enum Token {
     #[token = ".."]
     DoubleDot,
 
+    #[token = "..="]
+    DoubleDotEquals,
+
+    #[token = "?"]
+    QuestionMark,
+
     #[token = "return"]
     #[prio = 1]
     Return,
 
+    #[token = "sizeof"]
+    #[prio = 1]
+    SizeOf,
+
+    #[token = "typeof"]
+    #[prio = 1]
+    TypeOf,
+
+    #[token = "as"]
+    #[prio = 1]
+    As,
+
+    #[token = "static_assert"]
+    #[prio = 1]
+    StaticAssert,
+
+    #[token = "throw"]
+    #[prio = 1]
+    Throw,
+
+    #[token = "spawn"]
+    #[prio = 1]
+    Spawn,
+
+    #[token = "yield"]
+    #[prio = 1]
+    Yield,
+
+    // Unary minus, never produced by the lexer.
+    // The parser turns a "-" without a left operand into this.
+    Negate,
+
     #[end]
     End,
 
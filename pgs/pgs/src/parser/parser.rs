@@ -23,7 +23,9 @@ use std::{
     },
     error::Error,
     ops::Range,
-    cell::RefCell
+    cell::RefCell,
+    convert::TryFrom,
+    path::Path
 };
 
 use pglex::prelude::Lexable;
@@ -56,12 +58,19 @@ pub enum ParseErrorType {
     ExpectedOpenParan,
     ExpectedCloseParan,
     ExpectedStructName,
+    ExpectedEnumName,
+    ExpectedVariantName,
+    DuplicateVariant,
     ExpectedModName,
     ExpectedOpenBlock,
     ExpectedMemberType,
     ExpectedMemberName,
     ExpectedContainerName,
     ExpectedArraySize,
+    InvalidIntLiteral,
+    InvalidCharLiteral,
+    InvalidTupleIndex,
+    EmptyArrayLiteral,
     ExpectedCloseBracket,
     InvalidTypename(String),
     InvalidTokenInTypename(Token),
@@ -70,11 +79,24 @@ pub enum ParseErrorType {
     ExpectedImportString,
     ExpectedMod,
     ExpectedIf,
+    ExpectedElse,
+    ExpectedMatch,
+    ExpectedFatArrow,
+    ArmAfterDefaultArm,
+    ExpectedFor,
+    ExpectedIn,
     ExpectedImpl,
     ExpectedImplType,
     ExpectedThis,
     ThisOnlyAllowedInImpls,
-    MalformedImport
+    MalformedImport,
+    ExpectedPipe,
+    ExpectedTypeParamName,
+    ExpectedGreaterThan,
+    ExpectedInterfaceName,
+    ExpectedPubDecl,
+    ExpectedBlockValue,
+    ExpectedCall
 }
 
 #[derive(Debug)]
@@ -110,13 +132,63 @@ pub type ParseResult<T> = Result<T, ParseError>;
 
 pub struct Parser {
     code: String,
-    current_cont: RefCell<String>
+    current_cont: RefCell<String>,
+    positions: bool
+}
+
+/// Parses a decimal, hexadecimal ("0x"), binary ("0b") or octal ("0o") int literal.
+/// Prefixed literals may use all 64 bits, e.g. "0xFFFFFFFFFFFFFFFF" is -1.
+fn parse_int_literal(literal: &str) -> Option<i64> {
+    let radix = match literal.get(0..2) {
+        Some("0x") => 16,
+        Some("0b") => 2,
+        Some("0o") => 8,
+        _ => return literal.parse::<i64>().ok()
+    };
+    u64::from_str_radix(&literal[2..], radix).ok()
+        .map(|int| int as i64)
+}
+
+/// Parses a quoted char literal like 'a' or '\n' into its char.
+fn parse_char_literal(literal: &str) -> Option<char> {
+    let inner = literal.get(1..literal.len() - 1)?;
+    let mut chars = inner.chars();
+    let character = match chars.next()? {
+        '\\' => {
+            match chars.next()? {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                '0' => '\0',
+                '\\' => '\\',
+                '\'' => '\'',
+                '"' => '"',
+                _ => return None
+            }
+        },
+        character => character
+    };
+    match chars.next() {
+        Some(_) => None,
+        None => Some(character)
+    }
+}
+
+/// Checks if the lexer is at a tuple element access like ".0"
+fn is_tuple_index(lexer: &Lexer) -> bool {
+    if lexer.token != Token::Dot {
+        return false;
+    }
+    let mut peek_lexer = lexer.clone();
+    peek_lexer.advance();
+    peek_lexer.token == Token::IntLiteral || peek_lexer.token == Token::FloatLiteral
 }
 
 fn is_op(token: &Token) -> bool {
     match token {
         Token::Times => true,
         Token::Divide => true,
+        Token::Modulo => true,
         Token::Plus => true,
         Token::Minus => true,
         Token::Equals => true,
@@ -126,6 +198,7 @@ fn is_op(token: &Token) -> bool {
         Token::LessThan => true,
         Token::LessThanEquals => true,
         Token::Not => true,
+        Token::Negate => true,
         Token::Tilde => true,
         Token::And => true,
         Token::Dot => true,
@@ -135,6 +208,7 @@ fn is_op(token: &Token) -> bool {
         Token::SubAssign => true,
         Token::DivAssign => true,
         Token::DoubleDot => true,
+        Token::DoubleDotEquals => true,
         Token::Or => true,
         Token::DoubleAnd => true,
         _ => false
@@ -143,28 +217,31 @@ fn is_op(token: &Token) -> bool {
 
 fn op_prec(token: &Token) -> i8 {
     match token {
-        Token::Times => 3,
-        Token::Divide => 3,
-        Token::Plus => 2,
-        Token::Minus => 2,
-        Token::Equals => 1,
-        Token::NotEquals => 1,
-        Token::GreaterThan => 1,
-        Token::GreaterThanEquals => 1,
-        Token::LessThan => 1,
-        Token::LessThanEquals => 1,
-        Token::Not => 4,
-        Token::And => 2,
-        Token::Tilde => 2,
-        Token::Dot => 5,
+        Token::Times => 5,
+        Token::Divide => 5,
+        Token::Modulo => 5,
+        Token::Plus => 4,
+        Token::Minus => 4,
+        Token::Equals => 3,
+        Token::NotEquals => 3,
+        Token::GreaterThan => 3,
+        Token::GreaterThanEquals => 3,
+        Token::LessThan => 3,
+        Token::LessThanEquals => 3,
+        Token::Not => 6,
+        Token::Negate => 6,
+        Token::And => 4,
+        Token::Tilde => 4,
+        Token::Dot => 7,
         Token::Assign => 0,
         Token::AddAssign => 0,
         Token::MulAssign => 0,
         Token::SubAssign => 0,
         Token::DivAssign => 0,
-        Token::DoubleDot => 0,
-        Token::Or => 0,
-        Token::DoubleAnd => 0,
+        Token::DoubleDot => 1,
+        Token::DoubleDotEquals => 1,
+        Token::Or => 1,
+        Token::DoubleAnd => 2,
         _ => {
             panic!("ERROR! Not an operator");
         }
@@ -173,8 +250,9 @@ fn op_prec(token: &Token) -> i8 {
 
 fn is_op_right_assoc(token: &Token) -> bool {
     match token {
-        Token::Times => true,
+        Token::Times => false,
         Token::Divide => false,
+        Token::Modulo => false,
         Token::Plus => false,
         Token::Minus => false,
         Token::Equals => false,
@@ -184,6 +262,7 @@ fn is_op_right_assoc(token: &Token) -> bool {
         Token::LessThan => false,
         Token::LessThanEquals => false,
         Token::Not => true,
+        Token::Negate => true,
         Token::Tilde => true,
         Token::And => true,
         Token::Dot => true,
@@ -193,6 +272,7 @@ fn is_op_right_assoc(token: &Token) -> bool {
         Token::SubAssign => true,
         Token::DivAssign => true,
         Token::DoubleDot => false,
+        Token::DoubleDotEquals => false,
         Token::Or => false,
         Token::DoubleAnd => false,
         _ => {
@@ -205,10 +285,29 @@ impl Parser {
     pub fn new(code: String) -> Self {
         Parser {
             code: code,
-            current_cont: RefCell::new(String::new())
+            current_cont: RefCell::new(String::new()),
+            positions: false
         }
     }
 
+    /// Wraps every statement of a block in Statement::Positioned, with the line and column it starts at
+    pub fn with_positions(mut self) -> Self {
+        self.positions = true;
+        self
+    }
+
+    /// Gets the source line and column of a byte offset, both starting at 1
+    fn get_position(&self, offset: usize) -> (usize, usize) {
+        let code = match self.code.get(..offset) {
+            Some(code) => code,
+            None => return (0, 0)
+        };
+        let line_start = code.rfind('\n')
+            .map(|newline| newline + 1)
+            .unwrap_or(0);
+        (code.matches('\n').count() + 1, code[line_start..].chars().count() + 1)
+    }
+
     pub fn parse_decl_list(&self, lexer: &mut Lexer, delims: &[Token]) -> ParseResult<Vec<Declaration>> {
         let mut ret = Vec::new();
         
@@ -222,16 +321,29 @@ impl Parser {
                 Token::Container => {
                     ret.push(self.parse_container_decl(lexer)?);
                 },
+                Token::Enum => {
+                    ret.push(self.parse_enum_decl(lexer)?);
+                },
                 Token::Import => {
                     let mut import_decls = self.parse_import_decl(lexer)?;
                     ret.append(&mut import_decls);
                 },
+                Token::Pub => {
+                    let mut pub_decls = self.parse_pub_decl(lexer)?;
+                    ret.append(&mut pub_decls);
+                },
                 Token::Mod => {
                     ret.push(self.parse_mod_decl(lexer)?);
                 },
                 Token::Impl => {
                     ret.push(self.parse_impl_decl(lexer)?);
                 },
+                Token::Var => {
+                    ret.push(self.parse_static_var_decl(lexer)?);
+                },
+                Token::Iface => {
+                    ret.push(self.parse_iface_decl(lexer)?);
+                },
                 _ => {
                     return Err(ParseError::new(ParseErrorType::ExpectedMod, lexer.range()));
                 }
@@ -241,6 +353,14 @@ impl Parser {
         Ok(ret)
     }
 
+    /// Parses a module level variable declaration, e.g. "var counter: int = 0;"
+    pub fn parse_static_var_decl(&self, lexer: &mut Lexer) -> ParseResult<Declaration> {
+        match self.parse_var_decl(lexer)? {
+            Statement::VariableDecl(var_decl_args) => Ok(Declaration::StaticVar(var_decl_args)),
+            _ => make_parse_error!(lexer, ParseErrorType::ExpectedVarName)
+        }
+    }
+
     pub fn parse_impl_decl(&self, lexer: &mut Lexer) -> ParseResult<Declaration> {
         if lexer.token != Token::Impl {
             return make_parse_error!(lexer, ParseErrorType::ExpectedImpl);
@@ -256,11 +376,16 @@ impl Parser {
         // Swallow ":"
         lexer.advance();
 
-        if lexer.token != Token::Text {
-            return make_parse_error!(lexer, ParseErrorType::ExpectedImplType);
-        }
-
-        let impl_type = self.parse_mod_path(lexer)?;
+        let impl_type = match lexer.token {
+            Token::Text => self.parse_mod_path(lexer)?,
+            // Member functions of builtin types
+            Token::Int |
+            Token::Float |
+            Token::Bool |
+            Token::Char |
+            Token::String => self.parse_type(lexer)?.to_string(),
+            _ => return make_parse_error!(lexer, ParseErrorType::ExpectedImplType)
+        };
         let mut impl_for = impl_type.clone();
 
         if lexer.token == Token::For {
@@ -277,7 +402,8 @@ impl Parser {
         // Swallow "{"
         lexer.advance();
 
-        *(self.current_cont.borrow_mut()) = impl_type.clone();
+        // "this" refers to the implementing container, also for interface impls
+        *(self.current_cont.borrow_mut()) = impl_for.clone();
 
         let decl_list = self.parse_decl_list(lexer, &[Token::CloseBlock])?;
 
@@ -319,8 +445,16 @@ impl Parser {
         // Swallow mod name
         lexer.advance();
 
+        // Without a body, the module is loaded from its own file
+        if lexer.token == Token::Semicolon {
+            lexer.advance();
+            return Ok(
+                Declaration::ModuleFile(mod_name)
+            );
+        }
+
         if lexer.token != Token::OpenBlock {
-            return Err(ParseError::new(ParseErrorType::ExpectedOpenBlock, lexer.range()));
+            return Err(ParseError::new(ParseErrorType::ExpectedBlockOrSemicolon, lexer.range()));
         }
 
         // Swallow "{"
@@ -347,7 +481,7 @@ impl Parser {
                 }
                 lexer.advance();
                 import_as = String::from("*");
-                continue;
+                break;
             }
 
             if lexer.token != Token::Text {
@@ -446,12 +580,14 @@ impl Parser {
         // Swallow "import"
         lexer.advance();
 
-        if lexer.token != Token::Colon {
-            return make_parse_error!(lexer, ParseErrorType::ExpectedColon);
+        // Swallow ":" if present
+        if lexer.token == Token::Colon {
+            lexer.advance();
         }
 
-        // Swallow ":"
-        lexer.advance();
+        if lexer.token == Token::StringLiteral {
+            return Ok(vec![ self.parse_file_import(lexer)? ]);
+        }
 
         let delims = &[
             Token::Semicolon,
@@ -469,6 +605,97 @@ impl Parser {
         )
     }
 
+    /// Parses the rest of a file import, e.g. "\"lib/utils.pgs\";" or "\"utils.pgs\" = helpers;".
+    /// Without an alias, the module is named after the file.
+    pub fn parse_file_import(&self, lexer: &mut Lexer) -> ParseResult<Declaration> {
+        if lexer.token != Token::StringLiteral {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedImportString);
+        }
+        let literal = lexer.slice();
+        let file_path = String::from(&literal[1..literal.len() - 1]);
+        lexer.advance();
+
+        let mod_name = if lexer.token == Token::Assign {
+            // Swallow "="
+            lexer.advance();
+            if lexer.token != Token::Text {
+                return make_parse_error!(lexer, ParseErrorType::ExpectedModName);
+            }
+            let mod_name = String::from(lexer.slice());
+            lexer.advance();
+            mod_name
+        } else {
+            Path::new(&file_path).file_stem()
+                .and_then(|stem| stem.to_str())
+                .filter(|stem| !stem.is_empty() && stem.chars().all(|c| c.is_alphanumeric() || c == '_'))
+                .map(String::from)
+                .ok_or_else(|| ParseError::new(ParseErrorType::MalformedImport, lexer.range()))?
+        };
+
+        if lexer.token != Token::Semicolon {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedSemicolon);
+        }
+        // Swallow ";"
+        lexer.advance();
+
+        Ok(
+            Declaration::FileImport(file_path, mod_name)
+        )
+    }
+
+    /// Parses a "pub import" declaration, re-exporting the imported symbols
+    /// Parses a declaration visible outside of its module, e.g. "pub fn: area() ~ int {...}"
+    pub fn parse_pub_decl(&self, lexer: &mut Lexer) -> ParseResult<Vec<Declaration>> {
+        let mut next_lexer = lexer.clone();
+        next_lexer.advance();
+        if next_lexer.token == Token::Import {
+            return self.parse_pub_import_decl(lexer);
+        }
+
+        // Swallow "pub"
+        lexer.advance();
+
+        let decl = match lexer.token {
+            Token::Fn => self.parse_fn_decl(lexer)?,
+            Token::Container => self.parse_container_decl(lexer)?,
+            Token::Mod => self.parse_mod_decl(lexer)?,
+            _ => return make_parse_error!(lexer, ParseErrorType::ExpectedPubDecl)
+        };
+
+        let pub_decl = match decl {
+            Declaration::Function(mut fn_decl_args) => {
+                fn_decl_args.public = true;
+                Declaration::Function(fn_decl_args)
+            },
+            Declaration::Container(mut cont_decl_args) => {
+                cont_decl_args.public = true;
+                Declaration::Container(cont_decl_args)
+            },
+            Declaration::Module(mod_name, decl_list) => Declaration::PubModule(mod_name, decl_list),
+            Declaration::ModuleFile(mod_name) => Declaration::PubModuleFile(mod_name),
+            decl => decl
+        };
+
+        Ok(vec![ pub_decl ])
+    }
+
+    pub fn parse_pub_import_decl(&self, lexer: &mut Lexer) -> ParseResult<Vec<Declaration>> {
+        // Swallow "pub"
+        lexer.advance();
+
+        let import_decls = self.parse_import_decl(lexer)?
+            .into_iter()
+            .map(|decl| {
+                match decl {
+                    Declaration::Import(import_path, import_as) => Declaration::PubImport(import_path, import_as),
+                    decl => decl
+                }
+            })
+            .collect();
+
+        Ok(import_decls)
+    }
+
     pub fn parse_fn_decl(&self, lexer: &mut Lexer) -> ParseResult<Declaration> {
         let mut fn_decl_opt = None;
 
@@ -491,6 +718,13 @@ impl Parser {
         let fn_name = String::from(lexer.slice());
         lexer.advance();
 
+        // Parse optional type parameters
+        let generics = if lexer.token == Token::LessThan {
+            self.parse_type_params(lexer)?
+        } else {
+            Vec::new()
+        };
+
         // Parse "("
         if lexer.token != Token::OpenParan {
             return Err(ParseError::new(ParseErrorType::OpenParanMissing, lexer.range()));
@@ -522,8 +756,7 @@ impl Parser {
             },
             Token::OpenBlock => {
                 lexer.advance();
-                let statements = self.parse_statement_list(lexer)?;
-                code_block_opt = Some(statements);
+                code_block_opt = Some(self.parse_statement_list(lexer)?);
             },
             _ => {
                 return Err(ParseError::new(ParseErrorType::ExpectedBlockOrSemicolon, lexer.range()));
@@ -539,9 +772,11 @@ impl Parser {
 
         let fn_raw = FunctionDeclArgs {
             name: fn_name,
+            generics: generics,
             arguments: fn_args,
             returns: fn_return_type,
-            code_block: code_block_opt
+            code_block: code_block_opt,
+            public: false
         };
 
         fn_decl_opt = Some(
@@ -551,6 +786,34 @@ impl Parser {
         fn_decl_opt.ok_or(ParseError::new(ParseErrorType::Unknown, lexer.range()))
     }
 
+    /// Parses a list of type parameter names, e.g. "<T, U>"
+    pub fn parse_type_params(&self, lexer: &mut Lexer) -> ParseResult<Vec<String>> {
+        let mut ret = Vec::new();
+
+        // Swallow "<"
+        lexer.advance();
+
+        loop {
+            if lexer.token != Token::Text {
+                return Err(ParseError::new(ParseErrorType::ExpectedTypeParamName, lexer.range()));
+            }
+            ret.push(String::from(lexer.slice()));
+            lexer.advance();
+
+            if lexer.token != Token::Comma {
+                break;
+            }
+            lexer.advance();
+        }
+
+        if lexer.token != Token::GreaterThan {
+            return Err(ParseError::new(ParseErrorType::ExpectedGreaterThan, lexer.range()));
+        }
+        lexer.advance();
+
+        Ok(ret)
+    }
+
     pub fn parse_fn_args(&self, lexer: &mut Lexer) -> ParseResult<Vec<(String, Type)>> {
         let mut ret = Vec::new();
         let mut fn_arg_set = HashSet::new();
@@ -601,7 +864,9 @@ impl Parser {
             if cont_name.is_empty() {
                 return make_parse_error!(lexer, ParseErrorType::ThisOnlyAllowedInImpls);
             }
-            let arg_type = Type::Reference(Box::new(Type::Other(cont_name)));
+            let this_type = Type::from_builtin_name(&cont_name)
+                .unwrap_or(Type::Other(cont_name));
+            let arg_type = Type::Reference(Box::new(this_type));
 
             // Swallow "this"
             lexer.advance();
@@ -652,6 +917,13 @@ impl Parser {
         // Swallow container name
         lexer.advance();
 
+        // Parse optional type parameters
+        let generics = if lexer.token == Token::LessThan {
+            self.parse_type_params(lexer)?
+        } else {
+            Vec::new()
+        };
+
         if lexer.token != Token::OpenBlock {
             return Err(ParseError::new(ParseErrorType::ExpectedOpenBlock, lexer.range()));
         }
@@ -666,7 +938,9 @@ impl Parser {
 
         let container_args = ContainerDeclArgs {
             name: container_name,
-            members: members
+            generics: generics,
+            members: members,
+            public: false
         };
 
         Ok(
@@ -674,6 +948,145 @@ impl Parser {
         )
     }
 
+    /// Parses an interface declaration, e.g. "iface: Shape { fn: area(&this) ~ float; }"
+    pub fn parse_iface_decl(&self, lexer: &mut Lexer) -> ParseResult<Declaration> {
+        if lexer.token != Token::Iface {
+            return make_parse_error!(lexer, ParseErrorType::Unknown);
+        }
+
+        // Swallow "iface"
+        lexer.advance();
+
+        if lexer.token != Token::Colon {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedColon);
+        }
+
+        // Swallow ":"
+        lexer.advance();
+
+        if lexer.token != Token::Text {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedInterfaceName);
+        }
+
+        let iface_name = String::from(lexer.slice());
+
+        // Swallow interface name
+        lexer.advance();
+
+        if lexer.token != Token::OpenBlock {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedOpenBlock);
+        }
+
+        // Swallow "{"
+        lexer.advance();
+
+        *(self.current_cont.borrow_mut()) = iface_name.clone();
+
+        let mut functions = Vec::new();
+        while lexer.token == Token::Fn {
+            match self.parse_fn_decl(lexer)? {
+                Declaration::Function(fn_decl_args) if fn_decl_args.code_block.is_none() => {
+                    functions.push(fn_decl_args);
+                },
+                _ => {
+                    return make_parse_error!(lexer, ParseErrorType::ExpectedSemicolon);
+                }
+            };
+        }
+
+        *(self.current_cont.borrow_mut()) = String::new();
+
+        if lexer.token != Token::CloseBlock {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedCloseBlock);
+        }
+
+        // Swallow "}"
+        lexer.advance();
+
+        let iface_args = InterfaceDeclArgs {
+            name: iface_name,
+            functions: functions
+        };
+
+        Ok(
+            Declaration::Interface(iface_args)
+        )
+    }
+
+    /// Parses an enum declaration, e.g. "enum: Color { Red; Green; Blue; }"
+    pub fn parse_enum_decl(&self, lexer: &mut Lexer) -> ParseResult<Declaration> {
+        if lexer.token != Token::Enum {
+            return make_parse_error!(lexer, ParseErrorType::Unknown);
+        }
+
+        // Swallow "enum"
+        lexer.advance();
+
+        if lexer.token != Token::Colon {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedColon);
+        }
+
+        // Swallow ":"
+        lexer.advance();
+
+        if lexer.token != Token::Text {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedEnumName);
+        }
+
+        let enum_name = String::from(lexer.slice());
+
+        // Swallow enum name
+        lexer.advance();
+
+        if lexer.token != Token::OpenBlock {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedOpenBlock);
+        }
+
+        // Swallow "{"
+        lexer.advance();
+
+        let mut variants = Vec::new();
+        while lexer.token != Token::CloseBlock &&
+            lexer.token != Token::End &&
+            lexer.token != Token::Error {
+            if lexer.token != Token::Text {
+                return make_parse_error!(lexer, ParseErrorType::ExpectedVariantName);
+            }
+
+            let variant = String::from(lexer.slice());
+            if variants.contains(&variant) {
+                return make_parse_error!(lexer, ParseErrorType::DuplicateVariant);
+            }
+            variants.push(variant);
+
+            // Swallow variant name
+            lexer.advance();
+
+            if lexer.token != Token::Semicolon {
+                return make_parse_error!(lexer, ParseErrorType::ExpectedSemicolon);
+            }
+
+            // Swallow ";"
+            lexer.advance();
+        }
+
+        if lexer.token != Token::CloseBlock {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedCloseBlock);
+        }
+
+        // Swallow "}"
+        lexer.advance();
+
+        let enum_args = EnumDeclArgs {
+            name: enum_name,
+            variants: variants
+        };
+
+        Ok(
+            Declaration::Enum(enum_args)
+        )
+    }
+
     pub fn parse_type(&self, lexer: &mut Lexer) -> ParseResult<Type> {
         let ret_type = match lexer.token {
             Token::Int => {
@@ -688,6 +1101,10 @@ impl Parser {
                 lexer.advance();
                 Type::Bool
             },
+            Token::Char => {
+                lexer.advance();
+                Type::Char
+            },
             Token::String => {
                 lexer.advance();
                 Type::String
@@ -698,6 +1115,26 @@ impl Parser {
                 let inner_type = self.parse_type(lexer)?;
                 Type::Reference(Box::new(inner_type))
             },
+            Token::OpenParan => {
+                // Swallow "("
+                lexer.advance();
+                let mut tuple_types = Vec::new();
+                while lexer.token != Token::CloseParan {
+                    tuple_types.push(self.parse_type(lexer)?);
+                    if lexer.token == Token::Comma {
+                        // Swallow ","
+                        lexer.advance();
+                    } else if lexer.token != Token::CloseParan {
+                        return make_parse_error!(lexer, ParseErrorType::ExpectedCloseParan);
+                    }
+                }
+                if tuple_types.is_empty() {
+                    return make_parse_error!(lexer, ParseErrorType::InvalidTokenInTypename(Token::CloseParan));
+                }
+                // Swallow ")"
+                lexer.advance();
+                Type::Tuple(tuple_types)
+            },
             Token::OpenBracket => {
                 // Swallow "["
                 lexer.advance();
@@ -709,10 +1146,11 @@ impl Parser {
                     if lexer.token != Token::IntLiteral {
                         return make_parse_error!(lexer, ParseErrorType::ExpectedArraySize);
                     }
-                    let arr_size_raw = String::from(lexer.slice());
+                    let arr_size_raw = parse_int_literal(lexer.slice())
+                        .ok_or_else(|| ParseError::new(ParseErrorType::InvalidIntLiteral, lexer.range()))?;
                     arr_size = Some(
-                        arr_size_raw.parse::<usize>()
-                            .map_err(|_| ParseError::new(ParseErrorType::Unknown, lexer.range()))?
+                        usize::try_from(arr_size_raw)
+                            .map_err(|_| ParseError::new(ParseErrorType::ExpectedArraySize, lexer.range()))?
                     );
                     // Swallow arr size
                     lexer.advance();
@@ -727,6 +1165,36 @@ impl Parser {
                     Type::Array(Box::new(arr_type), arr_size.unwrap())
                 }
             },
+            Token::Fn => {
+                // Swallow "fn"
+                lexer.advance();
+                if lexer.token != Token::OpenParan {
+                    return make_parse_error!(lexer, ParseErrorType::ExpectedOpenParan);
+                }
+                // Swallow "("
+                lexer.advance();
+                let mut arg_types = Vec::new();
+                while lexer.token != Token::CloseParan {
+                    arg_types.push(self.parse_type(lexer)?);
+                    if lexer.token == Token::Comma {
+                        // Swallow ","
+                        lexer.advance();
+                    } else if lexer.token != Token::CloseParan {
+                        return make_parse_error!(lexer, ParseErrorType::ExpectedCloseParan);
+                    }
+                }
+                // Swallow ")"
+                lexer.advance();
+                let ret_type = if lexer.token == Token::Tilde {
+                    // Swallow "~"
+                    lexer.advance();
+                    self.parse_type(lexer)?
+                } else {
+                    Type::Void
+                };
+                // The return type already took a trailing "?" or "!"
+                return Ok(Type::Function(arg_types, Box::new(ret_type)));
+            },
             Token::Text => {
                 let mut typename = String::new();
                 while lexer.token == Token::Text ||
@@ -737,13 +1205,62 @@ impl Parser {
                 if typename.ends_with("::") {
                     return make_parse_error!(lexer, ParseErrorType::InvalidTypename(typename));
                 }
-                Type::Other(typename)
+                if lexer.token == Token::LessThan {
+                    let mut type_args = self.parse_type_args(lexer)?;
+                    if typename == "co" && type_args.len() == 1 {
+                        // Like "double", "co" is not a keyword
+                        Type::Coroutine(Box::new(type_args.remove(0)))
+                    } else {
+                        Type::Generic(typename, type_args)
+                    }
+                } else if typename == "double" {
+                    // Not a keyword, "double" stays usable as a name
+                    Type::Double
+                } else {
+                    Type::Other(typename)
+                }
             },
             _ => return make_parse_error!(lexer, ParseErrorType::InvalidTokenInTypename(lexer.token.clone()))
         };
+        // A trailing "?" makes the type optional, a trailing "!" a result
+        if lexer.token == Token::QuestionMark {
+            // Swallow "?"
+            lexer.advance();
+            return Ok(Type::Optional(Box::new(ret_type)));
+        }
+        if lexer.token == Token::Not {
+            // Swallow "!"
+            lexer.advance();
+            return Ok(Type::Result(Box::new(ret_type)));
+        }
         Ok(ret_type)
     }
 
+    /// Parses the type arguments of a generic container type, e.g. "<int, string>"
+    pub fn parse_type_args(&self, lexer: &mut Lexer) -> ParseResult<Vec<Type>> {
+        let mut ret = Vec::new();
+
+        // Swallow "<"
+        lexer.advance();
+
+        loop {
+            ret.push(self.parse_type(lexer)?);
+            if lexer.token != Token::Comma {
+                break;
+            }
+            // Swallow ","
+            lexer.advance();
+        }
+
+        if lexer.token != Token::GreaterThan {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedGreaterThan);
+        }
+        // Swallow ">"
+        lexer.advance();
+
+        Ok(ret)
+    }
+
     pub fn parse_container_members(&self, lexer: &mut Lexer) -> ParseResult<Vec<(String, Type)>> {
         let mut ret = Vec::new();
         let mut members = HashSet::new();
@@ -796,6 +1313,7 @@ impl Parser {
         )
     }
 
+    /// Parses a loop, or a do-while loop if the block is followed by "while expr;"
     pub fn parse_loop(&self, lexer: &mut Lexer) -> ParseResult<Statement> {
         if lexer.token != Token::Loop {
             return Err(ParseError::new(ParseErrorType::ExpectedLoop, lexer.range()));
@@ -820,8 +1338,26 @@ impl Parser {
         // Swallow "}"
         lexer.advance();
 
-        Ok(
-            Statement::Loop(stmt_list)
+        if lexer.token == Token::While {
+            // Swallow "while"
+            lexer.advance();
+
+            let while_expr = self.parse_expr(lexer, &[Token::Semicolon])?;
+
+            if lexer.token != Token::Semicolon {
+                return Err(ParseError::new(ParseErrorType::ExpectedSemicolon, lexer.range()));
+            }
+
+            // Swallow ";"
+            lexer.advance();
+
+            return Ok(
+                Statement::DoWhile(stmt_list, Box::new(while_expr))
+            );
+        }
+
+        Ok(
+            Statement::Loop(stmt_list)
         )
     }
 
@@ -863,6 +1399,49 @@ impl Parser {
         )
     }
 
+    pub fn parse_for_in(&self, lexer: &mut Lexer) -> ParseResult<Statement> {
+        if lexer.token != Token::For {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedFor);
+        }
+
+        // Swallow "for"
+        lexer.advance();
+
+        if lexer.token != Token::Text {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedVarName);
+        }
+
+        let item_name = String::from(lexer.slice());
+
+        // Swallow item name
+        lexer.advance();
+
+        if lexer.token != Token::In {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedIn);
+        }
+
+        // Swallow "in"
+        lexer.advance();
+
+        let iter_expr = self.parse_expr(lexer, &[Token::OpenBlock])?;
+
+        if lexer.token != Token::OpenBlock {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedOpenBlock);
+        }
+
+        // Swallow "{"
+        lexer.advance();
+
+        let stmt_list = self.parse_statement_list(lexer)?;
+
+        // Swallow "}"
+        lexer.advance();
+
+        Ok(
+            Statement::ForIn(item_name, Box::new(iter_expr), stmt_list)
+        )
+    }
+
     pub fn parse_if(&self, lexer: &mut Lexer) -> ParseResult<Statement> {
         if lexer.token != Token::If {
             return Err(ParseError::new(ParseErrorType::ExpectedIf, lexer.range()));
@@ -953,45 +1532,151 @@ impl Parser {
         )
     }
 
+    pub fn parse_match(&self, lexer: &mut Lexer) -> ParseResult<Statement> {
+        if lexer.token != Token::Match {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedMatch);
+        }
+        // Swallow "match"
+        lexer.advance();
+
+        let match_expr = self.parse_expr(lexer, &[Token::OpenBlock])?;
+
+        if lexer.token != Token::OpenBlock {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedOpenBlock);
+        }
+        // Swallow "{"
+        lexer.advance();
+
+        let mut arms = Vec::new();
+        let mut default_block = None;
+
+        while lexer.token != Token::CloseBlock &&
+            lexer.token != Token::End &&
+            lexer.token != Token::Error {
+            // The default arm has to be the last one
+            if default_block.is_some() {
+                return make_parse_error!(lexer, ParseErrorType::ArmAfterDefaultArm);
+            }
+
+            let is_default = lexer.token == Token::Text && lexer.slice() == "_";
+            let arm_expr = if is_default {
+                // Swallow "_"
+                lexer.advance();
+                None
+            } else {
+                Some(self.parse_expr(lexer, &[Token::FatArrow])?)
+            };
+
+            if lexer.token != Token::FatArrow {
+                return make_parse_error!(lexer, ParseErrorType::ExpectedFatArrow);
+            }
+            // Swallow "=>"
+            lexer.advance();
+
+            if lexer.token != Token::OpenBlock {
+                return make_parse_error!(lexer, ParseErrorType::ExpectedOpenBlock);
+            }
+            // Swallow "{"
+            lexer.advance();
+
+            let arm_stmt_list = self.parse_statement_list(lexer)?;
+
+            if lexer.token != Token::CloseBlock {
+                return make_parse_error!(lexer, ParseErrorType::ExpectedCloseBlock);
+            }
+            // Swallow "}"
+            lexer.advance();
+
+            // Arms may optionally be separated by commas
+            if lexer.token == Token::Comma {
+                lexer.advance();
+            }
+
+            match arm_expr {
+                Some(arm_expr) => arms.push((arm_expr, arm_stmt_list)),
+                None => default_block = Some(arm_stmt_list)
+            };
+        }
+
+        if lexer.token != Token::CloseBlock {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedCloseBlock);
+        }
+        // Swallow "}"
+        lexer.advance();
+
+        let match_stmt_args = MatchStatementArgs {
+            match_expr: match_expr,
+            arms: arms,
+            default_block: default_block
+        };
+
+        Ok(
+            Statement::Match(match_stmt_args)
+        )
+    }
+
     pub fn parse_statement_list(&self, lexer: &mut Lexer) -> ParseResult<Vec<Statement>> {
+        let (stmt_list, value_expr) = self.parse_block_body(lexer)?;
+        if value_expr.is_some() {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedSemicolon);
+        }
+        Ok(stmt_list)
+    }
+
+    /// Parses the statements of a block up to the closing "}".
+    /// A trailing expression without ";" is returned separately.
+    fn parse_block_body(&self, lexer: &mut Lexer) -> ParseResult<(Vec<Statement>, Option<Expression>)> {
         let mut ret = Vec::new();
 
         while lexer.token != Token::CloseBlock &&
             lexer.token != Token::End &&
             lexer.token != Token::Error {
-            match lexer.token {
-                Token::Var => {
-                    ret.push(self.parse_var_decl(lexer)?);
-                },
-                Token::Return => {
-                    ret.push(self.parse_return(lexer)?);
-                },
-                Token::If => {
-                    ret.push(self.parse_if(lexer)?);
-                },
-                Token::Continue => {
-                    ret.push(self.parse_continue(lexer)?);
-                },
-                Token::Break => {
-                    ret.push(self.parse_break(lexer)?);
-                },
-                Token::While => {
-                    ret.push(self.parse_while(lexer)?);
-                },
-                Token::Loop => {
-                    ret.push(self.parse_loop(lexer)?);
-                },
+            let (line, column) = self.get_position(lexer.range().start);
+            let stmt = match lexer.token {
+                Token::Var => self.parse_var_decl(lexer)?,
+                Token::Return => self.parse_return(lexer)?,
+                Token::If => self.parse_if(lexer)?,
+                Token::Match => self.parse_match(lexer)?,
+                Token::Continue => self.parse_continue(lexer)?,
+                Token::Break => self.parse_break(lexer)?,
+                Token::While => self.parse_while(lexer)?,
+                Token::Loop => self.parse_loop(lexer)?,
+                Token::For => self.parse_for_in(lexer)?,
+                Token::StaticAssert => self.parse_static_assert(lexer)?,
+                Token::Throw => self.parse_throw(lexer)?,
+                Token::Spawn => self.parse_spawn(lexer)?,
+                Token::Yield => self.parse_yield(lexer)?,
                 _ => {
-                    let expr = self.parse_expr(lexer, &[Token::Semicolon])?;
+                    let expr = self.parse_expr(lexer, &[Token::Semicolon, Token::Increment, Token::Decrement, Token::CloseBlock])?;
+                    if lexer.token == Token::CloseBlock {
+                        // The trailing expression is not a statement
+                        return Ok((ret, Some(expr)));
+                    }
+                    let stmt = match lexer.token {
+                        Token::Increment | Token::Decrement => {
+                            let step = if lexer.token == Token::Increment { 1 } else { -1 };
+                            // Swallow "++" or "--"
+                            lexer.advance();
+                            if lexer.token != Token::Semicolon {
+                                return make_parse_error!(lexer, ParseErrorType::ExpectedSemicolon);
+                            }
+                            Statement::Increment(expr, step)
+                        },
+                        _ => Statement::Expression(expr)
+                    };
                     // Swallow ";"
                     lexer.advance();
-                    ret.push(Statement::Expression(expr));
+                    stmt
                 }
             };
-            
+            if self.positions {
+                ret.push(Statement::Positioned(Box::new(stmt), line, column));
+            } else {
+                ret.push(stmt);
+            }
         }
 
-        Ok(ret)
+        Ok((ret, None))
     }
 
     pub fn try_parse_call_stmt(&self, lexer: &mut Lexer) -> ParseResult<Statement> {
@@ -1122,13 +1807,111 @@ impl Parser {
         // Swallow "return"
         lexer.advance();
 
-        let ret_expr = self.parse_expr(lexer, &[Token::Semicolon])?;
+        let ret_expr = self.parse_expr(lexer, &[Token::Semicolon, Token::CloseBlock])?;
+
+        // Swallow ";", the last statement of a block may leave it out
+        if lexer.token == Token::Semicolon {
+            lexer.advance();
+        }
+
+        Ok(
+            Statement::Return(Some(ret_expr))
+        )
+    }
+
+    /// Parses a "throw message;" statement
+    pub fn parse_throw(&self, lexer: &mut Lexer) -> ParseResult<Statement> {
+        // Swallow "throw"
+        lexer.advance();
+
+        let message_expr = self.parse_expr(lexer, &[Token::Semicolon])?;
 
         // Swallow ";"
         lexer.advance();
 
         Ok(
-            Statement::Return(Some(ret_expr))
+            Statement::Throw(message_expr)
+        )
+    }
+
+    /// Parses a "spawn function(args);" statement
+    pub fn parse_spawn(&self, lexer: &mut Lexer) -> ParseResult<Statement> {
+        // Swallow "spawn"
+        lexer.advance();
+
+        let call_expr = self.parse_expr(lexer, &[Token::Semicolon])?;
+        if !matches!(call_expr, Expression::Call(_, _)) {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedCall);
+        }
+
+        if lexer.token != Token::Semicolon {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedSemicolon);
+        }
+
+        // Swallow ";"
+        lexer.advance();
+
+        Ok(
+            Statement::Spawn(call_expr)
+        )
+    }
+
+    /// Parses a "yield value;" statement
+    pub fn parse_yield(&self, lexer: &mut Lexer) -> ParseResult<Statement> {
+        // Swallow "yield"
+        lexer.advance();
+
+        let value_expr = self.parse_expr(lexer, &[Token::Semicolon])?;
+
+        if lexer.token != Token::Semicolon {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedSemicolon);
+        }
+
+        // Swallow ";"
+        lexer.advance();
+
+        Ok(
+            Statement::Yield(value_expr)
+        )
+    }
+
+    /// Parses a static_assert(expr) or static_assert(expr, message) statement
+    pub fn parse_static_assert(&self, lexer: &mut Lexer) -> ParseResult<Statement> {
+        // Swallow "static_assert"
+        lexer.advance();
+
+        if lexer.token != Token::OpenParan {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedOpenParan);
+        }
+
+        // Swallow "("
+        lexer.advance();
+
+        let assert_expr = self.parse_expr(lexer, &[Token::Comma, Token::CloseParan])?;
+
+        let mut message_expr = None;
+        if lexer.token == Token::Comma {
+            // Swallow ","
+            lexer.advance();
+            message_expr = Some(self.parse_expr(lexer, &[Token::CloseParan])?);
+        }
+
+        if lexer.token != Token::CloseParan {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedCloseParan);
+        }
+
+        // Swallow ")"
+        lexer.advance();
+
+        if lexer.token != Token::Semicolon {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedSemicolon);
+        }
+
+        // Swallow ";"
+        lexer.advance();
+
+        Ok(
+            Statement::StaticAssert(assert_expr, message_expr)
         )
     }
 
@@ -1137,6 +1920,10 @@ impl Parser {
 
         // Swallow "var"
         lexer.advance();
+
+        if lexer.token == Token::OpenParan {
+            return self.parse_destructure_decl(lexer);
+        }
         
         if lexer.token != Token::Text {
             *lexer = lexer_backup;
@@ -1182,6 +1969,61 @@ impl Parser {
         )
     }
 
+    /// Parses the rest of a tuple destructuring declaration, e.g. "(a, b) = pair;"
+    pub fn parse_destructure_decl(&self, lexer: &mut Lexer) -> ParseResult<Statement> {
+        // Swallow "("
+        lexer.advance();
+
+        let mut names = Vec::new();
+        while lexer.token != Token::CloseParan {
+            if lexer.token != Token::Text {
+                return make_parse_error!(lexer, ParseErrorType::ExpectedVarName);
+            }
+            names.push(String::from(lexer.slice()));
+            // Swallow var name
+            lexer.advance();
+            if lexer.token == Token::Comma {
+                // Swallow ","
+                lexer.advance();
+            } else if lexer.token != Token::CloseParan {
+                return make_parse_error!(lexer, ParseErrorType::ExpectedCloseParan);
+            }
+        }
+
+        // Swallow ")"
+        lexer.advance();
+
+        let mut var_type = Type::Auto;
+
+        // if type is specified
+        if lexer.token == Token::Colon {
+            // Swallow ":"
+            lexer.advance();
+
+            var_type = self.parse_type(lexer)?;
+        }
+
+        if lexer.token != Token::Assign {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedAssignment);
+        }
+
+        lexer.advance();
+
+        let expr = self.parse_expr(lexer, &[Token::Semicolon])?;
+
+        let destructure_decl_args = DestructureDeclArgs {
+            var_type: var_type,
+            names: names,
+            assignment: Box::new(expr)
+        };
+
+        lexer.advance();
+
+        Ok(
+            Statement::DestructureDecl(destructure_decl_args)
+        )
+    }
+
     pub fn parse_var_assign(&self, lexer: &mut Lexer) -> ParseResult<Statement> {
         if lexer.token != Token::Text {
             return Err(ParseError::new(ParseErrorType::UnknownStatement, lexer.range()));
@@ -1277,6 +2119,11 @@ impl Parser {
                 let lhs = operand_stack.pop_front().unwrap();
                 Expression::Division(Box::new(lhs), Box::new(rhs))
             },
+            Token::Modulo => {
+                let rhs = operand_stack.pop_front().unwrap();
+                let lhs = operand_stack.pop_front().unwrap();
+                Expression::Modulo(Box::new(lhs), Box::new(rhs))
+            },
             Token::Equals => {
                 let rhs = operand_stack.pop_front().unwrap();
                 let lhs = operand_stack.pop_front().unwrap();
@@ -1311,6 +2158,10 @@ impl Parser {
                 let op = operand_stack.pop_front().unwrap();
                 Expression::Not(Box::new(op))
             },
+            Token::Negate => {
+                let op = operand_stack.pop_front().unwrap();
+                Expression::Negation(Box::new(op))
+            },
             Token::Tilde => {
                 let op = operand_stack.pop_front().unwrap();
                 Expression::Deref(Box::new(op))
@@ -1359,6 +2210,16 @@ impl Parser {
                 let lhs = operand_stack.pop_front().unwrap();
                 Expression::Or(Box::new(lhs), Box::new(rhs))
             },
+            Token::DoubleDot => {
+                let rhs = operand_stack.pop_front().unwrap();
+                let lhs = operand_stack.pop_front().unwrap();
+                Expression::Range(Box::new(lhs), Box::new(rhs), false)
+            },
+            Token::DoubleDotEquals => {
+                let rhs = operand_stack.pop_front().unwrap();
+                let lhs = operand_stack.pop_front().unwrap();
+                Expression::Range(Box::new(lhs), Box::new(rhs), true)
+            },
             _ => {
                 return Err(ParseError::new(ParseErrorType::UnsupportedExpression, lexer.range()));
             }
@@ -1453,6 +2314,26 @@ impl Parser {
         Ok(ret)
     }
 
+    /// Tries to parse an enum value, e.g. "Color::Red".
+    /// Any path that is not a call or container instance is one.
+    pub fn try_parse_enum_value(&self, lexer: &mut Lexer) -> ParseResult<Expression> {
+        let lexer_backup = lexer.clone();
+
+        let path = self.parse_mod_path(lexer)?;
+
+        let (enum_name, variant) = match path.rfind("::") {
+            Some(pos) => (&path[..pos], &path[pos + 2..]),
+            None => {
+                *lexer = lexer_backup;
+                return make_parse_error!(lexer, ParseErrorType::ExpectedVariantName);
+            }
+        };
+
+        Ok(
+            Expression::EnumValue(String::from(enum_name), String::from(variant))
+        )
+    }
+
     pub fn try_parse_call_expr(&self, lexer: &mut Lexer) -> ParseResult<Expression> {
         let lexer_backup = lexer.clone(); // Create lexer backup for backtracking
 
@@ -1493,6 +2374,280 @@ impl Parser {
         )
     }
 
+    /// Parses the "[start..end]" part of a slice expression, both bounds are optional.
+    /// An inclusive "[start..=end]" needs an end. A single "[index]" is parsed as an index expression instead.
+    pub fn parse_slice_expr(&self, lexer: &mut Lexer, operand: Expression) -> ParseResult<Expression> {
+        // Swallow "["
+        lexer.advance();
+
+        let mut start_expr = None;
+        if lexer.token != Token::DoubleDot && lexer.token != Token::DoubleDotEquals {
+            start_expr = Some(Box::new(self.parse_expr(lexer, &[Token::DoubleDot, Token::DoubleDotEquals, Token::CloseBracket])?));
+        }
+
+        if lexer.token == Token::CloseBracket {
+            if let Some(index_expr) = start_expr {
+                // Swallow "]"
+                lexer.advance();
+
+                return Ok(
+                    Expression::Index(Box::new(operand), index_expr)
+                );
+            }
+        }
+
+        let inclusive = lexer.token == Token::DoubleDotEquals;
+        if lexer.token != Token::DoubleDot && !inclusive {
+            return make_parse_error!(lexer, ParseErrorType::UnsupportedExpression);
+        }
+
+        // Swallow ".." or "..="
+        lexer.advance();
+
+        let mut end_expr = None;
+        if lexer.token != Token::CloseBracket {
+            end_expr = Some(Box::new(self.parse_expr(lexer, &[Token::CloseBracket])?));
+        }
+        if inclusive {
+            let end = end_expr
+                .ok_or_else(|| ParseError::new(ParseErrorType::UnsupportedExpression, lexer.range()))?;
+            end_expr = Some(Box::new(Expression::Addition(end, Box::new(Expression::IntLiteral(1)))));
+        }
+
+        if lexer.token != Token::CloseBracket {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedCloseBracket);
+        }
+
+        // Swallow "]"
+        lexer.advance();
+
+        Ok(
+            Expression::Slice(Box::new(operand), start_expr, end_expr)
+        )
+    }
+
+    /// Parses the element accesses following a tuple operand, e.g. ".0"
+    pub fn parse_tuple_index_expr(&self, lexer: &mut Lexer, operand: Expression) -> ParseResult<Expression> {
+        // Swallow "."
+        lexer.advance();
+
+        // "pair.0.1" is lexed as the float literal "0.1" after the first "."
+        let mut expr = operand;
+        for index in lexer.slice().split('.') {
+            let index = index.parse::<usize>()
+                .map_err(|_| ParseError::new(ParseErrorType::InvalidTupleIndex, lexer.range()))?;
+            expr = Expression::TupleIndex(Box::new(expr), index);
+        }
+
+        // Swallow index
+        lexer.advance();
+
+        Ok(expr)
+    }
+
+    /// Parses an array literal, e.g. "[1, 2, 3]"
+    pub fn parse_array_literal(&self, lexer: &mut Lexer) -> ParseResult<Expression> {
+        // Swallow "["
+        lexer.advance();
+
+        let mut elem_exprs = Vec::new();
+        while lexer.token != Token::CloseBracket {
+            elem_exprs.push(self.parse_expr(lexer, &[Token::Comma, Token::CloseBracket])?);
+            if lexer.token == Token::Comma {
+                // Swallow ","
+                lexer.advance();
+            } else if lexer.token != Token::CloseBracket {
+                return make_parse_error!(lexer, ParseErrorType::ExpectedCloseBracket);
+            }
+        }
+
+        if elem_exprs.is_empty() {
+            return make_parse_error!(lexer, ParseErrorType::EmptyArrayLiteral);
+        }
+
+        // Swallow "]"
+        lexer.advance();
+
+        Ok(Expression::Array(elem_exprs))
+    }
+
+    /// Parses a parenthesized expression, which is a tuple if it contains a ","
+    pub fn parse_paran_expr(&self, lexer: &mut Lexer) -> ParseResult<Expression> {
+        // Swallow "("
+        lexer.advance();
+
+        let first_expr = self.parse_expr(lexer, &[Token::Comma, Token::CloseParan])?;
+        let mut exprs = vec![first_expr];
+        let mut is_tuple = false;
+        while lexer.token == Token::Comma {
+            is_tuple = true;
+            // Swallow ","
+            lexer.advance();
+            // Allow a trailing ","
+            if lexer.token == Token::CloseParan {
+                break;
+            }
+            exprs.push(self.parse_expr(lexer, &[Token::Comma, Token::CloseParan])?);
+        }
+
+        if lexer.token != Token::CloseParan {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedCloseParan);
+        }
+
+        // Swallow ")"
+        lexer.advance();
+
+        if is_tuple {
+            Ok(Expression::Tuple(exprs))
+        } else {
+            Ok(exprs.remove(0))
+        }
+    }
+
+    /// Parses a lambda expression "|x: int, y: int| ~ int { ... }", "||" starts one without arguments
+    pub fn parse_lambda_expr(&self, lexer: &mut Lexer) -> ParseResult<Expression> {
+        let arguments = if lexer.token == Token::Or {
+            // Swallow "||"
+            lexer.advance();
+            Vec::new()
+        } else {
+            // Swallow "|"
+            lexer.advance();
+            let arguments = self.parse_fn_args(lexer)?;
+            if lexer.token != Token::Pipe {
+                return make_parse_error!(lexer, ParseErrorType::ExpectedPipe);
+            }
+            // Swallow "|"
+            lexer.advance();
+            arguments
+        };
+
+        let returns = if lexer.token == Token::Tilde {
+            // Swallow "~"
+            lexer.advance();
+            self.parse_type(lexer)?
+        } else {
+            Type::Void
+        };
+
+        if lexer.token != Token::OpenBlock {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedOpenBlock);
+        }
+        // Swallow "{"
+        lexer.advance();
+        let statements = self.parse_statement_list(lexer)?;
+        if lexer.token != Token::CloseBlock {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedCloseBlock);
+        }
+        // Swallow "}"
+        lexer.advance();
+
+        Ok(
+            Expression::Lambda(Box::new(FunctionDeclArgs {
+                name: String::new(),
+                generics: Vec::new(),
+                arguments: arguments,
+                returns: returns,
+                code_block: Some(statements),
+                public: false
+            }))
+        )
+    }
+
+    /// Parses a conditional expression, e.g. "if x > 0 { x } else { 0 }".
+    /// The else branch is required, it may be another conditional.
+    pub fn parse_conditional_expr(&self, lexer: &mut Lexer) -> ParseResult<Expression> {
+        if lexer.token != Token::If {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedIf);
+        }
+        // Swallow "if"
+        lexer.advance();
+
+        let cond_expr = self.parse_expr(lexer, &[Token::OpenBlock])?;
+        let then_expr = self.parse_conditional_branch(lexer)?;
+
+        if lexer.token != Token::Else {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedElse);
+        }
+        // Swallow "else"
+        lexer.advance();
+
+        let else_expr = if lexer.token == Token::If {
+            self.parse_conditional_expr(lexer)?
+        } else {
+            self.parse_conditional_branch(lexer)?
+        };
+
+        Ok(
+            Expression::Conditional(Box::new(cond_expr), Box::new(then_expr), Box::new(else_expr))
+        )
+    }
+
+    /// Parses the "{ value }" branch of a conditional expression
+    fn parse_conditional_branch(&self, lexer: &mut Lexer) -> ParseResult<Expression> {
+        // A branch without statements is just its value
+        match self.parse_block_expr(lexer)? {
+            Expression::Block(stmt_list, value_expr) if stmt_list.is_empty() => Ok(*value_expr),
+            expr => Ok(expr)
+        }
+    }
+
+    /// Parses a block expression, e.g. "{ var y = x * 2; y + 1 }".
+    /// The last expression of the block is left without ";", it is the value of the block.
+    pub fn parse_block_expr(&self, lexer: &mut Lexer) -> ParseResult<Expression> {
+        if lexer.token != Token::OpenBlock {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedOpenBlock);
+        }
+        // Swallow "{"
+        lexer.advance();
+
+        let (stmt_list, value_expr) = self.parse_block_body(lexer)?;
+
+        if lexer.token != Token::CloseBlock {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedCloseBlock);
+        }
+        let value_expr = match value_expr {
+            Some(value_expr) => value_expr,
+            None => return make_parse_error!(lexer, ParseErrorType::ExpectedBlockValue)
+        };
+        // Swallow "}"
+        lexer.advance();
+
+        Ok(
+            Expression::Block(stmt_list, Box::new(value_expr))
+        )
+    }
+
+    /// Parses a sizeof(Type) or typeof(expr) expression
+    pub fn parse_intrinsic_expr(&self, lexer: &mut Lexer) -> ParseResult<Expression> {
+        let intrinsic = lexer.token.clone();
+
+        // Swallow "sizeof" or "typeof"
+        lexer.advance();
+
+        if lexer.token != Token::OpenParan {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedOpenParan);
+        }
+
+        // Swallow "("
+        lexer.advance();
+
+        let expr = if intrinsic == Token::SizeOf {
+            Expression::SizeOf(self.parse_type(lexer)?)
+        } else {
+            Expression::TypeOf(Box::new(self.parse_expr(lexer, &[Token::CloseParan])?))
+        };
+
+        if lexer.token != Token::CloseParan {
+            return make_parse_error!(lexer, ParseErrorType::ExpectedCloseParan);
+        }
+
+        // Swallow ")"
+        lexer.advance();
+
+        Ok(expr)
+    }
+
     pub fn parse_expr(&self, lexer: &mut Lexer, delims: &[Token]) -> ParseResult<Expression> {
         let mut operator_stack = VecDeque::new();
         let mut operand_stack = VecDeque::new();
@@ -1501,6 +2656,9 @@ impl Parser {
         let mut open_paran_count = 0;
         let mut dec_paran_count = false;
 
+        // Whether the next token starts an operand, used to tell unary from binary "-"
+        let mut expects_operand = true;
+
         while lexer.token != Token::End &&
             lexer.token != Token::Error {
 
@@ -1517,42 +2675,94 @@ impl Parser {
             if lexer.token == Token::True {
                 let expr = Expression::BoolLiteral(true);
                 operand_stack.push_front(expr);
+                expects_operand = false;
             }
 
             if lexer.token == Token::False {
                 let expr = Expression::BoolLiteral(false);
                 operand_stack.push_front(expr);
+                expects_operand = false;
+            }
+
+            if lexer.token == Token::Null {
+                operand_stack.push_front(Expression::NullLiteral);
+                expects_operand = false;
             }
             
+            if lexer.token == Token::SizeOf || lexer.token == Token::TypeOf {
+                let expr = self.parse_intrinsic_expr(lexer)?;
+                operand_stack.push_front(expr);
+                expects_operand = false;
+            }
+
             if lexer.token == Token::Text {
                 let expr;
                 let call_expr_res = self.try_parse_call_expr(lexer);
                 if call_expr_res.is_ok() {
-                    expr = call_expr_res.unwrap();
+                    expr = match call_expr_res.unwrap() {
+                        // "len(x)", "char_at(x, i)" and "resume(x)" are intrinsics, unless they are called as member functions
+                        Expression::Call(ref fn_name, ref mut args)
+                            if fn_name == "len" && args.len() == 1 && operator_stack.front() != Some(&Token::Dot) => {
+                            Expression::Len(Box::new(args.remove(0)))
+                        },
+                        Expression::Call(ref fn_name, ref mut args)
+                            if fn_name == "char_at" && args.len() == 2 && operator_stack.front() != Some(&Token::Dot) => {
+                            let base_expr = args.remove(0);
+                            Expression::CharAt(Box::new(base_expr), Box::new(args.remove(0)))
+                        },
+                        Expression::Call(ref fn_name, ref mut args)
+                            if fn_name == "resume" && args.len() == 1 && operator_stack.front() != Some(&Token::Dot) => {
+                            Expression::Resume(Box::new(args.remove(0)))
+                        },
+                        call_expr => call_expr
+                    };
                 } else {
                     let cont_inst_expr_res = self.try_parse_cont_instance(lexer);
                     if cont_inst_expr_res.is_ok() {
                         expr = cont_inst_expr_res.unwrap();
                     } else {
-                        let mut var_name = String::from(lexer.slice());
-                        expr = Expression::Variable(var_name);
+                        if let Ok(enum_value) = self.try_parse_enum_value(lexer) {
+                            expr = enum_value;
+                        } else {
+                            let var_name = String::from(lexer.slice());
+                            expr = Expression::Variable(var_name);
+                        }
                     }
                 }
                 operand_stack.push_front(expr);
+                expects_operand = false;
             }
 
             if lexer.token == Token::IntLiteral {
-                let int = String::from(lexer.slice()).parse::<i64>()
-                    .map_err(|_| ParseError::new(ParseErrorType::Unknown, lexer.range()))?;
+                let int = parse_int_literal(lexer.slice())
+                    .ok_or_else(|| ParseError::new(ParseErrorType::InvalidIntLiteral, lexer.range()))?;
                 let expr = Expression::IntLiteral(int);
                 operand_stack.push_front(expr);
+                expects_operand = false;
             }
 
             if lexer.token == Token::FloatLiteral {
-                let float = String::from(lexer.slice()).parse::<f32>()
+                let float = lexer.slice().trim_end_matches('f').parse::<f32>()
                     .map_err(|_| ParseError::new(ParseErrorType::Unknown, lexer.range()))?;
                 let expr = Expression::FloatLiteral(float);
                 operand_stack.push_front(expr);
+                expects_operand = false;
+            }
+
+            if lexer.token == Token::DoubleLiteral {
+                let double = lexer.slice().trim_end_matches('d').parse::<f64>()
+                    .map_err(|_| ParseError::new(ParseErrorType::Unknown, lexer.range()))?;
+                let expr = Expression::DoubleLiteral(double);
+                operand_stack.push_front(expr);
+                expects_operand = false;
+            }
+
+            if lexer.token == Token::CharLiteral {
+                let character = parse_char_literal(lexer.slice())
+                    .ok_or_else(|| ParseError::new(ParseErrorType::InvalidCharLiteral, lexer.range()))?;
+                let expr = Expression::CharLiteral(character);
+                operand_stack.push_front(expr);
+                expects_operand = false;
             }
 
             if lexer.token == Token::StringLiteral {
@@ -1560,9 +2770,95 @@ impl Parser {
                 //println!("Parsing string literal {}", string);
                 let expr = Expression::StringLiteral(string);
                 operand_stack.push_front(expr);
+                expects_operand = false;
+            }
+
+            if lexer.token == Token::OpenBracket && expects_operand {
+                let expr = self.parse_array_literal(lexer)?;
+                operand_stack.push_front(expr);
+                expects_operand = false;
+            }
+
+            if lexer.token == Token::If && expects_operand {
+                // The branches are parsed on their own, "}" is already swallowed
+                let expr = self.parse_conditional_expr(lexer)?;
+                operand_stack.push_front(expr);
+                expects_operand = false;
+                continue;
+            }
+
+            if lexer.token == Token::OpenBlock && expects_operand {
+                let expr = self.parse_block_expr(lexer)?;
+                operand_stack.push_front(expr);
+                expects_operand = false;
+                continue;
+            }
+
+            if (lexer.token == Token::Pipe || lexer.token == Token::Or) && expects_operand {
+                // The lambda is parsed on its own, "}" is already swallowed
+                let expr = self.parse_lambda_expr(lexer)?;
+                operand_stack.push_front(expr);
+                expects_operand = false;
+                continue;
+            }
+
+            while lexer.token == Token::OpenBracket || is_tuple_index(lexer) {
+                // Slices and tuple indices bind stronger than member accesses
+                while operator_stack.front() == Some(&Token::Dot) {
+                    let expr = self.parse_expr_push(lexer, &mut operand_stack, &mut operator_stack)?;
+                    operand_stack.push_front(expr);
+                }
+                let operand = operand_stack.pop_front()
+                    .ok_or_else(|| ParseError::new(ParseErrorType::UnsupportedExpression, lexer.range()))?;
+                let expr = if lexer.token == Token::OpenBracket {
+                    self.parse_slice_expr(lexer, operand)?
+                } else {
+                    self.parse_tuple_index_expr(lexer, operand)?
+                };
+                operand_stack.push_front(expr);
+                expects_operand = false;
+            }
+
+            if lexer.token == Token::QuestionMark && !expects_operand {
+                // "?" binds like a call, so member calls are reduced first
+                while operator_stack.front() == Some(&Token::Dot) {
+                    let expr = self.parse_expr_push(lexer, &mut operand_stack, &mut operator_stack)?;
+                    operand_stack.push_front(expr);
+                }
+                let operand = operand_stack.pop_front()
+                    .ok_or_else(|| ParseError::new(ParseErrorType::UnsupportedExpression, lexer.range()))?;
+                // Swallow "?"
+                lexer.advance();
+                operand_stack.push_front(Expression::Try(Box::new(operand)));
+            }
+
+            while lexer.token == Token::As {
+                // Casts bind weaker than unary operators, but stronger than binary ones
+                while matches!(operator_stack.front(), Some(op) if *op != Token::OpenParan && op_prec(op) >= op_prec(&Token::Negate)) {
+                    let expr = self.parse_expr_push(lexer, &mut operand_stack, &mut operator_stack)?;
+                    operand_stack.push_front(expr);
+                }
+                let operand = operand_stack.pop_front()
+                    .ok_or_else(|| ParseError::new(ParseErrorType::UnsupportedExpression, lexer.range()))?;
+                // Swallow "as"
+                lexer.advance();
+                let cast_type = self.parse_type(lexer)?;
+                operand_stack.push_front(Expression::Cast(Box::new(operand), cast_type));
+                expects_operand = false;
+            }
+
+            if (lexer.token == Token::Increment || lexer.token == Token::Decrement) && !delims.contains(&lexer.token) {
+                // Increments are statements, they have no value
+                return make_parse_error!(lexer, ParseErrorType::UnsupportedExpression);
             }
 
             if is_op(&lexer.token) {
+                // A "-" without a left operand negates
+                let op_token = if lexer.token == Token::Minus && expects_operand {
+                    Token::Negate
+                } else {
+                    lexer.token.clone()
+                };
                 loop {
                     let op_opt = operator_stack.get(0);
                     if op_opt.is_none() {
@@ -1573,23 +2869,34 @@ impl Parser {
                         break; // Break if operator is a "("
                     }
 
-                    if !(op_prec(&lexer.token) - op_prec(op) < 0) &&
-                        !(op_prec(&lexer.token) == op_prec(op) && !is_op_right_assoc(op)) {
+                    if !(op_prec(&op_token) - op_prec(op) < 0) &&
+                        !(op_prec(&op_token) == op_prec(op) && !is_op_right_assoc(op)) {
                         break; // Break if there is no operator of greater precedence on the stack or of equal precedence and right assoc
                     }
 
                     let expr = self.parse_expr_push(lexer, &mut operand_stack, &mut operator_stack)?;
                     operand_stack.push_front(expr);
                 }
-                operator_stack.push_front(lexer.token.clone());
+                operator_stack.push_front(op_token);
+                expects_operand = true;
+            }
+
+            if lexer.token == Token::OpenParan && expects_operand {
+                // Groups and tuples are parsed on their own, ")" is already swallowed
+                let expr = self.parse_paran_expr(lexer)?;
+                operand_stack.push_front(expr);
+                expects_operand = false;
+                continue;
             }
 
             if lexer.token == Token::OpenParan {
                 operator_stack.push_front(lexer.token.clone());
                 open_paran_count += 1;
+                expects_operand = true;
             }
 
             if lexer.token == Token::CloseParan {
+                expects_operand = false;
                 let mut pop = false;               
                 while operator_stack.len() > 0 {
                     {
@@ -1624,7 +2931,7 @@ impl Parser {
                 dec_paran_count = false;
                 open_paran_count -= 1;
             }
-            
+
             lexer.advance();
         }
 
@@ -0,0 +1,141 @@
+use crate::{
+    parser::{
+        parser::Parser,
+        ast::Declaration
+    },
+    engine::{
+        EngineError,
+        EngineResult
+    }
+};
+
+use std::{
+    path::{
+        Path,
+        PathBuf
+    },
+    fs::read_to_string,
+    mem::take
+};
+
+/// Default directory file imports of code not loaded from a file are relative to
+pub const DEFAULT_SOURCES_ROOT: &str = ".";
+
+/// Resolves file imports and module files to the script files they name.
+///
+/// `import "lib/utils.pgs";` loads the file relative to the importing file,
+/// or relative to the sources root for code that was not loaded from a file.
+/// The file is compiled as the module `utils` of the importing module,
+/// `import "lib/utils.pgs" = helpers;` names the module `helpers` instead.
+///
+/// `mod: shapes;` loads `shapes.pgs`, or `shapes/mod.pgs` if there is none,
+/// from the same directory. Module files of `shapes/mod.pgs` are in `shapes/`.
+pub struct SourceResolver {
+    root: PathBuf
+}
+
+impl SourceResolver {
+    /// Creates a new resolver for the given sources root
+    pub fn new<P: AsRef<Path>>(root: P) -> SourceResolver {
+        SourceResolver {
+            root: root.as_ref().to_path_buf()
+        }
+    }
+
+    /// Gets the sources root
+    pub fn get_root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Sets the sources root
+    pub fn set_root<P: AsRef<Path>>(&mut self, root: P) {
+        self.root = root.as_ref().to_path_buf();
+    }
+
+    /// Replaces the file imports and module files of a declaration list (and of the files it loads) with their modules
+    pub fn resolve(&self, decl_list: &mut [Declaration]) -> EngineResult<()> {
+        let mut loading = Vec::new();
+        self.resolve_in(decl_list, &self.root, &mut loading)
+    }
+
+    /// Like resolve(), for the declaration list of the given script file
+    pub fn resolve_file(&self, decl_list: &mut [Declaration], file_path: &Path) -> EngineResult<()> {
+        let file_path = file_path.canonicalize()
+            .map_err(|_| Box::new(EngineError::SourceNotReadable(file_path.to_path_buf())))?;
+        let dir = file_path.parent()
+            .unwrap_or(&self.root)
+            .to_path_buf();
+        let mut loading = vec![ file_path ];
+        self.resolve_in(decl_list, &dir, &mut loading)
+    }
+
+    /// Resolves the file imports and module files of a declaration list relative to a directory.
+    /// The files being loaded are kept on a stack, importing one of them again is a cycle.
+    fn resolve_in(&self, decl_list: &mut [Declaration], dir: &Path, loading: &mut Vec<PathBuf>) -> EngineResult<()> {
+        for decl in decl_list.iter_mut() {
+            match decl {
+                Declaration::FileImport(import_path, mod_name) => {
+                    let file_decls = self.load_file(&dir.join(import_path.as_str()), loading)?;
+                    let mod_name = take(mod_name);
+                    *decl = Declaration::FileModule(mod_name, file_decls);
+                },
+                Declaration::ModuleFile(mod_name) |
+                Declaration::PubModuleFile(mod_name) => {
+                    let file_path = Self::find_module_file(dir, mod_name)
+                        .ok_or_else(|| Box::new(EngineError::ModuleFileNotFound(dir.join(format!("{}.pgs", mod_name)))))?;
+                    let file_decls = self.load_file(&file_path, loading)?;
+                    let mod_name = take(mod_name);
+                    *decl = match decl {
+                        Declaration::PubModuleFile(_) => Declaration::PubFileModule(mod_name, file_decls),
+                        _ => Declaration::FileModule(mod_name, file_decls)
+                    };
+                },
+                Declaration::Module(_, mod_decl_list) |
+                Declaration::PubModule(_, mod_decl_list) => {
+                    self.resolve_in(mod_decl_list, dir, loading)?;
+                },
+                _ => {}
+            };
+        }
+
+        Ok(())
+    }
+
+    /// Finds the file of a module declared without a body
+    fn find_module_file(dir: &Path, mod_name: &str) -> Option<PathBuf> {
+        let file_path = dir.join(format!("{}.pgs", mod_name));
+        if file_path.is_file() {
+            return Some(file_path);
+        }
+        let file_path = dir.join(mod_name).join("mod.pgs");
+        if file_path.is_file() {
+            return Some(file_path);
+        }
+        None
+    }
+
+    /// Parses a script file and resolves its own file imports and module files
+    fn load_file(&self, file_path: &Path, loading: &mut Vec<PathBuf>) -> EngineResult<Vec<Declaration>> {
+        let file_path = file_path.canonicalize()
+            .map_err(|_| Box::new(EngineError::SourceNotReadable(file_path.to_path_buf())))?;
+
+        if loading.contains(&file_path) {
+            return Err(Box::new(EngineError::ImportCycle(file_path)));
+        }
+
+        let code = read_to_string(&file_path)
+            .map_err(|_| Box::new(EngineError::SourceNotReadable(file_path.clone())))?;
+        let parser = Parser::new(code);
+        let mut file_decls = parser.parse_root_decl_list()
+            .map_err(|p| Box::new(EngineError::SourceParseError(file_path.clone(), p)))?;
+
+        let file_dir = file_path.parent()
+            .unwrap_or(&self.root)
+            .to_path_buf();
+        loading.push(file_path);
+        self.resolve_in(&mut file_decls, &file_dir, loading)?;
+        loading.pop();
+
+        Ok(file_decls)
+    }
+}
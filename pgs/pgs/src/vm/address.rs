@@ -1,6 +1,12 @@
+use crate::{
+    vm::{
+        core::CoreError
+    }
+};
+
 use std::{
     convert::{
-        From,
+        TryFrom,
         Into
     }
 };
@@ -17,8 +23,7 @@ pub enum AddressType {
     Program,
     Stack,
     Heap,
-    Foreign,
-    Swap
+    Foreign
 }
 
 impl Address {
@@ -27,7 +32,6 @@ impl Address {
             AddressType::Program => 0,
             AddressType::Stack => 1,
             AddressType::Heap => 2,
-            AddressType::Swap => 3,
             AddressType::Foreign => 4
         };
         // Shift type to the 3 left most bits
@@ -43,35 +47,47 @@ impl Address {
     }
 
     pub fn with_offset(mut self, offset: i16) -> Address {
+        // Wraps instead of underflowing, the result is then caught as out of bounds
         if offset < 0 {
-            self.real_address -= offset.abs() as u64;
+            self.real_address = self.real_address.wrapping_sub(offset.unsigned_abs() as u64);
+            self.raw_address = self.raw_address.wrapping_sub(offset.unsigned_abs() as u64);
         } else {
-            self.real_address += offset as u64;
+            self.real_address = self.real_address.wrapping_add(offset as u64);
+            self.raw_address = self.raw_address.wrapping_add(offset as u64);
         }
         self
     } 
+
+    /// Checks if a raw address points into the heap
+    pub fn is_heap(raw: u64) -> bool {
+        raw >> 61 == 2
+    }
+
+    /// Removes the type from a raw address, i.e. its 3 left most bits
+    pub fn get_real_address(raw: u64) -> u64 {
+        (raw << 3) >> 3
+    }
 }
 
-impl From<u64> for Address {
-    fn from(raw: u64) -> Address {
+impl TryFrom<u64> for Address {
+    type Error = CoreError;
+
+    /// Fails with InvalidAddress if the type bits do not name a memory
+    fn try_from(raw: u64) -> Result<Address, CoreError> {
         let type_raw = raw >> 61;
         let address_type = match type_raw {
             0 => AddressType::Program,
             1 => AddressType::Stack,
             2 => AddressType::Heap,
-            3 => AddressType::Swap,
             4 => AddressType::Foreign,
-            _ => panic!("Address is not formatted correctly!")
+            _ => return Err(CoreError::InvalidAddress(raw))
         };
-        // Remove 2 left most bits, which are the type
-        let mut real_address = raw << 3;
-        real_address = real_address >> 3;
 
-        Address {
+        Ok(Address {
             raw_address: raw,
-            real_address: real_address,
+            real_address: Address::get_real_address(raw),
             address_type: address_type
-        }
+        })
     }
 }
 
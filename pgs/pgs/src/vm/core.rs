@@ -1,6 +1,7 @@
 use super::{
     is::{
-        Opcode
+        Opcode,
+        Operand
     },
     address::{
         Address,
@@ -8,12 +9,35 @@ use super::{
     },
     register::{
         Register,
-        RegisterAccess
+        RegisterAccess,
+        RegisterFile,
+        SP_INDEX
+    },
+    profile::{
+        Profile,
+        ProfileReport
+    },
+    interrupt::{
+        CoreHandle,
+        Interrupt
+    }
+};
+#[cfg(feature = "jit")]
+use super::{
+    jit::{
+        Jit,
+        DEFAULT_JIT_THRESHOLD,
+        JIT_REGISTER_COUNT
     }
 };
+#[cfg(feature = "extensions")]
+use crate::extension::Extension;
 use crate::{
     codegen::{
-        program::Program,
+        program::{
+            Program,
+            SourcePosition
+        },
         uid_generator::UIDGenerator
     },
     api::{
@@ -27,9 +51,11 @@ use std::{
     collections::{
         VecDeque,
         HashMap,
-        HashSet
+        HashSet,
+        BTreeMap
     },
     mem::{
+        self,
         size_of,
         size_of_val
     },
@@ -47,7 +73,14 @@ use std::{
         Formatter,
         Result as FmtResult
     },
+    io::Write,
     error::Error,
+    time::Instant,
+    any::{
+        type_name,
+        Any,
+        TypeId
+    },
     sync::{
         Arc,
         Mutex
@@ -76,20 +109,170 @@ pub type CoreResult<T> = Result<T, CoreError>;
 
 pub const STACK_GROW_INCREMENT: usize = 1024;
 pub const STACK_GROW_THRESHOLD: usize = 64;
-pub const SWAP_SPACE_SIZE: usize = 64;
+/// Stack space a call frame, i.e. the return address and saved frame pointer, counts against
+pub const CALL_FRAME_SIZE: usize = 16;
+/// Default number of instructions a task runs before the next one gets its turn
+pub const DEFAULT_TASK_SLICE: u64 = 100;
+/// Id of the task running the function a run started with
+pub const MAIN_TASK_ID: u64 = 0;
+
+/// Execution context of a task, swapped with the one of the core when switching tasks
+struct Task {
+    id: u64,
+    stack: Vec<u8>,
+    registers: RegisterFile,
+    call_stack: VecDeque<(usize, u64, Option<u64>)>,
+    current_fn: Option<u64>,
+    resumed_coroutines: Vec<u64>
+}
+
+/// A coroutine, created by calling a function returning "co<T>"
+struct Coroutine {
+    /// Context of the coroutine while it is suspended, of the one that resumed it while it runs.
+    /// None once its function returned.
+    context: Option<Task>,
+    running: bool,
+    /// Size of the values the one resuming it expects
+    value_size: usize
+}
 
 pub struct Core {
     stack: Vec<u8>,
     heap: Vec<u8>,
+    /// Live heap allocations, sorted by their start
     heap_pointers: Vec<Range<usize>>,
+    /// Freed ranges inside the heap, sorted and never adjacent to each other or to the end of the heap
+    heap_free_list: Vec<Range<usize>>,
+    /// Reference counts of heap allocations, by address. Allocations without an entry have a count of 1.
+    heap_refcounts: HashMap<u64, u64>,
     foreign_pointers: HashMap<u64, u64>,
-    foreign_function_uids: HashSet<u64>,
-    swap: Vec<u8>,
-    program: Option<Program>,
-    call_stack: VecDeque<usize>,
-    registers: [Register; 16],
-    ip: Register,
-    sp: Register,
+    /// Name of the item type of every foreign pointer, checked by get_foreign_object()
+    foreign_types: HashMap<u64, &'static str>,
+    /// Full names of the foreign functions by uid, e.g. "root::std::print"
+    foreign_function_names: HashMap<u64, String>,
+    /// Host values foreign functions can borrow, one per type. Not shared with isolates.
+    contexts: ContextMap,
+    /// The loaded program, shared with the isolates created from this core
+    program: Option<Arc<Program>>,
+    /// Data segment of the loaded program. Each core writes the statics to its own copy.
+    data: Vec<u8>,
+    /// Return addresses, frame pointers and function uids of the callers, innermost first
+    call_stack: VecDeque<(usize, u64, Option<u64>)>,
+    /// Uid of the function executing in the innermost frame, if it was entered through a function
+    current_fn: Option<u64>,
+    /// General purpose registers, stack, instruction and frame pointers.
+    /// The frame pointer is the base of the current stack frame, the stack pointer at function entry.
+    registers: RegisterFile,
+    instruction_count: u64,
+    max_instructions: Option<u64>,
+    /// Instructions left to execute across runs, unlimited if None
+    fuel: Option<u64>,
+    max_heap: Option<usize>,
+    foreign_error: Option<CoreError>,
+    foreign_blocked: bool,
+    /// Set while tick() executes instructions, the only time foreign calls may block
+    ticking: bool,
+    running: bool,
+    run_state: RunState,
+    /// Code offsets to pause at in run_to_breakpoint()
+    breakpoints: HashSet<usize>,
+    /// Id of the running task
+    task_id: u64,
+    next_task_id: u64,
+    /// Spawned tasks waiting for their turn, in scheduling order
+    tasks: VecDeque<Task>,
+    /// Context of the main task once its function returned while spawned tasks are left
+    main_task: Option<Task>,
+    task_slice: u64,
+    /// Instructions the running task executed since it got its turn
+    task_slice_used: u64,
+    task_stack_size: usize,
+    /// Coroutines by handle, they outlive runs so the host can resume them
+    coroutines: HashMap<u64, Coroutine>,
+    next_coroutine_id: u64,
+    /// Handles of the coroutines resumed by the running task, innermost last
+    resumed_coroutines: Vec<u64>,
+    trace_hook: Option<TraceHook>,
+    /// Operand bytes read by the current instruction, only collected while tracing
+    trace_operands: Option<Vec<u8>>,
+    /// Counters per opcode and function, only collected while profiling
+    profile: Option<Profile>,
+    /// Pause and cancel requests of the handles of this core
+    interrupt: Arc<Interrupt>,
+    /// Calls after which a function is compiled to native code, the jit is disabled if None
+    #[cfg(feature = "jit")]
+    jit_threshold: Option<u64>,
+    /// Created once the first function gets hot
+    #[cfg(feature = "jit")]
+    jit: Option<Jit>,
+    stats: CoreStats,
+    /// Extension libraries the closures of the program may live in, shared with the isolates.
+    /// Declared last, so the program is dropped first.
+    #[cfg(feature = "extensions")]
+    extensions: Vec<Arc<Extension>>
+}
+
+/// An executed instruction, passed to the trace hook
+#[derive(PartialEq, Debug, Clone)]
+pub struct TraceEntry {
+    /// Code offset of the instruction
+    pub ip: usize,
+    pub opcode: Opcode,
+    /// Raw bytes of the operands read by the instruction
+    pub operands: Vec<u8>,
+    /// Stack size in bytes after executing the instruction
+    pub sp: usize
+}
+
+impl Display for TraceEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{:>6}: {:?} {:?} sp={}", self.ip, self.opcode, self.operands, self.sp)
+    }
+}
+
+/// Host values set with Core::set_context(), by their type
+pub type ContextMap = HashMap<TypeId, Box<dyn Any + Send>>;
+
+/// Callback invoked with every executed instruction while tracing
+pub type TraceHook = Box<dyn FnMut(&TraceEntry) + Send>;
+
+/// State of the core after a tick
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum TickState {
+    /// The instruction budget was used up, the script continues on the next tick
+    Yielded,
+    /// A foreign function is waiting for the host, it is called again on the next tick
+    Blocked,
+    /// The script has finished running
+    Finished
+}
+
+/// Execution state of the core, as seen by a debugger
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum RunState {
+    /// Instructions are being executed
+    Running,
+    /// A function was started and waits to be stepped or continued, e.g. at a breakpoint
+    Paused,
+    /// No function is being executed
+    Halted
+}
+
+/// Execution counters, accumulated over all runs of a core
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct CoreStats {
+    /// Total number of executed instructions
+    pub instructions: u64,
+    /// Highest stack usage in bytes
+    pub peak_stack: usize,
+    /// Number of heap allocations
+    pub heap_allocs: u64,
+    /// Number of freed heap allocations
+    pub heap_frees: u64,
+    /// Number of calls per full foreign function name, e.g. "root::std::print"
+    pub foreign_calls: BTreeMap<String, u64>,
+    /// Number of script function calls run as native code, always 0 without the "jit" feature
+    pub jit_calls: u64
 }
 
 #[derive(Debug)]
@@ -106,48 +289,539 @@ pub enum CoreError {
     InvalidStackPointer,
     InvalidRegister,
     NoReturnValue,
-    Halted(u8)
+    Halted(u8),
+    InstructionLimit,
+    HeapLimit,
+    /// Start, end and length of an out of bounds slice or index
+    OutOfBounds(i64, i64, i64),
+    /// A foreign function was passed a handle it did not create
+    InvalidHandle(u64),
+    /// A foreign function borrows a context of this type, but none was set
+    MissingContext(&'static str),
+    NullValue,
+    UncaughtError(String),
+    /// The address is not the start of a live heap allocation
+    InvalidHeapAddress(u64),
+    /// Address and length of an access outside of its memory
+    MemoryOutOfBounds(u64, usize),
+    /// The address does not point into memory the instruction can access, e.g. its type bits are unknown
+    InvalidAddress(u64),
+    /// Offset of the integer division instruction with a zero divisor
+    DivisionByZero(usize),
+    /// The fuel is used up, the run continues with Core::resume()
+    OutOfFuel,
+    /// The handle is not a coroutine, or the coroutine is already running
+    InvalidCoroutine(u64),
+    /// A value was yielded outside of a coroutine
+    NotInCoroutine,
+    /// The byte index of a string is not the start of a char
+    InvalidCharBoundary(i64),
+    /// The run was cancelled through a CoreHandle
+    Cancelled,
+    /// A foreign function blocked while the script was not run with tick(), e.g. with run_fn()
+    BlockedOutsideTick,
+    /// An error raised by a foreign function
+    FunctionError(Box<FunctionError>),
+    /// An error raised by an instruction, with the source position of its statement
+    AtSource(Box<CoreError>, SourcePosition)
+}
+
+impl CoreError {
+    /// Gets the error without the source position it was raised at
+    pub fn get_cause(&self) -> &CoreError {
+        match self {
+            CoreError::AtSource(cause, _) => cause.get_cause(),
+            _ => self
+        }
+    }
+
+    /// Gets the error raised by a foreign function, if it caused this one
+    pub fn get_function_error(&self) -> Option<&FunctionError> {
+        match self.get_cause() {
+            CoreError::FunctionError(error) => Some(error),
+            _ => None
+        }
+    }
+
+    /// Gets the source position the error was raised at, if the program has a line table
+    pub fn get_position(&self) -> Option<SourcePosition> {
+        match self {
+            CoreError::AtSource(_, position) => Some(*position),
+            _ => None
+        }
+    }
 }
 
 impl Display for CoreError {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        write!(f, "{:?}", self)
+        match self {
+            CoreError::AtSource(cause, position) => write!(f, "{} at {}", cause, position),
+            CoreError::FunctionError(error) => write!(f, "{}", error),
+            _ => write!(f, "{:?}", self)
+        }
     }
 }
 
 impl Error for CoreError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.get_function_error()
+            .map(|error| error as &(dyn Error + 'static))
+    }
+}
+
+/// Returns whether a byte continues a multi-byte UTF-8 char, i.e. no char starts at it
+fn is_utf8_continuation(byte: u8) -> bool {
+    byte & 0xC0 == 0x80
+}
+
+/// Moves an address operand n bytes further, e.g. to a field of the value it points at
+fn offset_addr(addr: (u64, i16), n: i16) -> CoreResult<(u64, i16)> {
+    addr.1.checked_add(n)
+        .map(|offset| (addr.0, offset))
+        .ok_or(CoreError::MemoryOutOfBounds(addr.0, n as usize))
+}
+
+/// Adds a byte index to a raw address, failing if it leaves the memory of the address
+fn index_addr(addr: u64, index: u64) -> CoreResult<u64> {
+    addr.checked_add(index)
+        .filter(|indexed| indexed >> 61 == addr >> 61)
+        .ok_or(CoreError::MemoryOutOfBounds(addr, index as usize))
 }
 
+/// Handler executing an instruction, its operands follow at the instruction pointer
+type OpHandler = fn(&mut Core, usize) -> CoreResult<bool>;
+
+/// Handlers indexed by opcode, see Core::execute()
+const OP_HANDLERS: [OpHandler; 256] = {
+    let mut handlers = [Core::exec_unimplemented as OpHandler; 256];
+    handlers[Opcode::NOOP as usize] = Core::exec_noop;
+    handlers[Opcode::HALT as usize] = Core::exec_halt;
+    handlers[Opcode::MOVB as usize] = Core::exec_movb;
+    handlers[Opcode::MOVF as usize] = Core::exec_movf;
+    handlers[Opcode::MOVD as usize] = Core::exec_movd;
+    handlers[Opcode::MOVI as usize] = Core::exec_movi;
+    handlers[Opcode::MOVA as usize] = Core::exec_mova;
+    handlers[Opcode::MOVB_A as usize] = Core::exec_movb_a;
+    handlers[Opcode::MOVF_A as usize] = Core::exec_movf_a;
+    handlers[Opcode::MOVD_A as usize] = Core::exec_movd_a;
+    handlers[Opcode::MOVI_A as usize] = Core::exec_movi_a;
+    handlers[Opcode::MOVA_A as usize] = Core::exec_mova_a;
+    handlers[Opcode::MOVN_A as usize] = Core::exec_movn_a;
+    handlers[Opcode::MOVB_AR as usize] = Core::exec_movb_ar;
+    handlers[Opcode::MOVF_AR as usize] = Core::exec_movf_ar;
+    handlers[Opcode::MOVD_AR as usize] = Core::exec_movd_ar;
+    handlers[Opcode::MOVI_AR as usize] = Core::exec_movi_ar;
+    handlers[Opcode::MOVA_AR as usize] = Core::exec_mova_ar;
+    handlers[Opcode::MOVU8_AR as usize] = Core::exec_movu8_ar;
+    handlers[Opcode::MOVC_AR as usize] = Core::exec_movc_ar;
+    handlers[Opcode::MOVB_RA as usize] = Core::exec_movb_ra;
+    handlers[Opcode::MOVC_RA as usize] = Core::exec_movc_ra;
+    handlers[Opcode::MOVF_RA as usize] = Core::exec_movf_ra;
+    handlers[Opcode::MOVD_RA as usize] = Core::exec_movd_ra;
+    handlers[Opcode::MOVI_RA as usize] = Core::exec_movi_ra;
+    handlers[Opcode::MOVA_RA as usize] = Core::exec_mova_ra;
+    handlers[Opcode::LDB as usize] = Core::exec_ldb;
+    handlers[Opcode::LDF as usize] = Core::exec_ldf;
+    handlers[Opcode::LDD as usize] = Core::exec_ldd;
+    handlers[Opcode::LDI as usize] = Core::exec_ldi;
+    handlers[Opcode::LDA as usize] = Core::exec_lda;
+    handlers[Opcode::ADDI as usize] = Core::exec_addi;
+    handlers[Opcode::SUBI as usize] = Core::exec_subi;
+    handlers[Opcode::MULI as usize] = Core::exec_muli;
+    handlers[Opcode::DIVI as usize] = Core::exec_divi;
+    handlers[Opcode::MODI as usize] = Core::exec_modi;
+    handlers[Opcode::ADDI_I as usize] = Core::exec_addi_i;
+    handlers[Opcode::SUBI_I as usize] = Core::exec_subi_i;
+    handlers[Opcode::MULI_I as usize] = Core::exec_muli_i;
+    handlers[Opcode::DIVI_I as usize] = Core::exec_divi_i;
+    handlers[Opcode::ADDU as usize] = Core::exec_addu;
+    handlers[Opcode::SUBU as usize] = Core::exec_subu;
+    handlers[Opcode::MULU as usize] = Core::exec_mulu;
+    handlers[Opcode::DIVU as usize] = Core::exec_divu;
+    handlers[Opcode::ADDU_I as usize] = Core::exec_addu_i;
+    handlers[Opcode::SUBU_I as usize] = Core::exec_subu_i;
+    handlers[Opcode::MULU_I as usize] = Core::exec_mulu_i;
+    handlers[Opcode::DIVU_I as usize] = Core::exec_divu_i;
+    handlers[Opcode::ADDF as usize] = Core::exec_addf;
+    handlers[Opcode::ADDD as usize] = Core::exec_addd;
+    handlers[Opcode::SUBF as usize] = Core::exec_subf;
+    handlers[Opcode::SUBD as usize] = Core::exec_subd;
+    handlers[Opcode::MULF as usize] = Core::exec_mulf;
+    handlers[Opcode::MULD as usize] = Core::exec_muld;
+    handlers[Opcode::DIVF as usize] = Core::exec_divf;
+    handlers[Opcode::DIVD as usize] = Core::exec_divd;
+    handlers[Opcode::MODF as usize] = Core::exec_modf;
+    handlers[Opcode::MODD as usize] = Core::exec_modd;
+    handlers[Opcode::ADDF_I as usize] = Core::exec_addf_i;
+    handlers[Opcode::SUBF_I as usize] = Core::exec_subf_i;
+    handlers[Opcode::MULF_I as usize] = Core::exec_mulf_i;
+    handlers[Opcode::DIVF_I as usize] = Core::exec_divf_i;
+    handlers[Opcode::JMP as usize] = Core::exec_jmp;
+    handlers[Opcode::JMPT as usize] = Core::exec_jmpt;
+    handlers[Opcode::JMPF as usize] = Core::exec_jmpf;
+    handlers[Opcode::DJMP as usize] = Core::exec_djmp;
+    handlers[Opcode::DJMPT as usize] = Core::exec_djmpt;
+    handlers[Opcode::DJMPF as usize] = Core::exec_djmpf;
+    handlers[Opcode::CALL as usize] = Core::exec_call;
+    handlers[Opcode::DCALL as usize] = Core::exec_dcall;
+    handlers[Opcode::TCALL as usize] = Core::exec_tcall;
+    handlers[Opcode::SPAWN as usize] = Core::exec_spawn;
+    handlers[Opcode::CORO as usize] = Core::exec_coro;
+    handlers[Opcode::YIELD as usize] = Core::exec_yield;
+    handlers[Opcode::RESUME as usize] = Core::exec_resume;
+    handlers[Opcode::RET as usize] = Core::exec_ret;
+    handlers[Opcode::NOT as usize] = Core::exec_not;
+    handlers[Opcode::ITOF as usize] = Core::exec_itof;
+    handlers[Opcode::ITOD as usize] = Core::exec_itod;
+    handlers[Opcode::FTOI as usize] = Core::exec_ftoi;
+    handlers[Opcode::DTOI as usize] = Core::exec_dtoi;
+    handlers[Opcode::FTOD as usize] = Core::exec_ftod;
+    handlers[Opcode::DTOF as usize] = Core::exec_dtof;
+    handlers[Opcode::BTOI as usize] = Core::exec_btoi;
+    handlers[Opcode::NEGI as usize] = Core::exec_negi;
+    handlers[Opcode::NEGF as usize] = Core::exec_negf;
+    handlers[Opcode::NEGD as usize] = Core::exec_negd;
+    handlers[Opcode::AND as usize] = Core::exec_and;
+    handlers[Opcode::OR as usize] = Core::exec_or;
+    handlers[Opcode::EQI as usize] = Core::exec_eqi;
+    handlers[Opcode::NEQI as usize] = Core::exec_neqi;
+    handlers[Opcode::LTI as usize] = Core::exec_lti;
+    handlers[Opcode::GTI as usize] = Core::exec_gti;
+    handlers[Opcode::LTEQI as usize] = Core::exec_lteqi;
+    handlers[Opcode::GTEQI as usize] = Core::exec_gteqi;
+    handlers[Opcode::EQF as usize] = Core::exec_eqf;
+    handlers[Opcode::EQD as usize] = Core::exec_eqd;
+    handlers[Opcode::NEQF as usize] = Core::exec_neqf;
+    handlers[Opcode::NEQD as usize] = Core::exec_neqd;
+    handlers[Opcode::LTF as usize] = Core::exec_ltf;
+    handlers[Opcode::LTD as usize] = Core::exec_ltd;
+    handlers[Opcode::GTF as usize] = Core::exec_gtf;
+    handlers[Opcode::GTD as usize] = Core::exec_gtd;
+    handlers[Opcode::LTEQF as usize] = Core::exec_lteqf;
+    handlers[Opcode::LTEQD as usize] = Core::exec_lteqd;
+    handlers[Opcode::GTEQF as usize] = Core::exec_gteqf;
+    handlers[Opcode::GTEQD as usize] = Core::exec_gteqd;
+    handlers[Opcode::CHKB as usize] = Core::exec_chkb;
+    handlers[Opcode::CHKN as usize] = Core::exec_chkn;
+    handlers[Opcode::ALLOC as usize] = Core::exec_alloc;
+    handlers[Opcode::FREE as usize] = Core::exec_free;
+    handlers[Opcode::REALLOC as usize] = Core::exec_realloc;
+    handlers[Opcode::INCREF as usize] = Core::exec_incref;
+    handlers[Opcode::DECREF as usize] = Core::exec_decref;
+    handlers[Opcode::SLEN as usize] = Core::exec_slen;
+    handlers[Opcode::SCAT as usize] = Core::exec_scat;
+    handlers[Opcode::SSUB as usize] = Core::exec_ssub;
+    handlers[Opcode::SCHR as usize] = Core::exec_schr;
+    handlers[Opcode::LDI_RA as usize] = Core::exec_ldi_ra;
+    handlers[Opcode::JMPF_CMPI as usize] = Core::exec_jmpf_cmpi;
+    handlers[Opcode::INCSP_RA as usize] = Core::exec_incsp_ra;
+    handlers[Opcode::LDC as usize] = Core::exec_ldc;
+    handlers[Opcode::CHKR as usize] = Core::exec_chkr;
+    handlers
+};
+
 impl Core {
+    /// Creates a core with a stack of the given size in bytes.
+    /// Values and call frames exceeding it are a stack overflow.
     pub fn new(stack_size: usize) -> Core {
         //println!("Core::new(): Stack size = {}", stack_size);
         let mut stack = Vec::new();
         stack.resize(stack_size, 0);
-        let mut swap = Vec::new();
-        swap.resize(SWAP_SPACE_SIZE, 0);
-        let mut sp = Register::new();
+        let mut registers = RegisterFile::new();
         let address = Address::new(0, AddressType::Stack);
-        sp.set::<u64>(address.into());
+        registers.sp.set::<u64>(address.into());
+        registers.fp = registers.sp;
         Core {
             program: None,
-            swap: swap,
+            data: Vec::new(),
             stack: stack,
             heap: Vec::new(),
             heap_pointers: Vec::new(),
+            heap_free_list: Vec::new(),
+            heap_refcounts: HashMap::new(),
             foreign_pointers: HashMap::new(),
-            foreign_function_uids: HashSet::new(),
+            foreign_types: HashMap::new(),
+            contexts: HashMap::new(),
+            foreign_function_names: HashMap::new(),
             call_stack: VecDeque::new(),
-            registers: [Register::new(); 16],
-            ip: Register::new(),
-            sp: sp
+            current_fn: None,
+            registers: registers,
+            instruction_count: 0,
+            max_instructions: None,
+            fuel: None,
+            max_heap: None,
+            foreign_error: None,
+            foreign_blocked: false,
+            ticking: false,
+            running: false,
+            run_state: RunState::Halted,
+            breakpoints: HashSet::new(),
+            task_id: MAIN_TASK_ID,
+            next_task_id: MAIN_TASK_ID + 1,
+            tasks: VecDeque::new(),
+            main_task: None,
+            task_slice: DEFAULT_TASK_SLICE,
+            task_slice_used: 0,
+            task_stack_size: stack_size,
+            coroutines: HashMap::new(),
+            // 0 is never a valid handle
+            next_coroutine_id: 1,
+            resumed_coroutines: Vec::new(),
+            trace_hook: None,
+            trace_operands: None,
+            profile: None,
+            interrupt: Arc::new(Interrupt::new()),
+            #[cfg(feature = "jit")]
+            jit_threshold: Some(DEFAULT_JIT_THRESHOLD),
+            #[cfg(feature = "jit")]
+            jit: None,
+            stats: CoreStats::default(),
+            #[cfg(feature = "extensions")]
+            extensions: Vec::new()
+        }
+    }
+
+    /// Sets the maximum number of instructions a single run may execute
+    pub fn set_max_instructions(&mut self, max_instructions: Option<u64>) {
+        self.max_instructions = max_instructions;
+    }
+
+    /// Sets the fuel, i.e. the number of instructions left to execute across runs.
+    /// None removes the limit.
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.fuel = fuel;
+    }
+
+    /// Gets the fuel left
+    pub fn get_fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+
+    /// Sets a callback invoked after every executed instruction, None disables tracing
+    pub fn set_trace_hook(&mut self, trace_hook: Option<TraceHook>) {
+        self.trace_operands = trace_hook.as_ref().map(|_| Vec::new());
+        self.trace_hook = trace_hook;
+    }
+
+    /// Writes a line per executed instruction to the given sink
+    pub fn trace_to<W: Write + Send + 'static>(&mut self, mut writer: W) {
+        self.set_trace_hook(Some(Box::new(move |entry: &TraceEntry| {
+            // Tracing is best effort, a failing sink must not abort the script
+            let _ = writeln!(writer, "{}", entry);
+        })));
+    }
+
+    /// Enables or disables counting executions and time per opcode and function.
+    /// Enabling it again starts over with empty counters.
+    pub fn set_profiling(&mut self, profiling: bool) {
+        self.profile = if profiling {
+            Some(Profile::new())
+        }
+        else {
+            None
+        };
+    }
+
+    pub fn is_profiling(&self) -> bool {
+        self.profile.is_some()
+    }
+
+    /// Gets a handle to pause, resume or cancel the scripts of this core from another thread
+    pub fn get_handle(&self) -> CoreHandle {
+        CoreHandle::new(self.interrupt.clone())
+    }
+
+    /// Gets where the scripts spent their time since profiling was enabled, None if it is disabled
+    pub fn profile_report(&self) -> Option<ProfileReport> {
+        let profile = self.profile.as_ref()?;
+        let report = match self.program.as_ref() {
+            Some(program) => profile.report(&program.function_uids),
+            None => profile.report(&HashMap::new())
+        };
+        Some(report)
+    }
+
+    /// Sets the number of calls after which a script function is compiled to native code.
+    /// None disables the jit, functions compiled so far are dropped either way.
+    #[cfg(feature = "jit")]
+    pub fn set_jit_threshold(&mut self, jit_threshold: Option<u64>) {
+        self.jit_threshold = jit_threshold;
+        self.jit = None;
+    }
+
+    /// Gets the number of calls after which a function is compiled, None if the jit is disabled
+    #[cfg(feature = "jit")]
+    pub fn get_jit_threshold(&self) -> Option<u64> {
+        self.jit_threshold
+    }
+
+    /// Gets the number of functions compiled to native code
+    #[cfg(feature = "jit")]
+    pub fn get_jit_compiled_count(&self) -> usize {
+        self.jit.as_ref()
+            .map(|jit| jit.get_compiled_count())
+            .unwrap_or(0)
+    }
+
+    /// Sets the number of instructions a task runs before the next one gets its turn
+    pub fn set_task_slice(&mut self, task_slice: u64) {
+        self.task_slice = task_slice.max(1);
+    }
+
+    /// Sets the stack size of spawned tasks, it defaults to the stack size of the core
+    pub fn set_task_stack_size(&mut self, task_stack_size: usize) {
+        self.task_stack_size = task_stack_size;
+    }
+
+    /// Gets the number of spawned tasks that have not finished
+    pub fn get_task_count(&self) -> usize {
+        let waiting = self.tasks.iter()
+            .filter(|task| task.id != MAIN_TASK_ID)
+            .count();
+        if self.task_id == MAIN_TASK_ID {
+            waiting
+        } else {
+            waiting + 1
+        }
+    }
+
+    /// Gets the id of the running task, MAIN_TASK_ID for the function the run started with
+    pub fn get_task_id(&self) -> u64 {
+        self.task_id
+    }
+
+    /// Gets the number of coroutines that have not finished
+    pub fn get_coroutine_count(&self) -> usize {
+        self.coroutines.values()
+            .filter(|coroutine| coroutine.context.is_some())
+            .count()
+    }
+
+    /// Checks if the coroutine with the given handle has finished
+    pub fn is_coroutine_finished(&self, handle: u64) -> CoreResult<bool> {
+        self.coroutines.get(&handle)
+            .map(|coroutine| coroutine.context.is_none())
+            .ok_or(CoreError::InvalidCoroutine(handle))
+    }
+
+    /// Creates a suspended coroutine running a function, with the arguments on top of the stack as its own.
+    /// Returns its handle.
+    pub fn new_coroutine(&mut self, fn_uid: u64, args_size: usize) -> CoreResult<u64> {
+        let handle = self.next_coroutine_id;
+        let context = self.new_context(handle, fn_uid, args_size)?;
+        self.next_coroutine_id += 1;
+        self.coroutines.insert(handle, Coroutine {
+            context: Some(context),
+            running: false,
+            value_size: 0
+        });
+        Ok(handle)
+    }
+
+    /// Runs a coroutine until it yields, returns the bytes of the value or None once it finished
+    pub fn resume_coroutine(&mut self, handle: u64, value_size: usize) -> CoreResult<Option<Vec<u8>>> {
+        let depth = self.resumed_coroutines.len();
+        let running = self.running;
+        let run_state = self.run_state;
+        self.enter_coroutine(handle, value_size)?;
+        while self.resumed_coroutines.len() > depth {
+            match self.step() {
+                Ok(true) => {},
+                Ok(false) => break,
+                Err(error) => {
+                    self.abandon_coroutines(depth);
+                    self.running = running;
+                    self.run_state = run_state;
+                    return Err(error);
+                }
+            };
+        }
+        self.running = running;
+        self.run_state = run_state;
+
+        let has_value: u64 = self.pop_stack()?;
+        let value = self.pop_bytes(value_size)?;
+        if has_value == 0 {
+            return Ok(None);
         }
+        Ok(Some(value))
+    }
+
+    /// Sets the maximum heap size in bytes
+    pub fn set_max_heap(&mut self, max_heap: Option<usize>) {
+        self.max_heap = max_heap;
+    }
+
+    /// Sets an error raised by a foreign function, it is returned once the function returns
+    pub fn set_foreign_error(&mut self, error: CoreError) {
+        self.foreign_error = Some(error);
+    }
+
+    /// Gets the execution counters
+    pub fn get_stats(&self) -> &CoreStats {
+        &self.stats
+    }
+
+    /// Resets the execution counters
+    pub fn reset_stats(&mut self) {
+        self.stats = CoreStats::default();
+    }
+
+    /// Marks the running foreign function as blocked. The call is repeated once execution resumes.
+    pub fn block_foreign_call(&mut self) {
+        self.foreign_blocked = true;
     }
 
     #[inline]
     pub fn load_program(&mut self, program: Program) {
-        self.foreign_function_uids.clear();
-        self.foreign_function_uids = program.foreign_functions.iter().map(|(k, _)| *k).collect();
+        self.load_shared_program(Arc::new(program));
+    }
+
+    /// Loads a program shared with other cores, the statics start out with their initial values
+    pub fn load_shared_program(&mut self, program: Arc<Program>) {
+        self.foreign_function_names = program.foreign_functions.iter()
+            .map(|(fn_uid, function)| {
+                let fn_name = program.function_uids.iter()
+                    .find(|(_, uid)| *uid == fn_uid)
+                    .map(|(fn_name, _)| fn_name.clone())
+                    .unwrap_or_else(|| function.name.clone());
+                (*fn_uid, fn_name)
+            })
+            .collect();
+        self.data = program.code[..program.data_size.min(program.code.len())].to_vec();
         self.program = Some(program);
+        // Native code and call counts belong to the previous program
+        #[cfg(feature = "jit")]
+        {
+            self.jit = None;
+        }
+    }
+
+    /// Gets the loaded program
+    pub fn get_program(&self) -> Option<&Program> {
+        self.program.as_deref()
+    }
+
+    /// Creates a core sharing the loaded program, with a stack, heap and statics of its own.
+    /// The stack size and the limits of this core carry over.
+    pub fn new_isolate(&self) -> CoreResult<Core> {
+        let program = self.program.clone()
+            .ok_or(CoreError::NoProgram)?;
+        let mut core = Core::new(self.stack.len());
+        core.load_shared_program(program);
+        core.max_instructions = self.max_instructions;
+        core.max_heap = self.max_heap;
+        core.task_slice = self.task_slice;
+        core.task_stack_size = self.task_stack_size;
+        #[cfg(feature = "jit")]
+        {
+            core.jit_threshold = self.jit_threshold;
+        }
+        #[cfg(feature = "extensions")]
+        {
+            core.extensions = self.extensions.clone();
+        }
+        Ok(core)
     }
 
     #[inline]
@@ -161,16 +835,67 @@ impl Core {
 
     #[inline]
     pub fn get_stack_size(&self) -> usize {
-        let sp_raw: u64 = self.sp.get();
-        let sp_addr = Address::from(sp_raw);
-        sp_addr.real_address as usize
+        let sp_raw: u64 = self.registers.sp.get();
+        Address::get_real_address(sp_raw) as usize
+    }
+
+    /// Drops the stack values above the given stack size
+    pub fn truncate_stack(&mut self, stack_size: usize) -> CoreResult<()> {
+        if stack_size > self.get_stack_size() {
+            return Err(CoreError::InvalidStackPointer);
+        }
+        self.registers.sp.set::<u64>(Address::new(stack_size as u64, AddressType::Stack).into());
+        Ok(())
+    }
+
+    /// Fails with a stack overflow if n more bytes do not fit into the stack
+    /// next to the stack values and call frames
+    fn check_stack_space(&self, n: usize) -> CoreResult<()> {
+        let used = self.get_stack_size() + self.call_stack.len() * CALL_FRAME_SIZE;
+        if used + n > self.stack.len() {
+            return Err(CoreError::StackOverflow);
+        }
+        Ok(())
+    }
+
+    /// Gets the current instruction pointer
+    #[inline]
+    pub fn get_ip(&self) -> usize {
+        self.registers.ip.get()
+    }
+
+    /// Gets the return addresses on the call stack, innermost first
+    pub fn get_call_stack(&self) -> Vec<usize> {
+        self.call_stack.iter()
+            .map(|(ret_ip, _, _)| *ret_ip)
+            .collect()
+    }
+
+    /// Gets the uids of the functions of all frames, innermost first.
+    /// Frames not entered through a function, e.g. by run(), have no uid.
+    pub fn get_function_stack(&self) -> Vec<Option<u64>> {
+        let mut fn_uids = vec![self.current_fn];
+        fn_uids.extend(self.call_stack.iter().map(|(_, _, fn_uid)| *fn_uid));
+        fn_uids
+    }
+
+    /// Reads a value on the stack, relative to the stack pointer
+    pub fn get_stack_value<T: DeserializeOwned>(&self, sp_offset: i16) -> CoreResult<T> {
+        self.mem_get((self.registers.sp.get(), sp_offset))
+    }
+
+    /// Reads a string on the stack, relative to the stack pointer
+    pub fn get_stack_string(&self, sp_offset: i16) -> CoreResult<String> {
+        let sp: u64 = self.registers.sp.get();
+        let string_addr = (sp as i64 + sp_offset as i64) as u64;
+        self.mem_get_string(string_addr)
     }
 
     #[inline]
     pub fn get_opcode(&mut self) -> CoreResult<Opcode> {
         let program = self.program.as_ref()
             .ok_or(CoreError::NoProgram)?;
-        //println!("ip: {}", self.ip.get::<usize>());
+        //println!("ip: {}", self.registers.ip.get::<usize>());
         let op: u8 = self.get_op()?;
         let opcode = Opcode::try_from(op)?;
         //println!("opcode: {:?}", opcode);
@@ -198,1097 +923,2744 @@ impl Core {
     }
 
     pub fn run_at(&mut self, offset: usize) -> CoreResult<()> {
-        self.ip.set(offset);
-        let program_len = self.program_len()?;
-        //println!("Program length: {}", program_len);
-        while self.ip.get::<usize>() < program_len {
-            //println!("ip: {}", self.ip.get::<usize>());
-            let opcode = self.get_opcode()?;
-            //println!("opcode: {:?}", opcode);
-            //println!("Stack values: {:?}", &self.stack[0..self.sp]);
-            //println!("IP: {}", self.ip);
-
-            match opcode {
-                Opcode::NOOP => {},
-                Opcode::HALT => {
-                    let err_code: u8 = self.get_op()?;
-                    match err_code {
-                        1 => {
-                            return Err(CoreError::NoReturnValue);
-                        },
-                        _ => {
-                            return Err(CoreError::Halted(err_code))
-                        }
-                    };
-                },
-                Opcode::MOVB => {
-                    let lhs: u8 = self.get_op()?;
-                    let rhs: u8 = self.get_op()?;
-                    let boolean: bool = {
-                        self.reg(lhs)?.get()
-                    };
-                    self.reg(rhs)?.set(boolean);
-                },
-                Opcode::MOVF => {
-                    let lhs: u8 = self.get_op()?;
-                    let rhs: u8 = self.get_op()?;
-                    let float: f32 = {
-                        self.reg(lhs)?.get()
-                    };
-                    self.reg(rhs)?.set(float);
-                },
-                Opcode::MOVI => {
-                    let lhs: u8 = self.get_op()?;
-                    let rhs: u8 = self.get_op()?;
-                    let int64: i64 = {
-                        self.reg(lhs)?.get()
-                    };
-                    self.reg(rhs)?.set(int64);
-                },
-                Opcode::MOVA => {
-                    let lhs: u8 = self.get_op()?;
-                    let rhs: u8 = self.get_op()?;
-                    let uint64: u64 = {
-                        self.reg(lhs)?.get()
-                    };
-                    self.reg(rhs)?.set(uint64);
-                },
-                Opcode::MOVB_A => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let lhs_offset: i16 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let rhs_offset: i16 = self.get_op()?;
-                    let lhs_addr: u64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let rhs_addr: u64 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    self.mem_mov_n((lhs_addr, lhs_offset), (rhs_addr, rhs_offset), 1)?;
-                },
-                Opcode::MOVF_A => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let lhs_offset: i16 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let rhs_offset: i16 = self.get_op()?;
-                    let lhs_addr: u64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let rhs_addr: u64 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    self.mem_mov_n((lhs_addr, lhs_offset), (rhs_addr, rhs_offset), 4)?;
-                },
-                Opcode::MOVI_A => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let lhs_offset: i16 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let rhs_offset: i16 = self.get_op()?;
-                    let lhs_addr: u64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let rhs_addr: u64 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    self.mem_mov_n((lhs_addr, lhs_offset), (rhs_addr, rhs_offset), 8)?;
-                },
-                Opcode::MOVA_A => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let lhs_offset: i16 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let rhs_offset: i16 = self.get_op()?;
-                    let lhs_addr: u64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let rhs_addr: u64 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    self.mem_mov_n((lhs_addr, lhs_offset), (rhs_addr, rhs_offset), 8)?;
-                },
-                Opcode::MOVN_A => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let lhs_offset: i16 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let rhs_offset: i16 = self.get_op()?;
-                    let n: usize = self.get_op::<u32>()? as usize;
-                    let lhs_addr: u64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let rhs_addr: u64 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    self.mem_mov_n((lhs_addr, lhs_offset), (rhs_addr, rhs_offset), n)?;
-                },
-                Opcode::MOVB_AR => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let lhs_offset: i16 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let lhs_addr: u64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let boolean: bool = self.mem_get((lhs_addr, lhs_offset))?;
-                    self.reg(rhs_reg)?.set(boolean);
-                },
-                Opcode::MOVF_AR => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let lhs_offset: i16 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let lhs_addr: u64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let float: f32 = self.mem_get((lhs_addr, lhs_offset))?;
-                    self.reg(rhs_reg)?.set(float)
-                },
-                Opcode::MOVI_AR => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let lhs_offset: i16 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let lhs_addr: u64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let int64: i64 = self.mem_get((lhs_addr, lhs_offset))?;
-                    self.reg(rhs_reg)?.set(int64)
-                },
-                Opcode::MOVA_AR => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let lhs_offset: i16 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let lhs_addr: u64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let uint64: u64 = self.mem_get((lhs_addr, lhs_offset))?;
-                    self.reg(rhs_reg)?.set(uint64)
-                },
-                Opcode::MOVB_RA => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let rhs_offset: i16 = self.get_op()?;
-                    let rhs_addr: u64 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    let boolean: bool = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    self.mem_set((rhs_addr, rhs_offset), boolean)?;
-                },
-                Opcode::MOVF_RA => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let rhs_offset: i16 = self.get_op()?;
-                    let rhs_addr: u64 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    let float: f32 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    self.mem_set((rhs_addr, rhs_offset), float)?;
-                },
-                Opcode::MOVI_RA => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let rhs_offset: i16 = self.get_op()?;
-                    let rhs_addr: u64 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    let int64: i64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    self.mem_set((rhs_addr, rhs_offset), int64)?;
-                },
-                Opcode::MOVA_RA => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let rhs_offset: i16 = self.get_op()?;
-                    let rhs_addr: u64 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    let uint64: u64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    self.mem_set((rhs_addr, rhs_offset), uint64)?;
-                },
-                Opcode::LDB => {
-                    let boolean: bool = self.get_op()?;
-                    let lhs_reg: u8 = self.get_op()?;
-                    self.reg(lhs_reg)?.set(boolean);
-                },
-                Opcode::LDF => {
-                    let float: f32 = self.get_op()?;
-                    let lhs_reg: u8 = self.get_op()?;
-                    self.reg(lhs_reg)?.set(float);
-                },
-                Opcode::LDI => {
-                    let int64: i64 = self.get_op()?;
-                    let lhs_reg: u8 = self.get_op()?;
-                    self.reg(lhs_reg)?.set(int64);
-                },
-                Opcode::LDA => {
-                    let uint64: u64 = self.get_op()?;
-                    let lhs_reg: u8 = self.get_op()?;
-                    self.reg(lhs_reg)?.set(uint64)
-                },
-                Opcode::ADDI => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: i64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let rhs: i64 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs + rhs);
-                },
-                Opcode::SUBI => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: i64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let rhs: i64 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs - rhs);
-                },
-                Opcode::MULI => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: i64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let rhs: i64 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs * rhs);
-                },
-                Opcode::DIVI => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: i64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let rhs: i64 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs / rhs)
-                },
-                Opcode::ADDI_I => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs: i64 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: i64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs + rhs);
-                },
-                Opcode::SUBI_I => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs: i64 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: i64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs - rhs);
-                },
-                Opcode::MULI_I => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs: i64 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: i64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs * rhs);
-                },
-                Opcode::DIVI_I => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs: i64 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: i64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs / rhs);
-                },
-                Opcode::ADDU => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: u64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let rhs: u64 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs + rhs);
-                },
-                Opcode::SUBU => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: u64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let rhs: u64 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs - rhs)
-                },
-                Opcode::MULU => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: u64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let rhs: u64 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs * rhs)
-                },
-                Opcode::DIVU => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: u64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let rhs: u64 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs / rhs)
-                },
-                Opcode::ADDU_I => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs: u64 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: u64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    //println!("ADDUI: {} + {}", lhs, rhs);
-                    if lhs_reg == 16 && target_reg == 16 {
-                        let lhs = Address::from(self.sp.get::<u64>()).real_address;
-                        //println!("Incrementing SP(={}) by {}", lhs, rhs);
-                        if lhs + rhs > self.stack.len() as u64 {
-                            return Err(CoreError::StackOverflow);
-                        }
-                    }
-                    self.reg(target_reg)?.set(lhs + rhs);
-                    //println!("SP After ADDU_I: {}", Address::from(self.sp.get::<u64>()).real_address);
-                },
-                Opcode::SUBU_I => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs: u64 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: u64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    if lhs_reg == 16 && target_reg == 16 {
-                        let lhs = Address::from(self.sp.get::<u64>()).real_address;
-                        //println!("Decrementing SP(={}) by {}", lhs, rhs);
-                    }
-                    self.reg(target_reg)?.set(lhs - rhs);
-                },
-                Opcode::MULU_I => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs: u64 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: u64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs * rhs);
-                },
-                Opcode::DIVU_I => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs: u64 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: u64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs / rhs);
-                },
-                Opcode::ADDF => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: f32 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let rhs: f32 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs + rhs);
-                },
-                Opcode::SUBF => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: f32 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let rhs: f32 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs - rhs);
-                },
-                Opcode::MULF => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: f32 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let rhs: f32 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs * rhs);
-                },
-                Opcode::DIVF => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: f32 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let rhs: f32 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs / rhs);
-                },
-                Opcode::ADDF_I => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs: f32 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: f32 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs + rhs);
-                },
-                Opcode::SUBF_I => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs: f32 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: f32 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs - rhs);
-                },
-                Opcode::MULF_I => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs: f32 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: f32 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs * rhs);
-                },
-                Opcode::DIVF_I => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs: f32 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: f32 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs / rhs);
-                },
-                Opcode::JMP => {
-                    let target_ip: u64 = self.get_op()?;
-                    self.ip.set(target_ip);
-                },
-                Opcode::JMPT => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let target_ip: u64 = self.get_op()?;
-                    let lhs: bool = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    if lhs {
-                        self.ip.set(target_ip);
-                    }
-                },
-                Opcode::JMPF => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let target_ip: u64 = self.get_op()?;
-                    let lhs: bool = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    if !lhs {
-                        self.ip.set(target_ip);
-                    }
-                },
-                Opcode::DJMP => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let target_ip: u64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    self.ip.set(target_ip);
-                },
-                Opcode::DJMPT => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let target_ip: u64 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    let lhs: bool = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    if lhs {
-                        self.ip.set(target_ip);
-                    }
-                },
-                Opcode::DJMPF => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let target_ip: u64 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    let lhs: bool = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    if !lhs {
-                        self.ip.set(target_ip);
-                    }
-                },
-                Opcode::CALL => {
-                    self.call()?;
-                },
-                Opcode::RET => {
-                    // Special case if function was called externally, the callstack is empty
-                    if self.call_stack.len() == 0 {
-                        break;
-                    }
-                    self.ret()?;
-                },
-                Opcode::NOT => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let lhs: bool = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    self.reg(rhs_reg)?.set(!lhs);
-                },
-                Opcode::AND => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: bool = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let rhs: bool = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs && rhs);
-                },
-                Opcode::OR => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: bool = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let rhs: bool = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs || rhs);
-                },
-                Opcode::EQI => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: i64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let rhs: i64 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs == rhs);
-                },
-                Opcode::NEQI => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: i64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let rhs: i64 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs != rhs);
-                },
-                Opcode::LTI => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: i64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let rhs: i64 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs < rhs);
-                },
-                Opcode::GTI => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: i64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let rhs: i64 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs > rhs);
-                },
-                Opcode::LTEQI => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: i64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let rhs: i64 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs <= rhs);
-                },
-                Opcode::GTEQI => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: i64 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let rhs: i64 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs >= rhs);
-                },
-                Opcode::EQF => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: f32 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let rhs: f32 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs == rhs);
-                },
-                Opcode::NEQF => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: f32 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let rhs: f32 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs != rhs);
-                },
-                Opcode::LTF => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: f32 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let rhs: f32 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs < rhs);
-                },
-                Opcode::GTF => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: f32 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let rhs: f32 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs > rhs);
-                },
-                Opcode::LTEQF => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: f32 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let rhs: f32 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs <= rhs);
-                },
-                Opcode::GTEQF => {
-                    let lhs_reg: u8 = self.get_op()?;
-                    let rhs_reg: u8 = self.get_op()?;
-                    let target_reg: u8 = self.get_op()?;
-                    let lhs: f32 = {
-                        self.reg(lhs_reg)?.get()
-                    };
-                    let rhs: f32 = {
-                        self.reg(rhs_reg)?.get()
-                    };
-                    self.reg(target_reg)?.set(lhs >= rhs);
-                },
-                _ => {
-                    return Err(CoreError::UnimplementedOpcode(opcode));
-                }
-            };
-        }
+        self.current_fn = self.program.as_ref()
+            .and_then(|program| program.get_function_uid_at(offset));
+        self.reset_tasks();
+        self.registers.ip.set(offset);
+        self.registers.fp.set::<u64>(self.registers.sp.get());
+        self.instruction_count = 0;
+        self.run_state = RunState::Running;
+        while self.step()? {}
         Ok(())
     }
 
-    fn mem_mov_n(&mut self, lhs: (u64, i16), rhs: (u64, i16), n: usize) -> CoreResult<()> {
-        let lhs_addr = Address::from(lhs.0).with_offset(lhs.1);
-        let rhs_addr = Address::from(rhs.0).with_offset(rhs.1);
-
-        let source_addr = lhs_addr.real_address as usize;
-        let target_addr = rhs_addr.real_address as usize;
-
-        let bytes = {
-            let source: &[u8] = match lhs_addr.address_type {
-                AddressType::Stack => {
-                    &self.stack
-                },
-                AddressType::Program => {
-                    let program = self.program.as_ref()
-                        .ok_or(CoreError::Unknown)?;
-                    &program.code
-                },
-                AddressType::Swap => {
-                    &self.swap
-                },
-                _ => return Err(CoreError::Unknown)
-            };
-            
-            let mut ret = Vec::with_capacity(n);
-            ret.resize(n, 0);
-
-            for i in 0..n {
-                ret[i] = source[source_addr + i];
-            }
+    /// Runs the program with the given fuel.
+    /// Running out of it fails with OutOfFuel, resume() then continues where it stopped.
+    pub fn run_with_fuel(&mut self, fuel: u64) -> CoreResult<()> {
+        self.fuel = Some(fuel);
+        self.run()
+    }
 
-            ret
-        };
+    /// Adds fuel and continues a run that ran out of it
+    pub fn resume(&mut self, fuel: u64) -> CoreResult<()> {
+        self.fuel = Some(self.fuel.unwrap_or(0).saturating_add(fuel));
+        while self.step()? {}
+        Ok(())
+    }
 
-        match rhs_addr.address_type {
-            AddressType::Stack => {
-                for i in 0..n {
-                    self.stack[target_addr + i] = bytes[i];
-                }
-            },
-            AddressType::Program => {
-                let program = self.program.as_mut()
-                    .ok_or(CoreError::Unknown)?;
-                for i in 0..n {
-                    program.code[target_addr + i] = bytes[i];
-                }
-            },
-            AddressType::Swap => {
-                for i in 0..n {
-                    self.swap[target_addr + i] = bytes[i];
-                }
-            },
-            _ => return Err(CoreError::Unknown)
+    /// Sets the instruction pointer to the start of a function, without running it
+    pub fn start_fn(&mut self, uid: u64) -> CoreResult<()> {
+        let fn_offset = {
+            let program = self.program.as_ref()
+                .ok_or(CoreError::NoProgram)?;
+            program.functions.get(&uid)
+                .ok_or(CoreError::UnknownFunctionUid)?
+                .clone()
         };
-
+        self.registers.ip.set(fn_offset);
+        self.registers.fp.set::<u64>(self.registers.sp.get());
+        self.current_fn = Some(uid);
+        self.reset_tasks();
+        self.instruction_count = 0;
+        self.running = true;
+        self.run_state = RunState::Paused;
         Ok(())
     }
 
-    fn mem_get_n(&self, addr: (u64, i16), n: usize) -> CoreResult<Vec<u8>> {
-        let mut data = Vec::with_capacity(n);
-        data.resize(n, 0);
-
-        let lhs_addr = Address::from(addr.0).with_offset(addr.1);
-        //println!("Getting n = {} bytes at address {:?}", n, lhs_addr);
-        //println!("SP: {}", Address::from(self.sp.get::<u64>()).real_address);
+    /// Checks if a function is currently being executed
+    pub fn is_running(&self) -> bool {
+        self.running || !self.call_stack.is_empty()
+    }
 
-        let source_addr = lhs_addr.real_address as usize;
+    /// Gets the execution state
+    pub fn get_run_state(&self) -> RunState {
+        self.run_state
+    }
 
-        let source: &[u8] = match lhs_addr.address_type {
-            AddressType::Stack => {
-                &self.stack
-            },
-            AddressType::Program => {
-                let program = self.program.as_ref()
-                    .ok_or(CoreError::Unknown)?;
-                &program.code
-            },
-            AddressType::Swap => {
-                &self.swap
-            },
-            _ => return Err(CoreError::Unknown)
-        };
+    /// Pauses run_to_breakpoint() before the instruction at the given code offset
+    pub fn add_breakpoint(&mut self, offset: usize) {
+        self.breakpoints.insert(offset);
+    }
 
-        for i in 0..n {
-            data[i] = source[source_addr + i];
-        }
+    /// Removes the breakpoint at the given code offset, returns false if there was none
+    pub fn remove_breakpoint(&mut self, offset: usize) -> bool {
+        self.breakpoints.remove(&offset)
+    }
 
-        Ok(data)
+    /// Removes all breakpoints
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
     }
-    
-    #[inline]
-    pub fn mem_get_string(&self, addr: u64) -> CoreResult<String> {
-        //println!("mem_get_string(): string addr: {:?}", Address::from(addr));
-        let string_size: u64 = self.mem_get((addr, 0))?;
-        //println!("String size: {}", string_size);
-        let string_addr: u64 = self.mem_get((addr + 8, 0))?;
-        //println!("String addr: {}", string_addr);
-        let string_data = self.mem_get_n((string_addr, 0), string_size as usize)?;
-        String::from_utf8(string_data)
-            .map_err(|_| CoreError::OperatorDeserialize)
+
+    /// Continues the function started with start_fn() until it reaches a breakpoint or finishes.
+    /// The instruction at the instruction pointer always runs, so a paused function moves past its breakpoint.
+    pub fn run_to_breakpoint(&mut self) -> CoreResult<RunState> {
+        if self.run_state == RunState::Halted {
+            return Ok(RunState::Halted);
+        }
+        self.run_state = RunState::Running;
+        while self.step()? {
+            if self.breakpoints.contains(&self.get_ip()) {
+                self.run_state = RunState::Paused;
+                break;
+            }
+        }
+        Ok(self.run_state)
     }
 
-    #[inline]
-    pub fn mem_get<T: DeserializeOwned>(&self, addr: (u64, i16)) -> CoreResult<T> {
-        let n = size_of::<T>();
+    /// Executes at most budget instructions of the function started with start_fn()
+    pub fn tick(&mut self, budget: u64) -> CoreResult<TickState> {
+        if !self.running {
+            return Ok(TickState::Finished);
+        }
+        self.run_state = RunState::Running;
+        self.ticking = true;
+        let tick_res = self.tick_instructions(budget);
+        self.ticking = false;
+        tick_res
+    }
 
-        let data = self.mem_get_n(addr, n)?;
+    fn tick_instructions(&mut self, budget: u64) -> CoreResult<TickState> {
+        for _ in 0..budget {
+            if !self.step()? {
+                return Ok(TickState::Finished);
+            }
+            if self.foreign_blocked {
+                self.foreign_blocked = false;
+                self.run_state = RunState::Paused;
+                return Ok(TickState::Blocked);
+            }
+        }
+        self.run_state = RunState::Paused;
+        Ok(TickState::Yielded)
+    }
 
-        deserialize(&data)
-            .map_err(|_| CoreError::OperatorDeserialize)
+    /// Executes the instruction at the instruction pointer.
+    /// Returns false once the program has finished running.
+    pub fn step(&mut self) -> CoreResult<bool> {
+        let instr_ip = self.registers.ip.get::<usize>();
+        match self.step_instr() {
+            Ok(true) => {
+                self.schedule_tasks();
+                Ok(true)
+            },
+            // The function can be resumed by refueling
+            Err(CoreError::OutOfFuel) => {
+                self.run_state = RunState::Paused;
+                Err(CoreError::OutOfFuel)
+            },
+            Err(error) => {
+                self.running = false;
+                self.run_state = RunState::Halted;
+                Err(self.with_source_position(error, instr_ip))
+            },
+            // The run goes on while spawned tasks are left
+            Ok(false) if self.finish_task() => Ok(true),
+            Ok(false) => {
+                self.running = false;
+                self.run_state = RunState::Halted;
+                Ok(false)
+            }
+        }
     }
-    
-    #[inline]
-    pub fn mem_set<T: Serialize>(&mut self, addr: (u64, i16), item: T) -> CoreResult<()> {
-        let n = size_of::<T>();
 
-        let lhs_addr = Address::from(addr.0).with_offset(addr.1);
+    /// Drops the tasks of a previous run, the new run starts as the main task
+    fn reset_tasks(&mut self) {
+        // A failed run may have left us inside of coroutines
+        self.abandon_coroutines(0);
+        self.tasks.clear();
+        self.main_task = None;
+        self.task_id = MAIN_TASK_ID;
+        self.next_task_id = MAIN_TASK_ID + 1;
+        self.task_slice_used = 0;
+    }
 
-        let data = serialize(&item)
-            .map_err(|_| CoreError::OperatorSerialize)?;
+    /// Swaps the context of the running task with the given one, returns the context of the previous task
+    fn switch_task(&mut self, mut task: Task) -> Task {
+        mem::swap(&mut self.task_id, &mut task.id);
+        mem::swap(&mut self.resumed_coroutines, &mut task.resumed_coroutines);
+        self.swap_context(&mut task);
+        self.task_slice_used = 0;
+        task
+    }
 
-        let target_addr = lhs_addr.real_address as usize;
-        
-        match lhs_addr.address_type {
-            AddressType::Stack => {
-                for i in 0..n {
-                    self.stack[target_addr + i] = data[i];
-                }
-            },
-            AddressType::Program => {
-                let program = self.program.as_mut()
-                    .ok_or(CoreError::Unknown)?;
-                for i in 0..n {
-                    program.code[target_addr + i] = data[i];
-                }
+    /// Swaps the stack, registers and call stack with the ones of the given context.
+    /// Used for task switches and to enter or leave coroutines.
+    fn swap_context(&mut self, context: &mut Task) {
+        mem::swap(&mut self.stack, &mut context.stack);
+        mem::swap(&mut self.registers, &mut context.registers);
+        mem::swap(&mut self.call_stack, &mut context.call_stack);
+        mem::swap(&mut self.current_fn, &mut context.current_fn);
+    }
+
+    /// Gives the next task its turn once the running one used up its slice
+    fn schedule_tasks(&mut self) {
+        self.task_slice_used += 1;
+        if self.task_slice_used < self.task_slice {
+            return;
+        }
+        match self.tasks.pop_front() {
+            Some(task) => {
+                let prev_task = self.switch_task(task);
+                self.tasks.push_back(prev_task);
             },
-            _ => return Err(CoreError::Unknown)
+            None => self.task_slice_used = 0
         };
-
-        Ok(())
     }
 
-    #[inline]
-    pub fn reg(&mut self, reg: u8) -> CoreResult<&mut Register> {
-        if reg == 16 {
-            return Ok(&mut self.sp);
-        }
-        if reg == 17 {
-            return Ok(&mut self.ip);
-        }
-        else if reg < 16 {
-            return Ok(&mut self.registers[reg as usize]);
-        }
-        else {
-            return Err(CoreError::InvalidRegister);
+    /// Ends the running task after its function returned and switches to the next one.
+    /// Returns false once all tasks finished, the context of the main task is then restored.
+    fn finish_task(&mut self) -> bool {
+        let task = match self.tasks.pop_front() {
+            Some(task) => task,
+            None => {
+                if let Some(main_task) = self.main_task.take() {
+                    self.switch_task(main_task);
+                }
+                return false;
+            }
+        };
+        let finished_task = self.switch_task(task);
+        // Keeps the return value of the main function for the host
+        if finished_task.id == MAIN_TASK_ID {
+            self.main_task = Some(finished_task);
         }
+        true
     }
 
-    #[inline]
-    fn call(&mut self) -> CoreResult<()> {
+    /// Starts a task running a function, with the arguments on top of the stack as its own.
+    /// Arguments pointing into the stack of the spawning task are not supported.
+    fn spawn(&mut self) -> CoreResult<()> {
         let fn_uid: u64 = self.get_op()?;
-        if self.foreign_function_uids.contains(&fn_uid) {
-            return self.call_foreign_fn(fn_uid);
-        }
+        let args_size: u32 = self.get_op()?;
 
-        let program = self.program.as_ref()
-            .ok_or(CoreError::NoProgram)?;
+        let task = self.new_context(self.next_task_id, fn_uid, args_size as usize)?;
+        self.next_task_id += 1;
+        self.tasks.push_back(task);
 
-        let new_ip = program.functions.get(&fn_uid)
-            .ok_or(CoreError::UnknownFunctionUid)?;
-        
-        let old_ip: usize = self.ip.get();
-        self.call_stack.push_front(old_ip);
-        self.ip.set(*new_ip);
+        Ok(())
+    }
+
+    /// Creates a suspended coroutine for the CORO instruction, its handle is stored in R0
+    fn create_coroutine(&mut self) -> CoreResult<()> {
+        let fn_uid: u64 = self.get_op()?;
+        let args_size: u32 = self.get_op()?;
+
+        let handle = self.new_coroutine(fn_uid, args_size as usize)?;
+        self.reg(0)?.set::<u64>(handle);
 
         Ok(())
     }
 
-    /// Retrieves a foreign pointer and returns the correct
-    /// Arc<Mutex<T>> if found.
-    pub fn get_foreign_ptr<T>(&self, ptr: u64) -> CoreResult<Arc<Mutex<T>>> {
-        let arc_box_int = self.foreign_pointers.get(&ptr)
-            .ok_or(CoreError::Unknown)?;
-        let arc = unsafe {
-            let arc_box_raw: *mut Arc<Mutex<T>> = std::mem::transmute(*arc_box_int);
-            let arc_box = Box::from_raw(arc_box_raw);
-            let ret = arc_box.deref().clone();
-            std::mem::forget(arc_box);
-            ret
+    /// Creates a context with a stack of its own, about to run a function.
+    /// The arguments on top of the current stack are copied to it.
+    fn new_context(&self, id: u64, fn_uid: u64, args_size: usize) -> CoreResult<Task> {
+        let fn_offset = {
+            let program = self.program.as_ref()
+                .ok_or(CoreError::NoProgram)?;
+            program.functions.get(&fn_uid)
+                .cloned()
+                .ok_or(CoreError::UnknownFunctionUid)?
         };
-        Ok(arc)
-    }
+        if args_size > self.task_stack_size {
+            return Err(CoreError::StackOverflow);
+        }
 
-    /// Inserts a foreign pointer
-    pub fn insert_foreign_ptr<T>(&mut self, item: Arc<Mutex<T>>) -> CoreResult<u64> {
-        let mut uid_gen = UIDGenerator::new();
+        let sp = self.get_stack_size();
+        let args_start = sp.checked_sub(args_size)
+            .ok_or(CoreError::InvalidStackPointer)?;
+        let mut stack = vec![0; self.task_stack_size];
+        stack[..args_size].copy_from_slice(self.mem_slice(&Address::new(args_start as u64, AddressType::Stack), args_size)?);
 
-        let mut addr = Address::new(uid_gen.generate(), AddressType::Foreign);
-        while self.foreign_pointers.contains_key(&addr.raw_address) {
-            addr = Address::new(uid_gen.generate(), AddressType::Foreign);
-        }
+        let mut registers = RegisterFile::new();
+        registers.sp.set::<u64>(Address::new(args_size as u64, AddressType::Stack).into());
+        registers.fp = registers.sp;
+        registers.ip.set(fn_offset);
+        Ok(
+            Task {
+                id: id,
+                stack: stack,
+                registers: registers,
+                call_stack: VecDeque::new(),
+                current_fn: Some(fn_uid),
+                resumed_coroutines: Vec::new()
+            }
+        )
+    }
 
-        let ptr = addr.into();
-        
-        let arc_box = Box::new(item);
-        let arc_box_int: u64 = unsafe {
-            let arc_box_raw = Box::into_raw(arc_box);
-            std::mem::transmute(arc_box_raw)
+    /// Switches into a suspended coroutine, which runs until it yields or finishes.
+    /// A finished coroutine is not entered, null is pushed right away.
+    fn enter_coroutine(&mut self, handle: u64, value_size: usize) -> CoreResult<()> {
+        let context = {
+            let coroutine = self.coroutines.get_mut(&handle)
+                .ok_or(CoreError::InvalidCoroutine(handle))?;
+            if coroutine.running {
+                return Err(CoreError::InvalidCoroutine(handle));
+            }
+            coroutine.value_size = value_size;
+            coroutine.context.take()
+        };
+        let mut context = match context {
+            Some(context) => context,
+            None => return self.push_yielded(&vec![0; value_size], false)
         };
 
-        self.foreign_pointers.insert(ptr, arc_box_int);
+        self.swap_context(&mut context);
+        self.resumed_coroutines.push(handle);
+        let coroutine = self.coroutines.get_mut(&handle)
+            .ok_or(CoreError::InvalidCoroutine(handle))?;
+        coroutine.context = Some(context);
+        coroutine.running = true;
 
-        Ok(ptr)
+        Ok(())
     }
 
-    /// Removes a foreign pointer
-    pub fn remove_foreign_ptr<T>(&mut self, ptr: u64) -> CoreResult<Arc<Mutex<T>>> {
-        let arc_box_int = self.foreign_pointers.remove(&ptr)
-            .ok_or(CoreError::Unknown)?;
-        let arc = unsafe {
-            let arc_box_raw: *mut Arc<Mutex<T>> = std::mem::transmute(arc_box_int);
-            let arc_box = Box::from_raw(arc_box_raw);
-            *arc_box
+    /// Switches from the running coroutine back to the one that resumed it, handing it the yielded value.
+    /// Without a value the coroutine finished, the one that resumed it gets null.
+    fn leave_coroutine(&mut self, value: Option<Vec<u8>>) -> CoreResult<()> {
+        let handle = self.resumed_coroutines.pop()
+            .ok_or(CoreError::NotInCoroutine)?;
+        let (mut context, value_size) = {
+            let coroutine = self.coroutines.get_mut(&handle)
+                .ok_or(CoreError::InvalidCoroutine(handle))?;
+            coroutine.running = false;
+            let context = coroutine.context.take()
+                .ok_or(CoreError::InvalidCoroutine(handle))?;
+            (context, coroutine.value_size)
         };
-        Ok(arc)
+
+        self.swap_context(&mut context);
+        match value {
+            Some(value) => {
+                self.coroutines.get_mut(&handle)
+                    .ok_or(CoreError::InvalidCoroutine(handle))?
+                    .context = Some(context);
+                if value.len() != value_size {
+                    return Err(CoreError::InvalidCoroutine(handle));
+                }
+                self.push_yielded(&value, true)
+            },
+            // The context of a finished coroutine is dropped with its stack
+            None => self.push_yielded(&vec![0; value_size], false)
+        }
     }
 
-    fn call_foreign_fn(&mut self, uid: u64) -> CoreResult<()> {
-        let function = {
-            self.program.as_mut()
-                .ok_or(CoreError::NoProgram)?
-                .foreign_functions
-                .remove(&uid)
-                .ok_or(CoreError::UnknownFunctionUid)?
-        };
+    /// Leaves the coroutines resumed above the given depth, after one of them failed.
+    /// They can not be resumed again.
+    fn abandon_coroutines(&mut self, depth: usize) {
+        while self.resumed_coroutines.len() > depth {
+            let handle = match self.resumed_coroutines.pop() {
+                Some(handle) => handle,
+                None => break
+            };
+            let context = self.coroutines.get_mut(&handle)
+                .and_then(|coroutine| {
+                    coroutine.running = false;
+                    coroutine.context.take()
+                });
+            if let Some(mut context) = context {
+                self.swap_context(&mut context);
+            }
+        }
+    }
 
-        //println!("Calling foreign function {}", function.name);
+    /// Pushes a value yielded by a coroutine, laid out like an optional
+    fn push_yielded(&mut self, value: &[u8], has_value: bool) -> CoreResult<()> {
+        self.push_bytes(value)?;
+        self.push_stack::<u64>(has_value as u64)
+    }
 
-        {
-            let mut adapter = Adapter::new(&function, self);
-            function.run(&mut adapter);
+    /// Attaches the source position of the instruction at the given offset to an error
+    fn with_source_position(&self, error: CoreError, instr_ip: usize) -> CoreError {
+        // Errors of nested runs, e.g. from foreign functions calling back, already carry theirs
+        if let CoreError::AtSource(_, _) = error {
+            return error;
+        }
+        let position = self.program.as_ref()
+            .and_then(|program| program.get_position_at(instr_ip));
+        match position {
+            Some(position) => CoreError::AtSource(Box::new(error), position),
+            None => error
         }
+    }
 
-        self.program.as_mut()
-            .ok_or(CoreError::NoProgram)?
-            .foreign_functions
-            .insert(uid, function);
+    fn step_instr(&mut self) -> CoreResult<bool> {
+        let program_len = self.program_len()?;
+        if self.registers.ip.get::<usize>() >= program_len {
+            return Ok(false);
+        }
+        if self.interrupt.is_requested() {
+            self.interrupt.check()?;
+        }
+        // Checked before executing anything, so the instruction runs once resumed
+        if let Some(fuel) = self.fuel {
+            if fuel == 0 {
+                return Err(CoreError::OutOfFuel);
+            }
+            self.fuel = Some(fuel - 1);
+        }
+        self.instruction_count += 1;
+        self.stats.instructions += 1;
+        if let Some(max_instructions) = self.max_instructions {
+            if self.instruction_count > max_instructions {
+                return Err(CoreError::InstructionLimit);
+            }
+        }
+        //println!("ip: {}", self.registers.ip.get::<usize>());
+        let instr_ip = self.registers.ip.get::<usize>();
+        let opcode = self.get_opcode()?;
+        //println!("opcode: {:?}", opcode);
+        if self.trace_hook.is_none() && self.profile.is_none() {
+            return self.execute(opcode, instr_ip);
+        }
+        if self.trace_hook.is_none() {
+            return self.execute_profiled(opcode, instr_ip);
+        }
 
-        Ok(())
+        // Drops the opcode byte, only the operands are traced
+        if let Some(operands) = self.trace_operands.as_mut() {
+            operands.clear();
+        }
+        let result = self.execute_profiled(opcode.clone(), instr_ip);
+        let entry = TraceEntry {
+            ip: instr_ip,
+            opcode: opcode,
+            operands: self.trace_operands.as_mut()
+                .map(mem::take)
+                .unwrap_or_default(),
+            sp: self.get_stack_size()
+        };
+        if let Some(trace_hook) = self.trace_hook.as_mut() {
+            trace_hook(&entry);
+        }
+        result
     }
 
-    #[inline]
-    fn ret(&mut self) -> CoreResult<()> {
-        let old_ip = self.call_stack.pop_front()
-            .ok_or(CoreError::EmptyCallStack)?;
-        self.ip.uint64 = old_ip as u64;
-        Ok(())
+    /// Executes the given instruction, adding its time to the counters of its opcode and function if profiling
+    fn execute_profiled(&mut self, opcode: Opcode, instr_ip: usize) -> CoreResult<bool> {
+        if self.profile.is_none() {
+            return self.execute(opcode, instr_ip);
+        }
+        // The instruction counts for the function it is part of, even if it calls or returns
+        let fn_uid = self.current_fn;
+        let opcode_raw: u8 = opcode.clone().into();
+        let start = Instant::now();
+        let result = self.execute(opcode, instr_ip);
+        let time = start.elapsed();
+        if let Some(profile) = self.profile.as_mut() {
+            profile.record(opcode_raw, fn_uid, time);
+        }
+        result
     }
 
+    /// Executes the given instruction with its handler from OP_HANDLERS, its operands follow at the instruction pointer
     #[inline]
-    fn get_op<T: DeserializeOwned + Debug>(&mut self) -> CoreResult<T> {
-        let op_size = size_of::<T>();
+    fn execute(&mut self, opcode: Opcode, instr_ip: usize) -> CoreResult<bool> {
+        //println!("Stack values: {:?}", &self.stack[0..self.registers.sp]);
+        //println!("IP: {}", self.registers.ip);
+
+        let running = OP_HANDLERS[opcode as usize](self, instr_ip)?;
+        let stack_size = self.get_stack_size();
+        if stack_size > self.stats.peak_stack {
+            self.stats.peak_stack = stack_size;
+        }
+        Ok(running)
+    }
 
-        let program = self.program.as_ref()
-            .ok_or(CoreError::NoProgram)?;
+    /// Returns the n bytes of memory at the given address
+    fn mem_slice(&self, addr: &Address, n: usize) -> CoreResult<&[u8]> {
+        let memory: &[u8] = match addr.address_type {
+            AddressType::Stack => {
+                &self.stack
+            },
+            // The data segment is read from the copy of this core
+            AddressType::Program if (addr.real_address as usize) < self.data.len() => {
+                &self.data
+            },
+            AddressType::Program => {
+                let program = self.program.as_ref()
+                    .ok_or(CoreError::Unknown)?;
+                &program.code
+            },
+            AddressType::Heap => {
+                &self.heap
+            },
+            AddressType::Foreign => return Err(CoreError::InvalidAddress(addr.raw_address))
+        };
+        let start = addr.real_address as usize;
+        start.checked_add(n)
+            .and_then(|end| memory.get(start..end))
+            .ok_or(CoreError::MemoryOutOfBounds(addr.raw_address, n))
+    }
 
+    /// Returns the n bytes of memory at the given address for writing
+    fn mem_slice_mut(&mut self, addr: &Address, n: usize) -> CoreResult<&mut [u8]> {
+        let memory: &mut [u8] = match addr.address_type {
+            AddressType::Stack => {
+                &mut self.stack
+            },
+            // Only the data segment is writable, the code is shared
+            AddressType::Program => {
+                &mut self.data
+            },
+            AddressType::Heap => {
+                &mut self.heap
+            },
+            AddressType::Foreign => return Err(CoreError::InvalidAddress(addr.raw_address))
+        };
+        let start = addr.real_address as usize;
+        start.checked_add(n)
+            .and_then(move |end| memory.get_mut(start..end))
+            .ok_or(CoreError::MemoryOutOfBounds(addr.raw_address, n))
+    }
 
-        let tmp_ip = self.ip.get::<usize>();
+    fn mem_mov_n(&mut self, lhs: (u64, i16), rhs: (u64, i16), n: usize) -> CoreResult<()> {
+        let lhs_addr = Address::try_from(lhs.0)?.with_offset(lhs.1);
+        let rhs_addr = Address::try_from(rhs.0)?.with_offset(rhs.1);
 
-        //println!("Getting op with size {}...", op_size);
-        //println!("Op ends at {}!", tmp_ip + op_size);
-        //println!("Program size: {}", program.code.len());
+        let bytes = self.mem_slice(&lhs_addr, n)?.to_vec();
+        self.mem_slice_mut(&rhs_addr, n)?.copy_from_slice(&bytes);
 
-        let raw_bytes: &[u8] = &program.code[tmp_ip..tmp_ip + op_size];
-        //println!("get_op raw bytes: {:?}", raw_bytes);
+        Ok(())
+    }
 
-        let ret: T = deserialize(raw_bytes)
-            .map_err(|_| CoreError::OperatorDeserialize)?;
+    fn mem_get_n(&self, addr: (u64, i16), n: usize) -> CoreResult<Vec<u8>> {
+        let lhs_addr = Address::try_from(addr.0)?.with_offset(addr.1);
+        //println!("Getting n = {} bytes at address {:?}", n, lhs_addr);
 
-        //println!("Op: {:?}", ret);
+        Ok(
+            self.mem_slice(&lhs_addr, n)?.to_vec()
+        )
+    }
+    
+    /// Reads n raw bytes at the given address
+    pub fn mem_get_bytes(&self, addr: u64, n: usize) -> CoreResult<&[u8]> {
+        self.mem_slice(&Address::try_from(addr)?, n)
+    }
 
-        self.ip.inc(op_size);
+    /// Writes raw bytes at the given address
+    pub fn mem_set_bytes(&mut self, addr: u64, bytes: &[u8]) -> CoreResult<()> {
+        self.mem_slice_mut(&Address::try_from(addr)?, bytes.len())?
+            .copy_from_slice(bytes);
+        Ok(())
+    }
 
-        Ok(ret)
+    #[inline]
+    pub fn mem_get_string(&self, addr: u64) -> CoreResult<String> {
+        //println!("mem_get_string(): string addr: {:?}", Address::from(addr));
+        let string_size: u64 = self.mem_get((addr, 0))?;
+        //println!("String size: {}", string_size);
+        let string_addr: u64 = self.mem_get((index_addr(addr, 8)?, 0))?;
+        //println!("String addr: {}", string_addr);
+        let string_data = self.mem_get_n((string_addr, 0), string_size as usize)?;
+        String::from_utf8(string_data)
+            .map_err(|_| CoreError::OperatorDeserialize)
     }
 
     #[inline]
-    pub fn push_stack<T: Serialize>(&mut self, item: T) -> CoreResult<()> {
-        let op_size = size_of::<T>();
+    pub fn mem_get<T: DeserializeOwned>(&self, addr: (u64, i16)) -> CoreResult<T> {
+        let n = size_of::<T>();
 
-        let raw_bytes = serialize(&item)
-            .map_err(|_| CoreError::OperatorSerialize)?;
+        let data = self.mem_get_n(addr, n)?;
 
-        let sp_addr = Address::from(self.sp.get::<u64>());
-        let sp_real = sp_addr.real_address as usize;
+        deserialize(&data)
+            .map_err(|_| CoreError::OperatorDeserialize)
+    }
+    
+    #[inline]
+    pub fn mem_set<T: Serialize>(&mut self, addr: (u64, i16), item: T) -> CoreResult<()> {
+        let n = size_of::<T>();
 
-        for i in 0..op_size {
-            self.stack[sp_real + i] = raw_bytes[i];
-        }
+        let lhs_addr = Address::try_from(addr.0)?.with_offset(addr.1);
 
-        self.sp.inc(op_size);
+        let data = serialize(&item)
+            .map_err(|_| CoreError::OperatorSerialize)?;
+
+        self.mem_slice_mut(&lhs_addr, n)?.copy_from_slice(&data[..n]);
 
         Ok(())
     }
 
-    #[inline]
-    pub fn pop_stack<T: DeserializeOwned>(&mut self) -> CoreResult<T> {
-        let op_size = size_of::<T>();
+    /// Copies the given bytes onto the heap and returns their address
+    pub fn heap_alloc(&mut self, data: &[u8]) -> CoreResult<u64> {
+        let start = self.heap_reserve(data.len())?;
+        self.heap[start..start + data.len()].copy_from_slice(data);
+        self.stats.heap_allocs += 1;
+        Ok(
+            Address::new(start as u64, AddressType::Heap).into()
+        )
+    }
 
-        let mut raw_bytes = Vec::with_capacity(op_size);
-        raw_bytes.resize(op_size, 0);
+    /// Frees the heap allocation starting at the given address
+    pub fn heap_free(&mut self, addr: u64) -> CoreResult<()> {
+        let index = self.find_heap_alloc(addr)?;
+        let range = self.heap_pointers.remove(index);
+        self.heap_release(range);
+        self.heap_refcounts.remove(&addr);
+        self.stats.heap_frees += 1;
+        Ok(())
+    }
 
-        let sp_raw = self.sp.get::<u64>();
-        let sp_addr = Address::from(sp_raw);
+    /// Resizes the heap allocation starting at the given address and returns its new address.
+    /// The contents are kept up to the smaller of both sizes, grown memory is zeroed.
+    pub fn heap_realloc(&mut self, addr: u64, size: usize) -> CoreResult<u64> {
+        let index = self.find_heap_alloc(addr)?;
+        let range = self.heap_pointers[index].clone();
+        let new_end = range.start + size;
+
+        if size <= range.len() {
+            self.heap_pointers[index].end = new_end;
+            self.heap_release(new_end..range.end);
+            return Ok(addr);
+        }
 
-        if op_size > self.sp.get::<usize>() {
-            return Err(CoreError::InvalidStackPointer);
+        // The last allocation grows in place
+        if range.end == self.heap.len() {
+            self.check_heap_limit(new_end)?;
+            self.heap.resize(new_end, 0);
+            self.heap_pointers[index].end = new_end;
+            return Ok(addr);
         }
 
-        let mut source_addr = sp_addr.real_address as usize;
-        source_addr -= op_size;
+        let mut data = self.heap[range.clone()].to_vec();
+        data.resize(size, 0);
+        self.heap_pointers.remove(index);
+        self.heap_release(range);
+        let new_addr = self.heap_alloc(&data)?;
+        if let Some(count) = self.heap_refcounts.remove(&addr) {
+            self.heap_refcounts.insert(new_addr, count);
+        }
+        Ok(new_addr)
+    }
 
-        for i in 0..op_size {
-            raw_bytes[i] = self.stack[source_addr + i];
+    /// Returns the address of the heap allocation holding the data of the string value at the given address.
+    /// Slices of a string share its allocation, strings with data elsewhere, e.g. literals in the program, have none.
+    fn get_string_heap_alloc(&self, addr: (u64, i16)) -> CoreResult<Option<u64>> {
+        let len: u64 = self.mem_get(addr)?;
+        let data: u64 = self.mem_get(offset_addr(addr, 8)?)?;
+        if len == 0 || !Address::is_heap(data) {
+            return Ok(None);
         }
+        let start = Address::get_real_address(data) as usize;
+        let end = self.heap_pointers.partition_point(|range| range.start <= start);
+        // Non-empty allocations never overlap, the closest one below is the only candidate
+        let alloc = self.heap_pointers[..end].iter()
+            .rev()
+            .find(|range| range.start != range.end)
+            .filter(|range| start.checked_add(len as usize).is_some_and(|end| end <= range.end))
+            .map(|range| Address::new(range.start as u64, AddressType::Heap).into());
+        Ok(alloc)
+    }
 
-        self.sp.dec(op_size);
+    /// Counts another reference to the heap data of the string value at the given address
+    pub fn string_incref(&mut self, addr: (u64, i16)) -> CoreResult<()> {
+        if let Some(alloc) = self.get_string_heap_alloc(addr)? {
+            *self.heap_refcounts.entry(alloc).or_insert(1) += 1;
+        }
+        Ok(())
+    }
 
-        deserialize(&raw_bytes)
-            .map_err(|_| CoreError::Unknown)
+    /// Drops a reference to the heap data of the string value at the given address,
+    /// the data is freed with the last one
+    pub fn string_decref(&mut self, addr: (u64, i16)) -> CoreResult<()> {
+        if let Some(alloc) = self.get_string_heap_alloc(addr)? {
+            match self.heap_refcounts.remove(&alloc) {
+                Some(count) if count > 1 => {
+                    self.heap_refcounts.insert(alloc, count - 1);
+                },
+                _ => self.heap_free(alloc)?
+            };
+        }
+        Ok(())
     }
 
-    #[inline]
-    fn save_swap<T: Serialize>(&mut self, item: T) -> CoreResult<()> {
-        let op_size = size_of::<T>();
+    /// Returns the bytes of the string value at the given address
+    pub fn string_get_bytes(&self, addr: (u64, i16)) -> CoreResult<Vec<u8>> {
+        let len: u64 = self.mem_get(addr)?;
+        if len == 0 {
+            return Ok(Vec::new());
+        }
+        let data: u64 = self.mem_get(offset_addr(addr, 8)?)?;
+        self.mem_get_n((data, 0), len as usize)
+    }
 
-        if self.swap.len() < op_size {
-            self.swap.resize(self.swap.len() + op_size, 0);
+    /// Concatenates the string values at the given addresses into a new one on the heap.
+    /// Returns its length and data address, the caller owns the only reference.
+    pub fn string_concat(&mut self, lhs: (u64, i16), rhs: (u64, i16)) -> CoreResult<(u64, u64)> {
+        let mut bytes = self.string_get_bytes(lhs)?;
+        bytes.append(&mut self.string_get_bytes(rhs)?);
+        if bytes.is_empty() {
+            return Ok((0, 0));
         }
+        let data = self.heap_alloc(&bytes)?;
+        Ok((bytes.len() as u64, data))
+    }
 
-        let raw_bytes = serialize(&item)
-            .map_err(|_| CoreError::OperatorSerialize)?;
+    /// Returns the length and data address of the bytes start..end of the string value at the given address.
+    /// Both bounds have to be on char boundaries, the substring shares the data of the string.
+    pub fn string_substring(&self, addr: (u64, i16), start: i64, end: i64) -> CoreResult<(u64, u64)> {
+        let len: u64 = self.mem_get(addr)?;
+        if start < 0 || start > end || end > len as i64 {
+            return Err(CoreError::OutOfBounds(start, end, len as i64));
+        }
+        let data: u64 = self.mem_get(offset_addr(addr, 8)?)?;
+        for index in [start, end].iter().filter(|index| **index < len as i64) {
+            let byte: u8 = self.mem_get((index_addr(data, *index as u64)?, 0))?;
+            if is_utf8_continuation(byte) {
+                return Err(CoreError::InvalidCharBoundary(*index));
+            }
+        }
+        Ok(((end - start) as u64, index_addr(data, start as u64)?))
+    }
 
-        for i in 0..op_size {
-            self.swap[i] = raw_bytes[i];
+    /// Decodes the char starting at the given byte index of the string value at the given address
+    pub fn string_char_at(&self, addr: (u64, i16), index: i64) -> CoreResult<char> {
+        let len: u64 = self.mem_get(addr)?;
+        if index < 0 || index >= len as i64 {
+            return Err(CoreError::OutOfBounds(index, index.saturating_add(1), len as i64));
         }
+        let data: u64 = self.mem_get(offset_addr(addr, 8)?)?;
+        // A char takes up to 4 bytes
+        let n = (len as i64 - index).min(4) as usize;
+        let bytes = self.mem_get_n((index_addr(data, index as u64)?, 0), n)?;
+        let width = match bytes[0] {
+            byte if byte < 0x80 => 1,
+            byte if byte < 0xE0 => 2,
+            byte if byte < 0xF0 => 3,
+            _ => 4
+        };
+        bytes.get(..width)
+            .filter(|_| !is_utf8_continuation(bytes[0]))
+            .and_then(|bytes| std::str::from_utf8(bytes).ok())
+            .and_then(|string| string.chars().next())
+            .ok_or(CoreError::InvalidCharBoundary(index))
+    }
 
-        Ok(())
+    /// Finds room for n bytes, in the first free range that fits or else at the end of the heap
+    fn heap_reserve(&mut self, n: usize) -> CoreResult<usize> {
+        let free_index = match n {
+            0 => None,
+            _ => self.heap_free_list.iter().position(|range| range.len() >= n)
+        };
+
+        let start = match free_index {
+            Some(index) => {
+                let range = &mut self.heap_free_list[index];
+                let start = range.start;
+                range.start += n;
+                if range.start == range.end {
+                    self.heap_free_list.remove(index);
+                }
+                start
+            },
+            None => {
+                let start = self.heap.len();
+                self.check_heap_limit(start + n)?;
+                self.heap.resize(start + n, 0);
+                start
+            }
+        };
+
+        let index = self.heap_pointers.partition_point(|range| range.start <= start);
+        self.heap_pointers.insert(index, start..start + n);
+        Ok(start)
+    }
+
+    /// Returns a range of the heap to the free list, merging it with its neighbours.
+    /// A range at the end of the heap shrinks the heap instead.
+    fn heap_release(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        let mut index = self.heap_free_list.partition_point(|free| free.start < range.start);
+        let mut start = range.start;
+        let mut end = range.end;
+
+        if index < self.heap_free_list.len() && self.heap_free_list[index].start == end {
+            end = self.heap_free_list.remove(index).end;
+        }
+        if index > 0 && self.heap_free_list[index - 1].end == start {
+            start = self.heap_free_list.remove(index - 1).start;
+            index -= 1;
+        }
+
+        if end == self.heap.len() {
+            self.heap.truncate(start);
+        }
+        else {
+            self.heap_free_list.insert(index, start..end);
+        }
+    }
+
+    /// Finds the index of the allocation starting at the given address.
+    /// If an empty allocation shares its start with another one, the latter is found.
+    fn find_heap_alloc(&self, addr: u64) -> CoreResult<usize> {
+        if !Address::is_heap(addr) {
+            return Err(CoreError::InvalidHeapAddress(addr));
+        }
+        let start = Address::get_real_address(addr) as usize;
+        let end = self.heap_pointers.partition_point(|range| range.start <= start);
+        if end == 0 || self.heap_pointers[end - 1].start != start {
+            return Err(CoreError::InvalidHeapAddress(addr));
+        }
+        Ok(end - 1)
+    }
+
+    /// Checks a new end of the heap against the maximum heap size
+    fn check_heap_limit(&self, heap_end: usize) -> CoreResult<()> {
+        if let Some(max_heap) = self.max_heap {
+            if heap_end > max_heap {
+                return Err(CoreError::HeapLimit);
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks if a heap allocation of the given size starts at the given address
+    pub fn is_heap_alloc(&self, addr: u64, size: usize) -> bool {
+        if !Address::is_heap(addr) {
+            return false;
+        }
+        let start = Address::get_real_address(addr) as usize;
+        // Empty allocations share their start with the next one
+        let first = self.heap_pointers.partition_point(|range| range.start < start);
+        self.heap_pointers[first..].iter()
+            .take_while(|range| range.start == start)
+            .any(|range| range.len() == size)
+    }
+
+    /// Gets the register with the given index for writing, fails with InvalidRegister past FP_INDEX
+    #[inline]
+    pub fn reg(&mut self, reg: u8) -> CoreResult<&mut Register> {
+        self.registers.get_mut(reg)
+    }
+
+    /// Gets the value of the register with the given index, fails with InvalidRegister past FP_INDEX
+    pub fn get_register(&self, reg: u8) -> CoreResult<Register> {
+        self.registers.get(reg)
+            .copied()
+    }
+
+    pub fn get_registers(&self) -> &RegisterFile {
+        &self.registers
+    }
+
+    #[inline]
+    fn call(&mut self, instr_ip: usize) -> CoreResult<()> {
+        let fn_uid: u64 = self.get_op()?;
+        self.call_uid(fn_uid, instr_ip)
+    }
+
+    /// Calls the function with the given uid, used by CALL and by DCALL with the uid of a callable value.
+    /// The offset of the calling instruction is where a blocked foreign call is repeated from.
+    fn call_uid(&mut self, fn_uid: u64, instr_ip: usize) -> CoreResult<()> {
+        if self.foreign_function_names.contains_key(&fn_uid) {
+            return self.call_foreign_fn(fn_uid, instr_ip);
+        }
+
+        let program = self.program.as_ref()
+            .ok_or(CoreError::NoProgram)?;
+
+        let new_ip = *program.functions.get(&fn_uid)
+            .ok_or(CoreError::UnknownFunctionUid)?;
+        
+        self.check_stack_space(CALL_FRAME_SIZE)?;
+
+        let old_ip: usize = self.registers.ip.get();
+        let old_fp: u64 = self.registers.fp.get();
+        self.call_stack.push_front((old_ip, old_fp, self.current_fn));
+        self.registers.fp.set::<u64>(self.registers.sp.get());
+        self.registers.ip.set(new_ip);
+        self.current_fn = Some(fn_uid);
+
+        #[cfg(feature = "jit")]
+        self.run_jit(fn_uid, new_ip);
+
+        Ok(())
+    }
+
+    /// Runs a called script function as native code once it is hot.
+    /// The interpreter continues where the native code stopped, at the latest at its RET.
+    #[cfg(feature = "jit")]
+    fn run_jit(&mut self, fn_uid: u64, fn_offset: usize) {
+        // Native code does not count instructions, limits, tracing, profiling, breakpoints and task switches need the interpreter
+        let interpreted = self.fuel.is_some() ||
+            self.max_instructions.is_some() ||
+            self.trace_hook.is_some() ||
+            self.profile.is_some() ||
+            !self.breakpoints.is_empty() ||
+            !self.tasks.is_empty();
+        let threshold = match self.jit_threshold {
+            Some(threshold) if !interpreted => threshold,
+            _ => return
+        };
+        if self.jit.is_none() {
+            self.jit = Jit::new(threshold);
+            // Cranelift does not support the host
+            if self.jit.is_none() {
+                self.jit_threshold = None;
+                return;
+            }
+        }
+        let function = match (self.jit.as_mut(), self.program.as_ref()) {
+            (Some(jit), Some(program)) => jit.on_call(program, fn_uid, fn_offset),
+            _ => None
+        };
+        let function = match function {
+            Some(function) => function,
+            None => return
+        };
+
+        let mut registers: [u64; JIT_REGISTER_COUNT] = self.registers.to_bits();
+
+        let resume_ip = unsafe {
+            function(registers.as_mut_ptr(), self.stack.as_mut_ptr(), self.stack.len() as u64, self.interrupt.get_requested_ptr())
+        };
+
+        self.registers = RegisterFile::from_bits(&registers);
+        self.registers.ip.set(resume_ip);
+        self.stats.jit_calls += 1;
+        let stack_size = self.get_stack_size();
+        if stack_size > self.stats.peak_stack {
+            self.stats.peak_stack = stack_size;
+        }
+    }
+
+    /// Calls a function in place of the current one, without pushing the call stack.
+    /// The arguments on top of the stack are moved down to the base of the current frame,
+    /// i.e. right above the arguments of the current function, and the callee returns to our caller.
+    /// The frame pointer stays the same, as the new frame begins where the current one did.
+    /// Foreign functions can not be tail called.
+    fn tail_call(&mut self) -> CoreResult<()> {
+        let fn_uid: u64 = self.get_op()?;
+        // Size of the current frame without its arguments
+        let frame_size: u32 = self.get_op()?;
+        let args_size: u32 = self.get_op()?;
+
+        let new_ip = {
+            let program = self.program.as_ref()
+                .ok_or(CoreError::NoProgram)?;
+            program.functions.get(&fn_uid)
+                .cloned()
+                .ok_or(CoreError::UnknownFunctionUid)?
+        };
+
+        // The arguments, the frame and the arguments of the current function are all below the stack pointer
+        let sp: u64 = self.registers.sp.get();
+        let below = 2 * args_size as u64 + frame_size as u64;
+        if Address::try_from(sp)?.real_address < below {
+            return Err(CoreError::InvalidStackPointer);
+        }
+        let args_addr = sp - args_size as u64;
+        let frame_addr = args_addr - frame_size as u64;
+        self.mem_mov_n((args_addr, 0), (frame_addr - args_size as u64, 0), args_size as usize)?;
+        self.registers.sp.set(frame_addr);
+        self.registers.ip.set(new_ip);
+        self.current_fn = Some(fn_uid);
+
+        Ok(())
+    }
+
+    /// Retrieves a foreign pointer and returns the correct
+    /// Arc<Mutex<T>> if found.
+    pub fn get_foreign_ptr<T>(&self, ptr: u64) -> CoreResult<Arc<Mutex<T>>> {
+        let arc_box_int = self.foreign_pointers.get(&ptr)
+            .ok_or(CoreError::Unknown)?;
+        let arc = unsafe {
+            let arc_box_raw: *mut Arc<Mutex<T>> = std::mem::transmute(*arc_box_int);
+            let arc_box = Box::from_raw(arc_box_raw);
+            let ret = arc_box.deref().clone();
+            std::mem::forget(arc_box);
+            ret
+        };
+        Ok(arc)
+    }
+
+    /// Inserts a foreign pointer
+    pub fn insert_foreign_ptr<T>(&mut self, item: Arc<Mutex<T>>) -> CoreResult<u64> {
+        let mut uid_gen = UIDGenerator::new();
+
+        // The upper 3 bits are reserved for the address type
+        let mut addr = Address::new(uid_gen.generate() >> 3, AddressType::Foreign);
+        while self.foreign_pointers.contains_key(&addr.raw_address) {
+            addr = Address::new(uid_gen.generate() >> 3, AddressType::Foreign);
+        }
+
+        let ptr = addr.into();
+        
+        let arc_box = Box::new(item);
+        let arc_box_int: u64 = unsafe {
+            let arc_box_raw = Box::into_raw(arc_box);
+            std::mem::transmute(arc_box_raw)
+        };
+
+        self.foreign_pointers.insert(ptr, arc_box_int);
+        self.foreign_types.insert(ptr, type_name::<T>());
+
+        Ok(ptr)
+    }
+
+    /// Keeps an extension library open as long as this core and its isolates
+    #[cfg(feature = "extensions")]
+    pub fn add_extension(&mut self, extension: Arc<Extension>) {
+        self.extensions.push(extension);
+    }
+
+    /// Retrieves a foreign pointer like get_foreign_ptr(), but fails with InvalidHandle
+    /// if the item is not of the given type
+    pub fn get_foreign_object<T>(&self, ptr: u64) -> CoreResult<Arc<Mutex<T>>> {
+        match self.foreign_types.get(&ptr) {
+            Some(item_type) if *item_type == type_name::<T>() => self.get_foreign_ptr(ptr),
+            _ => Err(CoreError::InvalidHandle(ptr))
+        }
+    }
+
+    /// Sets the context of its type foreign functions can borrow, e.g. the game state.
+    /// Returns the context of the same type it replaces.
+    pub fn set_context<T: Any + Send>(&mut self, context: T) -> Option<T> {
+        self.contexts.insert(TypeId::of::<T>(), Box::new(context))
+            .map(|replaced| *replaced.downcast::<T>().unwrap())
+    }
+
+    /// Sets a boxed context, its type is the one of the boxed value
+    pub fn set_context_box(&mut self, context: Box<dyn Any + Send>) {
+        self.contexts.insert(context.as_ref().type_id(), context);
+    }
+
+    /// Gets the context of the given type
+    pub fn get_context<T: Any + Send>(&self) -> Option<&T> {
+        self.contexts.get(&TypeId::of::<T>())
+            .and_then(|context| context.downcast_ref::<T>())
+    }
+
+    /// Gets the context of the given type for modification
+    pub fn get_context_mut<T: Any + Send>(&mut self) -> Option<&mut T> {
+        self.contexts.get_mut(&TypeId::of::<T>())
+            .and_then(|context| context.downcast_mut::<T>())
+    }
+
+    /// Removes the context of the given type
+    pub fn take_context<T: Any + Send>(&mut self) -> Option<T> {
+        self.contexts.remove(&TypeId::of::<T>())
+            .map(|context| *context.downcast::<T>().unwrap())
+    }
+
+    /// Removes all contexts, e.g. to hand them to a new core
+    pub fn take_contexts(&mut self) -> ContextMap {
+        mem::take(&mut self.contexts)
+    }
+
+    /// Removes a foreign pointer like remove_foreign_ptr(), but fails with InvalidHandle
+    /// if the item is not of the given type
+    pub fn remove_foreign_object<T>(&mut self, ptr: u64) -> CoreResult<Arc<Mutex<T>>> {
+        match self.foreign_types.get(&ptr) {
+            Some(item_type) if *item_type == type_name::<T>() => self.remove_foreign_ptr(ptr),
+            _ => Err(CoreError::InvalidHandle(ptr))
+        }
+    }
+
+    /// Removes a foreign pointer
+    pub fn remove_foreign_ptr<T>(&mut self, ptr: u64) -> CoreResult<Arc<Mutex<T>>> {
+        let arc_box_int = self.foreign_pointers.remove(&ptr)
+            .ok_or(CoreError::Unknown)?;
+        self.foreign_types.remove(&ptr);
+        let arc = unsafe {
+            let arc_box_raw: *mut Arc<Mutex<T>> = std::mem::transmute(arc_box_int);
+            let arc_box = Box::from_raw(arc_box_raw);
+            *arc_box
+        };
+        Ok(arc)
+    }
+
+    fn call_foreign_fn(&mut self, uid: u64, instr_ip: usize) -> CoreResult<()> {
+        // Held on to separately, as the function gets the core to work with
+        let program = self.program.clone()
+            .ok_or(CoreError::NoProgram)?;
+        let function = program.foreign_functions
+            .get(&uid)
+            .ok_or(CoreError::UnknownFunctionUid)?;
+
+        //println!("Calling foreign function {}", function.name);
+        let fn_name = self.foreign_function_names.get(&uid)
+            .unwrap_or(&function.name);
+        match self.stats.foreign_calls.get_mut(fn_name) {
+            Some(count) => *count += 1,
+            None => {
+                self.stats.foreign_calls.insert(fn_name.clone(), 1);
+            }
+        };
+
+        {
+            let mut adapter = Adapter::new(function, self);
+            function.run(&mut adapter);
+        }
+
+        if let Some(error) = self.foreign_error.take() {
+            return Err(error);
+        }
+
+        if self.foreign_blocked {
+            // Nothing would resume the call, the host would repeat it forever
+            if !self.ticking {
+                self.foreign_blocked = false;
+                return Err(CoreError::BlockedOutsideTick);
+            }
+            // Rewind to the calling instruction, so the call is repeated on resume
+            self.registers.ip.set(instr_ip);
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    fn ret(&mut self) -> CoreResult<()> {
+        let (old_ip, old_fp, old_fn) = self.call_stack.pop_front()
+            .ok_or(CoreError::EmptyCallStack)?;
+        self.registers.ip.uint64 = old_ip as u64;
+        self.registers.fp.set(old_fp);
+        self.current_fn = old_fn;
+        Ok(())
+    }
+
+    /// Reads the next operand of the current instruction
+    #[inline]
+    fn get_op<T: Operand>(&mut self) -> CoreResult<T> {
+        let op_size = T::SIZE;
+
+        let program = self.program.as_ref()
+            .ok_or(CoreError::NoProgram)?;
+
+        let tmp_ip = self.registers.ip.get::<usize>();
+
+        let raw_bytes: &[u8] = program.code.get(tmp_ip..tmp_ip + op_size)
+            .ok_or(CoreError::MemoryOutOfBounds(tmp_ip as u64, op_size))?;
+        if let Some(operands) = self.trace_operands.as_mut() {
+            operands.extend_from_slice(raw_bytes);
+        }
+
+        let ret = T::read_le(raw_bytes);
+
+        self.registers.ip.inc(op_size);
+
+        Ok(ret)
+    }
+
+    #[inline]
+    pub fn push_stack<T: Serialize>(&mut self, item: T) -> CoreResult<()> {
+        let op_size = size_of::<T>();
+
+        let raw_bytes = serialize(&item)
+            .map_err(|_| CoreError::OperatorSerialize)?;
+
+        self.check_stack_space(op_size)?;
+
+        let sp_addr = Address::try_from(self.registers.sp.get::<u64>())?;
+        self.mem_slice_mut(&sp_addr, op_size)?.copy_from_slice(&raw_bytes[..op_size]);
+
+        self.registers.sp.inc(op_size);
+
+        Ok(())
+    }
+
+    #[inline]
+    pub fn pop_stack<T: DeserializeOwned>(&mut self) -> CoreResult<T> {
+        let op_size = size_of::<T>();
+
+        let sp_raw = self.registers.sp.get::<u64>();
+        let sp_addr = Address::try_from(sp_raw)?;
+
+        if op_size as u64 > sp_addr.real_address {
+            return Err(CoreError::InvalidStackPointer);
+        }
+
+        let source_addr = Address::new(sp_addr.real_address - op_size as u64, AddressType::Stack);
+        let raw_bytes = self.mem_slice(&source_addr, op_size)?.to_vec();
+
+        self.registers.sp.dec(op_size);
+
+        deserialize(&raw_bytes)
+            .map_err(|_| CoreError::Unknown)
+    }
+
+    /// Pushes raw bytes on the stack
+    pub fn push_bytes(&mut self, bytes: &[u8]) -> CoreResult<()> {
+        self.check_stack_space(bytes.len())?;
+
+        let sp_addr = Address::try_from(self.registers.sp.get::<u64>())?;
+        self.mem_slice_mut(&sp_addr, bytes.len())?.copy_from_slice(bytes);
+
+        self.registers.sp.inc(bytes.len());
+
+        Ok(())
+    }
+
+    /// Pops n raw bytes off the stack
+    fn pop_bytes(&mut self, n: usize) -> CoreResult<Vec<u8>> {
+        let sp_addr = Address::try_from(self.registers.sp.get::<u64>())?;
+
+        if n as u64 > sp_addr.real_address {
+            return Err(CoreError::InvalidStackPointer);
+        }
+
+        let source_addr = Address::new(sp_addr.real_address - n as u64, AddressType::Stack);
+        let bytes = self.mem_slice(&source_addr, n)?.to_vec();
+
+        self.registers.sp.dec(n);
+
+        Ok(bytes)
+    }
+}
+
+/// Instruction handlers, one per opcode
+impl Core {
+    /// Fails on opcodes without a handler
+    fn exec_unimplemented(&mut self, instr_ip: usize) -> CoreResult<bool> {
+        let opcode = self.program.as_ref()
+            .and_then(|program| program.code.get(instr_ip))
+            .and_then(|op| Opcode::try_from(*op).ok())
+            .ok_or(CoreError::Unknown)?;
+        Err(CoreError::UnimplementedOpcode(opcode))
+    }
+
+    fn exec_noop(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        Ok(true)
+    }
+
+    fn exec_halt(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let err_code: u8 = self.get_op()?;
+        match err_code {
+            1 => Err(CoreError::NoReturnValue),
+            _ => Err(CoreError::Halted(err_code))
+        }
+    }
+
+    fn exec_movb(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs: u8 = self.get_op()?;
+        let rhs: u8 = self.get_op()?;
+        let boolean: bool = {
+            self.reg(lhs)?.get()
+        };
+        self.reg(rhs)?.set(boolean);
+        Ok(true)
+    }
+
+    fn exec_movf(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs: u8 = self.get_op()?;
+        let rhs: u8 = self.get_op()?;
+        let float: f32 = {
+            self.reg(lhs)?.get()
+        };
+        self.reg(rhs)?.set(float);
+        Ok(true)
+    }
+
+    fn exec_movd(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs: u8 = self.get_op()?;
+        let rhs: u8 = self.get_op()?;
+        let double: f64 = {
+            self.reg(lhs)?.get()
+        };
+        self.reg(rhs)?.set(double);
+        Ok(true)
+    }
+
+    fn exec_movi(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs: u8 = self.get_op()?;
+        let rhs: u8 = self.get_op()?;
+        let int64: i64 = {
+            self.reg(lhs)?.get()
+        };
+        self.reg(rhs)?.set(int64);
+        Ok(true)
+    }
+
+    fn exec_mova(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs: u8 = self.get_op()?;
+        let rhs: u8 = self.get_op()?;
+        let uint64: u64 = {
+            self.reg(lhs)?.get()
+        };
+        self.reg(rhs)?.set(uint64);
+        Ok(true)
+    }
+
+    fn exec_movb_a(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let lhs_offset: i16 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let rhs_offset: i16 = self.get_op()?;
+        let lhs_addr: u64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs_addr: u64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.mem_mov_n((lhs_addr, lhs_offset), (rhs_addr, rhs_offset), 1)?;
+        Ok(true)
+    }
+
+    fn exec_movf_a(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let lhs_offset: i16 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let rhs_offset: i16 = self.get_op()?;
+        let lhs_addr: u64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs_addr: u64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.mem_mov_n((lhs_addr, lhs_offset), (rhs_addr, rhs_offset), 4)?;
+        Ok(true)
+    }
+
+    fn exec_movd_a(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let lhs_offset: i16 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let rhs_offset: i16 = self.get_op()?;
+        let lhs_addr: u64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs_addr: u64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.mem_mov_n((lhs_addr, lhs_offset), (rhs_addr, rhs_offset), 8)?;
+        Ok(true)
+    }
+
+    fn exec_movi_a(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let lhs_offset: i16 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let rhs_offset: i16 = self.get_op()?;
+        let lhs_addr: u64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs_addr: u64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.mem_mov_n((lhs_addr, lhs_offset), (rhs_addr, rhs_offset), 8)?;
+        Ok(true)
+    }
+
+    fn exec_mova_a(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let lhs_offset: i16 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let rhs_offset: i16 = self.get_op()?;
+        let lhs_addr: u64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs_addr: u64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.mem_mov_n((lhs_addr, lhs_offset), (rhs_addr, rhs_offset), 8)?;
+        Ok(true)
+    }
+
+    fn exec_movn_a(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let lhs_offset: i16 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let rhs_offset: i16 = self.get_op()?;
+        let n: usize = self.get_op::<u32>()? as usize;
+        let lhs_addr: u64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs_addr: u64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.mem_mov_n((lhs_addr, lhs_offset), (rhs_addr, rhs_offset), n)?;
+        Ok(true)
+    }
+
+    fn exec_movb_ar(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let lhs_offset: i16 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let lhs_addr: u64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let boolean: bool = self.mem_get((lhs_addr, lhs_offset))?;
+        self.reg(rhs_reg)?.set(boolean);
+        Ok(true)
+    }
+
+    fn exec_movf_ar(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let lhs_offset: i16 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let lhs_addr: u64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let float: f32 = self.mem_get((lhs_addr, lhs_offset))?;
+        self.reg(rhs_reg)?.set(float);
+        Ok(true)
+    }
+
+    fn exec_movd_ar(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let lhs_offset: i16 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let lhs_addr: u64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let double: f64 = self.mem_get((lhs_addr, lhs_offset))?;
+        self.reg(rhs_reg)?.set(double);
+        Ok(true)
+    }
+
+    fn exec_movi_ar(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let lhs_offset: i16 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let lhs_addr: u64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let int64: i64 = self.mem_get((lhs_addr, lhs_offset))?;
+        self.reg(rhs_reg)?.set(int64);
+        Ok(true)
+    }
+
+    fn exec_mova_ar(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let lhs_offset: i16 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let lhs_addr: u64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let uint64: u64 = self.mem_get((lhs_addr, lhs_offset))?;
+        self.reg(rhs_reg)?.set(uint64);
+        Ok(true)
+    }
+
+    fn exec_movu8_ar(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let lhs_offset: i16 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let lhs_addr: u64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let byte: u8 = self.mem_get((lhs_addr, lhs_offset))?;
+        self.reg(rhs_reg)?.set(byte as i64);
+        Ok(true)
+    }
+
+    fn exec_movc_ar(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let lhs_offset: i16 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let lhs_addr: u64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let character: u32 = self.mem_get((lhs_addr, lhs_offset))?;
+        self.reg(rhs_reg)?.set(character as i64);
+        Ok(true)
+    }
+
+    fn exec_movb_ra(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let rhs_offset: i16 = self.get_op()?;
+        let rhs_addr: u64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        let boolean: bool = {
+            self.reg(lhs_reg)?.get()
+        };
+        self.mem_set((rhs_addr, rhs_offset), boolean)?;
+        Ok(true)
+    }
+
+    fn exec_movc_ra(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let rhs_offset: i16 = self.get_op()?;
+        let rhs_addr: u64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        let character: i64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        self.mem_set((rhs_addr, rhs_offset), character as u32)?;
+        Ok(true)
+    }
+
+    fn exec_movf_ra(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let rhs_offset: i16 = self.get_op()?;
+        let rhs_addr: u64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        let float: f32 = {
+            self.reg(lhs_reg)?.get()
+        };
+        self.mem_set((rhs_addr, rhs_offset), float)?;
+        Ok(true)
+    }
+
+    fn exec_movd_ra(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let rhs_offset: i16 = self.get_op()?;
+        let rhs_addr: u64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        let double: f64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        self.mem_set((rhs_addr, rhs_offset), double)?;
+        Ok(true)
+    }
+
+    fn exec_movi_ra(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let rhs_offset: i16 = self.get_op()?;
+        let rhs_addr: u64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        let int64: i64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        self.mem_set((rhs_addr, rhs_offset), int64)?;
+        Ok(true)
+    }
+
+    fn exec_mova_ra(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let rhs_offset: i16 = self.get_op()?;
+        let rhs_addr: u64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        let uint64: u64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        self.mem_set((rhs_addr, rhs_offset), uint64)?;
+        Ok(true)
+    }
+
+    fn exec_ldb(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let boolean: bool = self.get_op()?;
+        let lhs_reg: u8 = self.get_op()?;
+        self.reg(lhs_reg)?.set(boolean);
+        Ok(true)
+    }
+
+    fn exec_ldf(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let float: f32 = self.get_op()?;
+        let lhs_reg: u8 = self.get_op()?;
+        self.reg(lhs_reg)?.set(float);
+        Ok(true)
+    }
+
+    fn exec_ldd(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let double: f64 = self.get_op()?;
+        let lhs_reg: u8 = self.get_op()?;
+        self.reg(lhs_reg)?.set(double);
+        Ok(true)
+    }
+
+    fn exec_ldi(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let int64: i64 = self.get_op()?;
+        let lhs_reg: u8 = self.get_op()?;
+        self.reg(lhs_reg)?.set(int64);
+        Ok(true)
+    }
+
+    /// Loads the raw bits of an int or double constant from the data segment
+    fn exec_ldc(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let addr: u32 = self.get_op()?;
+        let lhs_reg: u8 = self.get_op()?;
+        let bits: u64 = self.mem_get((addr as u64, 0))?;
+        self.reg(lhs_reg)?.set(bits);
+        Ok(true)
+    }
+
+    fn exec_lda(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let uint64: u64 = self.get_op()?;
+        let lhs_reg: u8 = self.get_op()?;
+        self.reg(lhs_reg)?.set(uint64);
+        Ok(true)
+    }
+
+    fn exec_addi(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: i64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: i64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs + rhs);
+        Ok(true)
+    }
+
+    fn exec_subi(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: i64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: i64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs - rhs);
+        Ok(true)
+    }
+
+    fn exec_muli(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: i64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: i64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs * rhs);
+        Ok(true)
+    }
+
+    fn exec_divi(&mut self, instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: i64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: i64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        if rhs == 0 {
+            return Err(CoreError::DivisionByZero(instr_ip));
+        }
+        self.reg(target_reg)?.set(lhs.wrapping_div(rhs));
+        Ok(true)
+    }
+
+    fn exec_modi(&mut self, instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: i64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: i64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        if rhs == 0 {
+            return Err(CoreError::DivisionByZero(instr_ip));
+        }
+        self.reg(target_reg)?.set(lhs.wrapping_rem(rhs));
+        Ok(true)
+    }
+
+    fn exec_addi_i(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs: i64 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: i64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs + rhs);
+        Ok(true)
+    }
+
+    fn exec_subi_i(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs: i64 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: i64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs - rhs);
+        Ok(true)
+    }
+
+    fn exec_muli_i(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs: i64 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: i64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs * rhs);
+        Ok(true)
+    }
+
+    fn exec_divi_i(&mut self, instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs: i64 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: i64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        if rhs == 0 {
+            return Err(CoreError::DivisionByZero(instr_ip));
+        }
+        self.reg(target_reg)?.set(lhs.wrapping_div(rhs));
+        Ok(true)
+    }
+
+    fn exec_addu(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: u64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: u64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs + rhs);
+        Ok(true)
+    }
+
+    fn exec_subu(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: u64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: u64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs - rhs);
+        Ok(true)
+    }
+
+    fn exec_mulu(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: u64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: u64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs * rhs);
+        Ok(true)
+    }
+
+    fn exec_divu(&mut self, instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: u64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: u64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        if rhs == 0 {
+            return Err(CoreError::DivisionByZero(instr_ip));
+        }
+        self.reg(target_reg)?.set(lhs.wrapping_div(rhs));
+        Ok(true)
+    }
+
+    fn exec_addu_i(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs: u64 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: u64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        //println!("ADDUI: {} + {}", lhs, rhs);
+        if lhs_reg == SP_INDEX && target_reg == SP_INDEX {
+            let lhs = Address::try_from(self.registers.sp.get::<u64>())?.real_address;
+            //println!("Incrementing SP(={}) by {}", lhs, rhs);
+            if lhs.checked_add(rhs).is_none_or(|end| end > self.stack.len() as u64) {
+                return Err(CoreError::StackOverflow);
+            }
+        }
+        self.reg(target_reg)?.set(lhs + rhs);
+        Ok(true)
+    }
+
+    fn exec_subu_i(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs: u64 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: u64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        if lhs_reg == SP_INDEX && target_reg == SP_INDEX {
+            let lhs = Address::try_from(self.registers.sp.get::<u64>())?.real_address;
+            //println!("Decrementing SP(={}) by {}", lhs, rhs);
+        }
+        self.reg(target_reg)?.set(lhs - rhs);
+        Ok(true)
+    }
+
+    fn exec_mulu_i(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs: u64 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: u64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs * rhs);
+        Ok(true)
+    }
+
+    fn exec_divu_i(&mut self, instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs: u64 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: u64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        if rhs == 0 {
+            return Err(CoreError::DivisionByZero(instr_ip));
+        }
+        self.reg(target_reg)?.set(lhs.wrapping_div(rhs));
+        Ok(true)
+    }
+
+    fn exec_addf(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: f32 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: f32 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs + rhs);
+        Ok(true)
+    }
+
+    fn exec_addd(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: f64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: f64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs + rhs);
+        Ok(true)
+    }
+
+    fn exec_subf(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: f32 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: f32 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs - rhs);
+        Ok(true)
+    }
+
+    fn exec_subd(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: f64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: f64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs - rhs);
+        Ok(true)
+    }
+
+    fn exec_mulf(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: f32 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: f32 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs * rhs);
+        Ok(true)
+    }
+
+    fn exec_muld(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: f64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: f64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs * rhs);
+        Ok(true)
+    }
+
+    fn exec_divf(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: f32 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: f32 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs / rhs);
+        Ok(true)
+    }
+
+    fn exec_divd(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: f64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: f64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs / rhs);
+        Ok(true)
+    }
+
+    fn exec_modf(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: f32 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: f32 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs % rhs);
+        Ok(true)
+    }
+
+    fn exec_modd(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: f64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: f64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs % rhs);
+        Ok(true)
+    }
+
+    fn exec_addf_i(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs: f32 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: f32 = {
+            self.reg(lhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs + rhs);
+        Ok(true)
+    }
+
+    fn exec_subf_i(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs: f32 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: f32 = {
+            self.reg(lhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs - rhs);
+        Ok(true)
+    }
+
+    fn exec_mulf_i(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs: f32 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: f32 = {
+            self.reg(lhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs * rhs);
+        Ok(true)
+    }
+
+    fn exec_divf_i(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs: f32 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: f32 = {
+            self.reg(lhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs / rhs);
+        Ok(true)
+    }
+
+    /// Jumps by the given offset, relative to the start of the jump instruction
+    #[inline]
+    fn jump_relative(&mut self, instr_ip: usize, offset: i64) {
+        self.registers.ip.set((instr_ip as i64).wrapping_add(offset) as u64);
+    }
+
+    fn exec_jmp(&mut self, instr_ip: usize) -> CoreResult<bool> {
+        let offset: i64 = self.get_op()?;
+        self.jump_relative(instr_ip, offset);
+        Ok(true)
+    }
+
+    fn exec_jmpt(&mut self, instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let offset: i64 = self.get_op()?;
+        let lhs: bool = {
+            self.reg(lhs_reg)?.get()
+        };
+        if lhs {
+            self.jump_relative(instr_ip, offset);
+        }
+        Ok(true)
+    }
+
+    fn exec_jmpf(&mut self, instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let offset: i64 = self.get_op()?;
+        let lhs: bool = {
+            self.reg(lhs_reg)?.get()
+        };
+        if !lhs {
+            self.jump_relative(instr_ip, offset);
+        }
+        Ok(true)
+    }
+
+    fn exec_djmp(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let target_ip: u64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        self.registers.ip.set(target_ip);
+        Ok(true)
+    }
+
+    fn exec_djmpt(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_ip: u64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        let lhs: bool = {
+            self.reg(lhs_reg)?.get()
+        };
+        if lhs {
+            self.registers.ip.set(target_ip);
+        }
+        Ok(true)
+    }
+
+    fn exec_djmpf(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_ip: u64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        let lhs: bool = {
+            self.reg(lhs_reg)?.get()
+        };
+        if !lhs {
+            self.registers.ip.set(target_ip);
+        }
+        Ok(true)
+    }
+
+    fn exec_call(&mut self, instr_ip: usize) -> CoreResult<bool> {
+        self.call(instr_ip)?;
+        Ok(true)
+    }
+
+    fn exec_dcall(&mut self, instr_ip: usize) -> CoreResult<bool> {
+        let uid_reg: u8 = self.get_op()?;
+        let fn_uid: u64 = {
+            self.reg(uid_reg)?.get()
+        };
+        self.call_uid(fn_uid, instr_ip)?;
+        Ok(true)
+    }
+
+    fn exec_tcall(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        self.tail_call()?;
+        Ok(true)
+    }
+
+    fn exec_spawn(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        self.spawn()?;
+        Ok(true)
+    }
+
+    fn exec_coro(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        self.create_coroutine()?;
+        Ok(true)
+    }
+
+    fn exec_yield(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let value_size: u32 = self.get_op()?;
+        let value = self.pop_bytes(value_size as usize)?;
+        self.leave_coroutine(Some(value))?;
+        Ok(true)
+    }
+
+    fn exec_resume(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let handle_reg: u8 = self.get_op()?;
+        let value_size: u32 = self.get_op()?;
+        let handle: u64 = self.reg(handle_reg)?.get();
+        self.enter_coroutine(handle, value_size as usize)?;
+        Ok(true)
+    }
+
+    fn exec_ret(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        // Special case if function was called externally, the callstack is empty
+        if self.call_stack.len() == 0 {
+            // The function of a coroutine returned, it is finished
+            if !self.resumed_coroutines.is_empty() {
+                self.leave_coroutine(None)?;
+                return Ok(true);
+            }
+            return Ok(false);
+        }
+        self.ret()?;
+        Ok(true)
+    }
+
+    fn exec_not(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let lhs: bool = {
+            self.reg(lhs_reg)?.get()
+        };
+        self.reg(rhs_reg)?.set(!lhs);
+        Ok(true)
+    }
+
+    fn exec_itof(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let lhs: i64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        self.reg(rhs_reg)?.set(lhs as f32);
+        Ok(true)
+    }
+
+    fn exec_itod(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let lhs: i64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        self.reg(rhs_reg)?.set(lhs as f64);
+        Ok(true)
+    }
+
+    fn exec_ftoi(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let lhs: f32 = {
+            self.reg(lhs_reg)?.get()
+        };
+        // Truncates towards zero, saturating at the int bounds
+        self.reg(rhs_reg)?.set(lhs as i64);
+        Ok(true)
+    }
+
+    fn exec_dtoi(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let lhs: f64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        // Truncates towards zero, saturating at the int bounds
+        self.reg(rhs_reg)?.set(lhs as i64);
+        Ok(true)
+    }
+
+    fn exec_ftod(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let lhs: f32 = {
+            self.reg(lhs_reg)?.get()
+        };
+        self.reg(rhs_reg)?.set(lhs as f64);
+        Ok(true)
+    }
+
+    fn exec_dtof(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let lhs: f64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        self.reg(rhs_reg)?.set(lhs as f32);
+        Ok(true)
+    }
+
+    fn exec_btoi(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let lhs: bool = {
+            self.reg(lhs_reg)?.get()
+        };
+        self.reg(rhs_reg)?.set(lhs as i64);
+        Ok(true)
+    }
+
+    fn exec_negi(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let lhs: i64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        self.reg(rhs_reg)?.set(lhs.wrapping_neg());
+        Ok(true)
+    }
+
+    fn exec_negf(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let lhs: f32 = {
+            self.reg(lhs_reg)?.get()
+        };
+        self.reg(rhs_reg)?.set(-lhs);
+        Ok(true)
+    }
+
+    fn exec_negd(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let lhs: f64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        self.reg(rhs_reg)?.set(-lhs);
+        Ok(true)
+    }
+
+    fn exec_and(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: bool = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: bool = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs && rhs);
+        Ok(true)
+    }
+
+    fn exec_or(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: bool = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: bool = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs || rhs);
+        Ok(true)
+    }
+
+    fn exec_eqi(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: i64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: i64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs == rhs);
+        Ok(true)
+    }
+
+    fn exec_neqi(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: i64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: i64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs != rhs);
+        Ok(true)
+    }
+
+    fn exec_lti(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: i64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: i64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs < rhs);
+        Ok(true)
+    }
+
+    fn exec_gti(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: i64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: i64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs > rhs);
+        Ok(true)
+    }
+
+    fn exec_lteqi(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: i64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: i64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs <= rhs);
+        Ok(true)
+    }
+
+    fn exec_gteqi(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: i64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: i64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs >= rhs);
+        Ok(true)
+    }
+
+    fn exec_eqf(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: f32 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: f32 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs == rhs);
+        Ok(true)
+    }
+
+    fn exec_eqd(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: f64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: f64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs == rhs);
+        Ok(true)
+    }
+
+    fn exec_neqf(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: f32 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: f32 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs != rhs);
+        Ok(true)
+    }
+
+    fn exec_neqd(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: f64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: f64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs != rhs);
+        Ok(true)
+    }
+
+    fn exec_ltf(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: f32 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: f32 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs < rhs);
+        Ok(true)
+    }
+
+    fn exec_ltd(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: f64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: f64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs < rhs);
+        Ok(true)
+    }
+
+    fn exec_gtf(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: f32 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: f32 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs > rhs);
+        Ok(true)
+    }
+
+    fn exec_gtd(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: f64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: f64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs > rhs);
+        Ok(true)
+    }
+
+    fn exec_lteqf(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: f32 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: f32 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs <= rhs);
+        Ok(true)
+    }
+
+    fn exec_lteqd(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: f64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: f64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs <= rhs);
+        Ok(true)
+    }
+
+    fn exec_gteqf(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: f32 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: f32 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs >= rhs);
+        Ok(true)
+    }
+
+    fn exec_gteqd(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let lhs: f64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: f64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.reg(target_reg)?.set(lhs >= rhs);
+        Ok(true)
+    }
+
+    fn exec_chkb(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let start_reg: u8 = self.get_op()?;
+        let end_reg: u8 = self.get_op()?;
+        let len_reg: u8 = self.get_op()?;
+        let start: i64 = {
+            self.reg(start_reg)?.get()
+        };
+        let end: i64 = {
+            self.reg(end_reg)?.get()
+        };
+        let len: i64 = {
+            self.reg(len_reg)?.get()
+        };
+        if start < 0 || start > end || end > len {
+            return Err(CoreError::OutOfBounds(start, end, len));
+        }
+        Ok(true)
+    }
+
+    fn exec_chkn(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let flag_reg: u8 = self.get_op()?;
+        let has_value: i64 = {
+            self.reg(flag_reg)?.get()
+        };
+        if has_value == 0 {
+            return Err(CoreError::NullValue);
+        }
+        Ok(true)
+    }
+
+    fn exec_alloc(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        // Copies n bytes onto the heap, e.g. the captured environment of a lambda
+        let lhs_reg: u8 = self.get_op()?;
+        let lhs_offset: i16 = self.get_op()?;
+        let n: usize = self.get_op::<u32>()? as usize;
+        let target_reg: u8 = self.get_op()?;
+        let lhs_addr: u64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let data = self.mem_get_n((lhs_addr, lhs_offset), n)?;
+        let heap_addr = self.heap_alloc(&data)?;
+        self.reg(target_reg)?.set(heap_addr);
+        Ok(true)
+    }
+
+    fn exec_free(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let addr_reg: u8 = self.get_op()?;
+        let addr: u64 = {
+            self.reg(addr_reg)?.get()
+        };
+        self.heap_free(addr)?;
+        Ok(true)
+    }
+
+    fn exec_realloc(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        // Resizes a heap allocation to the size in a register, it may move
+        let addr_reg: u8 = self.get_op()?;
+        let size_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let addr: u64 = {
+            self.reg(addr_reg)?.get()
+        };
+        let size: u64 = {
+            self.reg(size_reg)?.get()
+        };
+        let heap_addr = self.heap_realloc(addr, size as usize)?;
+        self.reg(target_reg)?.set(heap_addr);
+        Ok(true)
+    }
+
+    fn exec_incref(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        // Points at a string value, literals are left alone
+        let base_reg: u8 = self.get_op()?;
+        let offset: i16 = self.get_op()?;
+        let base: u64 = {
+            self.reg(base_reg)?.get()
+        };
+        self.string_incref((base, offset))?;
+        Ok(true)
+    }
+
+    fn exec_decref(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let base_reg: u8 = self.get_op()?;
+        let offset: i16 = self.get_op()?;
+        let base: u64 = {
+            self.reg(base_reg)?.get()
+        };
+        self.string_decref((base, offset))?;
+        Ok(true)
+    }
+
+    fn exec_slen(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let base_reg: u8 = self.get_op()?;
+        let offset: i16 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let base: u64 = {
+            self.reg(base_reg)?.get()
+        };
+        let len: u64 = self.mem_get((base, offset))?;
+        self.reg(target_reg)?.set(len as i64);
+        Ok(true)
+    }
+
+    fn exec_scat(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        // Points at both strings, the new one is written right after them
+        let base_reg: u8 = self.get_op()?;
+        let offset: i16 = self.get_op()?;
+        let base: u64 = {
+            self.reg(base_reg)?.get()
+        };
+        let (len, data) = self.string_concat((base, offset), offset_addr((base, offset), 16)?)?;
+        self.mem_set(offset_addr((base, offset), 32)?, len)?;
+        self.mem_set(offset_addr((base, offset), 40)?, data)?;
+        Ok(true)
+    }
+
+    fn exec_ssub(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        // The substring replaces the string, sharing its data
+        let base_reg: u8 = self.get_op()?;
+        let offset: i16 = self.get_op()?;
+        let start_reg: u8 = self.get_op()?;
+        let end_reg: u8 = self.get_op()?;
+        let base: u64 = {
+            self.reg(base_reg)?.get()
+        };
+        let start: i64 = {
+            self.reg(start_reg)?.get()
+        };
+        let end: i64 = {
+            self.reg(end_reg)?.get()
+        };
+        let (len, data) = self.string_substring((base, offset), start, end)?;
+        self.mem_set((base, offset), len)?;
+        self.mem_set(offset_addr((base, offset), 8)?, data)?;
+        Ok(true)
+    }
+
+    fn exec_schr(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let base_reg: u8 = self.get_op()?;
+        let offset: i16 = self.get_op()?;
+        let index_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let base: u64 = {
+            self.reg(base_reg)?.get()
+        };
+        let index: i64 = {
+            self.reg(index_reg)?.get()
+        };
+        let character = self.string_char_at((base, offset), index)?;
+        self.reg(target_reg)?.set(character as i64);
+        Ok(true)
+    }
+
+    /// LDI followed by MOVI_RA of the loaded register
+    fn exec_ldi_ra(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let int64: i64 = self.get_op()?;
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let rhs_offset: i16 = self.get_op()?;
+        self.reg(lhs_reg)?.set(int64);
+        let rhs_addr: u64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        self.mem_set((rhs_addr, rhs_offset), int64)?;
+        Ok(true)
+    }
+
+    /// An integer comparison followed by JMPF on its result, the comparison is given by its opcode
+    fn exec_jmpf_cmpi(&mut self, instr_ip: usize) -> CoreResult<bool> {
+        let cmp_opcode: u8 = self.get_op()?;
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_reg: u8 = self.get_op()?;
+        let target_reg: u8 = self.get_op()?;
+        let offset: i64 = self.get_op()?;
+        let lhs: i64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        let rhs: i64 = {
+            self.reg(rhs_reg)?.get()
+        };
+        let result = match Opcode::try_from(cmp_opcode)? {
+            Opcode::EQI => lhs == rhs,
+            Opcode::NEQI => lhs != rhs,
+            Opcode::LTI => lhs < rhs,
+            Opcode::GTI => lhs > rhs,
+            Opcode::LTEQI => lhs <= rhs,
+            Opcode::GTEQI => lhs >= rhs,
+            _ => return Err(CoreError::InvalidOpcode(cmp_opcode))
+        };
+        self.reg(target_reg)?.set(result);
+        if !result {
+            self.jump_relative(instr_ip, offset);
+        }
+        Ok(true)
+    }
+
+    /// ADDU_I growing the stack followed by MOVI_RA or MOVA_RA relative to SP
+    fn exec_incsp_ra(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        let inc: u64 = self.get_op()?;
+        let lhs_reg: u8 = self.get_op()?;
+        let rhs_offset: i16 = self.get_op()?;
+        let sp: u64 = self.registers.sp.get();
+        if Address::try_from(sp)?.real_address.checked_add(inc).is_none_or(|end| end > self.stack.len() as u64) {
+            return Err(CoreError::StackOverflow);
+        }
+        self.registers.sp.set(sp + inc);
+        let uint64: u64 = {
+            self.reg(lhs_reg)?.get()
+        };
+        self.mem_set((sp + inc, rhs_offset), uint64)?;
+        Ok(true)
+    }
+
+    fn exec_chkr(&mut self, _instr_ip: usize) -> CoreResult<bool> {
+        // Points at the error message of a result, followed by its "is ok" flag
+        let message_reg: u8 = self.get_op()?;
+        let message_addr: u64 = {
+            self.reg(message_reg)?.get()
+        };
+        let is_ok: i64 = self.mem_get((message_addr, 16))?;
+        if is_ok == 0 {
+            let message = self.mem_get_string(message_addr)?;
+            return Err(CoreError::UncaughtError(message));
+        }
+        Ok(true)
     }
 }
@@ -0,0 +1,134 @@
+use super::{
+    core::{
+        CoreError,
+        CoreResult
+    }
+};
+
+use std::{
+    sync::{
+        atomic::{
+            AtomicBool,
+            Ordering
+        },
+        Arc,
+        Condvar,
+        Mutex,
+        MutexGuard,
+        PoisonError
+    }
+};
+
+/// State requested through the handles of a core
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum InterruptState {
+    Running,
+    Paused,
+    Cancelled
+}
+
+/// Interrupt requests of a core, shared with its handles
+pub(crate) struct Interrupt {
+    /// Set while the state is not Running, so the core only locks the state once something was requested
+    requested: AtomicBool,
+    state: Mutex<InterruptState>,
+    /// Notified when a paused core may continue
+    changed: Condvar
+}
+
+impl Interrupt {
+    pub fn new() -> Interrupt {
+        Interrupt {
+            requested: AtomicBool::new(false),
+            state: Mutex::new(InterruptState::Running),
+            changed: Condvar::new()
+        }
+    }
+
+    /// Returns whether a pause or a cancel is pending, checked before every instruction
+    #[inline]
+    pub fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::Relaxed)
+    }
+
+    /// Gets the flag behind is_requested() as a byte, read by native code at backward jumps
+    #[cfg(feature = "jit")]
+    pub fn get_requested_ptr(&self) -> *const u8 {
+        &self.requested as *const AtomicBool as *const u8
+    }
+
+    /// Blocks while paused, fails with Cancelled once cancelled.
+    /// The cancel is used up by the run it stops.
+    pub fn check(&self) -> CoreResult<()> {
+        let mut state = self.lock();
+        while *state == InterruptState::Paused {
+            state = self.changed.wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        if *state == InterruptState::Cancelled {
+            self.set(&mut state, InterruptState::Running);
+            return Err(CoreError::Cancelled);
+        }
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, InterruptState> {
+        self.state.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn set(&self, state: &mut InterruptState, new_state: InterruptState) {
+        *state = new_state;
+        self.requested.store(new_state != InterruptState::Running, Ordering::SeqCst);
+        self.changed.notify_all();
+    }
+}
+
+/// Pauses, resumes and cancels the scripts run by a core from another thread, created with Core::get_handle().
+///
+/// Requests take effect before the next instruction, so a handle can stop a runaway script
+/// without killing the process. Native code of the jit checks them at every backward jump.
+/// A request made while no script runs applies to the next run.
+#[derive(Clone)]
+pub struct CoreHandle {
+    interrupt: Arc<Interrupt>
+}
+
+impl CoreHandle {
+    pub(crate) fn new(interrupt: Arc<Interrupt>) -> CoreHandle {
+        CoreHandle {
+            interrupt: interrupt
+        }
+    }
+
+    /// Blocks the thread running the script until resume() or cancel() is called
+    pub fn pause(&self) {
+        let mut state = self.interrupt.lock();
+        if *state == InterruptState::Running {
+            self.interrupt.set(&mut state, InterruptState::Paused);
+        }
+    }
+
+    /// Continues a paused script
+    pub fn resume(&self) {
+        let mut state = self.interrupt.lock();
+        if *state == InterruptState::Paused {
+            self.interrupt.set(&mut state, InterruptState::Running);
+        }
+    }
+
+    /// Stops the script, the run fails with CoreError::Cancelled. A paused script is cancelled as well.
+    pub fn cancel(&self) {
+        let mut state = self.interrupt.lock();
+        self.interrupt.set(&mut state, InterruptState::Cancelled);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.interrupt.lock() == InterruptState::Paused
+    }
+
+    /// Returns whether a cancel is pending, i.e. the script has not stopped yet
+    pub fn is_cancelled(&self) -> bool {
+        *self.interrupt.lock() == InterruptState::Cancelled
+    }
+}
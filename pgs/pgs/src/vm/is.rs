@@ -15,6 +15,7 @@ use std::{
     fmt::{
         UpperHex
     },
+    mem::size_of
 };
 
 use epd::*;
@@ -92,7 +93,62 @@ pub enum Opcode {
     LTF = 67,
     GTF = 68,
     LTEQF = 69,
-    GTEQF = 70
+    GTEQF = 70,
+    CHKB = 71,
+    MOVU8_AR = 72,
+    TCALL = 73,
+    MODI = 74,
+    MODF = 75,
+    NEGI = 76,
+    NEGF = 77,
+    MOVC_AR = 78,
+    MOVC_RA = 79,
+    ITOF = 80,
+    FTOI = 81,
+    BTOI = 82,
+    CHKN = 83,
+    CHKR = 84,
+    DCALL = 85,
+    ALLOC = 86,
+    MOVD = 87,
+    MOVD_A = 88,
+    MOVD_AR = 89,
+    MOVD_RA = 90,
+    LDD = 91,
+    ADDD = 92,
+    SUBD = 93,
+    MULD = 94,
+    DIVD = 95,
+    MODD = 96,
+    NEGD = 97,
+    EQD = 98,
+    NEQD = 99,
+    LTD = 100,
+    GTD = 101,
+    LTEQD = 102,
+    GTEQD = 103,
+    ITOD = 104,
+    DTOI = 105,
+    FTOD = 106,
+    DTOF = 107,
+    FREE = 108,
+    REALLOC = 109,
+    INCREF = 110,
+    DECREF = 111,
+    SPAWN = 112,
+    CORO = 113,
+    YIELD = 114,
+    RESUME = 115,
+    SLEN = 116,
+    SCAT = 117,
+    SSUB = 118,
+    SCHR = 119,
+    // Superinstructions, emitted by codegen::fusion in place of common instruction pairs
+    LDI_RA = 120,
+    JMPF_CMPI = 121,
+    INCSP_RA = 122,
+    // Loads 8 bytes of the constant pool in the data segment
+    LDC = 123
 }
 
 impl TryFrom<u8> for Opcode {
@@ -108,4 +164,198 @@ impl Into<u8> for Opcode {
     fn into(self) -> u8 {
         self as u8
     }
-}
\ No newline at end of file
+}
+
+impl Opcode {
+    /// Gets the size in bytes of the operands following the opcode
+    pub fn get_operands_size(&self) -> usize {
+        match self {
+            Opcode::NOOP |
+            Opcode::RET => 0,
+            Opcode::HALT |
+            Opcode::DJMP |
+            Opcode::DCALL |
+            Opcode::CHKN |
+            Opcode::CHKR |
+            Opcode::FREE => 1,
+            Opcode::MOVB |
+            Opcode::MOVF |
+            Opcode::MOVD |
+            Opcode::MOVI |
+            Opcode::MOVA |
+            Opcode::LDB |
+            Opcode::DJMPT |
+            Opcode::DJMPF |
+            Opcode::NOT |
+            Opcode::ITOF |
+            Opcode::ITOD |
+            Opcode::FTOI |
+            Opcode::DTOI |
+            Opcode::FTOD |
+            Opcode::DTOF |
+            Opcode::BTOI |
+            Opcode::NEGI |
+            Opcode::NEGF |
+            Opcode::NEGD => 2,
+            Opcode::ADDI |
+            Opcode::SUBI |
+            Opcode::MULI |
+            Opcode::DIVI |
+            Opcode::MODI |
+            Opcode::ADDU |
+            Opcode::SUBU |
+            Opcode::MULU |
+            Opcode::DIVU |
+            Opcode::ADDF |
+            Opcode::SUBF |
+            Opcode::MULF |
+            Opcode::DIVF |
+            Opcode::MODF |
+            Opcode::ADDD |
+            Opcode::SUBD |
+            Opcode::MULD |
+            Opcode::DIVD |
+            Opcode::MODD |
+            Opcode::AND |
+            Opcode::OR |
+            Opcode::EQI |
+            Opcode::NEQI |
+            Opcode::LTI |
+            Opcode::GTI |
+            Opcode::LTEQI |
+            Opcode::GTEQI |
+            Opcode::EQF |
+            Opcode::NEQF |
+            Opcode::LTF |
+            Opcode::GTF |
+            Opcode::LTEQF |
+            Opcode::GTEQF |
+            Opcode::EQD |
+            Opcode::NEQD |
+            Opcode::LTD |
+            Opcode::GTD |
+            Opcode::LTEQD |
+            Opcode::GTEQD |
+            Opcode::CHKB |
+            Opcode::REALLOC |
+            Opcode::INCREF |
+            Opcode::DECREF |
+            Opcode::SCAT => 3,
+            Opcode::YIELD |
+            Opcode::MOVB_AR |
+            Opcode::MOVF_AR |
+            Opcode::MOVD_AR |
+            Opcode::MOVI_AR |
+            Opcode::MOVA_AR |
+            Opcode::MOVU8_AR |
+            Opcode::MOVC_AR |
+            Opcode::MOVB_RA |
+            Opcode::MOVF_RA |
+            Opcode::MOVD_RA |
+            Opcode::MOVI_RA |
+            Opcode::MOVA_RA |
+            Opcode::MOVC_RA |
+            Opcode::SLEN => 4,
+            Opcode::LDF |
+            Opcode::LDC |
+            Opcode::RESUME |
+            Opcode::SSUB |
+            Opcode::SCHR => 5,
+            Opcode::MOVB_A |
+            Opcode::MOVF_A |
+            Opcode::MOVD_A |
+            Opcode::MOVI_A |
+            Opcode::MOVA_A |
+            Opcode::ADDF_I |
+            Opcode::SUBF_I |
+            Opcode::MULF_I |
+            Opcode::DIVF_I => 6,
+            Opcode::JMP |
+            Opcode::CALL |
+            Opcode::ALLOC => 8,
+            Opcode::LDI |
+            Opcode::LDD |
+            Opcode::LDA |
+            Opcode::JMPT |
+            Opcode::JMPF => 9,
+            Opcode::MOVN_A |
+            Opcode::ADDI_I |
+            Opcode::SUBI_I |
+            Opcode::MULI_I |
+            Opcode::DIVI_I |
+            Opcode::ADDU_I |
+            Opcode::SUBU_I |
+            Opcode::MULU_I |
+            Opcode::DIVU_I => 10,
+            Opcode::INCSP_RA => 11,
+            Opcode::SPAWN |
+            Opcode::CORO |
+            Opcode::LDI_RA |
+            Opcode::JMPF_CMPI => 12,
+            Opcode::TCALL => 16
+        }
+    }
+
+    /// Gets the position of the jump target within the operands of a static jump, None for other opcodes.
+    /// The target is an i64 offset relative to the start of the jump.
+    pub fn get_jump_target_pos(&self) -> Option<usize> {
+        match self {
+            Opcode::JMP => Some(0),
+            Opcode::JMPT |
+            Opcode::JMPF => Some(1),
+            Opcode::JMPF_CMPI => Some(4),
+            _ => None
+        }
+    }
+}
+
+/// An operand of an instruction, encoded in a fixed little endian layout
+pub trait Operand: Sized {
+    /// Size of the operand in bytes
+    const SIZE: usize;
+
+    /// Reads the operand from the first SIZE bytes
+    fn read_le(bytes: &[u8]) -> Self;
+
+    /// Appends the SIZE bytes of the operand
+    fn write_le(&self, bytes: &mut Vec<u8>);
+}
+
+macro_rules! impl_operand {
+    ($($operand_type:ty),*) => {
+        $(
+            impl Operand for $operand_type {
+                const SIZE: usize = size_of::<$operand_type>();
+
+                #[inline]
+                fn read_le(bytes: &[u8]) -> $operand_type {
+                    let mut raw = [0u8; size_of::<$operand_type>()];
+                    raw.copy_from_slice(&bytes[..Self::SIZE]);
+                    <$operand_type>::from_le_bytes(raw)
+                }
+
+                #[inline]
+                fn write_le(&self, bytes: &mut Vec<u8>) {
+                    bytes.extend_from_slice(&self.to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+
+impl_operand!(u8, i8, u16, i16, u32, i32, u64, i64, f32, f64);
+
+/// Bools take up a byte, anything but 0 is true
+impl Operand for bool {
+    const SIZE: usize = 1;
+
+    #[inline]
+    fn read_le(bytes: &[u8]) -> bool {
+        bytes[0] != 0
+    }
+
+    #[inline]
+    fn write_le(&self, bytes: &mut Vec<u8>) {
+        bytes.push(*self as u8);
+    }
+}
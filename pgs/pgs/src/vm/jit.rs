@@ -0,0 +1,749 @@
+use super::{
+    is::{
+        Opcode,
+        Operand
+    },
+    register::{
+        REGISTER_COUNT,
+        SP_INDEX,
+        IP_INDEX
+    }
+};
+use crate::{
+    codegen::{
+        program::Program
+    }
+};
+
+use std::{
+    collections::{
+        HashMap,
+        VecDeque
+    },
+    convert::TryFrom,
+    mem
+};
+
+use cranelift_codegen::{
+    entity::EntityRef,
+    ir::{
+        condcodes::IntCC,
+        types,
+        AbiParam,
+        Block,
+        Endianness,
+        InstBuilder,
+        MemFlags,
+        Value
+    },
+    settings::{
+        self,
+        Configurable
+    },
+    Context
+};
+use cranelift_frontend::{
+    FunctionBuilder,
+    FunctionBuilderContext,
+    Variable
+};
+use cranelift_jit::{
+    JITBuilder,
+    JITModule
+};
+use cranelift_module::{
+    default_libcall_names,
+    Module
+};
+
+/// Calls of a function after which it is compiled to native code
+pub const DEFAULT_JIT_THRESHOLD: u64 = 1000;
+
+/// Number of registers handed to native code: R0 to R15, SP, IP and FP
+pub const JIT_REGISTER_COUNT: usize = REGISTER_COUNT;
+
+/// Largest number of instructions of a function that is compiled
+const MAX_JIT_INSTRUCTIONS: usize = 4096;
+
+/// Register numbers of the stack pointer and the instruction pointer
+const SP_REG: u8 = SP_INDEX;
+const IP_REG: u8 = IP_INDEX;
+
+/// Bits of an address below its type
+const ADDRESS_MASK: i64 = (1 << 61) - 1;
+
+/// Native code of a function, called with the registers, the stack, the stack size in bytes
+/// and the interrupt flag of the core. Returns the code offset the interpreter continues at.
+pub type JitFn = unsafe extern "C" fn(*mut u64, *mut u8, u64, *const u8) -> u64;
+
+/// Compiles frequently called script functions to native code.
+///
+/// Native code runs on the registers and the stack of the core. Only integer, boolean and address
+/// instructions and jumps are compiled, functions using anything else stay interpreted.
+/// At RET, and at instructions that would fail, e.g. dividing by zero or accessing memory outside
+/// of the stack, the native code hands back to the interpreter, which continues at that instruction.
+/// It does the same at backward jumps while a pause or a cancel is requested, so loops can be interrupted.
+pub struct Jit {
+    module: JITModule,
+    context: Context,
+    builder_context: FunctionBuilderContext,
+    threshold: u64,
+    call_counts: HashMap<u64, u64>,
+    /// Native code by function offset, None if the function can not be compiled
+    functions: HashMap<usize, Option<JitFn>>
+}
+
+// The module is only used through &mut self, its code is never shared with another Jit
+unsafe impl Send for Jit {}
+
+impl Jit {
+    /// Creates a jit for the host, None if Cranelift does not support it
+    pub fn new(threshold: u64) -> Option<Jit> {
+        let mut flag_builder = settings::builder();
+        flag_builder.set("opt_level", "speed").ok()?;
+        let isa = cranelift_native::builder().ok()?
+            .finish(settings::Flags::new(flag_builder))
+            .ok()?;
+        let module = JITModule::new(JITBuilder::with_isa(isa, default_libcall_names()));
+        Some(
+            Jit {
+                context: module.make_context(),
+                module: module,
+                builder_context: FunctionBuilderContext::new(),
+                threshold: threshold,
+                call_counts: HashMap::new(),
+                functions: HashMap::new()
+            }
+        )
+    }
+
+    pub fn get_threshold(&self) -> u64 {
+        self.threshold
+    }
+
+    /// Gets the number of functions compiled to native code
+    pub fn get_compiled_count(&self) -> usize {
+        self.functions.values()
+            .filter(|function| function.is_some())
+            .count()
+    }
+
+    /// Counts a call of the function with the given uid and offset.
+    /// Returns its native code once the function reached the threshold and could be compiled.
+    pub fn on_call(&mut self, program: &Program, fn_uid: u64, fn_offset: usize) -> Option<JitFn> {
+        let count = self.call_counts.entry(fn_uid)
+            .or_insert(0);
+        if *count < self.threshold {
+            *count += 1;
+            return None;
+        }
+        if let Some(function) = self.functions.get(&fn_offset) {
+            return *function;
+        }
+        let function = self.compile(program, fn_offset);
+        self.functions.insert(fn_offset, function);
+        function
+    }
+
+    fn compile(&mut self, program: &Program, fn_offset: usize) -> Option<JitFn> {
+        let instructions = decode_function(program, fn_offset)?;
+
+        self.module.clear_context(&mut self.context);
+        let pointer_type = self.module.target_config().pointer_type();
+        let signature = &mut self.context.func.signature;
+        signature.params.push(AbiParam::new(pointer_type));
+        signature.params.push(AbiParam::new(pointer_type));
+        signature.params.push(AbiParam::new(types::I64));
+        signature.params.push(AbiParam::new(pointer_type));
+        signature.returns.push(AbiParam::new(types::I64));
+
+        {
+            let builder = FunctionBuilder::new(&mut self.context.func, &mut self.builder_context);
+            let translator = Translator::new(builder, &instructions, program.code.len());
+            translator.translate(fn_offset)?;
+        }
+
+        let signature = self.context.func.signature.clone();
+        let func_id = self.module.declare_anonymous_function(&signature).ok()?;
+        let defined = self.module.define_function(func_id, &mut self.context);
+        self.module.clear_context(&mut self.context);
+        defined.ok()?;
+        self.module.finalize_definitions().ok()?;
+
+        let code = self.module.get_finalized_function(func_id);
+        Some(unsafe { mem::transmute::<*const u8, JitFn>(code) })
+    }
+}
+
+/// A decoded instruction of a function
+struct JitInstruction {
+    opcode: Opcode,
+    /// Operands with the jump target as a code offset
+    operands: Vec<u8>,
+    /// Offset of the following instruction
+    next: usize
+}
+
+/// Reads the operands of an instruction in order
+struct OperandReader<'a> {
+    bytes: &'a [u8],
+    pos: usize
+}
+
+impl<'a> OperandReader<'a> {
+    fn new(bytes: &'a [u8]) -> OperandReader<'a> {
+        OperandReader {
+            bytes: bytes,
+            pos: 0
+        }
+    }
+
+    fn read<T: Operand>(&mut self) -> T {
+        let ret = T::read_le(&self.bytes[self.pos..self.pos + T::SIZE]);
+        self.pos += T::SIZE;
+        ret
+    }
+}
+
+/// Decodes the instructions reachable from the start of a function, None if one of them can not be compiled
+fn decode_function(program: &Program, fn_offset: usize) -> Option<HashMap<usize, JitInstruction>> {
+    let code = &program.code;
+    let mut instructions = HashMap::new();
+    let mut queue = VecDeque::new();
+    queue.push_back(fn_offset);
+
+    while let Some(offset) = queue.pop_front() {
+        // Jumping to the end of the code finishes the program, which the interpreter takes care of
+        if offset == code.len() || instructions.contains_key(&offset) {
+            continue;
+        }
+        if offset < program.data_size || offset > code.len() || instructions.len() >= MAX_JIT_INSTRUCTIONS {
+            return None;
+        }
+        let opcode = Opcode::try_from(code[offset]).ok()?;
+        if !is_supported(&opcode) {
+            return None;
+        }
+        let next = offset + 1 + opcode.get_operands_size();
+        let operands = code.get(offset + 1..next)?.to_vec();
+        // Constants are never written to, so they compile like loading their value
+        let (opcode, mut operands) = match opcode {
+            Opcode::LDC => {
+                let addr = OperandReader::new(&operands).read::<u32>() as usize;
+                if addr + 8 > program.data_size {
+                    return None;
+                }
+                let mut ldi_operands = code[addr..addr + 8].to_vec();
+                ldi_operands.push(operands[4]);
+                (Opcode::LDI, ldi_operands)
+            },
+            _ => (opcode, operands)
+        };
+        // Jump targets are relative to the jump, the translation works with code offsets
+        if let Some(pos) = opcode.get_jump_target_pos() {
+            let target = (offset as i64).wrapping_add(OperandReader::new(&operands[pos..]).read::<i64>());
+            if target < 0 {
+                return None;
+            }
+            operands[pos..pos + 8].copy_from_slice(&(target as u64).to_le_bytes());
+        }
+
+        let mut reader = OperandReader::new(&operands);
+        match opcode {
+            Opcode::JMP => {
+                queue.push_back(reader.read::<u64>() as usize);
+            },
+            Opcode::JMPT |
+            Opcode::JMPF => {
+                reader.read::<u8>();
+                queue.push_back(reader.read::<u64>() as usize);
+                queue.push_back(next);
+            },
+            Opcode::JMPF_CMPI => {
+                reader.read::<u32>();
+                queue.push_back(reader.read::<u64>() as usize);
+                queue.push_back(next);
+            },
+            Opcode::RET => {},
+            _ => queue.push_back(next)
+        };
+
+        instructions.insert(offset, JitInstruction {
+            opcode: opcode,
+            operands: operands,
+            next: next
+        });
+    }
+
+    Some(instructions)
+}
+
+fn is_supported(opcode: &Opcode) -> bool {
+    matches!(opcode,
+        Opcode::NOOP |
+        Opcode::MOVI | Opcode::MOVA | Opcode::MOVB |
+        Opcode::MOVI_AR | Opcode::MOVA_AR |
+        Opcode::MOVI_RA | Opcode::MOVA_RA |
+        Opcode::LDI | Opcode::LDA | Opcode::LDB | Opcode::LDC |
+        Opcode::ADDI | Opcode::SUBI | Opcode::MULI | Opcode::DIVI | Opcode::MODI |
+        Opcode::ADDI_I | Opcode::SUBI_I | Opcode::MULI_I | Opcode::DIVI_I |
+        Opcode::ADDU | Opcode::SUBU | Opcode::MULU | Opcode::DIVU |
+        Opcode::ADDU_I | Opcode::SUBU_I | Opcode::MULU_I | Opcode::DIVU_I |
+        Opcode::NEGI | Opcode::BTOI |
+        Opcode::NOT | Opcode::AND | Opcode::OR |
+        Opcode::EQI | Opcode::NEQI | Opcode::LTI | Opcode::GTI | Opcode::LTEQI | Opcode::GTEQI |
+        Opcode::JMP | Opcode::JMPT | Opcode::JMPF |
+        Opcode::LDI_RA | Opcode::JMPF_CMPI | Opcode::INCSP_RA |
+        Opcode::RET
+    )
+}
+
+/// Kinds of integer division, as the interpreter performs them
+#[derive(Clone, Copy)]
+enum Division {
+    SignedDiv,
+    SignedRem,
+    UnsignedDiv
+}
+
+/// Translates the decoded instructions of a function to Cranelift IR
+struct Translator<'a, 'b> {
+    builder: FunctionBuilder<'b>,
+    instructions: &'a HashMap<usize, JitInstruction>,
+    code_len: usize,
+    /// Block of every instruction, by offset
+    blocks: HashMap<usize, Block>,
+    /// Writes back the registers and returns the offset passed to it
+    exit_block: Block,
+    registers_ptr: Value,
+    stack_ptr: Value,
+    stack_size: Value,
+    interrupt_ptr: Value
+}
+
+impl<'a, 'b> Translator<'a, 'b> {
+    fn new(mut builder: FunctionBuilder<'b>, instructions: &'a HashMap<usize, JitInstruction>, code_len: usize) -> Translator<'a, 'b> {
+        let entry_block = builder.create_block();
+        builder.append_block_params_for_function_params(entry_block);
+        builder.switch_to_block(entry_block);
+        let params = builder.block_params(entry_block).to_vec();
+
+        let exit_block = builder.create_block();
+        builder.append_block_param(exit_block, types::I64);
+
+        let blocks = instructions.keys()
+            .map(|offset| (*offset, builder.create_block()))
+            .collect();
+
+        Translator {
+            builder: builder,
+            instructions: instructions,
+            code_len: code_len,
+            blocks: blocks,
+            exit_block: exit_block,
+            registers_ptr: params[0],
+            stack_ptr: params[1],
+            stack_size: params[2],
+            interrupt_ptr: params[3]
+        }
+    }
+
+    fn translate(mut self, fn_offset: usize) -> Option<()> {
+        for reg in 0..JIT_REGISTER_COUNT {
+            let var = Variable::new(reg);
+            self.builder.declare_var(var, types::I64);
+            let value = self.builder.ins().load(types::I64, MemFlags::trusted(), self.registers_ptr, (reg * 8) as i32);
+            self.builder.def_var(var, value);
+        }
+        self.jump_to(fn_offset);
+
+        let mut offsets: Vec<usize> = self.instructions.keys().cloned().collect();
+        offsets.sort_unstable();
+        for offset in offsets {
+            let block = self.blocks[&offset];
+            self.builder.switch_to_block(block);
+            self.translate_instruction(offset)?;
+        }
+
+        self.builder.switch_to_block(self.exit_block);
+        for reg in 0..JIT_REGISTER_COUNT {
+            let value = self.builder.use_var(Variable::new(reg));
+            self.builder.ins().store(MemFlags::trusted(), value, self.registers_ptr, (reg * 8) as i32);
+        }
+        let resume_offset = self.builder.block_params(self.exit_block)[0];
+        self.builder.ins().return_(&[resume_offset]);
+
+        self.builder.seal_all_blocks();
+        self.builder.finalize();
+        Some(())
+    }
+
+    fn translate_instruction(&mut self, offset: usize) -> Option<()> {
+        let instructions = self.instructions;
+        let instruction = &instructions[&offset];
+        let next = instruction.next;
+        let mut ops = OperandReader::new(&instruction.operands);
+
+        // Loops hand back to the interpreter while a pause or a cancel is requested, before changing anything
+        if let Some(pos) = instruction.opcode.get_jump_target_pos() {
+            let target = OperandReader::new(&instruction.operands[pos..]).read::<u64>() as usize;
+            if target <= offset {
+                let requested = self.builder.ins().load(types::I8, MemFlags::trusted(), self.interrupt_ptr, 0);
+                self.exit_if(requested, offset);
+            }
+        }
+
+        match instruction.opcode {
+            Opcode::NOOP => {},
+            Opcode::MOVI |
+            Opcode::MOVA |
+            Opcode::MOVB => {
+                let lhs = self.get_reg(ops.read())?;
+                self.set_reg(ops.read(), lhs)?;
+            },
+            Opcode::MOVI_AR |
+            Opcode::MOVA_AR => {
+                let addr_reg = ops.read();
+                let addr_offset: i16 = ops.read();
+                let target_reg = ops.read();
+                let addr = self.get_reg(addr_reg)?;
+                let addr = self.stack_address(addr, addr_offset, offset);
+                let value = self.builder.ins().load(types::I64, little_endian(), addr, 0);
+                self.set_reg(target_reg, value)?;
+            },
+            Opcode::MOVI_RA |
+            Opcode::MOVA_RA => {
+                let value = self.get_reg(ops.read())?;
+                let addr_reg = ops.read();
+                let addr_offset: i16 = ops.read();
+                let addr = self.get_reg(addr_reg)?;
+                let addr = self.stack_address(addr, addr_offset, offset);
+                self.builder.ins().store(little_endian(), value, addr, 0);
+            },
+            // The checks of the fused instructions come first, failing hands back before any register changed
+            Opcode::LDI_RA => {
+                let value = self.builder.ins().iconst(types::I64, ops.read::<i64>());
+                let reg = ops.read();
+                let addr_reg = ops.read();
+                let addr_offset: i16 = ops.read();
+                let addr = if addr_reg == reg {
+                    value
+                }
+                else {
+                    self.get_reg(addr_reg)?
+                };
+                let addr = self.stack_address(addr, addr_offset, offset);
+                self.set_reg(reg, value)?;
+                self.builder.ins().store(little_endian(), value, addr, 0);
+            },
+            Opcode::INCSP_RA => {
+                let inc: u64 = ops.read();
+                let reg = ops.read();
+                let addr_offset: i16 = ops.read();
+                let sp = self.get_reg(SP_REG)?;
+                let real_sp = self.builder.ins().band_imm(sp, ADDRESS_MASK);
+                let new_real_sp = self.builder.ins().iadd_imm(real_sp, inc as i64);
+                let overflow = self.builder.ins().icmp(IntCC::UnsignedGreaterThan, new_real_sp, self.stack_size);
+                let wrapped = self.builder.ins().icmp(IntCC::UnsignedLessThan, new_real_sp, real_sp);
+                let overflow = self.builder.ins().bor(overflow, wrapped);
+                self.exit_if(overflow, offset);
+                let new_sp = self.builder.ins().iadd_imm(sp, inc as i64);
+                let value = if reg == SP_REG {
+                    new_sp
+                }
+                else {
+                    self.get_reg(reg)?
+                };
+                let addr = self.stack_address(new_sp, addr_offset, offset);
+                self.set_reg(SP_REG, new_sp)?;
+                self.builder.ins().store(little_endian(), value, addr, 0);
+            },
+            Opcode::JMPF_CMPI => {
+                let cmp_opcode = Opcode::try_from(ops.read::<u8>()).ok()?;
+                let lhs = self.get_reg(ops.read())?;
+                let rhs = self.get_reg(ops.read())?;
+                let target_reg = ops.read();
+                let target = ops.read::<u64>() as usize;
+                let cond = match cmp_opcode {
+                    Opcode::EQI => IntCC::Equal,
+                    Opcode::NEQI => IntCC::NotEqual,
+                    Opcode::LTI => IntCC::SignedLessThan,
+                    Opcode::GTI => IntCC::SignedGreaterThan,
+                    Opcode::LTEQI => IntCC::SignedLessThanOrEqual,
+                    Opcode::GTEQI => IntCC::SignedGreaterThanOrEqual,
+                    _ => return None
+                };
+                let value = self.builder.ins().icmp(cond, lhs, rhs);
+                self.set_bool(target_reg, value)?;
+                let then_block = self.builder.create_block();
+                let else_block = self.builder.create_block();
+                self.builder.ins().brif(value, then_block, &[], else_block, &[]);
+                self.builder.switch_to_block(then_block);
+                self.jump_to(next);
+                self.builder.switch_to_block(else_block);
+                self.jump_to(target);
+                return Some(());
+            },
+            Opcode::LDI => {
+                let value = self.builder.ins().iconst(types::I64, ops.read::<i64>());
+                self.set_reg(ops.read(), value)?;
+            },
+            Opcode::LDA => {
+                let value = self.builder.ins().iconst(types::I64, ops.read::<u64>() as i64);
+                self.set_reg(ops.read(), value)?;
+            },
+            Opcode::LDB => {
+                let value = self.builder.ins().iconst(types::I64, ops.read::<bool>() as i64);
+                self.set_reg(ops.read(), value)?;
+            },
+            Opcode::ADDI |
+            Opcode::SUBI |
+            Opcode::MULI |
+            Opcode::ADDU |
+            Opcode::SUBU |
+            Opcode::MULU => {
+                let lhs = self.get_reg(ops.read())?;
+                let rhs = self.get_reg(ops.read())?;
+                let value = match instruction.opcode {
+                    Opcode::ADDI | Opcode::ADDU => self.builder.ins().iadd(lhs, rhs),
+                    Opcode::SUBI | Opcode::SUBU => self.builder.ins().isub(lhs, rhs),
+                    _ => self.builder.ins().imul(lhs, rhs)
+                };
+                self.set_reg(ops.read(), value)?;
+            },
+            Opcode::ADDI_I |
+            Opcode::SUBI_I |
+            Opcode::MULI_I => {
+                let lhs = self.get_reg(ops.read())?;
+                let rhs: i64 = ops.read();
+                let value = match instruction.opcode {
+                    Opcode::ADDI_I => self.builder.ins().iadd_imm(lhs, rhs),
+                    Opcode::SUBI_I => self.builder.ins().iadd_imm(lhs, rhs.wrapping_neg()),
+                    _ => self.builder.ins().imul_imm(lhs, rhs)
+                };
+                self.set_reg(ops.read(), value)?;
+            },
+            Opcode::ADDU_I |
+            Opcode::SUBU_I |
+            Opcode::MULU_I => {
+                let lhs_reg = ops.read();
+                let rhs: u64 = ops.read();
+                let target_reg = ops.read();
+                let lhs = self.get_reg(lhs_reg)?;
+                // Growing the stack past its end is left to the interpreter, which fails with a stack overflow
+                if instruction.opcode == Opcode::ADDU_I && lhs_reg == SP_REG && target_reg == SP_REG {
+                    let sp = self.builder.ins().band_imm(lhs, ADDRESS_MASK);
+                    let new_sp = self.builder.ins().iadd_imm(sp, rhs as i64);
+                    let overflow = self.builder.ins().icmp(IntCC::UnsignedGreaterThan, new_sp, self.stack_size);
+                    self.exit_if(overflow, offset);
+                }
+                let value = match instruction.opcode {
+                    Opcode::ADDU_I => self.builder.ins().iadd_imm(lhs, rhs as i64),
+                    Opcode::SUBU_I => self.builder.ins().iadd_imm(lhs, (rhs as i64).wrapping_neg()),
+                    _ => self.builder.ins().imul_imm(lhs, rhs as i64)
+                };
+                self.set_reg(target_reg, value)?;
+            },
+            Opcode::DIVI |
+            Opcode::MODI |
+            Opcode::DIVU => {
+                let lhs = self.get_reg(ops.read())?;
+                let rhs = self.get_reg(ops.read())?;
+                let division = match instruction.opcode {
+                    Opcode::DIVI => Division::SignedDiv,
+                    Opcode::MODI => Division::SignedRem,
+                    _ => Division::UnsignedDiv
+                };
+                let value = self.divide(division, lhs, rhs, offset);
+                self.set_reg(ops.read(), value)?;
+            },
+            Opcode::DIVI_I |
+            Opcode::DIVU_I => {
+                let lhs = self.get_reg(ops.read())?;
+                let rhs = match instruction.opcode {
+                    Opcode::DIVI_I => self.builder.ins().iconst(types::I64, ops.read::<i64>()),
+                    _ => self.builder.ins().iconst(types::I64, ops.read::<u64>() as i64)
+                };
+                let division = match instruction.opcode {
+                    Opcode::DIVI_I => Division::SignedDiv,
+                    _ => Division::UnsignedDiv
+                };
+                let value = self.divide(division, lhs, rhs, offset);
+                self.set_reg(ops.read(), value)?;
+            },
+            Opcode::NEGI => {
+                let lhs = self.get_reg(ops.read())?;
+                let value = self.builder.ins().ineg(lhs);
+                self.set_reg(ops.read(), value)?;
+            },
+            Opcode::BTOI => {
+                let lhs = self.get_bool(ops.read())?;
+                let value = self.builder.ins().uextend(types::I64, lhs);
+                self.set_reg(ops.read(), value)?;
+            },
+            Opcode::NOT => {
+                let lhs = self.get_bool(ops.read())?;
+                let value = self.builder.ins().bxor_imm(lhs, 1);
+                self.set_bool(ops.read(), value)?;
+            },
+            Opcode::AND |
+            Opcode::OR => {
+                let lhs = self.get_bool(ops.read())?;
+                let rhs = self.get_bool(ops.read())?;
+                let value = match instruction.opcode {
+                    Opcode::AND => self.builder.ins().band(lhs, rhs),
+                    _ => self.builder.ins().bor(lhs, rhs)
+                };
+                self.set_bool(ops.read(), value)?;
+            },
+            Opcode::EQI |
+            Opcode::NEQI |
+            Opcode::LTI |
+            Opcode::GTI |
+            Opcode::LTEQI |
+            Opcode::GTEQI => {
+                let lhs = self.get_reg(ops.read())?;
+                let rhs = self.get_reg(ops.read())?;
+                let cond = match instruction.opcode {
+                    Opcode::EQI => IntCC::Equal,
+                    Opcode::NEQI => IntCC::NotEqual,
+                    Opcode::LTI => IntCC::SignedLessThan,
+                    Opcode::GTI => IntCC::SignedGreaterThan,
+                    Opcode::LTEQI => IntCC::SignedLessThanOrEqual,
+                    _ => IntCC::SignedGreaterThanOrEqual
+                };
+                let value = self.builder.ins().icmp(cond, lhs, rhs);
+                self.set_bool(ops.read(), value)?;
+            },
+            Opcode::JMP => {
+                self.jump_to(ops.read::<u64>() as usize);
+                return Some(());
+            },
+            Opcode::JMPT |
+            Opcode::JMPF => {
+                let cond = self.get_bool(ops.read())?;
+                let target = ops.read::<u64>() as usize;
+                let (then_offset, else_offset) = match instruction.opcode {
+                    Opcode::JMPT => (target, next),
+                    _ => (next, target)
+                };
+                let then_block = self.builder.create_block();
+                let else_block = self.builder.create_block();
+                self.builder.ins().brif(cond, then_block, &[], else_block, &[]);
+                self.builder.switch_to_block(then_block);
+                self.jump_to(then_offset);
+                self.builder.switch_to_block(else_block);
+                self.jump_to(else_offset);
+                return Some(());
+            },
+            // Returning is left to the interpreter, which knows the call stack
+            Opcode::RET => {
+                self.exit(offset);
+                return Some(());
+            },
+            _ => return None
+        };
+
+        self.jump_to(next);
+        Some(())
+    }
+
+    fn get_reg(&mut self, reg: u8) -> Option<Value> {
+        if reg as usize >= JIT_REGISTER_COUNT || reg == IP_REG {
+            return None;
+        }
+        Some(self.builder.use_var(Variable::new(reg as usize)))
+    }
+
+    fn set_reg(&mut self, reg: u8, value: Value) -> Option<()> {
+        if reg as usize >= JIT_REGISTER_COUNT || reg == IP_REG {
+            return None;
+        }
+        self.builder.def_var(Variable::new(reg as usize), value);
+        Some(())
+    }
+
+    /// Reads a boolean register as 0 or 1, only its lowest byte holds the boolean
+    fn get_bool(&mut self, reg: u8) -> Option<Value> {
+        let value = self.get_reg(reg)?;
+        let byte = self.builder.ins().band_imm(value, 0xff);
+        Some(self.builder.ins().icmp_imm(IntCC::NotEqual, byte, 0))
+    }
+
+    fn set_bool(&mut self, reg: u8, value: Value) -> Option<()> {
+        let value = self.builder.ins().uextend(types::I64, value);
+        self.set_reg(reg, value)
+    }
+
+    /// Divides like the interpreter. Dividing by zero is left to it, to fail with the position of the instruction.
+    fn divide(&mut self, division: Division, lhs: Value, rhs: Value, offset: usize) -> Value {
+        let is_zero = self.builder.ins().icmp_imm(IntCC::Equal, rhs, 0);
+        self.exit_if(is_zero, offset);
+        match division {
+            Division::UnsignedDiv => self.builder.ins().udiv(lhs, rhs),
+            // Dividing the smallest integer by -1 traps natively, the interpreter wraps instead
+            Division::SignedDiv |
+            Division::SignedRem => {
+                let is_minus_one = self.builder.ins().icmp_imm(IntCC::Equal, rhs, -1);
+                let one = self.builder.ins().iconst(types::I64, 1);
+                let safe_rhs = self.builder.ins().select(is_minus_one, one, rhs);
+                let (result, wrapped) = match division {
+                    Division::SignedDiv => (self.builder.ins().sdiv(lhs, safe_rhs), self.builder.ins().ineg(lhs)),
+                    _ => (self.builder.ins().srem(lhs, safe_rhs), self.builder.ins().iconst(types::I64, 0))
+                };
+                self.builder.ins().select(is_minus_one, wrapped, result)
+            }
+        }
+    }
+
+    /// Computes the native address of 8 bytes at an address plus an offset.
+    /// Addresses outside of the stack are left to the interpreter.
+    fn stack_address(&mut self, addr: Value, addr_offset: i16, offset: usize) -> Value {
+        let address_type = self.builder.ins().ushr_imm(addr, 61);
+        let not_stack = self.builder.ins().icmp_imm(IntCC::NotEqual, address_type, 1);
+        self.exit_if(not_stack, offset);
+
+        let real_address = self.builder.ins().band_imm(addr, ADDRESS_MASK);
+        let real_address = self.builder.ins().iadd_imm(real_address, addr_offset as i64);
+        // The stack is always larger than a single value
+        let last_address = self.builder.ins().iadd_imm(self.stack_size, -8);
+        let out_of_bounds = self.builder.ins().icmp(IntCC::UnsignedGreaterThan, real_address, last_address);
+        self.exit_if(out_of_bounds, offset);
+
+        self.builder.ins().iadd(self.stack_ptr, real_address)
+    }
+
+    /// Jumps to the block of the instruction at the given offset, or exits at the end of the code
+    fn jump_to(&mut self, offset: usize) {
+        match self.blocks.get(&offset) {
+            Some(block) => {
+                let block = *block;
+                self.builder.ins().jump(block, &[]);
+            },
+            None => {
+                debug_assert_eq!(offset, self.code_len);
+                self.exit(offset);
+            }
+        }
+    }
+
+    /// Hands back to the interpreter, which continues at the given offset
+    fn exit(&mut self, offset: usize) {
+        let offset = self.builder.ins().iconst(types::I64, offset as i64);
+        self.builder.ins().jump(self.exit_block, &[offset]);
+    }
+
+    /// Hands back to the interpreter at the given offset if the condition holds, continues in a new block otherwise
+    fn exit_if(&mut self, cond: Value, offset: usize) {
+        let exit_block = self.builder.create_block();
+        let continue_block = self.builder.create_block();
+        self.builder.ins().brif(cond, exit_block, &[], continue_block, &[]);
+        self.builder.switch_to_block(exit_block);
+        self.exit(offset);
+        self.builder.switch_to_block(continue_block);
+    }
+}
+
+fn little_endian() -> MemFlags {
+    MemFlags::new().with_endianness(Endianness::Little)
+}
@@ -4,4 +4,13 @@ pub mod is;
 
 pub mod address;
 
-pub mod register;
\ No newline at end of file
+pub mod register;
+
+pub mod verifier;
+
+pub mod profile;
+
+pub mod interrupt;
+
+#[cfg(feature = "jit")]
+pub mod jit;
\ No newline at end of file
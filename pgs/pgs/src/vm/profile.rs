@@ -0,0 +1,137 @@
+use super::{
+    is::Opcode
+};
+
+use std::{
+    cmp::Reverse,
+    collections::{
+        HashMap
+    },
+    convert::TryFrom,
+    fmt::{
+        Display,
+        Formatter,
+        Result as FmtResult
+    },
+    time::Duration
+};
+
+/// Executions and cumulative time of an opcode
+#[derive(PartialEq, Debug, Clone)]
+pub struct OpcodeProfile {
+    pub opcode: Opcode,
+    pub executions: u64,
+    pub time: Duration
+}
+
+/// Executed instructions and their cumulative time within a function, calls excluded
+#[derive(PartialEq, Debug, Clone)]
+pub struct FunctionProfile {
+    /// None for code not entered through a function
+    pub fn_uid: Option<u64>,
+    pub name: Option<String>,
+    pub executions: u64,
+    pub time: Duration
+}
+
+/// Where a script spent its time while profiling was enabled, see Core::profile_report()
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct ProfileReport {
+    /// Executed opcodes, the most time consuming first
+    pub opcodes: Vec<OpcodeProfile>,
+    /// Functions that executed instructions, the most time consuming first
+    pub functions: Vec<FunctionProfile>
+}
+
+impl Display for ProfileReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        writeln!(f, "{:<12} {:>12} {:>14}", "opcode", "executions", "time")?;
+        for entry in self.opcodes.iter() {
+            writeln!(f, "{:<12} {:>12} {:>14?}", format!("{:?}", entry.opcode), entry.executions, entry.time)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "{:<32} {:>12} {:>14}", "function", "executions", "time")?;
+        for entry in self.functions.iter() {
+            let name = entry.name.clone()
+                .or_else(|| entry.fn_uid.map(|fn_uid| format!("<{}>", fn_uid)))
+                .unwrap_or_else(|| String::from("<none>"));
+            writeln!(f, "{:<32} {:>12} {:>14?}", name, entry.executions, entry.time)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct Counter {
+    executions: u64,
+    time: Duration
+}
+
+impl Counter {
+    fn add(&mut self, time: Duration) {
+        self.executions += 1;
+        self.time += time;
+    }
+}
+
+/// Counters collected by the core while profiling
+pub(crate) struct Profile {
+    /// Indexed by opcode
+    opcodes: Vec<Counter>,
+    functions: HashMap<Option<u64>, Counter>
+}
+
+impl Profile {
+    pub fn new() -> Profile {
+        Profile {
+            opcodes: vec![Counter::default(); 256],
+            functions: HashMap::new()
+        }
+    }
+
+    /// Records an executed instruction and the function it belongs to
+    #[inline]
+    pub fn record(&mut self, opcode: u8, fn_uid: Option<u64>, time: Duration) {
+        self.opcodes[opcode as usize].add(time);
+        self.functions.entry(fn_uid)
+            .or_default()
+            .add(time);
+    }
+
+    /// Creates the report, the function uids are named after the given table
+    pub fn report(&self, function_uids: &HashMap<String, u64>) -> ProfileReport {
+        let names: HashMap<u64, &String> = function_uids.iter()
+            .map(|(name, fn_uid)| (*fn_uid, name))
+            .collect();
+
+        let mut opcodes: Vec<OpcodeProfile> = self.opcodes.iter()
+            .enumerate()
+            .filter(|(_, counter)| counter.executions > 0)
+            .filter_map(|(opcode, counter)| {
+                Opcode::try_from(opcode as u8).ok()
+                    .map(|opcode| OpcodeProfile {
+                        opcode: opcode,
+                        executions: counter.executions,
+                        time: counter.time
+                    })
+            })
+            .collect();
+        opcodes.sort_by_key(|entry| Reverse(entry.time));
+
+        let mut functions: Vec<FunctionProfile> = self.functions.iter()
+            .map(|(fn_uid, counter)| FunctionProfile {
+                fn_uid: *fn_uid,
+                name: fn_uid.and_then(|fn_uid| names.get(&fn_uid))
+                    .map(|name| (*name).clone()),
+                executions: counter.executions,
+                time: counter.time
+            })
+            .collect();
+        functions.sort_by_key(|entry| Reverse(entry.time));
+
+        ProfileReport {
+            opcodes: opcodes,
+            functions: functions
+        }
+    }
+}
@@ -1,3 +1,10 @@
+use super::{
+    core::{
+        CoreError,
+        CoreResult
+    }
+};
+
 use std::{
     marker::Copy,
     fmt::{
@@ -12,6 +19,7 @@ pub union Register {
     pub uint64: u64,
     pub int64: i64,
     pub float: f32,
+    pub double: f64,
     pub boolean: bool
 }
 
@@ -52,6 +60,92 @@ impl Register {
 
 impl Copy for Register {}
 
+/// Number of general purpose registers, R0 to R15
+pub const GENERAL_REGISTER_COUNT: usize = 16;
+/// Index of the stack pointer in instructions
+pub const SP_INDEX: u8 = 16;
+/// Index of the instruction pointer in instructions
+pub const IP_INDEX: u8 = 17;
+/// Index of the frame pointer in instructions
+pub const FP_INDEX: u8 = 18;
+/// Number of registers addressable by instructions
+pub const REGISTER_COUNT: usize = 19;
+
+/// The registers of a core, addressed by instructions with their index.
+/// R0 to R15 are followed by SP_INDEX, IP_INDEX and FP_INDEX.
+#[derive(Clone, Copy, Debug)]
+pub struct RegisterFile {
+    pub general: [Register; GENERAL_REGISTER_COUNT],
+    pub sp: Register,
+    pub ip: Register,
+    /// Base of the current stack frame, the stack pointer at function entry
+    pub fp: Register
+}
+
+impl RegisterFile {
+    /// Creates a register file with all registers zeroed
+    pub fn new() -> RegisterFile {
+        RegisterFile {
+            general: [Register::new(); GENERAL_REGISTER_COUNT],
+            sp: Register::new(),
+            ip: Register::new(),
+            fp: Register::new()
+        }
+    }
+
+    /// Gets the register with the given index, fails with InvalidRegister past FP_INDEX
+    #[inline]
+    pub fn get(&self, index: u8) -> CoreResult<&Register> {
+        match index {
+            SP_INDEX => Ok(&self.sp),
+            IP_INDEX => Ok(&self.ip),
+            FP_INDEX => Ok(&self.fp),
+            _ => self.general.get(index as usize)
+                .ok_or(CoreError::InvalidRegister)
+        }
+    }
+
+    /// Gets the register with the given index for writing, fails with InvalidRegister past FP_INDEX
+    #[inline]
+    pub fn get_mut(&mut self, index: u8) -> CoreResult<&mut Register> {
+        match index {
+            SP_INDEX => Ok(&mut self.sp),
+            IP_INDEX => Ok(&mut self.ip),
+            FP_INDEX => Ok(&mut self.fp),
+            _ => self.general.get_mut(index as usize)
+                .ok_or(CoreError::InvalidRegister)
+        }
+    }
+
+    /// Gets the raw bits of all registers, in index order
+    pub fn to_bits(&self) -> [u64; REGISTER_COUNT] {
+        let mut bits = [0; REGISTER_COUNT];
+        for (index, bits) in bits.iter_mut().enumerate() {
+            *bits = self.get(index as u8)
+                .map(|register| register.get())
+                .unwrap_or_default();
+        }
+        bits
+    }
+
+    /// Creates a register file from the raw bits of all registers, in index order
+    pub fn from_bits(bits: &[u64; REGISTER_COUNT]) -> RegisterFile {
+        let mut registers = RegisterFile::new();
+        for (index, bits) in bits.iter().enumerate() {
+            if let Ok(register) = registers.get_mut(index as u8) {
+                register.set(*bits);
+            }
+        }
+        registers
+    }
+}
+
+impl Default for RegisterFile {
+    fn default() -> RegisterFile {
+        RegisterFile::new()
+    }
+}
+
 pub trait RegisterAccess<T> {
     fn get_val(&self) -> T;
     fn set_val(&mut self, item: T);
@@ -122,6 +216,27 @@ impl RegisterAccess<f32> for Register {
     }
 }
 
+impl RegisterAccess<f64> for Register {
+    fn get_val(&self) -> f64 {
+        unsafe {
+            self.double
+        }
+    }
+    fn set_val(&mut self, item: f64) {
+        self.double = item;
+    }
+    fn inc_val(&mut self, item: f64) {
+        unsafe {
+            self.double += item;
+        }
+    }
+    fn dec_val(&mut self, item: f64) {
+        unsafe {
+            self.double -= item;
+        }
+    }
+}
+
 impl RegisterAccess<bool> for Register {
     fn get_val(&self) -> bool {
         unsafe {
@@ -0,0 +1,125 @@
+use super::{
+    is::{
+        Opcode,
+        Operand
+    }
+};
+use crate::{
+    codegen::{
+        program::Program
+    }
+};
+
+use std::{
+    collections::HashSet,
+    convert::TryFrom
+};
+
+/// Reasons a program is rejected by the verifier, with the code offset of the offending instruction
+#[derive(PartialEq, Debug, Clone)]
+pub enum VerifyError {
+    /// The data segment is larger than the code
+    InvalidDataSize(usize),
+    InvalidOpcode(usize, u8),
+    /// The operands of the instruction run past the end of the code
+    TruncatedInstruction(usize),
+    /// Offset of the jump and its target, which is not the start of an instruction
+    InvalidJumpTarget(usize, i64),
+    /// Offset of the call and the uid of the function, which is not part of the program
+    UnknownFunction(usize, u64),
+    /// Uid and offset of a function that does not start at an instruction
+    InvalidFunctionOffset(u64, usize),
+    /// Offset of the constant load and the address of the constant, which is not within the data segment
+    InvalidConstant(usize, u32),
+    /// Offset of the string instruction and its address offset, past which its last field does not fit into an i16
+    InvalidFieldOffset(usize, i16)
+}
+
+pub type VerifyResult<T> = Result<T, VerifyError>;
+
+/// Checks that the code of a program decodes into whole instructions, that static jumps land on them,
+/// that calls and the function table refer to functions of the program, that constants are within its data
+/// and that the fields of string operands can be addressed.
+/// Dynamic jumps and calls are left to the core, as their targets are only known at run time.
+pub fn verify(program: &Program) -> VerifyResult<()> {
+    let code = &program.code;
+    if program.data_size > code.len() {
+        return Err(VerifyError::InvalidDataSize(program.data_size));
+    }
+
+    let mut instr_offsets = HashSet::new();
+    // Offset of every static jump with its target
+    let mut jumps = Vec::new();
+
+    let mut offset = program.data_size;
+    while offset < code.len() {
+        let opcode = Opcode::try_from(code[offset])
+            .map_err(|_| VerifyError::InvalidOpcode(offset, code[offset]))?;
+        let operands_start = offset + 1;
+        let operands_end = operands_start + opcode.get_operands_size();
+        if operands_end > code.len() {
+            return Err(VerifyError::TruncatedInstruction(offset));
+        }
+        let operands = &code[operands_start..operands_end];
+
+        if let Some(pos) = opcode.get_jump_target_pos() {
+            let target = (offset as i64).wrapping_add(i64::read_le(&operands[pos..pos + 8]));
+            jumps.push((offset, target));
+        }
+
+        match opcode {
+            Opcode::CALL |
+            Opcode::TCALL => {
+                let fn_uid = u64::read_le(&operands[0..8]);
+                if !program.functions.contains_key(&fn_uid) && !program.foreign_functions.contains_key(&fn_uid) {
+                    return Err(VerifyError::UnknownFunction(offset, fn_uid));
+                }
+            },
+            // Only script functions run as tasks or coroutines
+            Opcode::SPAWN |
+            Opcode::CORO => {
+                let fn_uid = u64::read_le(&operands[0..8]);
+                if !program.functions.contains_key(&fn_uid) {
+                    return Err(VerifyError::UnknownFunction(offset, fn_uid));
+                }
+            },
+            Opcode::LDC => {
+                let addr = u32::read_le(&operands[0..4]);
+                if addr as usize + 8 > program.data_size {
+                    return Err(VerifyError::InvalidConstant(offset, addr));
+                }
+            },
+            // Base register and offset of the strings, whose fields are accessed at up to
+            // 40 bytes past the offset for SCAT and 8 bytes past it for SSUB and SCHR
+            Opcode::SCAT |
+            Opcode::SSUB |
+            Opcode::SCHR => {
+                let addr_offset = i16::read_le(&operands[1..3]);
+                let last_field = if opcode == Opcode::SCAT { 40 } else { 8 };
+                if addr_offset.checked_add(last_field).is_none() {
+                    return Err(VerifyError::InvalidFieldOffset(offset, addr_offset));
+                }
+            },
+            _ => {}
+        };
+
+        instr_offsets.insert(offset);
+        offset = operands_end;
+    }
+
+    for (jump_offset, target) in jumps {
+        // Jumping to the end of the code finishes the program
+        let is_end = target == code.len() as i64;
+        if !is_end && (target < 0 || !instr_offsets.contains(&(target as usize))) {
+            return Err(VerifyError::InvalidJumpTarget(jump_offset, target));
+        }
+    }
+
+    for (fn_uid, fn_offset) in program.functions.iter() {
+        if !instr_offsets.contains(fn_offset) {
+            return Err(VerifyError::InvalidFunctionOffset(*fn_uid, *fn_offset));
+        }
+    }
+
+    Ok(())
+}
@@ -9,11 +9,19 @@ use pgs::{
         },
         instruction::{
             Instruction
-        }
+        },
+        data::Data,
+        optimizer::ConstantPropagator
     },
     parser::{
         parser::Parser,
-        lexer::Token
+        lexer::Token,
+        ast::{
+            Declaration,
+            Expression,
+            Statement,
+            Type
+        }
     }
 };
 
@@ -277,4 +285,114 @@ fn test_compile_member_call() {
         println!("{}:  {:?}", pos, instr);
         pos += instr.get_size();
     }
-}
\ No newline at end of file
+}
+#[test]
+fn test_constant_propagation() {
+    let code = String::from("
+        fn: main(n: int) ~ int {
+            var size: int = 8;
+            var doubled = size * 2;
+            var counter = size;
+            counter += 1;
+            if doubled > 10 {
+                var inner = doubled + n;
+            }
+            return doubled + counter;
+        }
+    ");
+
+    let parser = Parser::new(code);
+    let decl_list_res = parser.parse_root_decl_list();
+    assert!(decl_list_res.is_ok());
+    let decl_list = decl_list_res.unwrap();
+
+    let fn_decl_args = match &decl_list[0] {
+        Declaration::Function(fn_decl_args) => fn_decl_args,
+        decl => panic!("Expected a function, got {:?}", decl)
+    };
+
+    let stmt_list = ConstantPropagator::new().propagate_fn(fn_decl_args).unwrap();
+    for stmt in stmt_list.iter() {
+        println!("{:?}", stmt);
+    }
+    assert_eq!(fn_decl_args.code_block.as_ref().unwrap().len(), stmt_list.len());
+
+    // The initializer of doubled is folded, counter is mutated and stays a variable
+    match &stmt_list[1] {
+        Statement::VariableDecl(var_decl_args) => assert_eq!(Expression::IntLiteral(16), *var_decl_args.assignment),
+        stmt => panic!("Expected a variable declaration, got {:?}", stmt)
+    };
+    match &stmt_list[4] {
+        Statement::If(if_stmt_args) => assert_eq!(Expression::BoolLiteral(true), if_stmt_args.if_expr),
+        stmt => panic!("Expected an if statement, got {:?}", stmt)
+    };
+    match &stmt_list[5] {
+        Statement::Return(Some(ret_expr)) => {
+            assert_eq!(
+                Expression::Addition(
+                    Box::new(Expression::IntLiteral(16)),
+                    Box::new(Expression::Variable(String::from("counter")))
+                ),
+                *ret_expr
+            );
+        },
+        stmt => panic!("Expected a return statement, got {:?}", stmt)
+    };
+}
+
+#[test]
+fn test_string_interning() {
+    let mut data = Data::new();
+    let hello = data.get_string_slice(&String::from("hello world"));
+    assert_eq!((11, 0), hello);
+    assert_eq!(hello, data.get_string_slice(&String::from("hello world")));
+
+    // Literals contained in an earlier one share its bytes
+    assert_eq!((5, 6), data.get_string_slice(&String::from("world")));
+    assert_eq!((2, 2), data.get_string_slice(&String::from("ll")));
+    assert_eq!(11, data.bytes.len());
+
+    let static_addr = data.add_static(&[1, 2, 3, 4]);
+    assert_eq!(11, static_addr);
+    let other = data.get_string_slice(&String::from("other"));
+    assert_eq!((5, 15), other);
+    assert_eq!(b"hello world\x01\x02\x03\x04other".to_vec(), data.bytes);
+}
+
+#[test]
+fn test_string_offsets() {
+    let code = String::from("
+        cont: Player {
+            score: int;
+            name: string;
+            tags: (int, string);
+        }
+    ");
+
+    let parser = Parser::new(code);
+    let decl_list_res = parser.parse_root_decl_list();
+    assert!(decl_list_res.is_ok());
+    let decl_list = decl_list_res.unwrap();
+
+    let mut compiler = Compiler::new();
+    let compile_res = compiler.compile_root(&decl_list);
+    println!("{:?}", compile_res);
+    assert!(compile_res.is_ok());
+
+    let player_type = Type::Other(String::from("Player"));
+    let mut player_offsets = compiler.get_all_string_offsets(&player_type).unwrap();
+    player_offsets.sort_unstable();
+    assert_eq!(vec![8, 32], player_offsets);
+
+    let mut array_offsets = compiler.get_all_string_offsets(&Type::Array(Box::new(player_type.clone()), 2)).unwrap();
+    array_offsets.sort_unstable();
+    assert_eq!(vec![8, 32, 56, 80], array_offsets);
+
+    // Optionals and results may be unset, so their strings are not reference counted
+    let optional_type = Type::Optional(Box::new(Type::String));
+    assert_eq!(vec![0], compiler.get_all_string_offsets(&optional_type).unwrap());
+    assert!(compiler.get_string_offsets(&optional_type).unwrap().is_empty());
+    let result_type = Type::Result(Box::new(Type::Tuple(vec![Type::Int, Type::String])));
+    assert_eq!(vec![8, 24], compiler.get_all_string_offsets(&result_type).unwrap());
+    assert!(compiler.get_string_offsets(&result_type).unwrap().is_empty());
+}
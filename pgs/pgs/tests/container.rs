@@ -0,0 +1,355 @@
+extern crate pgs;
+use pgs::{
+    codegen::{
+        compiler::CompilerError,
+        register::Register
+    },
+    engine::{
+        Engine,
+        EngineError
+    },
+    vm::{
+        core::CoreError
+    },
+    api::{
+        container::{
+            PgsContainer,
+            PgsValue
+        },
+        function::Function,
+        module::Module,
+        adapter::{
+            ObjectAdapter,
+            Method
+        }
+    }
+};
+
+use std::{
+    sync::{
+        Arc,
+        Mutex
+    }
+};
+
+#[derive(PgsContainer)]
+struct GameState {
+    score: i64,
+    speed: f32,
+    paused: bool,
+    player: String,
+    #[skip]
+    frames: Vec<u8>
+}
+
+#[test]
+fn test_container_derive() {
+    let code = String::from("
+        fn: main(state: int) ~ int {
+            var score = GameState::get_score(state);
+            GameState::set_score(state, score + 10);
+            var speed = GameState::get_speed(state);
+            GameState::set_speed(state, speed * 2.0);
+            GameState::set_paused(state, true);
+            GameState::set_player(state, \"bob\");
+            var player = GameState::get_player(state);
+            GameState::set_player(state, player);
+            return score;
+        }
+    ");
+
+    let state = Arc::new(Mutex::new(GameState {
+        score: 32,
+        speed: 1.5,
+        paused: false,
+        player: String::from("alice"),
+        frames: Vec::new()
+    }));
+
+    let mut engine = Engine::new(1024);
+    let reg_res = engine.register_container::<GameState>();
+    println!("{:?}", reg_res);
+    assert!(reg_res.is_ok());
+
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let handle_res = engine.insert_foreign_ptr(state.clone());
+    assert!(handle_res.is_ok());
+    let push_res = engine.push_stack(handle_res.unwrap() as i64);
+    assert!(push_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+
+    let result_res = engine.get_register_value::<i64>(Register::R0);
+    assert_eq!(32, result_res.unwrap());
+
+    let state = state.lock().unwrap();
+    assert_eq!(42, state.score);
+    assert_eq!(3.0, state.speed);
+    assert!(state.paused);
+    assert_eq!("bob", state.player);
+    assert!(state.frames.is_empty());
+    drop(state);
+
+    // Handles of other types are rejected
+    let other = Arc::new(Mutex::new(String::from("not a game state")));
+    let handle = engine.insert_foreign_ptr(other.clone()).unwrap();
+    match engine.call_function::<(i64,), i64>("root::main", (handle as i64,)) {
+        Err(error) => {
+            match *error {
+                EngineError::CoreError(core_error) => {
+                    assert!(matches!(core_error.get_cause(), CoreError::InvalidHandle(invalid) if *invalid == handle));
+                },
+                other => panic!("Unexpected error {:?}", other)
+            };
+        },
+        Ok(_) => panic!("Foreign handle of another type was accepted")
+    };
+    assert_eq!("not a game state", *other.lock().unwrap());
+}
+
+#[derive(PgsValue, PartialEq, Debug, Clone)]
+struct Vec2 {
+    x: f32,
+    y: f32
+}
+
+#[derive(PgsValue, PartialEq, Debug)]
+struct Body {
+    position: Vec2,
+    mass: f64,
+    id: i64,
+    active: bool,
+    #[skip]
+    name: String
+}
+
+#[test]
+fn test_container_value_derive() {
+    let code = String::from("
+        fn: length_sq(v: Vec2) ~ float {
+            return v.x * v.x + v.y * v.y;
+        }
+
+        fn: scaled(v: Vec2, f: float) ~ Vec2 {
+            return Vec2 { x: v.x * f, y: v.y * f };
+        }
+
+        fn: step(body: &Body, dt: float) ~ int {
+            body.position.x = body.position.x + dt;
+            body.mass = body.mass * 2.0d;
+            body.active = false;
+            return body.id;
+        }
+
+        fn: main() ~ int {
+            var v = Vec2 { x: 3.0, y: 4.0 };
+            static_assert(sizeof(Body) == 28);
+            return (v.x * v.y) as int;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    assert!(engine.register_value::<Vec2>().is_ok());
+    assert!(engine.register_value::<Body>().is_ok());
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    assert!(engine.run_fn("root::main").is_ok());
+    assert_eq!(12, engine.get_register_value::<i64>(Register::R0).unwrap());
+
+    let v = Vec2 { x: 1.5, y: 2.0 };
+    assert_eq!(6.25, engine.call_function::<(Vec2,), f32>("root::length_sq", (v.clone(),)).unwrap());
+    let scaled = engine.call_function::<(Vec2, f32), Vec2>("root::scaled", (v, 2.0)).unwrap();
+    assert_eq!(Vec2 { x: 3.0, y: 4.0 }, scaled);
+
+    let mut body = Body {
+        position: Vec2 { x: 1.0, y: 2.0 },
+        mass: 1.25,
+        id: 7,
+        active: true,
+        name: String::from("ball")
+    };
+    let id = engine.call_function::<(&mut Body, f32), i64>("root::step", (&mut body, 0.5)).unwrap();
+    assert_eq!(7, id);
+    assert_eq!(Vec2 { x: 1.5, y: 2.0 }, body.position);
+    assert_eq!(2.5, body.mass);
+    assert!(!body.active);
+    assert_eq!("ball", body.name);
+    assert_eq!(0, engine.get_stack_size());
+
+    // Passing a value where a reference is expected is rejected
+    let step_res = engine.call_function::<(Vec2, f32), i64>("root::step", (Vec2 { x: 0.0, y: 0.0 }, 0.5));
+    assert!(step_res.is_err());
+}
+
+#[derive(PgsContainer)]
+struct File {
+    size: i64
+}
+
+#[test]
+fn test_container_module_builder() {
+    let code = String::from("
+        fn: main(file: int) ~ int {
+            var pi = std::math::PI;
+            var twice = std::math::double(pi);
+            if twice > 6.28d && twice < 6.29d && std::math::TAU > twice && std::fs::SEPARATOR == '/' && std::fs::len(std::fs::NAME) == 2 {
+                std::fs::File::set_size(file, std::fs::File::get_size(file) + std::math::ANSWER);
+                return std::fs::File::get_size(file);
+            }
+            return 0;
+        }
+    ");
+
+    let module = Module::new("std")
+        .with_module(Module::new("math")
+            .with_constant("PI", std::f64::consts::PI)
+            .with_constant("TAU", 2.0 * std::f64::consts::PI + 0.001)
+            .with_constant("ANSWER", 42i64)
+            .with_function(Function::wrap("double", |value: f64| value * 2.0)))
+        .with_module(Module::new("fs")
+            .with_constant("SEPARATOR", '/')
+            .with_constant("NAME", "fs")
+            .with_function(Function::wrap("len", |text: String| text.len() as i64))
+            .with_container_type::<File>()
+            .with_value_type::<Vec2>());
+
+    let file = Arc::new(Mutex::new(File {
+        size: 100
+    }));
+
+    let mut engine = Engine::new(1024);
+    let reg_res = engine.register_module(module.clone());
+    println!("{:?}", reg_res);
+    assert!(reg_res.is_ok());
+
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let handle_res = engine.insert_foreign_ptr(file.clone());
+    assert!(handle_res.is_ok());
+    let push_res = engine.push_stack(handle_res.unwrap() as i64);
+    assert!(push_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(142, engine.get_register_value::<i64>(Register::R0).unwrap());
+    assert_eq!(142, file.lock().unwrap().size);
+
+    // Module constants can not be assigned to
+    let code = String::from("
+        fn: main() ~ int {
+            std::math::ANSWER = 41;
+            return std::math::ANSWER;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    assert!(engine.register_module(module).is_ok());
+    match engine.load_code(&code) {
+        Err(error) => {
+            match *error {
+                EngineError::CompileError(CompilerError::AssignToConstant(name)) => {
+                    assert_eq!("root::std::math::ANSWER", name);
+                },
+                other => panic!("Unexpected error {:?}", other)
+            };
+        },
+        Ok(_) => panic!("Module constant was assigned to")
+    };
+}
+
+struct Counter {
+    count: i64,
+    label: String
+}
+
+impl ObjectAdapter for Counter {
+    fn type_name() -> String {
+        String::from("Counter")
+    }
+
+    fn methods() -> Vec<Method<Counter>> {
+        vec![
+            Method::wrap("add", |counter: &mut Counter, amount: i64| {
+                counter.count += amount;
+                counter.count
+            }),
+            Method::wrap("label", |counter: &mut Counter| counter.label.clone()),
+            Method::wrap("relabel", |counter: &mut Counter, label: String| counter.label = label),
+            // Hands half of the count to a new counter
+            Method::wrap("split", |counter: &mut Counter| {
+                let half = counter.count / 2;
+                counter.count -= half;
+                Arc::new(Mutex::new(Counter {
+                    count: half,
+                    label: counter.label.clone() + "/2"
+                }))
+            })
+        ]
+    }
+}
+
+#[test]
+fn test_container_object_adapter() {
+    let code = String::from("
+        fn: main(counter: int) ~ int {
+            Counter::add(counter, 5);
+            var other = Counter::split(counter);
+            Counter::add(other, 100);
+            Counter::relabel(counter, Counter::label(other));
+            return other;
+        }
+
+        fn: bump(counter: int) ~ int {
+            return Counter::add(counter, 1);
+        }
+    ");
+
+    let counter = Arc::new(Mutex::new(Counter {
+        count: 15,
+        label: String::from("c")
+    }));
+
+    let mut engine = Engine::new(1024);
+    let reg_res = engine.register_adapter::<Counter>();
+    println!("{:?}", reg_res);
+    assert!(reg_res.is_ok());
+
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let handle = engine.insert_foreign_ptr(counter.clone()).unwrap();
+    let call_res = engine.call_function::<(i64,), Arc<Mutex<Counter>>>("root::main", (handle as i64,));
+    assert!(call_res.is_ok());
+    let other = call_res.unwrap();
+    assert_eq!(110, other.lock().unwrap().count);
+    assert_eq!("c/2", other.lock().unwrap().label);
+    let counter_state = counter.lock().unwrap();
+    assert_eq!(10, counter_state.count);
+    assert_eq!("c/2", counter_state.label);
+    drop(counter_state);
+
+    // Handles of other types are rejected
+    let handle = engine.insert_foreign_ptr(Arc::new(Mutex::new(String::from("not a counter")))).unwrap();
+    match engine.call_function::<(i64,), i64>("root::bump", (handle as i64,)) {
+        Err(error) => {
+            match *error {
+                EngineError::CoreError(core_error) => {
+                    assert!(matches!(core_error.get_cause(), CoreError::InvalidHandle(invalid) if *invalid == handle));
+                },
+                other => panic!("Unexpected error {:?}", other)
+            };
+        },
+        Ok(_) => panic!("Foreign handle of another type was accepted")
+    };
+}
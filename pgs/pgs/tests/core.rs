@@ -2,7 +2,19 @@ extern crate pgs;
 use pgs::{
     vm::{
         core::*,
-        is::Opcode
+        is::{
+            Opcode,
+            Operand
+        },
+        register::{
+            RegisterFile,
+            SP_INDEX,
+            FP_INDEX
+        },
+        verifier::{
+            verify,
+            VerifyError
+        }
     },
     codegen::{
         program::Program,
@@ -12,6 +24,8 @@ use pgs::{
 };
 
 use bincode::serialize;
+use std::collections::HashMap;
+
 #[test]
 fn test_core_addi() {
     let mut builder = Builder::new();
@@ -126,4 +140,506 @@ fn test_core_foreign_ptr() {
         let int = int_arc.lock().unwrap();
         assert_eq!(int.0, 10);
     }
-}
\ No newline at end of file
+}
+#[test]
+fn test_core_tail_call() {
+    let mut builder = Builder::new();
+
+    let ret_instr = Instruction::new(Opcode::RET);
+    builder.push_instr(ret_instr);
+
+    // fn countdown(n: int), counts its calls in r3
+    let fn_offset = builder.get_current_offset();
+    let movi_instr = Instruction::new(Opcode::MOVI_AR) // MOVI [sp-8], r0
+        .with_operand(16u8)
+        .with_operand::<i16>(-8)
+        .with_operand(0u8);
+    let ldi_instr = Instruction::new(Opcode::LDI) // LDI 0, r1
+        .with_operand(0i64)
+        .with_operand(1u8);
+    let eqi_instr = Instruction::new(Opcode::EQI) // EQI r0, r1, r2
+        .with_operand(0u8)
+        .with_operand(1u8)
+        .with_operand(2u8);
+    let jmpt_instr = Instruction::new(Opcode::JMPT) // JMPT r2, 0
+        .with_operand(2u8)
+        .with_operand(0u64);
+    let count_instr = Instruction::new(Opcode::ADDI_I) // ADDI_I r3, 1, r3
+        .with_operand(3u8)
+        .with_operand(1i64)
+        .with_operand(3u8);
+    let subi_instr = Instruction::new(Opcode::SUBI_I) // SUBI_I r0, 1, r0
+        .with_operand(0u8)
+        .with_operand(1i64)
+        .with_operand(0u8);
+    let push_instr = Instruction::new_inc_stack(8);
+    let mov_instr = Instruction::new(Opcode::MOVI_RA) // MOVI r0, [sp-8]
+        .with_operand(0u8)
+        .with_operand(16u8)
+        .with_operand::<i16>(-8);
+    let tcall_instr = Instruction::new(Opcode::TCALL) // TCALL 1, 0, 8
+        .with_operand(1u64)
+        .with_operand(0u32)
+        .with_operand(8u32);
+
+    builder.push_instr(movi_instr);
+    builder.push_instr(ldi_instr);
+    builder.push_instr(eqi_instr);
+    builder.push_instr(jmpt_instr);
+    builder.push_instr(count_instr);
+    builder.push_instr(subi_instr);
+    builder.push_instr(push_instr);
+    builder.push_instr(mov_instr);
+    builder.push_instr(tcall_instr);
+
+    let mut functions = HashMap::new();
+    functions.insert(1u64, fn_offset);
+
+    let program = Program::new()
+        .with_code(builder.build())
+        .with_functions(functions);
+
+    let mut core = Core::new(1024);
+    core.load_program(program);
+    assert!(core.push_stack(100000i64).is_ok());
+
+    // Far deeper than the stack would allow with CALL
+    let run_res = core.run_fn(1);
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(100000, core.reg(3).unwrap().get::<i64>());
+    assert_eq!(8, core.get_stack_size());
+    assert!(core.get_call_stack().is_empty());
+}
+
+#[test]
+fn test_core_heap_alloc() {
+    let mut builder = Builder::new();
+
+    let ldi_instr = Instruction::new(Opcode::LDI) // LDI 42, r0
+        .with_operand(42i64)
+        .with_operand(0u8);
+    let push_instr = Instruction::new_inc_stack(8);
+    let mov_instr = Instruction::new(Opcode::MOVI_RA) // MOVI r0, [sp-8]
+        .with_operand(0u8)
+        .with_operand(16u8)
+        .with_operand::<i16>(-8);
+    let alloc_instr = Instruction::new(Opcode::ALLOC) // ALLOC [sp-8], 8, r1
+        .with_operand(16u8)
+        .with_operand::<i16>(-8)
+        .with_operand(8u32)
+        .with_operand(1u8);
+    let lda_instr = Instruction::new(Opcode::LDA) // LDA 32, r2
+        .with_operand(32u64)
+        .with_operand(2u8);
+    let realloc_instr = Instruction::new(Opcode::REALLOC) // REALLOC r1, r2, r1
+        .with_operand(1u8)
+        .with_operand(2u8)
+        .with_operand(1u8);
+    let load_instr = Instruction::new(Opcode::MOVI_AR) // MOVI [r1], r3
+        .with_operand(1u8)
+        .with_operand::<i16>(0)
+        .with_operand(3u8);
+    let free_instr = Instruction::new(Opcode::FREE) // FREE r1
+        .with_operand(1u8);
+    let ret_instr = Instruction::new(Opcode::RET);
+
+    builder.push_instr(ldi_instr);
+    builder.push_instr(push_instr);
+    builder.push_instr(mov_instr);
+    builder.push_instr(alloc_instr);
+    builder.push_instr(lda_instr);
+    builder.push_instr(realloc_instr);
+    builder.push_instr(load_instr);
+    builder.push_instr(free_instr);
+    builder.push_instr(ret_instr);
+
+    let program = Program::new().with_code(builder.build());
+
+    let mut core = Core::new(1024);
+    core.load_program(program);
+    let run_res = core.run();
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(42, core.reg(3).unwrap().get::<i64>());
+    assert_eq!(1, core.get_stats().heap_frees);
+
+    // The freed memory is handed out again
+    let freed_addr = core.reg(1).unwrap().get::<u64>();
+    let a = core.heap_alloc(&[1; 8]).unwrap();
+    assert_eq!(freed_addr, a);
+    let b = core.heap_alloc(&[2; 8]).unwrap();
+    let c = core.heap_alloc(&[3; 8]).unwrap();
+    assert!(core.heap_free(b).is_ok());
+    assert!(!core.is_heap_alloc(b, 8));
+    assert_eq!(b, core.heap_alloc(&[4; 8]).unwrap());
+
+    // Growing a block in the middle of the heap moves it
+    let moved = core.heap_realloc(a, 16).unwrap();
+    assert_ne!(a, moved);
+    assert!(core.is_heap_alloc(moved, 16));
+    assert_eq!(1u8, core.mem_get::<u8>((moved, 7)).unwrap());
+    assert_eq!(0u8, core.mem_get::<u8>((moved, 8)).unwrap());
+    assert_eq!(3u8, core.mem_get::<u8>((c, 0)).unwrap());
+
+    match core.heap_free(a) {
+        Err(CoreError::InvalidHeapAddress(addr)) => assert_eq!(a, addr),
+        other => panic!("Unexpected result {:?}", other)
+    };
+}
+
+#[test]
+fn test_core_memory_bounds() {
+    let load_instr = Instruction::new(Opcode::MOVI_AR) // MOVI [sp+1000], r0
+        .with_operand(16u8)
+        .with_operand::<i16>(1000)
+        .with_operand(0u8);
+    let store_instr = Instruction::new(Opcode::MOVI_RA) // MOVI r0, [sp-8]
+        .with_operand(0u8)
+        .with_operand(16u8)
+        .with_operand::<i16>(-8);
+    // Missing its operands
+    let truncated_instr = Instruction::new(Opcode::LDI);
+
+    for instr in [load_instr, store_instr, truncated_instr] {
+        let mut builder = Builder::new();
+        builder.push_instr(instr);
+        let program = Program::new().with_code(builder.build());
+
+        let mut core = Core::new(64);
+        core.load_program(program);
+        let run_res = core.run();
+        println!("{:?}", run_res);
+        match run_res {
+            Err(CoreError::MemoryOutOfBounds(_, 8)) => {},
+            other => panic!("Unexpected result {:?}", other)
+        };
+    }
+}
+
+#[test]
+fn test_core_malformed_address() {
+    // Type bits 3, 5 and 7 name no memory, foreign pointers can not be read as memory
+    let raw_addresses = [0xFFFFFFFFFFFFFFFFu64, 3 << 61, 5 << 61, 4 << 61];
+
+    for raw_address in raw_addresses {
+        let lda_instr = Instruction::new(Opcode::LDA) // LDA raw_address, r1
+            .with_operand(raw_address)
+            .with_operand(1u8);
+        let load_instr = Instruction::new(Opcode::MOVI_AR) // MOVI [r1], r0
+            .with_operand(1u8)
+            .with_operand::<i16>(0)
+            .with_operand(0u8);
+        let store_instr = Instruction::new(Opcode::MOVI_RA) // MOVI r0, [r1]
+            .with_operand(0u8)
+            .with_operand(1u8)
+            .with_operand::<i16>(0);
+
+        for instr in [load_instr, store_instr] {
+            let mut builder = Builder::new();
+            builder.push_instr(lda_instr.clone());
+            builder.push_instr(instr);
+            let program = Program::new().with_code(builder.build());
+
+            let mut core = Core::new(64);
+            core.load_program(program);
+            let run_res = core.run();
+            println!("{:?}", run_res);
+            match run_res {
+                Err(CoreError::InvalidAddress(invalid)) => assert_eq!(raw_address, invalid),
+                other => panic!("Unexpected result {:?}", other)
+            };
+        }
+
+        let core = Core::new(64);
+        assert!(matches!(core.mem_get_bytes(raw_address, 8), Err(CoreError::InvalidAddress(_))));
+    }
+}
+
+#[test]
+fn test_core_address_overflow() {
+    // Offsets past the string operands of SCAT do not fit into an i16
+    let scat_instr = Instruction::new(Opcode::SCAT) // SCAT [sp + 32767]
+        .with_operand(SP_INDEX)
+        .with_operand::<i16>(32767);
+    // Growing the stack by this much wraps the stack pointer around
+    let incsp_instr = Instruction::new(Opcode::INCSP_RA) // INCSP u64::MAX, r0, [sp]
+        .with_operand(u64::MAX)
+        .with_operand(0u8)
+        .with_operand::<i16>(0);
+
+    for (instr, is_scat) in [(scat_instr, true), (incsp_instr, false)] {
+        let mut builder = Builder::new();
+        builder.push_instr(instr);
+        builder.push_instr(Instruction::new(Opcode::HALT));
+        let program = Program::new().with_code(builder.build());
+
+        let mut core = Core::new(64);
+        core.load_program(program);
+        let run_res = core.run();
+        println!("{:?}", run_res);
+        match run_res {
+            Err(CoreError::MemoryOutOfBounds(_, _)) if is_scat => {},
+            Err(CoreError::StackOverflow) if !is_scat => {},
+            other => panic!("Unexpected result {:?}", other)
+        };
+    }
+}
+
+#[test]
+fn test_core_stack_overflow() {
+    let mut core = Core::new(16);
+    assert!(core.push_stack(1u64).is_ok());
+    assert!(core.push_stack(2u64).is_ok());
+    match core.push_stack(3u64) {
+        Err(CoreError::StackOverflow) => {},
+        other => panic!("Unexpected result {:?}", other)
+    };
+    assert_eq!(16, core.get_stack_size());
+}
+
+#[test]
+fn test_core_fuel() {
+    let mut builder = Builder::new();
+
+    let ldi_instr0 = Instruction::new(Opcode::LDI) // LDI 58, r0
+        .with_operand(58i64)
+        .with_operand(0u8);
+    let ldi_instr1 = Instruction::new(Opcode::LDI) // LDI 42, r1
+        .with_operand(42i64)
+        .with_operand(1u8);
+    let addi_instr = Instruction::new(Opcode::ADDI) // ADDI r0, r1, r0
+        .with_operand(0u8)
+        .with_operand(1u8)
+        .with_operand(0u8);
+
+    builder.push_instr(ldi_instr0);
+    builder.push_instr(ldi_instr1);
+    builder.push_instr(addi_instr);
+
+    let program = Program::new().with_code(builder.build());
+
+    let mut core = Core::new(1024);
+    core.load_program(program);
+    match core.run_with_fuel(2) {
+        Err(CoreError::OutOfFuel) => {},
+        other => panic!("Unexpected result {:?}", other)
+    };
+    assert_eq!(42, core.reg(1).unwrap().get::<i64>());
+
+    // The addition did not run yet and runs once refueled
+    assert!(core.resume(1).is_ok());
+    assert_eq!(100, core.reg(0).unwrap().get::<i64>());
+    assert_eq!(Some(0), core.get_fuel());
+}
+
+#[test]
+fn test_core_trace() {
+    use std::{
+        sync::{
+            Arc,
+            Mutex
+        }
+    };
+
+    let mut builder = Builder::new();
+
+    let ldi_instr0 = Instruction::new(Opcode::LDI) // LDI 58, r0
+        .with_operand(58i64)
+        .with_operand(0u8);
+    let ldi_instr1 = Instruction::new(Opcode::LDI) // LDI 42, r1
+        .with_operand(42i64)
+        .with_operand(1u8);
+    let addi_instr = Instruction::new(Opcode::ADDI) // ADDI r0, r1, r0
+        .with_operand(0u8)
+        .with_operand(1u8)
+        .with_operand(0u8);
+
+    builder.push_instr(ldi_instr0);
+    builder.push_instr(ldi_instr1);
+    builder.push_instr(addi_instr);
+
+    let program = Program::new().with_code(builder.build());
+
+    let entries = Arc::new(Mutex::new(Vec::new()));
+    let hook_entries = entries.clone();
+
+    let mut core = Core::new(1024);
+    core.load_program(program);
+    core.set_trace_hook(Some(Box::new(move |entry: &TraceEntry| {
+        hook_entries.lock().unwrap().push(entry.clone());
+    })));
+    assert!(core.run().is_ok());
+
+    let entries = entries.lock().unwrap();
+    assert_eq!(3, entries.len());
+    assert_eq!(vec![0, 10, 20], entries.iter().map(|entry| entry.ip).collect::<Vec<usize>>());
+    assert_eq!(Opcode::LDI, entries[1].opcode);
+    let mut ldi_operands = serialize(&42i64).unwrap();
+    ldi_operands.push(1);
+    assert_eq!(ldi_operands, entries[1].operands);
+    assert_eq!(Opcode::ADDI, entries[2].opcode);
+    assert_eq!(vec![0u8, 1, 0], entries[2].operands);
+    assert_eq!(0, entries[2].sp);
+}
+
+#[test]
+fn test_core_verify() {
+    let ldi_instr = Instruction::new(Opcode::LDI) // LDI 42, r0
+        .with_operand(42i64)
+        .with_operand(0u8);
+
+    // JMP 19 skips the LDI and ends the program
+    let mut builder = Builder::new();
+    builder.push_instr(Instruction::new(Opcode::JMP).with_operand(19u64));
+    builder.push_instr(ldi_instr.clone());
+    let program = Program::new().with_code(builder.build());
+    assert_eq!(Ok(()), verify(&program));
+
+    // JMP into the operands of the LDI
+    let mut builder = Builder::new();
+    builder.push_instr(Instruction::new(Opcode::JMP).with_operand(12u64));
+    builder.push_instr(ldi_instr.clone());
+    let program = Program::new().with_code(builder.build());
+    assert_eq!(Err(VerifyError::InvalidJumpTarget(0, 12)), verify(&program));
+
+    // LDI missing its register operand
+    let mut builder = Builder::new();
+    builder.push_instr(ldi_instr.clone());
+    let mut code = builder.build();
+    code.pop();
+    let program = Program::new().with_code(code);
+    assert_eq!(Err(VerifyError::TruncatedInstruction(0)), verify(&program));
+
+    let mut builder = Builder::new();
+    builder.push_instr(ldi_instr.clone());
+    let mut code = builder.build();
+    code.push(250);
+    let program = Program::new().with_code(code);
+    assert_eq!(Err(VerifyError::InvalidOpcode(10, 250)), verify(&program));
+
+    let mut builder = Builder::new();
+    builder.push_instr(Instruction::new(Opcode::CALL).with_operand(7u64));
+    let program = Program::new().with_code(builder.build());
+    assert_eq!(Err(VerifyError::UnknownFunction(0, 7)), verify(&program));
+
+    // Function starting inside the operands of the LDI
+    let mut builder = Builder::new();
+    builder.push_instr(ldi_instr);
+    let mut functions = HashMap::new();
+    functions.insert(0, 4);
+    let program = Program::new()
+        .with_code(builder.build())
+        .with_functions(functions);
+    assert_eq!(Err(VerifyError::InvalidFunctionOffset(0, 4)), verify(&program));
+
+    let program = Program::new()
+        .with_code(vec![0; 4])
+        .with_data_size(8);
+    assert_eq!(Err(VerifyError::InvalidDataSize(8)), verify(&program));
+
+    // SCAT writes the new string 32 bytes past its offset
+    let mut builder = Builder::new();
+    builder.push_instr(Instruction::new(Opcode::SCAT) // SCAT [sp + 32767]
+        .with_operand(SP_INDEX)
+        .with_operand::<i16>(32767));
+    let program = Program::new().with_code(builder.build());
+    assert_eq!(Err(VerifyError::InvalidFieldOffset(0, 32767)), verify(&program));
+}
+
+#[test]
+fn test_core_relative_jumps() {
+    let mut builder = Builder::new();
+    for (value, reg) in [(5i64, 0u8), (0, 1), (1, 2), (0, 3)] {
+        builder.push_instr(Instruction::new(Opcode::LDI) // LDI value, reg
+            .with_operand(value)
+            .with_operand(reg));
+    }
+    builder.push_instr(Instruction::new(Opcode::ADDI) // ADDI r1, r2, r1
+        .with_operand(1u8)
+        .with_operand(2u8)
+        .with_operand(1u8));
+    builder.push_instr(Instruction::new(Opcode::SUBI) // SUBI r0, r2, r0
+        .with_operand(0u8)
+        .with_operand(2u8)
+        .with_operand(0u8));
+    builder.push_instr(Instruction::new(Opcode::GTI) // GTI r0, r3, r4
+        .with_operand(0u8)
+        .with_operand(3u8)
+        .with_operand(4u8));
+    builder.push_instr(Instruction::new(Opcode::JMPT) // JMPT r4, 40
+        .with_operand(4u8)
+        .with_operand(40u64));
+
+    // The target is encoded relative to the JMPT at 52
+    let code = builder.build();
+    assert_eq!((-12i64).to_le_bytes(), code[54..62]);
+
+    // The same code runs unchanged behind a data segment
+    for data_size in [0, 16] {
+        let mut relocated = vec![0u8; data_size];
+        relocated.extend_from_slice(&code);
+        let program = Program::new()
+            .with_code(relocated)
+            .with_data_size(data_size);
+        assert_eq!(Ok(()), verify(&program));
+
+        let mut core = Core::new(1024);
+        core.load_program(program);
+        assert!(core.run_at(data_size).is_ok());
+        assert_eq!(5, core.reg(1).unwrap().get::<i64>());
+    }
+}
+
+#[test]
+fn test_core_registers() {
+    let mut builder = Builder::new();
+    builder.push_instr(Instruction::new(Opcode::LDI) // LDI 42, r3
+        .with_operand(42i64)
+        .with_operand(3u8));
+    builder.push_instr(Instruction::new(Opcode::ADDU_I) // ADDU_I sp, 8, sp
+        .with_operand(16u8)
+        .with_operand(8u64)
+        .with_operand(16u8));
+    let program = Program::new()
+        .with_code(builder.build());
+
+    let mut core = Core::new(1024);
+    core.load_program(program);
+    assert!(core.run().is_ok());
+
+    assert_eq!(42, core.get_register(3).unwrap().get::<i64>());
+    assert_eq!(42, core.get_registers().general[3].get::<i64>());
+    assert_eq!(8, core.get_stack_size());
+    assert_eq!(core.get_registers().sp.get::<u64>(), core.get_register(SP_INDEX).unwrap().get::<u64>());
+    assert_eq!(core.get_registers().fp.get::<u64>(), core.get_register(FP_INDEX).unwrap().get::<u64>());
+
+    // Indices past the frame pointer are rejected instead of read out of bounds
+    assert!(matches!(core.get_register(FP_INDEX + 1), Err(CoreError::InvalidRegister)));
+    assert!(matches!(core.reg(255), Err(CoreError::InvalidRegister)));
+
+    let registers = *core.get_registers();
+    let bits = registers.to_bits();
+    assert_eq!(42, bits[3]);
+    assert_eq!(bits, RegisterFile::from_bits(&bits).to_bits());
+}
+
+#[test]
+fn test_core_operand_encoding() {
+    // Operands are laid out little endian at fixed sizes, bools take up a byte
+    let instr = Instruction::new(Opcode::LDI)
+        .with_operand(-2i64)
+        .with_operand(3u8);
+    let mut expected = (-2i64).to_le_bytes().to_vec();
+    expected.push(3);
+    assert_eq!(expected, instr.operands);
+    assert_eq!(-2i64, instr.get_operand::<i64>(0, 8));
+
+    let instr = Instruction::new(Opcode::LDB)
+        .with_operand(true)
+        .with_operand(0u8);
+    assert_eq!(vec![1u8, 0], instr.operands);
+    assert!(bool::read_le(&[2]));
+    assert_eq!(1.5f32, f32::read_le(&1.5f32.to_le_bytes()));
+    assert_eq!(-300i16, i16::read_le(&[0xD4, 0xFE]));
+}
@@ -1,20 +1,56 @@
 extern crate pgs;
 use pgs::{
     codegen::{
-        compiler::Compiler,
-        register::Register
+        compiler::{
+            Compiler,
+            CompilerError
+        },
+        register::Register,
+        program::{
+            Program,
+            ProgramError,
+            SourcePosition
+        }
     },
     parser::{
         parser::Parser,
         ast::Type
     },
-    engine::Engine,
+    engine::{
+        Engine,
+        EngineBuilder,
+        EngineError
+    },
+    vm::{
+        core::{
+            CoreError,
+            TickState,
+            RunState
+        },
+        is::Opcode,
+        verifier::verify
+    },
     api::{
         module::Module,
-        function::Function,
+        function::{
+            Function,
+            FunctionError
+        },
         adapter::Adapter
     }
 };
+
+use std::{
+    rc::Rc,
+    cell::RefCell,
+    io::Write,
+    sync::{
+        Arc,
+        Mutex
+    },
+    thread,
+    time::Duration
+};
 /*
 #[test]
 fn test_engine_simple_function() {
@@ -325,6 +361,256 @@ fn test_engine_cont_simple() {
     assert!(run_res.is_ok());
 }
 */
+#[test]
+fn test_engine_cont_member_access() {
+    let code = String::from("
+        cont: Inner {
+            a: int;
+            b: int;
+        }
+
+        cont: Record {
+            x: int;
+            ratio: float;
+            flag: bool;
+            letter: char;
+            pair: (int, int);
+            inner: Inner;
+        }
+
+        fn: main() ~ int {
+            var record = Record {
+                x: 1,
+                ratio: 0.5,
+                flag: true,
+                letter: 'a',
+                pair: (2, 3),
+                inner: Inner { a: 4, b: 5 }
+            };
+            var result = record.x * 3 + 1;
+            record.x = 10;
+            var record_ref = &record;
+            record_ref.x += 1;
+            result = result + record.x * 10;
+            if record.flag && record.letter == 'a' && record.ratio == 0.5 {
+                result = result + 1000;
+            }
+            record.flag = false;
+            if record.flag {
+                result = 0;
+            }
+            record.pair = (6, 7);
+            var pair = record.pair;
+            result = result + pair.1 * 10000;
+            record.inner = Inner { a: 8, b: 9 };
+            var inner = record.inner;
+            result = result + inner.b * 100000;
+            return result;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(4 + 110 + 1000 + 70000 + 900000, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_nested_cont() {
+    let code = String::from("
+        mod: geo {
+            pub cont: Line {
+                start: Point;
+                end: Point;
+            }
+
+            pub cont: Point {
+                x: int;
+                y: int;
+            }
+        }
+
+        cont: Node {
+            value: int;
+            next: &Node;
+        }
+
+        cont: Shape {
+            id: int;
+            line: geo::Line;
+        }
+
+        cont: Holder {
+            line: &geo::Line;
+        }
+
+        fn: main() ~ int {
+            var shape = Shape {
+                id: 1,
+                line: geo::Line {
+                    start: geo::Point { x: 2, y: 3 },
+                    end: geo::Point { x: 4, y: 5 }
+                }
+            };
+            shape.line.end.y = 50;
+            var result = shape.line.end.y;
+            var start_x = shape.line.start.x;
+            result = result + start_x * 100;
+            var end = shape.line.end;
+            result = result + end.x * 1000;
+
+            static_assert(sizeof(Node) == 16);
+            static_assert(sizeof(Shape) == 40);
+            var origin = geo::Point { x: 7, y: 0 };
+            var segment = geo::Line { start: origin, end: origin };
+            var holder = Holder { line: &segment };
+            var held_x = holder.line.start.x;
+            result = result + held_x * 10000;
+            return result;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(50 + 200 + 4000 + 70000, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_nested_cont_errors() {
+    let self_containing = String::from("
+        cont: Node {
+            value: int;
+            next: Node;
+        }
+    ");
+    let mutually_containing = String::from("
+        cont: Left {
+            right: (int, Right);
+        }
+
+        cont: Right {
+            left: Left?;
+        }
+    ");
+
+    for code in [ self_containing, mutually_containing ].iter() {
+        let mut engine = Engine::new(1024);
+        let load_res = engine.load_code(code);
+        println!("{:?}", load_res);
+        assert!(load_res.is_err());
+    }
+}
+
+#[test]
+fn test_engine_impl_builtin_and_foreign() {
+    let code = String::from("
+        mod: geo {
+            pub cont: Point {
+                x: int;
+                y: int;
+            }
+        }
+
+        mod: app {
+            import geo::Point;
+
+            impl: Point {
+                pub fn: sum(&this) ~ int {
+                    return this.x + this.y;
+                }
+            }
+        }
+
+        impl: int {
+            fn: abs(&this) ~ int {
+                if ~this < 0 {
+                    var value = ~this;
+                    return 0 - value;
+                }
+                return ~this;
+            }
+
+            fn: min(&this, other: int) ~ int {
+                if ~this > other {
+                    return other;
+                }
+                return ~this;
+            }
+        }
+
+        impl: bool {
+            fn: flip(&this) ~ bool {
+                var value = ~this;
+                return !value;
+            }
+        }
+
+        impl: geo::Point {
+            fn: scale(&this, factor: int) {
+                this.x = this.x * factor;
+                this.y = this.y * factor;
+            }
+        }
+
+        fn: main() ~ int {
+            var n = 0 - 7;
+            var result = n.abs();
+            var big = 4000;
+            var small = big.min(300);
+            result = result + small;
+            var p = geo::Point { x: 2, y: 3 };
+            p.scale(10);
+            var sum = p.sum();
+            result = result + sum * 1000;
+            var r = &n;
+            var r_abs = r.abs();
+            result = result + r_abs * 100000;
+            var q = geo::Point { x: 0 - 9, y: 1 };
+            var qx = q.x.abs();
+            result = result + qx * 1000000;
+            var flag = true;
+            var flipped = flag.flip();
+            if flipped {
+                result = 0;
+            }
+            return result;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(7 + 300 + 50 * 1000 + 7 * 100000 + 9 * 1000000, engine.get_register_value::<i64>(Register::R0).unwrap());
+
+    let code = String::from("
+        fn: main() ~ int {
+            var n = 3;
+            var m = n.abs();
+            return m;
+        }
+    ");
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_err());
+}
+
 #[test]
 fn test_engine_member_call() {
     let code = String::from("
@@ -429,4 +715,5134 @@ fn test_engine_member_call() {
     assert_eq!(engine.get_stack_size(), 0);
     println!("{:?}", run_res);
     assert!(run_res.is_ok());
-}
\ No newline at end of file
+}
+#[test]
+fn test_engine_member_call_stmt() {
+    let code = String::from("
+        iface: Resettable {
+            fn: reset(&this);
+        }
+
+        cont: Counter {
+            count: int;
+        }
+
+        impl: Counter {
+            fn: add(&this, amount: int) ~ int {
+                this.count += amount;
+                return this.count;
+            }
+
+            fn: get(&this) ~ int {
+                return this.count;
+            }
+        }
+
+        impl: Resettable for Counter {
+            fn: reset(&this) {
+                this.count = 100;
+            }
+        }
+
+        fn: main() ~ int {
+            var counter = Counter { count: 1 };
+            counter.add(4);
+            var counter_ref = &counter;
+            counter_ref.add(5);
+            var before_reset = counter.get();
+            var resettable: &Resettable = &counter;
+            resettable.reset();
+            counter.add(1);
+            var after_reset = counter.get();
+            return before_reset * 1000 + after_reset;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(10 * 1000 + 101, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_package_import() {
+    let packages_root = std::env::temp_dir().join("pgs_test_engine_package_import");
+    let package_dir = packages_root.join("extlib");
+    std::fs::create_dir_all(&package_dir).unwrap();
+    std::fs::write(package_dir.join("json.pgs"), "
+        pub fn: value() ~ int {
+            return 40;
+        }
+    ").unwrap();
+
+    let code = String::from("
+        import extlib::json;
+
+        fn: value() ~ int {
+            return 2;
+        }
+
+        fn: main() ~ int {
+            var package_value = json::value();
+            var local_value = value();
+            return package_value + local_value;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    engine.set_packages_root(&packages_root);
+
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+
+    let result_res = engine.get_register_value::<i64>(Register::R0);
+    assert!(result_res.is_ok());
+
+    assert_eq!(42, result_res.unwrap());
+}
+
+#[test]
+fn test_engine_package_module_collision() {
+    let packages_root = std::env::temp_dir().join("pgs_test_engine_package_module_collision");
+    let package_dir = packages_root.join("extlib");
+    std::fs::create_dir_all(package_dir.join("json")).unwrap();
+    std::fs::write(package_dir.join("json.pgs"), "
+        pub mod: util {
+            pub fn: value() ~ int {
+                return 40;
+            }
+        }
+    ").unwrap();
+    std::fs::write(package_dir.join("json").join("util.pgs"), "
+        pub fn: other() ~ int {
+            return 2;
+        }
+    ").unwrap();
+
+    let check_collision = |code: &str, mod_path: &str| {
+        let mut engine = Engine::new(1024);
+        engine.set_packages_root(&packages_root);
+        match engine.load_code(code) {
+            Err(error) => {
+                match *error {
+                    EngineError::PackageModuleCollision(name) => assert_eq!(mod_path, name),
+                    other => panic!("Unexpected error {:?}", other)
+                };
+            },
+            Ok(_) => panic!("Package module was merged into {}", mod_path)
+        };
+    };
+
+    // A module of the importing code is not extended by a package
+    check_collision("
+        import extlib::json;
+
+        mod: extlib {
+            fn: value() ~ int {
+                return 1;
+            }
+        }
+
+        fn: main() ~ int {
+            return json::util::value();
+        }
+    ", "root::extlib");
+
+    // Neither is a module declared by another package
+    check_collision("
+        import extlib::json;
+        import extlib::json::util;
+
+        fn: main() ~ int {
+            return util::other();
+        }
+    ", "root::extlib::json::util");
+}
+
+#[test]
+fn test_engine_file_import() {
+    let sources_root = std::env::temp_dir().join("pgs_test_engine_file_import");
+    let lib_dir = sources_root.join("lib");
+    std::fs::create_dir_all(&lib_dir).unwrap();
+    std::fs::write(lib_dir.join("math.pgs"), "
+        pub fn: square(x: int) ~ int {
+            return x * x;
+        }
+    ").unwrap();
+    // Imports of an imported file are relative to that file
+    std::fs::write(lib_dir.join("utils.pgs"), "
+        import \"math.pgs\";
+
+        pub fn: area(width: int) ~ int {
+            return math::square(width) + 1;
+        }
+    ").unwrap();
+    std::fs::write(sources_root.join("main.pgs"), "
+        import \"lib/utils.pgs\";
+        import \"lib/math.pgs\" = m;
+
+        fn: main() ~ int {
+            var area = utils::area(4);
+            var square = m::square(3);
+            return area * 100 + square;
+        }
+    ").unwrap();
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_file(&sources_root.join("main.pgs"));
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(1709, engine.get_register_value::<i64>(Register::R0).unwrap());
+
+    // Code not loaded from a file imports relative to the sources root
+    let code = String::from("
+        import \"lib/math.pgs\";
+
+        fn: main() ~ int {
+            return math::square(5);
+        }
+    ");
+    let mut engine = Engine::new(1024);
+    engine.set_sources_root(&sources_root);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+    assert!(engine.run_fn("root::main").is_ok());
+    assert_eq!(25, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_file_import_cycle() {
+    let sources_root = std::env::temp_dir().join("pgs_test_engine_file_import_cycle");
+    std::fs::create_dir_all(&sources_root).unwrap();
+    std::fs::write(sources_root.join("first.pgs"), "
+        import \"second.pgs\";
+    ").unwrap();
+    std::fs::write(sources_root.join("second.pgs"), "
+        import \"first.pgs\";
+    ").unwrap();
+
+    let code = String::from("
+        import \"first.pgs\";
+
+        fn: main() ~ int {
+            return 0;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    engine.set_sources_root(&sources_root);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    match load_res {
+        Err(error) => {
+            match *error {
+                EngineError::ImportCycle(file_path) => {
+                    assert_eq!(Some(std::ffi::OsStr::new("first.pgs")), file_path.file_name());
+                },
+                other => panic!("Unexpected error {:?}", other)
+            };
+        },
+        Ok(_) => panic!("Import cycle was not detected")
+    };
+}
+
+#[test]
+fn test_engine_module_file() {
+    let sources_root = std::env::temp_dir().join("pgs_test_engine_module_file");
+    let util_dir = sources_root.join("util");
+    std::fs::create_dir_all(&util_dir).unwrap();
+    std::fs::write(sources_root.join("shapes.pgs"), "
+        pub fn: square_area(side: int) ~ int {
+            return side * side;
+        }
+    ").unwrap();
+    // Module files of "util/mod.pgs" are looked up in "util/"
+    std::fs::write(util_dir.join("mod.pgs"), "
+        pub mod: numbers;
+
+        pub fn: double(x: int) ~ int {
+            return x * 2;
+        }
+    ").unwrap();
+    std::fs::write(util_dir.join("numbers.pgs"), "
+        pub fn: seven() ~ int {
+            return 7;
+        }
+    ").unwrap();
+    std::fs::write(sources_root.join("main.pgs"), "
+        mod: shapes;
+        mod: util;
+
+        fn: main() ~ int {
+            var area = shapes::square_area(3);
+            var doubled = util::double(area);
+            return doubled * 10 + util::numbers::seven();
+        }
+    ").unwrap();
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_file(&sources_root.join("main.pgs"));
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(187, engine.get_register_value::<i64>(Register::R0).unwrap());
+
+    let code = String::from("
+        mod: missing;
+
+        fn: main() ~ int {
+            return 0;
+        }
+    ");
+    let mut engine = Engine::new(1024);
+    engine.set_sources_root(&sources_root);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    match load_res {
+        Err(error) => {
+            match *error {
+                EngineError::ModuleFileNotFound(file_path) => {
+                    assert_eq!(sources_root.join("missing.pgs"), file_path);
+                },
+                other => panic!("Unexpected error {:?}", other)
+            };
+        },
+        Ok(_) => panic!("Missing module file was not reported")
+    };
+
+    let missing_path = sources_root.join("missing.pgs");
+    match engine.load_file(&missing_path) {
+        Err(error) => {
+            match *error {
+                EngineError::SourceNotReadable(file_path) => assert_eq!(missing_path, file_path),
+                other => panic!("Unexpected error {:?}", other)
+            };
+        },
+        Ok(_) => panic!("Missing script file was loaded")
+    };
+}
+
+#[test]
+fn test_engine_step_debug_info() {
+    let code = String::from("
+        fn: main() ~ int {
+            var x: int = 4;
+            var y: int = x + 1;
+            return y;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let debug_info_res = engine.get_debug_info();
+    assert!(debug_info_res.is_ok());
+    let debug_info = debug_info_res.unwrap();
+
+    let lines: Vec<usize> = debug_info.statements.values()
+        .map(|stmt_info| stmt_info.line)
+        .collect();
+    assert_eq!(lines, vec![3, 4, 5]);
+
+    let start_res = engine.start_fn("root::main");
+    assert!(start_res.is_ok());
+    assert_eq!(debug_info.get_function_at(engine.get_ip()), Some(&String::from("root::main")));
+
+    // Run up to the return statement
+    let return_offset = debug_info.get_line_offset(5).unwrap();
+    while engine.get_ip() != return_offset {
+        let step_res = engine.step();
+        assert!(step_res.is_ok());
+        assert!(step_res.unwrap());
+    }
+
+    let stmt_info = debug_info.get_statement(return_offset).unwrap();
+    let y_info = stmt_info.variables.iter()
+        .find(|var_info| var_info.name == "y")
+        .unwrap();
+    let y_res = engine.get_stack_value::<i64>(y_info.sp_offset);
+    assert!(y_res.is_ok());
+    assert_eq!(5, y_res.unwrap());
+
+    loop {
+        let step_res = engine.step();
+        assert!(step_res.is_ok());
+        if !step_res.unwrap() {
+            break;
+        }
+    }
+
+    let result_res = engine.get_register_value::<i64>(Register::R0);
+    assert!(result_res.is_ok());
+    assert_eq!(5, result_res.unwrap());
+}
+
+#[test]
+fn test_engine_builder_max_instructions() {
+    let code = String::from("
+        fn: main() ~ int {
+            var i: int = 0;
+            while i < 100000 {
+                i = i + 1;
+            }
+            return i;
+        }
+    ");
+
+    let mut engine = EngineBuilder::new()
+        .with_stack_size(2048)
+        .with_max_instructions(1000)
+        .build()
+        .unwrap();
+    let load_res = engine.load_code(&code);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    match run_res {
+        Err(err) => {
+            match *err {
+                EngineError::CoreError(ref core_error) if matches!(core_error.get_cause(), CoreError::InstructionLimit) => {},
+                _ => panic!("Expected InstructionLimit, got {:?}", err)
+            };
+        },
+        Ok(_) => panic!("Expected InstructionLimit")
+    };
+}
+
+#[test]
+fn test_engine_stats() {
+    let code = String::from("
+        fn: main() ~ int {
+            var i: int = 0;
+            while i < 3 {
+                std::tick(i);
+                i = i + 1;
+            }
+            game::tick(i);
+            return i;
+        }
+    ");
+
+    let tick_function = Function::new("tick")
+        .with_arg(Type::Int)
+        .with_ret_type(Type::Void)
+        .with_closure(Box::new(|_adapter: &mut Adapter| {}));
+    let module = Module::new("std")
+        .with_function(tick_function.clone());
+    // Functions of the same name in other modules are counted separately
+    let game_module = Module::new("game")
+        .with_function(tick_function);
+
+    let mut engine = Engine::new(1024);
+    let reg_res = engine.register_module(module);
+    assert!(reg_res.is_ok());
+    assert!(engine.register_module(game_module).is_ok());
+
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+
+    let stats = engine.get_stats().clone();
+    println!("{:?}", stats);
+    assert!(stats.instructions > 0);
+    assert!(stats.peak_stack >= 8);
+    assert_eq!(Some(&3), stats.foreign_calls.get("root::std::tick"));
+    assert_eq!(Some(&1), stats.foreign_calls.get("root::game::tick"));
+
+    engine.reset_stats();
+    assert_eq!(0, engine.get_stats().instructions);
+}
+
+#[test]
+fn test_engine_on_runtime_error() {
+    let code = String::from("
+        fn: spin() ~ int {
+            var i: int = 0;
+            while i < 100000 {
+                i = i + 1;
+            }
+            return i;
+        }
+
+        fn: main() ~ int {
+            var result = spin();
+            return result;
+        }
+    ");
+
+    let mut engine = EngineBuilder::new()
+        .with_max_instructions(1000)
+        .build()
+        .unwrap();
+    let load_res = engine.load_code(&code);
+    assert!(load_res.is_ok());
+
+    let reported = Rc::new(RefCell::new(None));
+    let reported_handler = reported.clone();
+    engine.on_runtime_error(Box::new(move |info| {
+        let frames: Vec<Option<String>> = info.stack_trace.iter()
+            .map(|frame| frame.function.clone())
+            .collect();
+        *reported_handler.borrow_mut() = Some((format!("{:?}", info.error.get_cause()), info.function.clone(), frames));
+    }));
+
+    let run_res = engine.run_fn("root::main");
+    assert!(run_res.is_err());
+
+    let (error, function, frames) = reported.borrow_mut().take()
+        .expect("Runtime error callback was not invoked");
+    assert_eq!("InstructionLimit", error);
+    assert_eq!(Some(String::from("root::spin")), function);
+    assert_eq!(
+        vec![Some(String::from("root::spin")), Some(String::from("root::main"))],
+        frames
+    );
+}
+
+#[test]
+fn test_engine_tick() {
+    let code = String::from("
+        fn: main() ~ int {
+            var i: int = 0;
+            while i < 50 {
+                i = i + 1;
+            }
+            var ready = std::wait_ready(i);
+            return ready;
+        }
+    ");
+
+    let polls = Arc::new(Mutex::new(0));
+    let polls_fn = polls.clone();
+    let wait_function = Function::new("wait_ready")
+        .with_arg(Type::Int)
+        .with_ret_type(Type::Int)
+        .with_closure(Box::new(move |adapter: &mut Adapter| {
+            let value: i64 = adapter.get_arg(0);
+            let mut count = polls_fn.lock().unwrap();
+            *count += 1;
+            if *count < 3 {
+                adapter.block();
+            } else {
+                adapter.return_value(value + 1);
+            }
+        }));
+    let module = Module::new("std")
+        .with_function(wait_function);
+
+    let mut engine = Engine::new(1024);
+    let reg_res = engine.register_module(module);
+    assert!(reg_res.is_ok());
+
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let start_res = engine.start_fn("root::main");
+    assert!(start_res.is_ok());
+
+    let mut states = Vec::new();
+    loop {
+        let state = engine.tick(100).unwrap();
+        states.push(state);
+        if state == TickState::Finished {
+            break;
+        }
+        assert!(states.len() < 100);
+    }
+    println!("{:?}", states);
+
+    assert!(states.contains(&TickState::Yielded));
+    assert_eq!(2, states.iter().filter(|state| **state == TickState::Blocked).count());
+    assert_eq!(3, *polls.lock().unwrap());
+    assert_eq!(51, engine.get_register_value::<i64>(Register::R0).unwrap());
+    assert_eq!(TickState::Finished, engine.tick(100).unwrap());
+}
+
+#[test]
+fn test_engine_reload_code() {
+    let code = String::from("
+        fn: helper(x: int) ~ int {
+            return x + 1;
+        }
+
+        fn: unused() ~ int {
+            return 0;
+        }
+
+        fn: main() ~ int {
+            var result = helper(1);
+            return result;
+        }
+    ");
+    let new_code = String::from("
+        fn: helper(x: int) ~ int {
+            return x * 10;
+        }
+
+        fn: added(x: float) ~ float {
+            return x;
+        }
+
+        fn: main(base: int) ~ int {
+            var result = helper(base);
+            return result;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    assert!(load_res.is_ok());
+
+    let helper_uid = engine.compiler.get_function_uid(&String::from("root::helper")).unwrap();
+
+    let run_res = engine.run_fn("root::main");
+    assert!(run_res.is_ok());
+    assert_eq!(2, engine.get_register_value::<i64>(Register::R0).unwrap());
+
+    let reload_res = engine.reload_code(&new_code);
+    println!("{:?}", reload_res);
+    let report = reload_res.unwrap();
+    assert_eq!(vec![String::from("root::added")], report.added);
+    assert_eq!(vec![String::from("root::unused")], report.removed);
+    assert_eq!(vec![String::from("root::main")], report.changed);
+    assert_eq!(vec![String::from("root::helper")], report.unchanged);
+
+    assert_eq!(helper_uid, engine.compiler.get_function_uid(&String::from("root::helper")).unwrap());
+
+    let push_res = engine.push_stack(5i64);
+    assert!(push_res.is_ok());
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(50, engine.get_register_value::<i64>(Register::R0).unwrap());
+
+    // A failed reload keeps the old program
+    let reload_res = engine.reload_code("fn: main( {");
+    assert!(reload_res.is_err());
+    let pop_res = engine.pop_stack::<i64>();
+    assert!(pop_res.is_ok());
+    let push_res = engine.push_stack(7i64);
+    assert!(push_res.is_ok());
+    let run_res = engine.run_fn("root::main");
+    assert!(run_res.is_ok());
+    assert_eq!(70, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_constant_locals() {
+    let code = String::from("
+        fn: main() ~ int {
+            var size: int = 8;
+            var step = size / 4;
+            var total = 0;
+            while total < size * 2 {
+                total += step;
+            }
+            var scale: float = 0.5;
+            if scale * 4.0 == 2.0 {
+                total = total + size;
+            }
+            return total;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(24, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_sizeof_typeof() {
+    let code = String::from("
+        cont: Vector {
+            x: float;
+            y: float;
+            z: int;
+        }
+
+        fn: main() ~ int {
+            var vec = Vector {
+                x: 1.0,
+                y: 2.0,
+                z: 3
+            };
+            static_assert(typeof(vec.z) == \"int\");
+            static_assert(sizeof(Vector) == 16, \"Vector is \" + typeof(vec));
+            return sizeof(Vector) + sizeof([int; 4]);
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(48, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_static_assert() {
+    let code = String::from("
+        fn: main() ~ int {
+            var scale = 2.5;
+            static_assert(typeof(scale) == \"int\", \"expected int, got \" + typeof(scale));
+            return 0;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    match load_res {
+        Err(error) => {
+            match *error {
+                EngineError::CompileError(CompilerError::StaticAssertion(message)) => {
+                    assert_eq!("expected int, got float", message);
+                },
+                other => panic!("Unexpected error {:?}", other)
+            };
+        },
+        Ok(_) => panic!("Static assertion did not fail")
+    };
+}
+
+#[test]
+fn test_engine_associated_fn() {
+    let code = String::from("
+        mod: geo {
+            pub cont: Point {
+                x: int;
+                y: int;
+            }
+
+            impl: Point {
+                pub fn: new(x: int, y: int) ~ Point {
+                    return Point {
+                        x: x,
+                        y: y
+                    };
+                }
+
+                pub fn: diagonal(v: int) ~ Point {
+                    return Point::new(v, v);
+                }
+            }
+        }
+
+        fn: main() ~ int {
+            var p = geo::Point::new(3, 4);
+            var d = geo::Point::diagonal(5);
+            return p.y + d.x;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(9, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_associated_fn_on_instance() {
+    let code = String::from("
+        cont: Point {
+            x: int;
+            y: int;
+        }
+
+        impl: Point {
+            fn: new(x: int, y: int) ~ Point {
+                return Point {
+                    x: x,
+                    y: y
+                };
+            }
+        }
+
+        fn: main() ~ int {
+            var p = Point::new(3, 4);
+            var q = p.new(1, 2);
+            return q.x;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    match load_res {
+        Err(error) => {
+            match *error {
+                EngineError::CompileError(CompilerError::NotAMemberFunction(fn_name)) => {
+                    assert_eq!("new", fn_name);
+                },
+                other => panic!("Unexpected error {:?}", other)
+            };
+        },
+        Ok(_) => panic!("Associated function was called on an instance")
+    };
+}
+
+#[test]
+fn test_engine_pub_visibility() {
+    let code = String::from("
+        mod: geo {
+            pub cont: Point {
+                x: int;
+                y: int;
+            }
+
+            impl: Point {
+                pub fn: new(x: int, y: int) ~ Point {
+                    var point = Point {
+                        x: x,
+                        y: y
+                    };
+                    return point;
+                }
+
+                fn: secret(&this) ~ int {
+                    return this.x * 10;
+                }
+
+                pub fn: reveal(&this) ~ int {
+                    var value = this.secret();
+                    return value;
+                }
+            }
+
+            fn: scale() ~ int {
+                return 100;
+            }
+
+            pub fn: scaled(v: int) ~ int {
+                var factor = scale();
+                return v * factor;
+            }
+
+            mod: detail {
+                pub fn: offset() ~ int {
+                    var factor = super_scale();
+                    return factor;
+                }
+
+                fn: super_scale() ~ int {
+                    return root::geo::scale() * 10;
+                }
+            }
+
+            pub fn: offset() ~ int {
+                var value = detail::offset();
+                return value;
+            }
+        }
+
+        fn: main() ~ int {
+            var p = geo::Point::new(3, 4);
+            var revealed = p.reveal();
+            var scaled = geo::scaled(5);
+            var offset = geo::offset();
+            return revealed + scaled + offset;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(30 + 500 + 1000, engine.get_register_value::<i64>(Register::R0).unwrap());
+
+    let decls = "
+        mod: geo {
+            cont: Hidden {
+                x: int;
+            }
+
+            pub cont: Point {
+                x: int;
+            }
+
+            impl: Point {
+                fn: secret(&this) ~ int {
+                    return this.x;
+                }
+            }
+
+            fn: scale() ~ int {
+                return 100;
+            }
+
+            mod: detail {
+                pub fn: offset() ~ int {
+                    return 1;
+                }
+            }
+        }
+    ";
+    let private_uses = [
+        ("var value = geo::scale();", "root::geo::scale"),
+        ("var value = geo::detail::offset();", "root::geo::detail"),
+        ("var hidden = geo::Hidden { x: 1 }; var value = hidden.x;", "root::geo::Hidden"),
+        ("var point = geo::Point { x: 1 }; var value = point.secret();", "root::geo::Point::secret")
+    ];
+
+    for (private_use, item_name) in private_uses.iter() {
+        let code = format!("{} fn: main() ~ int {{ {} return value; }}", decls, private_use);
+        let mut engine = Engine::new(1024);
+        let load_res = engine.load_code(&code);
+        println!("{:?}", load_res);
+        match load_res {
+            Err(error) => {
+                match *error {
+                    EngineError::CompileError(CompilerError::PrivateItem(name)) => {
+                        assert_eq!(*item_name, name);
+                    },
+                    other => panic!("Unexpected error {:?}", other)
+                };
+            },
+            Ok(_) => panic!("Private item {} was used outside of its module", item_name)
+        };
+    }
+}
+
+#[test]
+fn test_engine_associated_constants() {
+    let code = String::from("
+        mod: geo {
+            pub cont: Point {
+                x: int;
+                y: int;
+            }
+
+            impl: Point {
+                var ORIGIN_X: int = 3;
+                var SCALE = 2 * 5;
+
+                pub fn: new(x: int, y: int) ~ Point {
+                    return Point {
+                        x: x + ORIGIN_X,
+                        y: y
+                    };
+                }
+
+                pub fn: origin() ~ Point {
+                    return Point::new(0, 0);
+                }
+
+                pub fn: scaled(&this) ~ int {
+                    return this.x * Point::SCALE;
+                }
+            }
+        }
+
+        import geo::Point;
+
+        fn: main() ~ int {
+            var p = geo::Point::origin();
+            var scaled = p.scaled();
+            var scale = geo::Point::SCALE;
+            var origin_x = Point::ORIGIN_X;
+            return scaled + scale * 100 + origin_x * 10000;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(30 + 1000 + 30000, engine.get_register_value::<i64>(Register::R0).unwrap());
+
+    let code = String::from("
+        cont: Point {
+            x: int;
+        }
+
+        impl: Point {
+            var SCALE = 10;
+        }
+
+        fn: main() ~ int {
+            Point::SCALE = 5;
+            return Point::SCALE;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    match load_res {
+        Err(error) => {
+            match *error {
+                EngineError::CompileError(CompilerError::AssignToConstant(name)) => {
+                    assert_eq!("root::Point::SCALE", name);
+                },
+                other => panic!("Unexpected error {:?}", other)
+            };
+        },
+        Ok(_) => panic!("Associated constant was assigned to")
+    };
+}
+
+#[test]
+fn test_engine_do_while() {
+    let code = String::from("
+        fn: main() ~ int {
+            var runs = 0;
+            loop {
+                runs += 1;
+            } while false;
+            var x = 0;
+            loop {
+                x += 1;
+                if x == 2 {
+                    continue;
+                }
+                if x == 6 {
+                    break;
+                }
+                var double = x * 2;
+                runs += double * 10;
+            } while x < 8;
+            return runs;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(1 + 20 + 60 + 80 + 100, engine.get_register_value::<i64>(Register::R0).unwrap());
+    assert_eq!(0, engine.get_stack_size());
+}
+
+#[test]
+fn test_engine_conditional_expr() {
+    let code = String::from("
+        fn: sign(x: int) ~ int {
+            return if x > 0 { 1 } else if x < 0 { 0 - 1 } else { 0 };
+        }
+
+        fn: main() ~ int {
+            var a = 7;
+            var max = if a > 5 { a } else { 5 };
+            var half = if max % 2 == 0 { 0.5 } else { 1.5 };
+            var odd = if half > 1.0 { true } else { false };
+            var bonus = if odd { 100 } else { 200 };
+            var total = max + bonus;
+            total += if a == 7 { 1000 } else { 2000 } * 2;
+            var s = sign(0 - 3);
+            return total + s;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(7 + 100 + 2000 - 1, engine.get_register_value::<i64>(Register::R0).unwrap());
+    assert_eq!(0, engine.get_stack_size());
+
+    let code = String::from("
+        fn: main() ~ int {
+            var a = 7;
+            return if a > 5 { a } else { 1.0 };
+        }
+    ");
+    let mut engine = Engine::new(1024);
+    assert!(engine.load_code(&code).is_err());
+}
+
+#[test]
+fn test_engine_block_expr() {
+    let code = String::from("
+        fn: twice(x: int) ~ int {
+            return x * 2;
+        }
+
+        fn: main() ~ int {
+            var a = 3;
+            var b = {
+                var sq = a * a;
+                sq + 1
+            };
+            var total = a + {
+                var d = 5;
+                d * 10
+            };
+            var e = if b > 5 {
+                var f = b * 100;
+                f
+            } else { 0 };
+            var g = twice({ var h = 4; h + 1 });
+            return b + total + e + g;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(10 + 53 + 1000 + 10, engine.get_register_value::<i64>(Register::R0).unwrap());
+    assert_eq!(0, engine.get_stack_size());
+
+    let code = String::from("
+        fn: main() ~ int {
+            var x = { var y = 2; };
+            return x;
+        }
+    ");
+    let mut engine = Engine::new(1024);
+    assert!(engine.load_code(&code).is_err());
+}
+
+#[test]
+fn test_engine_compound_assign() {
+    let code = String::from("
+        cont: Counter {
+            count: int;
+        }
+
+        var TOTAL: int = 100;
+
+        fn: scale(value: &int, factor: int) {
+            ~value *= factor;
+            ~value -= 1;
+        }
+
+        fn: halve(value: &float) {
+            ~value /= 2.0;
+        }
+
+        fn: main() ~ int {
+            var x = 20;
+            x -= 5;
+            x *= 3;
+            x /= 9;
+            x += 1;
+            var f = 9.0;
+            halve(&f);
+            var counter = Counter {
+                count: 7
+            };
+            counter.count *= 2;
+            counter.count -= 4;
+            var values = [3, 8];
+            values[1] /= 2;
+            values[1] += values[0];
+            scale(&x, 10);
+            TOTAL -= 1;
+            TOTAL /= 3;
+            return x + (f * 10.0) as int * 100 + counter.count * 10000 + values[1] * 1000000 + TOTAL * 100000000;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(59 + 4500 + 100000 + 7000000 + 3300000000, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_increment() {
+    let code = String::from("
+        cont: Counter {
+            count: int;
+        }
+
+        var CALLS: int = 0;
+
+        fn: bump(value: &int) {
+            ~value++;
+            CALLS++;
+        }
+
+        fn: main() ~ int {
+            var i = 0;
+            var steps = 0;
+            while i < 5 {
+                i++;
+                steps++;
+            }
+            var counter = Counter {
+                count: 3
+            };
+            counter.count--;
+            var values = [7, 9];
+            values[1]--;
+            values[1]--;
+            bump(&i);
+            bump(&values[0]);
+            return i + steps * 10 + counter.count * 100 + values[0] * 1000 + values[1] * 10000 + CALLS * 100000;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(6 + 50 + 200 + 8000 + 70000 + 200000, engine.get_register_value::<i64>(Register::R0).unwrap());
+
+    let code = String::from("
+        fn: main() ~ int {
+            var x = 1.5;
+            x++;
+            return 0;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_err());
+}
+
+#[test]
+fn test_engine_for_in() {
+    let code = String::from("
+        cont: Countdown {
+            left: int;
+        }
+
+        impl: Countdown {
+            fn: new(from: int) ~ Countdown {
+                return Countdown {
+                    left: from
+                };
+            }
+
+            fn: has_next(&this) ~ bool {
+                return this.left > 0;
+            }
+
+            fn: next(&this) ~ int {
+                this.left -= 1;
+                return this.left + 1;
+            }
+        }
+
+        fn: main() ~ int {
+            var total = 0;
+            for n in Countdown::new(10) {
+                if n > 8 {
+                    continue;
+                }
+                if n == 3 {
+                    break;
+                }
+                total += n;
+            }
+            var countdown = Countdown::new(3);
+            for n in countdown {
+                for m in Countdown::new(n) {
+                    total += 100;
+                }
+            }
+            return total + countdown.left;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(633, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_for_in_array() {
+    let code = String::from("
+        fn: sum(values: &[int]) ~ int {
+            var total = 0;
+            for value in values {
+                total += value;
+            }
+            return total;
+        }
+
+        fn: main() ~ int {
+            var a = [1, 2, 3, 4];
+            var res = 0;
+            for n in a {
+                if n == 2 {
+                    continue;
+                }
+                res += n;
+            }
+            for row in [[1, 2], [3, 4]] {
+                for cell in row {
+                    res += cell * 10;
+                }
+            }
+            var tail = sum(a[1..]);
+            res += tail * 1000;
+            for flag in [true, false, true] {
+                if flag {
+                    res += 100000;
+                }
+            }
+            return res;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(8 + 100 + 9000 + 200000, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_range() {
+    let code = String::from("
+        fn: main() ~ int {
+            var res = 0;
+            for i in 0..4 {
+                res += i;
+            }
+            var n = 3;
+            for i in 1..=n {
+                if i == 2 {
+                    continue;
+                }
+                res += i * 10;
+            }
+            var span = 2..5;
+            for i in span {
+                res += 100;
+            }
+            var a = [1, 2, 3, 4, 5];
+            var mid = 1..=3;
+            for value in a[mid] {
+                res += value * 1000;
+            }
+            var text = \"abcdef\";
+            var short = len(text[1..=2]);
+            var spanned = len(text[span]);
+            res += short * 100000 + spanned * 1000000;
+            res += (span.1 - span.0) * 10000000;
+            return res;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(6 + 40 + 300 + 9000 + 200000 + 3000000 + 30000000, engine.get_register_value::<i64>(Register::R0).unwrap());
+
+    let code = String::from("
+        fn: main() ~ int {
+            var r = 1..true;
+            return 0;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_err());
+}
+
+#[test]
+fn test_engine_string_slice() {
+    let code = String::from("
+        fn: middle(text: string) ~ string {
+            return text[1..4];
+        }
+
+        fn: main() ~ int {
+            var text = \"x1234y\";
+            var a = std::parse(text[1..5]);
+            var b = std::parse(middle(text)[..2]);
+            var c = std::parse(text[3..][..1]);
+            return a + b + c;
+        }
+
+        fn: out_of_bounds() ~ int {
+            var text = \"x1234y\";
+            var end = 7;
+            return std::parse(text[2..end]);
+        }
+    ");
+
+    let parse_function = Function::new("parse")
+        .with_arg(Type::String)
+        .with_ret_type(Type::Int)
+        .with_closure(Box::new(|adapter: &mut Adapter| {
+            let arg: String = adapter.get_arg(0);
+            adapter.return_value(arg.parse::<i64>().unwrap());
+        }));
+    let module = Module::new("std")
+        .with_function(parse_function);
+
+    let mut engine = Engine::new(1024);
+    let reg_res = engine.register_module(module);
+    assert!(reg_res.is_ok());
+
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(1234 + 12 + 3, engine.get_register_value::<i64>(Register::R0).unwrap());
+
+    let run_res = engine.run_fn("root::out_of_bounds");
+    println!("{:?}", run_res);
+    match run_res {
+        Err(error) => {
+            match *error {
+                EngineError::CoreError(ref core_error) if matches!(core_error.get_cause(), CoreError::OutOfBounds(2, 7, 6)) => {},
+                other => panic!("Unexpected error {:?}", other)
+            };
+        },
+        Ok(_) => panic!("Slice out of bounds did not fail")
+    };
+}
+
+#[test]
+fn test_engine_string_index() {
+    let code = String::from("
+        fn: count(text: string, byte: int) ~ int {
+            var count = 0;
+            var i = 0;
+            while i < len(text) {
+                if text[i] == byte {
+                    count += 1;
+                }
+                i += 1;
+            }
+            return count;
+        }
+
+        fn: main() ~ int {
+            var text = \"banana\";
+            var first = text[0];
+            var last = text[len(text) - 1];
+            var a_count = count(text, 97);
+            return first + last * 1000 + a_count * 1000000 + len(text[2..]) * 100000000;
+        }
+
+        fn: out_of_bounds() ~ int {
+            var text = \"abc\";
+            return text[len(text)];
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(98 + 97 * 1000 + 3 * 1000000 + 4 * 100000000, engine.get_register_value::<i64>(Register::R0).unwrap());
+
+    let run_res = engine.run_fn("root::out_of_bounds");
+    println!("{:?}", run_res);
+    match run_res {
+        Err(error) => {
+            match *error {
+                EngineError::CoreError(ref core_error) if matches!(core_error.get_cause(), CoreError::OutOfBounds(3, 4, 3)) => {},
+                other => panic!("Unexpected error {:?}", other)
+            };
+        },
+        Ok(_) => panic!("Index out of bounds did not fail")
+    };
+}
+
+#[test]
+fn test_engine_wildcard_import() {
+    let code = String::from("
+        mod: utils {
+            pub fn: double(x: int) ~ int {
+                return x * 2;
+            }
+
+            pub cont: Pair {
+                a: int;
+                b: int;
+            }
+
+            impl: Pair {
+                pub fn: new(a: int, b: int) ~ Pair {
+                    return Pair {
+                        a: a,
+                        b: b
+                    };
+                }
+
+                pub fn: sum(&this) ~ int {
+                    return this.a + this.b;
+                }
+            }
+        }
+
+        mod: shapes {
+            pub mod: inner {
+                pub fn: area(w: int, h: int) ~ int {
+                    return w * h;
+                }
+            }
+
+            pub import inner::area;
+            pub import utils::*;
+        }
+
+        mod: app {
+            import utils::*;
+
+            pub fn: run() ~ int {
+                var p: Pair = Pair::new(3, 4);
+                return double(p.sum());
+            }
+        }
+
+        import shapes::area;
+
+        fn: main() ~ int {
+            var x = app::run();
+            var y = area(2, 5);
+            var z = shapes::double(50);
+            return x + y + z;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(14 + 10 + 100, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_import_not_reexported() {
+    let code = String::from("
+        mod: utils {
+            fn: double(x: int) ~ int {
+                return x * 2;
+            }
+        }
+
+        mod: app {
+            import utils::double;
+        }
+
+        fn: main() ~ int {
+            return app::double(2);
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    match load_res {
+        Err(error) => {
+            match *error {
+                EngineError::CompileError(CompilerError::UnknownFunction(fn_name)) => {
+                    assert_eq!(String::from("app::double"), fn_name);
+                },
+                other => panic!("Unexpected error {:?}", other)
+            };
+        },
+        Ok(_) => panic!("Private import was accessible from outside its module")
+    };
+}
+
+#[test]
+fn test_engine_reexport_chain() {
+    let code = String::from("
+        mod: lib {
+            mod: internal {
+                pub cont: Point {
+                    x: int;
+                    y: int;
+                }
+
+                impl: Point {
+                    pub fn: sum(&this) ~ int {
+                        return this.x + this.y;
+                    }
+                }
+
+                pub fn: triple(x: int) ~ int {
+                    return x * 3;
+                }
+
+                pub fn: square(x: int) ~ int {
+                    return x * x;
+                }
+            }
+
+            pub import internal::Point;
+            pub import internal::triple;
+            pub import internal::*;
+        }
+
+        mod: api {
+            pub import lib::Point;
+            pub import lib::*;
+        }
+
+        import api::triple;
+
+        fn: main() ~ int {
+            var p = api::Point {
+                x: 2,
+                y: 3
+            };
+            var a = p.sum();
+            var b = triple(10);
+            var c = api::square(4);
+            return a + b + c;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(5 + 30 + 16, engine.get_register_value::<i64>(Register::R0).unwrap());
+
+    let code = String::from("
+        mod: lib {
+            mod: internal {
+                fn: secret() ~ int {
+                    return 4;
+                }
+            }
+
+            pub import internal::secret;
+        }
+
+        mod: api {
+            pub import lib::secret;
+        }
+
+        fn: main() ~ int {
+            return api::secret();
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    match load_res {
+        Err(error) => {
+            match *error {
+                EngineError::CompileError(CompilerError::UnknownFunction(fn_name)) => {
+                    assert_eq!(String::from("api::secret"), fn_name);
+                },
+                other => panic!("Unexpected error {:?}", other)
+            };
+        },
+        Ok(_) => panic!("Private function was re-exported")
+    };
+}
+
+#[test]
+fn test_engine_tail_call() {
+    let code = String::from("
+        cont: Pair {
+            a: int;
+            b: int;
+        }
+
+        fn: sum_to(n: int, acc: int) ~ int {
+            if n == 0 {
+                return acc;
+            }
+            return sum_to(n - 1, acc + n);
+        }
+
+        fn: fib_pair(n: int, a: int, b: int) ~ Pair {
+            if n == 0 {
+                return Pair {
+                    a: a,
+                    b: b
+                };
+            }
+            var next = a + b;
+            return fib_pair(n - 1, b, next);
+        }
+
+        fn: is_even(n: int) ~ bool {
+            if n == 0 {
+                return true;
+            }
+            return is_odd(n - 1);
+        }
+
+        fn: is_odd(n: int) ~ bool {
+            if n == 0 {
+                return false;
+            }
+            return is_even(n - 1);
+        }
+
+        fn: main() ~ int {
+            var sum = sum_to(10000, 0);
+            var pair = fib_pair(10, 0, 1);
+            var even = 0;
+            if is_even(10000) {
+                even = 1;
+            }
+            return sum + pair.a + even;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(50005000 + 55 + 1, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_match() {
+    let code = String::from("
+        fn: classify(x: int) ~ int {
+            var r = 0;
+            match x + 1 {
+                1 => {
+                    r = 10;
+                },
+                2 => {
+                    var y = 5;
+                    r = 20 + y;
+                },
+                2 * 2 => {
+                    r = 40;
+                },
+                _ => {
+                    r = 99;
+                }
+            }
+            return r;
+        }
+
+        fn: is_half(f: float) ~ int {
+            match f {
+                0.5 => {
+                    return 1;
+                }
+            }
+            return 0;
+        }
+
+        fn: main() ~ int {
+            var a = classify(0);
+            var b = classify(1);
+            var c = classify(3);
+            var d = classify(7);
+            var e = is_half(0.5);
+            var f = is_half(1.5);
+            return a + b + c + d + e + f;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(10 + 25 + 40 + 99 + 1, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_match_duplicate_arm() {
+    let code = String::from("
+        fn: main() ~ int {
+            match 3 {
+                1 + 1 => {}
+                2 => {}
+            }
+            return 0;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_err());
+}
+
+#[test]
+fn test_engine_short_circuit() {
+    let code = String::from("
+        fn: is_b_at(s: string, i: int) ~ bool {
+            return i < len(s) && s[i] == 98;
+        }
+
+        fn: is_past_end(s: string, i: int) ~ bool {
+            return i >= len(s) || s[i] == 0;
+        }
+
+        fn: yes() ~ bool {
+            return true;
+        }
+
+        fn: no() ~ bool {
+            return false;
+        }
+
+        fn: main() ~ int {
+            var r = 0;
+            var a = is_b_at(\"abc\", 1);
+            var b = is_b_at(\"abc\", 10);
+            var c = is_past_end(\"abc\", 10);
+            var d = is_past_end(\"abc\", 0);
+            var t = true;
+            var f = false;
+            if a {
+                r += 1;
+            }
+            if !b {
+                r += 2;
+            }
+            if c {
+                r += 4;
+            }
+            if !d {
+                r += 8;
+            }
+            if t || f && f {
+                r += 16;
+            }
+            // Calls on the rhs, skipped or run
+            var e = true || no();
+            if e {
+                r += 32;
+            }
+            var y = yes();
+            var g = false && yes();
+            if !g {
+                r += 64;
+            }
+            if f || yes() {
+                r += 128;
+            }
+            if t && no() {
+                r += 256;
+            }
+            if y && !g {
+                r += 512;
+            }
+            return r;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(31 + 32 + 64 + 128 + 512, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_modulo() {
+    let code = String::from("
+        fn: main() ~ int {
+            var a = 17;
+            var b = 5;
+            var f = 7.5 % 2.0;
+            var r = a % b + 10 * (a * 3 % 4);
+            if f == 1.5 {
+                r += 100;
+            }
+            static_assert(17 % 5 == 2);
+            return r - 7 % 4;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(2 + 30 + 100 - 3, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_negation() {
+    let code = String::from("
+        fn: abs(x: int) ~ int {
+            if x < 0 {
+                return -x;
+            }
+            return x;
+        }
+
+        fn: main() ~ int {
+            var x: int = -5;
+            var y = 3 - -x * 2;
+            var f: float = -1.5;
+            var z = abs(-(y - 20));
+            var r = x + y + z;
+            r -= -1;
+            if -f == 1.5 {
+                r += 100;
+            }
+            return r;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(-5 - 7 + 27 + 1 + 100, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_char() {
+    let code = String::from("
+        cont: Cell {
+            value: char;
+        }
+
+        fn: is_digit(c: char) ~ bool {
+            return c >= '0' && c <= '9';
+        }
+
+        fn: next(c: &char) ~ char {
+            return ~c;
+        }
+
+        fn: main() ~ int {
+            var c: char = 'a';
+            var cell = Cell {
+                value: '7'
+            };
+            var r = 0;
+            if c == 'a' && c != 'b' && c < 'z' {
+                r += 1;
+            }
+            if is_digit(cell.value) && !is_digit(c) {
+                r += 10;
+            }
+            cell.value = '\\n';
+            if next(&cell.value) == '\\n' {
+                r += 100;
+            }
+            match c {
+                'x' => {
+                    r += 5000;
+                },
+                'a' => {
+                    r += 1000;
+                },
+                _ => {}
+            }
+            return r;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(1111, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_cast() {
+    let code = String::from("
+        fn: average(a: int, b: int) ~ float {
+            return (a + b) as float / 2.0;
+        }
+
+        fn: main() ~ int {
+            var avg = average(3, 4);
+            var r = (avg * 10.0) as int;
+            r += -2.9 as int;
+            r += (1 < 2) as int;
+            r += 'A' as int;
+            static_assert(7 as float == 7.0);
+            return r;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(35 - 2 + 1 + 65, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_invalid_cast() {
+    let code = String::from("
+        fn: main() ~ int {
+            return \"12\" as int;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_err());
+}
+
+#[test]
+fn test_engine_double() {
+    let code = String::from("
+        cont: Vec {
+            x: double;
+            y: double;
+        }
+
+        fn: half(x: double) ~ double {
+            return x / 2.0d;
+        }
+
+        fn: main() ~ int {
+            var v = Vec {
+                x: 1.5d,
+                y: 0.25d
+            };
+            var sum = v.x + v.y;
+            var h = half(sum);
+            var f: float = 2.5f;
+            var r = ((h + f as double) * 1000.0d) as int;
+            // Beyond the precision of a float
+            r += (16777217 as double) as int * 10000;
+            if 0.1d + 0.2d > 0.3d {
+                r += 1;
+            }
+            var x = 1.0d;
+            x += 0.5d;
+            if x == 1.5d && -x < 0.0d {
+                r += 10;
+            }
+            static_assert(0.75d as float as double == 0.75d);
+            return r;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(3375 + 167772170000 + 1 + 10, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_float_literals() {
+    let code = String::from("
+        fn: scale(x: float) ~ float {
+            return x * 1e-2;
+        }
+
+        fn: main() ~ int {
+            var a = 1.5e3;
+            var b = scale(2.5E2f);
+            var c = 4e3d;
+            return (a + b) as int + c as int;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(1500 + 2 + 4000, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_numeric_promotion() {
+    let code = String::from("
+        fn: main() ~ int {
+            var i = 3;
+            var f = i + 2.5;
+            var d = 1.25d * f;
+            var r = (d * 100) as int;
+            if i < 3.5 {
+                r += 1000;
+            }
+            var g = 1.5;
+            g += 1;
+            if g == 2.5 && 7.0 == 7 {
+                r += 10000;
+            }
+            static_assert(1 + 2.5 == 3.5);
+            return r;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(687 + 1000 + 10000, engine.get_register_value::<i64>(Register::R0).unwrap());
+
+    let mut engine = EngineBuilder::new()
+        .with_strict_numeric(true)
+        .build()
+        .unwrap();
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    match load_res {
+        Err(error) => {
+            match *error {
+                EngineError::CompileError(CompilerError::TypeMismatch(Type::Int, Type::Float)) => {},
+                other => panic!("Unexpected error {:?}", other)
+            };
+        },
+        Ok(_) => panic!("Mixed numeric operands were promoted in strict mode")
+    };
+}
+
+#[test]
+fn test_engine_frame_pointer() {
+    let code = String::from("
+        fn: find(n: int, depth: int) ~ int {
+            var total = n;
+            if depth == 0 {
+                return total;
+            }
+            var i = 0;
+            while i < 10 {
+                var j = i * 2;
+                if j > n {
+                    var k = j + depth;
+                    if k > 4 {
+                        var inner = find(k, depth - 1);
+                        return total + inner;
+                    }
+                }
+                i += 1;
+            }
+            return -1;
+        }
+
+        fn: main() ~ int {
+            var a = 1;
+            var r = find(3, 2);
+            return r * 10 + a;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(181, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+fn ref_counting_engine(ref_counting: bool) -> Engine {
+    let make_function = Function::new("make")
+        .with_arg(Type::Int)
+        .with_ret_type(Type::String)
+        .with_closure(Box::new(|adapter: &mut Adapter| {
+            let n: i64 = adapter.get_arg(0);
+            adapter.return_string("x".repeat(n as usize));
+        }));
+    let length_function = Function::new("length")
+        .with_arg(Type::String)
+        .with_ret_type(Type::Int)
+        .with_closure(Box::new(|adapter: &mut Adapter| {
+            let arg: String = adapter.get_arg(0);
+            adapter.return_value(arg.len() as i64);
+        }));
+    let text_module = Module::new("text")
+        .with_function(make_function)
+        .with_function(length_function);
+
+    let mut engine = EngineBuilder::new()
+        .with_stack_size(1024)
+        .with_ref_counting(ref_counting)
+        .build()
+        .unwrap();
+    let reg_res = engine.register_module(text_module);
+    assert!(reg_res.is_ok());
+    engine
+}
+
+#[test]
+fn test_engine_ref_counting() {
+    let code = String::from("
+        fn: keep(s: string) ~ string {
+            return s;
+        }
+
+        fn: main() ~ int {
+            var total = 0;
+            var i = 0;
+            while i < 5 {
+                var s = text::make(i + 1);
+                var t = s;
+                var u = keep(t);
+                var part = u[1..];
+                var n = text::length(part);
+                total += n;
+                i += 1;
+            }
+            var last = text::make(3);
+            last = text::make(4);
+            var n = text::length(last);
+            return total + n * 100;
+        }
+    ");
+
+    let mut engine = ref_counting_engine(true);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(410, engine.get_register_value::<i64>(Register::R0).unwrap());
+    assert_eq!(7, engine.get_stats().heap_allocs);
+    assert_eq!(7, engine.get_stats().heap_frees);
+
+    let mut engine = ref_counting_engine(false);
+    assert!(engine.load_code(&code).is_ok());
+    assert!(engine.run_fn("root::main").is_ok());
+    assert_eq!(410, engine.get_register_value::<i64>(Register::R0).unwrap());
+    assert_eq!(0, engine.get_stats().heap_frees);
+}
+
+#[test]
+fn test_engine_string_ops() {
+    let code = String::from("
+        fn: join(a: string, b: string) ~ string {
+            return a + \", \" + b;
+        }
+
+        fn: main() ~ int {
+            var name = text::make(2) + \"y\";
+            var joined = join(name, intl::word());
+            if char_at(joined, 5) != 'h' || char_at(joined, 6) as int != 233 {
+                return -1;
+            }
+            var total = len(joined);
+            total += text::length(joined[5..8]) * 100;
+            joined += \"!\";
+            return total + len(joined) * 1000;
+        }
+
+        fn: split_char() ~ int {
+            var text = intl::word();
+            return len(text[2..]);
+        }
+
+        fn: char_out_of_bounds() ~ int {
+            var text = \"hello\";
+            var c = char_at(text, 5);
+            return 0;
+        }
+    ");
+
+    // The lexer only takes ASCII, other chars come from the host
+    let word_function = Function::new("word")
+        .with_ret_type(Type::String)
+        .with_closure(Box::new(|adapter: &mut Adapter| {
+            adapter.return_string(String::from("h\u{e9}llo"));
+        }));
+    let mut engine = ref_counting_engine(true);
+    let reg_res = engine.register_module(Module::new("intl").with_function(word_function));
+    assert!(reg_res.is_ok());
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(11 + 300 + 12000, engine.get_register_value::<i64>(Register::R0).unwrap());
+    assert_eq!(6, engine.get_stats().heap_allocs);
+    assert_eq!(6, engine.get_stats().heap_frees);
+
+    match engine.run_fn("root::split_char") {
+        Err(error) => {
+            match *error {
+                EngineError::CoreError(ref core_error) if matches!(core_error.get_cause(), CoreError::InvalidCharBoundary(2)) => {},
+                other => panic!("Unexpected error {:?}", other)
+            };
+        },
+        Ok(_) => panic!("Sliced inside of a char")
+    };
+    match engine.run_fn("root::char_out_of_bounds") {
+        Err(error) => {
+            match *error {
+                EngineError::CoreError(ref core_error) if matches!(core_error.get_cause(), CoreError::OutOfBounds(5, 6, 5)) => {},
+                other => panic!("Unexpected error {:?}", other)
+            };
+        },
+        Ok(_) => panic!("Read a char past the end")
+    };
+
+    // Strings only concatenate with strings
+    let code = String::from("
+        fn: main() ~ int {
+            var text = \"a\" + 1;
+            return 0;
+        }
+    ");
+    let mut engine = ref_counting_engine(true);
+    assert!(engine.load_code(&code).is_err());
+}
+
+#[test]
+fn test_engine_division_by_zero() {
+    let code = String::from("
+        fn: divide(a: int, b: int) ~ int {
+            return a / b;
+        }
+
+        fn: remainder(a: int, b: int) ~ int {
+            return a % b;
+        }
+
+        fn: main() ~ int {
+            return divide(7, 2);
+        }
+
+        fn: by_zero() ~ int {
+            return divide(7, 0);
+        }
+
+        fn: remainder_by_zero() ~ int {
+            return remainder(7, 0);
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(3, engine.get_register_value::<i64>(Register::R0).unwrap());
+
+    for (name, opcode) in [("root::by_zero", Opcode::DIVI), ("root::remainder_by_zero", Opcode::MODI)] {
+        let run_res = engine.run_fn(name);
+        println!("{:?}", run_res);
+        let offset = match run_res {
+            Err(error) => {
+                match *error {
+                    EngineError::CoreError(ref core_error) => match core_error.get_cause() {
+                        CoreError::DivisionByZero(offset) => *offset,
+                        other => panic!("Unexpected error {:?}", other)
+                    },
+                    other => panic!("Unexpected error {:?}", other)
+                }
+            },
+            Ok(_) => panic!("Division by zero did not fail")
+        };
+
+        // The offset points at the failing instruction
+        let mut instr_offset = 0;
+        let instr = engine.compiler.get_builder().instructions.iter()
+            .find(|instr| {
+                let found = instr_offset == offset;
+                instr_offset += instr.get_size();
+                found
+            })
+            .unwrap();
+        assert_eq!(opcode, instr.opcode);
+    }
+}
+
+#[cfg(feature = "jit")]
+#[test]
+fn test_engine_jit() {
+    let code = String::from("
+        fn: fib(n: int) ~ int {
+            if n < 2 {
+                return n;
+            }
+            var a = 0;
+            var b = 1;
+            var i = 1;
+            while i < n {
+                var t = a + b;
+                a = b;
+                b = t;
+                i += 1;
+            }
+            return b;
+        }
+
+        fn: divide(a: int, b: int) ~ int {
+            return a / b;
+        }
+
+        fn: main() ~ int {
+            var sum = 0;
+            var i = 0;
+            while i < 200 {
+                sum += fib(i % 30) + divide(i, 7);
+                i += 1;
+            }
+            return sum;
+        }
+
+        fn: by_zero() ~ int {
+            return divide(7, 0);
+        }
+    ");
+
+    let mut results = Vec::new();
+    for jit_threshold in [None, Some(10)] {
+        let mut engine = Engine::new(1024);
+        engine.set_jit_threshold(jit_threshold);
+        let load_res = engine.load_code(&code);
+        println!("{:?}", load_res);
+        assert!(load_res.is_ok());
+
+        let run_res = engine.run_fn("root::main");
+        println!("{:?}", run_res);
+        assert!(run_res.is_ok());
+        results.push(engine.get_register_value::<i64>(Register::R0).unwrap());
+
+        let jit_calls = engine.get_stats().jit_calls;
+        if jit_threshold.is_some() {
+            assert_eq!(2, engine.get_jit_compiled_count());
+            assert_eq!(2 * (200 - 10), jit_calls);
+
+            // The native code of divide hands the failing division back to the interpreter
+            let run_res = engine.run_fn("root::by_zero");
+            println!("{:?}", run_res);
+            match run_res {
+                Err(error) => match *error {
+                    EngineError::CoreError(ref core_error) if matches!(core_error.get_cause(), CoreError::DivisionByZero(_)) => {},
+                    other => panic!("Unexpected error {:?}", other)
+                },
+                Ok(_) => panic!("Division by zero did not fail")
+            };
+            assert_eq!(jit_calls + 1, engine.get_stats().jit_calls);
+        }
+        else {
+            assert_eq!(0, jit_calls);
+        }
+    }
+    assert_eq!(results[0], results[1]);
+}
+
+#[test]
+fn test_engine_instruction_fusion() {
+    let code = String::from("
+        fn: main() ~ int {
+            var total = 0;
+            var i = 0;
+            while i < 100 {
+                var step = 3;
+                total += i * step;
+                i += 1;
+            }
+            return total;
+        }
+    ");
+
+    let mut results = Vec::new();
+    let mut instructions = Vec::new();
+    for instruction_fusion in [false, true] {
+        let mut engine = Engine::new(1024);
+        engine.set_instruction_fusion(instruction_fusion);
+        let load_res = engine.load_code(&code);
+        println!("{:?}", load_res);
+        assert!(load_res.is_ok());
+
+        let fused_count = engine.compiler.get_builder().instructions.iter()
+            .filter(|instr| matches!(instr.opcode, Opcode::LDI_RA | Opcode::JMPF_CMPI | Opcode::INCSP_RA))
+            .count();
+        assert_eq!(instruction_fusion, fused_count > 0);
+
+        let run_res = engine.run_fn("root::main");
+        println!("{:?}", run_res);
+        assert!(run_res.is_ok());
+        results.push(engine.get_register_value::<i64>(Register::R0).unwrap());
+        instructions.push(engine.get_stats().instructions);
+    }
+    assert_eq!(14850, results[0]);
+    assert_eq!(results[0], results[1]);
+    assert!(instructions[1] < instructions[0]);
+}
+
+#[test]
+fn test_engine_constant_pool() {
+    let code = String::from("
+        cont: Sample {
+            id: int;
+            ratio: double;
+            flag: bool;
+            letter: char;
+        }
+
+        fn: scale(x: int) ~ int {
+            return x * 5000000000 + 5000000000;
+        }
+
+        fn: main() ~ int {
+            var r = 0;
+            if scale(2) - 14999999999 == 1 {
+                r += 1;
+            }
+            var tenth = 0.1d;
+            if tenth * 10.0d > 0.99d {
+                r += 10;
+            }
+            var s = Sample { id: 7, ratio: 0.5d, flag: true, letter: 'a' };
+            var t = Sample { letter: 'a', flag: true, ratio: 0.5d, id: 7 };
+            if s.id == 7 && s.ratio == 0.5d && s.flag && s.letter == 'a' {
+                r += 100;
+            }
+            t.id = 8;
+            if s.id == 7 && t.id == 8 && t.letter == 'a' {
+                r += 1000;
+            }
+            return r;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    // Both uses of 5000000000 load the same constant, 14999999999 and 0.1d have their own
+    let const_addrs: Vec<u32> = engine.compiler.get_builder().instructions.iter()
+        .filter(|instr| instr.opcode == Opcode::LDC)
+        .map(|instr| instr.get_operand(0, 4))
+        .collect();
+    println!("{:?}", const_addrs);
+    assert_eq!(4, const_addrs.len());
+    assert_eq!(const_addrs[0], const_addrs[1]);
+    assert_ne!(const_addrs[0], const_addrs[2]);
+    assert_ne!(const_addrs[2], const_addrs[3]);
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(1111, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_stack_overflow() {
+    let code = String::from("
+        fn: deep(n: int) ~ int {
+            var r = deep(n + 1);
+            return r;
+        }
+
+        fn: start_deep() ~ int {
+            var r = deep(0);
+            return r;
+        }
+
+        fn: spin() {
+            spin();
+        }
+
+        fn: count(n: int) ~ int {
+            if n == 0 {
+                return 0;
+            }
+            var r = count(n - 1);
+            return r + 1;
+        }
+
+        fn: main() ~ int {
+            var r = count(20);
+            return r;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(20, engine.get_register_value::<i64>(Register::R0).unwrap());
+
+    for name in ["root::start_deep", "root::spin"] {
+        let mut engine = Engine::new(1024);
+        assert!(engine.load_code(&code).is_ok());
+        let run_res = engine.run_fn(name);
+        println!("{:?}", run_res);
+        match run_res {
+            Err(error) => {
+                match *error {
+                    EngineError::CoreError(ref core_error) if matches!(core_error.get_cause(), CoreError::StackOverflow) => {},
+                    other => panic!("Unexpected error {:?}", other)
+                };
+            },
+            Ok(_) => panic!("Unbounded recursion did not overflow the stack")
+        };
+    }
+}
+
+#[test]
+fn test_engine_fuel() {
+    let code = String::from("
+        fn: main() ~ int {
+            var i = 0;
+            while i < 1000 {
+                i += 1;
+            }
+            return i;
+        }
+    ");
+
+    let mut engine = EngineBuilder::new()
+        .with_fuel(100)
+        .build()
+        .unwrap();
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let mut run_res = engine.run_fn("root::main");
+    let mut resumes = 0;
+    loop {
+        match run_res {
+            Ok(_) => break,
+            Err(error) => {
+                match *error {
+                    EngineError::CoreError(CoreError::OutOfFuel) => {},
+                    other => panic!("Unexpected error {:?}", other)
+                };
+            }
+        };
+        assert_eq!(Some(0), engine.get_fuel());
+        resumes += 1;
+        run_res = engine.resume(100);
+    }
+    assert!(resumes > 10);
+    assert_eq!(1000, engine.get_register_value::<i64>(Register::R0).unwrap());
+
+    engine.set_fuel(None);
+    assert!(engine.run_fn("root::main").is_ok());
+    assert_eq!(None, engine.get_fuel());
+}
+
+/// Trace sink sharing its output with the test
+#[derive(Clone)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_engine_trace() {
+    let code = String::from("
+        fn: main() ~ int {
+            var a = 5;
+            return a * 2;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    // Keeps the plain instructions the trace is checked for
+    engine.set_instruction_fusion(false);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let buffer = SharedBuffer(Arc::new(Mutex::new(Vec::new())));
+    engine.trace_to(buffer.clone());
+    engine.reset_stats();
+    assert!(engine.run_fn("root::main").is_ok());
+    assert_eq!(10, engine.get_register_value::<i64>(Register::R0).unwrap());
+
+    let trace = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    println!("{}", trace);
+    assert_eq!(engine.get_stats().instructions as usize, trace.lines().count());
+    assert!(trace.lines().any(|line| line.contains("ADDU_I")));
+    assert!(trace.lines().last().unwrap().contains("RET"));
+
+    // Nothing is written once tracing is disabled
+    engine.set_trace_hook(None);
+    assert!(engine.run_fn("root::main").is_ok());
+    assert_eq!(trace.len(), buffer.0.lock().unwrap().len());
+}
+
+#[test]
+fn test_engine_profiler() {
+    let code = String::from("
+        fn: square(x: int) ~ int {
+            return x * x;
+        }
+
+        fn: main() ~ int {
+            var total = 0;
+            var i = 0;
+            while i < 10 {
+                total += square(i);
+                i += 1;
+            }
+            return total;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+    assert!(engine.get_profile_report().is_none());
+
+    engine.set_profiling(true);
+    engine.reset_stats();
+    assert!(engine.run_fn("root::main").is_ok());
+    assert_eq!(285, engine.get_register_value::<i64>(Register::R0).unwrap());
+
+    let report = engine.get_profile_report().unwrap();
+    println!("{}", report);
+    let instructions = engine.get_stats().instructions;
+    assert_eq!(instructions, report.opcodes.iter().map(|entry| entry.executions).sum::<u64>());
+    assert_eq!(instructions, report.functions.iter().map(|entry| entry.executions).sum::<u64>());
+    assert!(report.opcodes.iter().any(|entry| entry.opcode == Opcode::CALL && entry.executions == 10));
+    assert!(report.opcodes.windows(2).all(|pair| pair[0].time >= pair[1].time));
+
+    let square = report.functions.iter()
+        .find(|entry| entry.name.as_deref() == Some("root::square"))
+        .unwrap();
+    let main = report.functions.iter()
+        .find(|entry| entry.name.as_deref() == Some("root::main"))
+        .unwrap();
+    assert!(square.executions > 0);
+    assert!(main.executions > 0);
+    assert!(report.to_string().contains("root::square"));
+
+    engine.set_profiling(false);
+    assert!(engine.get_profile_report().is_none());
+}
+
+#[test]
+fn test_engine_interrupt() {
+    let code = String::from("
+        fn: spin() ~ int {
+            var i = 0;
+            while i >= 0 {
+                i += 1;
+            }
+            return i;
+        }
+
+        fn: count() ~ int {
+            var i = 0;
+            while i < 1000 {
+                i += 1;
+            }
+            return i;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    // Cancelling stops a script that never returns
+    let mut isolate = engine.create_isolate().unwrap();
+    let handle = isolate.get_handle();
+    let runner = thread::spawn(move || {
+        isolate.run_fn("root::spin")
+            .map_err(|e| e.to_string())
+    });
+    thread::sleep(Duration::from_millis(50));
+    handle.cancel();
+    let run_res = runner.join().unwrap();
+    println!("{:?}", run_res);
+    assert!(run_res.unwrap_err().contains("Cancelled"));
+    assert!(!handle.is_cancelled());
+
+    // A paused script waits until it is resumed
+    let mut isolate = engine.create_isolate().unwrap();
+    let handle = isolate.get_handle();
+    handle.pause();
+    assert!(handle.is_paused());
+    let runner = thread::spawn(move || {
+        assert!(isolate.run_fn("root::count").is_ok());
+        isolate.get_register_value::<i64>(Register::R0).unwrap()
+    });
+    thread::sleep(Duration::from_millis(50));
+    assert!(!runner.is_finished());
+    handle.resume();
+    assert!(!handle.is_paused());
+    assert_eq!(1000, runner.join().unwrap());
+
+    // The engine stops with the same error
+    let handle = engine.get_handle();
+    handle.cancel();
+    match engine.run_fn("root::count") {
+        Err(e) => match *e {
+            EngineError::CoreError(ref core_error) if matches!(core_error.get_cause(), CoreError::Cancelled) => {},
+            _ => panic!("Expected a cancelled run, got {:?}", e)
+        },
+        Ok(_) => panic!("Expected a cancelled run")
+    };
+    assert!(engine.run_fn("root::count").is_ok());
+}
+
+#[cfg(feature = "jit")]
+#[test]
+fn test_engine_jit_interrupt() {
+    let code = String::from("
+        fn: spin(n: int) ~ int {
+            var i = 0;
+            while i != n {
+                i += 1;
+            }
+            return i;
+        }
+
+        fn: warm_up() ~ int {
+            var i = 0;
+            while i < 20 {
+                spin(10);
+                i += 1;
+            }
+            return i;
+        }
+
+        fn: forever() ~ int {
+            return spin(-1);
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    engine.set_jit_threshold(Some(10));
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    // The loop of the compiled function hands back to the interpreter once cancelled
+    let mut isolate = engine.create_isolate().unwrap();
+    assert!(isolate.run_fn("root::warm_up").is_ok());
+    assert!(isolate.get_stats().jit_calls > 0);
+    let handle = isolate.get_handle();
+    let runner = thread::spawn(move || {
+        isolate.run_fn("root::forever")
+            .map_err(|e| e.to_string())
+    });
+    thread::sleep(Duration::from_millis(50));
+    handle.cancel();
+    let run_res = runner.join().unwrap();
+    println!("{:?}", run_res);
+    assert!(run_res.unwrap_err().contains("Cancelled"));
+}
+
+#[test]
+fn test_engine_breakpoints() {
+    let code = String::from("
+        fn: main() ~ int {
+            var total = 0;
+            var i = 0;
+            while i < 3 {
+                total += i;
+                i += 1;
+            }
+            return total;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+    assert_eq!(RunState::Halted, engine.get_run_state());
+
+    let debug_info = engine.get_debug_info().unwrap();
+    let body_offset = debug_info.get_line_offset(6).unwrap();
+    engine.add_breakpoint(body_offset);
+
+    assert!(engine.start_fn("root::main").is_ok());
+    assert_eq!(RunState::Paused, engine.get_run_state());
+
+    let mut pauses = 0;
+    loop {
+        let run_res = engine.run_to_breakpoint();
+        println!("{:?}", run_res);
+        assert_eq!(run_res.unwrap(), engine.get_run_state());
+        if engine.get_run_state() == RunState::Halted {
+            break;
+        }
+        assert_eq!(body_offset, engine.get_ip());
+        pauses += 1;
+    }
+    assert_eq!(3, pauses);
+    assert_eq!(3, engine.get_register_value::<i64>(Register::R0).unwrap());
+
+    assert!(engine.remove_breakpoint(body_offset));
+    assert!(!engine.remove_breakpoint(body_offset));
+
+    // Without breakpoints the function runs to the end
+    assert!(engine.start_fn("root::main").is_ok());
+    assert_eq!(RunState::Halted, engine.run_to_breakpoint().unwrap());
+    assert_eq!(3, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_save_program() {
+    let code = String::from("
+        fn: twice(s: string) ~ int {
+            var n = text::length(s);
+            return n * 2;
+        }
+
+        fn: main() ~ int {
+            var s = text::make(4);
+            var n = twice(s);
+            return n + 1;
+        }
+    ");
+
+    let program_path = std::env::temp_dir().join("pgs_test_engine_save_program.pgsb");
+
+    let mut engine = ref_counting_engine(false);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+    let save_res = engine.save_program(&program_path);
+    println!("{:?}", save_res);
+    assert!(save_res.is_ok());
+
+    // Runs without compiling, binding the foreign functions of the new engine
+    let mut engine = ref_counting_engine(false);
+    let load_res = engine.load_program(&program_path);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(9, engine.get_register_value::<i64>(Register::R0).unwrap());
+
+    let mut engine = Engine::new(1024);
+    match engine.load_program(&program_path) {
+        Err(error) => {
+            match *error {
+                EngineError::UnboundForeignFunction(_) => {},
+                other => panic!("Unexpected error {:?}", other)
+            };
+        },
+        Ok(_) => panic!("Loaded a program without its foreign functions")
+    };
+
+    std::fs::write(&program_path, "fn: main() {}").unwrap();
+    match engine.load_program(&program_path) {
+        Err(error) => {
+            match *error {
+                EngineError::ProgramError(ProgramError::InvalidMagic) => {},
+                other => panic!("Unexpected error {:?}", other)
+            };
+        },
+        Ok(_) => panic!("Loaded a source file as a program")
+    };
+}
+
+#[test]
+fn test_engine_run_bytecode_file() {
+    let code = String::from("
+        fn: main() ~ int {
+            var s = text::make(3);
+            var n = text::length(s);
+            return n * 10;
+        }
+    ");
+
+    let program_path = std::env::temp_dir().join("pgs_test_engine_run_bytecode_file.pgsb");
+
+    let mut engine = ref_counting_engine(false);
+    assert!(engine.load_code(&code).is_ok());
+    assert!(engine.save_program(&program_path).is_ok());
+
+    let mut engine = ref_counting_engine(false);
+    let run_res = engine.run_bytecode_file(&program_path);
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(30, engine.get_register_value::<i64>(Register::R0).unwrap());
+    assert_eq!(Some(&1), engine.get_stats().foreign_calls.get("root::text::make"));
+    assert_eq!(Some(&1), engine.get_stats().foreign_calls.get("root::text::length"));
+}
+
+#[test]
+fn test_engine_verify_compiled_program() {
+    let code = String::from("
+        cont: Point {
+            x: float;
+            y: float;
+        }
+
+        fn: scale(p: &Point, f: float) ~ float {
+            return p.x * f + p.y;
+        }
+
+        fn: sum(values: &[int]) ~ int {
+            var total = 0;
+            for value in values {
+                total += value;
+            }
+            return total;
+        }
+
+        fn: main() ~ int {
+            var p = Point { x: 1.5, y: 2.0 };
+            var scaled = scale(&p, 2.0);
+            var values = [1, 2, 3];
+            var total = sum(values[0..]);
+            var d = 2.5d;
+            if scaled > 2.0 && total != 0 {
+                total = total % 4;
+            }
+            var s = \"text\";
+            return total + len(s) - (d as int);
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+    assert!(engine.run_fn("root::main").is_ok());
+
+    for instr in engine.compiler.get_builder().instructions.iter() {
+        assert_eq!(instr.opcode.get_operands_size(), instr.operands.len(), "{:?}", instr.opcode);
+    }
+
+    let program_path = std::env::temp_dir().join("pgs_test_engine_verify_compiled_program.pgsb");
+    assert!(engine.save_program(&program_path).is_ok());
+    let program = Program::load(&program_path).unwrap();
+    assert_eq!(Ok(()), verify(&program));
+}
+
+#[test]
+fn test_engine_error_position() {
+    let code = String::from("
+        fn: divide(a: int, b: int) ~ int {
+            var q = a / b;
+            return q;
+        }
+
+        fn: main() ~ int {
+            return divide(7, 0);
+        }
+    ");
+
+    let program_path = std::env::temp_dir().join("pgs_test_engine_error_position.pgsb");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+    assert!(engine.save_program(&program_path).is_ok());
+
+    // The line table is kept in bytecode files
+    let mut loaded_engine = Engine::new(1024);
+    let load_res = loaded_engine.load_program(&program_path);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+    std::fs::remove_file(&program_path).unwrap();
+
+    for engine in [&mut engine, &mut loaded_engine] {
+        let run_res = engine.run_fn("root::main");
+        println!("{:?}", run_res);
+        let core_error = match run_res {
+            Err(error) => {
+                match *error {
+                    EngineError::CoreError(core_error) => core_error,
+                    other => panic!("Unexpected error {:?}", other)
+                }
+            },
+            Ok(_) => panic!("Division by zero did not fail")
+        };
+        match core_error.get_cause() {
+            CoreError::DivisionByZero(_) => {},
+            other => panic!("Unexpected error {:?}", other)
+        };
+        assert_eq!(Some(SourcePosition { line: 3, column: 13 }), core_error.get_position());
+        assert!(core_error.to_string().ends_with(" at 3:13"));
+    }
+}
+
+#[test]
+fn test_engine_run_file_stack_trace() {
+    let code = String::from("
+        fn: bar(b: int) ~ int {
+            var q = 10 / b;
+            return q;
+        }
+
+        fn: foo(b: int) ~ int {
+            var r = bar(b);
+            return r + 1;
+        }
+
+        fn: main() ~ int {
+            var x = foo(0);
+            return x;
+        }
+    ");
+
+    let script_path = std::env::temp_dir().join("pgs_test_engine_run_file_stack_trace.pgs");
+    std::fs::write(&script_path, &code).unwrap();
+
+    let mut engine = Engine::new(1024);
+    let run_res = engine.run_file(&script_path);
+    println!("{:?}", run_res);
+    std::fs::remove_file(&script_path).unwrap();
+
+    let error = match run_res {
+        Err(error) => error,
+        Ok(_) => panic!("Division by zero did not fail")
+    };
+    match &*error {
+        EngineError::RuntimeError(core_error, stack_trace) => {
+            match core_error.get_cause() {
+                CoreError::DivisionByZero(_) => {},
+                other => panic!("Unexpected error {:?}", other)
+            };
+            let functions: Vec<Option<String>> = stack_trace.iter()
+                .map(|frame| frame.function.clone())
+                .collect();
+            assert_eq!(
+                vec![Some(String::from("root::bar")), Some(String::from("root::foo")), Some(String::from("root::main"))],
+                functions
+            );
+            assert_eq!(Some(3), stack_trace[0].line);
+            assert_eq!(Some(8), stack_trace[1].line);
+            assert_eq!(Some(13), stack_trace[2].line);
+        },
+        other => panic!("Unexpected error {:?}", other)
+    };
+    assert!(error.to_string().ends_with("fn main -> fn foo -> fn bar"));
+}
+
+#[test]
+fn test_engine_spawn() {
+    let code = String::from("
+        var ORDER: int = 0;
+
+        fn: worker(digit: int, count: int) {
+            var i = 0;
+            while i < count {
+                ORDER = ORDER * 10 + digit;
+                i++;
+            }
+        }
+
+        fn: main() ~ int {
+            spawn worker(1, 3);
+            spawn worker(2, 3);
+            return 7;
+        }
+
+        fn: get_order() ~ int {
+            return ORDER;
+        }
+    ");
+
+    // A slice longer than the tasks runs them one after another
+    let mut engine = EngineBuilder::new()
+        .with_task_slice(10000)
+        .build()
+        .unwrap();
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    // The return value of main is kept while the tasks run
+    assert_eq!(7, engine.get_register_value::<i64>(Register::R0).unwrap());
+    assert_eq!(0, engine.get_task_count());
+    assert!(engine.run_fn("root::get_order").is_ok());
+    assert_eq!(111222, engine.get_register_value::<i64>(Register::R0).unwrap());
+
+    // Switching after every instruction interleaves them
+    let mut engine = EngineBuilder::new()
+        .with_task_slice(1)
+        .build()
+        .unwrap();
+    assert!(engine.load_code(&code).is_ok());
+    assert!(engine.start_fn("root::main").is_ok());
+    let mut ticks = 0;
+    loop {
+        match engine.tick(10) {
+            Ok(TickState::Finished) => break,
+            Ok(_) => ticks += 1,
+            Err(error) => panic!("Unexpected error {:?}", error)
+        };
+    }
+    assert!(ticks > 1);
+    assert_eq!(7, engine.get_register_value::<i64>(Register::R0).unwrap());
+    assert!(engine.run_fn("root::get_order").is_ok());
+    let order = engine.get_register_value::<i64>(Register::R0).unwrap();
+    assert_ne!(111222, order);
+    let mut digits: Vec<char> = order.to_string().chars().collect();
+    digits.sort();
+    assert_eq!(vec!['1', '1', '1', '2', '2', '2'], digits);
+
+    // References could point into the stack of the spawning task
+    let code = String::from("
+        fn: worker(value: &int) {
+            ~value++;
+        }
+
+        fn: main() ~ int {
+            var value = 1;
+            spawn worker(&value);
+            return value;
+        }
+    ");
+    let mut engine = Engine::new(1024);
+    match engine.load_code(&code) {
+        Err(error) => {
+            match *error {
+                EngineError::CompileError(CompilerError::InvalidSpawn(_)) => {},
+                other => panic!("Unexpected error {:?}", other)
+            };
+        },
+        Ok(_) => panic!("Spawned a function taking a reference")
+    };
+}
+
+#[test]
+fn test_engine_coroutine() {
+    let code = String::from("
+        fn: counter(start: int, count: int) ~ co<int> {
+            var i = 0;
+            while i < count {
+                yield start + i;
+                i++;
+            }
+        }
+
+        fn: doubled(count: int) ~ co<int> {
+            var inner = counter(1, count);
+            var value = resume(inner);
+            while value != null {
+                yield (value as int) * 2;
+                value = resume(inner);
+            }
+        }
+
+        fn: sum(gen: co<int>) ~ int {
+            var sum = 0;
+            var value = resume(gen);
+            while value != null {
+                sum = sum + (value as int);
+                value = resume(gen);
+            }
+            // A finished coroutine keeps yielding null
+            if resume(gen) != null {
+                return -1;
+            }
+            return sum;
+        }
+
+        fn: sum_counter() ~ int {
+            return sum(counter(10, 3));
+        }
+
+        fn: sum_doubled() ~ int {
+            return sum(doubled(4));
+        }
+
+        fn: numbers() ~ co<int> {
+            yield 4;
+            yield 2;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+    let run_res = engine.run_fn("root::sum_counter");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(33, engine.get_register_value::<i64>(Register::R0).unwrap());
+    assert!(engine.run_fn("root::sum_doubled").is_ok());
+    assert_eq!(20, engine.get_register_value::<i64>(Register::R0).unwrap());
+    assert_eq!(0, engine.get_coroutine_count());
+
+    // Coroutines can be resumed from the host as well
+    let handle = engine.create_coroutine("root::numbers").unwrap();
+    assert_eq!(1, engine.get_coroutine_count());
+    assert_eq!(Some(4), engine.resume_coroutine::<i64>(handle).unwrap());
+    assert_eq!(Some(2), engine.resume_coroutine::<i64>(handle).unwrap());
+    assert_eq!(None, engine.resume_coroutine::<i64>(handle).unwrap());
+    assert_eq!(None, engine.resume_coroutine::<i64>(handle).unwrap());
+    assert_eq!(0, engine.get_coroutine_count());
+    assert!(engine.resume_coroutine::<i64>(handle + 1).is_err());
+
+    // Only coroutine functions yield
+    let code = String::from("
+        fn: main() ~ int {
+            yield 1;
+            return 2;
+        }
+    ");
+    let mut engine = Engine::new(1024);
+    match engine.load_code(&code) {
+        Err(error) => {
+            match *error {
+                EngineError::CompileError(CompilerError::InvalidYield) => {},
+                other => panic!("Unexpected error {:?}", other)
+            };
+        },
+        Ok(_) => panic!("Yielded outside of a coroutine")
+    };
+}
+
+#[test]
+fn test_engine_isolate() {
+    let code = String::from("
+        var COUNT: int = 0;
+
+        fn: bump() ~ int {
+            COUNT = COUNT + 1;
+            return COUNT;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+    assert!(engine.run_fn("root::bump").is_ok());
+    assert_eq!(1, engine.get_register_value::<i64>(Register::R0).unwrap());
+
+    // Isolates start from the statics of the program, not the ones of the engine
+    let mut first = engine.create_isolate().unwrap();
+    let mut second = engine.create_isolate().unwrap();
+    assert!(first.run_fn("root::bump").is_ok());
+    assert!(first.run_fn("root::bump").is_ok());
+    assert_eq!(2, first.get_register_value::<i64>(Register::R0).unwrap());
+    assert!(second.run_fn("root::bump").is_ok());
+    assert_eq!(1, second.get_register_value::<i64>(Register::R0).unwrap());
+    assert!(engine.run_fn("root::bump").is_ok());
+    assert_eq!(2, engine.get_register_value::<i64>(Register::R0).unwrap());
+    assert!(first.run_fn("root::missing").is_err());
+
+    assert!(first.reset().is_ok());
+    assert!(first.run_fn("root::bump").is_ok());
+    assert_eq!(1, first.get_register_value::<i64>(Register::R0).unwrap());
+
+    // Isolates of a pool run in parallel and are reset when released
+    let pool = Arc::new(engine.create_pool(2).unwrap());
+    assert_eq!(2, pool.get_size());
+    assert_eq!(2, pool.get_idle_count());
+    let handles: Vec<_> = (0..4).map(|_| {
+        let pool = pool.clone();
+        thread::spawn(move || {
+            let mut isolate = pool.acquire().unwrap();
+            for _ in 0..3 {
+                assert!(isolate.run_fn("root::bump").is_ok());
+            }
+            let count = isolate.get_register_value::<i64>(Register::R0).unwrap();
+            pool.release(isolate).unwrap();
+            count
+        })
+    }).collect();
+    for handle in handles {
+        assert_eq!(3, handle.join().unwrap());
+    }
+    assert_eq!(2, pool.get_idle_count());
+}
+
+#[test]
+fn test_engine_tuple() {
+    let code = String::from("
+        fn: divmod(a: int, b: int) ~ (int, int) {
+            return (a / b, a % b);
+        }
+
+        fn: sum(pair: (int, int)) ~ int {
+            return pair.0 + pair.1;
+        }
+
+        fn: main() ~ int {
+            var pair: (int, float) = (3, 1.5);
+            var (q, r) = divmod(17, 5);
+            var nested = ((1, true), 'c', 2.5);
+            var (inner, _, f) = nested;
+            var res = pair.0 + q * 10 + r;
+            if inner.1 && nested.0.0 == 1 && nested.1 == 'c' {
+                res += 100;
+            }
+            res += (pair.1 * 2.0) as int;
+            res += (f * 2.0) as int;
+            res += sum(divmod(9, 4)) * 1000;
+            return res;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(35 + 100 + 3 + 5 + 3000, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_tuple_destructure_mismatch() {
+    let code = String::from("
+        fn: main() ~ int {
+            var (a, b) = (1, 2, 3);
+            return a;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_err());
+}
+
+#[test]
+fn test_engine_array() {
+    let code = String::from("
+        fn: main() ~ int {
+            var a = [1, 2, 3, 4];
+            var flags: [bool; 3] = [false, true, false];
+            var grid = [[1.5, 2.5], [3.5, 4.5]];
+            var res = 0;
+            var i = 0;
+            while i < len(a) {
+                res += a[i];
+                i += 1;
+            }
+            a[0] = 10;
+            a[3] += 30;
+            res += a[0] * 10 + a[3] * 100;
+            if flags[1] && !flags[2] {
+                res += 10000;
+            }
+            res += (grid[1][0] * 2.0) as int * 100000;
+            res += [5, 6, 7][2] * 1000000;
+            return res;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(10 + 100 + 3400 + 10000 + 700000 + 7000000, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_array_out_of_bounds() {
+    let code = String::from("
+        fn: main() ~ int {
+            var a = [1, 2, 3];
+            var i = 3;
+            return a[i];
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_err());
+}
+
+#[test]
+fn test_engine_enum() {
+    let code = String::from("
+        enum: Light {
+            Red;
+            Yellow;
+            Green;
+        }
+
+        fn: next(light: Light) ~ Light {
+            var res = Light::Red;
+            match light {
+                Light::Red => {
+                    res = Light::Green;
+                },
+                Light::Green => {
+                    res = Light::Yellow;
+                },
+                _ => {}
+            }
+            return res;
+        }
+
+        fn: main() ~ int {
+            var light: Light = Light::Red;
+            var res = 0;
+            light = next(light);
+            if light == Light::Green {
+                res += 1;
+            }
+            light = next(light);
+            if light != Light::Green {
+                res += 10;
+            }
+            light = next(light);
+            res += (light as int) * 100;
+            res += (Light::Green as int) * 1000;
+            return res;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(1 + 10 + 2000, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_enum_errors() {
+    let unknown_variant = String::from("
+        enum: Light {
+            Red;
+        }
+
+        fn: main() ~ int {
+            var light = Light::Blue;
+            return 0;
+        }
+    ");
+    let int_comparison = String::from("
+        enum: Light {
+            Red;
+        }
+
+        fn: main() ~ int {
+            var res = 0;
+            if Light::Red == 0 {
+                res = 1;
+            }
+            return res;
+        }
+    ");
+
+    for code in [ unknown_variant, int_comparison ].iter() {
+        let mut engine = Engine::new(1024);
+        let load_res = engine.load_code(code);
+        println!("{:?}", load_res);
+        assert!(load_res.is_err());
+    }
+}
+
+#[test]
+fn test_engine_static_var() {
+    let code = String::from("
+        var counter = 0;
+        var name: string = \"pgs\";
+        var scale = 2.5;
+
+        mod: config {
+            var limit: int = 3 * 4;
+        }
+
+        import root::config::limit;
+
+        fn: bump(by: int) ~ int {
+            counter += by;
+            return counter;
+        }
+
+        fn: read(value: &int) ~ int {
+            return ~value;
+        }
+
+        fn: main() ~ int {
+            bump(1);
+            var res = bump(limit);
+            var via_ref = read(&counter);
+            if scale > 2.0 {
+                res += 1000;
+            }
+            res += len(name) * 100;
+            return res + via_ref * 10000;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(131313, engine.get_register_value::<i64>(Register::R0).unwrap());
+
+    // Static variables keep their value between runs
+    let run_res = engine.run_fn("root::main");
+    assert!(run_res.is_ok());
+    assert_eq!(261326, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_static_var_errors() {
+    let not_constant = String::from("
+        fn: one() ~ int {
+            return 1;
+        }
+
+        var value = one();
+    ");
+    let type_mismatch = String::from("
+        var value: int = 1.5;
+    ");
+    let duplicate = String::from("
+        var value = 1;
+        var value = 2;
+    ");
+
+    for code in [ not_constant, type_mismatch, duplicate ].iter() {
+        let mut engine = Engine::new(1024);
+        let load_res = engine.load_code(code);
+        println!("{:?}", load_res);
+        assert!(load_res.is_err());
+    }
+}
+
+#[test]
+fn test_engine_optional() {
+    let code = String::from("
+        fn: find(value: int) ~ int? {
+            if value < 0 {
+                return null;
+            }
+            return value * 2;
+        }
+
+        fn: or_default(value: int?, default: int) ~ int {
+            var res = default;
+            if value != null {
+                res = value as int;
+            }
+            return res;
+        }
+
+        fn: main() ~ int {
+            var found = find(21);
+            var missing = find(-1);
+            var name: string? = null;
+            var res = 0;
+            if missing == null {
+                res += 1;
+            }
+            if name == null {
+                name = \"pgs\";
+            }
+            res += len(name as string) * 10;
+            res += or_default(found, 0) * 100;
+            res += or_default(null, 7) * 10000;
+            return res;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(1 + 30 + 4200 + 70000, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_optional_errors() {
+    let unwrap_null = String::from("
+        fn: main() ~ int {
+            var value: int? = null;
+            return value as int;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&unwrap_null);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_err());
+
+    let null_to_int = String::from("
+        fn: main() ~ int {
+            var value: int = 0;
+            value = null;
+            return value;
+        }
+    ");
+    let inferred_null = String::from("
+        fn: main() ~ int {
+            var value = null;
+            return 0;
+        }
+    ");
+    let non_optional_null_check = String::from("
+        fn: main() ~ int {
+            var value = 1;
+            if value == null {
+                return 1;
+            }
+            return 0;
+        }
+    ");
+
+    for code in [ null_to_int, inferred_null, non_optional_null_check ].iter() {
+        let mut engine = Engine::new(1024);
+        let load_res = engine.load_code(code);
+        println!("{:?}", load_res);
+        assert!(load_res.is_err());
+    }
+}
+
+#[test]
+fn test_engine_result() {
+    let code = String::from("
+        fn: checked_div(lhs: int, rhs: int) ~ int! {
+            if rhs == 0 {
+                throw \"division by zero\";
+            }
+            return lhs / rhs;
+        }
+
+        fn: div_sum(lhs: int, rhs: int) ~ int! {
+            var quotient = checked_div(lhs, rhs)?;
+            var divisor = checked_div(rhs, 1)?;
+            return quotient + divisor;
+        }
+
+        fn: main() ~ int {
+            var res = 0;
+            var sum = div_sum(10, 2);
+            res += sum as int;
+            var failed = div_sum(1, 0) as int?;
+            if failed == null {
+                res += 100;
+            }
+            var direct: int! = 3;
+            res += (direct as int) * 1000;
+            return res;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(7 + 100 + 3000, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_result_errors() {
+    let uncaught = String::from("
+        fn: fail() ~ int! {
+            throw \"failed\";
+        }
+
+        fn: main() ~ int {
+            return fail() as int;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&uncaught);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_err());
+
+    let throw_outside_result = String::from("
+        fn: main() ~ int {
+            throw \"failed\";
+        }
+    ");
+    let try_outside_result = String::from("
+        fn: fail() ~ int! {
+            throw \"failed\";
+        }
+
+        fn: main() ~ int {
+            return fail()?;
+        }
+    ");
+    let try_non_result = String::from("
+        fn: main() ~ int! {
+            var value = 1;
+            return value?;
+        }
+    ");
+
+    for code in [ throw_outside_result, try_outside_result, try_non_result ].iter() {
+        let mut engine = Engine::new(1024);
+        let load_res = engine.load_code(code);
+        println!("{:?}", load_res);
+        assert!(load_res.is_err());
+    }
+}
+
+#[test]
+fn test_engine_lambda() {
+    let code = String::from("
+        fn: adder(n: int) ~ fn(int) ~ int {
+            return |x: int| ~ int { return x + n; };
+        }
+
+        fn: apply(f: fn(int) ~ int, value: int) ~ int {
+            return f(value);
+        }
+
+        fn: main() ~ int {
+            var double = |x: int| ~ int { return x * 2; };
+            var add3 = adder(3);
+            var factor = 10;
+            var scaled = apply(|x: int| ~ int { return x * factor; }, 5);
+            // Captures are copies, taken when the lambda is created
+            factor = 0;
+            var count = 0;
+            var bump = || { count += 1; };
+            bump();
+            var res = double(21);
+            res += add3(1) * 100;
+            res += scaled * 1000;
+            res += count;
+            return res;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(42 + 400 + 50000, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_lambda_errors() {
+    let wrong_arg_count = String::from("
+        fn: main() ~ int {
+            var f = |x: int| ~ int { return x; };
+            return f(1, 2);
+        }
+    ");
+    let break_outer_loop = String::from("
+        fn: main() ~ int {
+            while true {
+                var f = || { break; };
+                f();
+            }
+            return 0;
+        }
+    ");
+
+    for code in [ wrong_arg_count, break_outer_loop ].iter() {
+        let mut engine = Engine::new(1024);
+        let load_res = engine.load_code(code);
+        println!("{:?}", load_res);
+        assert!(load_res.is_err());
+    }
+}
+
+#[test]
+fn test_engine_generic_fn() {
+    let code = String::from("
+        fn: max<T>(a: T, b: T) ~ T {
+            if a > b {
+                return a;
+            }
+            return b;
+        }
+
+        fn: first<A, B>(pair: (A, B)) ~ A {
+            var (a, b) = pair;
+            return a;
+        }
+
+        mod: util {
+            pub fn: count_down<T>(n: int, value: T) ~ T {
+                if n == 0 {
+                    return value;
+                }
+                return count_down(n - 1, value);
+            }
+        }
+
+        fn: main() ~ int {
+            var res = max(3, 7);
+            var max_float = max(2.5, 1.5);
+            if max_float == 2.5 {
+                res += 10;
+            }
+            var other = max(-1, -2);
+            res += other * 100;
+            var first_int = first((1000, 1.5));
+            res += first_int;
+            var counted = util::count_down(3, 20000);
+            res += counted;
+            return res;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(7 + 10 - 100 + 1000 + 20000, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_generic_fn_errors() {
+    let conflicting_types = String::from("
+        fn: max<T>(a: T, b: T) ~ T {
+            return a;
+        }
+
+        fn: main() ~ int {
+            return max(1, 2.5);
+        }
+    ");
+    let uninferable_type = String::from("
+        fn: make<T>() ~ T {
+            var value: T = 0;
+            return value;
+        }
+
+        fn: main() ~ int {
+            return make();
+        }
+    ");
+    let duplicate_fn = String::from("
+        fn: id<T>(a: T) ~ T {
+            return a;
+        }
+
+        fn: id(a: int) ~ int {
+            return a;
+        }
+    ");
+
+    for code in [ conflicting_types, uninferable_type, duplicate_fn ].iter() {
+        let mut engine = Engine::new(1024);
+        let load_res = engine.load_code(code);
+        println!("{:?}", load_res);
+        assert!(load_res.is_err());
+    }
+}
+
+#[test]
+fn test_engine_generic_cont() {
+    let code = String::from("
+        cont: Box<T> {
+            value: T;
+        }
+
+        cont: Pair<A, B> {
+            first: A;
+            second: B;
+        }
+
+        cont: Holder {
+            inner: Box<int>;
+        }
+
+        fn: unbox<T>(boxed: &Box<T>) ~ T {
+            return boxed.value;
+        }
+
+        fn: main() ~ int {
+            var a: Box<int> = Box { value: 5 };
+            var f = Box { value: 2.5 };
+            var p: Pair<int, Box<int>> = Pair { first: 100, second: a };
+            var res = a.value;
+            if f.value == 2.5 {
+                res += 10;
+            }
+            res += p.first;
+            var second = &p.second;
+            res += second.value * 1000;
+            var h = Holder { inner: Box { value: 7 } };
+            var inner = &h.inner;
+            res += inner.value * 10000;
+            var unboxed = unbox(inner);
+            res += unboxed * 100000;
+            static_assert(sizeof(Box<int>) == 8);
+            static_assert(sizeof(Pair<int, Box<float>>) == 12);
+            return res;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(5 + 10 + 100 + 5000 + 70000 + 700000, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_generic_cont_errors() {
+    let missing_type_args = String::from("
+        cont: Box<T> {
+            value: T;
+        }
+
+        fn: main() ~ int {
+            var b: Box = Box { value: 1 };
+            return 0;
+        }
+    ");
+    let wrong_type_arg_count = String::from("
+        cont: Box<T> {
+            value: T;
+        }
+
+        fn: main() ~ int {
+            var b: Box<int, int> = Box { value: 1 };
+            return 0;
+        }
+    ");
+    let uninferable_type_arg = String::from("
+        cont: Tagged<T> {
+            value: int;
+        }
+
+        fn: main() ~ int {
+            var t = Tagged { value: 1 };
+            return 0;
+        }
+    ");
+    let generic_impl = String::from("
+        cont: Box<T> {
+            value: T;
+        }
+
+        impl: Box {
+            fn: get(&this) ~ int {
+                return 0;
+            }
+        }
+    ");
+
+    for code in [ missing_type_args, wrong_type_arg_count, uninferable_type_arg, generic_impl ].iter() {
+        let mut engine = Engine::new(1024);
+        let load_res = engine.load_code(code);
+        println!("{:?}", load_res);
+        assert!(load_res.is_err());
+    }
+}
+
+#[test]
+fn test_engine_iface() {
+    let code = String::from("
+        iface: Shape {
+            fn: area(&this) ~ int;
+            fn: scaled_area(&this, factor: int) ~ int;
+        }
+
+        mod: names {
+            iface: Named {
+                fn: id(&this) ~ int;
+            }
+        }
+
+        cont: Square {
+            side: int;
+        }
+
+        cont: Rect {
+            width: int;
+            height: int;
+        }
+
+        impl: Shape for Square {
+            fn: area(&this) ~ int {
+                return this.side * this.side;
+            }
+
+            fn: scaled_area(&this, factor: int) ~ int {
+                return this.area() * factor;
+            }
+        }
+
+        impl: Shape for Rect {
+            fn: area(&this) ~ int {
+                return this.width * this.height;
+            }
+
+            fn: scaled_area(&this, factor: int) ~ int {
+                return this.area() * factor;
+            }
+        }
+
+        impl: names::Named for Rect {
+            fn: id(&this) ~ int {
+                return 7;
+            }
+        }
+
+        fn: combined_area(a: &Shape, b: &Shape) ~ int {
+            var first = a.area();
+            var second = b.scaled_area(10);
+            return first + second;
+        }
+
+        fn: get_id(named: &names::Named) ~ int {
+            return named.id();
+        }
+
+        fn: main() ~ int {
+            var square = Square { side: 3 };
+            var rect = Rect { width: 2, height: 5 };
+            var shape: &Shape = &square;
+            var result = shape.area();
+            shape = &rect;
+            var rect_area = shape.area();
+            result = result + rect_area;
+            var combined = combined_area(&rect, &square);
+            result = result + combined * 100;
+            var id = get_id(&rect);
+            result = result + id * 100000;
+            return result;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(9 + 10 + (10 + 90) * 100 + 7 * 100000, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_iface_errors() {
+    let missing_function = String::from("
+        iface: Shape {
+            fn: area(&this) ~ int;
+        }
+
+        cont: Square {
+            side: int;
+        }
+
+        impl: Shape for Square {
+        }
+    ");
+    let signature_mismatch = String::from("
+        iface: Shape {
+            fn: area(&this) ~ int;
+        }
+
+        cont: Square {
+            side: int;
+        }
+
+        impl: Shape for Square {
+            fn: area(&this) ~ float {
+                return 1.0;
+            }
+        }
+    ");
+    let not_implemented = String::from("
+        iface: Shape {
+            fn: area(&this) ~ int;
+        }
+
+        cont: Square {
+            side: int;
+        }
+
+        fn: main() ~ int {
+            var square = Square { side: 3 };
+            var shape: &Shape = &square;
+            return 0;
+        }
+    ");
+    let by_value = String::from("
+        iface: Shape {
+            fn: area(&this) ~ int;
+        }
+
+        fn: area_of(shape: Shape) ~ int {
+            return 0;
+        }
+    ");
+    let unknown_function = String::from("
+        iface: Shape {
+            fn: area(&this) ~ int;
+        }
+
+        fn: perimeter_of(shape: &Shape) ~ int {
+            return shape.perimeter();
+        }
+    ");
+
+    for code in [ missing_function, signature_mismatch, not_implemented, by_value, unknown_function ].iter() {
+        let mut engine = Engine::new(1024);
+        let load_res = engine.load_code(code);
+        println!("{:?}", load_res);
+        assert!(load_res.is_err());
+    }
+}
+
+#[test]
+fn test_engine_call_function() {
+    let code = String::from("
+        fn: update(tick: int, dt: float) ~ int {
+            if dt > 0.5 {
+                return tick * 2;
+            }
+            return tick + 1;
+        }
+
+        fn: scale(x: double, twice: bool) ~ double {
+            if twice {
+                return x * 2.0;
+            }
+            return x;
+        }
+
+        fn: is_letter(c: char) ~ bool {
+            return c == 'a';
+        }
+
+        fn: next(c: char) ~ char {
+            if c == 'a' {
+                return 'b';
+            }
+            return 'z';
+        }
+
+        fn: noop() {
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    assert_eq!(20, engine.call_function::<(i64, f32), i64>("root::update", (10, 0.75)).unwrap());
+    assert_eq!(11, engine.call_function::<(i64, f32), i64>("root::update", (10, 0.25)).unwrap());
+    assert_eq!(3.0, engine.call_function::<(f64, bool), f64>("root::scale", (1.5, true)).unwrap());
+    assert_eq!(1.5, engine.call_function::<(f64, bool), f64>("root::scale", (1.5, false)).unwrap());
+    assert!(engine.call_function::<(char,), bool>("root::is_letter", ('a',)).unwrap());
+    assert!(!engine.call_function::<(char,), bool>("root::is_letter", ('q',)).unwrap());
+    assert_eq!('b', engine.call_function::<(char,), char>("root::next", ('a',)).unwrap());
+    assert!(engine.call_function::<(), ()>("root::noop", ()).is_ok());
+    // The arguments are popped again
+    assert_eq!(0, engine.get_stack_size());
+
+    let mismatches = [
+        engine.call_function::<(i64,), i64>("root::update", (10,)).map(|_| ()),
+        engine.call_function::<(i64, i64), i64>("root::update", (10, 1)).map(|_| ()),
+        engine.call_function::<(i64, f32), f32>("root::update", (10, 0.5)).map(|_| ()),
+        engine.call_function::<(i64,), ()>("root::noop", (1,))
+    ];
+    for call_res in mismatches.iter() {
+        match call_res {
+            Err(ref error) => {
+                match error.as_ref() {
+                    EngineError::SignatureMismatch(name) => assert!(name.starts_with("root::")),
+                    error => panic!("Unexpected error: {:?}", error)
+                }
+            },
+            Ok(_) => panic!("Expected a signature mismatch")
+        }
+    }
+    assert_eq!(0, engine.get_stack_size());
+
+    let mut isolate = engine.create_isolate().unwrap();
+    assert_eq!(20, isolate.call_function::<(i64, f32), i64>("root::update", (10, 0.75)).unwrap());
+}
+
+#[test]
+fn test_engine_wrapped_functions() {
+    let code = String::from("
+        fn: main() ~ int {
+            var greeting = host::greet(\"bob\", 2);
+            host::log(greeting);
+            var sum = host::add(40, 2);
+            if host::is_even(sum) && host::half(3.0) == 1.5 {
+                sum += host::len(greeting);
+            }
+            return sum;
+        }
+    ");
+
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let log_clone = log.clone();
+    let module = Module::new("host")
+        .with_function(Function::wrap("add", |a: i64, b: i64| a + b))
+        .with_function(Function::wrap("is_even", |x: i64| x % 2 == 0))
+        .with_function(Function::wrap("half", |x: f32| x / 2.0))
+        .with_function(Function::wrap("len", |s: String| s.len() as i64))
+        .with_function(Function::wrap("greet", |name: String, times: i64| {
+            format!("hi {}", name).repeat(times as usize)
+        }))
+        .with_function(Function::wrap("log", move |message: String| {
+            log_clone.lock().unwrap().push(message);
+        }));
+
+    let mut engine = Engine::new(1024);
+    assert!(engine.register_module(module).is_ok());
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    assert!(run_res.is_ok());
+    assert_eq!(42 + 12, engine.get_register_value::<i64>(Register::R0).unwrap());
+    assert_eq!(vec![String::from("hi bobhi bob")], *log.lock().unwrap());
+
+    let add = Function::wrap("add", |a: i64, b: f64| a as f64 + b);
+    assert_eq!(vec![Type::Int, Type::Double], add.arg_types);
+    assert_eq!(Type::Double, add.return_type);
+}
+
+#[test]
+fn test_engine_function_errors() {
+    let code = String::from("
+        fn: main() ~ int {
+            var sum = host::parse(\"12\");
+            sum += host::parse(\"twelve\");
+            return sum;
+        }
+
+        fn: checked() ~ int {
+            host::check(1);
+            host::check(-1);
+            return 0;
+        }
+    ");
+
+    let module = Module::new("host")
+        .with_function(Function::wrap("parse", |text: String| {
+            text.parse::<i64>()
+                .map_err(FunctionError::from_error)
+        }))
+        .with_function(Function::new("check")
+            .with_arg(Type::Int)
+            .with_ret_type(Type::Void)
+            .with_closure(Box::new(|adapter: &mut Adapter| {
+                let value: i64 = adapter.get_arg(0);
+                if value < 0 {
+                    adapter.raise(format!("{} is negative", value));
+                }
+            })));
+
+    let mut engine = Engine::new(1024);
+    assert!(engine.register_module(module).is_ok());
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let core_error = match engine.run_fn("root::main") {
+        Err(error) => {
+            match *error {
+                EngineError::CoreError(core_error) => core_error,
+                other => panic!("Unexpected error {:?}", other)
+            }
+        },
+        Ok(_) => panic!("Parsing did not fail")
+    };
+    let function_error = core_error.get_function_error().unwrap();
+    assert_eq!("parse", function_error.function);
+    assert_eq!("invalid digit found in string", function_error.message);
+    assert!(function_error.get_payload::<std::num::ParseIntError>().is_some());
+    // The call site is attached
+    assert_eq!(Some(4), core_error.get_position().map(|position| position.line));
+    assert!(core_error.to_string().starts_with("parse: invalid digit found in string at 4:"));
+    assert!(std::error::Error::source(&core_error).is_some());
+
+    match engine.run_fn("root::checked") {
+        Err(ref error) => {
+            match error.as_ref() {
+                EngineError::CoreError(ref core_error) if matches!(core_error.get_cause(), CoreError::FunctionError(..)) => {
+                    let function_error = core_error.get_function_error().unwrap();
+                    assert_eq!("check", function_error.function);
+                    assert_eq!("-1 is negative", function_error.message);
+                    assert!(function_error.payload.is_none());
+                    assert_eq!(Some(10), core_error.get_position().map(|position| position.line));
+                },
+                other => panic!("Unexpected error {:?}", other)
+            }
+        },
+        Ok(_) => panic!("The check did not fail")
+    };
+}
+
+#[test]
+fn test_engine_builder_config() {
+    let code = String::from("
+        fn: main() ~ int {
+            return host::twice(21);
+        }
+    ");
+
+    let traced = Arc::new(Mutex::new(0usize));
+    let traced_hook = traced.clone();
+    let module = Module::new("host")
+        .with_function(Function::wrap("twice", |value: i64| value * 2));
+    let mut engine = EngineBuilder::new()
+        .with_stack_size(4096)
+        .with_fuel(10000)
+        .with_max_heap(1 << 16)
+        .with_module(module.clone())
+        .with_trace_hook(Box::new(move |_entry| {
+            *traced_hook.lock().unwrap() += 1;
+        }))
+        .with_profiling(true)
+        .build()
+        .unwrap();
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    assert!(engine.run_fn("root::main").is_ok());
+    assert_eq!(42, engine.get_register_value::<i64>(Register::R0).unwrap());
+    let executed = *traced.lock().unwrap();
+    assert!(executed > 0);
+    assert_eq!(Some(10000 - executed as u64), engine.get_fuel());
+    assert!(engine.get_profile_report().is_some());
+
+    // Registering a module twice fails the build
+    let build_res = EngineBuilder::new()
+        .with_module(module.clone())
+        .with_module(module)
+        .build();
+    assert!(build_res.is_err());
+}
+
+#[test]
+fn test_engine_reload_statics() {
+    let code = String::from("
+        var counter = 0;
+        var name = \"old\";
+        var scale = 1.5;
+
+        mod: game {
+            var level = 1;
+
+            pub fn: next_level() ~ int {
+                level = level + 1;
+                return level;
+            }
+        }
+
+        fn: tick() ~ int {
+            counter = counter + 1;
+            name = \"renamed\";
+            scale = 3.0;
+            return counter;
+        }
+    ");
+    let new_code = String::from("
+        var counter = 100;
+        var name = \"new\";
+        var scale = 2;
+        var fresh = 7;
+
+        mod: game {
+            var level = 1;
+
+            pub fn: next_level() ~ int {
+                level = level + 10;
+                return level;
+            }
+        }
+
+        fn: main() ~ int {
+            return counter * 1000 + host::len(name) * 100 + scale * 10 + fresh;
+        }
+    ");
+
+    let module = Module::new("host")
+        .with_function(Function::wrap("len", |text: String| text.len() as i64));
+    let mut engine = EngineBuilder::new()
+        .with_module(module)
+        .build()
+        .unwrap();
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    assert_eq!(1, engine.call_function::<(), i64>("root::tick", ()).unwrap());
+    assert_eq!(2, engine.call_function::<(), i64>("root::tick", ()).unwrap());
+    assert_eq!(2, engine.call_function::<(), i64>("root::game::next_level", ()).unwrap());
+
+    let reload_res = engine.reload_code(&new_code);
+    println!("{:?}", reload_res);
+    let report = reload_res.unwrap();
+    assert_eq!(
+        vec![String::from("root::counter"), String::from("root::game::level"), String::from("root::name")],
+        report.preserved
+    );
+
+    // The counter and the renamed string are kept, the retyped scale and the new static are initialized
+    assert_eq!(2 * 1000 + 7 * 100 + 2 * 10 + 7, engine.call_function::<(), i64>("root::main", ()).unwrap());
+    assert_eq!(12, engine.call_function::<(), i64>("root::game::next_level", ()).unwrap());
+}
+
+#[test]
+fn test_engine_compile_shared_program() {
+    let source = "
+        var runs = 0;
+
+        fn: main() ~ int {
+            runs = runs + 1;
+            return host::scale(runs);
+        }
+    ";
+
+    let module = Module::new("host")
+        .with_function(Function::wrap("scale", |value: i64| value * 10));
+    let mut engine = EngineBuilder::new()
+        .with_module(module.clone())
+        .build()
+        .unwrap();
+    let compile_res = engine.compile(source);
+    println!("{:?}", compile_res.as_ref().err());
+    let program = compile_res.unwrap();
+    // Compiling does not load the program
+    assert!(engine.run_fn("root::main").is_err());
+
+    assert!(engine.run_program(program.clone()).is_ok());
+    assert_eq!(10, engine.get_register_value::<i64>(Register::R0).unwrap());
+    assert!(engine.run_fn("root::main").is_ok());
+    assert_eq!(20, engine.get_register_value::<i64>(Register::R0).unwrap());
+    assert_eq!(30, engine.call_function::<(), i64>("root::main", ()).unwrap());
+
+    // Every engine and isolate gets statics of its own
+    let mut other_engine = EngineBuilder::new()
+        .with_module(module)
+        .build()
+        .unwrap();
+    assert!(other_engine.run_program(program.clone()).is_ok());
+    assert_eq!(10, other_engine.get_register_value::<i64>(Register::R0).unwrap());
+    let mut isolate = other_engine.create_isolate().unwrap();
+    assert!(isolate.run_fn("root::main").is_ok());
+    assert_eq!(10, isolate.get_register_value::<i64>(Register::R0).unwrap());
+
+    // Loading the program again resets its statics
+    assert!(engine.load_shared_program(program).is_ok());
+    assert!(engine.run_fn("root::main").is_ok());
+    assert_eq!(10, engine.get_register_value::<i64>(Register::R0).unwrap());
+
+    assert!(engine.compile("fn: main( {").is_err());
+    assert!(engine.run_source("fn: main() ~ int { return 5; }").is_ok());
+    assert_eq!(5, engine.get_register_value::<i64>(Register::R0).unwrap());
+}
+
+#[test]
+fn test_engine_globals() {
+    let code = String::from("
+        var difficulty = 1;
+        var speed = 1.5;
+        var hardcore = false;
+        var title = \"easy\";
+
+        mod: game {
+            var level = 1;
+        }
+
+        fn: main() ~ int {
+            var bonus = 0;
+            if hardcore {
+                bonus = 100;
+            }
+            return difficulty * 10 + game::level + bonus;
+        }
+    ");
+
+    let module = Module::new("host")
+        .with_constant("LIMIT", 5i64);
+    let mut engine = EngineBuilder::new()
+        .with_module(module)
+        .build()
+        .unwrap();
+    assert!(engine.get_global::<i64>("difficulty").is_err());
+
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+    assert_eq!(1, engine.get_global::<i64>("difficulty").unwrap());
+    assert_eq!(1, engine.get_global::<i64>("root::game::level").unwrap());
+    assert_eq!(String::from("easy"), engine.get_global::<String>("title").unwrap());
+    assert_eq!(5, engine.get_global::<i64>("host::LIMIT").unwrap());
+    assert_eq!(11, engine.call_function::<(), i64>("root::main", ()).unwrap());
+
+    assert!(engine.set_global("difficulty", 3i64).is_ok());
+    assert!(engine.set_global("root::game::level", 4i64).is_ok());
+    assert!(engine.set_global("hardcore", true).is_ok());
+    assert!(engine.set_global("title", String::from("nightmare")).is_ok());
+    assert_eq!(134, engine.call_function::<(), i64>("root::main", ()).unwrap());
+    assert_eq!(3, engine.get_global::<i64>("difficulty").unwrap());
+    assert!(engine.get_global::<bool>("hardcore").unwrap());
+    assert_eq!(String::from("nightmare"), engine.get_global::<String>("title").unwrap());
+
+    assert_eq!(1.5, engine.get_global::<f32>("speed").unwrap());
+    assert!(engine.set_global("speed", 2.5f32).is_ok());
+    assert_eq!(2.5, engine.get_global::<f32>("speed").unwrap());
+
+    match *engine.get_global::<i64>("unknown").unwrap_err() {
+        EngineError::UnknownGlobal(name) => assert_eq!("unknown", name),
+        err => panic!("unexpected error {:?}", err)
+    };
+    match *engine.set_global("difficulty", 2.0f32).unwrap_err() {
+        EngineError::GlobalTypeMismatch(name, var_type) => {
+            assert_eq!("difficulty", name);
+            assert_eq!(Type::Int, var_type);
+        },
+        err => panic!("unexpected error {:?}", err)
+    };
+    match *engine.set_global("host::LIMIT", 6i64).unwrap_err() {
+        EngineError::AssignToConstantGlobal(name) => assert_eq!("host::LIMIT", name),
+        err => panic!("unexpected error {:?}", err)
+    };
+
+    // Saved programs keep their globals table
+    let program_path = std::env::temp_dir().join("pgs_test_engine_globals.pgsb");
+    assert!(engine.save_program(&program_path).is_ok());
+    let mut engine = EngineBuilder::new()
+        .with_module(Module::new("host").with_constant("LIMIT", 5i64))
+        .build()
+        .unwrap();
+    let load_res = engine.load_program(&program_path);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+    assert_eq!(1, engine.get_global::<i64>("difficulty").unwrap());
+    assert!(engine.set_global("difficulty", 2i64).is_ok());
+    assert_eq!(21, engine.call_function::<(), i64>("root::main", ()).unwrap());
+    let _ = std::fs::remove_file(&program_path);
+}
+
+#[derive(Debug, PartialEq)]
+struct GameState {
+    health: i64,
+    log: Vec<String>
+}
+
+#[test]
+fn test_engine_context() {
+    let code = String::from("
+        fn: main() ~ int {
+            game::log(\"hit\");
+            game::damage(3);
+            game::damage(4);
+            return game::health();
+        }
+    ");
+
+    let module = Module::new("game")
+        .with_function(Function::wrap_with_context("damage", |game: &mut GameState, amount: i64| {
+            game.health -= amount;
+        }))
+        .with_function(Function::wrap_with_context("log", |game: &mut GameState, message: String| {
+            game.log.push(message);
+        }))
+        .with_function(Function::new("health")
+            .with_ret_type(Type::Int)
+            .with_closure(Box::new(|adapter: &mut Adapter| {
+                let health = adapter.get_context_mut::<GameState>()
+                    .map(|game| game.health)
+                    .unwrap_or(-1);
+                adapter.return_value(health);
+            })));
+    let mut engine = EngineBuilder::new()
+        .with_module(module)
+        .with_context(GameState { health: 20, log: Vec::new() })
+        .build()
+        .unwrap();
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    assert_eq!(13, engine.call_function::<(), i64>("root::main", ()).unwrap());
+    assert_eq!(13, engine.get_context::<GameState>().unwrap().health);
+    engine.get_context_mut::<GameState>().unwrap().health = 100;
+    assert_eq!(93, engine.call_function::<(), i64>("root::main", ()).unwrap());
+
+    let replaced = engine.set_context(GameState { health: 10, log: Vec::new() });
+    assert_eq!(Some(GameState { health: 93, log: vec![String::from("hit"), String::from("hit")] }), replaced);
+    assert_eq!(3, engine.call_function::<(), i64>("root::main", ()).unwrap());
+
+    // Isolates have contexts of their own, kept across resets
+    let mut isolate = engine.create_isolate().unwrap();
+    let run_res = isolate.run_fn("root::main");
+    println!("{:?}", run_res);
+    match *run_res.unwrap_err() {
+        EngineError::CoreError(error) => assert!(matches!(error.get_cause(), CoreError::MissingContext(_))),
+        err => panic!("unexpected error {:?}", err)
+    };
+    isolate.set_context(GameState { health: 50, log: Vec::new() });
+    assert!(isolate.reset().is_ok());
+    assert_eq!(43, isolate.call_function::<(), i64>("root::main", ()).unwrap());
+    assert_eq!(43, isolate.take_context::<GameState>().unwrap().health);
+    assert_eq!(3, engine.get_context::<GameState>().unwrap().health);
+
+    assert_eq!(3, engine.take_context::<GameState>().unwrap().health);
+    assert!(engine.get_context::<GameState>().is_none());
+    assert!(engine.call_function::<(), i64>("root::main", ()).is_err());
+}
+
+#[test]
+fn test_engine_link_programs() {
+    let lib_code = "
+        fn: one() ~ int {
+            var make = || ~ int { return 1; };
+            return make();
+        }
+
+        mod: lib {
+            var prefix = \"lib\";
+            var calls = 0;
+
+            pub fn: add(a: int, b: int) ~ int {
+                calls = calls + 1;
+                var twice = |x: int| ~ int { return x * 2; };
+                var sum = twice(a + b);
+                return sum + host::length(prefix) + calls * 1000;
+            }
+        }
+    ";
+    let app_code = "
+        var title = \"application\";
+
+        fn: main() ~ int {
+            var shift = |x: int| ~ int { return x * 100000; };
+            var sum = lib::add(1, 2);
+            return sum + shift(host::length(title));
+        }
+    ";
+
+    let host = Module::new("host")
+        .with_function(Function::wrap("length", |text: String| text.len() as i64));
+    // Functions of other programs are declared without a closure
+    let imports = Module::new("lib")
+        .with_function(Function::new("add")
+            .with_arg(Type::Int)
+            .with_arg(Type::Int)
+            .with_ret_type(Type::Int));
+
+    let lib_engine = EngineBuilder::new()
+        .with_module(host.clone())
+        .build()
+        .unwrap();
+    let lib_res = lib_engine.compile(lib_code);
+    println!("{:?}", lib_res.as_ref().err());
+    let lib_program = lib_res.unwrap();
+
+    let mut engine = EngineBuilder::new()
+        .with_module(host)
+        .with_module(imports)
+        .build()
+        .unwrap();
+    let app_res = engine.compile(app_code);
+    println!("{:?}", app_res.as_ref().err());
+    let app_program = app_res.unwrap();
+
+    let link_res = engine.link(&[lib_program.clone(), app_program.clone()]);
+    println!("{:?}", link_res.as_ref().err());
+    let program = link_res.unwrap();
+    assert_eq!(lib_program.data_size + app_program.data_size, program.data_size);
+    assert!(program.function_uids.contains_key("root::lib::add"));
+    // Lambdas of the application get its index appended
+    assert!(program.function_uids.contains_key("root::lambda#0"));
+    assert!(program.function_uids.contains_key("root::lambda#0#1"));
+    assert!(program.function_uids.contains_key("root::lib::lambda#1"));
+
+    assert!(engine.load_shared_program(program.clone()).is_ok());
+    assert_eq!(11 * 100000 + 1000 + 6 + 3, engine.call_function::<(), i64>("root::main", ()).unwrap());
+    assert_eq!(11 * 100000 + 2000 + 6 + 3, engine.call_function::<(), i64>("root::main", ()).unwrap());
+    assert_eq!(2, engine.get_global::<i64>("root::lib::calls").unwrap());
+    assert_eq!(String::from("application"), engine.get_global::<String>("title").unwrap());
+
+    // Linked programs are saved and loaded like any other
+    let program_path = std::env::temp_dir().join("pgs_test_engine_link_programs.pgsb");
+    assert!(program.save(&program_path).is_ok());
+    let load_res = engine.load_program(&program_path);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+    assert_eq!(11 * 100000 + 1000 + 6 + 3, engine.call_function::<(), i64>("root::main", ()).unwrap());
+    let _ = std::fs::remove_file(&program_path);
+
+    let link_res = engine.link(&[app_program.clone(), app_program.clone()]);
+    assert!(matches!(*link_res.unwrap_err(), EngineError::ProgramError(ProgramError::DuplicateFunction(_))));
+    // Imports nothing defines stay unbound
+    match *engine.link(&[app_program]).unwrap_err() {
+        EngineError::UnboundForeignFunction(name) => assert_eq!("root::lib::add", name),
+        err => panic!("unexpected error {:?}", err)
+    };
+}
+
+#[test]
+fn test_engine_block_outside_tick() {
+    let code = String::from("
+        fn: main() ~ int {
+            var ready = std::wait_ready(1);
+            return ready + std::wait_ready(2);
+        }
+    ");
+
+    let polls = Arc::new(Mutex::new(0));
+    let polls_fn = polls.clone();
+    let wait_function = Function::new("wait_ready")
+        .with_arg(Type::Int)
+        .with_ret_type(Type::Int)
+        .with_closure(Box::new(move |adapter: &mut Adapter| {
+            let value: i64 = adapter.get_arg(0);
+            *polls_fn.lock().unwrap() += 1;
+            if value == 2 {
+                adapter.block();
+            } else {
+                adapter.return_value(value);
+            }
+        }));
+    let module = Module::new("std")
+        .with_function(wait_function);
+
+    let mut engine = Engine::new(1024);
+    assert!(engine.register_module(module).is_ok());
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    // Nothing resumes a run, so blocking fails it instead of repeating the call
+    let run_res = engine.run_fn("root::main");
+    println!("{:?}", run_res);
+    match *run_res.unwrap_err() {
+        EngineError::CoreError(error) => assert!(matches!(error.get_cause(), CoreError::BlockedOutsideTick)),
+        err => panic!("unexpected error {:?}", err)
+    };
+    assert_eq!(2, *polls.lock().unwrap());
+
+    // Later calls are not affected
+    let start_res = engine.start_fn("root::main");
+    assert!(start_res.is_ok());
+    assert_eq!(TickState::Blocked, engine.tick(100).unwrap());
+    assert_eq!(4, *polls.lock().unwrap());
+    assert_eq!(TickState::Blocked, engine.tick(100).unwrap());
+    assert_eq!(5, *polls.lock().unwrap());
+}
+
+#[test]
+fn test_engine_error_position_after_for_in() {
+    let code = String::from("
+        fn: main(b: int) ~ int {
+            var s = 0;
+            for i in 0..3 {
+                s += i;
+            }
+            for x in [1, 2] {
+                s += x;
+            }
+            var q = s / b;
+            return q;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    let run_res = engine.call_function::<(i64,), i64>("root::main", (0,));
+    println!("{:?}", run_res);
+    let core_error = match run_res {
+        Err(error) => {
+            match *error {
+                EngineError::CoreError(core_error) => core_error,
+                other => panic!("Unexpected error {:?}", other)
+            }
+        },
+        Ok(_) => panic!("Division by zero did not fail")
+    };
+    match core_error.get_cause() {
+        CoreError::DivisionByZero(_) => {},
+        other => panic!("Unexpected error {:?}", other)
+    };
+    assert_eq!(Some(SourcePosition { line: 10, column: 13 }), core_error.get_position());
+}
+
+#[test]
+fn test_engine_error_position_in_generic_fn() {
+    let code = String::from("
+        fn: div<T>(a: T, b: T) ~ T {
+            var q = a / b;
+            return q;
+        }
+
+        fn: main(b: int, direct: bool) ~ int {
+            var s = div(10, 5);
+            var f = div(1.5, 0.5);
+            if direct {
+                return s / b;
+            }
+            return div(s, b);
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    // Generic bodies are compiled once per instance, each with the positions of the generic function
+    let expected_positions = [
+        (true, SourcePosition { line: 11, column: 17 }),
+        (false, SourcePosition { line: 3, column: 13 })
+    ];
+    for (direct, expected_position) in expected_positions {
+        let run_res = engine.call_function::<(i64, bool), i64>("root::main", (0, direct));
+        println!("{:?}", run_res);
+        let core_error = match run_res {
+            Err(error) => {
+                match *error {
+                    EngineError::CoreError(core_error) => core_error,
+                    other => panic!("Unexpected error {:?}", other)
+                }
+            },
+            Ok(_) => panic!("Division by zero did not fail")
+        };
+        assert_eq!(Some(expected_position), core_error.get_position());
+    }
+}
+
+#[test]
+fn test_engine_reload_while_running() {
+    let code = String::from("
+        var calls = 0;
+
+        fn: step(i: int) ~ int {
+            calls = calls + 1;
+            return i;
+        }
+
+        fn: main() ~ int {
+            var sum = 0;
+            var i = 0;
+            while i < 100 {
+                var value = step(i);
+                sum += value;
+                i += 1;
+            }
+            return sum;
+        }
+    ");
+    let new_code = String::from("
+        var calls = 0;
+
+        fn: step(i: int) ~ int {
+            calls = calls + 1;
+            return i + 1000;
+        }
+
+        fn: main() ~ int {
+            return calls;
+        }
+    ");
+
+    let mut engine = Engine::new(1024);
+    let load_res = engine.load_code(&code);
+    println!("{:?}", load_res);
+    assert!(load_res.is_ok());
+
+    // The running main finishes in the old code, calling the new step from then on
+    assert!(engine.start_fn("root::main").is_ok());
+    assert_eq!(TickState::Yielded, engine.tick(100).unwrap());
+    let reload_res = engine.reload_code(&new_code);
+    println!("{:?}", reload_res);
+    assert_eq!(vec![String::from("root::calls")], reload_res.unwrap().preserved);
+    while engine.tick(100).unwrap() != TickState::Finished {}
+    let sum = engine.get_register_value::<i64>(Register::R0).unwrap();
+    assert!(sum > 4950);
+    assert_eq!(950, sum % 1000);
+
+    // Both versions of step counted their calls in the same static
+    assert_eq!(100, engine.call_function::<(), i64>("root::main", ()).unwrap());
+    assert_eq!(100, engine.get_global::<i64>("calls").unwrap());
+
+    // Once the run finished, the engine is idle again and the old code is dropped
+    assert!(engine.reload_code(&new_code).is_ok());
+    assert_eq!(100, engine.call_function::<(), i64>("root::main", ()).unwrap());
+}
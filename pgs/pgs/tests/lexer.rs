@@ -22,6 +22,24 @@ fn test_lex_comment() {
     assert_eq!(lexer.token, Token::Text);
 }
 
+#[test]
+fn test_lex_comment_ends_at_terminator() {
+    let mut lexer = Token::lexer("// comment\nfn: main /* inline */; /* block\n comment */ x");
+
+    assert_eq!(lexer.token, Token::Fn);
+    lexer.advance();
+    assert_eq!(lexer.token, Token::Colon);
+    lexer.advance();
+    assert_eq!(lexer.token, Token::Text);
+    lexer.advance();
+    assert_eq!(lexer.token, Token::Semicolon);
+    lexer.advance();
+    assert_eq!(lexer.token, Token::Text);
+    assert_eq!(lexer.slice(), "x");
+    lexer.advance();
+    assert_eq!(lexer.token, Token::End);
+}
+
 #[test]
 fn test_lex_string_literal() {
     let lexer = Token::lexer("\"This is a string literal.\"");
@@ -100,4 +118,22 @@ fn test_lex_weird_mod_name() {
     lexer.advance();
     assert_eq!(lexer.token, Token::Text);
     lexer.advance();
-}
\ No newline at end of file
+}
+#[test]
+fn test_lex_float_literals() {
+    let mut lexer = Token::lexer("1.5e3 2E-2f 1e+3d 0x1e3");
+
+    assert_eq!(lexer.token, Token::FloatLiteral);
+    assert_eq!(lexer.slice(), "1.5e3");
+    lexer.advance();
+    assert_eq!(lexer.token, Token::FloatLiteral);
+    assert_eq!(lexer.slice(), "2E-2f");
+    lexer.advance();
+    assert_eq!(lexer.token, Token::DoubleLiteral);
+    assert_eq!(lexer.slice(), "1e+3d");
+    lexer.advance();
+    assert_eq!(lexer.token, Token::IntLiteral);
+    assert_eq!(lexer.slice(), "0x1e3");
+    lexer.advance();
+    assert_eq!(lexer.token, Token::End);
+}
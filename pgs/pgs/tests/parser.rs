@@ -30,6 +30,52 @@ fn test_parse_import_decl() {
     }
 }
 
+#[test]
+fn test_parse_file_import() {
+    let code = String::from("
+        import \"lib/utils.pgs\";
+        import \"lib/math.pgs\" = m;
+    ");
+
+    let parser = Parser::new(code);
+    let decl_list_res = parser.parse_root_decl_list();
+    assert!(decl_list_res.is_ok());
+
+    assert_eq!(vec![
+        Declaration::FileImport(String::from("lib/utils.pgs"), String::from("utils")),
+        Declaration::FileImport(String::from("lib/math.pgs"), String::from("m"))
+    ], decl_list_res.unwrap());
+
+    // The module name can not be taken from the file name
+    let parser = Parser::new(String::from("
+        import \"my-utils.pgs\";
+    "));
+    assert!(parser.parse_root_decl_list().is_err());
+}
+
+#[test]
+fn test_parse_module_file() {
+    let code = String::from("
+        mod: shapes;
+        pub mod: util;
+        mod: inline {
+            mod: nested;
+        }
+    ");
+
+    let parser = Parser::new(code);
+    let decl_list_res = parser.parse_root_decl_list();
+    assert!(decl_list_res.is_ok());
+
+    assert_eq!(vec![
+        Declaration::ModuleFile(String::from("shapes")),
+        Declaration::PubModuleFile(String::from("util")),
+        Declaration::Module(String::from("inline"), vec![
+            Declaration::ModuleFile(String::from("nested"))
+        ])
+    ], decl_list_res.unwrap());
+}
+
 #[test]
 fn test_parse_multi_import() {
     let code = String::from("
@@ -491,6 +537,126 @@ fn test_parse_loop() {
     }
 }
 
+#[test]
+fn test_parse_do_while() {
+    let code = String::from("
+        loop {
+            x += 1;
+        } while x < 10;
+    ");
+
+    let parser = Parser::new(code.clone());
+    let mut lexer = Token::lexer(code.as_str());
+    let stmt_res = parser.parse_loop(&mut lexer);
+    assert!(stmt_res.is_ok());
+
+    let stmt_list = vec![
+        Statement::Expression(Expression::AddAssign(
+            Box::new(Expression::Variable(String::from("x"))),
+            Box::new(Expression::IntLiteral(1))
+        ))
+    ];
+    let while_expr = Expression::LessThan(
+        Box::new(Expression::Variable(String::from("x"))),
+        Box::new(Expression::IntLiteral(10))
+    );
+    assert_eq!(Statement::DoWhile(stmt_list, Box::new(while_expr)), stmt_res.unwrap());
+}
+
+#[test]
+fn test_parse_increment() {
+    let code = String::from("
+        x++;
+        vec.len--;
+    ");
+
+    let parser = Parser::new(code.clone());
+    let mut lexer = Token::lexer(code.as_str());
+    let stmt_list_res = parser.parse_statement_list(&mut lexer);
+    assert!(stmt_list_res.is_ok());
+
+    let member_expr = Expression::MemberAccess(
+        Box::new(Expression::Variable(String::from("vec"))),
+        Box::new(Expression::Variable(String::from("len")))
+    );
+    assert_eq!(vec![
+        Statement::Increment(Expression::Variable(String::from("x")), 1),
+        Statement::Increment(member_expr, -1)
+    ], stmt_list_res.unwrap());
+
+    let code = String::from("
+        var y = x++;
+    ");
+    let mut lexer = Token::lexer(code.as_str());
+    assert!(parser.parse_statement_list(&mut lexer).is_err());
+}
+
+#[test]
+fn test_parse_conditional_expr() {
+    let code = String::from("
+        x = if a { 1 } else if b { 2 } else { 3 };
+    ");
+    let mut lexer = Token::lexer(code.as_str());
+    let parser = Parser::new(code.clone());
+
+    let expr_res = parser.parse_expr(&mut lexer, &[Token::Semicolon]);
+    assert!(expr_res.is_ok());
+
+    let else_if_expr = Expression::Conditional(
+        Box::new(Expression::Variable(String::from("b"))),
+        Box::new(Expression::IntLiteral(2)),
+        Box::new(Expression::IntLiteral(3))
+    );
+    let cond_expr = Expression::Conditional(
+        Box::new(Expression::Variable(String::from("a"))),
+        Box::new(Expression::IntLiteral(1)),
+        Box::new(else_if_expr)
+    );
+    assert_eq!(
+        Expression::Assign(Box::new(Expression::Variable(String::from("x"))), Box::new(cond_expr)),
+        expr_res.unwrap()
+    );
+
+    let code = String::from("
+        x = if a { 1 };
+    ");
+    let mut lexer = Token::lexer(code.as_str());
+    assert!(parser.parse_expr(&mut lexer, &[Token::Semicolon]).is_err());
+}
+
+#[test]
+fn test_parse_block_expr() {
+    let code = String::from("
+        x = { var y = 2; y * 3 };
+    ");
+    let mut lexer = Token::lexer(code.as_str());
+    let parser = Parser::new(code.clone());
+
+    let expr_res = parser.parse_expr(&mut lexer, &[Token::Semicolon]);
+    assert!(expr_res.is_ok());
+
+    let var_decl = Statement::VariableDecl(VariableDeclArgs {
+        var_type: Type::Auto,
+        name: String::from("y"),
+        assignment: Box::new(Expression::IntLiteral(2))
+    });
+    let value_expr = Expression::Multiplication(
+        Box::new(Expression::Variable(String::from("y"))),
+        Box::new(Expression::IntLiteral(3))
+    );
+    let block_expr = Expression::Block(vec![var_decl], Box::new(value_expr));
+    assert_eq!(
+        Expression::Assign(Box::new(Expression::Variable(String::from("x"))), Box::new(block_expr)),
+        expr_res.unwrap()
+    );
+
+    let code = String::from("
+        x = { y = 2; };
+    ");
+    let mut lexer = Token::lexer(code.as_str());
+    assert!(parser.parse_expr(&mut lexer, &[Token::Semicolon]).is_err());
+}
+
 #[test]
 fn test_parse_if() {
     let code = String::from("
@@ -571,6 +737,294 @@ fn test_parse_if_else_if_else() {
     println!("{:?}", stmt_res.unwrap());
 }
 
+#[test]
+fn test_parse_match() {
+    let code = String::from("
+        match x {
+            1 => {
+                y = 2;
+            },
+            2 => {}
+            _ => {
+                y = 0;
+            }
+        }
+    ");
+
+    let parser = Parser::new(code.clone());
+    let mut lexer = Token::lexer(code.as_str());
+    let stmt_res = parser.parse_match(&mut lexer);
+    assert!(stmt_res.is_ok());
+
+    let match_stmt_args = match stmt_res.unwrap() {
+        Statement::Match(match_stmt_args) => match_stmt_args,
+        _ => panic!("Expected a match statement!")
+    };
+    assert_eq!(match_stmt_args.match_expr, Expression::Variable(String::from("x")));
+    assert_eq!(match_stmt_args.arms.len(), 2);
+    assert_eq!(match_stmt_args.arms[0].0, Expression::IntLiteral(1));
+    assert_eq!(match_stmt_args.arms[1].1, Vec::new());
+    assert!(match_stmt_args.default_block.is_some());
+}
+
+#[test]
+fn test_parse_match_arm_after_default() {
+    let code = String::from("
+        match x {
+            _ => {}
+            1 => {}
+        }
+    ");
+
+    let parser = Parser::new(code.clone());
+    let mut lexer = Token::lexer(code.as_str());
+    let stmt_res = parser.parse_match(&mut lexer);
+    assert!(stmt_res.is_err());
+}
+
+#[test]
+fn test_parse_logical_precedence() {
+    let code = String::from("
+        x = a || b && c == 1;
+    ");
+
+    let parser = Parser::new(code.clone());
+    let mut lexer = Token::lexer(code.as_str());
+
+    let expr_res = parser.parse_expr(&mut lexer, &[ Token::Semicolon ]);
+    assert!(expr_res.is_ok());
+
+    let var = |name: &str| Box::new(Expression::Variable(String::from(name)));
+    let expected = Expression::Assign(
+        var("x"),
+        Box::new(Expression::Or(
+            var("a"),
+            Box::new(Expression::And(
+                var("b"),
+                Box::new(Expression::Equals(var("c"), Box::new(Expression::IntLiteral(1))))
+            ))
+        ))
+    );
+    assert_eq!(expr_res.unwrap(), expected);
+}
+
+#[test]
+fn test_parse_modulo() {
+    let code = String::from("
+        a * b % 4;
+    ");
+
+    let parser = Parser::new(code.clone());
+    let mut lexer = Token::lexer(code.as_str());
+
+    let expr_res = parser.parse_expr(&mut lexer, &[ Token::Semicolon ]);
+    assert!(expr_res.is_ok());
+
+    let expected = Expression::Modulo(
+        Box::new(Expression::Multiplication(
+            Box::new(Expression::Variable(String::from("a"))),
+            Box::new(Expression::Variable(String::from("b")))
+        )),
+        Box::new(Expression::IntLiteral(4))
+    );
+    assert_eq!(expr_res.unwrap(), expected);
+}
+
+#[test]
+fn test_parse_negation() {
+    let code = String::from("
+        a - -b * (c) - 2;
+    ");
+
+    let parser = Parser::new(code.clone());
+    let mut lexer = Token::lexer(code.as_str());
+
+    let expr_res = parser.parse_expr(&mut lexer, &[ Token::Semicolon ]);
+    assert!(expr_res.is_ok());
+
+    let var = |name: &str| Box::new(Expression::Variable(String::from(name)));
+    let expected = Expression::Subtraction(
+        Box::new(Expression::Subtraction(
+            var("a"),
+            Box::new(Expression::Multiplication(
+                Box::new(Expression::Negation(var("b"))),
+                var("c")
+            ))
+        )),
+        Box::new(Expression::IntLiteral(2))
+    );
+    assert_eq!(expr_res.unwrap(), expected);
+}
+
+#[test]
+fn test_parse_cast() {
+    let code = String::from("
+        -a as float * b + (c + 1) as float;
+    ");
+
+    let parser = Parser::new(code.clone());
+    let mut lexer = Token::lexer(code.as_str());
+
+    let expr_res = parser.parse_expr(&mut lexer, &[ Token::Semicolon ]);
+    assert!(expr_res.is_ok());
+
+    let var = |name: &str| Box::new(Expression::Variable(String::from(name)));
+    let expected = Expression::Addition(
+        Box::new(Expression::Multiplication(
+            Box::new(Expression::Cast(
+                Box::new(Expression::Negation(var("a"))),
+                Type::Float
+            )),
+            var("b")
+        )),
+        Box::new(Expression::Cast(
+            Box::new(Expression::Addition(var("c"), Box::new(Expression::IntLiteral(1)))),
+            Type::Float
+        ))
+    );
+    assert_eq!(expr_res.unwrap(), expected);
+}
+
+#[test]
+fn test_parse_radix_int_literals() {
+    let code = String::from("0xFf; 0b1010; 0o17; 0xFFFFFFFFFFFFFFFF;");
+
+    let parser = Parser::new(code.clone());
+    let mut lexer = Token::lexer(code.as_str());
+
+    let expected = [255, 10, 15, -1];
+    for int in expected.iter() {
+        let expr_res = parser.parse_expr(&mut lexer, &[ Token::Semicolon ]);
+        assert!(expr_res.is_ok());
+        assert_eq!(expr_res.unwrap(), Expression::IntLiteral(*int));
+        // Swallow ";"
+        lexer.advance();
+    }
+}
+
+#[test]
+fn test_parse_float_literals() {
+    let code = String::from("1.5d; 2.5f; 0.1; 0.1d as double; 1.5e3; 2e-2f; 1E+2d;");
+
+    let parser = Parser::new(code.clone());
+    let mut lexer = Token::lexer(code.as_str());
+
+    let expected = [
+        Expression::DoubleLiteral(1.5),
+        Expression::FloatLiteral(2.5),
+        Expression::FloatLiteral(0.1),
+        Expression::Cast(Box::new(Expression::DoubleLiteral(0.1)), Type::Double),
+        Expression::FloatLiteral(1500.0),
+        Expression::FloatLiteral(0.02),
+        Expression::DoubleLiteral(100.0)
+    ];
+    for expr in expected.iter() {
+        let expr_res = parser.parse_expr(&mut lexer, &[ Token::Semicolon ]);
+        assert!(expr_res.is_ok());
+        assert_eq!(&expr_res.unwrap(), expr);
+        // Swallow ";"
+        lexer.advance();
+    }
+}
+
+#[test]
+fn test_parse_hex_array_size() {
+    let code = String::from("[int; 0x10]");
+
+    let parser = Parser::new(code.clone());
+    let mut lexer = Token::lexer(code.as_str());
+
+    let type_res = parser.parse_type(&mut lexer);
+    assert!(type_res.is_ok());
+    assert_eq!(type_res.unwrap(), Type::Array(Box::new(Type::Int), 16));
+}
+
+#[test]
+fn test_parse_char_literals() {
+    let code = String::from("'a'; '\\n'; '\\''; '\\\\'; ';';");
+
+    let parser = Parser::new(code.clone());
+    let mut lexer = Token::lexer(code.as_str());
+
+    let expected = ['a', '\n', '\'', '\\', ';'];
+    for character in expected.iter() {
+        let expr_res = parser.parse_expr(&mut lexer, &[ Token::Semicolon ]);
+        assert!(expr_res.is_ok());
+        assert_eq!(expr_res.unwrap(), Expression::CharLiteral(*character));
+        // Swallow ";"
+        lexer.advance();
+    }
+}
+
+#[test]
+fn test_parse_tuple_type() {
+    let code = String::from("(int, (bool, float), &string)");
+
+    let parser = Parser::new(code.clone());
+    let mut lexer = Token::lexer(code.as_str());
+
+    let type_res = parser.parse_type(&mut lexer);
+    assert!(type_res.is_ok());
+    let expected = Type::Tuple(vec![
+        Type::Int,
+        Type::Tuple(vec![ Type::Bool, Type::Float ]),
+        Type::Reference(Box::new(Type::String))
+    ]);
+    assert_eq!(type_res.unwrap(), expected);
+}
+
+#[test]
+fn test_parse_tuple_expr() {
+    let code = String::from("
+        (a, (b + 1) * 2, (c,)).1 + pair.0.1;
+    ");
+
+    let parser = Parser::new(code.clone());
+    let mut lexer = Token::lexer(code.as_str());
+
+    let expr_res = parser.parse_expr(&mut lexer, &[ Token::Semicolon ]);
+    assert!(expr_res.is_ok());
+
+    let var = |name: &str| Box::new(Expression::Variable(String::from(name)));
+    let tuple = Expression::Tuple(vec![
+        Expression::Variable(String::from("a")),
+        Expression::Multiplication(
+            Box::new(Expression::Addition(var("b"), Box::new(Expression::IntLiteral(1)))),
+            Box::new(Expression::IntLiteral(2))
+        ),
+        Expression::Tuple(vec![ Expression::Variable(String::from("c")) ])
+    ]);
+    let expected = Expression::Addition(
+        Box::new(Expression::TupleIndex(Box::new(tuple), 1)),
+        Box::new(Expression::TupleIndex(
+            Box::new(Expression::TupleIndex(var("pair"), 0)),
+            1
+        ))
+    );
+    assert_eq!(expr_res.unwrap(), expected);
+}
+
+#[test]
+fn test_parse_destructure_decl() {
+    let code = String::from("
+        var (x, _, y): (int, bool, float) = t;
+    ");
+
+    let mut lexer = Token::lexer(code.as_str());
+    let parser = Parser::new(code.clone());
+    let stmt_list_res = parser.parse_statement_list(&mut lexer);
+
+    assert!(stmt_list_res.is_ok());
+    let stmt_list = stmt_list_res.unwrap();
+
+    let expected = Statement::DestructureDecl(DestructureDeclArgs {
+        var_type: Type::Tuple(vec![ Type::Int, Type::Bool, Type::Float ]),
+        names: vec![ String::from("x"), String::from("_"), String::from("y") ],
+        assignment: Box::new(Expression::Variable(String::from("t")))
+    });
+    assert_eq!(stmt_list, vec![ expected ]);
+}
+
 #[test]
 fn test_parse_member() {
     let code = String::from("
@@ -669,4 +1123,729 @@ fn test_parse_cont_instance() {
             }
         }
     }
-}
\ No newline at end of file
+}
+#[test]
+fn test_parse_slice_expr() {
+    let code = String::from("
+        vec.name[1..len - 1] == other[..2];
+    ");
+    let mut lexer = Token::lexer(code.as_str());
+    let parser = Parser::new(code.clone());
+
+    let expr_res = parser.parse_expr(&mut lexer, &[Token::Semicolon]);
+    assert!(expr_res.is_ok());
+
+    let member_expr = Expression::MemberAccess(
+        Box::new(Expression::Variable(String::from("vec"))),
+        Box::new(Expression::Variable(String::from("name")))
+    );
+    let lhs_expr = Expression::Slice(
+        Box::new(member_expr),
+        Some(Box::new(Expression::IntLiteral(1))),
+        Some(Box::new(Expression::Subtraction(
+            Box::new(Expression::Variable(String::from("len"))),
+            Box::new(Expression::IntLiteral(1))
+        )))
+    );
+    let rhs_expr = Expression::Slice(
+        Box::new(Expression::Variable(String::from("other"))),
+        None,
+        Some(Box::new(Expression::IntLiteral(2)))
+    );
+    assert_eq!(Expression::Equals(Box::new(lhs_expr), Box::new(rhs_expr)), expr_res.unwrap());
+}
+
+#[test]
+fn test_parse_range_expr() {
+    let code = String::from("
+        r = 0..n - 1;
+    ");
+    let mut lexer = Token::lexer(code.as_str());
+    let parser = Parser::new(code.clone());
+
+    let expr_res = parser.parse_expr(&mut lexer, &[Token::Semicolon]);
+    assert!(expr_res.is_ok());
+
+    let range_expr = Expression::Range(
+        Box::new(Expression::IntLiteral(0)),
+        Box::new(Expression::Subtraction(
+            Box::new(Expression::Variable(String::from("n"))),
+            Box::new(Expression::IntLiteral(1))
+        )),
+        false
+    );
+    assert_eq!(
+        Expression::Assign(Box::new(Expression::Variable(String::from("r"))), Box::new(range_expr)),
+        expr_res.unwrap()
+    );
+
+    let code = String::from("
+        text[1..=end];
+    ");
+    let mut lexer = Token::lexer(code.as_str());
+
+    let expr_res = parser.parse_expr(&mut lexer, &[Token::Semicolon]);
+    assert!(expr_res.is_ok());
+
+    let slice_expr = Expression::Slice(
+        Box::new(Expression::Variable(String::from("text"))),
+        Some(Box::new(Expression::IntLiteral(1))),
+        Some(Box::new(Expression::Addition(
+            Box::new(Expression::Variable(String::from("end"))),
+            Box::new(Expression::IntLiteral(1))
+        )))
+    );
+    assert_eq!(slice_expr, expr_res.unwrap());
+}
+
+#[test]
+fn test_parse_index_expr() {
+    let code = String::from("
+        text[i + 1] == vec.len(text);
+    ");
+    let mut lexer = Token::lexer(code.as_str());
+    let parser = Parser::new(code.clone());
+
+    let expr_res = parser.parse_expr(&mut lexer, &[Token::Semicolon]);
+    assert!(expr_res.is_ok());
+
+    let lhs_expr = Expression::Index(
+        Box::new(Expression::Variable(String::from("text"))),
+        Box::new(Expression::Addition(
+            Box::new(Expression::Variable(String::from("i"))),
+            Box::new(Expression::IntLiteral(1))
+        ))
+    );
+    let rhs_expr = Expression::MemberAccess(
+        Box::new(Expression::Variable(String::from("vec"))),
+        Box::new(Expression::Call(String::from("len"), vec![Expression::Variable(String::from("text"))]))
+    );
+    assert_eq!(Expression::Equals(Box::new(lhs_expr), Box::new(rhs_expr)), expr_res.unwrap());
+
+    let code = String::from("
+        len(text);
+    ");
+    let mut lexer = Token::lexer(code.as_str());
+    let expr_res = parser.parse_expr(&mut lexer, &[Token::Semicolon]);
+    assert!(expr_res.is_ok());
+    assert_eq!(Expression::Len(Box::new(Expression::Variable(String::from("text")))), expr_res.unwrap());
+}
+
+#[test]
+fn test_parse_pub_import_decl() {
+    let code = String::from("
+        pub import std::{
+            io::*,
+            println
+        };
+    ");
+    let mut lexer = Token::lexer(code.as_str());
+    let parser = Parser::new(code.clone());
+
+    let decl_res = parser.parse_decl_list(&mut lexer, &[]);
+    assert!(decl_res.is_ok());
+
+    let decl_list = decl_res.unwrap();
+    assert_eq!(decl_list[0], Declaration::PubImport(String::from("std::io::"), String::from("*")));
+    assert_eq!(decl_list[1], Declaration::PubImport(String::from("std::println"), String::from("println")));
+}
+
+#[test]
+fn test_parse_array_literal() {
+    let code = String::from("
+        [1, a + 2, 3][i] + [[1], [2]][0][0];
+    ");
+
+    let parser = Parser::new(code.clone());
+    let mut lexer = Token::lexer(code.as_str());
+
+    let expr_res = parser.parse_expr(&mut lexer, &[ Token::Semicolon ]);
+    println!("{:?}", expr_res);
+    assert!(expr_res.is_ok());
+
+    let int = |val: i64| Box::new(Expression::IntLiteral(val));
+    let array = Expression::Array(vec![
+        Expression::IntLiteral(1),
+        Expression::Addition(Box::new(Expression::Variable(String::from("a"))), int(2)),
+        Expression::IntLiteral(3)
+    ]);
+    let nested = Expression::Array(vec![
+        Expression::Array(vec![ Expression::IntLiteral(1) ]),
+        Expression::Array(vec![ Expression::IntLiteral(2) ])
+    ]);
+    let expected = Expression::Addition(
+        Box::new(Expression::Index(Box::new(array), Box::new(Expression::Variable(String::from("i"))))),
+        Box::new(Expression::Index(Box::new(Expression::Index(Box::new(nested), int(0))), int(0)))
+    );
+    assert_eq!(expr_res.unwrap(), expected);
+
+    let code = String::from("[];");
+    let mut lexer = Token::lexer(code.as_str());
+    assert!(parser.parse_expr(&mut lexer, &[ Token::Semicolon ]).is_err());
+}
+
+#[test]
+fn test_parse_enum_decl() {
+    let code = String::from("
+        enum: Color {
+            Red;
+            Green;
+            Blue;
+        }
+    ");
+
+    let mut lexer = Token::lexer(code.as_str());
+    let parser = Parser::new(code.clone());
+
+    let decl_res = parser.parse_decl_list(&mut lexer, &[]);
+    println!("{:?}", decl_res);
+    assert!(decl_res.is_ok());
+
+    let expected = Declaration::Enum(EnumDeclArgs {
+        name: String::from("Color"),
+        variants: vec![
+            String::from("Red"),
+            String::from("Green"),
+            String::from("Blue")
+        ]
+    });
+    assert_eq!(decl_res.unwrap(), vec![ expected ]);
+
+    let code = String::from("enum: Color { Red; Red; }");
+    let mut lexer = Token::lexer(code.as_str());
+    assert!(parser.parse_decl_list(&mut lexer, &[]).is_err());
+}
+
+#[test]
+fn test_parse_static_var_decl() {
+    let code = String::from("
+        var counter: int = 0;
+        var name = \"pgs\";
+    ");
+
+    let mut lexer = Token::lexer(code.as_str());
+    let parser = Parser::new(code.clone());
+
+    let decl_res = parser.parse_decl_list(&mut lexer, &[]);
+    println!("{:?}", decl_res);
+    assert!(decl_res.is_ok());
+
+    let expected = vec![
+        Declaration::StaticVar(VariableDeclArgs {
+            var_type: Type::Int,
+            name: String::from("counter"),
+            assignment: Box::new(Expression::IntLiteral(0))
+        }),
+        Declaration::StaticVar(VariableDeclArgs {
+            var_type: Type::Auto,
+            name: String::from("name"),
+            assignment: Box::new(Expression::StringLiteral(String::from("\"pgs\"")))
+        })
+    ];
+    assert_eq!(decl_res.unwrap(), expected);
+}
+
+#[test]
+fn test_parse_optional() {
+    let code = String::from("
+        var value: int? = null;
+    ");
+
+    let parser = Parser::new(code.clone());
+    let mut lexer = Token::lexer(code.as_str());
+
+    let stmt_res = parser.parse_var_decl(&mut lexer);
+    println!("{:?}", stmt_res);
+    assert!(stmt_res.is_ok());
+
+    let expected = Statement::VariableDecl(VariableDeclArgs {
+        var_type: Type::Optional(Box::new(Type::Int)),
+        name: String::from("value"),
+        assignment: Box::new(Expression::NullLiteral)
+    });
+    assert_eq!(stmt_res.unwrap(), expected);
+
+    let code = String::from("
+        value != null;
+    ");
+    let mut lexer = Token::lexer(code.as_str());
+
+    let expr_res = parser.parse_expr(&mut lexer, &[ Token::Semicolon ]);
+    println!("{:?}", expr_res);
+    assert!(expr_res.is_ok());
+
+    let expected = Expression::NotEquals(
+        Box::new(Expression::Variable(String::from("value"))),
+        Box::new(Expression::NullLiteral)
+    );
+    assert_eq!(expr_res.unwrap(), expected);
+}
+
+#[test]
+fn test_parse_result() {
+    let code = String::from("
+        var value: int! = parse(text)?;
+        throw \"invalid\";
+    ");
+
+    let mut lexer = Token::lexer(code.as_str());
+    let parser = Parser::new(code.clone());
+    let stmt_list_res = parser.parse_statement_list(&mut lexer);
+    println!("{:?}", stmt_list_res);
+    assert!(stmt_list_res.is_ok());
+
+    let expected = vec![
+        Statement::VariableDecl(VariableDeclArgs {
+            var_type: Type::Result(Box::new(Type::Int)),
+            name: String::from("value"),
+            assignment: Box::new(Expression::Try(
+                Box::new(Expression::Call(String::from("parse"), vec![
+                    Expression::Variable(String::from("text"))
+                ]))
+            ))
+        }),
+        Statement::Throw(Expression::StringLiteral(String::from("\"invalid\"")))
+    ];
+    assert_eq!(stmt_list_res.unwrap(), expected);
+}
+
+#[test]
+fn test_parse_lambda() {
+    let code = String::from("
+        var f: fn(int) ~ int = |x: int| ~ int { return x * factor; };
+    ");
+
+    let parser = Parser::new(code.clone());
+    let mut lexer = Token::lexer(code.as_str());
+
+    let stmt_res = parser.parse_var_decl(&mut lexer);
+    println!("{:?}", stmt_res);
+    assert!(stmt_res.is_ok());
+
+    let expected = Statement::VariableDecl(VariableDeclArgs {
+        var_type: Type::Function(vec![ Type::Int ], Box::new(Type::Int)),
+        name: String::from("f"),
+        assignment: Box::new(Expression::Lambda(Box::new(FunctionDeclArgs {
+            name: String::new(),
+            generics: Vec::new(),
+            arguments: vec![ (String::from("x"), Type::Int) ],
+            returns: Type::Int,
+            code_block: Some(vec![
+                Statement::Return(Some(Expression::Multiplication(
+                    Box::new(Expression::Variable(String::from("x"))),
+                    Box::new(Expression::Variable(String::from("factor")))
+                )))
+            ]),
+            public: false
+        })))
+    });
+    assert_eq!(stmt_res.unwrap(), expected);
+
+    let code = String::from("
+        run(|| { }, 1);
+    ");
+    let mut lexer = Token::lexer(code.as_str());
+
+    let expr_res = parser.parse_expr(&mut lexer, &[ Token::Semicolon ]);
+    println!("{:?}", expr_res);
+    assert!(expr_res.is_ok());
+
+    let expected = Expression::Call(String::from("run"), vec![
+        Expression::Lambda(Box::new(FunctionDeclArgs {
+            name: String::new(),
+            generics: Vec::new(),
+            arguments: Vec::new(),
+            returns: Type::Void,
+            code_block: Some(Vec::new()),
+            public: false
+        })),
+        Expression::IntLiteral(1)
+    ]);
+    assert_eq!(expr_res.unwrap(), expected);
+}
+
+#[test]
+fn test_parse_generic_fn_decl() {
+    let code = String::from("
+        fn: max<T>(a: T, b: T) ~ T {
+            return a;
+        }
+    ");
+
+    let parser = Parser::new(code.clone());
+    let mut lexer = Token::lexer(code.as_str());
+
+    let decl_res = parser.parse_fn_decl(&mut lexer);
+    println!("{:?}", decl_res);
+    assert!(decl_res.is_ok());
+
+    let expected = Declaration::Function(FunctionDeclArgs {
+        name: String::from("max"),
+        generics: vec![ String::from("T") ],
+        arguments: vec![
+            (String::from("a"), Type::Other(String::from("T"))),
+            (String::from("b"), Type::Other(String::from("T")))
+        ],
+        returns: Type::Other(String::from("T")),
+        code_block: Some(vec![
+            Statement::Return(Some(Expression::Variable(String::from("a"))))
+        ]),
+        public: false
+    });
+    assert_eq!(decl_res.unwrap(), expected);
+
+    let code = String::from("
+        fn: pair<K, V>(key: K, value: V) ~ (K, V);
+    ");
+    let mut lexer = Token::lexer(code.as_str());
+
+    let decl_res = parser.parse_fn_decl(&mut lexer);
+    println!("{:?}", decl_res);
+    match decl_res.unwrap() {
+        Declaration::Function(fn_decl_args) => {
+            assert_eq!(fn_decl_args.generics, vec![ String::from("K"), String::from("V") ]);
+        },
+        _ => panic!("Expected a function declaration")
+    };
+
+    let code = String::from("
+        fn: broken<T,>(a: T);
+    ");
+    let mut lexer = Token::lexer(code.as_str());
+
+    let decl_res = parser.parse_fn_decl(&mut lexer);
+    println!("{:?}", decl_res);
+    assert!(decl_res.is_err());
+}
+
+#[test]
+fn test_parse_generic_cont_decl() {
+    let code = String::from("
+        cont: Pair<A, B> {
+            first: A;
+            second: Box<B>;
+        }
+    ");
+
+    let parser = Parser::new(code.clone());
+    let mut lexer = Token::lexer(code.as_str());
+
+    let decl_res = parser.parse_container_decl(&mut lexer);
+    println!("{:?}", decl_res);
+    assert!(decl_res.is_ok());
+
+    let expected = Declaration::Container(ContainerDeclArgs {
+        name: String::from("Pair"),
+        generics: vec![ String::from("A"), String::from("B") ],
+        members: vec![
+            (String::from("first"), Type::Other(String::from("A"))),
+            (String::from("second"), Type::Generic(String::from("Box"), vec![ Type::Other(String::from("B")) ]))
+        ],
+        public: false
+    });
+    assert_eq!(decl_res.unwrap(), expected);
+
+    let code = String::from("
+        var p: &Pair<int, Box<float>>? = null;
+    ");
+    let mut lexer = Token::lexer(code.as_str());
+
+    let stmt_res = parser.parse_var_decl(&mut lexer);
+    println!("{:?}", stmt_res);
+    assert!(stmt_res.is_ok());
+
+    let expected_type = Type::Reference(Box::new(Type::Optional(Box::new(Type::Generic(
+        String::from("Pair"),
+        vec![
+            Type::Int,
+            Type::Generic(String::from("Box"), vec![ Type::Float ])
+        ]
+    )))));
+    match stmt_res.unwrap() {
+        Statement::VariableDecl(var_decl_args) => assert_eq!(var_decl_args.var_type, expected_type),
+        _ => panic!("Expected a variable declaration")
+    };
+}
+
+#[test]
+fn test_parse_iface_decl() {
+    let code = String::from("
+        iface: Shape {
+            fn: area(&this) ~ int;
+            fn: scale(&this, factor: int);
+        }
+    ");
+
+    let parser = Parser::new(code.clone());
+    let mut lexer = Token::lexer(code.as_str());
+
+    let decl_res = parser.parse_iface_decl(&mut lexer);
+    println!("{:?}", decl_res);
+    assert!(decl_res.is_ok());
+
+    let this_arg = (String::from("this"), Type::Reference(Box::new(Type::Other(String::from("Shape")))));
+    let expected = Declaration::Interface(InterfaceDeclArgs {
+        name: String::from("Shape"),
+        functions: vec![
+            FunctionDeclArgs {
+                name: String::from("area"),
+                generics: Vec::new(),
+                arguments: vec![ this_arg.clone() ],
+                returns: Type::Int,
+                code_block: None,
+                public: false
+            },
+            FunctionDeclArgs {
+                name: String::from("scale"),
+                generics: Vec::new(),
+                arguments: vec![ this_arg, (String::from("factor"), Type::Int) ],
+                returns: Type::Void,
+                code_block: None,
+                public: false
+            }
+        ]
+    });
+    assert_eq!(decl_res.unwrap(), expected);
+
+    let code = String::from("
+        iface: Shape {
+            fn: area(&this) ~ int {
+                return 0;
+            }
+        }
+    ");
+    let mut lexer = Token::lexer(code.as_str());
+
+    let decl_res = parser.parse_iface_decl(&mut lexer);
+    println!("{:?}", decl_res);
+    assert!(decl_res.is_err());
+}
+
+#[test]
+fn test_parse_pub_decl() {
+    let code = String::from("
+        pub mod: geo {
+            pub cont: Point {
+                x: int;
+            }
+
+            fn: helper() ~ int;
+        }
+
+        pub fn: area() ~ int;
+        pub import geo::Point;
+    ");
+
+    let parser = Parser::new(code.clone());
+
+    let decl_list_res = parser.parse_root_decl_list();
+    println!("{:?}", decl_list_res);
+    assert!(decl_list_res.is_ok());
+    let decl_list = decl_list_res.unwrap();
+    assert_eq!(decl_list.len(), 3);
+
+    match &decl_list[0] {
+        Declaration::PubModule(mod_name, mod_decl_list) => {
+            assert_eq!(mod_name, "geo");
+            match &mod_decl_list[0] {
+                Declaration::Container(cont_decl_args) => assert!(cont_decl_args.public),
+                _ => panic!("Expected a container declaration")
+            };
+            match &mod_decl_list[1] {
+                Declaration::Function(fn_decl_args) => assert!(!fn_decl_args.public),
+                _ => panic!("Expected a function declaration")
+            };
+        },
+        _ => panic!("Expected a public module declaration")
+    };
+    match &decl_list[1] {
+        Declaration::Function(fn_decl_args) => assert!(fn_decl_args.public),
+        _ => panic!("Expected a function declaration")
+    };
+    assert_eq!(decl_list[2], Declaration::PubImport(String::from("geo::Point"), String::from("Point")));
+
+    let code = String::from("
+        pub var counter: int = 0;
+    ");
+    let parser = Parser::new(code.clone());
+
+    let decl_list_res = parser.parse_root_decl_list();
+    println!("{:?}", decl_list_res);
+    assert!(decl_list_res.is_err());
+}
+
+#[test]
+fn test_parse_impl_builtin() {
+    let code = String::from("
+        impl: int {
+            fn: abs(&this) ~ int;
+        }
+    ");
+
+    let parser = Parser::new(code.clone());
+    let mut lexer = Token::lexer(code.as_str());
+
+    let decl_res = parser.parse_impl_decl(&mut lexer);
+    println!("{:?}", decl_res);
+    assert!(decl_res.is_ok());
+
+    let expected = Declaration::Impl(String::from("int"), String::from("int"), vec![
+        Declaration::Function(FunctionDeclArgs {
+            name: String::from("abs"),
+            generics: Vec::new(),
+            arguments: vec![ (String::from("this"), Type::Reference(Box::new(Type::Int))) ],
+            returns: Type::Int,
+            code_block: None,
+            public: false
+        })
+    ]);
+    assert_eq!(decl_res.unwrap(), expected);
+
+    let code = String::from("
+        impl: geo::Point {
+            fn: norm(&this) ~ int;
+        }
+    ");
+    let mut lexer = Token::lexer(code.as_str());
+
+    let decl_res = parser.parse_impl_decl(&mut lexer);
+    println!("{:?}", decl_res);
+    assert!(decl_res.is_ok());
+
+    let this_type = Type::Reference(Box::new(Type::Other(String::from("geo::Point"))));
+    match decl_res.unwrap() {
+        Declaration::Impl(impl_type, _, decl_list) => {
+            assert_eq!(impl_type, "geo::Point");
+            match &decl_list[0] {
+                Declaration::Function(fn_decl_args) => assert_eq!(fn_decl_args.arguments[0].1, this_type),
+                _ => panic!("Expected a function declaration")
+            };
+        },
+        _ => panic!("Expected an impl declaration")
+    };
+}
+
+#[test]
+fn test_parse_enum_value() {
+    let code = String::from("
+        color == shapes::Color::Red;
+    ");
+
+    let parser = Parser::new(code.clone());
+    let mut lexer = Token::lexer(code.as_str());
+
+    let expr_res = parser.parse_expr(&mut lexer, &[ Token::Semicolon ]);
+    println!("{:?}", expr_res);
+    assert!(expr_res.is_ok());
+
+    let expected = Expression::Equals(
+        Box::new(Expression::Variable(String::from("color"))),
+        Box::new(Expression::EnumValue(String::from("shapes::Color"), String::from("Red")))
+    );
+    assert_eq!(expr_res.unwrap(), expected);
+}
+
+#[test]
+fn test_parse_spawn() {
+    let code = String::from("
+        spawn patrol(guard, 3);
+    ");
+
+    let mut lexer = Token::lexer(code.as_str());
+    let parser = Parser::new(code.clone());
+    let stmt_list_res = parser.parse_statement_list(&mut lexer);
+    println!("{:?}", stmt_list_res);
+    assert!(stmt_list_res.is_ok());
+
+    let expected = vec![
+        Statement::Spawn(Expression::Call(String::from("patrol"), vec![
+            Expression::Variable(String::from("guard")),
+            Expression::IntLiteral(3)
+        ]))
+    ];
+    assert_eq!(stmt_list_res.unwrap(), expected);
+
+    let code = String::from("
+        spawn 1 + 2;
+    ");
+
+    let mut lexer = Token::lexer(code.as_str());
+    let parser = Parser::new(code.clone());
+    match parser.parse_statement_list(&mut lexer) {
+        Err(ParseError { error_type: ParseErrorType::ExpectedCall, .. }) => {},
+        other => panic!("Unexpected result {:?}", other)
+    };
+}
+
+#[test]
+fn test_parse_yield_resume() {
+    let code = String::from("
+        var gen: co<int> = counter(3);
+        yield resume(gen);
+    ");
+
+    let mut lexer = Token::lexer(code.as_str());
+    let parser = Parser::new(code.clone());
+    let stmt_list_res = parser.parse_statement_list(&mut lexer);
+    println!("{:?}", stmt_list_res);
+    assert!(stmt_list_res.is_ok());
+
+    let expected = vec![
+        Statement::VariableDecl(VariableDeclArgs {
+            var_type: Type::Coroutine(Box::new(Type::Int)),
+            name: String::from("gen"),
+            assignment: Box::new(Expression::Call(String::from("counter"), vec![
+                Expression::IntLiteral(3)
+            ]))
+        }),
+        Statement::Yield(Expression::Resume(Box::new(Expression::Variable(String::from("gen")))))
+    ];
+    assert_eq!(stmt_list_res.unwrap(), expected);
+}
+
+#[test]
+fn test_parse_statement_positions() {
+    let code = String::from("{
+    var x = 1;
+    while x < 10 {
+        x++;
+    }
+    x
+}");
+
+    let mut lexer = Token::lexer(code.as_str());
+    let parser = Parser::new(code.clone())
+        .with_positions();
+    let expr_res = parser.parse_expr(&mut lexer, &[Token::Semicolon]);
+    println!("{:?}", expr_res);
+    assert!(expr_res.is_ok());
+
+    let (stmt_list, value_expr) = match expr_res.unwrap() {
+        Expression::Block(stmt_list, value_expr) => (stmt_list, value_expr),
+        expr => panic!("Expected a block, got {:?}", expr)
+    };
+    assert_eq!(Expression::Variable(String::from("x")), *value_expr);
+    assert_eq!(2, stmt_list.len());
+    assert!(matches!(stmt_list[0], Statement::Positioned(_, 2, 5)));
+    let while_stmt_list = match &stmt_list[1] {
+        Statement::Positioned(while_stmt, 3, 5) => {
+            match while_stmt.as_ref() {
+                Statement::While(_, while_stmt_list) => while_stmt_list,
+                stmt => panic!("Expected a while statement, got {:?}", stmt)
+            }
+        },
+        stmt => panic!("Expected a positioned while statement, got {:?}", stmt)
+    };
+    assert_eq!(
+        vec![Statement::Positioned(Box::new(Statement::Increment(Expression::Variable(String::from("x")), 1)), 4, 9)],
+        *while_stmt_list
+    );
+
+    // Without positions, the statements are not wrapped
+    let mut lexer = Token::lexer(code.as_str());
+    let parser = Parser::new(code.clone());
+    match parser.parse_expr(&mut lexer, &[Token::Semicolon]) {
+        Ok(Expression::Block(stmt_list, _)) => assert!(matches!(stmt_list[0], Statement::VariableDecl(_))),
+        other => panic!("Expected a block, got {:?}", other)
+    };
+}
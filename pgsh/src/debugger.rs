@@ -0,0 +1,233 @@
+use pgs::{
+    engine::{
+        Engine,
+        EngineResult
+    },
+    codegen::{
+        register::Register,
+        debug::{
+            DebugInfo,
+            VariableInfo
+        }
+    },
+    parser::{
+        ast::Type
+    },
+    vm::{
+        core::RunState
+    }
+};
+
+use std::{
+    io::{
+        stdin,
+        stdout,
+        BufRead,
+        Write
+    }
+};
+
+/// Interactive debugger running a script step by step
+pub struct Debugger {
+    engine: Engine,
+    debug_info: DebugInfo,
+    source_lines: Vec<String>
+}
+
+impl Debugger {
+    /// Stops at the start of root::main of the code loaded into the engine
+    pub fn new(mut engine: Engine, code: &str) -> EngineResult<Debugger> {
+        engine.start_fn("root::main")?;
+        let debug_info = engine.get_debug_info()?;
+        Ok(
+            Debugger {
+                engine: engine,
+                debug_info: debug_info,
+                source_lines: code.lines().map(String::from).collect()
+            }
+        )
+    }
+
+    /// Runs the debugger prompt until the user quits, returns the exit code of the script
+    pub fn run_prompt(&mut self) -> EngineResult<i64> {
+        println!("Commands: break <fn|line>, step, next, continue, print <var>, backtrace, quit");
+        self.print_location();
+
+        let stdin = stdin();
+        loop {
+            print!("(pgdb) ");
+            stdout().flush().ok();
+
+            let mut input = String::new();
+            let read_len = stdin.lock().read_line(&mut input).unwrap_or(0);
+            if read_len == 0 {
+                break;
+            }
+
+            let mut words = input.split_whitespace();
+            let command = match words.next() {
+                Some(command) => command,
+                None => continue
+            };
+            let argument = words.next();
+
+            match (command, argument) {
+                ("break", Some(target)) | ("b", Some(target)) => self.cmd_break(target),
+                ("step", None) | ("s", None) => self.cmd_step(false),
+                ("next", None) | ("n", None) => self.cmd_step(true),
+                ("continue", None) | ("c", None) => self.cmd_continue(),
+                ("print", Some(var_name)) | ("p", Some(var_name)) => self.cmd_print(var_name),
+                ("backtrace", None) | ("bt", None) => self.cmd_backtrace(),
+                ("quit", None) | ("q", None) => break,
+                _ => println!("Unknown command: {}", input.trim())
+            };
+        }
+
+        self.engine.get_register_value::<i64>(Register::R0)
+    }
+
+    fn cmd_break(&mut self, target: &str) {
+        let offset_opt = match target.parse::<usize>() {
+            Ok(line) => self.debug_info.get_line_offset(line),
+            Err(_) => {
+                let fn_name = if target.contains("::") {
+                    String::from(target)
+                } else {
+                    format!("root::{}", target)
+                };
+                self.debug_info.get_function_offset(&fn_name)
+            }
+        };
+
+        match offset_opt {
+            Some(offset) => {
+                self.engine.add_breakpoint(offset);
+                println!("Breakpoint set at {}", self.describe(offset));
+            },
+            None => println!("No code found for {}", target)
+        };
+    }
+
+    /// Steps to the next statement. If over_calls is set, statements in called functions are skipped.
+    fn cmd_step(&mut self, over_calls: bool) {
+        let call_depth = self.engine.get_call_stack().len();
+        while self.step_instr() {
+            let ip = self.engine.get_ip();
+            let at_statement = self.debug_info.get_statement(ip).is_some();
+            if at_statement && (!over_calls || self.engine.get_call_stack().len() <= call_depth) {
+                self.print_location();
+                return;
+            }
+        }
+    }
+
+    fn cmd_continue(&mut self) {
+        if !self.is_running() {
+            println!("The program is not running");
+            return;
+        }
+
+        match self.engine.run_to_breakpoint() {
+            Ok(RunState::Paused) => {
+                println!("Breakpoint hit");
+                self.print_location();
+            },
+            Ok(_) => self.print_exit_code(),
+            Err(err) => println!("Program stopped with error: {}", err)
+        };
+    }
+
+    fn cmd_print(&self, var_name: &str) {
+        if !self.is_running() {
+            println!("The program is not running");
+            return;
+        }
+
+        let stmt_info = match self.debug_info.get_statement(self.engine.get_ip()) {
+            Some(stmt_info) => stmt_info,
+            None => {
+                println!("Not stopped at a statement");
+                return;
+            }
+        };
+
+        match stmt_info.variables.iter().find(|var_info| var_info.name == var_name) {
+            Some(var_info) => println!("{} = {}", var_name, self.format_variable(var_info)),
+            None => println!("No variable {} in scope", var_name)
+        };
+    }
+
+    fn cmd_backtrace(&self) {
+        if !self.is_running() {
+            println!("The program is not running");
+            return;
+        }
+
+        let mut frames = vec![self.engine.get_ip()];
+        frames.append(&mut self.engine.get_call_stack());
+        for (i, offset) in frames.iter().enumerate() {
+            println!("#{} {}", i, self.describe(*offset));
+        }
+    }
+
+    /// Executes a single instruction, returns false once the program stopped running
+    fn step_instr(&mut self) -> bool {
+        if !self.is_running() {
+            println!("The program is not running");
+            return false;
+        }
+
+        match self.engine.step() {
+            Ok(true) => return true,
+            Ok(false) => self.print_exit_code(),
+            Err(err) => println!("Program stopped with error: {}", err)
+        };
+        false
+    }
+
+    /// Checks if the started function has neither finished nor failed
+    fn is_running(&self) -> bool {
+        self.engine.get_run_state() != RunState::Halted
+    }
+
+    fn print_exit_code(&self) {
+        let exit_code = self.engine.get_register_value::<i64>(Register::R0).unwrap_or(0);
+        println!("Program exited with code {}", exit_code);
+    }
+
+    fn print_location(&self) {
+        let ip = self.engine.get_ip();
+        println!("Stopped in {}", self.describe(ip));
+        if let Some(line) = self.debug_info.get_line_at(ip) {
+            if let Some(source_line) = self.source_lines.get(line - 1) {
+                println!("{:>4} | {}", line, source_line.trim_end());
+            }
+        }
+    }
+
+    /// Describes a code offset by function name and source line
+    fn describe(&self, offset: usize) -> String {
+        let fn_name = self.debug_info.get_function_at(offset)
+            .cloned()
+            .unwrap_or_else(|| String::from("<unknown>"));
+        match self.debug_info.get_line_at(offset) {
+            Some(line) => format!("{} at line {}", fn_name, line),
+            None => fn_name
+        }
+    }
+
+    fn format_variable(&self, var_info: &VariableInfo) -> String {
+        let offset = var_info.sp_offset;
+        let value_res = match &var_info.var_type {
+            Type::Int => self.engine.get_stack_value::<i64>(offset).map(|v| v.to_string()),
+            Type::Float => self.engine.get_stack_value::<f32>(offset).map(|v| v.to_string()),
+            Type::Bool => self.engine.get_stack_value::<bool>(offset).map(|v| v.to_string()),
+            Type::Char => self.engine.get_stack_value::<u32>(offset)
+                .map(|v| format!("{:?}", std::char::from_u32(v).unwrap_or(std::char::REPLACEMENT_CHARACTER))),
+            Type::String => self.engine.get_stack_string(offset).map(|v| format!("{:?}", v)),
+            Type::Reference(_) => self.engine.get_stack_value::<u64>(offset).map(|v| format!("0x{:X}", v)),
+            var_type => Ok(format!("<{:?}>", var_type))
+        };
+        value_res.unwrap_or_else(|err| format!("<unreadable: {}>", err))
+    }
+}
@@ -0,0 +1,195 @@
+use pgs::{
+    engine::{
+        Engine,
+        EngineError,
+        format_stack_trace
+    },
+    vm::{
+        core::CoreError
+    }
+};
+
+use std::{
+    io::{
+        stderr,
+        IsTerminal
+    },
+    fs::read_to_string,
+    ops::Range
+};
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// When to colorize diagnostics
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never
+}
+
+impl ColorChoice {
+    /// Parses a --color value
+    pub fn parse(value: &str) -> Option<ColorChoice> {
+        match value {
+            "auto" => Some(ColorChoice::Auto),
+            "always" => Some(ColorChoice::Always),
+            "never" => Some(ColorChoice::Never),
+            _ => None
+        }
+    }
+
+    /// Checks if diagnostics written to stderr should be colorized
+    pub fn use_color(&self) -> bool {
+        match self {
+            ColorChoice::Auto => stderr().is_terminal(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false
+        }
+    }
+}
+
+/// An error message pointing into a source file
+#[derive(Debug)]
+pub struct Diagnostic {
+    message: String,
+    filename: String,
+    source: String,
+    span: Option<Range<usize>>,
+    notes: Vec<String>
+}
+
+impl Diagnostic {
+    /// Creates a new diagnostic for the given source file
+    pub fn new<T>(message: T, filename: &str, source: &str) -> Diagnostic
+    where String: From<T> {
+        Diagnostic {
+            message: String::from(message),
+            filename: filename.to_string(),
+            source: source.to_string(),
+            span: None,
+            notes: Vec::new()
+        }
+    }
+
+    /// Sets the byte range of the source the diagnostic points to
+    pub fn with_span(mut self, span: Range<usize>) -> Diagnostic {
+        self.span = Some(span);
+        self
+    }
+
+    /// Adds a note printed below the snippet
+    pub fn with_note<T>(mut self, note: T) -> Diagnostic
+    where String: From<T> {
+        self.notes.push(String::from(note));
+        self
+    }
+
+    /// Creates a diagnostic for an error returned by the engine
+    pub fn from_engine_error(error: &EngineError, engine: &Engine, filename: &str, source: &str) -> Diagnostic {
+        match error {
+            EngineError::ParseError(parse_error) => {
+                Diagnostic::new(format!("parse error: {:?}", parse_error.error_type), filename, source)
+                    .with_span(parse_error.token_pos.clone())
+            },
+            EngineError::PackageParseError(path, parse_error) => {
+                let package_filename = path.to_string_lossy();
+                let package_source = read_to_string(path).unwrap_or_default();
+                Diagnostic::new(format!("parse error: {:?}", parse_error.error_type), &package_filename, &package_source)
+                    .with_span(parse_error.token_pos.clone())
+            },
+            EngineError::CompileError(compile_error) => {
+                Diagnostic::new(format!("compile error: {:?}", compile_error), filename, source)
+            },
+            EngineError::CoreError(core_error) => {
+                Diagnostic::from_core_error(core_error, engine, filename, source)
+            },
+            EngineError::RuntimeError(core_error, stack_trace) => {
+                Diagnostic::from_core_error(core_error, engine, filename, source)
+                    .with_note(format!("stack trace: {}", format_stack_trace(stack_trace)))
+            },
+            _ => Diagnostic::new(format!("{:?}", error), filename, source)
+        }
+    }
+
+    /// Creates a diagnostic for an error raised while running, pointing at the failing line
+    fn from_core_error(core_error: &CoreError, engine: &Engine, filename: &str, source: &str) -> Diagnostic {
+        let mut diagnostic = Diagnostic::new(format!("runtime error: {:?}", core_error.get_cause()), filename, source);
+        if let Ok(debug_info) = engine.get_debug_info() {
+            // The instruction pointer has already moved past the failing opcode
+            let ip = engine.get_ip().saturating_sub(1);
+            let line = core_error.get_position()
+                .map(|position| position.line)
+                .or_else(|| debug_info.get_line_at(ip));
+            if let Some(span) = line.and_then(|line| line_span(source, line)) {
+                diagnostic = diagnostic.with_span(span);
+            }
+            if let Some(fn_name) = debug_info.get_function_at(ip) {
+                diagnostic = diagnostic.with_note(format!("in function {}", fn_name));
+            }
+        }
+        diagnostic
+    }
+
+    /// Renders the diagnostic like rustc does
+    pub fn render(&self, color: bool) -> String {
+        let paint = |style: &str, text: &str| {
+            if color {
+                format!("{}{}{}", style, text, RESET)
+            } else {
+                String::from(text)
+            }
+        };
+
+        let mut ret = format!("{}{}\n", paint(RED, "error"), paint(BOLD, &format!(": {}", self.message)));
+
+        let span = match &self.span {
+            Some(span) => span,
+            None => {
+                ret += &format!(" {} {}\n", paint(BLUE, "-->"), self.filename);
+                for note in self.notes.iter() {
+                    ret += &format!(" {} note: {}\n", paint(BLUE, "="), note);
+                }
+                return ret;
+            }
+        };
+
+        let start = span.start.min(self.source.len());
+        let line_start = self.source[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let line_end = self.source[start..].find('\n').map(|i| i + start).unwrap_or(self.source.len());
+        let line_nr = self.source[..start].matches('\n').count() + 1;
+        let line = &self.source[line_start..line_end];
+
+        let column = self.source[line_start..start].chars().count();
+        let end = span.end.max(start + 1).min(line_end);
+        let underline_len = self.source[start..end].chars().count().max(1);
+
+        let gutter = " ".repeat(line_nr.to_string().len());
+
+        ret += &format!("{}{} {}:{}:{}\n", gutter, paint(BLUE, "-->"), self.filename, line_nr, column + 1);
+        ret += &format!("{} {}\n", gutter, paint(BLUE, "|"));
+        ret += &format!("{} {}\n", paint(BLUE, &format!("{} |", line_nr)), line);
+        ret += &format!("{} {} {}{}\n", gutter, paint(BLUE, "|"), " ".repeat(column), paint(RED, &"^".repeat(underline_len)));
+        for note in self.notes.iter() {
+            ret += &format!("{} {} note: {}\n", gutter, paint(BLUE, "="), note);
+        }
+
+        ret
+    }
+}
+
+/// Gets the byte range of a source line (starting at 1), without surrounding whitespace
+fn line_span(source: &str, line: usize) -> Option<Range<usize>> {
+    let mut offset = 0;
+    for (i, line_str) in source.split('\n').enumerate() {
+        if i + 1 == line {
+            let trimmed_start = line_str.len() - line_str.trim_start().len();
+            return Some(offset + trimmed_start..offset + line_str.trim_end().len());
+        }
+        offset += line_str.len() + 1;
+    }
+    None
+}
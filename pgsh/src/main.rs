@@ -4,13 +4,19 @@ extern crate pgs;
 #[cfg(feature = "static_std")]
 extern crate pgs_std;
 
+mod debugger;
+
+mod diagnostics;
+
 use pgs::{
     engine::{
         Engine,
-        EngineResult
+        EngineBuilder,
+        EngineError
     },
     codegen::{
-        register::Register
+        register::Register,
+        program::PROGRAM_EXTENSION
     },
     api::{
         function::{
@@ -29,21 +35,71 @@ use pgs::{
 };
 
 use std::{
-    path::Path,
     error::Error,
-    boxed::Box
+    boxed::Box,
+    fs::read_to_string,
+    path::Path,
+    time::{
+        Duration,
+        Instant
+    }
+};
+
+use debugger::Debugger;
+
+use diagnostics::{
+    ColorChoice,
+    Diagnostic
 };
 
 use clap::{
     App,
+    ArgMatches,
     SubCommand,
     Arg
 };
 
-fn bootstrap_engine(engine: &mut Engine) -> EngineResult<()> {
-    pgs_std::register_extension(engine)
+fn build_engine(app_matches: &ArgMatches) -> Result<Engine, Box<dyn Error>> {
+    let mut builder = EngineBuilder::new()
+        .with_stack_size(app_matches.value_of("stack-size").unwrap().parse()?);
+    if let Some(max_instructions) = app_matches.value_of("max-instructions") {
+        builder = builder.with_max_instructions(max_instructions.parse()?);
+    }
+    if let Some(max_heap) = app_matches.value_of("max-heap") {
+        builder = builder.with_max_heap(max_heap.parse()?);
+    }
+    #[cfg(feature = "static_std")]
+    {
+        let capabilities = pgs_std::Capabilities::new()
+            .with_process(app_matches.is_present("allow-process"))
+            .with_net(app_matches.is_present("allow-net"));
+        builder = builder.with_module(pgs_std::std_module(&capabilities));
+    }
+    Ok(builder.build()?)
+}
+
+fn report_error(error: &EngineError, engine: &Engine, filename: &str, code: &str, app_matches: &ArgMatches) -> ! {
+    let color_choice = app_matches.value_of("color")
+        .and_then(ColorChoice::parse)
+        .unwrap_or(ColorChoice::Auto);
+    let diagnostic = Diagnostic::from_engine_error(error, engine, filename, code);
+    eprint!("{}", diagnostic.render(color_choice.use_color()));
+    std::process::exit(1);
 }
 
+fn print_stats(engine: &Engine, wall_time: Duration) {
+    let stats = engine.get_stats();
+    eprintln!("--- execution statistics ---");
+    eprintln!("instructions:   {}", stats.instructions);
+    eprintln!("peak stack:     {} bytes", stats.peak_stack);
+    eprintln!("heap allocs:    {}", stats.heap_allocs);
+    eprintln!("heap frees:     {}", stats.heap_frees);
+    eprintln!("foreign calls:  {}", stats.foreign_calls.values().sum::<u64>());
+    for (fn_name, count) in stats.foreign_calls.iter() {
+        eprintln!("  {:<14}{}", fn_name, count);
+    }
+    eprintln!("wall time:      {:.3} ms", wall_time.as_secs_f64() * 1000.0);
+}
 
 fn build_app<'a>() -> App<'a, 'a> {
     let about_string;
@@ -66,6 +122,48 @@ fn build_app<'a>() -> App<'a, 'a> {
                 .takes_value(true)
                 .help("Filename of the script to execute")
         )
+        .arg(
+            Arg::with_name("color")
+                .long("color")
+                .takes_value(true)
+                .possible_values(&["auto", "always", "never"])
+                .default_value("auto")
+                .help("Colorizes error messages")
+        )
+        .arg(
+            Arg::with_name("stack-size")
+                .long("stack-size")
+                .takes_value(true)
+                .default_value("1024")
+                .help("Stack size of the engine in bytes")
+        )
+        .arg(
+            Arg::with_name("max-instructions")
+                .long("max-instructions")
+                .takes_value(true)
+                .help("Aborts the script after executing this many instructions")
+        )
+        .arg(
+            Arg::with_name("max-heap")
+                .long("max-heap")
+                .takes_value(true)
+                .help("Maximum heap size of the engine in bytes")
+        )
+        .arg(
+            Arg::with_name("stats")
+                .long("stats")
+                .help("Prints execution statistics after the script has run")
+        )
+        .arg(
+            Arg::with_name("allow-process")
+                .long("allow-process")
+                .help("Allows the script to run external processes via std::process")
+        )
+        .arg(
+            Arg::with_name("allow-net")
+                .long("allow-net")
+                .help("Allows the script to open sockets via std::net")
+        )
         .arg(
             Arg::with_name("arguments")
                 .required(false)
@@ -74,6 +172,35 @@ fn build_app<'a>() -> App<'a, 'a> {
                 .multiple(true)
                 .last(true)
         )
+        .subcommand(
+            SubCommand::with_name("compile")
+                .about("Compiles a script to a bytecode file, which runs without compiling it again")
+                .arg(
+                    Arg::with_name("filename")
+                        .index(1)
+                        .required(true)
+                        .takes_value(true)
+                        .help("Filename of the script to compile")
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .takes_value(true)
+                        .help("Filename of the bytecode file, defaults to the script with a .pgsb extension")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("debug")
+                .about("Runs a script in the interactive debugger")
+                .arg(
+                    Arg::with_name("filename")
+                        .index(1)
+                        .required(true)
+                        .takes_value(true)
+                        .help("Filename of the script to debug")
+                )
+        )
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -81,12 +208,45 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     let app_matches = app.get_matches();
 
+    if let Some(compile_matches) = app_matches.subcommand_matches("compile") {
+        let filename = compile_matches.value_of("filename").unwrap();
+        let code = read_to_string(filename)?;
+        let output = match compile_matches.value_of("output") {
+            Some(output) => Path::new(output).to_path_buf(),
+            None => Path::new(filename).with_extension(PROGRAM_EXTENSION)
+        };
+
+        // Foreign functions are bound by name when the bytecode file is run
+        let mut engine = build_engine(&app_matches)?;
+        if let Err(error) = engine.load_file(Path::new(filename)) {
+            report_error(&error, &engine, filename, &code, &app_matches);
+        }
+        if let Err(error) = engine.save_program(&output) {
+            report_error(&error, &engine, filename, &code, &app_matches);
+        }
+        return Ok(());
+    }
+
+    if let Some(debug_matches) = app_matches.subcommand_matches("debug") {
+        let filename = debug_matches.value_of("filename").unwrap();
+        let code = read_to_string(filename)?;
+
+        let mut engine = build_engine(&app_matches)?;
+        if let Err(error) = engine.load_file(Path::new(filename)) {
+            report_error(&error, &engine, filename, &code, &app_matches);
+        }
+
+        let mut debugger = Debugger::new(engine, &code)?;
+        let exit_code = debugger.run_prompt()?;
+        std::process::exit(exit_code as i32);
+    }
+
     let filename_opt = app_matches.value_of("filename");
     assert!(filename_opt.is_some());
 
     let filename = filename_opt.unwrap();
 
-    let mut engine = Engine::new(1024);
+    let mut engine = build_engine(&app_matches)?;
 
     let arguments_opt = app_matches.values_of("arguments");
     if arguments_opt.is_some() {
@@ -106,10 +266,29 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
     }
 
-    #[cfg(feature = "static_std")]
-    bootstrap_engine(&mut engine)?;
-
-    engine.run_file(Path::new(filename))?;
+    let path = Path::new(filename);
+    let is_bytecode = path.extension()
+        .map(|extension| extension == PROGRAM_EXTENSION)
+        .unwrap_or(false);
+    // Bytecode files have no source to point errors at. Scripts are only read for that,
+    // run_file() resolves their imports and module files relative to the script.
+    let code = if is_bytecode {
+        String::new()
+    } else {
+        read_to_string(filename)?
+    };
+    let start_time = Instant::now();
+    let run_res = if is_bytecode {
+        engine.run_bytecode_file(path)
+    } else {
+        engine.run_file(path)
+    };
+    if app_matches.is_present("stats") {
+        print_stats(&engine, start_time.elapsed());
+    }
+    if let Err(error) = run_res {
+        report_error(&error, &engine, filename, &code, &app_matches);
+    }
 
     //println!("Script run. stack size: {}", engine.get_stack_size());
 
@@ -0,0 +1,31 @@
+use std::{
+    env,
+    fs,
+    process::Command
+};
+
+#[test]
+fn test_pgsh_run_from_other_dir() {
+    let script_dir = env::temp_dir().join("pgsh_test_run_from_other_dir");
+    fs::create_dir_all(&script_dir).unwrap();
+    fs::write(script_dir.join("shapes.pgs"), "
+        pub fn: square_area(side: int) ~ int {
+            return side * side;
+        }
+    ").unwrap();
+    fs::write(script_dir.join("main.pgs"), "
+        mod: shapes;
+
+        fn: main() ~ int {
+            return shapes::square_area(3);
+        }
+    ").unwrap();
+
+    // Module files are found next to the script, not in the working directory
+    let status = Command::new(env!("CARGO_BIN_EXE_pgsh"))
+        .arg(script_dir.join("main.pgs"))
+        .current_dir(env::temp_dir())
+        .status()
+        .unwrap();
+    assert_eq!(Some(9), status.code());
+}